@@ -0,0 +1,46 @@
+use eframe::egui::{self, Response, Ui};
+use egui::accesskit::{Live, Role};
+
+// 读屏和键盘操作的辅助。egui 通过 AccessKit 把控件树交给系统的读屏软件（讲述人、NVDA），
+// 按钮、复选框、带 from_label 的下拉框自己就有名字；这里补上缺的：
+// 说明文字写在旁边另一个标签里的输入框、数值框和下拉框要和标签关联，
+// 没有可见文字的控件（队列每行的复选框等）直接给个名字，进度条要有角色并且不能每帧播报。
+// Tab 键按控件创建的顺序移动焦点，界面从上到下、从左到右创建，顺序和看到的一致
+
+// 给没有可见说明文字的控件起名字，读屏软件读这个名字
+pub fn named(response: Response, name: &str) -> Response {
+    response.ctx.accesskit_node_builder(response.id, |builder| builder.set_name(name));
+    response
+}
+
+// 下拉框默认只读出名字，把当前选中的一项作为值一起读出。
+// 说明文字在旁边另一个标签里时再用 labelled_by 关联
+pub fn selected(response: Response, text: &str) -> Response {
+    response.ctx.accesskit_node_builder(response.id, |builder| builder.set_value(text));
+    response
+}
+
+// 进度条的读屏名字只在跨过整十时更新，并标成礼貌播报：不打断正在朗读的内容，也不会每帧都念一遍
+pub fn progress(response: Response, fraction: f32, name: &str) -> Response {
+    let step = ((fraction * 10.0).floor() * 10.0).clamp(0.0, 100.0);
+    response.ctx.accesskit_node_builder(response.id, |builder| {
+        builder.set_role(Role::ProgressIndicator);
+        builder.set_name(format!("{} {:.0}%", name, step));
+        builder.set_numeric_value(step as f64);
+        builder.set_min_numeric_value(0.0);
+        builder.set_max_numeric_value(100.0);
+        builder.set_live(Live::Polite);
+    });
+    response
+}
+
+// 日志区域：只读的多行文本框，能用 Tab 聚焦、方向键翻看，读屏软件能逐行朗读
+pub fn log_view(ui: &mut Ui, text: &str, name: &str) -> Response {
+    let mut text = text;
+    let response = ui.add(
+        egui::TextEdit::multiline(&mut text)
+            .font(egui::TextStyle::Monospace)
+            .desired_width(f32::INFINITY),
+    );
+    named(response, name)
+}
@@ -0,0 +1,606 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::args::Source;
+use crate::coverart;
+use crate::encoders;
+use crate::mp4meta;
+use crate::output;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::{self, MediaInfo, ProbeDepth};
+use crate::process;
+use crate::subtitle;
+
+// 整张专辑转成 Opus/AAC：按音轨顺序排队，每一轨写上一致的专辑、音轨号、碟号标签；
+// 整轨文件加 cue 时可以按 cue 的时间切成一轨一个文件。AAC 有编码延迟和末尾补齐，
+// 转换后按输出里实际的帧数写入 iTunSMPB，支持的播放器就能无缝连播。
+// Opus 的 OpusHead 里本来就记着 pre-skip，末尾按 granule 位置截掉，不需要另写
+#[derive(Clone, Copy, PartialEq)]
+pub enum AlbumCodec {
+    Opus,
+    Aac,
+}
+
+impl AlbumCodec {
+    pub const ALL: [AlbumCodec; 2] = [AlbumCodec::Opus, AlbumCodec::Aac];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AlbumCodec::Opus => "Opus (.opus)",
+            AlbumCodec::Aac => "AAC (.m4a)",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            AlbumCodec::Opus => "opus",
+            AlbumCodec::Aac => "aac",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<AlbumCodec> {
+        AlbumCodec::ALL.into_iter().find(|c| c.tag().eq_ignore_ascii_case(tag))
+    }
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            AlbumCodec::Opus => "opus",
+            AlbumCodec::Aac => "m4a",
+        }
+    }
+
+    fn encoder(self) -> &'static str {
+        match self {
+            AlbumCodec::Opus => "libopus",
+            AlbumCodec::Aac => "aac",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Album {
+    pub codec: AlbumCodec,
+    pub bitrate_k: u32,
+    // AAC 输出写入 iTunSMPB
+    pub gapless: bool,
+    // 整轨文件旁边有 cue 时按音轨切开；只影响专辑面板怎样排队
+    pub split_cue: bool,
+    // 以下由专辑面板按每一轨填写。要写入的标签，覆盖源文件里的同名标签
+    pub tags: Vec<(String, String)>,
+    // 按 cue 切开时这一轨在整轨文件里的 (开始, 结束) 秒，最后一轨到文件结尾
+    pub span: Option<(f64, Option<f64>)>,
+    // 输出文件名（不含扩展名），空时沿用源文件名
+    pub name: String,
+}
+
+impl Default for Album {
+    fn default() -> Self {
+        Album { codec: AlbumCodec::Opus, bitrate_k: 160, gapless: true, split_cue: true, tags: Vec::new(), span: None, name: String::new() }
+    }
+}
+
+impl Album {
+    pub fn writes_gapless(&self) -> bool {
+        self.gapless && self.codec == AlbumCodec::Aac
+    }
+}
+
+// 专辑里的无损/有损音频，cue 和封面图片之类的不算
+const AUDIO_EXTS: &[&str] = &["flac", "wav", "ape", "wv", "alac", "aif", "aiff", "m4a", "mp3", "ogg", "opus", "tta", "dsf"];
+
+#[derive(Default)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: String,
+    pub performer: String,
+    // 这一轨的 FILE，一般整张专辑只有一个
+    pub file: String,
+    // INDEX 01 的时间（秒）。INDEX 00 之前的间隔算在上一轨里，和抓轨软件一致
+    pub start: f64,
+}
+
+#[derive(Default)]
+pub struct Cue {
+    pub title: String,
+    pub performer: String,
+    pub genre: String,
+    pub date: String,
+    pub disc: Option<u32>,
+    pub tracks: Vec<CueTrack>,
+}
+
+impl Cue {
+    // 所有音轨都在同一个文件里时返回这个文件名
+    pub fn single_file(&self) -> Option<&str> {
+        let first = self.tracks.first()?.file.as_str();
+        self.tracks.iter().all(|t| t.file == first).then_some(first)
+    }
+
+    // 第 i 轨的 (开始, 结束)，结束是下一轨的开始；下一轨在另一个文件里时读到文件末尾
+    pub fn span(&self, i: usize) -> (f64, Option<f64>) {
+        let track = &self.tracks[i];
+        (track.start, self.tracks.get(i + 1).filter(|t| t.file == track.file).map(|t| t.start))
+    }
+}
+
+// 去掉两边的引号；没有引号时就是整段文字
+fn unquote(text: &str) -> String {
+    let text = text.trim();
+    match text.strip_prefix('"') {
+        Some(rest) => rest.split('"').next().unwrap_or_default().to_string(),
+        None => text.to_string(),
+    }
+}
+
+// mm:ss:ff，一秒 75 帧（CD 的扇区）
+pub fn parse_cue_time(text: &str) -> Option<f64> {
+    let parts: Vec<u32> = text.trim().split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [m, s, f] if *s < 60 && *f < 75 => Some(*m as f64 * 60.0 + *s as f64 + *f as f64 / 75.0),
+        _ => None,
+    }
+}
+
+// 解析 cue 文本。只认和转换有关的命令，其他的（CATALOG、FLAGS、ISRC……）忽略
+pub fn parse_cue(text: &str) -> Result<Cue, String> {
+    let mut cue = Cue::default();
+    let mut file = String::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let bad = |what: &str| format!("cue 第 {} 行: {}", n + 1, what);
+        match command.to_ascii_uppercase().as_str() {
+            "REM" => {
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                match key.to_ascii_uppercase().as_str() {
+                    "GENRE" => cue.genre = unquote(value),
+                    "DATE" => cue.date = unquote(value),
+                    "DISCNUMBER" => cue.disc = unquote(value).parse().ok(),
+                    _ => {}
+                }
+            }
+            // FILE "album.flac" WAVE：文件名可能带空格，类型在最后
+            "FILE" => {
+                file = if rest.starts_with('"') {
+                    unquote(rest)
+                } else {
+                    rest.rsplit_once(char::is_whitespace).map_or(rest, |(name, _)| name).to_string()
+                };
+            }
+            "TRACK" => {
+                if file.is_empty() {
+                    return Err(bad("TRACK 前面没有 FILE"));
+                }
+                let number = rest.split_whitespace().next().and_then(|n| n.parse().ok()).ok_or_else(|| bad("音轨号不对"))?;
+                cue.tracks.push(CueTrack { number, file: file.clone(), start: -1.0, ..Default::default() });
+            }
+            "TITLE" | "PERFORMER" => {
+                let value = unquote(rest);
+                let is_title = command.eq_ignore_ascii_case("TITLE");
+                match (cue.tracks.last_mut(), is_title) {
+                    (Some(track), true) => track.title = value,
+                    (Some(track), false) => track.performer = value,
+                    (None, true) => cue.title = value,
+                    (None, false) => cue.performer = value,
+                }
+            }
+            "INDEX" => {
+                let (index, time) = rest.split_once(char::is_whitespace).ok_or_else(|| bad("INDEX 缺少时间"))?;
+                let time = parse_cue_time(time).ok_or_else(|| bad("时间格式应为 分:秒:帧"))?;
+                let track = cue.tracks.last_mut().ok_or_else(|| bad("INDEX 前面没有 TRACK"))?;
+                if index.trim() == "01" {
+                    track.start = time;
+                }
+            }
+            _ => {}
+        }
+    }
+    if cue.tracks.is_empty() {
+        return Err("cue 里没有音轨".to_string());
+    }
+    if let Some(track) = cue.tracks.iter().find(|t| t.start < 0.0) {
+        return Err(format!("cue 里第 {} 轨没有 INDEX 01", track.number));
+    }
+    // 同一个文件里的音轨时间要递增，否则切出来的会是负长度
+    for pair in cue.tracks.windows(2) {
+        if pair[0].file == pair[1].file && pair[1].start <= pair[0].start {
+            return Err(format!("cue 里第 {} 轨的开始时间不晚于上一轨", pair[1].number));
+        }
+    }
+    Ok(cue)
+}
+
+// cue 常见 GBK、Shift_JIS 编码，和字幕一样先检测再解码
+pub fn read_cue(path: &Path) -> Result<Cue, String> {
+    let detected = subtitle::detect(path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))?;
+    let bytes = fs::read(path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))?;
+    let (text, _, _) = detected.encoding.decode(&bytes);
+    parse_cue(&text)
+}
+
+// 整轨文件对应的 cue：同名的 album.cue / album.flac.cue，或者同一目录里 FILE 指向它的 cue
+pub fn find_cue(audio: &Path) -> Option<(PathBuf, Cue)> {
+    let name = audio.file_name()?.to_string_lossy().into_owned();
+    let mut candidates = vec![audio.with_extension("cue"), audio.with_file_name(format!("{}.cue", name))];
+    if let Ok(entries) = fs::read_dir(folder(audio)) {
+        let mut others: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("cue")))
+            .collect();
+        others.sort();
+        candidates.extend(others);
+    }
+    candidates.into_iter().filter(|p| p.is_file()).find_map(|path| {
+        let cue = read_cue(&path).ok()?;
+        let file = cue.single_file()?;
+        Path::new(file).file_name()?.to_string_lossy().eq_ignore_ascii_case(&name).then_some((path, cue))
+    })
+}
+
+fn folder(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+// 文件名开头的 (碟号, 音轨号)："1-03 xxx" -> (Some(1), 3)，"03 - xxx"、"03. xxx"、"03_xxx" -> (None, 3)。
+// 超过 3 位的数字多半是年份，不算
+pub fn track_number(stem: &str) -> Option<(Option<u32>, u32)> {
+    let digits = |s: &str| s.chars().take_while(|c| c.is_ascii_digit()).count();
+    let first = digits(stem);
+    if first == 0 || first > 3 {
+        return None;
+    }
+    let number: u32 = stem[..first].parse().ok()?;
+    let rest = &stem[first..];
+    let separator = |s: &str| s.chars().next().is_none_or(|c| c.is_whitespace() || matches!(c, '-' | '.' | '_' | ')'));
+    if let Some(after) = rest.strip_prefix(['-', '.']) {
+        let second = digits(after);
+        if first <= 2 && (1..=3).contains(&second) && separator(&after[second..]) {
+            return Some((Some(number), after[..second].parse().ok()?));
+        }
+    }
+    separator(rest).then_some((None, number))
+}
+
+// Windows 文件名里不能有的字符换成下划线
+fn file_name(text: &str) -> String {
+    let name: String = text.chars().map(|c| if c.is_control() || "\\/:*?\"<>|".contains(c) { '_' } else { c }).collect();
+    name.trim().trim_end_matches('.').to_string()
+}
+
+// 一个音轨任务：源文件和这一轨的设置
+pub struct Track {
+    pub input: String,
+    pub album: Album,
+}
+
+// 标签值里出现最多的一个，没有时为空
+fn most_common(values: Vec<String>) -> String {
+    let mut best = (0, String::new());
+    for value in &values {
+        let count = values.iter().filter(|v| *v == value).count();
+        if count > best.0 {
+            best = (count, value.clone());
+        }
+    }
+    best.1
+}
+
+fn format_tag(info: &MediaInfo, key: &str) -> Option<String> {
+    info.format
+        .iter()
+        .find(|(k, _)| k.strip_prefix("tags.").is_some_and(|k| k.eq_ignore_ascii_case(key)))
+        .map(|(_, v)| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// 按 cue 把整轨文件切成一轨一个任务
+fn cue_tracks(input: &str, cue: &Cue, template: &Album) -> Vec<Track> {
+    let total = cue.tracks.len();
+    cue.tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let performer = if t.performer.is_empty() { &cue.performer } else { &t.performer };
+            let mut tags = vec![("track".to_string(), format!("{}/{}", t.number, total))];
+            let pairs = [
+                ("title", &t.title),
+                ("artist", performer),
+                ("album", &cue.title),
+                ("album_artist", &cue.performer),
+                ("date", &cue.date),
+                ("genre", &cue.genre),
+            ];
+            tags.extend(pairs.iter().filter(|(_, v)| !v.is_empty()).map(|(k, v)| (k.to_string(), v.to_string())));
+            if let Some(disc) = cue.disc {
+                tags.push(("disc".to_string(), disc.to_string()));
+            }
+            let name = match file_name(&t.title) {
+                title if title.is_empty() => format!("{:02}", t.number),
+                title => format!("{:02} - {}", t.number, title),
+            };
+            let album = Album { tags, span: Some(cue.span(i)), name, ..template.clone() };
+            Track { input: input.to_string(), album }
+        })
+        .collect()
+}
+
+// 同一目录里带音轨号的文件，按 (碟号, 音轨号) 排序。专辑名和专辑艺术家取各轨里最多的那个，
+// 写到每一轨上，避免个别音轨的标签不一样被播放器分成两张专辑
+fn numbered_tracks(dir: &Path, template: &Album) -> Result<(Vec<Track>, usize), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("无法读取 {}: {}", dir.display(), e))?;
+    let mut found = Vec::new();
+    let mut skipped = 0;
+    for path in entries.flatten().map(|e| e.path()) {
+        let audio = path.extension().is_some_and(|e| AUDIO_EXTS.iter().any(|x| e.eq_ignore_ascii_case(x)));
+        if !audio || !path.is_file() {
+            continue;
+        }
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        match track_number(&stem) {
+            Some((disc, track)) => found.push((disc.unwrap_or(1), track, path)),
+            None => skipped += 1,
+        }
+    }
+    if found.is_empty() {
+        return Err(format!("{} 里没有 cue，也没有文件名以音轨号开头的音频文件", dir.display()));
+    }
+    found.sort();
+    let infos: Vec<Option<MediaInfo>> = found.iter().map(|(_, _, p)| probe::probe(&p.to_string_lossy(), ProbeDepth::default()).ok()).collect();
+    let common = |key: &str| most_common(infos.iter().flatten().filter_map(|i| format_tag(i, key)).collect());
+    let album_title = common("album");
+    let album_artist = match common("album_artist") {
+        artist if artist.is_empty() => common("artist"),
+        artist => artist,
+    };
+    let discs = found.iter().map(|(d, _, _)| *d).max().unwrap_or(1);
+    let tracks = found
+        .iter()
+        .map(|(disc, track, path)| {
+            let total = found.iter().filter(|(d, _, _)| d == disc).count();
+            let mut tags = vec![("track".to_string(), format!("{}/{}", track, total))];
+            if discs > 1 {
+                tags.push(("disc".to_string(), format!("{}/{}", disc, discs)));
+            }
+            if !album_title.is_empty() {
+                tags.push(("album".to_string(), album_title.clone()));
+            }
+            if !album_artist.is_empty() {
+                tags.push(("album_artist".to_string(), album_artist.clone()));
+            }
+            let album = Album { tags, span: None, name: String::new(), ..template.clone() };
+            Track { input: path.to_string_lossy().into_owned(), album }
+        })
+        .collect();
+    Ok((tracks, skipped))
+}
+
+// 专辑面板的“按专辑排队”：input 是窗口里当前的文件。返回各轨任务和一行说明
+pub fn scan(input: &str, template: &Album) -> Result<(Vec<Track>, String), String> {
+    let path = Path::new(input);
+    if template.split_cue
+        && let Some((cue_path, cue)) = find_cue(path)
+    {
+        let name = cue_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let note = format!("按 {} 把整轨文件切成 {} 轨", name, cue.tracks.len());
+        return Ok((cue_tracks(input, &cue, template), note));
+    }
+    let (tracks, skipped) = numbered_tracks(folder(path), template)?;
+    let mut note = format!("按音轨号排好 {} 个文件", tracks.len());
+    if skipped > 0 {
+        note.push_str(&format!("，{} 个文件名不带音轨号的没有加入", skipped));
+    }
+    Ok((tracks, note))
+}
+
+// 输出路径：切出来的音轨按“音轨号 - 标题”命名，其他和普通转换一样沿用源文件名
+pub fn output_path(input: &str, album: &Album, dir: &str) -> String {
+    if album.name.is_empty() {
+        output::suggested_output(input, album.codec.ext(), dir)
+    } else {
+        output::named_output(input, &album.name, album.codec.ext(), dir)
+    }
+}
+
+// 由 plan::resolve 调用：检查编码器
+pub fn resolve(album: &Album) -> Vec<String> {
+    let mut notes = Vec::new();
+    if !encoders::available(album.codec.encoder()) {
+        notes.push(format!("ffmpeg 没有列出 {}，{} 输出可能失败", album.codec.encoder(), album.codec.label()));
+    }
+    notes
+}
+
+pub fn plan(settings: &JobSettings, album: &Album, info: &MediaInfo, input: &str, output: &str) -> JobPlan {
+    let mut job = JobPlan::default();
+    let mut args = plan::input_args(settings, input);
+    let art = coverart::pick(info, input);
+    args.append(coverart::input_args(art.as_ref(), album.codec.ext()));
+    // m4a 能带封面，opus 不能
+    let art = coverart::embed_args(art.as_ref(), album.codec.ext(), 1);
+    if art.position("-vn").is_none() {
+        args.append(art);
+    } else {
+        args.push(Source::Album, &["-map", "0:a:0", "-vn"]);
+    }
+    // 整轨文件的章节对切出来的一轨没有意义
+    let chapters = if album.span.is_some() { "-1" } else { "0" };
+    args.push(Source::Album, &["-sn", "-dn", "-map_metadata", "0", "-map_chapters", chapters]);
+    for (key, value) in &album.tags {
+        args.push(Source::Album, &["-metadata", &format!("{}={}", key, value)]);
+    }
+    if let Some((start, end)) = album.span {
+        args.push(Source::Album, &["-ss", &format!("{:.6}", start)]);
+        if let Some(end) = end {
+            args.push(Source::Album, &["-t", &format!("{:.6}", end - start)]);
+        }
+        let end = end.unwrap_or(info.duration);
+        if end > start {
+            job.run_secs = Some(end - start);
+        }
+    }
+    args.push(Source::Album, &["-c:a", album.codec.encoder(), "-b:a", &format!("{}k", album.bitrate_k)]);
+    args.append(plan::output_tail_args(settings));
+    args.push(Source::Output, &[output]);
+
+    let track = album.tags.iter().find(|(k, _)| k == "track").map(|(_, v)| format!("第 {} 轨，", v)).unwrap_or_default();
+    job.notes.push(format!(
+        "专辑: {}{} {} kbps{}",
+        track,
+        album.codec.label(),
+        album.bitrate_k,
+        match album.codec {
+            AlbumCodec::Opus => "，Opus 自带无缝播放信息",
+            AlbumCodec::Aac if album.gapless => "，转换后写入无缝播放信息 (iTunSMPB)",
+            AlbumCodec::Aac => "",
+        }
+    ));
+    job.runs.push(args);
+    job.outputs.push(output.to_string());
+    job
+}
+
+// AAC 每帧 1024 个采样
+const AAC_FRAME: u64 = 1024;
+
+// 输出里第一条音轨的 (编码延迟, 帧数, 采样率)。ffmpeg 写 mp4 时用编辑列表跳过延迟，
+// ffprobe 读出来第一帧的时间是负的，负多少就是延迟；读不到时按 ffmpeg aac 编码器固定的 1024
+fn encoder_frames(output: &str) -> Result<(u64, u64, u32), String> {
+    let out = process::command("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries", "stream=sample_rate,time_base:packet=pts", "-of", "compact", output])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| process::FfError::launch("ffprobe", e).to_string())?;
+    if !out.status.success() {
+        return Err(format!("ffprobe 无法读取 {}", output));
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    let field = |line: &str, key: &str| line.split('|').find_map(|f| f.strip_prefix(key)?.strip_prefix('=')).map(|v| v.to_string());
+    let (mut rate, mut time_base, mut first, mut frames) = (0u32, 0.0, None, 0u64);
+    for line in text.lines() {
+        if line.starts_with("packet|") {
+            frames += 1;
+            if first.is_none() {
+                first = field(line, "pts").and_then(|p| p.parse::<i64>().ok());
+            }
+        } else if line.starts_with("stream|") {
+            rate = field(line, "sample_rate").and_then(|r| r.parse().ok()).unwrap_or(0);
+            time_base = field(line, "time_base")
+                .and_then(|tb| {
+                    let (n, d) = tb.split_once('/')?;
+                    Some(n.parse::<f64>().ok()? / d.parse::<f64>().ok()?)
+                })
+                .unwrap_or(0.0);
+        }
+    }
+    if rate == 0 || frames == 0 {
+        return Err("输出里没有音频".to_string());
+    }
+    let delay = match first {
+        Some(pts) if pts < 0 && time_base > 0.0 => (-pts as f64 * time_base * rate as f64).round() as u64,
+        _ => AAC_FRAME,
+    };
+    Ok((delay, frames, rate))
+}
+
+// iTunSMPB：延迟、末尾补齐和有效采样数，后面 8 组保留为 0
+pub fn smpb(delay: u64, padding: u64, samples: u64) -> String {
+    format!(" 00000000 {:08X} {:08X} {:016X}{}", delay, padding, samples, " 00000000".repeat(8))
+}
+
+// AAC 转换成功后调用：secs 是这一轨应有的时长。返回一行日志
+pub fn write_gapless(output: &str, secs: f64) -> Result<String, String> {
+    let (delay, frames, rate) = encoder_frames(output)?;
+    let coded = frames * AAC_FRAME;
+    // 源文件的时长和实际采样数可能差一点，不超过编码出来的
+    let samples = ((secs * rate as f64).round() as u64).min(coded.saturating_sub(delay));
+    if samples == 0 {
+        return Err(format!("输出只有 {} 帧，不写无缝播放信息", frames));
+    }
+    let padding = coded - delay - samples;
+    mp4meta::add_freeform(Path::new(output), "iTunSMPB", &smpb(delay, padding, samples)).map_err(|e| e.to_string())?;
+    Ok(format!("无缝播放: 编码延迟 {} 个采样，末尾补齐 {} 个采样，已写入 iTunSMPB", delay, padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_beside_the_image() {
+        let dir = std::env::temp_dir().join(format!("ffui_album_test_cover_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("CDImage.flac").to_string_lossy().into_owned();
+        let cover = dir.join("folder.jpg").to_string_lossy().into_owned();
+        std::fs::write(&cover, b"jpg").unwrap();
+        let info = MediaInfo { duration: 3000.0, ..Default::default() };
+        let aac = Album { codec: AlbumCodec::Aac, ..Default::default() };
+        let settings = JobSettings { album: Some(aac.clone()), ..Default::default() };
+        let args = plan(&settings, &aac, &info, &input, "01.m4a").runs[0].argv().join(" ");
+        assert!(args.contains(&format!("-i {} -i {} -map 0:a:0 -map 1:0", input, cover)), "{}", args);
+        // opus 放不下封面
+        let opus = Album::default();
+        let args = plan(&settings, &opus, &info, &input, "01.opus").runs[0].argv().join(" ");
+        assert!(!args.contains(&cover) && args.contains("-map 0:a:0 -vn"), "{}", args);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_01_starts_the_track() {
+        let cue = parse_cue(
+            "\u{feff}REM GENRE Jazz\nREM DATE 1959\nPERFORMER \"Miles Davis\"\nTITLE \"Kind of Blue\"\n\
+             FILE \"Kind of Blue.flac\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"So What\"\n    INDEX 01 00:00:00\n\
+               TRACK 02 AUDIO\n    TITLE \"Freddie Freeloader\"\n    INDEX 00 09:20:50\n    INDEX 01 09:22:00\n",
+        )
+        .unwrap();
+        assert_eq!((cue.title.as_str(), cue.performer.as_str(), cue.genre.as_str(), cue.date.as_str()), ("Kind of Blue", "Miles Davis", "Jazz", "1959"));
+        assert_eq!(cue.tracks.len(), 2);
+        // INDEX 00 的间隔算在上一轨里
+        assert_eq!(cue.tracks[1].start, 562.0);
+        assert_eq!(cue.span(0), (0.0, Some(562.0)));
+        assert_eq!(cue.span(1), (562.0, None));
+        assert_eq!(cue.single_file(), Some("Kind of Blue.flac"));
+        assert_eq!(cue.tracks[1].title, "Freddie Freeloader");
+    }
+
+    #[test]
+    fn cue_time_is_in_frames() {
+        assert_eq!(parse_cue_time("01:02:30"), Some(62.4));
+        assert_eq!(parse_cue_time("00:60:00"), None);
+        assert_eq!(parse_cue_time("00:00:75"), None);
+        assert_eq!(parse_cue_time("1:02"), None);
+    }
+
+    #[test]
+    fn several_files_in_one_cue() {
+        let cue = parse_cue(
+            "FILE disc1.wav WAVE\nTRACK 1 AUDIO\nINDEX 01 00:00:00\nTRACK 2 AUDIO\nINDEX 01 03:00:00\n\
+             FILE \"disc 2.wav\" WAVE\nTRACK 3 AUDIO\nINDEX 00 00:00:00\nINDEX 01 00:02:00\n",
+        )
+        .unwrap();
+        let files: Vec<&str> = cue.tracks.iter().map(|t| t.file.as_str()).collect();
+        assert_eq!(files, ["disc1.wav", "disc1.wav", "disc 2.wav"]);
+        assert_eq!(cue.single_file(), None);
+        // 换了文件后时间从头算，不算倒退；前一个文件的最后一轨读到文件末尾
+        assert_eq!(cue.span(1), (180.0, None));
+        assert_eq!(cue.span(2), (2.0, None));
+    }
+
+    #[test]
+    fn broken_cues_are_rejected() {
+        let err = |text: &str| parse_cue(text).err().unwrap();
+        assert_eq!(err("TRACK 01 AUDIO\n"), "cue 第 1 行: TRACK 前面没有 FILE");
+        assert_eq!(err("FILE a.flac WAVE\nTRACK 01 AUDIO\nINDEX 00 00:00:00\n"), "cue 里第 1 轨没有 INDEX 01");
+        assert_eq!(
+            err("FILE a.flac WAVE\nTRACK 01 AUDIO\nINDEX 01 00:10:00\nTRACK 02 AUDIO\nINDEX 01 00:05:00\n"),
+            "cue 里第 2 轨的开始时间不晚于上一轨"
+        );
+        assert_eq!(err("FILE a.flac WAVE\nTRACK 01 AUDIO\nINDEX 01 0:0\n"), "cue 第 3 行: 时间格式应为 分:秒:帧");
+        assert_eq!(err("REM nothing\n"), "cue 里没有音轨");
+    }
+}
@@ -0,0 +1,299 @@
+// 生成的 ffmpeg 参数连同它来自哪个设置一起记下：命令预览里能看出每个参数的来源，
+// ffmpeg 拒绝某个参数时报错也能指出是哪个设置。运行时再展开成普通的参数列表
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    // ffui 固定加的：进度输出、-map 等
+    Base,
+    Overwrite,
+    Device,
+    ProbeDepth,
+    Fixes,
+    InputFormat,
+    Trim,
+    Input,
+    Tracks,
+    Filters,
+    Codec,
+    AudioCodec,
+    Quality,
+    TwoPass,
+    Preset,
+    Gop,
+    Lengths,
+    Retime,
+    Loudnorm,
+    Throttle,
+    CoverArt,
+    DataLoss,
+    Aspect,
+    Timecode,
+    Dates,
+    Ladder,
+    Web,
+    Snapshot,
+    Speech,
+    Album,
+    Quick,
+    Remux,
+    Preview,
+    Extra,
+    Output,
+}
+
+impl Source {
+    pub const ALL: [Source; 35] = [
+        Source::Base, Source::Overwrite, Source::Device, Source::ProbeDepth, Source::Fixes,
+        Source::InputFormat, Source::Trim, Source::Input, Source::Tracks, Source::Filters, Source::Codec,
+        Source::AudioCodec, Source::Quality, Source::TwoPass, Source::Preset, Source::Gop, Source::Lengths, Source::Retime, Source::Loudnorm, Source::Throttle, Source::CoverArt, Source::DataLoss, Source::Aspect, Source::Timecode, Source::Dates,
+        Source::Ladder, Source::Web, Source::Snapshot, Source::Speech, Source::Album, Source::Quick, Source::Remux, Source::Preview, Source::Extra, Source::Output,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::Base => "基本参数",
+            Source::Overwrite => "覆盖已存在的输出",
+            Source::Device => "处理设备",
+            Source::ProbeDepth => "分析时长/探测大小",
+            Source::Fixes => "修正参数",
+            Source::InputFormat => "输入格式",
+            Source::Trim => "裁剪",
+            Source::Input => "输入文件",
+            Source::Tracks => "音轨处理",
+            Source::Filters => "视频滤镜（反交错/字幕/宽高比/补帧/时间码）",
+            Source::Codec => "视频编码",
+            Source::AudioCodec => "音频编码",
+            Source::Quality => "质量设置",
+            Source::TwoPass => "两遍编码",
+            Source::Preset => "速度档位/调优",
+            Source::Gop => "关键帧间隔",
+            Source::Lengths => "音视频时长不一致",
+            Source::Retime => "帧率重映射",
+            Source::Loudnorm => "响度标准化",
+            Source::Throttle => "限制写入速度",
+            Source::CoverArt => "封面",
+            Source::DataLoss => "数据丢失预检（字幕/附件/章节/HDR）",
+            Source::Aspect => "非方形像素",
+            Source::Timecode => "时间码",
+            Source::Dates => "保留拍摄日期",
+            Source::Ladder => "多分辨率",
+            Source::Web => "一键方案",
+            Source::Snapshot => "导出截图",
+            Source::Speech => "语音优化",
+            Source::Album => "专辑",
+            Source::Quick => "快速操作",
+            Source::Remux => "仅转换封装",
+            Source::Preview => "预览",
+            Source::Extra => "附加参数",
+            Source::Output => "输出文件",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Args {
+    items: Vec<(String, Source)>,
+}
+
+impl Args {
+    pub fn new() -> Args {
+        Args::default()
+    }
+
+    pub fn push(&mut self, source: Source, a: &[&str]) {
+        self.items.extend(a.iter().map(|s| (s.to_string(), source)));
+    }
+
+    // 各模块自己生成的参数列表整段记在一个来源下
+    pub fn push_all(&mut self, source: Source, a: Vec<String>) {
+        self.items.extend(a.into_iter().map(|s| (s, source)));
+    }
+
+    pub fn append(&mut self, other: Args) {
+        self.items.extend(other.items);
+    }
+
+    pub fn insert(&mut self, at: usize, source: Source, arg: String) {
+        self.items.insert(at, (arg, source));
+    }
+
+    // 取下最后一个参数（输出路径）
+    pub fn pop(&mut self) -> Option<String> {
+        self.items.pop().map(|(a, _)| a)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn position(&self, arg: &str) -> Option<usize> {
+        self.items.iter().position(|(a, _)| a == arg)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Source)> {
+        self.items.iter().map(|(a, s)| (a.as_str(), *s))
+    }
+
+    // 交给 ffmpeg 的参数
+    pub fn argv(&self) -> Vec<String> {
+        self.items.iter().map(|(a, _)| a.clone()).collect()
+    }
+
+    // 选项名（不带 -）对应的来源，取第一次出现的
+    pub fn source_of(&self, option: &str) -> Option<Source> {
+        let flag = format!("-{}", option.trim_start_matches('-'));
+        self.items.iter().find(|(a, _)| *a == flag).map(|(_, s)| *s)
+    }
+}
+
+// 'cq'、cq 或 -cq 后面带标点的写法统一成选项名
+fn option_name(text: &str) -> Option<&str> {
+    let name = text.trim_matches(|c: char| c == '\'' || c == '"' || c == '.' || c == ',').trim_start_matches('-');
+    (!name.is_empty()).then_some(name)
+}
+
+// ffmpeg 因为某个参数退出时，说明是哪个参数、来自哪个设置：
+// Unrecognized option 'cq'. / Option cq not found. / Error setting option cq to value 30.
+pub fn explain_rejection<'a>(lines: impl IntoIterator<Item = &'a str>, args: &Args) -> Option<String> {
+    for line in lines {
+        let found = if let Some(rest) = line.split("Unrecognized option ").nth(1) {
+            option_name(rest).map(|n| (n, "ffmpeg 不认识"))
+        } else if let Some(rest) = line.split("Option ").nth(1).filter(|_| line.contains("not found")) {
+            rest.split_whitespace().next().and_then(option_name).map(|n| (n, "ffmpeg 不认识"))
+        } else if let Some(rest) = line.split("Error setting option ").nth(1) {
+            rest.split_whitespace().next().and_then(option_name).map(|n| (n, "编码器拒绝了"))
+        } else {
+            None
+        };
+        let Some((name, rejected_by)) = found else { continue };
+        let from = args.source_of(name).map(|s| format!("（来自：{}）", s.label())).unwrap_or_default();
+        return Some(format!("{} -{} 参数{}", rejected_by, name, from));
+    }
+    None
+}
+
+// 附加参数的文本拆成参数列表，规则接近 shell：空白分隔，双引号或单引号里的空白不分隔，
+// 引号可以在参数中间（-metadata title="My Video" 拆成 -metadata 和 title=My Video）。
+// 反斜杠只在 \" 里转义引号，Windows 路径里的反斜杠原样保留
+pub fn split_extra(text: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') if chars.peek() == Some(&'"') => {
+                chars.next();
+                current.get_or_insert_with(String::new).push('"');
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                // "" 也算一个参数（空值）
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("附加参数里的 {} 没有配对", q));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str) -> Vec<String> {
+        split_extra(text).unwrap()
+    }
+
+    #[test]
+    fn sources_listed_once() {
+        for (i, s) in Source::ALL.iter().enumerate() {
+            assert!(!Source::ALL[..i].contains(s), "{} 重复", s.label());
+        }
+    }
+
+    #[test]
+    fn args_keep_their_source() {
+        let mut args = Args::new();
+        args.push(Source::Base, &["-progress", "pipe:1"]);
+        args.push(Source::Quality, &["-crf", "23"]);
+        let mut extra = Args::new();
+        extra.push_all(Source::Extra, vec!["-crf".to_string(), "18".to_string()]);
+        args.append(extra);
+        args.push(Source::Output, &["out.mp4"]);
+        args.insert(2, Source::Input, "-i".to_string());
+        assert_eq!(args.argv(), ["-progress", "pipe:1", "-i", "-crf", "23", "-crf", "18", "out.mp4"]);
+        assert_eq!(args.position("-crf"), Some(3));
+        // 同名选项取第一次出现的来源，带不带 - 都行
+        assert!(args.source_of("crf") == Some(Source::Quality));
+        assert!(args.source_of("-i") == Some(Source::Input));
+        assert!(args.source_of("vf").is_none());
+        assert_eq!(args.pop().as_deref(), Some("out.mp4"));
+        assert_eq!(args.len(), 7);
+    }
+
+    #[test]
+    fn rejections_name_the_setting() {
+        let mut args = Args::new();
+        args.push(Source::Quality, &["-cq", "30"]);
+        args.push(Source::Extra, &["-tune", "film"]);
+        let explain = |line: &str| explain_rejection([line], &args);
+        assert_eq!(explain("Unrecognized option 'cq'.").as_deref(), Some("ffmpeg 不认识 -cq 参数（来自：质量设置）"));
+        assert_eq!(explain("Option tune not found.").as_deref(), Some("ffmpeg 不认识 -tune 参数（来自：附加参数）"));
+        assert_eq!(
+            explain("[libx264 @ 0x55] Error setting option tune to value film.").as_deref(),
+            Some("编码器拒绝了 -tune 参数（来自：附加参数）")
+        );
+        // 不是 ffui 加的参数时只说参数名
+        assert_eq!(explain("Unrecognized option 'foo'.").as_deref(), Some("ffmpeg 不认识 -foo 参数"));
+        assert!(explain_rejection(["Conversion failed!", "Option mapping"], &args).is_none());
+        // 取第一条能认出的
+        let lines = ["frame=1", "Option cq not found.", "Unrecognized option 'tune'."];
+        assert_eq!(explain_rejection(lines, &args).as_deref(), Some("ffmpeg 不认识 -cq 参数（来自：质量设置）"));
+    }
+
+    #[test]
+    fn split_on_whitespace() {
+        assert_eq!(split("  -an\t-sn \n -dn "), ["-an", "-sn", "-dn"]);
+        assert!(split("").is_empty());
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn split_quotes() {
+        assert_eq!(split("-metadata title=\"My Video\""), ["-metadata", "title=My Video"]);
+        assert_eq!(split("-metadata 'comment=say \"hi\"'"), ["-metadata", "comment=say \"hi\""]);
+        assert_eq!(split("-metadata \"artist=Bob's band\""), ["-metadata", "artist=Bob's band"]);
+        // 相邻的引号段拼成一个参数，空引号是空值
+        assert_eq!(split("a\"b c\"'d e'f"), ["ab cd ef"]);
+        assert_eq!(split("-metadata title= \"\" x"), ["-metadata", "title=", "", "x"]);
+        assert_eq!(split("-metadata title=\"say \\\"hi\\\"\""), ["-metadata", "title=say \"hi\""]);
+        assert_eq!(split("\\\"bare\\\""), ["\"bare\""]);
+        // 单引号里不转义
+        assert_eq!(split("'a\\\"b'"), ["a\\\"b"]);
+    }
+
+    #[test]
+    fn split_keeps_windows_paths() {
+        assert_eq!(split("-i C:\\media\\logo.png"), ["-i", "C:\\media\\logo.png"]);
+        assert_eq!(split("-i \"C:\\My Media\\logo.png\""), ["-i", "C:\\My Media\\logo.png"]);
+        assert_eq!(split("-vf subtitles=D\\:\\\\subs.srt"), ["-vf", "subtitles=D\\:\\\\subs.srt"]);
+    }
+
+    #[test]
+    fn unbalanced_quotes() {
+        assert_eq!(split_extra("-metadata title=\"My Video").unwrap_err(), "附加参数里的 \" 没有配对");
+        assert_eq!(split_extra("-metadata 'comment").unwrap_err(), "附加参数里的 ' 没有配对");
+        // 另一种引号里的不算
+        assert!(split_extra("\"it's\"").is_ok());
+        assert_eq!(split_extra("'say \"hi'\"").unwrap_err(), "附加参数里的 \" 没有配对");
+        // 转义的引号不开始引用
+        assert!(split_extra("a\\\"b").is_ok());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::probe::StreamInfo;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum AspectTarget {
+    #[default]
+    Off,
+    Wide,
+    Tall,
+    Square,
+    Classic,
+    Custom,
+}
+
+impl AspectTarget {
+    pub const ALL: [AspectTarget; 6] = [
+        AspectTarget::Off, AspectTarget::Wide, AspectTarget::Tall,
+        AspectTarget::Square, AspectTarget::Classic, AspectTarget::Custom,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AspectTarget::Off => "不调整",
+            AspectTarget::Wide => "16:9",
+            AspectTarget::Tall => "9:16",
+            AspectTarget::Square => "1:1",
+            AspectTarget::Classic => "4:3",
+            AspectTarget::Custom => "自定义",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Fill {
+    #[default]
+    Color,
+    // 同一画面放大裁切后模糊作为背景
+    Blur,
+}
+
+impl Fill {
+    pub fn label(self) -> &'static str {
+        match self {
+            Fill::Color => "纯色",
+            Fill::Blur => "模糊背景",
+        }
+    }
+}
+
+// 补边到目标宽高比，画面等比缩放居中，不拉伸
+#[derive(Clone, Default)]
+pub struct Fit {
+    pub target: AspectTarget,
+    pub custom: (u32, u32),
+    pub fill: Fill,
+    pub color: [u8; 3],
+    // 以下由 plan::resolve 根据探测结果填写
+    pub canvas: Option<(u32, u32)>,
+    pub anamorphic: bool,
+}
+
+impl Fit {
+    pub fn ratio(&self) -> Option<(u32, u32)> {
+        match self.target {
+            AspectTarget::Off => None,
+            AspectTarget::Wide => Some((16, 9)),
+            AspectTarget::Tall => Some((9, 16)),
+            AspectTarget::Square => Some((1, 1)),
+            AspectTarget::Classic => Some((4, 3)),
+            AspectTarget::Custom => Some(self.custom).filter(|(w, h)| *w > 0 && *h > 0),
+        }
+    }
+
+    pub fn filter(&self) -> Option<String> {
+        let (w, h) = self.canvas?;
+        // 非方形像素先还原成显示尺寸，否则等比缩放会按存储尺寸算错
+        let square = if self.anamorphic { "scale=trunc(iw*sar/2)*2:ih,setsar=1," } else { "" };
+        let fit = format!("scale={}:{}:force_original_aspect_ratio=decrease:force_divisible_by=2,setsar=1", w, h);
+        Some(match self.fill {
+            Fill::Color => {
+                let [r, g, b] = self.color;
+                format!("{}{},pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=0x{:02X}{:02X}{:02X}", square, fit, w, h, r, g, b)
+            }
+            // 只有一个输入一个输出，可以直接放进 -vf 和多分辨率的 split 前面
+            Fill::Blur => format!(
+                "{}split[fita][fitb];\
+                 [fita]scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},setsar=1,boxblur=20:2[fitbg];\
+                 [fitb]{}[fitfg];\
+                 [fitbg][fitfg]overlay=(W-w)/2:(H-h)/2",
+                square, fit, w = w, h = h,
+            ),
+        })
+    }
+}
+
+// 非方形像素的素材怎么输出
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum SarMode {
+    // 缩放到显示尺寸，输出方形像素，所有播放器都不会拉伸错
+    #[default]
+    Square,
+    // 不改变像素，只用 -aspect 标明显示比例
+    Keep,
+}
+
+impl SarMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            SarMode::Square => "按显示比例拉伸像素",
+            SarMode::Keep => "保留像素比",
+        }
+    }
+}
+
+// 奇数宽高和非方形像素的修正：(滤镜, -aspect 的值, 说明)
+pub fn pixel_fix(stream: &StreamInfo, mode: SarMode) -> Option<(Option<String>, Option<String>, String)> {
+    let get = |k: &str| stream.props.get(k).and_then(|v| v.parse::<u32>().ok()).filter(|v| *v > 0);
+    let (w, h) = (get("width")?, get("height")?);
+    let (dw, _, anamorphic) = display_size(stream)?;
+    let odd = w % 2 == 1 || h % 2 == 1;
+    let sar = stream.props.get("sample_aspect_ratio").cloned().unwrap_or_default();
+    const EVEN: &str = "scale=trunc(iw/2)*2:trunc(ih/2)*2";
+    match (anamorphic, mode) {
+        (true, SarMode::Square) => Some((
+            Some("scale=trunc(iw*sar/2)*2:trunc(ih/2)*2,setsar=1".to_string()),
+            None,
+            format!("像素比 {}：{}x{} 拉伸为 {}x{} 方形像素", sar, w, h, dw / 2 * 2, h / 2 * 2),
+        )),
+        (true, SarMode::Keep) => Some((
+            odd.then(|| EVEN.to_string()),
+            Some(format!("{}:{}", dw, h)),
+            format!("像素比 {}：保留原像素，显示比例标为 {}:{}{}", sar, dw, h, if odd { "，奇数宽高裁成偶数" } else { "" }),
+        )),
+        (false, _) if odd => Some((
+            Some(EVEN.to_string()),
+            None,
+            format!("{}x{} 不是偶数，缩放为 {}x{}", w, h, w / 2 * 2, h / 2 * 2),
+        )),
+        (false, _) => None,
+    }
+}
+
+fn even(v: u64) -> u32 {
+    (v.div_ceil(2) * 2) as u32
+}
+
+// 画布的长边等于原画面的长边，另一边按目标比例算，宽高都取偶数。
+// 横屏转竖屏时画面缩小放进画布，而不是把画布放大到几千像素
+pub fn canvas(width: u32, height: u32, (tw, th): (u32, u32)) -> (u32, u32) {
+    let (long, tw, th) = (width.max(height) as u64, tw as u64, th as u64);
+    if tw >= th {
+        (even(long), even(long * th / tw))
+    } else {
+        (even(long * tw / th), even(long))
+    }
+}
+
+// 按 sample_aspect_ratio 换算的显示尺寸，以及是否为非方形像素
+pub fn display_size(stream: &StreamInfo) -> Option<(u32, u32, bool)> {
+    let get = |k: &str| stream.props.get(k).and_then(|v| v.parse::<u32>().ok()).filter(|v| *v > 0);
+    let (w, h) = (get("width")?, get("height")?);
+    let sar = stream.props.get("sample_aspect_ratio")
+        .and_then(|s| s.split_once(':'))
+        .and_then(|(n, d)| Some((n.parse::<u32>().ok()?, d.parse::<u32>().ok()?)))
+        .filter(|(n, d)| *n > 0 && *d > 0 && n != d);
+    match sar {
+        Some((n, d)) => Some(((w as u64 * n as u64 / d as u64) as u32, h, true)),
+        None => Some((w, h, false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canvas_keeps_long_side_and_even_dimensions() {
+        assert_eq!(canvas(1080, 1920, (16, 9)), (1920, 1080));
+        assert_eq!(canvas(1920, 1080, (9, 16)), (1080, 1920));
+        assert_eq!(canvas(1920, 1080, (1, 1)), (1920, 1920));
+        // 奇数长边向上取偶数，另一边按比例取整后再取偶数
+        assert_eq!(canvas(1921, 1080, (4, 3)), (1922, 1440));
+        assert_eq!(canvas(1000, 500, (21, 9)), (1000, 428));
+        assert_eq!(canvas(720, 480, (4, 3)), (720, 540));
+    }
+
+    #[test]
+    fn custom_ratio_needs_both_sides() {
+        let fit = |target, custom| Fit { target, custom, ..Default::default() };
+        assert_eq!(fit(AspectTarget::Off, (16, 9)).ratio(), None);
+        assert_eq!(fit(AspectTarget::Classic, (0, 0)).ratio(), Some((4, 3)));
+        assert_eq!(fit(AspectTarget::Custom, (21, 9)).ratio(), Some((21, 9)));
+        assert_eq!(fit(AspectTarget::Custom, (21, 0)).ratio(), None);
+    }
+
+    #[test]
+    fn color_fill_scales_then_pads() {
+        let fit = Fit { target: AspectTarget::Wide, canvas: Some((1920, 1080)), color: [0x10, 0x20, 0xFF], ..Default::default() };
+        assert_eq!(
+            fit.filter().unwrap(),
+            "scale=1920:1080:force_original_aspect_ratio=decrease:force_divisible_by=2,setsar=1,\
+             pad=1920:1080:(ow-iw)/2:(oh-ih)/2:color=0x1020FF"
+        );
+        // 还没探测出画布时不加滤镜
+        assert_eq!(Fit { canvas: None, ..fit }.filter(), None);
+    }
+
+    #[test]
+    fn blur_fill_is_a_single_chain() {
+        let fit = Fit { target: AspectTarget::Tall, fill: Fill::Blur, canvas: Some((1080, 1920)), anamorphic: true, ..Default::default() };
+        let filter = fit.filter().unwrap();
+        assert!(filter.starts_with("scale=trunc(iw*sar/2)*2:ih,setsar=1,split[fita][fitb];"), "{}", filter);
+        assert!(filter.contains("[fita]scale=1080:1920:force_original_aspect_ratio=increase,crop=1080:1920,"), "{}", filter);
+        assert!(filter.contains("[fitb]scale=1080:1920:force_original_aspect_ratio=decrease"), "{}", filter);
+        assert!(filter.ends_with("[fitbg][fitfg]overlay=(W-w)/2:(H-h)/2"), "{}", filter);
+        // 一进一出，不能留下没接上的标签
+        for label in ["[fita]", "[fitb]", "[fitbg]", "[fitfg]"] {
+            assert_eq!(filter.matches(label).count(), 2, "{}", label);
+        }
+    }
+
+    fn video(width: &str, height: &str, sar: Option<&str>) -> StreamInfo {
+        let mut stream = StreamInfo { codec_type: "video".to_string(), ..Default::default() };
+        stream.props.insert("width".to_string(), width.to_string());
+        stream.props.insert("height".to_string(), height.to_string());
+        if let Some(sar) = sar {
+            stream.props.insert("sample_aspect_ratio".to_string(), sar.to_string());
+        }
+        stream
+    }
+
+    #[test]
+    fn display_size_applies_sar() {
+        assert_eq!(display_size(&video("720", "576", Some("16:15"))), Some((768, 576, true)));
+        assert_eq!(display_size(&video("1440", "1080", Some("4:3"))), Some((1920, 1080, true)));
+        // 1:1、0:1（未知）和写不对的都当作方形像素
+        for sar in [Some("1:1"), Some("0:1"), Some("N/A"), None] {
+            assert_eq!(display_size(&video("1920", "1080", sar)), Some((1920, 1080, false)), "{:?}", sar);
+        }
+        assert_eq!(display_size(&video("0", "1080", None)), None);
+    }
+
+    #[test]
+    fn anamorphic_source_is_squared_or_flagged() {
+        let dv = video("720", "576", Some("16:15"));
+        let (filter, aspect, note) = pixel_fix(&dv, SarMode::Square).unwrap();
+        assert_eq!(filter.as_deref(), Some("scale=trunc(iw*sar/2)*2:trunc(ih/2)*2,setsar=1"));
+        assert_eq!(aspect, None);
+        assert_eq!(note, "像素比 16:15：720x576 拉伸为 768x576 方形像素");
+        let (filter, aspect, _) = pixel_fix(&dv, SarMode::Keep).unwrap();
+        assert_eq!((filter, aspect.as_deref()), (None, Some("768:576")));
+        // 保留像素比时奇数宽高仍要裁成偶数
+        let (filter, _, note) = pixel_fix(&video("721", "576", Some("16:15")), SarMode::Keep).unwrap();
+        assert_eq!(filter.as_deref(), Some("scale=trunc(iw/2)*2:trunc(ih/2)*2"));
+        assert!(note.ends_with("奇数宽高裁成偶数"), "{}", note);
+    }
+
+    #[test]
+    fn odd_square_pixels_are_evened_and_normal_sources_untouched() {
+        let (filter, aspect, note) = pixel_fix(&video("1919", "1079", Some("1:1")), SarMode::Square).unwrap();
+        assert_eq!((filter.as_deref(), aspect), (Some("scale=trunc(iw/2)*2:trunc(ih/2)*2"), None));
+        assert_eq!(note, "1919x1079 不是偶数，缩放为 1918x1078");
+        assert!(pixel_fix(&video("1920", "1080", Some("1:1")), SarMode::Square).is_none());
+        assert!(pixel_fix(&video("1920", "1080", None), SarMode::Keep).is_none());
+        assert!(pixel_fix(&video("", "1080", None), SarMode::Keep).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use std::process::{Child, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Key};
+
+use crate::a11y;
+use crate::loudness::{self, Loudness};
+use crate::process;
+use crate::timestamp::{self, TimeField};
+
+// 转换前后的音频对比：两边各测一次响度、画一张波形图上下对齐，
+// 再用 ffplay 轮流播放同一段，按空格在原文件和输出之间切换，接着同一时刻继续放
+
+// 试听的片段长度
+const WINDOW: Duration = Duration::from_secs(10);
+const WAVE_SIZE: &str = "800x120";
+const SIDES: [&str; 2] = ["原文件", "转换后"];
+
+// 表格的一行：名称、单位、取值
+type Metric = (&'static str, &'static str, fn(&Loudness) -> f64);
+const METRICS: [Metric; 3] = [
+    ("综合响度", "LUFS", |l| l.integrated),
+    ("响度范围", "LU", |l| l.range),
+    ("真峰值", "dBTP", |l| l.true_peak),
+];
+
+#[derive(Default)]
+struct Side {
+    loudness: Option<Result<Loudness, String>>,
+    wave: Option<Result<egui::ColorImage, String>>,
+}
+
+// 整条音轨的波形，单声道合成一条
+fn waveform(input: &str) -> Result<egui::ColorImage, String> {
+    let output = process::command("ffmpeg")
+        .args([
+            "-nostdin", "-v", "error",
+            "-i", input,
+            "-filter_complex", &format!("[0:a:0]aformat=channel_layouts=mono,showwavespic=s={}:colors=#4a90d9", WAVE_SIZE),
+            "-frames:v", "1",
+            "-f", "image2pipe", "-c:v", "png", "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("无法启动 ffmpeg: {}", e))?;
+    let image = image::load_from_memory_with_format(&output.stdout, image::ImageFormat::Png)
+        .map_err(|_| "无法生成波形图".to_string())?
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
+
+// 正在播放的一边。position 是这次启动时片段内的位置
+struct Player {
+    child: Child,
+    side: usize,
+    started: Instant,
+    position: Duration,
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn play(input: &str, from: Duration, length: Duration) -> std::io::Result<Child> {
+    process::command("ffplay")
+        .args([
+            "-nodisp", "-autoexit", "-loglevel", "error",
+            "-ss", &format!("{:.3}", from.as_secs_f64()),
+            "-t", &format!("{:.3}", length.as_secs_f64()),
+            "-i", input,
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+pub struct AudioCompare {
+    files: [String; 2],
+    sides: Arc<Mutex<[Side; 2]>>,
+    textures: [Option<egui::TextureHandle>; 2],
+    start: Duration,
+    start_field: TimeField,
+    player: Option<Player>,
+    error: Option<String>,
+}
+
+impl AudioCompare {
+    // 立刻在后台开始测量和画波形，两边并行
+    pub fn new(source: &str, output: &str) -> Self {
+        let files = [source.to_string(), output.to_string()];
+        let sides: Arc<Mutex<[Side; 2]>> = Arc::default();
+        for (i, file) in files.iter().enumerate() {
+            let (file, results) = (file.clone(), sides.clone());
+            thread::spawn(move || {
+                let wave = waveform(&file);
+                results.lock().unwrap()[i].wave = Some(wave);
+                let measured = loudness::measure(&file);
+                results.lock().unwrap()[i].loudness = Some(measured);
+            });
+        }
+        AudioCompare {
+            files,
+            sides,
+            textures: [None, None],
+            start: Duration::ZERO,
+            start_field: TimeField::new(Duration::ZERO),
+            player: None,
+            error: None,
+        }
+    }
+
+    // 从片段内的 position 开始放 side 这一边，到片段结束为止
+    fn switch_to(&mut self, side: usize, position: Duration) {
+        self.player = None;
+        if position >= WINDOW {
+            return;
+        }
+        match play(&self.files[side], self.start + position, WINDOW - position) {
+            Ok(child) => {
+                self.player = Some(Player { child, side, started: Instant::now(), position });
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("无法启动 ffplay（{}），请确认它和 ffmpeg 在同一目录或在 PATH 里", e)),
+        }
+    }
+
+    // 当前在片段里放到的位置
+    fn position(&self) -> Option<Duration> {
+        self.player.as_ref().map(|p| p.position + p.started.elapsed())
+    }
+
+    fn toggle(&mut self) {
+        if let (Some(side), Some(position)) = (self.player.as_ref().map(|p| p.side), self.position()) {
+            self.switch_to(1 - side, position);
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if let Some(player) = &mut self.player
+            && !matches!(player.child.try_wait(), Ok(None))
+        {
+            self.player = None;
+        }
+        // 没有控件占用键盘时空格切换，避免和按钮、输入框的空格冲突
+        if ui.memory(|m| m.focus().is_none()) && ui.input(|i| i.key_pressed(Key::Space)) {
+            self.toggle();
+        }
+
+        let sides = self.sides.lock().unwrap();
+        let measured: Vec<Option<Loudness>> = sides.iter().map(|s| s.loudness.as_ref().and_then(|r| r.as_ref().ok()).copied()).collect();
+        egui::Grid::new("loudness_compare").num_columns(4).striped(true).show(ui, |ui| {
+            ui.label("");
+            ui.label(SIDES[0]);
+            ui.label(SIDES[1]);
+            ui.label("差值");
+            ui.end_row();
+            for (name, unit, get) in METRICS {
+                ui.label(name);
+                for side in sides.iter() {
+                    match &side.loudness {
+                        None => { ui.spinner(); }
+                        Some(Ok(l)) => { ui.label(format!("{:.1} {}", get(l), unit)); }
+                        Some(Err(e)) => { ui.colored_label(egui::Color32::RED, "测量失败").on_hover_text(e); }
+                    }
+                }
+                match (&measured[0], &measured[1]) {
+                    (Some(a), Some(b)) if (get(b) - get(a)).is_finite() => { ui.label(format!("{:+.1} {}", get(b) - get(a), unit)); }
+                    _ => { ui.label("-"); }
+                }
+                ui.end_row();
+            }
+        });
+
+        for (i, side) in sides.iter().enumerate() {
+            ui.label(SIDES[i]);
+            match &side.wave {
+                None => { ui.spinner(); }
+                Some(Err(e)) => { ui.colored_label(egui::Color32::RED, e); }
+                Some(Ok(image)) => {
+                    let texture = self.textures[i]
+                        .get_or_insert_with(|| ui.ctx().load_texture(format!("waveform_{}", i), image.clone(), Default::default()));
+                    a11y::named(ui.image(texture.id(), texture.size_vec2()), &format!("{}的波形", SIDES[i]));
+                }
+            }
+        }
+        drop(sides);
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let label = ui.label(format!("试听片段（{} 秒）从", WINDOW.as_secs()));
+            self.start_field.show(ui, &mut self.start, Duration::ZERO).labelled_by(label.id);
+        });
+        ui.horizontal(|ui| {
+            for (i, name) in SIDES.iter().enumerate() {
+                if ui.button(format!("播放{}", name)).clicked() {
+                    self.switch_to(i, Duration::ZERO);
+                }
+            }
+            if self.player.is_some() {
+                if ui.button("切换（空格）").clicked() {
+                    self.toggle();
+                }
+                if ui.button("停止").clicked() {
+                    self.player = None;
+                }
+            }
+        });
+        if let (Some(player), Some(position)) = (&self.player, self.position()) {
+            ui.label(format!(
+                "正在播放{} {}",
+                SIDES[player.side],
+                timestamp::format(Duration::from_secs((self.start + position.min(WINDOW)).as_secs()))
+            ));
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+    }
+}
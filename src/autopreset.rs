@@ -0,0 +1,307 @@
+use std::path::Path;
+
+use crate::json::Value;
+use crate::presets::Preset;
+use crate::probe::MediaInfo;
+
+// 预设的匹配规则：载入或排队一个文件时，按规则挑出合适的预设自动套用。
+// 规则里写了的条件都要满足，同一条件里逗号分隔的几项满足一项即可；什么都没写的规则不匹配任何文件。
+// 几个预设同时匹配时优先级大的胜出，优先级相同时按预设列表里的顺序（先保存的在前）
+
+#[derive(Clone, PartialEq, Default)]
+pub struct Rule {
+    // 文件名通配：*.mkv、clip_*.mov；只写扩展名（mkv）时等同于 *.mkv
+    pub names: String,
+    // 任一路视频或音频的编码（ffprobe 的 codec_name）：hevc、pcm_s16le
+    pub codecs: String,
+    // 视频高度的范围，0 表示不限
+    pub min_height: u32,
+    pub max_height: u32,
+    // 路径里包含的文字，不分大小写
+    pub paths: String,
+    pub priority: i32,
+}
+
+// presets.toml 里的键名，和预设的设置写在同一个表里
+const KEYS: [&str; 6] = ["match_names", "match_codecs", "match_min_height", "match_max_height", "match_paths", "match_priority"];
+
+fn items(list: &str) -> Vec<&str> {
+    list.split([',', '，']).map(str::trim).filter(|s| !s.is_empty()).collect()
+}
+
+// 只认 * 和 ?，不分大小写
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob(rest, &name[skip..])),
+        Some((p, rest)) => name.split_first().is_some_and(|(c, name)| (*p == '?' || p == c) && glob(rest, name)),
+    }
+}
+
+fn name_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let pattern = if pattern.contains(['*', '?', '.']) { pattern } else { format!("*.{}", pattern) };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    glob(&pattern, &name)
+}
+
+// 第一路视频的高度，封面图不算
+fn video_height(info: &MediaInfo) -> Option<u32> {
+    info.streams
+        .iter()
+        .filter(|s| s.codec_type == "video" && s.props.get("disposition.attached_pic").is_none_or(|v| v != "1"))
+        .find_map(|s| s.props.get("height")?.parse().ok())
+}
+
+impl Rule {
+    pub fn is_empty(&self) -> bool {
+        items(&self.names).is_empty()
+            && items(&self.codecs).is_empty()
+            && items(&self.paths).is_empty()
+            && self.min_height == 0
+            && self.max_height == 0
+    }
+
+    // 不匹配时返回第一个不满足的条件。info 为 None 表示读不到媒体信息，要看编码或分辨率的规则不匹配
+    pub fn check(&self, input: &str, info: Option<&MediaInfo>) -> Result<(), String> {
+        if self.is_empty() {
+            return Err("没有设置规则".to_string());
+        }
+        let names = items(&self.names);
+        let file_name = Path::new(input).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        if !names.is_empty() && !names.iter().any(|p| name_matches(p, &file_name)) {
+            return Err(format!("文件名不符合 {}", names.join("、")));
+        }
+        let paths = items(&self.paths);
+        let lower = input.to_lowercase();
+        if !paths.is_empty() && !paths.iter().any(|p| lower.contains(&p.to_lowercase())) {
+            return Err(format!("路径不包含 {}", paths.join("、")));
+        }
+        let codecs = items(&self.codecs);
+        if !codecs.is_empty() {
+            let info = info.ok_or("读不到媒体信息，无法判断编码")?;
+            let found: Vec<&str> = info.streams
+                .iter()
+                .filter(|s| s.codec_type == "video" || s.codec_type == "audio")
+                .map(|s| s.codec_name.as_str())
+                .collect();
+            if !found.iter().any(|c| codecs.iter().any(|want| want.eq_ignore_ascii_case(c))) {
+                return Err(format!("编码不是 {}（源文件: {}）", codecs.join("、"), found.join("、")));
+            }
+        }
+        if self.min_height > 0 || self.max_height > 0 {
+            let height = info.and_then(video_height).ok_or("没有视频或读不到分辨率")?;
+            if height < self.min_height {
+                return Err(format!("视频高度 {} 低于 {}", height, self.min_height));
+            }
+            if self.max_height > 0 && height > self.max_height {
+                return Err(format!("视频高度 {} 高于 {}", height, self.max_height));
+            }
+        }
+        Ok(())
+    }
+
+    // 写进 presets.toml 的项，没设置的不写
+    pub fn values(&self) -> Vec<(String, Value)> {
+        let text = |key: &str, s: &str| (!s.trim().is_empty()).then(|| (key.to_string(), Value::Str(s.trim().to_string())));
+        let num = |key: &str, n: f64| (n != 0.0).then(|| (key.to_string(), Value::Num(n)));
+        [
+            text(KEYS[0], &self.names),
+            text(KEYS[1], &self.codecs),
+            num(KEYS[2], self.min_height as f64),
+            num(KEYS[3], self.max_height as f64),
+            text(KEYS[4], &self.paths),
+            num(KEYS[5], self.priority as f64),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    // presets.toml 里读出的一项，不是规则的键返回 false
+    pub fn set(&mut self, key: &str, value: &Value) -> bool {
+        let text = || value.as_str().unwrap_or_default().to_string();
+        let num = || value.as_f64().unwrap_or_default();
+        match key {
+            "match_names" => self.names = text(),
+            "match_codecs" => self.codecs = text(),
+            "match_min_height" => self.min_height = num().clamp(0.0, u32::MAX as f64) as u32,
+            "match_max_height" => self.max_height = num().clamp(0.0, u32::MAX as f64) as u32,
+            "match_paths" => self.paths = text(),
+            "match_priority" => self.priority = num().clamp(i32::MIN as f64, i32::MAX as f64) as i32,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// 匹配这个文件的预设，胜出的在前
+pub fn candidates<'a>(presets: &'a [Preset], input: &str, info: Option<&MediaInfo>) -> Vec<&'a Preset> {
+    let mut matched: Vec<&Preset> = presets.iter().filter(|p| p.rule.check(input, info).is_ok()).collect();
+    // 稳定排序，优先级相同的保持列表顺序
+    matched.sort_by_key(|p| std::cmp::Reverse(p.rule.priority));
+    matched
+}
+
+pub fn pick<'a>(presets: &'a [Preset], input: &str, info: Option<&MediaInfo>) -> Option<&'a Preset> {
+    candidates(presets, input, info).into_iter().next()
+}
+
+pub fn any_rules(presets: &[Preset]) -> bool {
+    presets.iter().any(|p| !p.rule.is_empty())
+}
+
+// “用此文件测试规则”的结果：每个有规则的预设一行，最后说明会用哪个
+pub fn explain(presets: &[Preset], input: &str, info: Option<&MediaInfo>) -> Vec<String> {
+    let mut lines: Vec<String> = presets
+        .iter()
+        .filter(|p| !p.rule.is_empty())
+        .map(|p| match p.rule.check(input, info) {
+            Ok(()) => format!("✔ {}（优先级 {}）", p.name, p.rule.priority),
+            Err(reason) => format!("✘ {}: {}", p.name, reason),
+        })
+        .collect();
+    if lines.is_empty() {
+        return vec!["还没有预设设置了匹配规则".to_string()];
+    }
+    let matched = candidates(presets, input, info);
+    lines.push(match matched.as_slice() {
+        [] => "没有匹配的预设，保持当前设置".to_string(),
+        [only] => format!("将自动应用“{}”", only.name),
+        [first, rest @ ..] => format!(
+            "将自动应用“{}”（同时匹配的{}优先级较低或排在后面）",
+            first.name,
+            rest.iter().map(|p| format!("“{}”", p.name)).collect::<Vec<_>>().join("、")
+        ),
+    });
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::JobSettings;
+    use crate::probe::StreamInfo;
+
+    fn stream(kind: &str, codec: &str, height: Option<u32>) -> StreamInfo {
+        let mut s = StreamInfo { codec_type: kind.to_string(), codec_name: codec.to_string(), ..Default::default() };
+        if let Some(h) = height {
+            s.props.insert("height".to_string(), h.to_string());
+        }
+        s
+    }
+
+    fn media(streams: Vec<StreamInfo>) -> MediaInfo {
+        MediaInfo { duration: 60.0, streams, ..Default::default() }
+    }
+
+    fn preset(name: &str, rule: Rule) -> Preset {
+        let mut preset = Preset::capture(name, &JobSettings::default());
+        preset.rule = rule;
+        preset
+    }
+
+    fn rules() -> Vec<Preset> {
+        vec![
+            preset("电视兼容", Rule { names: "*.mkv".to_string(), ..Default::default() }),
+            preset("播客压缩", Rule { names: "wav, flac".to_string(), paths: "播客".to_string(), ..Default::default() }),
+            preset("4K 缩小", Rule { min_height: 2160, priority: 5, ..Default::default() }),
+            preset("HEVC 兼容", Rule { codecs: "hevc".to_string(), max_height: 1080, priority: 5, ..Default::default() }),
+            preset("手动", Rule::default()),
+        ]
+    }
+
+    #[test]
+    fn table_of_inputs() {
+        let presets = rules();
+        let h264_1080 = media(vec![stream("video", "h264", Some(1080)), stream("audio", "aac", None)]);
+        let hevc_720 = media(vec![stream("video", "hevc", Some(720)), stream("audio", "aac", None)]);
+        let hevc_2160 = media(vec![stream("video", "hevc", Some(2160))]);
+        let pcm = media(vec![stream("audio", "pcm_s16le", None)]);
+        let cases: Vec<(&str, Option<&MediaInfo>, Option<&str>)> = vec![
+            ("/videos/movie.mkv", Some(&h264_1080), Some("电视兼容")),
+            ("/videos/MOVIE.MKV", Some(&h264_1080), Some("电视兼容")),
+            ("/videos/movie.mp4", Some(&h264_1080), None),
+            // 优先级高的胜过排在前面的
+            ("/videos/movie.mkv", Some(&hevc_720), Some("HEVC 兼容")),
+            ("/videos/movie.mkv", Some(&hevc_2160), Some("4K 缩小")),
+            ("/videos/clip.mp4", Some(&hevc_2160), Some("4K 缩小")),
+            ("/录音/播客/第一期.wav", Some(&pcm), Some("播客压缩")),
+            ("/录音/会议/第一期.wav", Some(&pcm), None),
+            // 读不到媒体信息时只看文件名和路径
+            ("/videos/movie.mkv", None, Some("电视兼容")),
+            ("/videos/movie.mp4", None, None),
+        ];
+        for (input, info, want) in cases {
+            let got = pick(&presets, input, info).map(|p| p.name.as_str());
+            assert_eq!(got, want, "{}", input);
+        }
+    }
+
+    // 优先级相同时按列表顺序
+    #[test]
+    fn ties_keep_list_order() {
+        let a = preset("a", Rule { names: "mkv".to_string(), ..Default::default() });
+        let b = preset("b", Rule { paths: "videos".to_string(), ..Default::default() });
+        let names = |list: &[Preset]| candidates(list, "/videos/x.mkv", None).iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&[a.clone(), b.clone()]), ["a", "b"]);
+        assert_eq!(names(&[b, a]), ["b", "a"]);
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(name_matches("*.mkv", "a.mkv"));
+        assert!(name_matches("mkv", "A.MKV"));
+        assert!(!name_matches("mkv", "mkv"));
+        assert!(name_matches("clip_??.mov", "clip_01.mov"));
+        assert!(!name_matches("clip_??.mov", "clip_1.mov"));
+        assert!(name_matches("*", "任何文件"));
+        assert!(name_matches("第*集.mp4", "第12集.mp4"));
+        assert!(!name_matches("*.mkv", "a.mkv.part"));
+    }
+
+    #[test]
+    fn reasons_for_no_match() {
+        let rule = Rule { codecs: "hevc".to_string(), ..Default::default() };
+        let info = media(vec![stream("video", "h264", Some(1080)), stream("audio", "aac", None)]);
+        assert_eq!(rule.check("a.mp4", Some(&info)).unwrap_err(), "编码不是 hevc（源文件: h264、aac）");
+        assert_eq!(rule.check("a.mp4", None).unwrap_err(), "读不到媒体信息，无法判断编码");
+        let rule = Rule { min_height: 720, max_height: 1080, ..Default::default() };
+        assert!(rule.check("a.mp4", Some(&info)).is_ok());
+        assert_eq!(rule.check("a.mp3", Some(&media(vec![stream("audio", "mp3", None)]))).unwrap_err(), "没有视频或读不到分辨率");
+        // 封面图的尺寸不算视频高度
+        let mut cover = stream("video", "mjpeg", Some(600));
+        cover.props.insert("disposition.attached_pic".to_string(), "1".to_string());
+        assert!(rule.check("a.mp3", Some(&media(vec![stream("audio", "mp3", None), cover]))).is_err());
+        assert_eq!(Rule::default().check("a.mp4", Some(&info)).unwrap_err(), "没有设置规则");
+        assert!(Rule { names: " , ".to_string(), ..Default::default() }.is_empty());
+    }
+
+    #[test]
+    fn explain_lists_every_rule() {
+        let presets = rules();
+        let info = media(vec![stream("video", "hevc", Some(720))]);
+        let lines = explain(&presets, "/videos/movie.mkv", Some(&info));
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "✔ 电视兼容（优先级 0）");
+        assert_eq!(lines[1], "✘ 播客压缩: 文件名不符合 wav、flac");
+        assert_eq!(lines[2], "✘ 4K 缩小: 视频高度 720 低于 2160");
+        assert_eq!(lines[4], "将自动应用“HEVC 兼容”（同时匹配的“电视兼容”优先级较低或排在后面）");
+        assert_eq!(explain(&presets, "/videos/a.mp4", None).last().unwrap(), "没有匹配的预设，保持当前设置");
+        assert_eq!(explain(&presets[4..], "a.mp4", None), ["还没有预设设置了匹配规则"]);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let rule = Rule { names: " *.mkv ".to_string(), codecs: String::new(), min_height: 2160, max_height: 0, paths: "播客".to_string(), priority: -3 };
+        let values = rule.values();
+        assert_eq!(values.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(), ["match_names", "match_min_height", "match_paths", "match_priority"]);
+        let mut back = Rule::default();
+        for (key, value) in &values {
+            assert!(back.set(key, value));
+        }
+        assert!(back == Rule { names: "*.mkv".to_string(), ..rule });
+        assert!(!back.set("format", &Value::Str("mp4".to_string())));
+    }
+}
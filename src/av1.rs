@@ -0,0 +1,242 @@
+use crate::encoders;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    Hevc,
+    Av1,
+    Vp9,
+}
+
+impl VideoCodec {
+    pub const ALL: [VideoCodec; 4] = [VideoCodec::H264, VideoCodec::Hevc, VideoCodec::Av1, VideoCodec::Vp9];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "H.264",
+            VideoCodec::Hevc => "H.265/HEVC",
+            VideoCodec::Av1 => "AV1",
+            VideoCodec::Vp9 => "VP9",
+        }
+    }
+
+    // 任务列表和命令行里的写法
+    pub fn tag(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::Hevc => "hevc",
+            VideoCodec::Av1 => "av1",
+            VideoCodec::Vp9 => "vp9",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<VideoCodec> {
+        VideoCodec::ALL.into_iter().find(|c| c.tag() == tag)
+    }
+
+    // (编码格式, 处理设备) 对应的 ffmpeg 编码器，CPU 编码 AV1 用 soft 指定的库。
+    // 没有这种组合时返回 None，由 plan::resolve 改用 CPU 并写进日志。
+    // ffmpeg 的 vp9_qsv 只有少数 Intel 核显能用，VP9 一律用 CPU
+    pub fn encoder(self, gpu: &str, soft: SoftEncoder) -> Option<&'static str> {
+        Some(match (self, gpu) {
+            (VideoCodec::H264, "CPU") => "libx264",
+            (VideoCodec::H264, "NVIDIA") => "h264_nvenc",
+            (VideoCodec::H264, "Intel") => "h264_qsv",
+            (VideoCodec::H264, "AMD") => "h264_amf",
+            (VideoCodec::Hevc, "CPU") => "libx265",
+            (VideoCodec::Hevc, "NVIDIA") => "hevc_nvenc",
+            (VideoCodec::Hevc, "Intel") => "hevc_qsv",
+            (VideoCodec::Hevc, "AMD") => "hevc_amf",
+            (VideoCodec::Av1, "CPU") => soft.name(),
+            (VideoCodec::Av1, "NVIDIA") => "av1_nvenc",
+            (VideoCodec::Av1, "Intel") => "av1_qsv",
+            (VideoCodec::Av1, "AMD") => "av1_amf",
+            (VideoCodec::Vp9, "CPU") => "libvpx-vp9",
+            _ => return None,
+        })
+    }
+
+    // 能放进的视频容器
+    pub fn fits(self, format: &str) -> bool {
+        match self {
+            VideoCodec::H264 => true,
+            VideoCodec::Hevc | VideoCodec::Av1 => matches!(format, "mp4" | "mkv" | "mov"),
+            VideoCodec::Vp9 => matches!(format, "mp4" | "mkv"),
+        }
+    }
+}
+
+// CPU 编码 AV1 用哪个库，由 plan::resolve 按 ffmpeg 实际支持的编码器决定
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum SoftEncoder {
+    #[default]
+    Svt,
+    Aom,
+}
+
+impl SoftEncoder {
+    pub fn detect() -> Option<SoftEncoder> {
+        if encoders::available("libsvtav1") {
+            Some(SoftEncoder::Svt)
+        } else if encoders::available("libaom-av1") {
+            Some(SoftEncoder::Aom)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SoftEncoder::Svt => "libsvtav1",
+            SoftEncoder::Aom => "libaom-av1",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Tune {
+    // SVT-AV1 tune=0，主观画质优先
+    #[default]
+    Visual,
+    Psnr,
+}
+
+impl Tune {
+    pub fn label(self) -> &'static str {
+        match self {
+            Tune::Visual => "主观画质",
+            Tune::Psnr => "PSNR",
+        }
+    }
+
+    // 任务列表和预设里的写法
+    pub fn tag(self) -> &'static str {
+        match self {
+            Tune::Visual => "visual",
+            Tune::Psnr => "psnr",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Tune> {
+        [Tune::Visual, Tune::Psnr].into_iter().find(|t| t.tag() == tag)
+    }
+}
+
+#[derive(Clone)]
+pub struct Av1Settings {
+    pub encoder: SoftEncoder,
+    pub preset: u8,     // 0（最慢）～13（最快）
+    pub crf: u8,        // 0～63
+    pub film_grain: u8, // 胶片颗粒合成强度，0 关闭，最大 50
+    pub tune: Tune,
+}
+
+impl Default for Av1Settings {
+    fn default() -> Self {
+        // 默认 preset 8：libaom 和 SVT 的默认值在 1080p 上都只有个位数 fps
+        Av1Settings { encoder: SoftEncoder::Svt, preset: 8, crf: 35, film_grain: 0, tune: Tune::Visual }
+    }
+}
+
+impl Av1Settings {
+    // -svtav1-params 的值，键按固定顺序，便于对比命令
+    pub fn svtav1_params(&self, with_crf: bool) -> String {
+        let mut params = vec![format!("preset={}", self.preset.min(13))];
+        if with_crf {
+            params.push(format!("crf={}", self.crf.min(63)));
+        }
+        params.push(format!("tune={}", if self.tune == Tune::Visual { 0 } else { 1 }));
+        if self.film_grain > 0 {
+            // 合成颗粒时由编码器自己去噪，再在解码端加回
+            params.push(format!("film-grain={}", self.film_grain.min(50)));
+            params.push("film-grain-denoise=1".to_string());
+        }
+        params.join(":")
+    }
+
+    // SVT 的 0～13 映射到 libaom 的 cpu-used 0～8
+    pub fn cpu_used(&self) -> u8 {
+        (self.preset.min(13) as u32 * 8).div_ceil(13) as u8
+    }
+
+    // -c:v 之后的编码参数；多分辨率按码率控制，不带 crf
+    pub fn args(&self, with_crf: bool) -> Vec<String> {
+        let mut args = Vec::new();
+        match self.encoder {
+            SoftEncoder::Svt => {
+                args.push("-svtav1-params".to_string());
+                args.push(self.svtav1_params(with_crf));
+            }
+            SoftEncoder::Aom => {
+                args.extend(["-cpu-used".to_string(), self.cpu_used().to_string(), "-row-mt".to_string(), "1".to_string()]);
+                if with_crf {
+                    args.extend(["-crf".to_string(), self.crf.min(63).to_string(), "-b:v".to_string(), "0".to_string()]);
+                }
+                if self.film_grain > 0 {
+                    args.extend(["-denoise-noise-level".to_string(), self.film_grain.min(50).to_string()]);
+                }
+                if self.tune == Tune::Psnr {
+                    args.extend(["-tune".to_string(), "psnr".to_string()]);
+                }
+            }
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::JobSettings;
+    use crate::presets::Preset;
+
+    fn settings(preset: u8, crf: u8, film_grain: u8, tune: Tune) -> Av1Settings {
+        Av1Settings { encoder: SoftEncoder::Svt, preset, crf, film_grain, tune }
+    }
+
+    #[test]
+    fn svt_params_have_fixed_order() {
+        assert_eq!(Av1Settings::default().svtav1_params(true), "preset=8:crf=35:tune=0");
+        assert_eq!(settings(4, 28, 12, Tune::Psnr).svtav1_params(true), "preset=4:crf=28:tune=1:film-grain=12:film-grain-denoise=1");
+        // 多分辨率按码率控制时不带 crf
+        assert_eq!(settings(4, 28, 0, Tune::Visual).svtav1_params(false), "preset=4:tune=0");
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_eq!(settings(20, 99, 80, Tune::Visual).svtav1_params(true), "preset=13:crf=63:tune=0:film-grain=50:film-grain-denoise=1");
+        // 参数值里不会出现 : 或 =，不需要转义
+        let params = settings(255, 255, 255, Tune::Psnr).svtav1_params(true);
+        assert!(params.split(':').all(|kv| kv.matches('=').count() == 1), "{}", params);
+    }
+
+    #[test]
+    fn aom_maps_preset_to_cpu_used() {
+        let cpu_used: Vec<u8> = [0, 1, 6, 8, 12, 13, 20].iter().map(|p| settings(*p, 0, 0, Tune::Visual).cpu_used()).collect();
+        assert_eq!(cpu_used, [0, 1, 4, 5, 8, 8, 8]);
+        let aom = Av1Settings { encoder: SoftEncoder::Aom, ..settings(13, 30, 10, Tune::Psnr) };
+        assert_eq!(
+            aom.args(true),
+            ["-cpu-used", "8", "-row-mt", "1", "-crf", "30", "-b:v", "0", "-denoise-noise-level", "10", "-tune", "psnr"]
+        );
+        assert_eq!(aom.args(false), ["-cpu-used", "8", "-row-mt", "1", "-denoise-noise-level", "10", "-tune", "psnr"]);
+    }
+
+    #[test]
+    fn encoder_choice_per_device() {
+        assert_eq!(VideoCodec::Av1.encoder("CPU", SoftEncoder::Aom), Some("libaom-av1"));
+        assert_eq!(VideoCodec::Av1.encoder("NVIDIA", SoftEncoder::Svt), Some("av1_nvenc"));
+        assert_eq!(VideoCodec::Vp9.encoder("Intel", SoftEncoder::Svt), None);
+        assert!(!VideoCodec::Av1.fits("avi") && VideoCodec::Av1.fits("mkv"));
+    }
+
+    #[test]
+    fn presets_round_trip_av1_values() {
+        let saved = JobSettings { codec: VideoCodec::Av1, av1: settings(3, 24, 15, Tune::Psnr), ..Default::default() };
+        let mut loaded = JobSettings::default();
+        Preset::capture("av1", &saved).apply(&mut loaded).unwrap();
+        assert!(loaded.codec == VideoCodec::Av1);
+        assert_eq!(loaded.av1.svtav1_params(true), saved.av1.svtav1_params(true));
+    }
+}
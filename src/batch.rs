@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use crate::album::{self, Album};
+use crate::output;
+use crate::plan::JobSettings;
+use crate::presets::Preset;
+
+// 命令行一次传入多个文件时的转换队列：共用窗口里的设置，各自可以改目标格式，
+// 依次转换。和任务列表（joblist）不同，只在这个窗口里存在，不写文件
+
+#[derive(Clone, PartialEq)]
+pub enum ItemStatus {
+    Waiting,
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl ItemStatus {
+    pub fn label(&self) -> String {
+        match self {
+            ItemStatus::Waiting => "等待中".to_string(),
+            ItemStatus::Running => "转换中".to_string(),
+            ItemStatus::Done => "完成".to_string(),
+            ItemStatus::Failed(reason) => format!("失败：{}", reason),
+            ItemStatus::Cancelled => "已取消".to_string(),
+        }
+    }
+
+    // 已经有结果，不会再运行
+    pub fn finished(&self) -> bool {
+        matches!(self, ItemStatus::Done | ItemStatus::Failed(_) | ItemStatus::Cancelled)
+    }
+}
+
+pub struct BatchItem {
+    pub path: String,
+    pub format: String,
+    pub status: ItemStatus,
+    // 专辑面板排进来的一轨，格式由专辑的编码决定
+    pub album: Option<Album>,
+    // 排队时按匹配规则自动套用的预设，开始时叠加在窗口的设置上
+    pub preset: Option<Preset>,
+}
+
+impl BatchItem {
+    pub fn new(path: String, format: &str) -> Self {
+        BatchItem { path, format: format.to_string(), status: ItemStatus::Waiting, album: None, preset: None }
+    }
+
+    pub fn track(track: album::Track) -> Self {
+        let format = track.album.codec.ext().to_string();
+        BatchItem { path: track.input, format, status: ItemStatus::Waiting, album: Some(track.album), preset: None }
+    }
+
+    // 列表里显示的名字：按 cue 切出来的音轨显示输出名，其他显示源文件名
+    pub fn name(&self) -> String {
+        match &self.album {
+            Some(album) if !album.name.is_empty() => album.name.clone(),
+            _ => Path::new(&self.path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(self.path.clone()),
+        }
+    }
+
+    pub fn output(&self, settings: &JobSettings, dir: &str) -> String {
+        let output = match &self.album {
+            Some(album) => album::output_path(&self.path, album, dir),
+            None => output::suggested_output(&self.path, &self.format, dir),
+        };
+        output::avoid_existing(output, settings)
+    }
+}
+
+pub fn next_waiting(items: &[BatchItem]) -> Option<usize> {
+    items.iter().position(|item| item.status == ItemStatus::Waiting)
+}
+
+// 整个队列的进度：已结束的按 100% 算，正在转换的按当前进度算
+pub fn overall(items: &[BatchItem], current: f32) -> f32 {
+    if items.is_empty() {
+        return 0.0;
+    }
+    let finished = items.iter().filter(|item| item.status.finished()).count() as f32;
+    let running = if items.iter().any(|item| item.status == ItemStatus::Running) { current.clamp(0.0, 100.0) / 100.0 } else { 0.0 };
+    (finished + running) / items.len() as f32
+}
+
+// 中断后把还在等待的任务都标为已取消
+pub fn cancel_waiting(items: &mut [BatchItem]) {
+    for item in items.iter_mut().filter(|item| item.status == ItemStatus::Waiting) {
+        item.status = ItemStatus::Cancelled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_items_wait_without_a_preset() {
+        let item = BatchItem::new("/videos/第一集.mkv".to_string(), "mp4");
+        assert!(item.status == ItemStatus::Waiting && item.preset.is_none() && item.album.is_none());
+        assert_eq!(item.name(), "第一集.mkv");
+        assert_eq!(item.format, "mp4");
+    }
+
+    #[test]
+    fn overall_progress() {
+        let mut items = vec![BatchItem::new("a.mkv".to_string(), "mp4"), BatchItem::new("b.mkv".to_string(), "mp4")];
+        assert_eq!(next_waiting(&items), Some(0));
+        items[0].status = ItemStatus::Done;
+        items[1].status = ItemStatus::Running;
+        assert_eq!(next_waiting(&items), None);
+        // 当前进度是百分数
+        assert!((overall(&items, 50.0) - 0.75).abs() < 1e-6);
+        assert_eq!(overall(&[], 50.0), 0.0);
+        items.push(BatchItem::new("c.mkv".to_string(), "mp4"));
+        cancel_waiting(&mut items);
+        assert!(items[2].status == ItemStatus::Cancelled && items[1].status == ItemStatus::Running);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::inspect;
+
+// 判断转换慢在编码器还是磁盘：定时读 ffmpeg 进程读写的字节数和用掉的 CPU 时间。
+// 编码器忙不过来时 CPU 一直占满（和这个任务前面最忙时差不多）；CPU 明显闲下来、
+// 读写却还在继续，说明 ffmpeg 在等磁盘或网络。读写两边哪边更慢没法直接看出，
+// 按这段时间里搬运字节多的一侧算：高码率源转小文件时是读取，转封装时两边差不多
+
+// 按最近这么久的计数算速度
+const WINDOW: Duration = Duration::from_secs(5);
+// 样本跨度不到这么久时不判断，开头几秒读写忽快忽慢
+const MIN_SPAN: Duration = Duration::from_secs(3);
+// CPU 占用不低于本任务最高值的这个比例时算编码器在忙
+const BUSY_SHARE: f64 = 0.7;
+// 至少用满这么多个核心才有资格当最高值，一开始就卡在磁盘上的任务不会被当成编码器慢
+const MIN_PEAK_CORES: f64 = 0.5;
+// 一侧搬运的字节是另一侧的这么多倍时，只说这一侧
+const SIDE_RATIO: f64 = 3.0;
+
+// 某一时刻 ffmpeg 进程累计的读写字节数（含网络盘、管道）和 CPU 时间
+#[derive(Clone, Copy)]
+pub struct Counters {
+    pub read: u64,
+    pub written: u64,
+    pub cpu: Duration,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Verdict {
+    // 样本不够或读不到计数
+    #[default]
+    Unknown,
+    Encoder,
+    // 每秒字节数
+    Read(f64),
+    Write(f64),
+    Disk { read: f64, write: f64 },
+}
+
+fn rate(bytes_per_sec: f64) -> String {
+    format!("~{}/s", inspect::format_bytes(bytes_per_sec))
+}
+
+impl Verdict {
+    pub fn label(&self) -> Option<String> {
+        match self {
+            Verdict::Unknown => None,
+            Verdict::Encoder => Some("受限于: 编码器".to_string()),
+            Verdict::Read(r) => Some(format!("受限于: 磁盘读取 {}", rate(*r))),
+            Verdict::Write(w) => Some(format!("受限于: 磁盘写入 {}", rate(*w))),
+            Verdict::Disk { read, write } => Some(format!("受限于: 磁盘读写（读取 {}，写入 {}）", rate(*read), rate(*write))),
+        }
+    }
+}
+
+// 一次 ffmpeg 调用期间的样本
+#[derive(Default)]
+pub struct Monitor {
+    samples: VecDeque<(Instant, Counters)>,
+    // 本次调用里 CPU 占用的最高值（核心数）
+    peak_cores: f64,
+}
+
+impl Monitor {
+    pub fn push(&mut self, at: Instant, counters: Counters) {
+        // 计数不会倒退，倒退了说明换了进程，重新开始
+        if self.samples.back().is_some_and(|(_, last)| counters.read < last.read || counters.written < last.written) {
+            *self = Monitor::default();
+        }
+        self.samples.push_back((at, counters));
+        while self.samples.len() > 2 && self.samples.get(1).is_some_and(|(t, _)| at.duration_since(*t) >= WINDOW) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn verdict(&mut self) -> Verdict {
+        let (Some(&(t0, first)), Some(&(t1, last))) = (self.samples.front(), self.samples.back()) else {
+            return Verdict::Unknown;
+        };
+        let span = t1.duration_since(t0);
+        if span < MIN_SPAN {
+            return Verdict::Unknown;
+        }
+        let secs = span.as_secs_f64();
+        let read = last.read.saturating_sub(first.read) as f64 / secs;
+        let write = last.written.saturating_sub(first.written) as f64 / secs;
+        let cores = last.cpu.saturating_sub(first.cpu).as_secs_f64() / secs;
+        self.peak_cores = self.peak_cores.max(cores);
+        if cores >= BUSY_SHARE * self.peak_cores.max(MIN_PEAK_CORES) {
+            Verdict::Encoder
+        } else if read >= SIDE_RATIO * write {
+            Verdict::Read(read)
+        } else if write >= SIDE_RATIO * read {
+            Verdict::Write(write)
+        } else {
+            Verdict::Disk { read, write }
+        }
+    }
+
+    // 受限于读取时剩余时间按读取速度算：磁盘的速度比编码速度稳定。
+    // size 是输入文件的大小，只在整个文件从头读到尾时有意义
+    pub fn read_eta(&self, verdict: Verdict, size: u64) -> Option<f64> {
+        let Verdict::Read(rate) = verdict else { return None };
+        let read = self.samples.back()?.1.read;
+        (rate > 0.0 && read < size).then(|| (size - read) as f64 / rate)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn sample(pid: u32) -> Option<Counters> {
+    // rchar/wchar 是读写调用的字节数，网络盘上的读写也算在内（read_bytes 只算本地块设备）
+    let io = std::fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    let field = |name: &str| io.lines().find_map(|l| l.strip_prefix(name)?.strip_prefix(':')?.trim().parse::<u64>().ok());
+    let (read, written) = (field("rchar")?, field("wchar")?);
+    // 和 budget.rs 一样从右括号之后数，第 14、15 项是用户态和内核态时间，每秒 100 个滴答
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(Counters { read, written, cpu: Duration::from_millis((utime + stime) * 10) })
+}
+
+#[cfg(target_os = "windows")]
+pub fn sample(pid: u32) -> Option<Counters> {
+    use winapi::shared::minwindef::FILETIME;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::{GetProcessTimes, OpenProcess};
+    use winapi::um::winbase::GetProcessIoCounters;
+    use winapi::um::winnt::{IO_COUNTERS, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if handle.is_null() {
+        return None;
+    }
+    let mut io: IO_COUNTERS = unsafe { std::mem::zeroed() };
+    let zero = || FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+    let (mut created, mut exited, mut kernel, mut user) = (zero(), zero(), zero(), zero());
+    let ok = unsafe {
+        GetProcessIoCounters(handle, &mut io) != 0 && GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user) != 0
+    };
+    unsafe { CloseHandle(handle) };
+    if !ok {
+        return None;
+    }
+    // 单位是 100 纳秒
+    let ticks = |t: FILETIME| ((t.dwHighDateTime as u64) << 32) | t.dwLowDateTime as u64;
+    Some(Counters {
+        read: io.ReadTransferCount,
+        written: io.WriteTransferCount,
+        cpu: Duration::from_nanos((ticks(kernel) + ticks(user)) * 100),
+    })
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn sample(_pid: u32) -> Option<Counters> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1_000_000;
+
+    // 按秒推进的假进程：每秒读写多少字节、用掉多少个核心
+    struct Feed {
+        start: Instant,
+        secs: u64,
+        counters: Counters,
+    }
+
+    impl Feed {
+        fn new(monitor: &mut Monitor) -> Feed {
+            let feed = Feed { start: Instant::now(), secs: 0, counters: Counters { read: 0, written: 0, cpu: Duration::ZERO } };
+            monitor.push(feed.start, feed.counters);
+            feed
+        }
+
+        fn run(&mut self, monitor: &mut Monitor, secs: u64, read: u64, written: u64, cores: f64) {
+            for _ in 0..secs {
+                self.secs += 1;
+                self.counters.read += read;
+                self.counters.written += written;
+                self.counters.cpu += Duration::from_secs_f64(cores);
+                monitor.push(self.start + Duration::from_secs(self.secs), self.counters);
+            }
+        }
+    }
+
+    #[test]
+    fn needs_a_few_seconds_of_samples() {
+        let mut m = Monitor::default();
+        assert!(m.verdict() == Verdict::Unknown);
+        let mut feed = Feed::new(&mut m);
+        feed.run(&mut m, 2, 100 * MB, MB, 0.1);
+        assert!(m.verdict() == Verdict::Unknown);
+        feed.run(&mut m, 1, 100 * MB, MB, 0.1);
+        assert!(m.verdict() == Verdict::Read(100e6));
+    }
+
+    #[test]
+    fn busy_cpu_means_the_encoder() {
+        let mut m = Monitor::default();
+        let mut feed = Feed::new(&mut m);
+        feed.run(&mut m, 5, 10 * MB, MB, 4.0);
+        assert!(m.verdict() == Verdict::Encoder);
+        // 降到最高值的七成以下、读取还在继续时算磁盘
+        feed.run(&mut m, 5, 10 * MB, MB, 2.0);
+        assert!(m.verdict() == Verdict::Read(10e6));
+        // 七成以上仍然算编码器
+        feed.run(&mut m, 5, 10 * MB, MB, 3.0);
+        assert!(m.verdict() == Verdict::Encoder);
+    }
+
+    #[test]
+    fn a_job_stuck_on_disk_from_the_start_is_not_the_encoder() {
+        // 一开始就只用了 0.2 个核心，最高值按 0.5 个核心算
+        let mut m = Monitor::default();
+        let mut feed = Feed::new(&mut m);
+        feed.run(&mut m, 5, MB, 5 * MB, 0.2);
+        assert!(m.verdict() == Verdict::Write(5e6));
+        // 用到 0.35 个核心（0.5 的七成）就算编码器在忙
+        feed.run(&mut m, 5, MB, 5 * MB, 0.4);
+        assert!(m.verdict() == Verdict::Encoder);
+    }
+
+    #[test]
+    fn the_busier_side_is_named() {
+        let verdict = |read: u64, written: u64| {
+            let mut m = Monitor::default();
+            Feed::new(&mut m).run(&mut m, 5, read, written, 0.0);
+            m.verdict()
+        };
+        assert!(verdict(30 * MB, 10 * MB) == Verdict::Read(30e6));
+        assert!(verdict(10 * MB, 30 * MB) == Verdict::Write(30e6));
+        assert!(verdict(20 * MB, 10 * MB) == Verdict::Disk { read: 20e6, write: 10e6 });
+        assert_eq!(verdict(20 * MB, 10 * MB).label().unwrap(), "受限于: 磁盘读写（读取 ~19.1 MB/s，写入 ~9.5 MB/s）");
+    }
+
+    #[test]
+    fn old_samples_leave_the_window() {
+        let mut m = Monitor::default();
+        let mut feed = Feed::new(&mut m);
+        feed.run(&mut m, 20, MB, 0, 0.0);
+        let (first, last) = (m.samples.front().unwrap().0, m.samples.back().unwrap().0);
+        assert_eq!(last.duration_since(first), WINDOW);
+    }
+
+    #[test]
+    fn counters_going_back_start_over() {
+        let mut m = Monitor::default();
+        let mut feed = Feed::new(&mut m);
+        feed.run(&mut m, 5, 10 * MB, MB, 4.0);
+        assert!(m.verdict() == Verdict::Encoder);
+        // 下一次调用的新进程：计数从头开始，之前的最高值也不算
+        let mut next = Feed::new(&mut m);
+        assert_eq!(m.samples.len(), 1);
+        assert_eq!(m.peak_cores, 0.0);
+        assert!(m.verdict() == Verdict::Unknown);
+        next.run(&mut m, 5, 10 * MB, MB, 1.0);
+        assert!(m.verdict() == Verdict::Encoder);
+    }
+
+    #[test]
+    fn read_eta_from_the_read_speed() {
+        let mut m = Monitor::default();
+        let mut feed = Feed::new(&mut m);
+        feed.run(&mut m, 5, 10 * MB, 0, 0.0);
+        let verdict = m.verdict();
+        // 已读 50 MB，还剩 150 MB，每秒 10 MB
+        assert_eq!(m.read_eta(verdict, 200 * MB), Some(15.0));
+        // 读到的比文件还多（读了不止一遍）时没法估算
+        assert_eq!(m.read_eta(verdict, 50 * MB), None);
+        assert_eq!(m.read_eta(verdict, 10 * MB), None);
+        // 不是受限于读取时不按读取速度算
+        assert_eq!(m.read_eta(Verdict::Encoder, 200 * MB), None);
+        assert_eq!(m.read_eta(Verdict::Read(0.0), 200 * MB), None);
+    }
+}
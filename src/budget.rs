@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+// 节能界面：配置差的电脑上界面本身会和 ffmpeg 抢 CPU。
+// 开启后转换期间每秒只刷新 4 次，不生成缩略图和波形，暂停统计和其他实例的轮询，日志展开才显示。
+// 用到这些功能的地方都问 UiBudget，不各自读设置
+const REPAINT_EVERY: Duration = Duration::from_millis(250);
+// 多久取一次本进程的 CPU 时间
+const SAMPLE_EVERY: Duration = Duration::from_secs(2);
+// 转换期间界面进程平均占用超过一个核的这么多（百分比）时建议开启
+const SUGGEST_ABOVE: f32 = 15.0;
+// 至少观察这么久再下结论，刚开始的几帧（加载字体、纹理）不算
+const SUGGEST_AFTER: Duration = Duration::from_secs(10);
+
+pub struct UiBudget {
+    enabled: bool,
+    // 这次转换开始时的时刻和本进程的 CPU 时间
+    encode_start: Option<(Instant, Duration)>,
+    last_sample: Option<Instant>,
+    // 界面进程占用 CPU 高、建议开启；用户关掉提示后不再出现
+    suggest: Option<f32>,
+    dismissed: bool,
+}
+
+impl Default for UiBudget {
+    fn default() -> Self {
+        UiBudget { enabled: config::current().low_power, encode_start: None, last_sample: None, suggest: None, dismissed: false }
+    }
+}
+
+impl UiBudget {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) -> std::io::Result<()> {
+        self.enabled = enabled;
+        self.suggest = None;
+        config::save(&config::Config { low_power: enabled, ..config::current() })
+    }
+
+    // 转换期间下一帧至少隔多久；None 表示照常刷新
+    pub fn repaint_after(&self, running: bool) -> Option<Duration> {
+        (self.enabled && running).then_some(REPAINT_EVERY)
+    }
+
+    // 缩略图、波形这类预览图
+    pub fn previews(&self) -> bool {
+        !self.enabled
+    }
+
+    // 统计、其他实例这类定时读文件的面板
+    pub fn live_polling(&self, running: bool) -> bool {
+        !(self.enabled && running)
+    }
+
+    // 日志是否默认展开显示
+    pub fn log_open(&self, running: bool) -> bool {
+        !(self.enabled && running)
+    }
+
+    pub fn suggestion(&self) -> Option<f32> {
+        self.suggest.filter(|_| !self.enabled && !self.dismissed)
+    }
+
+    pub fn dismiss(&mut self) {
+        self.dismissed = true;
+    }
+
+    // 每帧调用。转换结束时返回这次转换期间界面进程的 CPU 占用，写进日志
+    pub fn tick(&mut self, running: bool) -> Option<String> {
+        let now = Instant::now();
+        match (running, self.encode_start) {
+            (true, None) => {
+                self.encode_start = process_cpu_time().map(|cpu| (now, cpu));
+                self.last_sample = Some(now);
+                None
+            }
+            (true, Some((start, cpu))) => {
+                if !self.enabled
+                    && self.last_sample.is_none_or(|t| now - t >= SAMPLE_EVERY)
+                    && now - start >= SUGGEST_AFTER
+                {
+                    self.last_sample = Some(now);
+                    let percent = usage(start, cpu, now)?;
+                    self.suggest = (percent > SUGGEST_ABOVE).then_some(percent);
+                }
+                None
+            }
+            (false, Some((start, cpu))) => {
+                self.encode_start = None;
+                let percent = usage(start, cpu, now)?;
+                let mode = if self.enabled { "已开启节能界面" } else { "未开启节能界面" };
+                Some(format!("转换期间界面进程平均占用 CPU {:.1}%（单核，{}）", percent, mode))
+            }
+            (false, None) => None,
+        }
+    }
+}
+
+fn usage(start: Instant, cpu: Duration, now: Instant) -> Option<f32> {
+    let wall = (now - start).as_secs_f64();
+    let used = process_cpu_time()?.checked_sub(cpu)?.as_secs_f64();
+    (wall > 0.0).then(|| (used / wall * 100.0) as f32)
+}
+
+// 本进程（不含 ffmpeg 子进程）用掉的用户态加内核态 CPU 时间
+#[cfg(target_os = "windows")]
+fn process_cpu_time() -> Option<Duration> {
+    use winapi::shared::minwindef::FILETIME;
+    use winapi::um::processthreadsapi::{GetCurrentProcess, GetProcessTimes};
+
+    let zero = || FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+    let (mut created, mut exited, mut kernel, mut user) = (zero(), zero(), zero(), zero());
+    let ok = unsafe { GetProcessTimes(GetCurrentProcess(), &mut created, &mut exited, &mut kernel, &mut user) };
+    if ok == 0 {
+        return None;
+    }
+    // 单位是 100 纳秒
+    let ticks = |t: FILETIME| ((t.dwHighDateTime as u64) << 32) | t.dwLowDateTime as u64;
+    Some(Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+}
+
+// /proc/self/stat 的第 14、15 项，单位是时钟滴答，Linux 上固定每秒 100 次
+#[cfg(target_os = "linux")]
+fn process_cpu_time() -> Option<Duration> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // 第 2 项是括号里的进程名，可能含空格，从右括号之后数
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(Duration::from_millis((utime + stime) * 10))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
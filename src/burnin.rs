@@ -0,0 +1,234 @@
+use crate::interlace::Deinterlace;
+use crate::plan::{self, JobSettings};
+use crate::probe::MediaInfo;
+use crate::retime::{self, Rate};
+use crate::subtitle;
+use crate::timecode::{self, Timecode};
+
+// 审片用的副本：用 drawtext 把时间或帧号烧进画面。
+// 按源文件时间时放在缩放之后、变速之前，显示的是这一帧在源文件里的位置；
+// 按输出时间时放在整条滤镜链最后，变速、补帧之后从 0 开始数
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Content {
+    // HH:MM:SS.mmm，或从源时间码开始的 SMPTE 时间码
+    #[default]
+    Time,
+    Frame,
+}
+
+impl Content {
+    pub const ALL: [Content; 2] = [Content::Time, Content::Frame];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Content::Time => "时间",
+            Content::Frame => "帧号",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Content::Time => "time",
+            Content::Frame => "frame",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Clock {
+    #[default]
+    Source,
+    Output,
+}
+
+impl Clock {
+    pub const ALL: [Clock; 2] = [Clock::Source, Clock::Output];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Clock::Source => "源文件时间",
+            Clock::Output => "输出时间",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Clock::Source => "source",
+            Clock::Output => "output",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Corner {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomCenter,
+    BottomRight,
+}
+
+impl Corner {
+    pub const ALL: [Corner; 6] = [
+        Corner::TopLeft, Corner::TopCenter, Corner::TopRight,
+        Corner::BottomLeft, Corner::BottomCenter, Corner::BottomRight,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Corner::TopLeft => "左上",
+            Corner::TopCenter => "上方居中",
+            Corner::TopRight => "右上",
+            Corner::BottomLeft => "左下",
+            Corner::BottomCenter => "下方居中",
+            Corner::BottomRight => "右下",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Corner::TopLeft => "top-left",
+            Corner::TopCenter => "top",
+            Corner::TopRight => "top-right",
+            Corner::BottomLeft => "bottom-left",
+            Corner::BottomCenter => "bottom",
+            Corner::BottomRight => "bottom-right",
+        }
+    }
+
+    // 离边缘留出一个字高的一半
+    fn position(self) -> (&'static str, &'static str) {
+        let x = match self {
+            Corner::TopLeft | Corner::BottomLeft => "th/2",
+            Corner::TopCenter | Corner::BottomCenter => "(w-tw)/2",
+            Corner::TopRight | Corner::BottomRight => "w-tw-th/2",
+        };
+        let y = match self {
+            Corner::TopLeft | Corner::TopCenter | Corner::TopRight => "th/2",
+            _ => "h-th-th/2",
+        };
+        (x, y)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct BurnIn {
+    pub content: Content,
+    pub clock: Clock,
+    pub corner: Corner,
+    pub font_size: u32,
+    // 文字下面垫半透明黑底，亮的画面上也看得清
+    pub boxed: bool,
+    // 按源文件时间显示时，从源文件的起始时间码开始数
+    pub source_timecode: bool,
+    // 字体文件，空时 Windows 用 Consolas，其他系统交给 fontconfig
+    pub font_file: String,
+    // 这段输出从源文件的第几秒开始，取样预览每段不同，由生成命令的地方填写；裁剪的开始时间另外加上
+    pub start_secs: f64,
+}
+
+impl Default for BurnIn {
+    fn default() -> Self {
+        BurnIn {
+            content: Content::Time,
+            clock: Clock::Source,
+            corner: Corner::BottomCenter,
+            font_size: 48,
+            boxed: true,
+            source_timecode: true,
+            font_file: String::new(),
+            start_secs: 0.0,
+        }
+    }
+}
+
+impl BurnIn {
+    pub fn label(&self) -> String {
+        let boxed = if self.boxed { "，黑底" } else { "" };
+        format!("{}（{}），{}，{} 号{}", self.content.label(), self.clock.label(), self.corner.label(), self.font_size, boxed)
+    }
+}
+
+const WINDOWS_FONT: &str = "C:/Windows/Fonts/consola.ttf";
+
+// 一键方案、多分辨率和截图自己组织滤镜，直接复制流时没有画面可画
+pub fn active(settings: &JobSettings) -> Option<&BurnIn> {
+    settings.burn_in.as_ref().filter(|_| plan::own_size(settings) && settings.snapshot.is_none() && !settings.remux)
+}
+
+// 滤镜所在位置的帧率：按源文件时间时在变速之前，帧率重映射还是原来的帧率
+fn frame_rate(settings: &JobSettings, info: &MediaInfo, clock: Clock) -> Option<Rate> {
+    if let Some(retime) = settings.retime {
+        return Some(if clock == Clock::Source { retime.from } else { retime.to });
+    }
+    if let Some(rate) = plan::output_fps_override(settings) {
+        return Some(rate);
+    }
+    let rate = retime::source_rate(info)?;
+    if settings.deinterlace == Deinterlace::Ivtc { Rate::new(rate.num * 4, rate.den * 5) } else { Some(rate) }
+}
+
+// 输出第一帧在源文件里的位置
+fn source_start(burn: &BurnIn, settings: &JobSettings) -> f64 {
+    burn.start_secs + settings.trim_start.map_or(0.0, |d| d.as_secs_f64())
+}
+
+// 真正用来起算的源时间码，已按这段输出的起点往后推
+fn start_timecode(burn: &BurnIn, info: &MediaInfo, rate: Rate, start: f64) -> Option<Timecode> {
+    if burn.content != Content::Time || burn.clock != Clock::Source || !burn.source_timecode {
+        return None;
+    }
+    let tc = timecode::from_info(info).filter(|tc| tc.fits_rate(rate.as_f64()))?;
+    Some(tc.add_frames((start * rate.as_f64()).round() as u64, rate.as_f64()))
+}
+
+// 选项值放在单引号里，滤镜链层面不拆；里面的冒号是 drawtext 自己的分隔符，要再转义一次
+pub fn filter(burn: &BurnIn, settings: &JobSettings, info: &MediaInfo) -> String {
+    let rate = frame_rate(settings, info, burn.clock);
+    let offset = if burn.clock == Clock::Source { source_start(burn, settings) } else { 0.0 };
+    let mut parts = Vec::new();
+    match (burn.content, rate.and_then(|r| Some((r, start_timecode(burn, info, r, offset)?)))) {
+        (Content::Time, Some((rate, tc))) => {
+            parts.push(format!("timecode='{}'", tc.to_string().replace(':', "\\:")));
+            parts.push(format!("rate={}", rate.tag()));
+        }
+        (Content::Time, None) if offset > 0.0 => parts.push(format!("text='%{{pts\\:hms\\:{:.3}}}'", offset)),
+        (Content::Time, None) => parts.push("text='%{pts\\:hms}'".to_string()),
+        (Content::Frame, _) => {
+            let first = rate.map_or(0, |r| (offset * r.as_f64()).round() as u64);
+            parts.push(if first > 0 { format!("text='%{{eif\\:n+{}\\:d}}'", first) } else { "text='%{n}'".to_string() });
+        }
+    }
+    let font = if burn.font_file.trim().is_empty() && cfg!(target_os = "windows") { WINDOWS_FONT } else { burn.font_file.trim() };
+    if !font.is_empty() {
+        parts.insert(0, format!("fontfile={}", subtitle::escape_filter_path(font)));
+    }
+    let (x, y) = burn.corner.position();
+    parts.push(format!("x={}:y={}", x, y));
+    parts.push(format!("fontsize={}:fontcolor=white", burn.font_size.max(8)));
+    if burn.boxed {
+        parts.push("box=1:boxcolor=black@0.6:boxborderw=8".to_string());
+    }
+    format!("drawtext={}", parts.join(":"))
+}
+
+// 开始前的说明：实际显示什么、从哪里开始
+pub fn note(burn: &BurnIn, settings: &JobSettings, info: &MediaInfo) -> String {
+    if active(settings).is_none() {
+        return "烧录时间码: 一键方案、多分辨率、截图和仅转换封装不支持，已忽略".to_string();
+    }
+    let what = burn.content.label();
+    let rate = frame_rate(settings, info, burn.clock);
+    match (burn.clock, rate.and_then(|r| start_timecode(burn, info, r, source_start(burn, settings)))) {
+        (Clock::Source, Some(tc)) => format!("烧录时间码: 源文件时间码，从 {} 开始", tc),
+        (Clock::Source, None) if burn.content == Content::Time && burn.source_timecode && timecode::from_info(info).is_some() => {
+            "烧录时间码: 源时间码和帧率不符，改为显示源文件时间".to_string()
+        }
+        (Clock::Source, None) => format!("烧录时间码: 源文件{}，{}", what, burn.corner.label()),
+        (Clock::Output, _) if settings.retime.is_some() => format!("烧录时间码: 输出{}，按变速后的时间从 0 开始", what),
+        (Clock::Output, _) => format!("烧录时间码: 输出{}，从 0 开始", what),
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// 分层的取消标记。一次转换持有一个根标记，编码、源文件校验、探测等各阶段各拿一个子标记：
+// 取消整个任务时所有阶段一起停下，只取消校验不影响编码。
+// 每次开始新任务都换一个新标记，不再复位共用的标记，上一个任务迟到的取消请求碰不到新任务
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    node: Arc<Node>,
+}
+
+#[derive(Default)]
+struct Node {
+    cancelled: AtomicBool,
+    parent: Option<Arc<Node>>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    // 父标记取消时子标记也算取消；取消子标记不影响父标记和其他子标记
+    pub fn child(&self) -> CancelToken {
+        CancelToken { node: Arc::new(Node { cancelled: AtomicBool::new(false), parent: Some(self.node.clone()) }) }
+    }
+
+    pub fn cancel(&self) {
+        self.node.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    // 沿着父标记往上查，层数只有两三层
+    pub fn is_cancelled(&self) -> bool {
+        let mut node = Some(&self.node);
+        while let Some(n) = node {
+            if n.cancelled.load(Ordering::SeqCst) {
+                return true;
+            }
+            node = n.parent.as_ref();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_cancels_children() {
+        let job = CancelToken::new();
+        let encode = job.child();
+        let verify = encode.child();
+        assert!(!job.is_cancelled() && !encode.is_cancelled() && !verify.is_cancelled());
+        job.cancel();
+        assert!(encode.is_cancelled() && verify.is_cancelled());
+    }
+
+    #[test]
+    fn child_does_not_cancel_parent_or_siblings() {
+        let job = CancelToken::new();
+        let encode = job.child();
+        let verify = job.child();
+        verify.cancel();
+        assert!(verify.is_cancelled());
+        assert!(!job.is_cancelled() && !encode.is_cancelled());
+        // 取消后再建的子标记一开始就是取消的
+        assert!(verify.child().is_cancelled());
+    }
+
+    #[test]
+    fn clones_share_state_and_new_tokens_are_fresh() {
+        let job = CancelToken::new();
+        let copy = job.clone();
+        copy.cancel();
+        assert!(job.is_cancelled());
+        // 上一个任务的取消碰不到新任务
+        assert!(!CancelToken::new().is_cancelled());
+    }
+
+    #[test]
+    fn cancel_from_another_thread() {
+        let job = CancelToken::new();
+        let encode = job.child();
+        std::thread::spawn(move || job.cancel()).join().unwrap();
+        assert!(encode.is_cancelled());
+    }
+}
@@ -0,0 +1,421 @@
+use std::path::Path;
+
+use crate::aspect;
+use crate::av1::VideoCodec;
+use crate::live;
+use crate::output;
+use crate::plan::{self, JobSettings};
+use crate::presets::{self, Preset};
+use crate::probe;
+use crate::resolution::Resolution;
+use crate::retime::Rate;
+use crate::timestamp;
+use crate::web::Platform;
+use crate::process;
+
+const USAGE: &str = "用法:
+  ffui                        打开右键菜单设置
+  ffui <文件>...              打开转换界面，多个文件依次转换
+  ffui --stdin-input --input-format 格式
+                              转换从管道送来的数据（如 采集程序 | ffui --stdin-input --input-format mpegts），
+                              输入也可以是命名管道 \\\\.\\pipe\\名字，同样要指定格式
+  ffui --inspect <文件>       查看媒体信息
+  ffui --share <文件>         一键转成可发送到聊天/邮件的视频
+  ffui --loudness <文件夹>    测量文件夹里所有音视频文件的响度，不转换
+  ffui --print-cmd [--preset 名称] [--format 格式] [--gpu 设备] [--codec h264|hevc|av1|vp9] [--incremental] [--remux] [--input-format 格式]
+                  [--resolution 1080p|宽x高] [--fps 30|23.976] [--start 时间] [--end 时间]
+                  [--web wechat|whatsapp|discord|email]
+                  [--aspect 宽:高 [--blur-fill]] <文件>
+                              只打印将要执行的 ffmpeg 命令；先套用预设（保存的，或内置的 web-wechat 等），
+                              其他参数再覆盖预设里的值
+  ffui --selftest             用测试片源检查各编码器能否正常工作
+  ffui --queue <任务列表.json> --no-gui
+                              不打开界面，依次转换任务列表里的文件
+  ffui --diagnose <记录编号>  为一次转换生成诊断包（zip），编号是转换记录里的结束时间
+  ffui --help | --version";
+
+pub enum Mode {
+    Setup,
+    // (输入，可以有多个, 强制的输入格式，空表示自动)
+    Convert(Vec<String>, String),
+    Inspect(String),
+    Share(String),
+    Loudness(String),
+    PrintCmd,
+    SelfTest,
+    Queue(String),
+    // 转换记录的编号
+    Diagnose(String),
+    // 已输出帮助/版本/错误，直接以该退出码结束
+    Exit(i32),
+}
+
+// 解析命令行（不含程序名）。以 - 开头的未知参数报错，-- 之后一律当作路径
+pub fn parse(args: &[String]) -> Mode {
+    if args.iter().any(|a| a == "--print-cmd") {
+        return Mode::PrintCmd;
+    }
+    let mut verb = None;
+    let mut queue = None;
+    let mut no_gui = false;
+    let mut input_format = None;
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                emit(USAGE, false);
+                return Mode::Exit(0);
+            }
+            "-V" | "--version" => {
+                emit(&version(), false);
+                return Mode::Exit(0);
+            }
+            "--selftest" => return Mode::SelfTest,
+            "--diagnose" => match iter.next() {
+                Some(id) => return Mode::Diagnose(id.clone()),
+                None => return usage_error("--diagnose 需要一个转换记录编号"),
+            },
+            "--no-gui" => no_gui = true,
+            "--queue" => match iter.next() {
+                Some(list) => queue = Some(list.clone()),
+                None => return usage_error("--queue 需要一个任务列表文件"),
+            },
+            "--inspect" | "--share" | "--loudness" => verb = Some(arg.as_str()),
+            "--stdin-input" => paths.push("-".to_string()),
+            "--input-format" => match iter.next() {
+                Some(format) => input_format = Some(format.clone()),
+                None => return usage_error("--input-format 需要一个格式名，如 mpegts"),
+            },
+            "--" => paths.extend(iter.by_ref().cloned()),
+            flag if flag.starts_with('-') && flag != "-" => return usage_error(&format!("未知参数: {}", flag)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if let Some(list) = queue {
+        // 任务列表目前只能在命令行里运行，界面里可以导入后逐个载入
+        if !no_gui || !paths.is_empty() || verb.is_some() {
+            return usage_error("--queue 需要和 --no-gui 一起使用，且不能再指定文件");
+        }
+        return Mode::Queue(list);
+    }
+    if no_gui {
+        return usage_error("--no-gui 只能和 --queue 一起使用");
+    }
+    // 多个文件只用于转换界面的队列；实时输入只能读一次，不能和其他文件排队
+    if paths.len() > 1 && (verb.is_some() || paths.iter().any(|p| live::is_live(p))) {
+        return usage_error("只有转换界面可以指定多个文件，且不能包含标准输入或命名管道");
+    }
+    if paths.len() > 1 {
+        return Mode::Convert(paths, input_format.unwrap_or_default());
+    }
+    let input_format = input_format.unwrap_or_default();
+    if let Some(path) = paths.last()
+        && live::is_live(path)
+        && (verb.is_some() || input_format.is_empty())
+    {
+        return usage_error("从标准输入或命名管道读取时只能打开转换界面，并且需要 --input-format");
+    }
+    match (paths.pop(), verb) {
+        (Some(_), Some(_)) if !input_format.is_empty() => usage_error("--input-format 只能用于转换界面"),
+        (Some(path), Some("--inspect")) => Mode::Inspect(path),
+        (Some(path), Some("--loudness")) => Mode::Loudness(path),
+        (Some(path), Some(_)) => Mode::Share(path),
+        (Some(path), None) => Mode::Convert(vec![path], input_format),
+        (None, Some(verb)) => usage_error(&format!("{} 需要一个文件", verb)),
+        (None, None) => Mode::Setup,
+    }
+}
+
+fn usage_error(message: &str) -> Mode {
+    emit(&format!("{}\n\n{}", message, USAGE), true);
+    Mode::Exit(2)
+}
+
+fn version() -> String {
+    let ffmpeg = process::command("ffmpeg")
+        .arg("-version")
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8_lossy(&o.stdout).lines().next().map(|l| l.to_string()))
+        .unwrap_or("未找到 ffmpeg".to_string());
+    format!("ffui {}\n{}", env!("CARGO_PKG_VERSION"), ffmpeg)
+}
+
+// windows_subsystem = "windows" 时没有控制台：标准输出已被重定向就直接用，
+// 否则从终端启动时借用父进程的控制台。双击启动没有可借的控制台，返回 false
+pub fn attach_console() -> bool {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+        use winapi::um::processenv::GetStdHandle;
+        use winapi::um::winbase::STD_OUTPUT_HANDLE;
+        use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        if !handle.is_null() && handle != INVALID_HANDLE_VALUE {
+            return true;
+        }
+        AttachConsole(ATTACH_PARENT_PROCESS) != 0
+    }
+    #[cfg(not(target_os = "windows"))]
+    { true }
+}
+
+// 有控制台就打印，否则弹出消息框，保证帮助和错误总能被看到
+fn emit(text: &str, error: bool) {
+    if !attach_console() {
+        message_box(text, error);
+    } else if error {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn message_box(text: &str, error: bool) {
+    use winapi::um::winuser::{MessageBoxW, MB_ICONERROR, MB_ICONINFORMATION, MB_OK};
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let icon = if error { MB_ICONERROR } else { MB_ICONINFORMATION };
+    unsafe {
+        MessageBoxW(std::ptr::null_mut(), wide(text).as_ptr(), wide("FFUI").as_ptr(), MB_OK | icon);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn message_box(text: &str, _error: bool) {
+    eprintln!("{}", text);
+}
+
+// 按名称找内置或保存的预设，找不到时列出有哪些
+fn find_preset<'a>(list: &'a [Preset], name: &str) -> Result<&'a Preset, String> {
+    list.iter().find(|p| p.name == name).ok_or_else(|| {
+        if list.is_empty() {
+            format!("没有名为“{}”的预设，还没有保存过任何预设", name)
+        } else {
+            let names: Vec<&str> = list.iter().map(|p| p.name.as_str()).collect();
+            format!("没有名为“{}”的预设，可用的预设: {}", name, names.join("，"))
+        }
+    })
+}
+
+// ffui --print-cmd [--preset 名称] [--format mp4] [--gpu CPU] [--codec h264|hevc|av1|vp9] [--incremental] [--remux] [--input-format mpegts] [--resolution 720p] [--fps 30] [--start 1:30] [--end 2:00] [--aspect 16:9 [--blur-fill]] [--web wechat] input
+// 按真实转换的流程生成命令并打印，不运行 ffmpeg
+pub fn print_cmd(args: &[String]) -> i32 {
+    let mut settings = JobSettings::default();
+    // 预设先套用，命令行上其他参数写的值优先
+    if let Some(at) = args.iter().position(|a| a == "--preset") {
+        let Some(name) = args.get(at + 1) else {
+            eprintln!("--preset 需要一个参数");
+            return 2;
+        };
+        let result = find_preset(&presets::all(&presets::load()), name).and_then(|preset| preset.apply(&mut settings));
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            return 2;
+        }
+    }
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--print-cmd" => {}
+            "--preset" => {
+                iter.next();
+            }
+            "--incremental" => settings.incremental = true,
+            "--remux" => settings.remux = true,
+            "--blur-fill" => settings.fit.fill = aspect::Fill::Blur,
+            "--stdin-input" => input = Some("-".to_string()),
+            "--format" | "--gpu" | "--aspect" | "--codec" | "--web" | "--input-format" | "--resolution" | "--fps" | "--start" | "--end" => {
+                let Some(value) = iter.next() else {
+                    eprintln!("{} 需要一个参数", arg);
+                    return 2;
+                };
+                if arg == "--aspect" {
+                    let Some((w, h)) = value.split_once(':').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?))) else {
+                        eprintln!("--aspect 需要形如 16:9 的参数");
+                        return 2;
+                    };
+                    settings.fit.target = aspect::AspectTarget::Custom;
+                    settings.fit.custom = (w, h);
+                } else if arg == "--web" {
+                    let Some(platform) = Platform::from_tag(value) else {
+                        eprintln!("--web 只支持 wechat / whatsapp / discord / email");
+                        return 2;
+                    };
+                    settings.web = Some(platform);
+                } else if arg == "--codec" {
+                    let Some(codec) = VideoCodec::from_tag(&value.to_ascii_lowercase()) else {
+                        eprintln!("--codec 只支持 h264 / hevc / av1 / vp9");
+                        return 2;
+                    };
+                    settings.codec = codec;
+                } else if arg == "--resolution" {
+                    let Some(resolution) = Resolution::from_tag(&value.to_ascii_lowercase()) else {
+                        eprintln!("--resolution 需要形如 1080p 或 1280x720 的参数");
+                        return 2;
+                    };
+                    settings.resolution = resolution;
+                } else if arg == "--fps" {
+                    let Some(rate) = Rate::parse(value) else {
+                        eprintln!("--fps 需要形如 30、23.976 或 24000/1001 的参数");
+                        return 2;
+                    };
+                    settings.fps = Some(rate);
+                } else if arg == "--start" || arg == "--end" {
+                    let time = match timestamp::parse(value) {
+                        Ok(time) => time,
+                        Err(e) => {
+                            eprintln!("{}: {}", arg, e);
+                            return 2;
+                        }
+                    };
+                    if arg == "--start" {
+                        settings.trim_start = Some(time);
+                    } else {
+                        settings.trim_end = Some(time);
+                    }
+                } else if arg == "--format" {
+                    settings.format = value.clone();
+                } else if arg == "--input-format" {
+                    settings.input_format = value.clone();
+                } else {
+                    settings.gpu = value.clone();
+                }
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("未知参数: {}\n\n{}", flag, USAGE);
+                return 2;
+            }
+            _ => input = Some(arg.clone()),
+        }
+    }
+    let Some(input) = input else {
+        eprintln!("缺少输入文件\n\n{}", USAGE);
+        return 2;
+    };
+
+    let output = match settings.web {
+        Some(platform) => output::web_output(&input, platform),
+        None => output::avoid_existing(output::default_output(&input, &settings.format), &settings),
+    };
+    if settings.incremental && output::is_up_to_date(Path::new(&input), Path::new(&output)) {
+        eprintln!("已跳过：{} 比源文件新", output);
+        return 0;
+    }
+
+    if let Err(e) = live::check(&settings, &input) {
+        eprintln!("{}", e);
+        return 2;
+    }
+    // 实时输入不探测，按没有时长处理
+    let info = match probe::probe(&input, settings.probe_depth) {
+        Ok(info) => info,
+        Err(_) if live::is_live(&input) => Default::default(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    for note in plan::resolve(&mut settings, &input, &info) {
+        eprintln!("{}", note);
+    }
+    if let Err(e) = settings.resolution.check()
+        .and_then(|_| plan::check_trim(&settings, &info))
+        .and_then(|_| plan::check_extra_args(&settings))
+        .and_then(|_| plan::check_audio(&settings, &info))
+    {
+        eprintln!("{}", e);
+        return 2;
+    }
+    // 只打印命令，中间文件的路径指向系统临时目录
+    let job = plan::plan_job(&settings, &info, &input, &output, &std::env::temp_dir());
+    if let Err(e) = live::check_plan(&input, &job) {
+        eprintln!("{}", e);
+        return 2;
+    }
+
+    for note in &job.notes {
+        eprintln!("{}", note);
+    }
+    for (i, args) in job.runs.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("ffmpeg");
+        for arg in args.argv() {
+            println!("{}", arg);
+        }
+        println!();
+        println!("{}", plan::quote_command("ffmpeg", &args.argv()));
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_is_found_and_applied() {
+        let saved = JobSettings { format: "mkv".to_string(), gpu: "NVIDIA".to_string(), ..Default::default() };
+        let list = vec![Preset::capture("电视兼容", &JobSettings::default()), Preset::capture("archive", &saved)];
+        let mut settings = JobSettings::default();
+        find_preset(&list, "archive").unwrap().apply(&mut settings).unwrap();
+        assert_eq!(settings.format, "mkv");
+        assert_eq!(settings.gpu, "NVIDIA");
+    }
+
+    #[test]
+    fn unknown_preset_lists_names() {
+        let list = vec![Preset::capture("电视兼容", &JobSettings::default()), Preset::capture("archive", &JobSettings::default())];
+        let e = find_preset(&list, "phone").err().unwrap();
+        assert!(e.contains("phone") && e.contains("电视兼容，archive"), "{}", e);
+        assert!(find_preset(&[], "phone").is_err());
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn bare_paths_open_converter() {
+        assert!(matches!(parse(&[]), Mode::Setup));
+        assert!(matches!(parse(&args(&["a.mkv"])), Mode::Convert(p, f) if p == ["a.mkv"] && f.is_empty()));
+        assert!(matches!(parse(&args(&["a.mkv", "b.mkv"])), Mode::Convert(p, _) if p.len() == 2));
+        // -- 之后以 - 开头的也是文件名
+        assert!(matches!(parse(&args(&["--", "--help"])), Mode::Convert(p, _) if p == ["--help"]));
+    }
+
+    #[test]
+    fn flags_select_modes() {
+        assert!(matches!(parse(&args(&["--inspect", "a.mkv"])), Mode::Inspect(p) if p == "a.mkv"));
+        assert!(matches!(parse(&args(&["--share", "a.mkv"])), Mode::Share(_)));
+        assert!(matches!(parse(&args(&["--loudness", "dir"])), Mode::Loudness(_)));
+        assert!(matches!(parse(&args(&["--print-cmd", "--bogus"])), Mode::PrintCmd));
+        assert!(matches!(parse(&args(&["--selftest"])), Mode::SelfTest));
+        assert!(matches!(parse(&args(&["--diagnose", "42"])), Mode::Diagnose(id) if id == "42"));
+        assert!(matches!(parse(&args(&["--queue", "q.json", "--no-gui"])), Mode::Queue(q) if q == "q.json"));
+        assert!(matches!(parse(&args(&["--stdin-input", "--input-format", "mpegts"])), Mode::Convert(p, f) if p == ["-"] && f == "mpegts"));
+        assert!(matches!(parse(&args(&["--help", "--bogus"])), Mode::Exit(0)));
+    }
+
+    #[test]
+    fn flag_errors_exit_with_2() {
+        for bad in [
+            &["--bogus"][..],
+            &["--diagnose"],
+            &["--queue", "q.json"],
+            &["--no-gui"],
+            &["--inspect"],
+            &["--inspect", "a.mkv", "b.mkv"],
+            &["--stdin-input"],
+            &["--stdin-input", "b.mkv", "--input-format", "mpegts"],
+            &["--inspect", "a.mkv", "--input-format", "mpegts"],
+        ] {
+            assert!(matches!(parse(&args(bad)), Mode::Exit(2)), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::aspect::{AspectTarget, Fill};
+use crate::plan::JobSettings;
+
+fn yes_no(v: &bool) -> String {
+    if *v { "是" } else { "否" }.to_string()
+}
+
+// 列出可以比较和合并的设置项。解构 JobSettings 时不带 ..，
+// 以后新增字段忘了加到这里会直接编译失败
+macro_rules! fields {
+    (
+        compare { $($field:ident: $label:literal => $show:expr,)* }
+        skip { $($skip:ident,)* }
+    ) => {
+        // (名称, 显示的值)
+        pub fn values(s: &JobSettings) -> Vec<(&'static str, String)> {
+            let JobSettings { $($field,)* $($skip: _,)* } = s;
+            vec![$(($label, ($show)($field)),)*]
+        }
+
+        // 把 src 里名为 label 的设置项复制到 dst
+        pub fn copy(dst: &mut JobSettings, src: &JobSettings, label: &str) {
+            match label {
+                $($label => dst.$field = src.$field.clone(),)*
+                _ => {}
+            }
+        }
+    };
+}
+
+fields! {
+    compare {
+        format: "目标格式" => |v: &String| v.clone(),
+        gpu: "处理设备" => |v: &String| v.clone(),
+        codec: "视频编码" => |v: &crate::av1::VideoCodec| v.label().to_string(),
+        av1: "AV1 参数" => |v: &crate::av1::Av1Settings| format!(
+            "速度 {}，CRF {}，胶片颗粒 {}，{}", v.preset, v.crf, v.film_grain, v.tune.label()
+        ),
+        audio_codec: "音频编码" => |v: &crate::plan::AudioCodec| v.label().to_string(),
+        audio_bitrate_k: "音频码率" => |v: &u32| format!("{}k", v),
+        loudnorm: "响度标准化" => |v: &Option<f64>| v.map_or("不调整".to_string(), |t| format!("{:.1} LUFS", t)),
+        quality: "画质" => |v: &crate::quality::Quality| v.label(),
+        two_pass: "两遍编码" => yes_no,
+        preset: "速度档位" => |v: &Option<String>| v.clone().unwrap_or("默认".to_string()),
+        tune: "调优" => |v: &Option<String>| v.clone().unwrap_or("默认".to_string()),
+        remux: "仅转换封装" => yes_no,
+        keep_all_audio: "保留所有音轨" => yes_no,
+        audio_tracks: "音轨处理" => |v: &Vec<crate::plan::TrackChoice>| {
+            if v.is_empty() { "自动".to_string() } else { v.iter().map(|c| c.label()).collect::<Vec<_>>().join("，") }
+        },
+        subtitle_file: "烧录字幕" => |v: &String| if v.is_empty() { "无".to_string() } else { v.clone() },
+        subtitle_encoding: "字幕编码" => |v: &Option<String>| v.clone().unwrap_or("自动".to_string()),
+        overwrite: "覆盖已存在的输出" => yes_no,
+        incremental: "只转换比输出新的文件" => yes_no,
+        ladder_enabled: "多分辨率" => yes_no,
+        ladder: "多分辨率档位" => |v: &Vec<crate::ladder::Rung>| {
+            v.iter().map(|r| format!("{}p {}k", r.height, r.bitrate_k)).collect::<Vec<_>>().join("，")
+        },
+        ladder_hls: "HLS 主播放列表" => yes_no,
+        cover_file: "封面另存为 cover.jpg" => yes_no,
+        data_loss: "数据丢失处理" => |v: &Vec<(crate::dataloss::Kind, crate::dataloss::Choice)>| {
+            if v.is_empty() { "默认".to_string() } else { v.iter().map(|(k, c)| format!("{}: {}", k.label(), k.choice_label(*c))).collect::<Vec<_>>().join("，") }
+        },
+        gop: "关键帧间隔" => |v: &crate::gop::Gop| v.label(),
+        length_policy: "音视频时长不一致" => |v: &crate::lengths::LengthPolicy| v.label().to_string(),
+        write_limit_mb: "限制写入速度" => |v: &f64| if *v > 0.0 { format!("{} MB/s", v) } else { "不限".to_string() },
+        extra_args: "附加参数" => |v: &String| if v.trim().is_empty() { "无".to_string() } else { v.clone() },
+        ffmpeg: "ffmpeg 版本" => |v: &String| if v.is_empty() { "默认".to_string() } else { v.clone() },
+        retime: "帧率重映射" => |v: &Option<crate::retime::Retime>| v.map(|r| r.label()).unwrap_or("无".to_string()),
+        deinterlace: "反交错" => |v: &crate::interlace::Deinterlace| v.label().to_string(),
+        resolution: "分辨率" => |v: &crate::resolution::Resolution| v.label(),
+        trim_start: "开始时间" => |v: &Option<std::time::Duration>| v.map(crate::timestamp::format).unwrap_or("开头".to_string()),
+        trim_end: "结束时间" => |v: &Option<std::time::Duration>| v.map(crate::timestamp::format).unwrap_or("结尾".to_string()),
+        burn_in: "烧录时间码" => |v: &Option<crate::burnin::BurnIn>| v.as_ref().map(|b| b.label()).unwrap_or("无".to_string()),
+        fps: "帧率" => |v: &Option<crate::retime::Rate>| v.map(|r| format!("{} fps", r.label())).unwrap_or("保持原始".to_string()),
+        fit: "目标宽高比" => |v: &crate::aspect::Fit| match v.target {
+            AspectTarget::Off => v.target.label().to_string(),
+            target => {
+                let ratio = v.ratio().map(|(w, h)| format!("{}:{}", w, h)).unwrap_or(target.label().to_string());
+                match v.fill {
+                    Fill::Color => format!("{}，{} #{:02X}{:02X}{:02X}", ratio, v.fill.label(), v.color[0], v.color[1], v.color[2]),
+                    Fill::Blur => format!("{}，{}", ratio, v.fill.label()),
+                }
+            }
+        },
+        input_format: "输入格式" => |v: &String| if v.is_empty() { "自动".to_string() } else { v.clone() },
+        probe_depth: "分析时长/探测大小" => |v: &crate::probe::ProbeDepth| format!("{} 秒 / {} MB", v.analyze_secs, v.probesize_mb),
+        sar_mode: "非方形像素" => |v: &crate::aspect::SarMode| v.label().to_string(),
+        filter_order: "滤镜顺序" => |v: &Vec<crate::filterchain::FilterStep>| {
+            v.iter().map(|s| s.label()).collect::<Vec<_>>().join(" → ")
+        },
+        hash_source: "记录源文件 SHA-256" => yes_no,
+        hash_embed: "校验值写入注释" => yes_no,
+        hook_success: "成功后运行" => |v: &String| if v.is_empty() { "无".to_string() } else { v.clone() },
+        hook_failure: "失败后运行" => |v: &String| if v.is_empty() { "无".to_string() } else { v.clone() },
+        hook_timeout_secs: "命令超时" => |v: &u64| format!("{} 秒", v),
+        keep_dates: "保留拍摄日期" => yes_no,
+        fixes: "修正参数" => |v: &Vec<String>| if v.is_empty() { "无".to_string() } else { v.join("，") },
+    }
+    // 每次任务临时决定或由 resolve 填写，不参与比较
+    skip {
+        pixel_filter,
+        display_aspect,
+        hw_scale,
+        creation_time,
+        web,
+        snapshot,
+        speech,
+        album,
+        quick,
+        preview_secs,
+        preview_samples,
+    }
+}
+
+// 只返回不同的项：(名称, a 的值, b 的值)
+pub fn diff(a: &JobSettings, b: &JobSettings) -> Vec<(&'static str, String, String)> {
+    values(a)
+        .into_iter()
+        .zip(values(b))
+        .filter(|((_, x), (_, y))| x != y)
+        .map(|((label, x), (_, y))| (label, x, y))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::av1::VideoCodec;
+    use crate::presets::Preset;
+
+    fn changed() -> JobSettings {
+        JobSettings {
+            format: "mkv".to_string(),
+            codec: VideoCodec::Hevc,
+            two_pass: true,
+            audio_bitrate_k: 320,
+            loudnorm: Some(-16.0),
+            extra_args: "-tag:v hvc1".to_string(),
+            subtitle_file: "a.srt".to_string(),
+            keep_dates: true,
+            fixes: vec!["async".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn labels_are_unique_and_in_a_fixed_order() {
+        let labels: Vec<&str> = values(&JobSettings::default()).into_iter().map(|(l, _)| l).collect();
+        let mut unique = labels.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), labels.len());
+        let other: Vec<&str> = values(&changed()).into_iter().map(|(l, _)| l).collect();
+        assert_eq!(labels, other);
+    }
+
+    #[test]
+    fn diff_lists_only_changed_fields() {
+        let a = JobSettings::default();
+        let b = changed();
+        assert!(diff(&a, &a.clone()).is_empty());
+        let labels: Vec<&str> = diff(&a, &b).into_iter().map(|(l, _, _)| l).collect();
+        assert_eq!(labels, ["目标格式", "视频编码", "音频码率", "响度标准化", "两遍编码", "烧录字幕", "附加参数", "保留拍摄日期", "修正参数"]);
+        let (_, x, y) = diff(&a, &b).into_iter().find(|(l, _, _)| *l == "响度标准化").unwrap();
+        assert_eq!((x.as_str(), y.as_str()), ("不调整", "-16.0 LUFS"));
+    }
+
+    // 每一项都能单独复制过去，复制后这一项不再不同，其他项不受影响
+    #[test]
+    fn copy_round_trips_each_field() {
+        let b = changed();
+        let rows = diff(&JobSettings::default(), &b);
+        for (i, (label, _, _)) in rows.iter().enumerate() {
+            let mut a = JobSettings::default();
+            copy(&mut a, &b, label);
+            let left: Vec<&str> = diff(&a, &b).into_iter().map(|(l, _, _)| l).collect();
+            let expected: Vec<&str> = rows.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, (l, _, _))| *l).collect();
+            assert_eq!(left, expected, "{}", label);
+        }
+        let mut a = JobSettings::default();
+        for (label, _, _) in &rows {
+            copy(&mut a, &b, label);
+        }
+        assert!(diff(&a, &b).is_empty());
+        // 认不出的名称不做任何事
+        copy(&mut a, &JobSettings::default(), "不存在的设置");
+        assert!(diff(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn presets_compare_and_merge() {
+        let small = Preset::capture("小体积H265", &JobSettings { codec: VideoCodec::Hevc, audio_bitrate_k: 96, ..Default::default() });
+        let tv = Preset::capture("电视兼容", &JobSettings { format: "mkv".to_string(), ..Default::default() });
+        let (a, b) = (small.settings().unwrap(), tv.settings().unwrap());
+        let labels: Vec<&str> = diff(&a, &b).into_iter().map(|(l, _, _)| l).collect();
+        assert_eq!(labels, ["目标格式", "视频编码", "音频码率"]);
+        // 只把视频编码合并到“电视兼容”
+        let mut merged = b.clone();
+        copy(&mut merged, &a, "视频编码");
+        let saved = Preset::capture(&tv.name, &merged).settings().unwrap();
+        let labels: Vec<&str> = diff(&a, &saved).into_iter().map(|(l, _, _)| l).collect();
+        assert_eq!(labels, ["目标格式", "音频码率"]);
+    }
+}
@@ -0,0 +1,548 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use eframe::egui;
+
+use crate::paths;
+
+// 用户的基本设置，首次运行向导写入，一行一个 key=value
+#[derive(Clone, PartialEq)]
+pub struct Config {
+    // 已经走完首次运行向导
+    pub onboarded: bool,
+    // ffmpeg/ffprobe 所在目录，空表示自动查找（PATH、ffui 程序旁边）
+    pub ffmpeg_dir: String,
+    // 另外装的几个 ffmpeg 版本，任务和预设可以按名字指定用哪个
+    pub ffmpeg_builds: Vec<Build>,
+    pub theme: Theme,
+    // 输出目录，空表示和源文件放在一起
+    pub output_dir: String,
+    // 新任务默认的目标格式和处理设备
+    pub format: String,
+    pub gpu: String,
+    // 中间文件放在哪里，空表示系统临时目录
+    pub scratch_dir: String,
+    // 任务列表表格显示的列，逗号分隔，空表示全部显示
+    pub queue_columns: String,
+    // 开始前确认里选了“不再提示”的类型，逗号分隔
+    pub confirm_skip: String,
+    // 节能界面：转换期间降低刷新率、暂停预览和轮询
+    pub low_power: bool,
+    // 无界面运行任务列表时同时转换几个文件，1..=max_parallel()
+    pub parallel_jobs: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            onboarded: false,
+            ffmpeg_dir: String::new(),
+            ffmpeg_builds: Vec::new(),
+            theme: Theme::Dark,
+            output_dir: String::new(),
+            format: "mp4".to_string(),
+            gpu: "CPU".to_string(),
+            scratch_dir: String::new(),
+            queue_columns: String::new(),
+            confirm_skip: String::new(),
+            low_power: false,
+            parallel_jobs: 1,
+        }
+    }
+}
+
+// 一个命名的 ffmpeg 版本：名字和 ffmpeg/ffprobe 所在目录
+#[derive(Clone, PartialEq)]
+pub struct Build {
+    pub name: String,
+    pub dir: String,
+}
+
+// 存成一行：名字=目录;名字=目录。名字里不能有 = 和 ;
+fn parse_builds(text: &str) -> Vec<Build> {
+    text.split(';')
+        .filter_map(|item| item.split_once('='))
+        .map(|(name, dir)| Build { name: name.trim().to_string(), dir: dir.trim().to_string() })
+        .filter(|b| !b.name.is_empty())
+        .collect()
+}
+
+fn format_builds(builds: &[Build]) -> String {
+    builds.iter().map(|b| format!("{}={}", b.name, b.dir)).collect::<Vec<_>>().join(";")
+}
+
+// 同时转换的任务数上限：CPU 的逻辑核数
+pub fn max_parallel() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// 手改的设置文件、换到核数更少的电脑上时收回到有效范围
+pub fn clamp_parallel(n: usize) -> usize {
+    n.clamp(1, max_parallel())
+}
+
+// 界面上输入的名字去掉分隔符
+pub fn build_name(text: &str) -> String {
+    text.replace(['=', ';'], "").trim().to_string()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Dark => "深色",
+            Theme::Light => "浅色",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+
+    pub fn apply(self, ctx: &egui::Context) {
+        ctx.set_visuals(match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        });
+    }
+}
+
+// 第一行写格式版本，旧版本写的文件没有这一行
+const HEADER: &str = "# ffui-config ";
+pub const VERSION: u32 = 1;
+
+pub fn config_path() -> PathBuf {
+    paths::store_dir().join("config.txt")
+}
+
+// 上一份能完整读出的设置，每次保存前更新
+fn backup_path() -> PathBuf {
+    paths::store_dir().join("config.txt.bak")
+}
+
+// 读不出的文件在第一次保存前改存到这里，不会被新设置覆盖
+fn broken_path() -> PathBuf {
+    paths::store_dir().join("config.txt.broken")
+}
+
+// 认不出的行和值忽略，用默认值
+fn parse(text: &str) -> Config {
+    let mut c = Config::default();
+    for (key, value) in text.lines().filter_map(|l| l.split_once('=')) {
+        let value = value.trim().to_string();
+        match key.trim() {
+            "onboarded" => c.onboarded = value == "1",
+            "ffmpeg_dir" => c.ffmpeg_dir = value,
+            "ffmpeg_builds" => c.ffmpeg_builds = parse_builds(&value),
+            "theme" => c.theme = if value == "light" { Theme::Light } else { Theme::Dark },
+            "output_dir" => c.output_dir = value,
+            "format" if !value.is_empty() => c.format = value,
+            "gpu" if !value.is_empty() => c.gpu = value,
+            "scratch_dir" => c.scratch_dir = value,
+            "queue_columns" => c.queue_columns = value,
+            "confirm_skip" => c.confirm_skip = value,
+            "low_power" => c.low_power = value == "1",
+            "parallel_jobs" => c.parallel_jobs = clamp_parallel(value.parse().unwrap_or(1)),
+            _ => {}
+        }
+    }
+    c
+}
+
+// 严格检查：保存时断电会留下写了一半的文件（最后一行不完整、全是 NUL），
+// 手工编辑可能写重复；更新版本的文件可能有这里不认识的含义
+fn parse_strict(text: &str) -> Result<Config, String> {
+    if !text.is_empty() && !text.ends_with('\n') {
+        return Err("文件不完整，最后一行没有写完".to_string());
+    }
+    let mut seen: Vec<&str> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if let Some(version) = line.strip_prefix(HEADER) {
+            match version.trim().parse::<u32>() {
+                Ok(v) if v <= VERSION => continue,
+                Ok(v) => return Err(format!("来自更新版本的 ffui（格式 {}）", v)),
+                Err(_) => return Err(format!("第 {} 行的版本号无法识别", n + 1)),
+            }
+        }
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, _)) = line.split_once('=') else {
+            return Err(format!("第 {} 行无法识别", n + 1));
+        };
+        if seen.contains(&key.trim()) {
+            return Err(format!("第 {} 行重复设置了 {}", n + 1, key.trim()));
+        }
+        seen.push(key.trim());
+    }
+    Ok(parse(text))
+}
+
+fn format(c: &Config) -> String {
+    format!(
+        "{}{}\nonboarded={}\nffmpeg_dir={}\nffmpeg_builds={}\ntheme={}\noutput_dir={}\nformat={}\ngpu={}\nscratch_dir={}\nqueue_columns={}\nconfirm_skip={}\nlow_power={}\nparallel_jobs={}\n",
+        HEADER,
+        VERSION,
+        if c.onboarded { 1 } else { 0 },
+        c.ffmpeg_dir,
+        format_builds(&c.ffmpeg_builds),
+        c.theme.tag(),
+        c.output_dir,
+        c.format,
+        c.gpu,
+        c.scratch_dir,
+        c.queue_columns,
+        c.confirm_skip,
+        if c.low_power { 1 } else { 0 },
+        c.parallel_jobs,
+    )
+}
+
+// 当前设置按文件格式写出的文本，诊断包里附带
+pub fn snapshot() -> String {
+    format(&current())
+}
+
+// 读设置文件时发现的问题，界面上提示用户处理
+fn problem_cell() -> &'static Mutex<Option<String>> {
+    static PROBLEM: Mutex<Option<String>> = Mutex::new(None);
+    &PROBLEM
+}
+
+// 文件坏了也不清空：先用能读出的部分，等用户选择恢复备份或重置
+fn load() -> Config {
+    let (c, problem) = load_from(&config_path());
+    *problem_cell().lock().unwrap() = problem;
+    c
+}
+
+// (能读出的设置, 发现的问题)。没有文件不算问题
+fn load_from(path: &Path) -> (Config, Option<String>) {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return (Config::default(), None),
+        Err(e) => return (Config::default(), Some(format!("无法读取: {}", e))),
+    };
+    let text = String::from_utf8_lossy(&bytes);
+    let checked = if matches!(text, std::borrow::Cow::Owned(_)) {
+        Err("文件里有无法识别的字节".to_string())
+    } else {
+        parse_strict(&text)
+    };
+    match checked {
+        Ok(c) => (c, None),
+        Err(e) => (parse(&text), Some(e)),
+    }
+}
+
+fn cell() -> &'static RwLock<Config> {
+    static CONFIG: OnceLock<RwLock<Config>> = OnceLock::new();
+    CONFIG.get_or_init(|| RwLock::new(load()))
+}
+
+// 第一次用到时读文件，之后读内存里的副本
+pub fn current() -> Config {
+    cell().read().unwrap().clone()
+}
+
+// 设置文件读出来有问题时的说明
+pub fn problem() -> Option<String> {
+    cell();
+    problem_cell().lock().unwrap().clone()
+}
+
+pub fn has_backup() -> bool {
+    backup_path().is_file()
+}
+
+// 先写临时文件（config.txt.tmp）、刷到磁盘再改名，断电时要么是旧文件要么是新文件
+fn write_atomic(path: &Path, text: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = File::create(&tmp)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+pub fn save(c: &Config) -> io::Result<()> {
+    fs::create_dir_all(paths::store_dir())?;
+    let broken = problem().is_some().then(broken_path);
+    store(&config_path(), &backup_path(), broken.as_deref(), &format(c))?;
+    *cell().write().unwrap() = c.clone();
+    *problem_cell().lock().unwrap() = None;
+    Ok(())
+}
+
+// 写入 path。旧文件读坏了时另存到 broken，否则能完整读出的旧文件留作备份
+fn store(path: &Path, backup: &Path, broken: Option<&Path>, text: &str) -> io::Result<()> {
+    store_with(path, backup, broken, text, |old| parse_strict(old).is_ok())
+}
+
+// 同上，旧文件是否完整由 valid 判断。预设等其他文件也这样保存
+pub(crate) fn store_with(path: &Path, backup: &Path, broken: Option<&Path>, text: &str, valid: impl Fn(&str) -> bool) -> io::Result<()> {
+    match broken {
+        Some(broken) if path.exists() => {
+            fs::copy(path, broken)?;
+        }
+        Some(_) => {}
+        None if fs::read_to_string(path).is_ok_and(|text| valid(&text)) => {
+            fs::copy(path, backup)?;
+        }
+        None => {}
+    }
+    write_atomic(path, text)
+}
+
+fn load_backup(backup: &Path) -> Result<Config, String> {
+    let text = fs::read_to_string(backup).map_err(|e| format!("无法读取备份: {}", e))?;
+    parse_strict(&text).map_err(|e| format!("备份也无法读取: {}", e))
+}
+
+// 用备份覆盖读坏的设置，坏文件另存一份
+pub fn restore_backup() -> Result<(), String> {
+    let c = load_backup(&backup_path())?;
+    save(&c).map_err(|e| format!("无法写入设置: {}", e))
+}
+
+// 坏文件另存一份后写入默认设置
+pub fn reset() -> Result<(), String> {
+    save(&Config::default()).map_err(|e| format!("无法写入设置: {}", e))
+}
+
+fn exe_name(name: &str) -> String {
+    if cfg!(target_os = "windows") { format!("{}.exe", name) } else { name.to_string() }
+}
+
+// 指定了目录时只看那里；否则先找 PATH（返回原名，交给系统查找），再找 ffui 程序旁边。都没有时返回 None
+pub fn locate(dir: &str, name: &str) -> Option<PathBuf> {
+    let file = exe_name(name);
+    let dir = dir.trim();
+    if !dir.is_empty() {
+        let path = Path::new(dir).join(file);
+        return path.is_file().then_some(path);
+    }
+    let on_path = std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|d| d.join(&file).is_file()));
+    if on_path {
+        return Some(PathBuf::from(name));
+    }
+    paths::exe_dir().map(|d| d.join(file)).filter(|p| p.is_file())
+}
+
+// 找不到时返回原名或指定目录里的路径，运行时的报错里能看出找的是哪里
+pub fn tool_in(dir: &str, name: &str) -> PathBuf {
+    locate(dir, name).unwrap_or_else(|| match dir.trim() {
+        "" => PathBuf::from(name),
+        dir => Path::new(dir).join(exe_name(name)),
+    })
+}
+
+// ffmpeg 和 ffprobe 有一个找不到时说明在哪里找过
+pub fn missing_tools() -> Option<String> {
+    let dir = current().ffmpeg_dir;
+    let missing: Vec<&str> = ["ffmpeg", "ffprobe"].into_iter().filter(|name| locate(&dir, name).is_none()).collect();
+    if missing.is_empty() {
+        return None;
+    }
+    let place = if dir.trim().is_empty() { "PATH 和 ffui 程序所在的文件夹".to_string() } else { dir.trim().to_string() };
+    Some(format!("在 {} 里找不到 {}，无法转换", place, missing.join(" 和 ")))
+}
+
+// 任务指定的 ffmpeg 版本所在目录；列表里没有这个名字时返回 None
+pub fn build_dir(name: &str) -> Option<String> {
+    current().ffmpeg_builds.into_iter().find(|b| b.name == name).map(|b| b.dir)
+}
+
+// ffmpeg/ffprobe 的完整路径
+pub fn tool_path(name: &str) -> PathBuf {
+    tool_in(&current().ffmpeg_dir, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ffui_config_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn strict_err(text: &str) -> String {
+        parse_strict(text).err().expect("应该被拒绝")
+    }
+
+    fn sample() -> Config {
+        Config {
+            onboarded: true,
+            ffmpeg_dir: "C:\\tools\\ffmpeg\\bin".to_string(),
+            ffmpeg_builds: vec![
+                Build { name: "6.1".to_string(), dir: "D:\\ffmpeg-6.1".to_string() },
+                Build { name: "nightly".to_string(), dir: "/opt/ffmpeg".to_string() },
+            ],
+            theme: Theme::Light,
+            output_dir: "D:\\输出".to_string(),
+            format: "mkv".to_string(),
+            gpu: "NVIDIA".to_string(),
+            scratch_dir: String::new(),
+            queue_columns: "name,progress".to_string(),
+            confirm_skip: "overwrite".to_string(),
+            low_power: true,
+            parallel_jobs: 1,
+        }
+    }
+
+    #[test]
+    fn format_round_trips() {
+        let text = format(&sample());
+        assert!(text.starts_with("# ffui-config 1\n"));
+        assert!(parse_strict(&text) == Ok(sample()));
+        assert!(parse_strict(&text.replace('\n', "\r\n")) == Ok(sample()));
+        assert!(parse_strict("") == Ok(Config::default()));
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let text = format(&sample());
+        let cut = &text[..text.len() - 3];
+        assert!(strict_err(cut).contains("文件不完整"));
+        // 断电后常见的全是 NUL 的文件
+        assert!(strict_err("\0\0\0\0").contains("文件不完整"));
+        // 宽松解析仍然取出写完的部分
+        let partial = parse(cut);
+        assert!(partial.onboarded);
+        assert_eq!(partial.format, "mkv");
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        let err = strict_err("format=mp4\ngpu=CPU\n format = mkv\n");
+        assert_eq!(err, "第 3 行重复设置了 format");
+    }
+
+    #[test]
+    fn version_header() {
+        assert!(parse_strict("# ffui-config 0\nformat=mkv\n").is_ok());
+        assert!(parse_strict("# ffui-config 1\nformat=mkv\n").is_ok());
+        assert_eq!(strict_err("# ffui-config 2\nformat=mkv\n"), "来自更新版本的 ffui（格式 2）");
+        assert_eq!(strict_err("# ffui-config x\n"), "第 1 行的版本号无法识别");
+        // 其他注释照常跳过
+        assert!(parse_strict("# 手写的注释\n\nformat=mkv\n").is_ok());
+        assert_eq!(strict_err("format=mkv\n乱码\n"), "第 2 行无法识别");
+    }
+
+    #[test]
+    fn lenient_parse_keeps_defaults() {
+        let c = parse("format=\ngpu=  \nunknown=1\ntheme=purple\n");
+        assert_eq!(c.format, "mp4");
+        assert_eq!(c.gpu, "CPU");
+        assert!(c.theme == Theme::Dark);
+    }
+
+    #[test]
+    fn parallel_jobs_are_clamped() {
+        assert_eq!(parse("").parallel_jobs, 1);
+        assert_eq!(parse("parallel_jobs=0\n").parallel_jobs, 1);
+        assert_eq!(parse("parallel_jobs=x\n").parallel_jobs, 1);
+        assert_eq!(parse("parallel_jobs=100000\n").parallel_jobs, max_parallel());
+        assert_eq!(parse(&format!("parallel_jobs={}\n", max_parallel())).parallel_jobs, max_parallel());
+        assert_eq!(clamp_parallel(0), 1);
+        assert_eq!(clamp_parallel(usize::MAX), max_parallel());
+    }
+
+    #[test]
+    fn builds() {
+        assert!(parse_builds("a=/x; =/y;broken;b = /z ") == vec![
+            Build { name: "a".to_string(), dir: "/x".to_string() },
+            Build { name: "b".to_string(), dir: "/z".to_string() },
+        ]);
+        assert_eq!(build_name(" 6.1=new;x "), "6.1newx");
+    }
+
+    #[test]
+    fn load_reports_problems() {
+        let dir = scratch("load");
+        let path = dir.join("config.txt");
+        let (c, problem) = load_from(&path);
+        assert!(c == Config::default() && problem.is_none());
+
+        fs::write(&path, format(&sample())).unwrap();
+        let (c, problem) = load_from(&path);
+        assert!(c == sample() && problem.is_none());
+
+        fs::write(&path, b"format=mkv\ngpu=\xff\xfe\n").unwrap();
+        let (c, problem) = load_from(&path);
+        assert_eq!(problem.as_deref(), Some("文件里有无法识别的字节"));
+        assert_eq!(c.format, "mkv");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn store_with_other_files() {
+        let dir = scratch("store_with");
+        let (path, backup) = (dir.join("presets.toml"), dir.join("presets.toml.bak"));
+        let valid = |text: &str| text.starts_with("ok");
+        store_with(&path, &backup, None, "ok 1", valid).unwrap();
+        store_with(&path, &backup, None, "bad", valid).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "ok 1");
+        // valid 不认可的旧文件不留作备份
+        store_with(&path, &backup, None, "ok 2", valid).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "ok 1");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ok 2");
+        // 临时文件名在完整的文件名后面加 .tmp，不换掉扩展名
+        assert!(!dir.join("presets.toml.tmp").exists() && !dir.join("presets.txt.tmp").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn backup_fallback() {
+        let dir = scratch("backup");
+        let (path, backup, broken) = (dir.join("config.txt"), dir.join("config.txt.bak"), dir.join("config.txt.broken"));
+
+        // 第一次保存没有旧文件，不留备份
+        let first = Config { onboarded: true, ..Config::default() };
+        store(&path, &backup, None, &format(&first)).unwrap();
+        assert!(!backup.exists());
+
+        // 再保存时上一份完整的文件成了备份
+        store(&path, &backup, None, &format(&sample())).unwrap();
+        assert!(load_backup(&backup) == Ok(first.clone()));
+        assert!(load_from(&path).0 == sample());
+        assert!(!dir.join("config.txt.tmp").exists());
+
+        // 写了一半的文件不会覆盖好的备份
+        fs::write(&path, "format=mkv\ngpu=NV").unwrap();
+        let (_, problem) = load_from(&path);
+        assert!(problem.is_some());
+        store(&path, &backup, None, &format(&Config::default())).unwrap();
+        assert!(load_backup(&backup) == Ok(first.clone()));
+
+        // 读坏时保存：坏文件另存，备份不动，恢复得到备份里的设置
+        fs::write(&path, "format=mkv\ngpu=NV").unwrap();
+        store(&path, &backup, Some(&broken), &format(&load_backup(&backup).unwrap())).unwrap();
+        assert_eq!(fs::read_to_string(&broken).unwrap(), "format=mkv\ngpu=NV");
+        assert!(load_from(&path) == (first.clone(), None));
+
+        fs::write(&backup, "# ffui-config 9\n").unwrap();
+        assert!(load_backup(&backup).err().unwrap().starts_with("备份也无法读取"));
+        fs::remove_file(&backup).unwrap();
+        assert!(load_backup(&backup).err().unwrap().starts_with("无法读取备份"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
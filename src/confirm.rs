@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use crate::dataloss::{self, Choice};
+use crate::inspect;
+use crate::output;
+use crate::plan::{self, JobSettings};
+use crate::queueview::Speeds;
+use crate::throttle::{self, Mechanism};
+
+// 开始前的确认清单：要转换的文件、预计耗时、会被覆盖的文件、完成后还会做的事。
+// 耗时很长、会覆盖已有文件、一次排了很多文件、完成后要运行用户命令、会丢失源文件里的数据时先给用户过目；
+// 无界面运行任务列表时把同一份清单打印出来
+
+// 预计超过一小时算长任务
+pub const LONG_SECS: f64 = 3600.0;
+// 一次排进这么多文件时先确认
+pub const MANY_FILES: usize = 10;
+// 确认框里最多列出的文件数
+const LIST_LIMIT: usize = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Trigger {
+    LongJob,
+    Overwrite,
+    ManyFiles,
+    PostCommand,
+    DataLoss,
+}
+
+impl Trigger {
+    pub const ALL: [Trigger; 5] = [Trigger::LongJob, Trigger::Overwrite, Trigger::ManyFiles, Trigger::PostCommand, Trigger::DataLoss];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Trigger::LongJob => "预计耗时较长",
+            Trigger::Overwrite => "会覆盖已有文件",
+            Trigger::ManyFiles => "一次转换很多文件",
+            Trigger::PostCommand => "完成后运行命令",
+            Trigger::DataLoss => "会丢失源文件里的数据",
+        }
+    }
+
+    // 设置文件里 confirm_skip 用的名字
+    pub fn tag(self) -> &'static str {
+        match self {
+            Trigger::LongJob => "long",
+            Trigger::Overwrite => "overwrite",
+            Trigger::ManyFiles => "many",
+            Trigger::PostCommand => "hook",
+            Trigger::DataLoss => "loss",
+        }
+    }
+}
+
+// 设置里的 confirm_skip：逗号分隔，用户选了“不再提示”的类型
+pub fn parse_skip(text: &str) -> Vec<Trigger> {
+    text.split(',').filter_map(|t| Trigger::ALL.into_iter().find(|tr| tr.tag() == t.trim())).collect()
+}
+
+pub fn format_skip(skip: &[Trigger]) -> String {
+    skip.iter().map(|t| t.tag()).collect::<Vec<_>>().join(",")
+}
+
+// 按统计里这个编码器的平均速度估算；限制了写入速度时不会比限速允许的更快
+pub fn estimate_secs(settings: &JobSettings, duration: f64, size: Option<u64>, speeds: &Speeds) -> Option<f64> {
+    let estimate = speeds
+        .get(plan::video_codec(settings))
+        .filter(|(media, _)| *media > 0.0)
+        .map(|(media, spent)| duration * spent / media);
+    let source = size.and_then(|size| throttle::bits_from_size(size as f64, duration));
+    let floor = match settings.write_limit_mb {
+        limit if limit <= 0.0 => None,
+        // 仅转换封装按源码率放慢读取，不知道码率时不限速
+        limit if settings.remux => Mechanism::copy(limit, source),
+        limit if plan::is_video_container(&settings.format) => Some(Mechanism::encode(limit, source)),
+        _ => None,
+    }
+    .map(|mechanism| mechanism.min_secs(duration));
+    match (estimate, floor) {
+        (Some(e), Some(f)) => Some(e.max(f)),
+        (e, f) => e.or(f),
+    }
+}
+
+pub struct Item {
+    pub input: String,
+    pub output: String,
+    // 输出已存在且设置为覆盖
+    pub overwrites: bool,
+    pub secs: Option<f64>,
+    // 数据丢失预检的结果，没有探测过的文件为空
+    pub losses: Vec<dataloss::Loss>,
+}
+
+impl Item {
+    // duration 为 None 表示时长未知（未探测、实时输入）
+    pub fn new(settings: &JobSettings, input: &str, output: &str, duration: Option<f64>, speeds: &Speeds) -> Item {
+        let size = std::fs::metadata(input).ok().map(|m| m.len());
+        let up_to_date = settings.incremental && output::is_up_to_date(Path::new(input), Path::new(output));
+        Item {
+            input: input.to_string(),
+            output: output.to_string(),
+            overwrites: settings.overwrite && !up_to_date && Path::new(output).exists(),
+            secs: duration.filter(|d| *d > 0.0).and_then(|d| estimate_secs(settings, d, size, speeds)),
+            losses: Vec::new(),
+        }
+    }
+}
+
+pub struct Summary {
+    pub items: Vec<Item>,
+    // 转换成功后还会发生的事
+    pub post_actions: Vec<String>,
+    pub hook: bool,
+    // 取样预览推算的大小等，没有时不显示
+    pub size_note: Option<String>,
+}
+
+impl Summary {
+    // 各任务的设置可以不同，完成后的动作合并去重
+    pub fn new(items: Vec<Item>, settings: &[&JobSettings]) -> Summary {
+        let mut post_actions: Vec<String> = Vec::new();
+        let mut hook = false;
+        for s in settings {
+            let mut actions = Vec::new();
+            if !s.hook_success.trim().is_empty() {
+                actions.push(format!("成功后运行: {}", s.hook_success.trim()));
+            }
+            if !s.hook_failure.trim().is_empty() {
+                actions.push(format!("失败后运行: {}", s.hook_failure.trim()));
+            }
+            hook |= !actions.is_empty();
+            if s.hash_source && s.hash_embed {
+                actions.push("把源文件校验值写入输出（重写一遍输出文件）".to_string());
+            }
+            if s.keep_dates {
+                actions.push("把源文件的时间戳复制到输出".to_string());
+            }
+            for action in actions {
+                if !post_actions.contains(&action) {
+                    post_actions.push(action);
+                }
+            }
+        }
+        Summary { items, post_actions, hook, size_note: None }
+    }
+
+    // 已知耗时的合计，以及有几个文件的耗时未知
+    pub fn total_secs(&self) -> (f64, usize) {
+        let known: f64 = self.items.iter().filter_map(|i| i.secs).sum();
+        (known, self.items.iter().filter(|i| i.secs.is_none()).count())
+    }
+
+    // 需要确认的原因，去掉用户选了不再提示的
+    pub fn triggers(&self, skip: &[Trigger]) -> Vec<Trigger> {
+        let fired = [
+            (Trigger::LongJob, self.total_secs().0 > LONG_SECS),
+            (Trigger::Overwrite, self.items.iter().any(|i| i.overwrites)),
+            (Trigger::ManyFiles, self.items.len() > MANY_FILES),
+            (Trigger::PostCommand, self.hook),
+            // 选了转换或另存的不算丢失，清单里照样列出
+            (Trigger::DataLoss, self.items.iter().flat_map(|i| &i.losses).any(|l| l.choice == Choice::Accept)),
+        ];
+        fired.into_iter().filter(|(t, on)| *on && !skip.contains(t)).map(|(t, _)| t).collect()
+    }
+
+    // 确认框和无界面运行共用的文字
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("共 {} 个文件", self.items.len())];
+        for item in self.items.iter().take(LIST_LIMIT) {
+            let mark = if item.overwrites { "（覆盖已有文件）" } else { "" };
+            lines.push(format!("  {} → {}{}", item.input, item.output, mark));
+            for loss in &item.losses {
+                lines.push(format!("    {}", loss.line()));
+            }
+        }
+        if self.items.len() > LIST_LIMIT {
+            lines.push(format!("  ……还有 {} 个", self.items.len() - LIST_LIMIT));
+        }
+        let overwrites = self.items.iter().filter(|i| i.overwrites).count();
+        if overwrites > 0 {
+            lines.push(format!("{} 个输出文件已存在，将被覆盖", overwrites));
+        }
+        match self.total_secs() {
+            (secs, 0) if secs > 0.0 => lines.push(format!("预计耗时 {}", inspect::format_duration(secs))),
+            (secs, unknown) if secs > 0.0 => lines.push(format!("预计耗时 {} 以上（{} 个文件无法估算）", inspect::format_duration(secs), unknown)),
+            _ => lines.push("预计耗时: 无法估算（时长未知或还没有这个编码器的统计）".to_string()),
+        }
+        if let Some(note) = &self.size_note {
+            lines.push(note.clone());
+        }
+        for action in &self.post_actions {
+            lines.push(format!("完成后: {}", action));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remux_estimate_respects_the_write_limit() {
+        // 100 秒、250 MB 的源文件是 20 Mbps；限速 4 MB/s 复制不快于 1.6 倍实时
+        let settings = JobSettings { remux: true, format: "mkv".to_string(), write_limit_mb: 4.0, ..Default::default() };
+        let secs = estimate_secs(&settings, 100.0, Some(250_000_000), &Speeds::new()).unwrap();
+        assert!((secs - 62.5).abs() < 1e-9, "{}", secs);
+        // 不知道大小时不限速，也就没有估算
+        assert_eq!(estimate_secs(&settings, 100.0, None, &Speeds::new()), None);
+    }
+
+    #[test]
+    fn estimate_is_never_faster_than_the_write_limit() {
+        // 统计里编码器跑 10 倍实时，但 10 MB/s 限速下 20 Mbps 的源最多 80 / 20.32 倍实时
+        let settings = JobSettings { write_limit_mb: 10.0, ..Default::default() };
+        let mut speeds = Speeds::new();
+        speeds.insert(plan::video_codec(&settings).to_string(), (1000.0, 100.0));
+        let secs = estimate_secs(&settings, 100.0, Some(250_000_000), &speeds).unwrap();
+        assert!((secs - 100.0 * 20_320_000.0 / 80_000_000.0).abs() < 1e-6, "{}", secs);
+        // 编码器比限速慢时按编码器算
+        speeds.insert(plan::video_codec(&settings).to_string(), (100.0, 100.0));
+        assert_eq!(estimate_secs(&settings, 100.0, Some(250_000_000), &speeds), Some(100.0));
+        let unlimited = JobSettings { write_limit_mb: 0.0, ..settings };
+        speeds.insert(plan::video_codec(&unlimited).to_string(), (1000.0, 100.0));
+        assert_eq!(estimate_secs(&unlimited, 100.0, Some(250_000_000), &speeds), Some(10.0));
+    }
+}
@@ -0,0 +1,289 @@
+use std::fs;
+use std::path::Path;
+
+use crate::args::{Args, Source};
+use crate::live;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::MediaInfo;
+
+// 音频文件里内嵌的封面（attached_pic）。FLAC 转 Opus 之类的批量转换默认会丢掉封面：
+// 能嵌入的容器原样复制过去，不能嵌入的可以另存为输出旁边的 cover.jpg。
+// 源文件没有内嵌封面时，用和它放在一起的 folder.jpg 或 cover.*
+
+pub const FALLBACK_NAME: &str = "cover.jpg";
+
+// 源文件旁边的图片，按优先顺序，不分大小写
+const BESIDE: [(&str, &str); 6] = [
+    ("folder.jpg", "mjpeg"),
+    ("cover.jpg", "mjpeg"),
+    ("cover.jpeg", "mjpeg"),
+    ("cover.png", "png"),
+    ("cover.webp", "webp"),
+    ("cover.bmp", "bmp"),
+];
+
+pub struct Art {
+    // 在源文件所有流里的序号，-map 0:序号
+    pub index: usize,
+    pub codec: String,
+    // 来自源文件旁边的图片时是它的路径，作为另一个输入
+    pub file: Option<String>,
+}
+
+pub fn find(info: &MediaInfo) -> Option<Art> {
+    let index = info.streams.iter().position(|s| {
+        s.codec_type == "video" && s.props.get("disposition.attached_pic").is_some_and(|v| v == "1")
+    })?;
+    Some(Art { index, codec: info.streams[index].codec_name.clone(), file: None })
+}
+
+pub fn find_beside(input: &str) -> Option<Art> {
+    if live::is_live(input) {
+        return None;
+    }
+    let dir = Path::new(input).parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let names: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| !t.is_dir()))
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    BESIDE.iter().find_map(|(want, codec)| {
+        let name = names.iter().find(|n| n.to_lowercase() == *want)?;
+        Some(Art { index: 0, codec: codec.to_string(), file: Some(dir.join(name).to_string_lossy().into_owned()) })
+    })
+}
+
+// 内嵌的封面优先
+pub fn pick(info: &MediaInfo, input: &str) -> Option<Art> {
+    find(info).or_else(|| find_beside(input))
+}
+
+// 用旁边的图片时多一个输入，紧跟在其他输入后面
+pub fn input_args(art: Option<&Art>, format: &str) -> Args {
+    let mut args = Args::new();
+    if let Some(file) = art.and_then(|a| a.file.as_deref()).filter(|_| embeds(format)) {
+        args.push(Source::CoverArt, &["-i", file]);
+    }
+    args
+}
+
+// 各音频容器能否带封面：mp3 写 ID3 APIC，m4a/m4b 写 covr，flac 写 PICTURE 块。
+// ffmpeg 的 ogg/opus 封装不支持封面流，aac（ADTS）和 wav 没有地方放
+pub fn embeds(format: &str) -> bool {
+    matches!(format, "mp3" | "m4a" | "m4b" | "flac")
+}
+
+// mp3 和 m4a 只接受 JPEG/PNG，其他格式的图片转成 JPEG
+fn codec_args(art: &Art) -> [&'static str; 2] {
+    if matches!(art.codec.as_str(), "mjpeg" | "png") { ["-c:v", "copy"] } else { ["-c:v", "mjpeg"] }
+}
+
+// 音频输出的视频部分：能嵌入时带上封面，否则 -vn，避免把封面或视频流交给不支持的封装。
+// input 是 input_args 加的图片在所有输入里的序号
+pub fn embed_args(art: Option<&Art>, format: &str, input: usize) -> Args {
+    let mut args = Args::new();
+    match art {
+        Some(art) if embeds(format) => {
+            let map = match art.file {
+                Some(_) => format!("{}:0", input),
+                None => format!("0:{}", art.index),
+            };
+            args.push(Source::CoverArt, &["-map", "0:a:0", "-map", &map]);
+            args.push(Source::CoverArt, &codec_args(art));
+            args.push(Source::CoverArt, &["-disposition:v:0", "attached_pic"]);
+        }
+        _ => args.push(Source::CoverArt, &["-vn"]),
+    }
+    args
+}
+
+// 另存的封面放在输出旁边
+pub fn fallback_path(output: &str) -> String {
+    let dir = Path::new(output).parent().unwrap_or(Path::new(""));
+    dir.join(FALLBACK_NAME).to_string_lossy().into_owned()
+}
+
+// 单独调用一次 ffmpeg，只取出封面这一帧
+pub fn fallback_args(settings: &JobSettings, art: &Art, input: &str, path: &str) -> Args {
+    // 封面是附加图片，不随裁剪定位
+    let mut args = plan::input_args(&JobSettings { trim_start: None, trim_end: None, ..settings.clone() }, input);
+    args.push(Source::CoverArt, &["-map", &format!("0:{}", art.index)]);
+    args.push(Source::CoverArt, &codec_args(art));
+    args.push(Source::CoverArt, &["-frames:v", "1", "-update", "1"]);
+    args.push(Source::Output, &[path]);
+    args
+}
+
+// 由 plan_job 对音频输出调用：说明封面怎么处理，需要时加上另存封面的调用。
+// 同一个文件夹里的曲目通常是同一张封面，已有 cover.jpg 时不覆盖
+pub fn plan(job: &mut JobPlan, settings: &JobSettings, format: &str, info: &MediaInfo, input: &str, output: &str) {
+    let Some(art) = find(info) else {
+        if embeds(format)
+            && let Some(file) = find_beside(input).and_then(|a| a.file)
+        {
+            job.notes.push(format!("封面: 源文件没有封面，使用旁边的 {}", file));
+        }
+        return;
+    };
+    if embeds(format) {
+        job.notes.push(format!("封面: 保留源文件的封面（{}）", art.codec));
+        return;
+    }
+    if !settings.cover_file {
+        job.notes.push(format!("封面: {} 不能嵌入封面，输出里没有封面（可以选择另存为 {}）", format, FALLBACK_NAME));
+        job.art_lost = true;
+        return;
+    }
+    let path = fallback_path(output);
+    if Path::new(&path).exists() {
+        job.notes.push(format!("封面: {} 不能嵌入封面，{} 已存在，不覆盖", format, path));
+        return;
+    }
+    job.notes.push(format!("封面: {} 不能嵌入封面，另存为 {}", format, path));
+    // 不算进 outputs：校验时长、复制日期都只针对音频本身
+    job.copy_runs.push(job.runs.len());
+    job.runs.push(fallback_args(settings, &art, input, &path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("ffui_coverart_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // 一条音频，cover 为 Some 时再加一条封面流
+    fn media(cover: Option<&str>) -> MediaInfo {
+        let mut streams = vec![StreamInfo { codec_type: "audio".to_string(), codec_name: "flac".to_string(), ..Default::default() }];
+        if let Some(codec) = cover {
+            let mut art = StreamInfo { codec_type: "video".to_string(), codec_name: codec.to_string(), ..Default::default() };
+            art.props.insert("disposition.attached_pic".to_string(), "1".to_string());
+            streams.push(art);
+        }
+        MediaInfo { duration: 180.0, streams, ..Default::default() }
+    }
+
+    fn argv(args: Args) -> String {
+        args.argv().join(" ")
+    }
+
+    #[test]
+    fn embedded_cover() {
+        assert!(find(&media(None)).is_none());
+        let art = find(&media(Some("png"))).unwrap();
+        assert_eq!((art.index, art.codec.as_str(), art.file.as_deref()), (1, "png", None));
+        // 普通视频流不算封面
+        let mut info = media(None);
+        info.streams.push(StreamInfo { codec_type: "video".to_string(), codec_name: "h264".to_string(), ..Default::default() });
+        assert!(find(&info).is_none());
+    }
+
+    #[test]
+    fn embed_args_per_format() {
+        let art = find(&media(Some("mjpeg")));
+        assert_eq!(argv(embed_args(art.as_ref(), "mp3", 1)), "-map 0:a:0 -map 0:1 -c:v copy -disposition:v:0 attached_pic");
+        assert_eq!(argv(embed_args(art.as_ref(), "opus", 1)), "-vn");
+        assert_eq!(argv(embed_args(None, "mp3", 1)), "-vn");
+        // 其他格式的图片转成 JPEG
+        let art = find(&media(Some("webp")));
+        assert_eq!(argv(embed_args(art.as_ref(), "m4a", 1)), "-map 0:a:0 -map 0:1 -c:v mjpeg -disposition:v:0 attached_pic");
+        // 旁边的图片是另一个输入
+        let art = Art { index: 0, codec: "png".to_string(), file: Some("/music/cover.png".to_string()) };
+        assert_eq!(argv(input_args(Some(&art), "flac")), "-i /music/cover.png");
+        assert_eq!(argv(input_args(Some(&art), "ogg")), "");
+        assert_eq!(argv(embed_args(Some(&art), "flac", 2)), "-map 0:a:0 -map 2:0 -c:v copy -disposition:v:0 attached_pic");
+        assert_eq!(argv(input_args(find(&media(Some("png"))).as_ref(), "mp3")), "");
+    }
+
+    #[test]
+    fn images_beside_the_input() {
+        let dir = scratch("beside");
+        let input = dir.join("01 track.flac").to_string_lossy().into_owned();
+        assert!(find_beside(&input).is_none());
+        // 目录不算
+        fs::create_dir(dir.join("cover.jpg")).unwrap();
+        assert!(find_beside(&input).is_none());
+        fs::write(dir.join("Cover.PNG"), b"png").unwrap();
+        let art = find_beside(&input).unwrap();
+        assert_eq!(art.codec, "png");
+        assert_eq!(art.file.unwrap(), dir.join("Cover.PNG").to_string_lossy());
+        // folder.jpg 优先
+        fs::write(dir.join("Folder.jpg"), b"jpg").unwrap();
+        let art = find_beside(&input).unwrap();
+        assert_eq!(art.codec, "mjpeg");
+        assert_eq!(art.file.unwrap(), dir.join("Folder.jpg").to_string_lossy());
+        // 内嵌的封面比旁边的优先
+        assert!(pick(&media(Some("mjpeg")), &input).unwrap().file.is_none());
+        assert!(pick(&media(None), &input).unwrap().file.is_some());
+        assert!(find_beside("-").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn build_args_adds_the_image_input() {
+        let dir = scratch("build");
+        let input = dir.join("song.flac").to_string_lossy().into_owned();
+        let cover = dir.join("cover.jpg").to_string_lossy().into_owned();
+        fs::write(&cover, b"jpg").unwrap();
+        let settings = JobSettings { format: "mp3".to_string(), ..Default::default() };
+        let args = plan::build_args(&settings, &media(None), &input, "/out/song.mp3").argv();
+        let i = args.iter().position(|a| a == &input).unwrap();
+        // 图片紧跟在源文件后面，输出选项之前
+        assert_eq!(args[i + 1..i + 7], ["-i", cover.as_str(), "-map", "0:a:0", "-map", "1:0"]);
+        // 不能嵌入时不加这个输入
+        let settings = JobSettings { format: "opus".to_string(), ..Default::default() };
+        let args = plan::build_args(&settings, &media(None), &input, "/out/song.opus").argv();
+        assert!(!args.contains(&cover));
+        assert!(args.contains(&"-vn".to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn notes_and_fallback() {
+        let dir = scratch("plan");
+        let input = dir.join("song.flac").to_string_lossy().into_owned();
+        let output = dir.join("out").join("song.ogg").to_string_lossy().into_owned();
+        let settings = JobSettings::default();
+
+        let mut job = JobPlan::default();
+        plan(&mut job, &settings, "mp3", &media(Some("mjpeg")), &input, &output);
+        assert_eq!(job.notes, ["封面: 保留源文件的封面（mjpeg）"]);
+
+        let mut job = JobPlan::default();
+        plan(&mut job, &settings, "ogg", &media(Some("mjpeg")), &input, &output);
+        assert!(job.art_lost && job.runs.is_empty());
+
+        let with_file = JobSettings { cover_file: true, ..Default::default() };
+        let mut job = JobPlan::default();
+        plan(&mut job, &with_file, "ogg", &media(Some("mjpeg")), &input, &output);
+        assert!(!job.art_lost);
+        let path = fallback_path(&output);
+        assert_eq!(path, dir.join("out").join(FALLBACK_NAME).to_string_lossy());
+        let run = job.runs[0].argv();
+        assert_eq!(run[run.len() - 9..], ["-map", "0:1", "-c:v", "copy", "-frames:v", "1", "-update", "1", path.as_str()]);
+        // 另存封面只是取出一帧，不加附加参数
+        assert_eq!(job.copy_runs, [0]);
+
+        // 已有 cover.jpg 时不覆盖
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(&path, b"jpg").unwrap();
+        let mut job = JobPlan::default();
+        plan(&mut job, &with_file, "ogg", &media(Some("mjpeg")), &input, &output);
+        assert!(job.runs.is_empty() && job.notes[0].contains("已存在"));
+
+        // 没有内嵌封面时说明用了旁边的图片
+        let mut job = JobPlan::default();
+        plan(&mut job, &settings, "mp3", &media(None), &input, &output);
+        assert!(job.notes.is_empty());
+        fs::write(dir.join("folder.jpg"), b"jpg").unwrap();
+        plan(&mut job, &settings, "mp3", &media(None), &input, &output);
+        assert_eq!(job.notes, [format!("封面: 源文件没有封面，使用旁边的 {}", dir.join("folder.jpg").to_string_lossy())]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
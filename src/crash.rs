@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use crate::history;
+use crate::paths;
+use crate::plan::{self, JobSettings};
+
+// ffmpeg 崩溃（段错误、访问冲突），而不是自己报错退出。常见于滤镜组合出错或硬件驱动有问题，
+// 单独提示、把命令和错误输出存成崩溃报告，并给出换个路径重试的办法
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Crash {
+    // Windows 的异常状态码（NTSTATUS），如 0xC0000005
+    Exception(u32),
+    // Unix 上被信号结束，如 SIGSEGV
+    Signal(i32),
+}
+
+impl Crash {
+    pub fn label(self) -> String {
+        match self {
+            Crash::Exception(code) => match exception_name(code) {
+                Some(name) => format!("FFmpeg 异常崩溃 (0x{:08X}，{})", code, name),
+                None => format!("FFmpeg 异常崩溃 (0x{:08X})", code),
+            },
+            Crash::Signal(signal) => match signal_name(signal) {
+                Some(name) => format!("FFmpeg 异常崩溃 (信号 {}，{})", signal, name),
+                None => format!("FFmpeg 异常崩溃 (信号 {})", signal),
+            },
+        }
+    }
+}
+
+fn exception_name(code: u32) -> Option<&'static str> {
+    Some(match code {
+        0xC000_0005 => "访问冲突",
+        0xC000_001D => "非法指令",
+        0xC000_0094 => "整数除以零",
+        0xC000_00FD => "栈溢出",
+        0xC000_0374 => "堆损坏",
+        0xC000_0409 => "栈缓冲区溢出",
+        0x8000_0003 => "断点",
+        _ => return None,
+    })
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        4 => "SIGILL 非法指令",
+        6 => "SIGABRT 异常终止",
+        7 => "SIGBUS 总线错误",
+        8 => "SIGFPE 算术错误",
+        11 => "SIGSEGV 段错误",
+        _ => return None,
+    })
+}
+
+// 按退出码和结束信号判断是不是崩溃，不依赖当前平台。
+// ffmpeg 自己报错只用很小的退出码；Windows 上最高位为 1 的退出码是 NTSTATUS 异常。
+// SIGKILL、SIGTERM、SIGINT 是停止、卡住检测或系统结束的，不算崩溃
+pub fn classify(code: Option<i32>, signal: Option<i32>) -> Option<Crash> {
+    match (code, signal) {
+        (_, Some(9 | 15 | 2)) => None,
+        (_, Some(signal)) => Some(Crash::Signal(signal)),
+        (Some(code), None) if code < 0 => Some(Crash::Exception(code as u32)),
+        _ => None,
+    }
+}
+
+pub fn from_status(status: &ExitStatus) -> Option<Crash> {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(status);
+    #[cfg(not(unix))]
+    let signal = None;
+    classify(status.code(), signal)
+}
+
+// 崩溃后可以一键换的路径
+#[derive(Clone, Copy, PartialEq)]
+pub enum Retry {
+    // 硬件解码、编码改成 CPU
+    Cpu,
+    // 去掉会生成 -vf/-af 的设置
+    NoFilters,
+}
+
+impl Retry {
+    pub fn label(self) -> &'static str {
+        match self {
+            Retry::Cpu => "改用 CPU 重试",
+            Retry::NoFilters => "去掉滤镜重试",
+        }
+    }
+
+    pub fn cause(self) -> &'static str {
+        match self {
+            Retry::Cpu => "可能是硬件解码/编码的驱动出错",
+            Retry::NoFilters => "可能是滤镜组合出错（反交错、字幕、缩放、补边、烧录时间码、变速或响度标准化）",
+        }
+    }
+
+    // 直接改窗口里的设置，重试后界面上看到的就是实际用的设置
+    pub fn apply(self, settings: &mut JobSettings) {
+        match self {
+            Retry::Cpu => settings.gpu = "CPU".to_string(),
+            Retry::NoFilters => {
+                settings.deinterlace = Default::default();
+                settings.fps = None;
+                settings.subtitle_file.clear();
+                settings.fit = Default::default();
+                settings.resolution = Default::default();
+                settings.burn_in = None;
+                settings.retime = None;
+                settings.loudnorm = None;
+            }
+        }
+    }
+}
+
+// 按崩溃的那次调用猜原因，最可能的排在前面
+pub fn suggestions(settings: &JobSettings, argv: &[String]) -> Vec<Retry> {
+    let mut retries = Vec::new();
+    if (settings.gpu != "CPU" && plan::is_video_container(&settings.format)) || argv.iter().any(|a| a == "-hwaccel") {
+        retries.push(Retry::Cpu);
+    }
+    if argv.iter().any(|a| matches!(a.as_str(), "-vf" | "-af" | "-filter_complex")) {
+        retries.push(Retry::NoFilters);
+    }
+    retries
+}
+
+pub fn report_dir() -> PathBuf {
+    paths::store_dir().join("crashes")
+}
+
+// 写出崩溃报告：原因、完整命令和 stderr 最后几百行，返回文件路径
+pub fn write_report(crash: Crash, input: &str, argv: &[String], tail: &str) -> io::Result<PathBuf> {
+    let dir = report_dir();
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crash-{}.txt", history::now()));
+    let text = format!(
+        "{}\n时间: {}\n输入: {}\n命令: {}\n\n=== ffmpeg 错误输出（最后部分）===\n{}\n",
+        crash.label(),
+        history::now(),
+        input,
+        plan::quote_command("ffmpeg", argv),
+        tail
+    );
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+// 界面上显示的崩溃信息
+pub struct Report {
+    pub label: String,
+    // 没能写出报告文件时为空
+    pub path: Option<PathBuf>,
+    pub retries: Vec<Retry>,
+}
@@ -0,0 +1,622 @@
+use std::path::Path;
+
+use crate::args::{Args, Source};
+use crate::av1::VideoCodec;
+use crate::coverart;
+use crate::encoders;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::{Chapter, MediaInfo, StreamInfo};
+use crate::speech;
+
+// 数据丢失预检：源文件里有、按当前的目标格式和编码转换后会悄悄没掉的东西——字幕、附件（ASS 用的字体）、
+// 章节、HDR 和封面。各容器和编码能带什么都记在这里，开始前列出会丢的内容，每一类可以选
+// 转换（能转的话）、另存为输出旁边的文件，或者接受丢失。
+// 一键方案、截图、语音优化、专辑、多分辨率和快速操作自己决定保留什么，不检查
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    TextSubtitles,
+    BitmapSubtitles,
+    Attachments,
+    Chapters,
+    Hdr,
+    CoverArt,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 6] = [Kind::TextSubtitles, Kind::BitmapSubtitles, Kind::Attachments, Kind::Chapters, Kind::Hdr, Kind::CoverArt];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::TextSubtitles => "文字字幕",
+            Kind::BitmapSubtitles => "图形字幕",
+            Kind::Attachments => "附件",
+            Kind::Chapters => "章节",
+            Kind::Hdr => "HDR",
+            Kind::CoverArt => "封面",
+        }
+    }
+
+    // 任务列表里 data_loss 的写法
+    pub fn tag(self) -> &'static str {
+        match self {
+            Kind::TextSubtitles => "text_subs",
+            Kind::BitmapSubtitles => "bitmap_subs",
+            Kind::Attachments => "attachments",
+            Kind::Chapters => "chapters",
+            Kind::Hdr => "hdr",
+            Kind::CoverArt => "cover",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|k| k.tag() == tag.trim())
+    }
+
+    // 界面和确认清单上这一类各个选项的说明
+    pub fn choice_label(self, choice: Choice) -> &'static str {
+        match (self, choice) {
+            (Kind::TextSubtitles, Choice::Convert) => "转成 mov_text（样式和字体会丢失）",
+            (Kind::Hdr, Choice::Convert) => "色调映射成 SDR",
+            (_, Choice::Convert) => "转换",
+            (Kind::Attachments, Choice::Sidecar) => "另存到输出旁边的文件夹",
+            (Kind::Chapters, Choice::Sidecar) => "另存为章节文件（ffmetadata）",
+            (Kind::CoverArt, Choice::Sidecar) => "另存为 cover.jpg",
+            (_, Choice::Sidecar) => "另存为输出旁边的字幕文件",
+            (_, Choice::Accept) => "接受丢失",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Choice {
+    Convert,
+    Sidecar,
+    Accept,
+}
+
+impl Choice {
+    pub const ALL: [Choice; 3] = [Choice::Convert, Choice::Sidecar, Choice::Accept];
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Choice::Convert => "convert",
+            Choice::Sidecar => "sidecar",
+            Choice::Accept => "accept",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Choice> {
+        Choice::ALL.into_iter().find(|c| c.tag() == tag.trim())
+    }
+}
+
+// HDR 转 SDR：先转成线性光，按 BT.709 色域用 hable 曲线压缩亮度，再转回 8 位
+pub const TONEMAP: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+
+// 精简编译的 ffmpeg 可能没有 zscale（libzimg），那样只能接受丢失
+fn tonemap_available() -> bool {
+    encoders::filter_available("zscale") && encoders::filter_available("tonemap")
+}
+
+pub fn applies(settings: &JobSettings) -> bool {
+    settings.web.is_none() && settings.snapshot.is_none() && settings.speech.is_none() && settings.album.is_none() && settings.quick.is_none()
+        && !(settings.ladder_enabled && !settings.remux && plan::is_video_container(&settings.format))
+}
+
+// 各目标格式能原样带上的字幕：mkv 什么都能装（mov_text 要转成 srt，不算丢），mp4/mov 只有 mov_text，
+// avi、flv、wmv 和音频格式没有能用的字幕格式
+fn carries_subtitle(format: &str, stream: &StreamInfo) -> bool {
+    match format {
+        "mkv" => true,
+        "mp4" | "mov" => stream.codec_name == "mov_text",
+        _ => false,
+    }
+}
+
+fn bitmap(stream: &StreamInfo) -> bool {
+    matches!(stream.codec_name.as_str(), "hdmv_pgs_subtitle" | "dvd_subtitle" | "dvb_subtitle" | "xsub")
+}
+
+// mp3 写 ID3 的 CHAP，ogg 写在注释里，wmv 写成 ASF 标记；avi、flv、wav 和 aac 没有章节
+fn carries_chapters(format: &str) -> bool {
+    matches!(format, "mkv" | "mp4" | "mov" | "wmv" | "mp3" | "ogg")
+}
+
+// 附件只有 mkv 有
+fn carries_attachments(format: &str) -> bool {
+    format == "mkv"
+}
+
+// 重新编码时只有能编 10 位的 HEVC、AV1 和 VP9 会带上 PQ/HLG 的色彩信息，H.264 按 8 位 SDR 输出
+fn carries_hdr(settings: &JobSettings) -> bool {
+    settings.remux || settings.codec != VideoCodec::H264
+}
+
+fn subtitles(info: &MediaInfo) -> Vec<&StreamInfo> {
+    info.streams.iter().filter(|s| s.codec_type == "subtitle").collect()
+}
+
+fn attachments(info: &MediaInfo) -> Vec<&StreamInfo> {
+    info.streams.iter().filter(|s| s.codec_type == "attachment").collect()
+}
+
+// PQ（HDR10、杜比视界的基础层）或 HLG 的视频流
+fn hdr_transfer(info: &MediaInfo) -> Option<&'static str> {
+    let video = info.streams.iter().find(|s| s.codec_type == "video" && s.props.get("disposition.attached_pic").is_none_or(|v| v != "1"))?;
+    match video.props.get("color_transfer").map(|t| t.as_str()) {
+        Some("smpte2084") => Some("PQ"),
+        Some("arib-std-b67") => Some("HLG"),
+        _ => None,
+    }
+}
+
+fn language(stream: &StreamInfo) -> Option<&str> {
+    stream.props.get("tags.language").map(|l| l.as_str()).filter(|l| !l.is_empty() && *l != "und")
+}
+
+fn describe_subtitle(index: usize, stream: &StreamInfo) -> String {
+    match language(stream) {
+        Some(lang) => format!("#{} {}（{}）", index, stream.codec_name, lang),
+        None => format!("#{} {}", index, stream.codec_name),
+    }
+}
+
+// 附件另存时用的文件名：源文件里记的名字，去掉路径
+fn attachment_name(index: usize, stream: &StreamInfo) -> String {
+    stream
+        .props
+        .get("tags.filename")
+        .and_then(|n| Path::new(n).file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(format!("attachment{}", index))
+}
+
+// 会丢失的一类内容
+pub struct Loss {
+    pub kind: Kind,
+    // 给用户看的具体内容，如 "#0 ass（chi）"
+    pub items: Vec<String>,
+    // 字幕、附件在同类流里的序号（0:s:N、0:t:N）
+    pub streams: Vec<usize>,
+    pub choices: Vec<Choice>,
+    pub choice: Choice,
+}
+
+impl Loss {
+    pub fn line(&self) -> String {
+        format!("{}: {} → {}", self.kind.label(), self.items.join("，"), self.kind.choice_label(self.choice))
+    }
+}
+
+// 用户的选择不在可选范围里时（换了目标格式、ffmpeg 没有滤镜），能转换就转换，否则接受丢失
+fn choice(settings: &JobSettings, kind: Kind, choices: &[Choice]) -> Choice {
+    let chosen = match kind {
+        Kind::CoverArt => Some(if settings.cover_file { Choice::Sidecar } else { Choice::Accept }),
+        _ => settings.data_loss.iter().find(|(k, _)| *k == kind).map(|(_, c)| *c),
+    };
+    chosen
+        .filter(|c| choices.contains(c))
+        .unwrap_or(if choices.contains(&Choice::Convert) { Choice::Convert } else { Choice::Accept })
+}
+
+// 封面沿用原来的“另存为 cover.jpg”设置，其他的记在 data_loss 里
+pub fn set_choice(settings: &mut JobSettings, kind: Kind, choice: Choice) {
+    if kind == Kind::CoverArt {
+        settings.cover_file = choice == Choice::Sidecar;
+        return;
+    }
+    settings.data_loss.retain(|(k, _)| *k != kind);
+    settings.data_loss.push((kind, choice));
+}
+
+fn text_choices(format: &str) -> Vec<Choice> {
+    match format {
+        "mp4" | "mov" => vec![Choice::Convert, Choice::Sidecar, Choice::Accept],
+        _ => vec![Choice::Sidecar, Choice::Accept],
+    }
+}
+
+fn hdr_choices() -> Vec<Choice> {
+    if tonemap_available() { vec![Choice::Convert, Choice::Accept] } else { vec![Choice::Accept] }
+}
+
+// 按当前设置转换会丢失的内容，每类一项
+pub fn report(settings: &JobSettings, info: &MediaInfo) -> Vec<Loss> {
+    let mut losses = Vec::new();
+    if !applies(settings) {
+        return losses;
+    }
+    let format = settings.format.as_str();
+    let mut push = |kind: Kind, items: Vec<String>, streams: Vec<usize>, choices: Vec<Choice>| {
+        if !items.is_empty() {
+            let choice = choice(settings, kind, &choices);
+            losses.push(Loss { kind, items, streams, choices, choice });
+        }
+    };
+    if plan::is_video_container(format) {
+        let dropped: Vec<(usize, &StreamInfo)> = subtitles(info).into_iter().enumerate().filter(|(_, s)| !carries_subtitle(format, s)).collect();
+        for (kind, is_bitmap) in [(Kind::TextSubtitles, false), (Kind::BitmapSubtitles, true)] {
+            let group: Vec<&(usize, &StreamInfo)> = dropped.iter().filter(|(_, s)| bitmap(s) == is_bitmap).collect();
+            let choices = if is_bitmap { vec![Choice::Sidecar, Choice::Accept] } else { text_choices(format) };
+            push(kind, group.iter().map(|(i, s)| describe_subtitle(*i, s)).collect(), group.iter().map(|(i, _)| *i).collect(), choices);
+        }
+        if !carries_attachments(format) {
+            let list = attachments(info);
+            push(
+                Kind::Attachments,
+                list.iter().enumerate().map(|(i, s)| attachment_name(i, s)).collect(),
+                (0..list.len()).collect(),
+                vec![Choice::Sidecar, Choice::Accept],
+            );
+        }
+        if let Some(transfer) = hdr_transfer(info)
+            && !carries_hdr(settings)
+        {
+            push(Kind::Hdr, vec![format!("{}，输出为 {} 8 位 SDR，颜色会发灰", transfer, settings.codec.label())], Vec::new(), hdr_choices());
+        }
+    } else if coverart::find(info).is_some() && !coverart::embeds(format) {
+        push(Kind::CoverArt, vec![format!("{} 不能嵌入封面", format)], Vec::new(), vec![Choice::Sidecar, Choice::Accept]);
+    }
+    if !info.chapters.is_empty() && !carries_chapters(format) {
+        push(Kind::Chapters, vec![format!("{} 个章节", info.chapters.len())], Vec::new(), vec![Choice::Sidecar, Choice::Accept]);
+    }
+    losses
+}
+
+// 主输出里这条字幕用的编码，None 表示不带上
+fn output_codec(settings: &JobSettings, stream: &StreamInfo) -> Option<&'static str> {
+    let format = settings.format.as_str();
+    match format {
+        "mkv" if stream.codec_name == "mov_text" => Some("srt"),
+        _ if carries_subtitle(format, stream) => Some("copy"),
+        "mp4" | "mov" if !bitmap(stream) && choice(settings, Kind::TextSubtitles, &text_choices(format)) == Choice::Convert => Some("mov_text"),
+        _ => None,
+    }
+}
+
+// 主输出里字幕和附件的映射，放在视频、音轨映射之后；mapped 表示已经逐条映射过视频和音轨。
+// 有要带上的字幕或附件时明确映射全部视频、第一条音轨（和检查音频时一样）和这些字幕；
+// 否则 -sn，免得 ffmpeg 自动选上一条目标格式装不下的字幕而失败
+pub fn stream_args(settings: &JobSettings, info: &MediaInfo, mapped: bool) -> Args {
+    let mut args = Args::new();
+    let subs = subtitles(info);
+    let keep_attachments = carries_attachments(&settings.format) && !attachments(info).is_empty();
+    let kept: Vec<(usize, &'static str)> = subs.iter().enumerate().filter_map(|(i, s)| output_codec(settings, s).map(|c| (i, c))).collect();
+    if kept.is_empty() && !keep_attachments {
+        if !subs.is_empty() {
+            args.push(Source::DataLoss, &["-sn"]);
+        }
+        return args;
+    }
+    if !mapped {
+        args.push(Source::DataLoss, &["-map", "0:V?", "-map", "0:a:0?"]);
+    }
+    for (n, (index, codec)) in kept.iter().enumerate() {
+        args.push(Source::DataLoss, &["-map", &format!("0:s:{}", index), &format!("-c:s:{}", n), codec]);
+    }
+    if keep_attachments {
+        args.push(Source::DataLoss, &["-map", "0:t", "-c:t", "copy"]);
+    }
+    args
+}
+
+// 仅转换封装时 -map 0 把所有流都复制过去，装不下的字幕和附件要去掉，否则 ffmpeg 直接报错
+pub fn remux_args(settings: &JobSettings, info: &MediaInfo) -> Args {
+    let mut args = Args::new();
+    let mut n = 0;
+    for (index, stream) in subtitles(info).into_iter().enumerate() {
+        match output_codec(settings, stream) {
+            None => args.push(Source::DataLoss, &["-map", &format!("-0:s:{}", index)]),
+            Some("copy") => {}
+            Some(codec) => args.push(Source::DataLoss, &[&format!("-c:s:{}", n), codec]),
+        }
+        n += output_codec(settings, stream).is_some() as usize;
+    }
+    if !carries_attachments(&settings.format) && !attachments(info).is_empty() {
+        args.push(Source::DataLoss, &["-map", "-0:t"]);
+    }
+    args
+}
+
+// 选了转换时放在视频滤镜最前面
+pub fn tonemap(settings: &JobSettings, info: &MediaInfo) -> Option<&'static str> {
+    let loss = report(settings, info).into_iter().find(|l| l.kind == Kind::Hdr)?;
+    (loss.choice == Choice::Convert).then_some(TONEMAP)
+}
+
+// 另存的文件和输出同名，如 movie.mp4 → movie.0.chi.ass、movie.chapters.txt、movie.attachments/
+fn sidecar_path(output: &str, suffix: &str) -> String {
+    Path::new(output).with_extension(suffix).to_string_lossy().into_owned()
+}
+
+// (扩展名, 编码)：能直接复制的写成对应的字幕文件，mov_text 转成 srt，其余的装进只有字幕的 mkv
+fn subtitle_file(stream: &StreamInfo) -> (&'static str, &'static str) {
+    match stream.codec_name.as_str() {
+        "ass" => ("ass", "copy"),
+        "subrip" => ("srt", "copy"),
+        "mov_text" => ("srt", "srt"),
+        "webvtt" => ("vtt", "copy"),
+        "hdmv_pgs_subtitle" => ("sup", "copy"),
+        _ => ("mks", "copy"),
+    }
+}
+
+// 单独调用一次 ffmpeg 取出一条字幕，按同样的范围裁剪，和输出对得上
+fn subtitle_args(settings: &JobSettings, input: &str, index: usize, stream: &StreamInfo, path: &str) -> Args {
+    let mut args = plan::input_args(&JobSettings { gpu: "CPU".to_string(), ..settings.clone() }, input);
+    let (ext, codec) = subtitle_file(stream);
+    args.push(Source::DataLoss, &["-map", &format!("0:s:{}", index), "-c:s", codec]);
+    if ext == "mks" {
+        args.push(Source::DataLoss, &["-f", "matroska"]);
+    }
+    // 附加参数是给主输出的，不放进另存的文件
+    args.push(Source::DataLoss, &[path]);
+    args
+}
+
+// 附件在文件头里，打开输入时由 -dump_attachment 写出，不用解码，也不随裁剪定位
+fn attachment_args(settings: &JobSettings, input: &str, files: &[(usize, String)]) -> Args {
+    let mut args = plan::input_args(&JobSettings { gpu: "CPU".to_string(), trim_start: None, trim_end: None, ..settings.clone() }, input);
+    let at = args.position("-i").unwrap_or(args.len());
+    for (index, path) in files {
+        args.insert(at, Source::DataLoss, path.clone());
+        args.insert(at, Source::DataLoss, format!("-dump_attachment:t:{}", index));
+    }
+    args.push(Source::DataLoss, &["-t", "0", "-f", "null", "-"]);
+    args
+}
+
+// 裁剪过的输出只保留范围内的章节，时间从裁剪起点算
+fn trimmed_chapters(settings: &JobSettings, input: &str, chapters: &[Chapter]) -> Vec<Chapter> {
+    if !plan::trims(settings, input) {
+        return chapters.to_vec();
+    }
+    let start = settings.trim_start.map_or(0.0, |d| d.as_secs_f64());
+    let end = settings.trim_end.map_or(f64::INFINITY, |d| d.as_secs_f64());
+    chapters
+        .iter()
+        .filter(|c| c.end > start && c.start < end)
+        .map(|c| Chapter { start: c.start.max(start) - start, end: c.end.min(end) - start, title: c.title.clone() })
+        .collect()
+}
+
+// 由 plan_runs 调用：把预检结果写进日志，选了另存的加上对应的调用或文件。
+// 另存的文件不算进 outputs，校验时长、复制日期都只针对输出本身
+pub fn plan(job: &mut JobPlan, settings: &JobSettings, info: &MediaInfo, input: &str, output: &str) {
+    let subs = subtitles(info);
+    let atts = attachments(info);
+    // 封面由 coverart::plan 处理
+    for loss in report(settings, info).into_iter().filter(|l| l.kind != Kind::CoverArt) {
+        job.notes.push(format!("数据丢失预检: {}", loss.line()));
+        if loss.choice != Choice::Sidecar {
+            continue;
+        }
+        match loss.kind {
+            Kind::TextSubtitles | Kind::BitmapSubtitles => {
+                for &index in &loss.streams {
+                    let stream = subs[index];
+                    let suffix = match language(stream) {
+                        Some(lang) => format!("{}.{}.{}", index, lang, subtitle_file(stream).0),
+                        None => format!("{}.{}", index, subtitle_file(stream).0),
+                    };
+                    job.runs.push(subtitle_args(settings, input, index, stream, &sidecar_path(output, &suffix)));
+                }
+            }
+            Kind::Attachments => {
+                let dir = sidecar_path(output, "attachments");
+                let files: Vec<(usize, String)> = loss
+                    .streams
+                    .iter()
+                    .map(|&i| (i, Path::new(&dir).join(attachment_name(i, atts[i])).to_string_lossy().into_owned()))
+                    .collect();
+                job.dirs.push(dir);
+                job.runs.push(attachment_args(settings, input, &files));
+            }
+            Kind::Chapters => {
+                let chapters = trimmed_chapters(settings, input, &info.chapters);
+                job.write_after.push((sidecar_path(output, "chapters.txt"), speech::ffmetadata(&chapters)));
+            }
+            Kind::Hdr | Kind::CoverArt => {}
+        }
+    }
+}
+
+// 任务列表和预设里写成 "text_subs=sidecar,hdr=accept"，只记用户改过的
+pub fn format_choices(choices: &[(Kind, Choice)]) -> String {
+    choices.iter().map(|(k, c)| format!("{}={}", k.tag(), c.tag())).collect::<Vec<_>>().join(",")
+}
+
+pub fn parse_choices(text: &str) -> Result<Vec<(Kind, Choice)>, String> {
+    let mut choices: Vec<(Kind, Choice)> = Vec::new();
+    for part in text.split(',').filter(|p| !p.trim().is_empty()) {
+        let (kind, choice) = part.split_once('=').ok_or(format!("数据丢失处理写法不对: {}", part.trim()))?;
+        let kind = Kind::from_tag(kind).filter(|k| *k != Kind::CoverArt).ok_or(format!("未知的数据类别 {}", kind.trim()))?;
+        let choice = Choice::from_tag(choice).ok_or(format!("未知的处理方式 {}", choice.trim()))?;
+        choices.retain(|(k, _)| *k != kind);
+        choices.push((kind, choice));
+    }
+    Ok(choices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stream(kind: &str, codec: &str, props: &[(&str, &str)]) -> StreamInfo {
+        StreamInfo {
+            codec_type: kind.to_string(),
+            codec_name: codec.to_string(),
+            props: props.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    // 一个典型的字幕组 mkv：ASS、PGS 和 srt 字幕，两个字体附件，三个章节
+    fn anime() -> MediaInfo {
+        let chapter = |start: f64, end: f64, title: &str| Chapter { start, end, title: title.to_string() };
+        MediaInfo {
+            duration: 1440.0,
+            streams: vec![
+                stream("video", "hevc", &[]),
+                stream("audio", "flac", &[]),
+                stream("subtitle", "ass", &[("tags.language", "chi")]),
+                stream("subtitle", "hdmv_pgs_subtitle", &[("tags.language", "jpn")]),
+                stream("subtitle", "subrip", &[("tags.language", "und")]),
+                stream("attachment", "ttf", &[("tags.filename", "fonts/A.ttf")]),
+                stream("attachment", "otf", &[]),
+            ],
+            chapters: vec![chapter(0.0, 90.0, "OP"), chapter(90.0, 1350.0, "本篇"), chapter(1350.0, 1440.0, "ED")],
+            ..Default::default()
+        }
+    }
+
+    fn media_with_video(props: &[(&str, &str)]) -> MediaInfo {
+        MediaInfo { streams: vec![stream("video", "hevc", props)], ..Default::default() }
+    }
+
+    fn to(format: &str) -> JobSettings {
+        JobSettings { format: format.to_string(), ..Default::default() }
+    }
+
+    fn kinds(losses: &[Loss]) -> Vec<&'static str> {
+        losses.iter().map(|l| l.kind.tag()).collect()
+    }
+
+    #[test]
+    fn mkv_keeps_everything() {
+        assert!(report(&to("mkv"), &anime()).is_empty());
+    }
+
+    #[test]
+    fn mp4_loses_subtitles_and_attachments() {
+        let losses = report(&to("mp4"), &anime());
+        assert_eq!(kinds(&losses), ["text_subs", "bitmap_subs", "attachments"]);
+        assert_eq!(losses[0].items, ["#0 ass（chi）", "#2 subrip"]);
+        assert_eq!(losses[0].streams, [0, 2]);
+        // 文字字幕能转成 mov_text，默认就转
+        assert!(losses[0].choice == Choice::Convert);
+        assert_eq!(losses[1].streams, [1]);
+        assert!(losses[1].choices == [Choice::Sidecar, Choice::Accept] && losses[1].choice == Choice::Accept);
+        assert_eq!(losses[2].items, ["A.ttf", "attachment1"]);
+    }
+
+    #[test]
+    fn avi_also_loses_chapters() {
+        let losses = report(&to("avi"), &anime());
+        assert_eq!(kinds(&losses), ["text_subs", "bitmap_subs", "attachments", "chapters"]);
+        // avi 没有能转的字幕格式
+        assert!(losses[0].choices == [Choice::Sidecar, Choice::Accept] && losses[0].choice == Choice::Accept);
+        assert_eq!(losses[3].items, ["3 个章节"]);
+    }
+
+    #[test]
+    fn saved_choices_apply_when_allowed() {
+        let mut settings = to("mp4");
+        set_choice(&mut settings, Kind::TextSubtitles, Choice::Sidecar);
+        set_choice(&mut settings, Kind::BitmapSubtitles, Choice::Convert);
+        let losses = report(&settings, &anime());
+        assert!(losses[0].choice == Choice::Sidecar);
+        // 图形字幕不能转换，回到接受丢失
+        assert!(losses[1].choice == Choice::Accept);
+        // 换成 avi 后“另存”仍然可选，照样用
+        let avi = JobSettings { format: "avi".to_string(), ..settings };
+        assert!(report(&avi, &anime())[0].choice == Choice::Sidecar);
+    }
+
+    #[test]
+    fn hdr_is_lost_only_when_encoding_h264() {
+        let mut info = media_with_video(&[("color_transfer", "smpte2084")]);
+        let losses = report(&to("mp4"), &info);
+        assert_eq!(kinds(&losses), ["hdr"]);
+        assert!(losses[0].items[0].starts_with("PQ，"));
+        assert!(losses[0].choices.contains(&Choice::Accept));
+        // 仅转换封装和 10 位的编码器都带得上
+        assert!(report(&JobSettings { remux: true, ..to("mp4") }, &info).is_empty());
+        assert!(report(&JobSettings { codec: VideoCodec::Hevc, ..to("mp4") }, &info).is_empty());
+        info.streams[0].props.insert("color_transfer".to_string(), "bt709".to_string());
+        assert!(report(&to("mp4"), &info).is_empty());
+    }
+
+    #[test]
+    fn audio_output_reports_the_cover() {
+        let info = MediaInfo {
+            streams: vec![stream("audio", "flac", &[]), stream("video", "mjpeg", &[("disposition.attached_pic", "1")])],
+            ..Default::default()
+        };
+        assert_eq!(kinds(&report(&to("ogg"), &info)), ["cover"]);
+        assert!(report(&to("mp3"), &info).is_empty());
+        let mut settings = to("ogg");
+        set_choice(&mut settings, Kind::CoverArt, Choice::Sidecar);
+        assert!(settings.cover_file && settings.data_loss.is_empty());
+    }
+
+    #[test]
+    fn stream_args_renumber_kept_subtitles() {
+        // mp4：PGS 去掉，ASS 和 srt 转成 mov_text，输出里是第 0、1 条
+        let argv = stream_args(&to("mp4"), &anime(), false).argv().join(" ");
+        assert_eq!(argv, "-map 0:V? -map 0:a:0? -map 0:s:0 -c:s:0 mov_text -map 0:s:2 -c:s:1 mov_text");
+        // 选了另存时主输出一条字幕也不带
+        let mut settings = to("mp4");
+        set_choice(&mut settings, Kind::TextSubtitles, Choice::Sidecar);
+        assert_eq!(stream_args(&settings, &anime(), true).argv().join(" "), "-sn");
+        // mkv 全带上，附件也是
+        let argv = stream_args(&to("mkv"), &anime(), true).argv().join(" ");
+        assert_eq!(argv, "-map 0:s:0 -c:s:0 copy -map 0:s:1 -c:s:1 copy -map 0:s:2 -c:s:2 copy -map 0:t -c:t copy");
+        // 没有字幕也没有附件时什么都不加
+        assert!(stream_args(&to("mp4"), &media_with_video(&[]), false).argv().is_empty());
+    }
+
+    #[test]
+    fn remux_args_drop_and_renumber() {
+        // -map 0 之后去掉 PGS；剩下的两条在输出里是第 0、1 条字幕
+        let argv = remux_args(&to("mp4"), &anime()).argv().join(" ");
+        assert_eq!(argv, "-c:s:0 mov_text -map -0:s:1 -c:s:1 mov_text -map -0:t");
+        // 被去掉的字幕在前面时，后面的序号往前挪
+        let info = MediaInfo {
+            streams: vec![stream("subtitle", "hdmv_pgs_subtitle", &[]), stream("subtitle", "mov_text", &[]), stream("subtitle", "ass", &[])],
+            ..Default::default()
+        };
+        let mut settings = to("mp4");
+        set_choice(&mut settings, Kind::TextSubtitles, Choice::Accept);
+        assert_eq!(remux_args(&settings, &info).argv().join(" "), "-map -0:s:0 -map -0:s:2");
+        assert_eq!(remux_args(&to("mp4"), &info).argv().join(" "), "-map -0:s:0 -c:s:1 mov_text");
+        // mkv 放不下 mov_text，转成 srt
+        assert_eq!(remux_args(&to("mkv"), &info).argv().join(" "), "-c:s:1 srt");
+    }
+
+    #[test]
+    fn chapters_follow_the_trim() {
+        let chapters = anime().chapters;
+        let settings = JobSettings { trim_start: Some(Duration::from_secs(60)), trim_end: Some(Duration::from_secs(1380)), ..to("avi") };
+        let spans: Vec<(f64, f64, String)> =
+            trimmed_chapters(&settings, "in.mkv", &chapters).into_iter().map(|c| (c.start, c.end, c.title)).collect();
+        assert_eq!(spans, [(0.0, 30.0, "OP".to_string()), (30.0, 1290.0, "本篇".to_string()), (1290.0, 1320.0, "ED".to_string())]);
+        // 范围外的章节不要
+        let late = JobSettings { trim_start: Some(Duration::from_secs(1400)), ..to("avi") };
+        let titles: Vec<String> = trimmed_chapters(&late, "in.mkv", &chapters).into_iter().map(|c| c.title).collect();
+        assert_eq!(titles, ["ED"]);
+        assert_eq!(trimmed_chapters(&to("avi"), "in.mkv", &chapters).len(), 3);
+    }
+
+    #[test]
+    fn choices_round_trip() {
+        let choices = parse_choices("text_subs=sidecar, hdr=accept,,text_subs=convert").unwrap();
+        // 同一类写了两次时后面的算数
+        assert!(choices == [(Kind::Hdr, Choice::Accept), (Kind::TextSubtitles, Choice::Convert)]);
+        let text = format_choices(&choices);
+        assert_eq!(text, "hdr=accept,text_subs=convert");
+        assert!(parse_choices(&text).unwrap() == choices);
+        assert!(parse_choices("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_choices_are_rejected() {
+        // 封面用 cover_file 记，不写在这里
+        assert_eq!(parse_choices("cover=sidecar").err().unwrap(), "未知的数据类别 cover");
+        assert_eq!(parse_choices("fonts=accept").err().unwrap(), "未知的数据类别 fonts");
+        assert_eq!(parse_choices("hdr=keep").err().unwrap(), "未知的处理方式 keep");
+        assert_eq!(parse_choices("hdr").err().unwrap(), "数据丢失处理写法不对: hdr");
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+
+use crate::config;
+use crate::history::{self, Record};
+use crate::json;
+use crate::paths;
+use crate::probe;
+use crate::process;
+use crate::zipfile;
+
+// 诊断包：一次转换的设置、命令、日志、输入文件的探测结果和运行环境打成一个 zip，
+// 用户把它发过来就能在别的机器上复现。不含音视频内容本身
+
+pub const PRIVACY_NOTE: &str = "诊断包不含音视频内容，但含有输入、输出文件的完整路径和文件名（可能带有用户名、项目名）、\
+ffmpeg 的完整命令和设置文件的内容。发给别人之前请确认这些信息可以公开。";
+
+pub struct Bundle {
+    // 转换记录的编号（结束时间），也用在文件名里
+    pub id: u64,
+    // (包里的文件名, 内容)
+    pub files: Vec<(String, String)>,
+}
+
+impl Bundle {
+    // log 是界面上的日志，命令行模式下没有。探测输入和 ffmpeg 版本用这次转换选的 ffmpeg
+    pub fn from_record(record: &Record, log: Option<&str>) -> Bundle {
+        let build = json::parse(&record.settings)
+            .ok()
+            .and_then(|v| v.get("ffmpeg").and_then(|b| b.as_str()).map(|b| b.to_string()))
+            .unwrap_or_default();
+        let previous = process::build();
+        process::use_build(&build);
+        let missing = "（这条记录来自旧版本，没有保存）\n".to_string();
+        let or_missing = |text: &str| if text.is_empty() { missing.clone() } else { format!("{}\n", text.trim_end()) };
+        let mut files = vec![
+            ("README.txt".to_string(), readme(record)),
+            ("settings.json".to_string(), or_missing(&record.settings)),
+            ("command.txt".to_string(), or_missing(&record.command)),
+            ("stderr.txt".to_string(), if record.ok { "（转换成功，没有保存错误输出）\n".to_string() } else { or_missing(&record.stderr_tail) }),
+        ];
+        if let Some(log) = log {
+            files.push(("log.txt".to_string(), log.to_string()));
+        }
+        let probed = probe::json(&record.input).unwrap_or_else(|e| format!("无法读取输入文件的媒体信息: {}\n", e));
+        files.push(("ffprobe.json".to_string(), probed));
+        files.push(("environment.txt".to_string(), environment(&build)));
+        files.push(("versions.txt".to_string(), versions()));
+        process::use_build(&previous);
+        Bundle { id: record.time, files }
+    }
+
+    pub fn size(&self) -> usize {
+        self.files.iter().map(|(_, text)| text.len()).sum()
+    }
+
+    pub fn write(&self) -> io::Result<PathBuf> {
+        let dir = bundle_dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("diagnose-{}.zip", self.id));
+        let files: Vec<(String, Vec<u8>)> = self.files.iter().map(|(name, text)| (name.clone(), text.clone().into_bytes())).collect();
+        let mut out = BufWriter::new(File::create(&path)?);
+        zipfile::write(&mut out, &files, history::now())?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(path)
+    }
+}
+
+pub fn bundle_dir() -> PathBuf {
+    paths::store_dir().join("diagnostics")
+}
+
+fn readme(record: &Record) -> String {
+    format!(
+        "ffui 诊断包\n记录编号: {}\n结果: {}\n输入: {}\n输出: {}\n编码器: {}\n媒体时长: {:.3} 秒，耗时 {:.3} 秒\n\n{}\n",
+        record.time,
+        if record.ok { "成功" } else { "失败" },
+        record.input,
+        record.output,
+        record.encoder,
+        record.media_secs,
+        record.encode_secs,
+        PRIVACY_NOTE,
+    )
+}
+
+// 系统、ffmpeg/ffprobe 的位置和版本
+fn environment(build: &str) -> String {
+    let mut out = format!(
+        "系统: {} {}\n数据目录: {}{}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        paths::store_dir().display(),
+        if paths::is_portable() { "（便携模式）" } else { "" },
+    );
+    out.push_str(&format!("ffmpeg 版本: {}\n", if build.is_empty() { "默认" } else { build }));
+    for name in ["ffmpeg", "ffprobe"] {
+        out.push_str(&format!("{}: {}\n", name, process::tool(name).display()));
+    }
+    if let Some(missing) = config::missing_tools() {
+        out.push_str(&format!("{}\n", missing));
+    }
+    out.push('\n');
+    match process::command("ffmpeg").arg("-version").output() {
+        Ok(output) => out.push_str(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => out.push_str(&format!("{}\n", process::FfError::launch("ffmpeg", e))),
+    }
+    out
+}
+
+// ffui 和各个文件格式的版本，以及设置文件的内容
+fn versions() -> String {
+    format!(
+        "ffui {}\n设置文件格式 {}\n转换记录格式 {}\n\n=== 设置 ===\n{}",
+        env!("CARGO_PKG_VERSION"),
+        config::VERSION,
+        history::HEADER.trim_start_matches("# ffui-history "),
+        config::snapshot(),
+    )
+}
+
+// --diagnose <记录编号>：不打开界面，把诊断包的内容列出来再写出，打印保存位置
+pub fn run_headless(id: &str) -> i32 {
+    let record = match id.trim().parse::<u64>().ok().and_then(history::find) {
+        Some(record) => record,
+        None => {
+            eprintln!("找不到编号为 {} 的转换记录。最近的记录:", id);
+            for r in history::load().iter().rev().take(5) {
+                eprintln!("  {}  {}  {}", r.time, if r.ok { "成功" } else { "失败" }, r.input);
+            }
+            return 2;
+        }
+    };
+    let bundle = Bundle::from_record(&record, None);
+    println!("诊断包内容:");
+    for (name, text) in &bundle.files {
+        println!("  {:<16} {} 字节", name, text.len());
+    }
+    println!("{}", PRIVACY_NOTE);
+    match bundle.write() {
+        Ok(path) => {
+            println!("已保存到 {}", path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("无法写入诊断包: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::process;
+
+// 一个 ffmpeg 程序支持的编码器和滤镜
+#[derive(Default)]
+struct Capabilities {
+    encoders: HashSet<String>,
+    filters: HashSet<String>,
+}
+
+// 任务可以指定不同版本的 ffmpeg，按程序路径分别缓存，每个只在第一次用到时查询。
+// 当前线程用哪个 ffmpeg 由 process::use_build 决定
+fn capabilities() -> Arc<Capabilities> {
+    static CACHE: Mutex<Option<HashMap<PathBuf, Arc<Capabilities>>>> = Mutex::new(None);
+    let program = process::tool("ffmpeg");
+    if let Some(caps) = CACHE.lock().unwrap().get_or_insert_with(HashMap::new).get(&program) {
+        return caps.clone();
+    }
+    // 查询不持锁，其他线程问别的 ffmpeg 不用等
+    let caps = Arc::new(Capabilities { encoders: list("-encoders"), filters: list("-filters") });
+    CACHE.lock().unwrap().get_or_insert_with(HashMap::new).insert(program, caps.clone());
+    caps
+}
+
+// " V....D libsvtav1            SVT-AV1(...)"
+// " ... subtitles         V->V       Render text subtitles onto input video using the libass library."
+fn list(option: &str) -> HashSet<String> {
+    let Ok(output) = process::command("ffmpeg").args(["-hide_banner", option]).output() else {
+        return HashSet::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            // 编码器前面是 6 个标志字符，滤镜是 3 个
+            let width = if option == "-filters" { 3 } else { 6 };
+            if parts.next()?.len() != width {
+                return None;
+            }
+            parts.next().map(|n| n.to_string())
+        })
+        .collect()
+}
+
+// ffmpeg -encoders 列出的编码器名
+pub fn available(name: &str) -> bool {
+    capabilities().encoders.contains(name)
+}
+
+// ffmpeg -filters 列出的滤镜名（如 subtitles 需要 libass，drawtext 需要 freetype）。
+// 查不到列表时（ffmpeg 运行不了）不下结论，当作有
+pub fn filter_available(name: &str) -> bool {
+    let caps = capabilities();
+    caps.filters.is_empty() || caps.filters.contains(name)
+}
+
+// 设置里列出 ffmpeg 版本时显示：编码器和滤镜各多少个
+pub fn summary() -> String {
+    let caps = capabilities();
+    if caps.encoders.is_empty() {
+        return "无法运行".to_string();
+    }
+    format!("{} 个编码器，{} 个滤镜", caps.encoders.len(), caps.filters.len())
+}
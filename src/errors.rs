@@ -0,0 +1,151 @@
+use std::io;
+
+// 常见失败原因及给用户看的提示
+#[derive(Clone, Copy, PartialEq)]
+pub struct ErrorHint {
+    pub message: &'static str,
+    // 换个文件名重试可能解决问题
+    pub retry_rename: bool,
+}
+
+const LOCKED: ErrorHint = ErrorHint {
+    message: "输出文件正被其他程序占用",
+    retry_rename: true,
+};
+const DENIED: ErrorHint = ErrorHint {
+    message: "没有写入输出文件的权限（文件或文件夹可能是只读的）",
+    retry_rename: true,
+};
+const EXISTS: ErrorHint = ErrorHint {
+    message: "输出文件已存在，且设置为不覆盖",
+    retry_rename: true,
+};
+const READ_ONLY: ErrorHint = ErrorHint {
+    message: "输出位置所在的磁盘是只读的",
+    retry_rename: false,
+};
+// 直接复制的流（仅转换封装、音频选了直接复制）目标容器装不下
+const UNSUPPORTED_CODEC: ErrorHint = ErrorHint {
+    message: "目标格式装不下源文件里的某种编码（如 mp4 里放不下 ASS 字幕或 PCM 音频），请换一种目标格式或改为重新编码，详见 ffmpeg 错误输出",
+    retry_rename: false,
+};
+
+const UNKNOWN_ENCODER: ErrorHint = ErrorHint {
+    message: "这个 ffmpeg 没有所选的编码器，请换一种编码，或在“高级”里换一个带有该编码器的 ffmpeg 版本",
+    retry_rename: false,
+};
+const NOT_FOUND: ErrorHint = ErrorHint {
+    message: "找不到文件或文件夹（输入文件、字幕、字体或输出文件夹），请检查路径是否还在",
+    retry_rename: false,
+};
+const INVALID_DATA: ErrorHint = ErrorHint {
+    message: "输入文件已损坏，或者不是 ffmpeg 能识别的格式",
+    retry_rename: false,
+};
+// 选了 NVIDIA 但没有装驱动（或驱动太旧）
+const NO_CUDA: ErrorHint = ErrorHint {
+    message: "无法加载 NVIDIA 驱动（nvcuda.dll），请安装或更新显卡驱动，或改用 CPU 编码",
+    retry_rename: false,
+};
+
+// ffmpeg/ffprobe 找不到或启动不了
+pub const NO_FFMPEG: ErrorHint = ErrorHint {
+    message: "无法启动 ffmpeg/ffprobe，请在“高级”里检查 ffmpeg 目录",
+    retry_rename: false,
+};
+
+// stderr 片段 -> 提示，按顺序匹配，不区分大小写
+const STDERR_PATTERNS: &[(&str, ErrorHint)] = &[
+    ("already exists. exiting", EXISTS),
+    ("being used by another process", LOCKED),
+    ("sharing violation", LOCKED),
+    ("text file busy", LOCKED),
+    ("read-only file system", READ_ONLY),
+    ("permission denied", DENIED),
+    ("could not find tag for codec", UNSUPPORTED_CODEC),
+    ("not currently supported in container", UNSUPPORTED_CODEC),
+    ("unknown encoder", UNKNOWN_ENCODER),
+    ("encoder not found", UNKNOWN_ENCODER),
+    ("cannot load nvcuda.dll", NO_CUDA),
+    ("cannot load libcuda.so", NO_CUDA),
+    ("invalid data found when processing input", INVALID_DATA),
+    ("no such file or directory", NOT_FOUND),
+];
+
+pub fn match_stderr_line(line: &str) -> Option<ErrorHint> {
+    let line = line.to_lowercase();
+    STDERR_PATTERNS
+        .iter()
+        .find(|(pat, _)| line.contains(pat))
+        .map(|(_, hint)| *hint)
+}
+
+pub fn match_stderr<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<ErrorHint> {
+    lines.into_iter().find_map(match_stderr_line)
+}
+
+pub fn explain_io_error(e: &io::Error) -> ErrorHint {
+    match e.raw_os_error() {
+        // ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION
+        #[cfg(target_os = "windows")]
+        Some(32) | Some(33) => return LOCKED,
+        // ETXTBSY
+        #[cfg(unix)]
+        Some(26) => return LOCKED,
+        // EROFS
+        #[cfg(unix)]
+        Some(30) => return READ_ONLY,
+        _ => {}
+    }
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => DENIED,
+        _ => ErrorHint {
+            message: "无法写入输出位置",
+            retry_rename: false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stderr_lines_map_to_hints() {
+        let locked = [
+            "[out#0/mp4 @ 0x1] Error opening output out.mp4: The process cannot access the file because it is being used by another process.",
+            "out.mp4: Sharing violation",
+            "out.mp4: Text file busy",
+        ];
+        for line in locked {
+            assert!(match_stderr_line(line) == Some(LOCKED), "{}", line);
+        }
+        assert!(match_stderr_line("out.mp4: Permission denied") == Some(DENIED));
+        assert!(match_stderr_line("out.mp4: Read-only file system") == Some(READ_ONLY));
+        assert!(match_stderr_line("File 'out.mp4' already exists. Exiting.") == Some(EXISTS));
+        assert!(match_stderr_line("frame=  100 fps=25").is_none());
+    }
+
+    #[test]
+    fn first_matching_line_wins() {
+        let lines = ["Stream mapping:", "out.mp4: Text file busy", "out.mp4: Permission denied"];
+        assert!(match_stderr(lines) == Some(LOCKED));
+        assert!(match_stderr(["nothing", "here"]).is_none());
+    }
+
+    #[test]
+    fn io_errors_map_to_hints() {
+        assert!(explain_io_error(&io::Error::from(io::ErrorKind::PermissionDenied)) == DENIED);
+        assert!(!explain_io_error(&io::Error::from(io::ErrorKind::NotFound)).retry_rename);
+        #[cfg(target_os = "windows")]
+        {
+            assert!(explain_io_error(&io::Error::from_raw_os_error(32)) == LOCKED);
+            assert!(explain_io_error(&io::Error::from_raw_os_error(33)) == LOCKED);
+        }
+        #[cfg(unix)]
+        {
+            assert!(explain_io_error(&io::Error::from_raw_os_error(26)) == LOCKED);
+            assert!(explain_io_error(&io::Error::from_raw_os_error(30)) == READ_ONLY);
+        }
+    }
+}
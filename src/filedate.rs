@@ -0,0 +1,95 @@
+use std::fs::{self, FileTimes, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::probe::MediaInfo;
+
+// 保留拍摄日期：录制时间写进容器的 creation_time，转换完成后再把源文件的
+// 修改/创建时间复制到输出，照片库按时间排序时家庭录像不会都挤到转换那天
+
+// 相机和手机常用的几种标签，ffprobe 的 flat 输出把键名里的 . 换成了 _
+const DATE_TAGS: &[&str] = &[
+    "tags.creation_time",
+    "tags.com_apple_quicktime_creationdate",
+    "tags.date",
+];
+
+// 设备没设时钟时写的占位日期，不当作真正的录制时间
+fn placeholder(date: &str) -> bool {
+    date.starts_with("1970-01-01") || date.starts_with("1904-01-01") || date.starts_with("0000")
+}
+
+// 至少要有 YYYY-MM-DD
+fn looks_like_date(date: &str) -> bool {
+    let b = date.as_bytes();
+    b.len() >= 10 && b[..4].iter().all(u8::is_ascii_digit) && b[4] == b'-' && b[7] == b'-'
+}
+
+// 1970-01-01 起的天数换成年月日（Howard Hinnant 的 civil_from_days）
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+// 和 ffmpeg 写 creation_time 的格式一致：2024-05-01T08:30:00.000000Z
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let rest = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000000Z",
+        year, month, day, rest / 3600, rest % 3600 / 60, rest % 60
+    )
+}
+
+// 录制时间和来源说明：先找容器标签，再找各条流的标签，都没有时用源文件的修改时间
+pub fn recording_date(info: &MediaInfo, input: &str) -> Option<(String, &'static str)> {
+    let tagged = DATE_TAGS.iter()
+        .filter_map(|tag| info.format.get(*tag))
+        .chain(info.streams.iter().flat_map(|s| DATE_TAGS.iter().filter_map(|tag| s.props.get(*tag))))
+        .map(|date| date.trim())
+        .find(|date| looks_like_date(date) && !placeholder(date));
+    if let Some(date) = tagged {
+        return Some((date.to_string(), "源文件元数据"));
+    }
+    let modified = fs::metadata(input).and_then(|m| m.modified()).ok()?;
+    Some((format_utc(modified), "源文件修改时间"))
+}
+
+// 把 src 的访问/修改时间（Windows 上还有创建时间）复制到 dst。
+// 要在临时文件改名之后调用，否则改名后的新文件会带着当前时间
+pub fn copy_times(src: &Path, dst: &Path) -> io::Result<()> {
+    let meta = fs::metadata(src)?;
+    let mut times = FileTimes::new().set_modified(meta.modified()?);
+    if let Ok(accessed) = meta.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::fs::FileTimesExt;
+        if let Ok(created) = meta.created() {
+            times = times.set_created(created);
+        }
+    }
+    OpenOptions::new().write(true).open(dst)?.set_times(times)
+}
+
+// 复制到任务的每个输出，返回写进日志/报告的一行说明
+pub fn copy_to_outputs(input: &str, outputs: &[String]) -> String {
+    let failed: Vec<String> = outputs.iter()
+        .filter_map(|out| copy_times(Path::new(input), Path::new(out)).err().map(|e| format!("{}: {}", out, e)))
+        .collect();
+    if failed.is_empty() {
+        "已把源文件的时间戳复制到输出".to_string()
+    } else {
+        format!("无法复制时间戳 {}", failed.join("；"))
+    }
+}
@@ -0,0 +1,68 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+
+// 大小连续这么久不变才算写完
+const STABLE_FOR: Duration = Duration::from_secs(5);
+const POLL: Duration = Duration::from_secs(1);
+
+// 还有别的程序以写方式打开着文件（录屏、下载中）。
+// Windows 上只允许共享读去打开，对方持有写句柄时会共享冲突；其他系统没有强制锁，总是 false
+pub fn has_writer(path: &Path) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use std::fs::OpenOptions;
+        use std::os::windows::fs::OpenOptionsExt;
+        use winapi::um::winnt::FILE_SHARE_READ;
+        match OpenOptions::new().read(true).share_mode(FILE_SHARE_READ).open(path) {
+            Ok(_) => false,
+            Err(e) => e.raw_os_error() == Some(32),
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+// 判断文件是否已经写完：大小一段时间不变，并且没有写入方
+pub struct StableWatch {
+    last_size: Option<u64>,
+    since: Instant,
+}
+
+impl Default for StableWatch {
+    fn default() -> Self {
+        StableWatch { last_size: None, since: Instant::now() }
+    }
+}
+
+impl StableWatch {
+    pub fn poll(&mut self, path: &Path) -> io::Result<bool> {
+        let size = fs::metadata(path)?.len();
+        if self.last_size != Some(size) {
+            self.last_size = Some(size);
+            self.since = Instant::now();
+            return Ok(false);
+        }
+        Ok(self.since.elapsed() >= STABLE_FOR && !has_writer(path))
+    }
+}
+
+// 一直等到文件稳定；cancel 被取消或文件消失时返回 false
+pub fn wait_until_stable(path: &Path, cancel: &CancelToken) -> bool {
+    let mut watch = StableWatch::default();
+    while !cancel.is_cancelled() {
+        match watch.poll(path) {
+            Ok(true) => return File::open(path).is_ok(),
+            Ok(false) => thread::sleep(POLL),
+            Err(_) => return false,
+        }
+    }
+    false
+}
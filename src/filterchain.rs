@@ -0,0 +1,114 @@
+// 视频滤镜链里可以调整先后的几步。默认顺序就是原来固定的顺序：
+// 反交错 → 改帧率 → 像素比修正 → 字幕 → 补边 → 缩放。
+// 烧录时间、补最后一帧、变速和时间轴有关，硬件缩放要按编码器上限算，这些固定排在链的后面，不参与调整
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterStep {
+    Deinterlace,
+    FrameRate,
+    PixelAspect,
+    Subtitles,
+    Fit,
+    Scale,
+}
+
+impl FilterStep {
+    pub const ALL: [FilterStep; 6] = [
+        FilterStep::Deinterlace,
+        FilterStep::FrameRate,
+        FilterStep::PixelAspect,
+        FilterStep::Subtitles,
+        FilterStep::Fit,
+        FilterStep::Scale,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterStep::Deinterlace => "反交错",
+            FilterStep::FrameRate => "改帧率",
+            FilterStep::PixelAspect => "像素比修正",
+            FilterStep::Subtitles => "烧录字幕",
+            FilterStep::Fit => "补边",
+            FilterStep::Scale => "缩放",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            FilterStep::Deinterlace => "deinterlace",
+            FilterStep::FrameRate => "fps",
+            FilterStep::PixelAspect => "sar",
+            FilterStep::Subtitles => "subtitles",
+            FilterStep::Fit => "fit",
+            FilterStep::Scale => "scale",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<FilterStep> {
+        FilterStep::ALL.into_iter().find(|s| s.tag() == tag.trim())
+    }
+}
+
+// (必须在前的, 必须在后的, 原因)。隔行的画面一缩放、丢帧或叠加字幕，两场就混在一起没法再分开；
+// 像素比修正之前的宽高不是显示比例，补边和缩放会算错比例
+const RULES: &[(FilterStep, FilterStep, &str)] = &[
+    (FilterStep::Deinterlace, FilterStep::FrameRate, "反交错必须在改帧率之前"),
+    (FilterStep::Deinterlace, FilterStep::PixelAspect, "反交错必须在像素比修正之前"),
+    (FilterStep::Deinterlace, FilterStep::Subtitles, "反交错必须在烧录字幕之前"),
+    (FilterStep::Deinterlace, FilterStep::Fit, "反交错必须在补边之前"),
+    (FilterStep::Deinterlace, FilterStep::Scale, "反交错必须在缩放之前"),
+    (FilterStep::PixelAspect, FilterStep::Fit, "像素比修正必须在补边之前"),
+    (FilterStep::PixelAspect, FilterStep::Scale, "像素比修正必须在缩放之前"),
+];
+
+// 补全成每一步正好出现一次：重复的去掉，缺少的（旧设置、更新版本才有的步骤）按默认顺序接在后面
+pub fn normalized(order: &[FilterStep]) -> Vec<FilterStep> {
+    let mut out: Vec<FilterStep> = Vec::new();
+    for step in order.iter().chain(FilterStep::ALL.iter()) {
+        if !out.contains(step) {
+            out.push(*step);
+        }
+    }
+    out
+}
+
+// 违反的第一条规则
+pub fn check(order: &[FilterStep]) -> Result<(), &'static str> {
+    let at = |step: FilterStep| order.iter().position(|s| *s == step);
+    for (before, after, reason) in RULES {
+        if let (Some(a), Some(b)) = (at(*before), at(*after))
+            && a > b
+        {
+            return Err(reason);
+        }
+    }
+    Ok(())
+}
+
+// 实际使用的顺序：不合规则的（手改过的任务列表、预设）退回默认顺序
+pub fn effective(order: &[FilterStep]) -> Vec<FilterStep> {
+    let order = normalized(order);
+    if check(&order).is_ok() { order } else { FilterStep::ALL.to_vec() }
+}
+
+// 把第 i 步和第 j 步对调后的顺序，违反规则时返回原因
+pub fn swapped(order: &[FilterStep], i: usize, j: usize) -> Result<Vec<FilterStep>, &'static str> {
+    let mut order = normalized(order);
+    order.swap(i, j);
+    check(&order).map(|_| order)
+}
+
+// 任务列表和预设里写成 "deinterlace,fps,sar,subtitles,fit,scale"
+pub fn format_order(order: &[FilterStep]) -> String {
+    order.iter().map(|s| s.tag()).collect::<Vec<_>>().join(",")
+}
+
+pub fn parse_order(text: &str) -> Result<Vec<FilterStep>, String> {
+    let order = text
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| FilterStep::from_tag(t).ok_or(format!("未知的滤镜步骤 {}", t.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let order = normalized(&order);
+    check(&order)?;
+    Ok(order)
+}
@@ -0,0 +1,88 @@
+use eframe::egui;
+
+use crate::retime::Rate;
+
+// 改输出帧率：用 fps 滤镜丢帧或补帧，时长不变。和帧率重映射不同，这里不改播放速度，
+// 60 帧的录屏转成 30 帧就是每两帧留一帧
+
+// 界面上的固定选项，其余在自定义里输入
+pub const PRESETS: [u64; 4] = [60, 30, 25, 24];
+
+// 放在滤镜链里，和缩放等滤镜一起写进同一条 -vf
+pub fn filter(rate: Rate) -> String {
+    format!("fps={}", rate.tag())
+}
+
+// 帧率选择：保持原始、固定选项或自定义（25、23.976、30000/1001 都可以）。
+// 自定义的文字无法解析时保留原值，在旁边显示错误
+#[derive(Default)]
+pub struct RateField {
+    custom: bool,
+    text: String,
+    error: Option<String>,
+}
+
+impl RateField {
+    pub fn show(&mut self, ui: &mut egui::Ui, value: &mut Option<Rate>, source: Option<Rate>) {
+        let presets: Vec<Rate> = PRESETS.iter().filter_map(|&n| Rate::new(n, 1)).collect();
+        // 任务列表或“基于此任务新建”带来的不在固定选项里的值，按自定义显示
+        if value.is_some_and(|r| !presets.contains(&r)) && !self.custom {
+            self.custom = true;
+            self.text = value.map(|r| r.label()).unwrap_or_default();
+        }
+        ui.horizontal(|ui| {
+            let shown = match value {
+                _ if self.custom => "自定义".to_string(),
+                Some(rate) => format!("{} fps", rate.label()),
+                None => "保持原始".to_string(),
+            };
+            let combo = egui::ComboBox::from_label("帧率")
+                .selected_text(&shown)
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(!self.custom && value.is_none(), "保持原始").clicked() {
+                        (self.custom, self.error, *value) = (false, None, None);
+                    }
+                    for rate in &presets {
+                        if ui.selectable_label(!self.custom && *value == Some(*rate), format!("{} fps", rate.label())).clicked() {
+                            (self.custom, self.error, *value) = (false, None, Some(*rate));
+                        }
+                    }
+                    if ui.selectable_label(self.custom, "自定义").clicked() && !self.custom {
+                        self.custom = true;
+                        self.text = value.or(source).map(|r| r.label()).unwrap_or_default();
+                        self.parse(value);
+                    }
+                });
+            crate::a11y::selected(combo.response, &shown);
+            if self.custom {
+                let edit = ui.add(egui::TextEdit::singleline(&mut self.text).desired_width(80.0))
+                    .on_hover_text("如 50、23.976、30000/1001");
+                if edit.changed() {
+                    self.parse(value);
+                }
+                crate::a11y::named(edit, "自定义帧率");
+            }
+            if let Some(rate) = source {
+                ui.label(format!("源帧率 {} fps", rate.label()));
+            }
+            if let Some(e) = &self.error {
+                ui.colored_label(egui::Color32::RED, e);
+            }
+        });
+    }
+
+    fn parse(&mut self, value: &mut Option<Rate>) {
+        match Rate::parse(&self.text) {
+            Some(rate) if rate.as_f64() <= 1000.0 => {
+                *value = Some(rate);
+                self.error = None;
+            }
+            Some(_) => self.error = Some("帧率不能超过 1000".to_string()),
+            None => self.error = Some(format!("“{}” 不是有效的帧率", self.text.trim())),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,156 @@
+use crate::interlace::Deinterlace;
+use crate::plan::{self, JobSettings};
+use crate::probe::{self, MediaInfo};
+
+// 关键帧间隔（GOP）。HLS 的每个分片必须从关键帧开始：间隔不能整除分片时长、
+// 或者编码器在场景切换处自己插关键帧时，分片长短不一，播放器切换清晰度会卡顿
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Gop {
+    // 0 表示用编码器默认值
+    pub frames: u32,
+    // 固定间隔：最短间隔等于最长间隔，不在场景切换处插入关键帧
+    pub fixed: bool,
+}
+
+impl Gop {
+    pub fn label(&self) -> String {
+        match (self.frames, self.fixed) {
+            (0, _) => "编码器默认".to_string(),
+            (n, true) => format!("{} 帧，固定", n),
+            (n, false) => format!("{} 帧", n),
+        }
+    }
+}
+
+// 关键帧之间至少隔这么久，太密浪费码率
+const TARGET_SECS: f64 = 2.0;
+
+// 各编码器设置间隔和关闭场景切换关键帧的参数名不同
+pub fn args(encoder: &str, gop: Gop) -> Vec<String> {
+    if gop.frames == 0 {
+        return Vec::new();
+    }
+    let n = gop.frames.to_string();
+    let mut args = vec!["-g".to_string(), n.clone()];
+    if gop.fixed {
+        let extra: &[&str] = match encoder {
+            "libx264" | "libaom-av1" => &["-keyint_min", &n, "-sc_threshold", "0"],
+            "libx265" => &["-keyint_min", &n, "-x265-params", "scenecut=0"],
+            "libvpx-vp9" => &["-keyint_min", &n],
+            "h264_nvenc" | "hevc_nvenc" | "av1_nvenc" => &["-no-scenecut", "1", "-strict_gop", "1"],
+            "h264_qsv" | "hevc_qsv" | "av1_qsv" => &["-adaptive_i", "0"],
+            "h264_amf" | "hevc_amf" | "av1_amf" => &["-header_insertion_mode", "gop"],
+            // SVT-AV1 默认不做场景切换检测
+            _ => &[],
+        };
+        args.extend(extra.iter().map(|a| a.to_string()));
+    }
+    args
+}
+
+// 输出的帧率：IVTC 还原后约为原来的 4/5
+pub fn output_fps(settings: &JobSettings, info: &MediaInfo) -> Option<f64> {
+    if let Some(retime) = settings.retime {
+        return Some(retime.to.as_f64());
+    }
+    if let Some(rate) = plan::output_fps_override(settings) {
+        return Some(rate.as_f64());
+    }
+    let video = info.streams.iter().find(|s| s.codec_type == "video")?;
+    let fps = video.props.get("avg_frame_rate").and_then(|r| probe::parse_rate(r))
+        .or_else(|| video.props.get("r_frame_rate").and_then(|r| probe::parse_rate(r)))?;
+    Some(if settings.deinterlace == Deinterlace::Ivtc { fps * 4.0 / 5.0 } else { fps })
+}
+
+// 一个分片的帧数
+fn segment_frames(fps: f64, segment_secs: f64) -> u32 {
+    (fps * segment_secs).round().max(1.0) as u32
+}
+
+// 能整除分片、又不短于约 2 秒的间隔，6 秒分片 30 fps 时为 60 帧
+pub fn recommended(fps: f64, segment_secs: f64) -> u32 {
+    let frames = segment_frames(fps, segment_secs);
+    let parts = (segment_secs / TARGET_SECS).floor().max(1.0) as u32;
+    (1..=parts).rev().find(|p| frames.is_multiple_of(*p)).map(|p| frames / p).unwrap_or(frames)
+}
+
+// 间隔和分片时长不一致时返回说明
+pub fn check(gop: Gop, fps: f64, segment_secs: f64) -> Option<String> {
+    let frames = segment_frames(fps, segment_secs);
+    if gop.frames == 0 {
+        return Some(format!("没有设置关键帧间隔，编码器默认的间隔和 {} 秒分片对不上", segment_secs));
+    }
+    if gop.frames > frames || !frames.is_multiple_of(gop.frames) {
+        return Some(format!(
+            "关键帧间隔 {} 帧（{:.2} 秒）不能整除 {} 秒分片（{:.3} fps 下 {} 帧）",
+            gop.frames, gop.frames as f64 / fps, segment_secs, fps, frames
+        ));
+    }
+    if !gop.fixed {
+        return Some("场景切换处会插入额外的关键帧，分片长短不一".to_string());
+    }
+    None
+}
+
+// 自动修正：按帧率和分片时长设置固定间隔
+pub fn fix(fps: f64, segment_secs: f64) -> Gop {
+    Gop { frames: recommended(fps, segment_secs), fixed: true }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+
+    #[test]
+    fn recommended_divides_the_segment() {
+        assert_eq!(recommended(30.0, 6.0), 60);
+        assert_eq!(recommended(30000.0 / 1001.0, 6.0), 60);
+        assert_eq!(recommended(24000.0 / 1001.0, 6.0), 48);
+        assert_eq!(recommended(25.0, 4.0), 50);
+        assert_eq!(recommended(29.97, 10.0), 60);
+        // 分片短于 2 秒时一个分片一个关键帧
+        assert_eq!(recommended(24.0, 1.0), 24);
+        // 除不尽时退到整个分片
+        assert_eq!(recommended(23.0, 5.0), 115);
+        for (fps, secs) in [(30.0, 6.0), (25.0, 4.0), (60.0, 2.0), (23.0, 5.0)] {
+            let frames = (fps * secs) as u32;
+            assert!(frames.is_multiple_of(recommended(fps, secs)));
+            assert_eq!(check(fix(fps, secs), fps, secs), None);
+        }
+    }
+
+    #[test]
+    fn check_explains_mismatches() {
+        assert!(check(Gop { frames: 0, fixed: true }, 30.0, 6.0).unwrap().contains("没有设置"));
+        assert!(check(Gop { frames: 50, fixed: true }, 30.0, 6.0).unwrap().contains("不能整除"));
+        assert!(check(Gop { frames: 240, fixed: true }, 30.0, 6.0).unwrap().contains("不能整除"));
+        assert!(check(Gop { frames: 60, fixed: false }, 30.0, 6.0).unwrap().contains("场景切换"));
+        assert_eq!(check(Gop { frames: 180, fixed: true }, 30.0, 6.0), None);
+    }
+
+    #[test]
+    fn fixed_gop_args_per_encoder() {
+        let gop = Gop { frames: 48, fixed: true };
+        assert!(args("libx264", Gop::default()).is_empty());
+        assert_eq!(args("libx264", Gop { fixed: false, ..gop }), ["-g", "48"]);
+        assert_eq!(args("libx264", gop), ["-g", "48", "-keyint_min", "48", "-sc_threshold", "0"]);
+        assert_eq!(args("libx265", gop), ["-g", "48", "-keyint_min", "48", "-x265-params", "scenecut=0"]);
+        assert_eq!(args("hevc_nvenc", gop), ["-g", "48", "-no-scenecut", "1", "-strict_gop", "1"]);
+        assert_eq!(args("av1_qsv", gop), ["-g", "48", "-adaptive_i", "0"]);
+        assert_eq!(args("libsvtav1", gop), ["-g", "48"]);
+    }
+
+    #[test]
+    fn output_fps_follows_source_and_ivtc() {
+        let mut video = StreamInfo { codec_type: "video".to_string(), ..Default::default() };
+        video.props.insert("avg_frame_rate".to_string(), "30000/1001".to_string());
+        let info = MediaInfo { streams: vec![video], ..Default::default() };
+        let fps = output_fps(&JobSettings::default(), &info).unwrap();
+        assert!((fps - 29.97).abs() < 0.001);
+        let ivtc = JobSettings { deinterlace: Deinterlace::Ivtc, ..Default::default() };
+        assert!((output_fps(&ivtc, &info).unwrap() - 23.976).abs() < 0.001);
+        assert_eq!(output_fps(&JobSettings::default(), &MediaInfo::default()), None);
+        assert_eq!(Gop { frames: 60, fixed: true }.label(), "60 帧，固定");
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::paths;
+use crate::process;
+
+// 第一次选硬件设备时在后台试编码两秒，结果按 编码器 + 驱动版本 + ffmpeg 版本 缓存，
+// 驱动或 ffmpeg 更新后自动重测。ffmpeg -encoders 列出的编码器不一定真的能用。
+// 任务指定了其他 ffmpeg 版本时，那个版本的结果单独记一条
+const SOURCE: &str = "testsrc2=size=640x360:rate=30:duration=2";
+const LIMIT: Duration = Duration::from_secs(20);
+
+#[derive(Clone, PartialEq)]
+pub enum Status {
+    Testing,
+    Passed,
+    Failed(String),
+}
+
+#[derive(Clone)]
+struct Entry {
+    key: String,
+    status: Status,
+    // 测试失败时用户选择了“强制使用”
+    forced: bool,
+}
+
+fn cache_path() -> std::path::PathBuf {
+    paths::store_dir().join("gpu_tests.tsv")
+}
+
+// 编码器 \t 缓存键 \t 1/0 \t 失败原因 \t 强制使用
+fn load() -> HashMap<String, Entry> {
+    let text = fs::read_to_string(cache_path()).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split('\t').collect();
+            let status = match *f.get(2)? {
+                "1" => Status::Passed,
+                _ => Status::Failed(f.get(3).unwrap_or(&"").to_string()),
+            };
+            Some((f[0].to_string(), Entry { key: f[1].to_string(), status, forced: f.get(4) == Some(&"1") }))
+        })
+        .collect()
+}
+
+fn save(entries: &HashMap<String, Entry>) {
+    let mut text = String::new();
+    for (encoder, e) in entries {
+        let (ok, reason) = match &e.status {
+            Status::Passed => ("1", ""),
+            Status::Failed(reason) => ("0", reason.as_str()),
+            Status::Testing => continue,
+        };
+        let reason = reason.replace(['\t', '\n', '\r'], " ");
+        text.push_str(&format!("{}\t{}\t{}\t{}\t{}\n", encoder, e.key, ok, reason, if e.forced { 1 } else { 0 }));
+    }
+    if fs::create_dir_all(paths::store_dir()).is_ok() {
+        let _ = fs::write(cache_path(), text);
+    }
+}
+
+fn ffmpeg_version() -> String {
+    process::command("ffmpeg").arg("-version").output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).lines().next().unwrap_or("").trim().to_string())
+        .unwrap_or_default()
+}
+
+// 驱动版本：NVIDIA 用 nvidia-smi；其他厂商 Windows 上查 Win32_VideoController，
+// Linux 上驱动在内核里，用内核版本代替
+fn driver_version(encoder: &str) -> String {
+    if encoder.ends_with("_nvenc")
+        && let Ok(output) = process::command("nvidia-smi").args(["--query-gpu=driver_version", "--format=csv,noheader"]).output()
+        && output.status.success()
+    {
+        return String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("").trim().to_string();
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let vendor = if encoder.ends_with("_qsv") { "Intel" } else { "AMD" };
+        let script = "Get-CimInstance Win32_VideoController | ForEach-Object { $_.Name + '|' + $_.DriverVersion }";
+        if let Ok(output) = process::command("powershell").args(["-NoProfile", "-Command", script]).output() {
+            return String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|l| l.contains(vendor) || (vendor == "AMD" && l.contains("Radeon")))
+                .map(|l| l.trim().to_string())
+                .collect::<Vec<_>>()
+                .join(";");
+        }
+        String::new()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default().trim().to_string()
+    }
+}
+
+fn cache_key(encoder: &str) -> String {
+    format!("{}|{}", driver_version(encoder), ffmpeg_version())
+}
+
+// 用 lavfi 片源编码两秒丢弃输出，失败时返回 stderr 最后一行
+fn test_encode(encoder: &str) -> Status {
+    let args = ["-hide_banner", "-nostdin", "-f", "lavfi", "-i", SOURCE, "-c:v", encoder, "-f", "null", "-"];
+    match process::output_with_timeout(process::command("ffmpeg").args(args), LIMIT) {
+        Ok((Some(status), _, _)) if status.success() => Status::Passed,
+        Ok((Some(_), _, stderr)) => Status::Failed(
+            stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("ffmpeg 异常退出").trim().to_string(),
+        ),
+        Ok((None, _, _)) => Status::Failed(format!("超过 {} 秒没有完成", LIMIT.as_secs())),
+        Err(e) => Status::Failed(format!("无法启动 ffmpeg: {}", e)),
+    }
+}
+
+// 缓存里的名字：默认 ffmpeg 是编码器名，其他版本是 编码器@版本名
+fn slot(encoder: &str) -> String {
+    match process::build() {
+        build if build.is_empty() => encoder.to_string(),
+        build => format!("{}@{}", encoder, build),
+    }
+}
+
+// 界面持有一份，按需在后台测试，每帧只读内存里的结果
+#[derive(Clone)]
+pub struct GpuTests {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    // 本次运行已经核对过缓存键的编码器
+    checked: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Default for GpuTests {
+    fn default() -> Self {
+        GpuTests { entries: Arc::new(Mutex::new(load())), checked: Arc::default() }
+    }
+}
+
+impl GpuTests {
+    // 每次运行第一次用到某个编码器时在后台算缓存键：和缓存一致就沿用结果，
+    // 没有缓存或驱动/ffmpeg 变了就重新测试，之前的“强制使用”也一并作废
+    pub fn ensure(&self, encoder: &str) {
+        let slot = slot(encoder);
+        if !self.checked.lock().unwrap().insert(slot.clone()) {
+            return;
+        }
+        let cached = {
+            let mut all = self.entries.lock().unwrap();
+            all.entry(slot.clone())
+                .or_insert(Entry { key: String::new(), status: Status::Testing, forced: false })
+                .clone()
+        };
+        let (all, encoder, build) = (self.entries.clone(), encoder.to_string(), process::build());
+        thread::spawn(move || {
+            process::use_build(&build);
+            let key = cache_key(&encoder);
+            if cached.key == key && cached.status != Status::Testing {
+                return;
+            }
+            all.lock().unwrap().insert(slot.clone(), Entry { key: key.clone(), status: Status::Testing, forced: false });
+            let status = test_encode(&encoder);
+            let mut all = all.lock().unwrap();
+            all.insert(slot, Entry { key, status, forced: false });
+            save(&all);
+        });
+    }
+
+    pub fn status(&self, encoder: &str) -> Option<Status> {
+        self.entries.lock().unwrap().get(&slot(encoder)).map(|e| e.status.clone())
+    }
+
+    pub fn forced(&self, encoder: &str) -> bool {
+        self.entries.lock().unwrap().get(&slot(encoder)).is_some_and(|e| e.forced)
+    }
+
+    pub fn set_forced(&self, encoder: &str, forced: bool) {
+        let mut all = self.entries.lock().unwrap();
+        if let Some(e) = all.get_mut(&slot(encoder)) {
+            e.forced = forced;
+            save(&all);
+        }
+    }
+
+    // 测试失败且没有强制使用时不能开始转换
+    pub fn blocked(&self, encoder: &str) -> bool {
+        matches!(self.status(encoder), Some(Status::Failed(_))) && !self.forced(encoder)
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::paths;
+
+// 每次转换结束追加一条，制表符分隔，一行一条
+#[derive(Clone)]
+pub struct Record {
+    pub time: u64, // 结束时间，unix 秒
+    pub input: String,
+    pub output: String,
+    pub encoder: String,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub media_secs: f64,
+    pub encode_secs: f64,
+    pub ok: bool,
+    // 失败时 ffmpeg stderr 的最后几百行
+    pub stderr_tail: String,
+    // 开始转换前计算的源文件 SHA-256，没有计算时为空
+    pub source_sha256: String,
+    // 成功但 stderr 里有值得注意的警告时，警告名逗号分隔
+    pub warnings: String,
+    // 实际使用的设置（任务列表的 JSON 格式）和完整的 ffmpeg 命令（多次调用一行一条），生成诊断包时用
+    pub settings: String,
+    pub command: String,
+}
+
+// 首行记录格式版本。v1 没有首行，只有前 9 或 10 列；v2 增加源文件校验值；v3 增加警告；v4 增加设置和命令
+pub const HEADER: &str = "# ffui-history 4";
+
+pub fn history_path() -> PathBuf {
+    paths::store_dir().join("history.tsv")
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn clean(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+// 多行文本存成一个字段：\ \t \n 转义
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn format_line(record: &Record) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{:.3}\t{:.3}\t{}\t{}\t{}\t{}\t{}\t{}",
+        record.time,
+        clean(&record.input),
+        clean(&record.output),
+        clean(&record.encoder),
+        record.input_bytes,
+        record.output_bytes,
+        record.media_secs,
+        record.encode_secs,
+        if record.ok { 1 } else { 0 },
+        escape(&record.stderr_tail),
+        clean(&record.source_sha256),
+        clean(&record.warnings),
+        escape(&record.settings),
+        escape(&record.command),
+    )
+}
+
+// 旧版本的记录文件按当前格式整体重写一遍，先写临时文件再改名
+fn migrate() -> io::Result<()> {
+    let path = history_path();
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if text.lines().next() == Some(HEADER) {
+        return Ok(());
+    }
+    let mut out = format!("{}\n", HEADER);
+    for record in text.lines().filter_map(parse_line) {
+        out.push_str(&format_line(&record));
+        out.push('\n');
+    }
+    let tmp = path.with_extension("tsv.tmp");
+    fs::write(&tmp, out)?;
+    fs::rename(&tmp, &path)
+}
+
+pub fn append(record: &Record) -> io::Result<()> {
+    fs::create_dir_all(paths::store_dir())?;
+    migrate()?;
+    let mut file = OpenOptions::new().create(true).append(true).open(history_path())?;
+    writeln!(file, "{}", format_line(record))
+}
+
+fn parse_line(line: &str) -> Option<Record> {
+    if line.starts_with('#') {
+        return None;
+    }
+    let f: Vec<&str> = line.split('\t').collect();
+    if f.len() < 9 {
+        return None;
+    }
+    Some(Record {
+        time: f[0].parse().ok()?,
+        input: f[1].to_string(),
+        output: f[2].to_string(),
+        encoder: f[3].to_string(),
+        input_bytes: f[4].parse().ok()?,
+        output_bytes: f[5].parse().ok()?,
+        media_secs: f[6].parse().ok()?,
+        encode_secs: f[7].parse().ok()?,
+        ok: f[8] == "1",
+        // 旧记录没有这一列
+        stderr_tail: f.get(9).map(|t| unescape(t)).unwrap_or_default(),
+        source_sha256: f.get(10).unwrap_or(&"").to_string(),
+        warnings: f.get(11).unwrap_or(&"").to_string(),
+        settings: f.get(12).map(|t| unescape(t)).unwrap_or_default(),
+        command: f.get(13).map(|t| unescape(t)).unwrap_or_default(),
+    })
+}
+
+// 读不了的行直接跳过，不影响其余记录
+pub fn load() -> Vec<Record> {
+    fs::read_to_string(history_path())
+        .map(|text| text.lines().filter_map(parse_line).collect())
+        .unwrap_or_default()
+}
+
+// 记录的编号就是结束时间（unix 秒），同一秒有多条时取最后一条
+pub fn find(id: u64) -> Option<Record> {
+    load().into_iter().rev().find(|r| r.time == id)
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use crate::plan::{self, JobSettings};
+use crate::process;
+
+// 转换结束后运行的用户命令。模板先按空白和引号切成参数，再在每个参数里替换占位符，
+// 路径里的空格和引号不会把参数拆开，也不经过 shell
+pub const PLACEHOLDERS: &str = "{input} {output} {status} {preset}";
+
+// "..." 里可以用 \" 和 \\；'...' 原样保留；引号外的 \ 是普通字符（Windows 路径）
+pub fn tokenize(template: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"') | Some('\\')) => current.push(chars.next().unwrap()),
+                        Some(c) => current.push(c),
+                        None => return Err("双引号没有闭合".to_string()),
+                    }
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err("单引号没有闭合".to_string()),
+                    }
+                }
+            }
+            c => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+// 只替换认识的占位符，其余花括号原样保留
+fn substitute(arg: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        match vars.iter().find(|(name, _)| tail[1..].starts_with(name) && tail[1 + name.len()..].starts_with('}')) {
+            Some((name, value)) => {
+                out.push_str(value);
+                rest = &tail[name.len() + 2..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn expand(template: &str, vars: &[(&str, &str)]) -> Result<Vec<String>, String> {
+    Ok(tokenize(template)?.iter().map(|a| substitute(a, vars)).collect())
+}
+
+// 运行钩子并返回要写进任务日志的文字。退出码非零或超时只算警告，不影响转换结果
+fn run(template: &str, vars: &[(&str, &str)], timeout: Duration) -> String {
+    let args = match expand(template, vars) {
+        Ok(args) if !args.is_empty() => args,
+        Ok(_) => return String::new(),
+        Err(e) => return format!("⚠ 完成后命令无法解析: {}\n", e),
+    };
+    let mut log = format!("=== 运行完成后命令: {} ===\n", plan::quote_command(&args[0], &args[1..]));
+    match process::output_with_timeout(process::user_command(&args[0]).args(&args[1..]), timeout) {
+        Ok((status, stdout, stderr)) => {
+            for text in [stdout, stderr] {
+                if !text.trim().is_empty() {
+                    log.push_str(text.trim_end());
+                    log.push('\n');
+                }
+            }
+            match status {
+                Some(s) if s.success() => {}
+                Some(s) => log.push_str(&format!("⚠ 完成后命令退出码 {}\n", s.code().unwrap_or(-1))),
+                None => log.push_str(&format!("⚠ 完成后命令超过 {} 秒，已结束\n", timeout.as_secs())),
+            }
+        }
+        Err(e) => log.push_str(&format!("⚠ 无法运行完成后命令: {}\n", e)),
+    }
+    log
+}
+
+// 按结果选成功或失败的钩子；没有配置时返回空字符串。
+// {preset} 是格式、设备和编码的简述，如 mp4-CPU-H.264，和命名预设无关
+pub fn after_job(settings: &JobSettings, input: &str, output: &str, ok: bool) -> String {
+    let template = if ok { &settings.hook_success } else { &settings.hook_failure };
+    if template.trim().is_empty() {
+        return String::new();
+    }
+    let preset = format!("{}-{}-{}", settings.format, settings.gpu, settings.codec.label());
+    let vars = [("input", input), ("output", output), ("status", if ok { "ok" } else { "failed" }), ("preset", preset.as_str())];
+    run(template, &vars, Duration::from_secs(settings.hook_timeout_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(tokenize(r#"notify  "a b" 'c "d"' e"f"g"#).unwrap(), ["notify", "a b", r#"c "d""#, "efg"]);
+        assert_eq!(tokenize(r#""say \"hi\" \\ \n""#).unwrap(), [r#"say "hi" \ \n"#]);
+        // 引号外的反斜杠是 Windows 路径
+        assert_eq!(tokenize(r"C:\tools\up.exe {output}").unwrap(), [r"C:\tools\up.exe", "{output}"]);
+        assert_eq!(tokenize(r#"a "" ''"#).unwrap(), ["a", "", ""]);
+        assert!(tokenize("   ").unwrap().is_empty());
+        assert!(tokenize(r#"a "b"#).is_err());
+        assert!(tokenize("a 'b").is_err());
+    }
+
+    #[test]
+    fn placeholders_are_replaced_inside_arguments() {
+        let vars = [("input", "C:\\My Videos\\a \"b\".mov"), ("output", "{status}"), ("status", "ok")];
+        let args = expand("copy {input} --to=x/{output}.mp4 {unknown} {status", &vars).unwrap();
+        // 替换进来的值不会再被替换，也不会拆开参数
+        assert_eq!(args, ["copy", "C:\\My Videos\\a \"b\".mov", "--to=x/{status}.mp4", "{unknown}", "{status"]);
+        assert_eq!(expand("{input}{status}", &vars).unwrap(), ["C:\\My Videos\\a \"b\".mov".to_string() + "ok"]);
+    }
+
+    #[test]
+    fn empty_hook_does_nothing() {
+        let settings = JobSettings { hook_success: "  ".to_string(), ..Default::default() };
+        assert_eq!(after_job(&settings, "a", "b", true), "");
+        assert_eq!(after_job(&settings, "a", "b", false), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_exit_code_and_timeout_are_logged() {
+        let settings = JobSettings {
+            hook_success: "sh -c 'echo done {status} {preset}'".to_string(),
+            hook_failure: "sh -c 'exit 3'".to_string(),
+            ..Default::default()
+        };
+        let log = after_job(&settings, "in", "out", true);
+        assert!(log.contains("done ok mp4-"), "{}", log);
+        assert!(!log.contains('⚠'), "{}", log);
+        assert!(after_job(&settings, "in", "out", false).contains("退出码 3"));
+        let log = run("sleep 5", &[], Duration::from_millis(200));
+        assert!(log.contains("已结束"), "{}", log);
+        assert!(run("a 'b", &[], Duration::from_secs(1)).contains("无法解析"));
+        assert!(run("/nonexistent/ffui-hook", &[], Duration::from_secs(1)).contains("无法运行"));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::aspect;
+use crate::plan::{self, JobSettings};
+use crate::probe::MediaInfo;
+
+// 硬件编码器能接受的最大宽高。ffmpeg -h encoder= 不列出这个限制，
+// 也无法可靠地区分显卡代数，这里按各家较老但仍常见的型号取保守值
+pub fn max_size(encoder: &str) -> Option<(u32, u32)> {
+    match encoder {
+        "h264_nvenc" | "hevc_nvenc" => Some((4096, 4096)),
+        "av1_nvenc" => Some((8192, 8192)),
+        "h264_qsv" | "hevc_qsv" => Some((4096, 2304)),
+        "av1_qsv" => Some((8192, 8192)),
+        "h264_amf" | "hevc_amf" => Some((4096, 2160)),
+        "av1_amf" => Some((8192, 4352)),
+        _ => None,
+    }
+}
+
+// 超出上限时等比缩小到上限以内，宽高取偶数
+pub fn fit_within((w, h): (u32, u32), (max_w, max_h): (u32, u32)) -> Option<(u32, u32)> {
+    if w <= max_w && h <= max_h {
+        return None;
+    }
+    let scale = (max_w as f64 / w as f64).min(max_h as f64 / h as f64);
+    let even = |v: u32| ((v as f64 * scale) as u32 / 2 * 2).max(2);
+    Some((even(w), even(h)))
+}
+
+pub struct Oversize {
+    pub encoder: &'static str,
+    pub size: (u32, u32),
+    pub limit: (u32, u32),
+    pub scaled: (u32, u32),
+}
+
+// 送进编码器的画面（补边、缩放后）是否超过所选硬件编码器的上限
+pub fn check(settings: &JobSettings, info: &MediaInfo) -> Option<Oversize> {
+    if settings.gpu == "CPU" || settings.web.is_some() || !plan::is_video_container(&settings.format) {
+        return None;
+    }
+    let encoder = plan::video_codec(settings);
+    let limit = max_size(encoder)?;
+    let (w, h, _) = info.streams.iter().find(|s| s.codec_type == "video").and_then(aspect::display_size)?;
+    let mut size = settings.fit.canvas
+        .or_else(|| settings.fit.ratio().map(|r| aspect::canvas(w, h, r)))
+        .unwrap_or((w, h));
+    if plan::own_size(settings) {
+        size = settings.resolution.apply(size);
+    }
+    let scaled = fit_within(size, limit)?;
+    Some(Oversize { encoder, size, limit, scaled })
+}
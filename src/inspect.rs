@@ -0,0 +1,233 @@
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::{egui, App};
+
+use crate::budget;
+use crate::probe::{self, MediaInfo, ProbeDepth, StreamInfo};
+use crate::process;
+use crate::timecode;
+use crate::FFUIApp;
+
+// 右键“查看媒体信息”打开的只读窗口，可一键切换到转换界面
+pub struct InspectApp {
+    file: String,
+    info: Result<MediaInfo, String>,
+    thumb: Arc<Mutex<Option<egui::ColorImage>>>,
+    texture: Option<egui::TextureHandle>,
+    converter: Option<FFUIApp>,
+    depth: ProbeDepth,
+}
+
+impl InspectApp {
+    pub fn new(file: String) -> Self {
+        let mut app = InspectApp {
+            file,
+            info: Err(String::new()),
+            thumb: Arc::new(Mutex::new(None)),
+            texture: None,
+            converter: None,
+            depth: ProbeDepth::default(),
+        };
+        app.load();
+        app
+    }
+
+    fn load(&mut self) {
+        self.info = probe::probe(&self.file, self.depth).map_err(|e| e.to_string());
+        if let Ok(info) = &self.info
+            && budget::UiBudget::default().previews()
+            && self.texture.is_none()
+            && info.streams.iter().any(|s| s.codec_type == "video")
+        {
+            let (file, duration, depth, thumb) = (self.file.clone(), info.duration, self.depth, self.thumb.clone());
+            thread::spawn(move || {
+                *thumb.lock().unwrap() = extract_thumbnail(&file, duration, depth);
+            });
+        }
+    }
+}
+
+// 取 10% 处的一帧缩成 320 宽的 PNG
+fn extract_thumbnail(input: &str, duration: f64, depth: ProbeDepth) -> Option<egui::ColorImage> {
+    let mut cmd = process::command("ffmpeg");
+    cmd.args(["-nostdin", "-v", "error", "-ss", &format!("{:.3}", duration * 0.1)])
+    .args(depth.args())
+    .args([
+        "-i", input,
+        "-frames:v", "1",
+        "-vf", "scale=320:-2",
+        "-f", "image2pipe", "-c:v", "png", "-",
+    ])
+    .stdin(Stdio::null());
+
+    let output = cmd.output().ok()?;
+    let image = image::load_from_memory_with_format(&output.stdout, image::ImageFormat::Png).ok()?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Some(egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
+
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.max(0.0).round() as u64;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    if bytes >= 1024.0 * 1024.0 * 1024.0 {
+        format!("{:.2} GB", bytes / 1024.0 / 1024.0 / 1024.0)
+    } else if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MB", bytes / 1024.0 / 1024.0)
+    } else {
+        format!("{:.0} KB", bytes / 1024.0)
+    }
+}
+
+// 优先用 mkv 统计标签，否则按码率 × 时长估算
+pub fn stream_size(stream: &StreamInfo, duration: f64) -> Option<f64> {
+    let tag = stream.props.get("tags.NUMBER_OF_BYTES").or_else(|| stream.props.get("tags.NUMBER_OF_BYTES-eng"));
+    if let Some(bytes) = tag.and_then(|b| b.parse::<f64>().ok()) {
+        return Some(bytes);
+    }
+    let bit_rate = stream.props.get("bit_rate").and_then(|b| b.parse::<f64>().ok())?;
+    let duration = stream.props.get("duration").and_then(|d| d.parse::<f64>().ok()).unwrap_or(duration);
+    (duration > 0.0).then_some(bit_rate * duration / 8.0)
+}
+
+fn stream_summary(stream: &StreamInfo) -> String {
+    let get = |k: &str| stream.props.get(k).map(|v| v.as_str());
+    let mut parts = vec![stream.codec_name.clone()];
+    match stream.codec_type.as_str() {
+        "video" => {
+            if let (Some(w), Some(h)) = (get("width"), get("height")) {
+                parts.push(format!("{}x{}", w, h));
+            }
+            if let Some(fps) = get("avg_frame_rate").and_then(probe::parse_rate).or_else(|| get("r_frame_rate").and_then(probe::parse_rate)) {
+                parts.push(format!("{:.3} fps", fps).replace(".000", ""));
+            }
+        }
+        "audio" => {
+            if let Some(ch) = get("channels") {
+                parts.push(format!("{} 声道", ch));
+            }
+            if let Some(rate) = get("sample_rate") {
+                parts.push(format!("{} Hz", rate));
+            }
+        }
+        _ => {}
+    }
+    if let Some(br) = get("bit_rate").and_then(|b| b.parse::<f64>().ok()) {
+        parts.push(format!("{:.0} kbps", br / 1000.0));
+    }
+    if let Some(lang) = get("tags.language") {
+        parts.push(lang.to_string());
+    }
+    parts.join(" · ")
+}
+
+fn type_label(codec_type: &str) -> &str {
+    match codec_type {
+        "video" => "视频",
+        "audio" => "音频",
+        "subtitle" => "字幕",
+        "attachment" => "附件",
+        "data" => "数据",
+        other => other,
+    }
+}
+
+// 结构化的媒体信息表格，转换界面也可以复用
+pub fn media_info_panel(ui: &mut egui::Ui, info: &MediaInfo) {
+    let fmt = |k: &str| info.format.get(k).map(|v| v.as_str()).unwrap_or("-");
+    egui::Grid::new("media_info_format").num_columns(2).striped(true).show(ui, |ui| {
+        ui.label("容器");
+        ui.label(fmt("format_long_name"));
+        ui.end_row();
+        ui.label("时长");
+        ui.label(format_duration(info.duration));
+        ui.end_row();
+        ui.label("总码率");
+        ui.label(info.format.get("bit_rate").and_then(|b| b.parse::<f64>().ok())
+            .map(|b| format!("{:.0} kbps", b / 1000.0)).unwrap_or("-".to_string()));
+        ui.end_row();
+        if let Some(tc) = timecode::from_info(info) {
+            ui.label("时间码");
+            ui.label(tc.to_string());
+            ui.end_row();
+        }
+        ui.label("文件大小");
+        ui.label(info.format.get("size").and_then(|b| b.parse::<f64>().ok())
+            .map(format_bytes).unwrap_or("-".to_string()));
+        ui.end_row();
+    });
+    ui.separator();
+    egui::Grid::new("media_info_streams").num_columns(4).striped(true).show(ui, |ui| {
+        for (i, stream) in info.streams.iter().enumerate() {
+            ui.label(format!("#{}", i));
+            ui.label(type_label(&stream.codec_type));
+            ui.label(stream_summary(stream));
+            ui.label(stream_size(stream, info.duration).map(|b| format!("≈ {}", format_bytes(b))).unwrap_or_default());
+            ui.end_row();
+        }
+    });
+}
+
+impl App for InspectApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if let Some(converter) = &mut self.converter {
+            converter.update(ctx, frame);
+            return;
+        }
+
+        if self.texture.is_none()
+            && let Some(image) = self.thumb.lock().unwrap().take()
+        {
+            self.texture = Some(ctx.load_texture("thumbnail", image, Default::default()));
+        }
+        if self.texture.is_none() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(200));
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.label(format!("文件: {}", self.file));
+            if let Some(texture) = &self.texture {
+                ui.image(texture, texture.size_vec2());
+            }
+            let suspicious = match &self.info {
+                Ok(info) => info.suspicious(),
+                Err(_) => Some("ffprobe 无法读取"),
+            };
+            let mut reload = false;
+            if let Some(reason) = suspicious
+                && self.depth != ProbeDepth::DEEP
+            {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, format!("探测结果可能不准确：{}", reason));
+                    reload = ui.button("深度重新分析").clicked();
+                });
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| match &self.info {
+                Ok(info) => media_info_panel(ui, info),
+                Err(e) => { ui.colored_label(egui::Color32::RED, e); }
+            });
+            ui.separator();
+            if ui.button("转换此文件").clicked() {
+                let mut converter = FFUIApp::new(self.file.clone());
+                if self.depth != ProbeDepth::default() {
+                    converter.set_probe_depth(self.depth);
+                }
+                self.converter = Some(converter);
+            }
+            if reload {
+                self.depth = ProbeDepth::DEEP;
+                self.load();
+            }
+        });
+    }
+
+    fn on_exit(&mut self, gl: Option<&eframe::glow::Context>) {
+        if let Some(converter) = &mut self.converter {
+            converter.on_exit(gl);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::process::Stdio;
+
+use crate::probe::ProbeDepth;
+use crate::process;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Deinterlace {
+    #[default]
+    Off,
+    Auto,
+    Yadif,
+    // 3:2 下拉的电视电影素材，反向还原为 23.976
+    Ivtc,
+}
+
+impl Deinterlace {
+    pub const ALL: [Deinterlace; 4] = [Deinterlace::Off, Deinterlace::Auto, Deinterlace::Yadif, Deinterlace::Ivtc];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Deinterlace::Off => "不处理",
+            Deinterlace::Auto => "自动检测",
+            Deinterlace::Yadif => "反交错 (yadif)",
+            Deinterlace::Ivtc => "反电视电影 (IVTC)",
+        }
+    }
+
+    pub fn filter(self) -> Option<&'static str> {
+        match self {
+            Deinterlace::Yadif => Some("yadif"),
+            Deinterlace::Ivtc => Some("fieldmatch,yadif=deint=interlaced,decimate"),
+            _ => None,
+        }
+    }
+}
+
+const SAMPLE_FRAMES: &str = "600";
+
+#[derive(Default)]
+pub struct IdetStats {
+    pub repeated_neither: u64,
+    pub repeated_top: u64,
+    pub repeated_bottom: u64,
+    pub tff: u64,
+    pub bff: u64,
+    pub progressive: u64,
+    pub undetermined: u64,
+}
+
+fn field(line: &str, name: &str) -> u64 {
+    line.split(name)
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+// 解析 idet 滤镜结束时打印的统计
+pub fn parse_idet(stderr: &str) -> Option<IdetStats> {
+    let mut stats = IdetStats::default();
+    let mut found = false;
+    for line in stderr.lines() {
+        if line.contains("Repeated Fields:") {
+            stats.repeated_neither = field(line, "Neither:");
+            stats.repeated_top = field(line, "Top:");
+            stats.repeated_bottom = field(line, "Bottom:");
+            found = true;
+        } else if line.contains("Multi frame detection:") {
+            stats.tff = field(line, "TFF:");
+            stats.bff = field(line, "BFF:");
+            stats.progressive = field(line, "Progressive:");
+            stats.undetermined = field(line, "Undetermined:");
+            found = true;
+        }
+    }
+    found.then_some(stats)
+}
+
+// 重复场比例高说明是 3:2 下拉；否则看隔行帧占比
+pub fn classify(stats: &IdetStats) -> Deinterlace {
+    let fields = stats.repeated_neither + stats.repeated_top + stats.repeated_bottom;
+    let frames = stats.tff + stats.bff + stats.progressive + stats.undetermined;
+    if fields > 0 && (stats.repeated_top + stats.repeated_bottom) * 100 / fields >= 15 {
+        return Deinterlace::Ivtc;
+    }
+    if frames > 0 && (stats.tff + stats.bff) * 100 / frames >= 25 {
+        return Deinterlace::Yadif;
+    }
+    Deinterlace::Off
+}
+
+// 从片头之后取一段样本跑 idet
+pub fn detect(input: &str, duration: f64, depth: ProbeDepth) -> Result<(Deinterlace, IdetStats), String> {
+    let start = (duration * 0.1).min(60.0);
+    let mut cmd = process::command("ffmpeg");
+    cmd.args(["-nostdin", "-hide_banner", "-ss", &format!("{:.3}", start)])
+    .args(depth.args())
+    .args([
+        "-i", input,
+        "-vf", "idet",
+        "-frames:v", SAMPLE_FRAMES,
+        "-an", "-f", "null", "-",
+    ])
+    .stdin(Stdio::null());
+
+    let output = cmd.output().map_err(|e| format!("无法启动 ffmpeg: {}", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stats = parse_idet(&stderr).ok_or("idet 没有输出统计结果".to_string())?;
+    Ok((classify(&stats), stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idet_summary_is_parsed_and_classified() {
+        let stderr = "\
+[Parsed_idet_0 @ 0x6000] Repeated Fields: Neither:   540 Top:    30 Bottom:    30
+[Parsed_idet_0 @ 0x6000] Single frame detection: TFF:   200 BFF:     0 Progressive:   380 Undetermined:    20
+[Parsed_idet_0 @ 0x6000] Multi frame detection: TFF:   230 BFF:     0 Progressive:   370 Undetermined:     0
+";
+        let stats = parse_idet(stderr).unwrap();
+        assert_eq!((stats.repeated_neither, stats.repeated_top, stats.repeated_bottom), (540, 30, 30));
+        // 只取多帧检测那一行
+        assert_eq!((stats.tff, stats.bff, stats.progressive, stats.undetermined), (230, 0, 370, 0));
+        assert!(matches!(classify(&stats), Deinterlace::Yadif));
+    }
+
+    #[test]
+    fn pulldown_and_progressive() {
+        let pulldown = IdetStats { repeated_neither: 80, repeated_top: 10, repeated_bottom: 10, progressive: 100, ..Default::default() };
+        assert!(matches!(classify(&pulldown), Deinterlace::Ivtc));
+        let progressive = IdetStats { repeated_neither: 100, progressive: 100, tff: 3, ..Default::default() };
+        assert!(matches!(classify(&progressive), Deinterlace::Off));
+        assert!(parse_idet("frame=  600 fps=0.0 q=-0.0 Lsize=N/A\n").is_none());
+    }
+}
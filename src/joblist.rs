@@ -0,0 +1,991 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::album::{self, Album, AlbumCodec};
+use crate::args;
+use crate::aspect::{AspectTarget, Fill, SarMode};
+use crate::av1::{Tune, VideoCodec};
+use crate::burnin::{BurnIn, Clock, Content, Corner};
+use crate::cancel::CancelToken;
+use crate::config;
+use crate::confirm;
+use crate::crash;
+use crate::coverart;
+use crate::dataloss;
+use crate::errors;
+use crate::filedate;
+use crate::filterchain;
+use crate::hook;
+use crate::interlace::Deinterlace;
+use crate::json::Value;
+use crate::ladder::Rung;
+use crate::lengths::LengthPolicy;
+use crate::live;
+use crate::loudness;
+use crate::nvsession;
+use crate::output;
+use crate::paths;
+use crate::pipeline::{self, OnFailure, Outcome, Stage, StageKind, Status};
+use crate::plan::{self, AudioCodec, JobSettings};
+use crate::probe::{self, MediaInfo};
+use crate::process::{self, FfError};
+use crate::publish;
+use crate::quality::{self, RateMode};
+use crate::resolution::Resolution;
+use crate::retime::{Rate, Retime};
+use crate::runner;
+use crate::speech::{Normalize, Speech, SpeechCodec};
+use crate::subtitle;
+use crate::tempfiles::{self, TempFiles};
+use crate::warnings::{self, Tally};
+use crate::verify;
+use crate::web::Platform;
+
+// 可以带到另一台机器上运行的任务列表（JSON）。
+// 相对路径按列表文件所在目录解析，整个文件夹搬走后仍然有效
+const VERSION: f64 = 1.0;
+
+#[derive(Clone)]
+pub struct Job {
+    pub input: String,
+    // 空时按设置生成默认输出名
+    pub output: String,
+    pub settings: JobSettings,
+    // 转换和之后的检查、命令，按顺序执行
+    pub stages: Vec<Stage>,
+    // 导入时发现的问题（找不到输入、设置无法识别），有问题的任务不运行
+    pub problem: Option<String>,
+}
+
+impl Job {
+    pub fn output_path(&self) -> String {
+        if !self.output.is_empty() {
+            return self.output.clone();
+        }
+        match (&self.settings.web, &self.settings.speech, &self.settings.album) {
+            (Some(platform), _, _) => output::web_output(&self.input, *platform),
+            (None, Some(speech), _) => output::default_output(&self.input, speech.codec.ext()),
+            (None, None, Some(album)) => album::output_path(&self.input, album, ""),
+            (None, None, None) => output::default_output(&self.input, &self.settings.format),
+        }
+    }
+
+    // 实际运行时的输出：自动命名时遇到已有文件另起名字，列表里仍显示原名
+    pub fn run_output(&self) -> String {
+        if self.output.is_empty() { output::avoid_existing(self.output_path(), &self.settings) } else { self.output_path() }
+    }
+}
+
+fn deinterlace_tag(d: Deinterlace) -> &'static str {
+    match d {
+        Deinterlace::Off => "off",
+        Deinterlace::Auto => "auto",
+        Deinterlace::Yadif => "yadif",
+        Deinterlace::Ivtc => "ivtc",
+    }
+}
+
+fn str_value(s: &str) -> Value {
+    Value::Str(s.to_string())
+}
+
+// 只保存界面上能设置、和具体机器无关的项
+pub fn settings_to_value(s: &JobSettings) -> Value {
+    let ladder = s.ladder.iter().map(|r| Value::Obj(vec![
+        ("height".to_string(), Value::Num(r.height as f64)),
+        ("bitrate_k".to_string(), Value::Num(r.bitrate_k as f64)),
+    ])).collect();
+    let aspect = match s.fit.ratio() {
+        Some((w, h)) => Value::Str(format!("{}:{}", w, h)),
+        None => Value::Null,
+    };
+    let [r, g, b] = s.fit.color;
+    let speech = match &s.speech {
+        Some(sp) => Value::Obj(vec![
+            ("codec".to_string(), str_value(sp.codec.tag())),
+            ("bitrate_k".to_string(), Value::Num(sp.bitrate_k as f64)),
+            ("normalize".to_string(), str_value(sp.normalize.tag())),
+            ("trim_silence".to_string(), Value::Bool(sp.trim_silence)),
+            ("chapter_minutes".to_string(), Value::Num(sp.chapter_minutes as f64)),
+        ]),
+        None => Value::Null,
+    };
+    // 专辑面板填好的一轨：标签按顺序写成对象，切分范围的结束为 null 表示到文件结尾
+    let album = match &s.album {
+        Some(a) => Value::Obj(vec![
+            ("codec".to_string(), str_value(a.codec.tag())),
+            ("bitrate_k".to_string(), Value::Num(a.bitrate_k as f64)),
+            ("gapless".to_string(), Value::Bool(a.gapless)),
+            ("tags".to_string(), Value::Obj(a.tags.iter().map(|(k, v)| (k.clone(), str_value(v))).collect())),
+            ("start".to_string(), a.span.map_or(Value::Null, |(start, _)| Value::Num(start))),
+            ("end".to_string(), a.span.and_then(|(_, end)| end).map_or(Value::Null, Value::Num)),
+            ("name".to_string(), str_value(&a.name)),
+        ]),
+        None => Value::Null,
+    };
+    let retime = match &s.retime {
+        Some(r) => Value::Obj(vec![
+            ("from".to_string(), Value::Str(r.from.tag())),
+            ("to".to_string(), Value::Str(r.to.tag())),
+            ("keep_pitch".to_string(), Value::Bool(r.keep_pitch)),
+        ]),
+        None => Value::Null,
+    };
+    let burn_in = match &s.burn_in {
+        Some(b) => Value::Obj(vec![
+            ("content".to_string(), str_value(b.content.tag())),
+            ("clock".to_string(), str_value(b.clock.tag())),
+            ("corner".to_string(), str_value(b.corner.tag())),
+            ("font_size".to_string(), Value::Num(b.font_size as f64)),
+            ("box".to_string(), Value::Bool(b.boxed)),
+            ("source_timecode".to_string(), Value::Bool(b.source_timecode)),
+            ("font_file".to_string(), str_value(&b.font_file)),
+        ]),
+        None => Value::Null,
+    };
+    let fields = vec![
+        ("format", str_value(&s.format)),
+        ("gpu", str_value(&s.gpu)),
+        ("codec", str_value(s.codec.tag())),
+        ("av1_preset", Value::Num(s.av1.preset as f64)),
+        ("av1_crf", Value::Num(s.av1.crf as f64)),
+        ("av1_film_grain", Value::Num(s.av1.film_grain as f64)),
+        ("av1_tune", str_value(s.av1.tune.tag())),
+        ("quality_mode", str_value(s.quality.mode.tag())),
+        ("quality_level", Value::Num(s.quality.level as f64)),
+        ("quality_bitrate_k", Value::Num(s.quality.bitrate_k as f64)),
+        ("two_pass", Value::Bool(s.two_pass)),
+        ("preset", s.preset.as_deref().map(str_value).unwrap_or(Value::Null)),
+        ("tune", s.tune.as_deref().map(str_value).unwrap_or(Value::Null)),
+        ("remux", Value::Bool(s.remux)),
+        ("audio_codec", str_value(s.audio_codec.tag())),
+        ("audio_bitrate_k", Value::Num(s.audio_bitrate_k as f64)),
+        ("loudnorm", s.loudnorm.map_or(Value::Null, Value::Num)),
+        ("keep_all_audio", Value::Bool(s.keep_all_audio)),
+        ("subtitle_file", str_value(&s.subtitle_file)),
+        ("subtitle_encoding", s.subtitle_encoding.as_deref().map(str_value).unwrap_or(Value::Null)),
+        ("overwrite", Value::Bool(s.overwrite)),
+        ("incremental", Value::Bool(s.incremental)),
+        ("ladder_enabled", Value::Bool(s.ladder_enabled)),
+        ("ladder", Value::Arr(ladder)),
+        ("ladder_hls", Value::Bool(s.ladder_hls)),
+        ("cover_file", Value::Bool(s.cover_file)),
+        ("data_loss", Value::Str(dataloss::format_choices(&s.data_loss))),
+        ("gop_frames", Value::Num(s.gop.frames as f64)),
+        ("gop_fixed", Value::Bool(s.gop.fixed)),
+        ("length_policy", str_value(s.length_policy.tag())),
+        ("retime", retime),
+        ("write_limit_mb", Value::Num(s.write_limit_mb)),
+        ("extra_args", str_value(&s.extra_args)),
+        ("ffmpeg", str_value(&s.ffmpeg)),
+        ("deinterlace", str_value(deinterlace_tag(s.deinterlace))),
+        ("resolution", str_value(&s.resolution.tag())),
+        ("fps", s.fps.map_or(Value::Null, |r| Value::Str(r.tag()))),
+        ("trim_start", s.trim_start.map_or(Value::Null, |d| Value::Num(d.as_secs_f64()))),
+        ("trim_end", s.trim_end.map_or(Value::Null, |d| Value::Num(d.as_secs_f64()))),
+        ("burn_in", burn_in),
+        ("aspect", aspect),
+        ("fill", str_value(if s.fit.fill == Fill::Blur { "blur" } else { "color" })),
+        ("fill_color", Value::Str(format!("#{:02X}{:02X}{:02X}", r, g, b))),
+        ("keep_sar", Value::Bool(s.sar_mode == SarMode::Keep)),
+        ("filter_order", Value::Str(filterchain::format_order(&s.filter_order))),
+        ("input_format", str_value(&s.input_format)),
+        ("web", s.web.map(|p| str_value(p.tag())).unwrap_or(Value::Null)),
+        ("speech", speech),
+        ("album", album),
+        ("hash_source", Value::Bool(s.hash_source)),
+        ("hash_embed", Value::Bool(s.hash_embed)),
+        ("hook_success", str_value(&s.hook_success)),
+        ("hook_failure", str_value(&s.hook_failure)),
+        ("hook_timeout_secs", Value::Num(s.hook_timeout_secs as f64)),
+        ("keep_dates", Value::Bool(s.keep_dates)),
+        ("fixes", Value::Arr(s.fixes.iter().map(|f| str_value(f)).collect())),
+    ];
+    Value::Obj(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+// 缺少的项用默认值，便于手工编写；认不出的值报错
+pub(crate) fn settings_from_value(v: &Value) -> Result<JobSettings, String> {
+    let mut s = JobSettings::default();
+    let text = |k: &str| v.get(k).and_then(|x| x.as_str());
+    let flag = |k: &str, default: bool| v.get(k).and_then(|x| x.as_bool()).unwrap_or(default);
+    let num = |k: &str| v.get(k).and_then(|x| x.as_f64());
+
+    if let Some(f) = text("format") {
+        s.format = f.to_string();
+    }
+    if let Some(g) = text("gpu") {
+        s.gpu = g.to_string();
+    }
+    let codec = text("codec").unwrap_or("h264");
+    s.codec = VideoCodec::from_tag(codec).ok_or(format!("未知的视频编码 {}", codec))?;
+    if let Some(n) = num("av1_preset") {
+        s.av1.preset = n.clamp(0.0, 13.0) as u8;
+    }
+    if let Some(n) = num("av1_crf") {
+        s.av1.crf = n.clamp(0.0, 63.0) as u8;
+    }
+    if let Some(n) = num("av1_film_grain") {
+        s.av1.film_grain = n.clamp(0.0, 50.0) as u8;
+    }
+    let tune = text("av1_tune").unwrap_or("visual");
+    s.av1.tune = Tune::from_tag(tune).ok_or(format!("未知的 AV1 调优目标 {}", tune))?;
+    let mode = text("quality_mode").unwrap_or("auto");
+    s.quality.mode = RateMode::from_tag(mode).ok_or(format!("未知的画质模式 {}", mode))?;
+    if let Some(n) = num("quality_level") {
+        s.quality.level = n.clamp(0.0, quality::MAX_LEVEL as f64) as u8;
+    }
+    if let Some(n) = num("quality_bitrate_k") {
+        s.quality.bitrate_k = n.clamp(100.0, 100_000.0) as u32;
+    }
+    s.two_pass = flag("two_pass", false);
+    s.preset = text("preset").map(|p| p.to_string());
+    s.tune = text("tune").map(|t| t.to_string());
+    s.remux = flag("remux", false);
+    let audio_codec = text("audio_codec").unwrap_or("auto");
+    s.audio_codec = AudioCodec::from_tag(audio_codec).ok_or(format!("未知的音频编码 {}", audio_codec))?;
+    if let Some(n) = num("audio_bitrate_k") {
+        s.audio_bitrate_k = n.clamp(32.0, 512.0) as u32;
+    }
+    s.loudnorm = num("loudnorm").map(|n| n.clamp(loudness::TARGET_MIN, loudness::TARGET_MAX));
+    s.keep_all_audio = flag("keep_all_audio", false);
+    s.subtitle_file = text("subtitle_file").unwrap_or("").to_string();
+    s.subtitle_encoding = text("subtitle_encoding").map(|e| e.to_string());
+    s.overwrite = flag("overwrite", false);
+    s.incremental = flag("incremental", false);
+    s.ladder_enabled = flag("ladder_enabled", false);
+    if let Some(rungs) = v.get("ladder").and_then(|l| l.as_array()) {
+        s.ladder = rungs
+            .iter()
+            .filter_map(|r| {
+                let height = r.get("height")?.as_f64()? as u32;
+                let bitrate_k = r.get("bitrate_k")?.as_f64()? as u32;
+                Some(Rung { height, bitrate_k })
+            })
+            .collect();
+    }
+    s.ladder_hls = flag("ladder_hls", false);
+    s.cover_file = flag("cover_file", false);
+    if let Some(choices) = text("data_loss") {
+        s.data_loss = dataloss::parse_choices(choices)?;
+    }
+    if let Some(n) = num("gop_frames") {
+        s.gop.frames = n.max(0.0) as u32;
+    }
+    s.gop.fixed = flag("gop_fixed", false);
+    let tag = text("length_policy").unwrap_or("keep");
+    s.length_policy = LengthPolicy::ALL
+        .into_iter()
+        .find(|p| p.tag() == tag)
+        .ok_or(format!("未知的时长处理方式 {}", tag))?;
+    if let Some(n) = num("write_limit_mb") {
+        s.write_limit_mb = n.max(0.0);
+    }
+    s.extra_args = text("extra_args").unwrap_or("").to_string();
+    s.ffmpeg = text("ffmpeg").unwrap_or("").to_string();
+    if let Some(r) = v.get("retime").filter(|r| !matches!(r, Value::Null)) {
+        let rate = |key: &str| {
+            let text = r.get(key).and_then(|x| x.as_str()).ok_or(format!("帧率重映射缺少 {}", key))?;
+            Rate::parse(text).ok_or(format!("帧率 {} 应为 25、23.976 或 24000/1001", text))
+        };
+        let keep_pitch = r.get("keep_pitch").and_then(|x| x.as_bool()).unwrap_or(true);
+        s.retime = Some(Retime { from: rate("from")?, to: rate("to")?, keep_pitch });
+    }
+    let tag = text("deinterlace").unwrap_or("off");
+    s.deinterlace = Deinterlace::ALL
+        .into_iter()
+        .find(|d| deinterlace_tag(*d) == tag)
+        .ok_or(format!("未知的反交错方式 {}", tag))?;
+    let tag = text("resolution").unwrap_or("original");
+    s.resolution = Resolution::from_tag(tag).ok_or(format!("分辨率 {} 应为 original、1080p 或 宽x高", tag))?;
+    if let Some(fps) = text("fps") {
+        s.fps = Some(Rate::parse(fps).ok_or(format!("帧率 {} 应为 25、23.976 或 24000/1001", fps))?);
+    }
+    s.trim_start = num("trim_start").filter(|n| *n >= 0.0).map(Duration::from_secs_f64);
+    s.trim_end = num("trim_end").filter(|n| *n >= 0.0).map(Duration::from_secs_f64);
+    if let Some(b) = v.get("burn_in").filter(|b| !matches!(b, Value::Null)) {
+        let mut burn = BurnIn::default();
+        let tag = |key: &str| b.get(key).and_then(|x| x.as_str());
+        if let Some(t) = tag("content") {
+            burn.content = Content::ALL.into_iter().find(|c| c.tag() == t).ok_or(format!("未知的烧录内容 {}", t))?;
+        }
+        if let Some(t) = tag("clock") {
+            burn.clock = Clock::ALL.into_iter().find(|c| c.tag() == t).ok_or(format!("未知的烧录时间基准 {}", t))?;
+        }
+        if let Some(t) = tag("corner") {
+            burn.corner = Corner::ALL.into_iter().find(|c| c.tag() == t).ok_or(format!("未知的烧录位置 {}", t))?;
+        }
+        if let Some(n) = b.get("font_size").and_then(|x| x.as_f64()) {
+            burn.font_size = n.clamp(8.0, 400.0) as u32;
+        }
+        burn.boxed = b.get("box").and_then(|x| x.as_bool()).unwrap_or(burn.boxed);
+        burn.source_timecode = b.get("source_timecode").and_then(|x| x.as_bool()).unwrap_or(burn.source_timecode);
+        burn.font_file = tag("font_file").unwrap_or_default().to_string();
+        s.burn_in = Some(burn);
+    }
+    if let Some(aspect) = text("aspect") {
+        let (w, h) = aspect
+            .split_once(':')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .ok_or(format!("宽高比 {} 应为 宽:高", aspect))?;
+        s.fit.target = AspectTarget::Custom;
+        s.fit.custom = (w, h);
+    }
+    if text("fill") == Some("blur") {
+        s.fit.fill = Fill::Blur;
+    }
+    if let Some(hex) = text("fill_color").and_then(|c| c.strip_prefix('#'))
+        && let Ok(rgb) = u32::from_str_radix(hex, 16)
+    {
+        s.fit.color = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8];
+    }
+    if flag("keep_sar", false) {
+        s.sar_mode = SarMode::Keep;
+    }
+    if let Some(order) = text("filter_order") {
+        s.filter_order = filterchain::parse_order(order)?;
+    }
+    s.input_format = text("input_format").unwrap_or("").to_string();
+    if let Some(tag) = text("web") {
+        s.web = Some(Platform::from_tag(tag).ok_or(format!("未知的平台 {}", tag))?);
+    }
+    if let Some(sp) = v.get("speech").filter(|sp| !matches!(sp, Value::Null)) {
+        let mut speech = Speech::default();
+        if let Some(tag) = sp.get("codec").and_then(|x| x.as_str()) {
+            speech.codec = SpeechCodec::from_tag(tag).ok_or(format!("未知的语音编码 {}", tag))?;
+        }
+        if let Some(n) = sp.get("bitrate_k").and_then(|x| x.as_f64()) {
+            speech.bitrate_k = n.clamp(6.0, 256.0) as u32;
+        }
+        if let Some(tag) = sp.get("normalize").and_then(|x| x.as_str()) {
+            speech.normalize = Normalize::from_tag(tag).ok_or(format!("未知的响度均衡方式 {}", tag))?;
+        }
+        speech.trim_silence = sp.get("trim_silence").and_then(|x| x.as_bool()).unwrap_or(speech.trim_silence);
+        if let Some(n) = sp.get("chapter_minutes").and_then(|x| x.as_f64()) {
+            speech.chapter_minutes = n.max(0.0) as u32;
+        }
+        s.speech = Some(speech);
+    }
+    if let Some(a) = v.get("album").filter(|a| !matches!(a, Value::Null)) {
+        let mut album = Album::default();
+        if let Some(tag) = a.get("codec").and_then(|x| x.as_str()) {
+            album.codec = AlbumCodec::from_tag(tag).ok_or(format!("未知的专辑编码 {}", tag))?;
+        }
+        if let Some(n) = a.get("bitrate_k").and_then(|x| x.as_f64()) {
+            album.bitrate_k = n.clamp(32.0, 512.0) as u32;
+        }
+        album.gapless = a.get("gapless").and_then(|x| x.as_bool()).unwrap_or(album.gapless);
+        if let Some(Value::Obj(tags)) = a.get("tags") {
+            album.tags = tags.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))).collect();
+        }
+        if let Some(start) = a.get("start").and_then(|x| x.as_f64()) {
+            album.span = Some((start.max(0.0), a.get("end").and_then(|x| x.as_f64())));
+        }
+        album.name = a.get("name").and_then(|x| x.as_str()).unwrap_or("").to_string();
+        s.album = Some(album);
+    }
+    s.hash_source = flag("hash_source", false);
+    s.hash_embed = flag("hash_embed", false);
+    s.hook_success = text("hook_success").unwrap_or("").to_string();
+    s.hook_failure = text("hook_failure").unwrap_or("").to_string();
+    if let Some(n) = num("hook_timeout_secs") {
+        s.hook_timeout_secs = n.max(1.0) as u64;
+    }
+    s.keep_dates = flag("keep_dates", false);
+    for fix in v.get("fixes").and_then(|f| f.as_array()).unwrap_or_default() {
+        let id = fix.as_str().unwrap_or("");
+        if !warnings::FIXES.iter().any(|f| f.id == id) {
+            return Err(format!("未知的修正 {}", id));
+        }
+        s.fixes.push(id.to_string());
+    }
+    Ok(s)
+}
+
+// 策略是默认值的阶段只写名字，否则写成 {"stage": "vmaf", "on_failure": "abort"}
+fn stages_to_value(stages: &[Stage]) -> Value {
+    Value::Arr(stages.iter().map(|s| {
+        if s.on_failure == s.kind.default_policy() {
+            str_value(s.kind.tag())
+        } else {
+            Value::Obj(vec![
+                ("stage".to_string(), str_value(s.kind.tag())),
+                ("on_failure".to_string(), str_value(s.on_failure.tag())),
+            ])
+        }
+    }).collect())
+}
+
+fn stages_from_value(v: &Value) -> Result<Vec<Stage>, String> {
+    let mut stages = Vec::new();
+    for item in v.as_array().ok_or("stages 应为数组")? {
+        let tag = item.as_str().or_else(|| item.get("stage").and_then(|s| s.as_str())).unwrap_or("");
+        let mut stage = Stage::new(StageKind::from_tag(tag).ok_or(format!("未知的阶段 {}", tag))?);
+        if let Some(policy) = item.get("on_failure").and_then(|p| p.as_str()) {
+            stage.on_failure = OnFailure::from_tag(policy).ok_or(format!("未知的失败策略 {}", policy))?;
+        }
+        stages.push(stage);
+    }
+    pipeline::validate(&stages)?;
+    Ok(stages)
+}
+
+fn base_dir(list: &Path) -> PathBuf {
+    list.parent().map(|p| p.to_path_buf()).unwrap_or_default()
+}
+
+// 在列表目录下的路径存成相对路径，其他位置保持原样
+fn store_path(path: &str, base: &Path, relative: bool) -> String {
+    if !relative || path.is_empty() {
+        return path.to_string();
+    }
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let base = fs::canonicalize(base).unwrap_or_else(|_| base.to_path_buf());
+    match absolute.strip_prefix(&base) {
+        Ok(rel) => rel.to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+fn resolve_path(path: &str, base: &Path) -> String {
+    if path.is_empty() || live::is_stdin(path) || Path::new(path).is_absolute() {
+        return path.to_string();
+    }
+    base.join(path).to_string_lossy().into_owned()
+}
+
+pub fn load(path: &Path) -> Result<Vec<Job>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))?;
+    let root = crate::json::parse(text.trim_start_matches('\u{feff}'))?;
+    if root.get("version").and_then(|v| v.as_f64()).is_some_and(|v| v > VERSION) {
+        return Err("任务列表来自更新版本的 ffui".to_string());
+    }
+    let base = base_dir(path);
+    let mut jobs = Vec::new();
+    for (i, item) in root.get("jobs").and_then(|j| j.as_array()).unwrap_or_default().iter().enumerate() {
+        let input = item.get("input").and_then(|v| v.as_str()).ok_or(format!("第 {} 个任务缺少 input", i + 1))?;
+        let input = resolve_path(input, &base);
+        let output = resolve_path(item.get("output").and_then(|v| v.as_str()).unwrap_or(""), &base);
+        let (mut settings, mut problem) = match settings_from_value(item.get("settings").unwrap_or(&Value::Null)) {
+            Ok(settings) => (settings, None),
+            Err(e) => (JobSettings::default(), Some(e)),
+        };
+        settings.subtitle_file = resolve_path(&settings.subtitle_file, &base);
+        let stages = match item.get("stages") {
+            Some(v) => stages_from_value(v).unwrap_or_else(|e| {
+                problem.get_or_insert(e);
+                pipeline::default_stages()
+            }),
+            None => pipeline::default_stages(),
+        };
+        if live::is_live(&input) {
+            if let Err(e) = live::check(&settings, &input) {
+                problem.get_or_insert(e);
+            }
+        } else if !Path::new(&input).is_file() {
+            problem = Some("找不到输入文件".to_string());
+        }
+        jobs.push(Job { input, output, settings, stages, problem });
+    }
+    Ok(jobs)
+}
+
+pub fn save(path: &Path, jobs: &[Job], relative: bool) -> std::io::Result<()> {
+    let base = base_dir(path);
+    let items = jobs.iter().map(|job| {
+        let mut settings = job.settings.clone();
+        settings.subtitle_file = store_path(&settings.subtitle_file, &base, relative);
+        Value::Obj(vec![
+            ("input".to_string(), Value::Str(store_path(&job.input, &base, relative))),
+            ("output".to_string(), Value::Str(store_path(&job.output, &base, relative))),
+            ("settings".to_string(), settings_to_value(&settings)),
+            ("stages".to_string(), stages_to_value(&job.stages)),
+        ])
+    }).collect();
+    let root = Value::Obj(vec![
+        ("version".to_string(), Value::Num(VERSION)),
+        ("jobs".to_string(), Value::Arr(items)),
+    ]);
+    fs::write(path, root.to_pretty() + "\n")
+}
+
+// 把一个任务追加到列表文件，文件不存在时新建
+pub fn append(path: &Path, job: Job, relative: bool) -> Result<usize, String> {
+    extend(path, vec![job], relative)
+}
+
+// 一次追加多个任务，返回列表里的任务总数
+pub fn extend(path: &Path, new: Vec<Job>, relative: bool) -> Result<usize, String> {
+    let mut jobs = if path.exists() { load(path)? } else { Vec::new() };
+    jobs.extend(new);
+    save(path, &jobs, relative).map_err(|e| format!("无法写入 {}: {}", path.display(), e))?;
+    Ok(jobs.len())
+}
+
+// 转换阶段留给后面阶段的信息
+struct Encoded {
+    settings: JobSettings,
+    info: MediaInfo,
+    // 都在暂存目录里，发布阶段才移到输出位置
+    stage: PathBuf,
+    outputs: Vec<String>,
+    dirs: Vec<String>,
+    // 输出应有的时长
+    expected_secs: f64,
+    // 源文件的封面没能保留
+    art_lost: bool,
+}
+
+// 转换阶段，输出写到暂存目录 stage 里，成功时返回匹配到的警告
+fn encode(job: &Job, output: &str, stage: &Path, report: &mut Report) -> Result<(Tally, Encoded), String> {
+    let mut settings = job.settings.clone();
+    // 之后的校验、VMAF 等阶段在同一个线程里，也用这个版本
+    if !process::use_build(&settings.ffmpeg) {
+        return Err(format!("设置里没有名为 {} 的 ffmpeg 版本", settings.ffmpeg));
+    }
+    output::check_writable(Path::new(output)).map_err(|e| format!("无法写入 {}: {}", output, errors::explain_io_error(&e).message))?;
+    // 暂存目录里总是空的，ffmpeg 的 -n 管不到输出位置，这里先查
+    if !settings.overwrite && Path::new(output).exists() {
+        return Err(format!("{} 已存在（没有允许覆盖）", output));
+    }
+    fs::create_dir_all(stage).map_err(|e| format!("无法创建暂存目录 {}: {}", stage.display(), e))?;
+    let staged = publish::staged_path(stage, output);
+    // 离开这个函数时连同里面的中间文件一起删除
+    let temp = TempFiles::new().map_err(|e| format!("无法创建临时目录 {}: {}", tempfiles::root().display(), e))?;
+    if !settings.subtitle_file.is_empty() {
+        let note = subtitle::prepare(&mut settings, temp.dir()).map_err(|e| format!("无法读取字幕文件: {}", e))?;
+        report.err(format!("  {}", note));
+    }
+
+    // 实时输入不探测，按没有时长处理
+    let info = if live::is_live(&job.input) { MediaInfo::default() } else { probe::probe(&job.input, settings.probe_depth)? };
+    let mut notes = plan::resolve(&mut settings, &job.input, &info);
+    settings.resolution.check()?;
+    plan::check_trim(&settings, &info)?;
+    plan::check_extra_args(&settings)?;
+    plan::check_audio(&settings, &info)?;
+    let plan = plan::plan_job(&settings, &info, &job.input, &staged, temp.dir());
+    live::check_plan(&job.input, &plan)?;
+    notes.extend(plan.notes.iter().cloned());
+    for note in &notes {
+        report.err(format!("  {}", note));
+    }
+    for dir in &plan.dirs {
+        let _ = fs::create_dir_all(dir);
+    }
+    for (path, content) in &plan.write_before {
+        fs::write(path, content).map_err(|e| format!("无法写入 {}: {}", path, e))?;
+    }
+
+    let child = Arc::new(Mutex::new(None));
+    let stop = CancelToken::new();
+    let activity = Arc::new(Mutex::new(Instant::now()));
+    let mut result = Ok(Tally::default());
+    for args in &plan.runs {
+        report.err(format!("  命令: {}", plan::quote_command("ffmpeg", &args.argv())));
+        match runner::run_ffmpeg(&args.argv(), &child, &stop, &activity, None, |_| {}) {
+            Ok(outcome) if outcome.exited_ok => {
+                if let Ok(tally) = &mut result {
+                    tally.merge(outcome.warnings);
+                }
+            }
+            Ok(runner::RunOutcome { crash: Some(kind), tail, .. }) => {
+                result = Err(match crash::write_report(kind, &job.input, &args.argv(), tail.as_str()) {
+                    Ok(path) => format!("{}，崩溃报告: {}", kind.label(), path.display()),
+                    Err(_) => kind.label(),
+                });
+                break;
+            }
+            Ok(outcome) => {
+                let message = args::explain_rejection(outcome.tail.lines(), args)
+                    .or_else(|| errors::match_stderr(outcome.tail.lines()).map(|h| h.message.to_string()))
+                    .unwrap_or_else(|| {
+                        let code = outcome.exit_code.map(|c| format!("（退出码 {}）", c)).unwrap_or_default();
+                        match runner::last_lines(outcome.tail.as_str(), 1).first() {
+                            Some(line) => format!("ffmpeg 异常退出{}: {}", code, line.trim()),
+                            None => format!("ffmpeg 异常退出{}", code),
+                        }
+                    });
+                result = Err(message);
+                break;
+            }
+            Err(e) => {
+                result = Err(FfError::launch("ffmpeg", e).to_string());
+                break;
+            }
+        }
+    }
+    if result.is_ok() {
+        if plan.outputs.iter().any(|p| fs::metadata(p).map(|m| m.len()).unwrap_or(0) == 0) {
+            result = Err("输出文件为空".to_string());
+        }
+        for (path, content) in &plan.write_after {
+            let _ = fs::write(path, content);
+        }
+        if let Some(album) = &settings.album
+            && album.writes_gapless()
+            && let [out] = plan.outputs.as_slice()
+        {
+            match album::write_gapless(out, plan.run_secs.unwrap_or(info.duration)) {
+                Ok(note) => report.err(format!("  {}", note)),
+                Err(e) => report.err(format!("  无法写入无缝播放信息: {}", e)),
+            }
+        }
+    }
+    let expected_secs = plan.run_secs.unwrap_or(info.duration);
+    let art_lost = plan.art_lost;
+    let stage = stage.to_path_buf();
+    result.map(|tally| (tally, Encoded { settings, info, stage, outputs: plan.outputs, dirs: plan.dirs, expected_secs, art_lost }))
+}
+
+fn run_vmaf(job: &Job, encoded: &Encoded) -> Status {
+    let s = &encoded.settings;
+    // 画面尺寸、帧率或内容变了的输出没法和源文件逐帧对比
+    let skip = if !encoded.info.streams.iter().any(|st| st.codec_type == "video") || !plan::is_video_container(&s.format) {
+        Some("没有视频")
+    } else if encoded.outputs.len() != 1 {
+        Some("多个输出")
+    } else if s.fit.canvas.is_some() || s.web.is_some() {
+        Some("补边或一键方案改变了画面")
+    } else if s.deinterlace == Deinterlace::Ivtc {
+        Some("IVTC 改变了帧率")
+    } else {
+        None
+    };
+    if let Some(reason) = skip {
+        return Status::Skipped(reason.to_string());
+    }
+    match verify::vmaf(&encoded.outputs[0], &job.input, &encoded.info) {
+        Ok(score) if score >= verify::VMAF_MIN => Status::Passed(format!("{:.1}", score)),
+        Ok(score) => Status::Failed(format!("{:.1}，低于 {}", score, verify::VMAF_MIN)),
+        Err(e) => Status::Failed(e),
+    }
+}
+
+// 把暂存的输出一起移到输出位置，之后才复制文件日期
+fn publish_outputs(job: &Job, output: &str, encoded: &Encoded, report: &mut Report) -> Status {
+    let dest = publish::dest_dir(output);
+    let declared: Vec<String> = encoded.outputs.iter().chain(&encoded.dirs).cloned().collect();
+    match publish::publish(&encoded.stage, &dest, &declared, encoded.settings.overwrite) {
+        Ok(moved) => {
+            if encoded.settings.keep_dates {
+                let outputs: Vec<String> = encoded.outputs.iter().map(|o| publish::published_path(&encoded.stage, &dest, o)).collect();
+                report.out(format!("  {}", filedate::copy_to_outputs(&job.input, &outputs)));
+            }
+            Status::Passed(if moved.len() > 1 { format!("{} 项", moved.len()) } else { String::new() })
+        }
+        Err(e) => Status::Failed(e),
+    }
+}
+
+// 按任务的阶段依次执行，另外返回封面是否没能保留
+fn run_pipeline(job: &Job, output: &str, report: &mut Report) -> (Outcome, bool) {
+    // 离开时连同没有发布的输出一起删除
+    let staging = TempFiles::new();
+    let mut encoded: Option<Encoded> = None;
+    let outcome = pipeline::run(&job.stages, |kind, ok| {
+        if kind != StageKind::Encode && kind != StageKind::Hook && encoded.is_none() {
+            return Status::Skipped("没有转换结果".to_string());
+        }
+        match kind {
+            StageKind::Encode => match staging
+                .as_ref()
+                .map_err(|e| format!("无法创建临时目录 {}: {}", tempfiles::root().display(), e))
+                .and_then(|staging| encode(job, output, &staging.dir().join("out"), report))
+            {
+                Ok((tally, done)) => {
+                    let art_lost = done.art_lost;
+                    encoded = Some(done);
+                    if tally.is_empty() {
+                        return if art_lost { Status::Warned("封面没能保留".to_string()) } else { Status::Passed(String::new()) };
+                    }
+                    for line in tally.summary().lines() {
+                        report.out(format!("  {}", line));
+                    }
+                    for fix in tally.suggested(&job.settings.fixes) {
+                        report.out(format!("  建议: {}（任务列表里加上 \"fixes\": [\"{}\"]）", fix.label, fix.id));
+                    }
+                    Status::Warned("ffmpeg 有警告".to_string())
+                }
+                Err(e) => Status::Failed(e),
+            },
+            StageKind::Verify => {
+                let done = encoded.as_ref().unwrap();
+                match verify::check_outputs(&done.outputs, done.expected_secs) {
+                    Ok(text) => Status::Passed(text),
+                    Err(e) => Status::Failed(e),
+                }
+            }
+            StageKind::Vmaf => run_vmaf(job, encoded.as_ref().unwrap()),
+            StageKind::Publish => publish_outputs(job, output, encoded.as_ref().unwrap(), report),
+            StageKind::Hook => {
+                let text = hook::after_job(&job.settings, &job.input, output, ok);
+                for line in text.lines() {
+                    report.out(line.to_string());
+                }
+                // 钩子本身的问题只记成警告
+                if text.contains('⚠') { Status::Warned("命令没有正常结束".to_string()) } else { Status::Passed(String::new()) }
+            }
+        }
+    });
+    (outcome, encoded.is_some_and(|e| e.art_lost))
+}
+
+// 一个任务打印的内容。同时转换几个任务时先攒着，任务结束后整段打印，不和别的任务交错
+struct Report {
+    live: bool,
+    // (写到 stderr, 这一行)
+    lines: Vec<(bool, String)>,
+}
+
+impl Report {
+    fn out(&mut self, line: String) {
+        self.push(false, line);
+    }
+
+    fn err(&mut self, line: String) {
+        self.push(true, line);
+    }
+
+    fn push(&mut self, stderr: bool, line: String) {
+        match (self.live, stderr) {
+            (true, true) => eprintln!("{}", line),
+            (true, false) => println!("{}", line),
+            (false, _) => self.lines.push((stderr, line)),
+        }
+    }
+
+    fn print(self) {
+        for (stderr, line) in self.lines {
+            if stderr { eprintln!("{}", line) } else { println!("{}", line) }
+        }
+    }
+}
+
+// NVENC 会话：同时转换时用 NVIDIA 编码的任务合起来不超过驱动允许的路数，多出的等前面的结束
+struct Sessions {
+    limit: usize,
+    used: Mutex<usize>,
+    freed: Condvar,
+}
+
+struct SessionGuard<'a> {
+    sessions: &'a Sessions,
+    count: usize,
+}
+
+impl Sessions {
+    fn new(limit: usize) -> Sessions {
+        Sessions { limit: limit.max(1), used: Mutex::new(0), freed: Condvar::new() }
+    }
+
+    fn acquire(&self, count: usize) -> SessionGuard<'_> {
+        let count = count.min(self.limit);
+        let mut used = self.used.lock().unwrap();
+        while used.saturating_add(count) > self.limit {
+            used = self.freed.wait(used).unwrap();
+        }
+        *used += count;
+        SessionGuard { sessions: self, count }
+    }
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        *self.sessions.used.lock().unwrap() -= self.count;
+        self.sessions.freed.notify_all();
+    }
+}
+
+// 任务要占几路 NVENC：多分辨率在上限以内时各档同时编码，超过时依次编码只占一路
+fn nvenc_sessions(settings: &JobSettings, limit: usize) -> usize {
+    if settings.gpu != "NVIDIA" || settings.remux || !plan::is_video_container(&settings.format) {
+        return 0;
+    }
+    if settings.ladder_enabled && (1..=limit).contains(&settings.ladder.len()) { settings.ladder.len() } else { 1 }
+}
+
+enum Finished {
+    Skipped,
+    Failed,
+    // 封面是否没能保留
+    Warned(bool),
+    Done(bool),
+}
+
+fn run_listed(tag: &str, job: &Job, nvenc: &Sessions, report: &mut Report) -> Finished {
+    if let Some(problem) = &job.problem {
+        report.err(format!("{} {}，跳过: {}", tag, problem, job.input));
+        return Finished::Failed;
+    }
+    let output = job.run_output();
+    if job.settings.incremental && output::is_up_to_date(Path::new(&job.input), Path::new(&output)) {
+        report.out(format!("{} 已是最新，跳过: {}", tag, output));
+        return Finished::Skipped;
+    }
+    let _sessions = nvenc.acquire(nvenc_sessions(&job.settings, nvenc.limit));
+    // 同时转换时开始的这一行马上打印，看得出哪些任务在跑
+    println!("{} {} -> {}", tag, job.input, output);
+    let (outcome, lost) = run_pipeline(job, &output, report);
+    report.out(format!("  {}", outcome.badges()));
+    for (stage, status) in outcome.stages.iter().filter(|(_, st)| !st.detail().is_empty()) {
+        report.out(format!("  {} {}: {}", status.badge(), stage.kind.label(), status.detail()));
+    }
+    if let Some((stage, status)) = outcome.failed() {
+        report.err(format!("{} 失败（{}）: {}", tag, stage.kind.label(), status.detail()));
+        Finished::Failed
+    } else if outcome.warned() {
+        report.out(format!("{} 完成但有警告", tag));
+        Finished::Warned(lost)
+    } else {
+        report.out(format!("{} 完成", tag));
+        Finished::Done(lost)
+    }
+}
+
+// ffui --queue jobs.json --no-gui：转换列表里的任务，有失败或跳过的任务时返回 1。
+// 设置里的同时转换任务数大于 1 时几个任务一起跑
+pub fn run_headless(path: &str) -> i32 {
+    let jobs = match load(Path::new(path)) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    if let Some(warning) = paths::warning() {
+        eprintln!("{}", warning);
+    }
+    // 和界面里开始前确认的清单相同，无界面时只打印不等待
+    let ready: Vec<&Job> = jobs.iter().filter(|job| job.problem.is_none()).collect();
+    let items = ready.iter().map(|job| confirm::Item::new(&job.settings, &job.input, &job.run_output(), None, &Default::default())).collect();
+    let settings: Vec<&JobSettings> = ready.iter().map(|job| &job.settings).collect();
+    for line in confirm::Summary::new(items, &settings).lines() {
+        println!("{}", line);
+    }
+    // 同时转换几个任务、多分辨率输出时都要知道 NVENC 能同时开几路
+    let parallel = config::clamp_parallel(config::current().parallel_jobs).min(jobs.len().max(1));
+    if ready.iter().any(|job| job.settings.gpu == "NVIDIA" && (job.settings.ladder_enabled || parallel > 1)) {
+        nvsession::detect();
+        if let Some(limit) = nvsession::current() {
+            println!("NVENC 同时编码上限: {}", limit.label());
+        }
+    }
+    if parallel > 1 {
+        println!("同时转换 {} 个任务", parallel);
+    }
+    let nvenc = Sessions::new(nvsession::sessions());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..parallel {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = jobs.get(i) else { break };
+                    let tag = format!("[{}/{}]", i + 1, jobs.len());
+                    // 只有一个任务在跑时边转换边打印
+                    let mut report = Report { live: parallel == 1, lines: Vec::new() };
+                    let finished = run_listed(&tag, job, &nvenc, &mut report);
+                    let mut results = results.lock().unwrap();
+                    report.print();
+                    results.push((i, finished));
+                }
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    let failed = results.iter().filter(|(_, f)| matches!(f, Finished::Failed)).count();
+    let warned = results.iter().filter(|(_, f)| matches!(f, Finished::Warned(_))).count();
+    let art_lost: Vec<&str> = results
+        .iter()
+        .filter(|(_, f)| matches!(f, Finished::Done(true) | Finished::Warned(true)))
+        .map(|(i, _)| jobs[*i].input.as_str())
+        .collect();
+    println!("共 {} 个任务，{} 个未完成，{} 个完成但有警告", jobs.len(), failed, warned);
+    if !art_lost.is_empty() {
+        println!("{} 个文件的封面没能保留（输出格式不支持封面，可以在任务里加上 \"cover_file\": true 另存为 {}）:", art_lost.len(), coverart::FALLBACK_NAME);
+        for input in art_lost {
+            println!("  {}", input);
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn sessions_wait_for_a_free_slot() {
+        let sessions = Sessions::new(2);
+        let first = sessions.acquire(2);
+        assert_eq!(*sessions.used.lock().unwrap(), 2);
+        // 不用 NVENC 的任务不占路数，不用等
+        drop(sessions.acquire(0));
+        let started = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _second = sessions.acquire(1);
+                started.store(1, Ordering::SeqCst);
+            });
+            thread::sleep(Duration::from_millis(100));
+            assert_eq!(started.load(Ordering::SeqCst), 0);
+            drop(first);
+        });
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(*sessions.used.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn sessions_cap_requests_at_the_limit() {
+        // 要的比上限还多时只占满上限，不会一直等下去
+        let sessions = Sessions::new(2);
+        let guard = sessions.acquire(5);
+        assert_eq!(guard.count, 2);
+        drop(guard);
+        // 不限路数
+        let unlimited = Sessions::new(usize::MAX);
+        let a = unlimited.acquire(3);
+        let b = unlimited.acquire(3);
+        assert_eq!(a.count + b.count, 6);
+        // 检测结果是 0 时也至少能开一路
+        assert_eq!(Sessions::new(0).limit, 1);
+    }
+
+    #[test]
+    fn nvenc_sessions_per_job() {
+        let mut s = JobSettings { gpu: "NVIDIA".to_string(), format: "mp4".to_string(), ..JobSettings::default() };
+        assert_eq!(nvenc_sessions(&s, 3), 1);
+        s.ladder_enabled = true;
+        s.ladder = crate::ladder::default_rungs();
+        assert_eq!(nvenc_sessions(&s, 3), 3);
+        // 档数超过上限时逐档编码
+        assert_eq!(nvenc_sessions(&s, 2), 1);
+        s.remux = true;
+        assert_eq!(nvenc_sessions(&s, 3), 0);
+        s.remux = false;
+        s.format = "mp3".to_string();
+        assert_eq!(nvenc_sessions(&s, 3), 0);
+        s.format = "mp4".to_string();
+        s.gpu = "CPU".to_string();
+        assert_eq!(nvenc_sessions(&s, 3), 0);
+    }
+
+    #[test]
+    fn report_buffers_when_not_live() {
+        let mut report = Report { live: false, lines: Vec::new() };
+        report.out("完成".to_string());
+        report.err("  命令: ffmpeg".to_string());
+        assert!(report.lines == [(false, "完成".to_string()), (true, "  命令: ffmpeg".to_string())]);
+        let mut live = Report { live: true, lines: Vec::new() };
+        live.out("完成".to_string());
+        assert!(live.lines.is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use std::path::Path;
+
+use crate::args::{Args, Source};
+use crate::nvsession;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::MediaInfo;
+
+// 多分辨率输出中的一档
+#[derive(Clone, PartialEq)]
+pub struct Rung {
+    pub height: u32,
+    pub bitrate_k: u32,
+}
+
+pub fn default_rungs() -> Vec<Rung> {
+    vec![
+        Rung { height: 1080, bitrate_k: 5000 },
+        Rung { height: 720, bitrate_k: 2800 },
+        Rung { height: 480, bitrate_k: 1400 },
+    ]
+}
+
+const AUDIO_BITRATE_K: u32 = 128;
+pub const HLS_SEGMENT_SECS: u32 = 6;
+
+// 同一设备能同时开的编码会话数，消费级 N 卡驱动有限制，按检测到的上限
+pub fn max_sessions(gpu: &str) -> usize {
+    match gpu {
+        "NVIDIA" => nvsession::sessions(),
+        _ => usize::MAX,
+    }
+}
+
+fn without_ext(output: &str) -> String {
+    let path = Path::new(output);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(stem).to_string_lossy().into_owned()
+}
+
+// movie.mp4 -> movie_720p.mp4
+pub fn rendition_path(output: &str, height: u32) -> String {
+    let ext = Path::new(output).extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default();
+    format!("{}_{}p.{}", without_ext(output), height, ext)
+}
+
+fn hls_dir(output: &str) -> String {
+    format!("{}_hls", without_ext(output))
+}
+
+fn in_dir(dir: &str, file: &str) -> String {
+    Path::new(dir).join(file).to_string_lossy().into_owned()
+}
+
+// 按源画面比例算出该档的宽度（偶数）
+fn rung_width(info: &MediaInfo, height: u32) -> u32 {
+    let video = info.streams.iter().find(|s| s.codec_type == "video");
+    let dim = |key: &str| video.and_then(|v| v.props.get(key)).and_then(|v| v.parse::<f64>().ok());
+    match (dim("width"), dim("height")) {
+        (Some(w), Some(h)) if h > 0.0 => ((height as f64 * w / h / 2.0).round() * 2.0) as u32,
+        _ => height * 16 / 9 / 2 * 2,
+    }
+}
+
+fn split_graph(pre: &[String], rungs: &[Rung]) -> String {
+    let mut head = pre.to_vec();
+    head.push(format!("split={}", rungs.len()));
+    let mut graph = format!("[0:v]{}", head.join(","));
+    for i in 0..rungs.len() {
+        graph.push_str(&format!("[s{}]", i));
+    }
+    for (i, r) in rungs.iter().enumerate() {
+        graph.push_str(&format!(";[s{}]scale=-2:{}[v{}]", i, r.height, i));
+    }
+    graph
+}
+
+pub fn plan(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str) -> JobPlan {
+    let rungs = &settings.ladder;
+    let has_audio = info.streams.iter().any(|s| s.codec_type == "audio");
+    let pre = plan::video_filters(settings);
+    let codec = plan::video_codec(settings);
+    // 按码率控制，不带 crf
+    let codec_args = plan::video_codec_args(settings, false);
+    let audio_codec = plan::default_audio_codec(&settings.format);
+    let audio_bitrate = format!("{}k", AUDIO_BITRATE_K);
+    let parallel = rungs.len() <= max_sessions(&settings.gpu);
+
+    let mut job = JobPlan::default();
+    if !parallel {
+        job.notes.push(format!(
+            "{} 最多同时进行 {} 路编码，{} 个清晰度将依次编码",
+            settings.gpu, max_sessions(&settings.gpu), rungs.len()
+        ));
+    }
+
+    let hls_args = |args: &mut Args, segments: &str| {
+        args.push(Source::Ladder, &[
+            "-f", "hls",
+            "-hls_time", &HLS_SEGMENT_SECS.to_string(),
+            "-hls_playlist_type", "vod",
+            "-hls_segment_filename", segments,
+        ]);
+    };
+
+    if settings.ladder_hls {
+        let dir = hls_dir(output);
+        job.dirs.push(dir.clone());
+        if parallel {
+            let mut args = plan::input_args(settings, input);
+            args.push(Source::Ladder, &["-filter_complex", &split_graph(&pre, rungs)]);
+            let mut stream_map = Vec::new();
+            for i in 0..rungs.len() {
+                args.push(Source::Ladder, &["-map", &format!("[v{}]", i)]);
+                if has_audio {
+                    args.push(Source::Base, &["-map", "0:a:0"]);
+                    stream_map.push(format!("v:{},a:{}", i, i));
+                } else {
+                    stream_map.push(format!("v:{}", i));
+                }
+            }
+            args.push(Source::Codec, &["-c:v", codec]);
+            args.append(codec_args.clone());
+            for (i, r) in rungs.iter().enumerate() {
+                args.push(Source::Ladder, &[&format!("-b:v:{}", i), &format!("{}k", r.bitrate_k)]);
+            }
+            if has_audio {
+                args.push(Source::Ladder, &["-c:a", "aac", "-b:a", &audio_bitrate]);
+            }
+            args.append(plan::output_tail_args(settings));
+            hls_args(&mut args, &in_dir(&dir, "stream_%v_%03d.ts"));
+            args.push(Source::Ladder, &["-master_pl_name", "master.m3u8", "-var_stream_map", &stream_map.join(" ")]);
+            args.push(Source::Output, &[&in_dir(&dir, "stream_%v.m3u8")]);
+            job.runs.push(args);
+            job.outputs.push(in_dir(&dir, "master.m3u8"));
+        } else {
+            // 分开编码时主播放列表由 ffui 自己写
+            let mut master = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+            for (i, r) in rungs.iter().enumerate() {
+                let mut args = plan::input_args(settings, input);
+                let mut filters = pre.clone();
+                filters.push(format!("scale=-2:{}", r.height));
+                args.push(Source::Filters, &["-vf", &filters.join(",")]);
+                args.push(Source::Codec, &["-c:v", codec]);
+                args.push(Source::Ladder, &["-b:v", &format!("{}k", r.bitrate_k)]);
+                args.append(codec_args.clone());
+                if has_audio {
+                    args.push(Source::Ladder, &["-c:a", "aac", "-b:a", &audio_bitrate]);
+                }
+                args.append(plan::output_tail_args(settings));
+                hls_args(&mut args, &in_dir(&dir, &format!("stream_{}_%03d.ts", i)));
+                let playlist = format!("stream_{}.m3u8", i);
+                args.push(Source::Output, &[&in_dir(&dir, &playlist)]);
+                job.runs.push(args);
+                job.outputs.push(in_dir(&dir, &playlist));
+
+                let bandwidth = (r.bitrate_k + if has_audio { AUDIO_BITRATE_K } else { 0 }) * 1000;
+                master.push_str(&format!(
+                    "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}x{}\n{}\n",
+                    bandwidth, rung_width(info, r.height), r.height, playlist
+                ));
+            }
+            job.write_after.push((in_dir(&dir, "master.m3u8"), master));
+        }
+        return job;
+    }
+
+    if parallel {
+        let mut args = plan::input_args(settings, input);
+        args.push(Source::Ladder, &["-filter_complex", &split_graph(&pre, rungs)]);
+        for (i, r) in rungs.iter().enumerate() {
+            let out = rendition_path(output, r.height);
+            args.push(Source::Ladder, &["-map", &format!("[v{}]", i)]);
+            if has_audio {
+                args.push(Source::Base, &["-map", "0:a:0"]);
+            }
+            args.push(Source::Codec, &["-c:v", codec]);
+            args.push(Source::Ladder, &["-b:v", &format!("{}k", r.bitrate_k)]);
+            args.append(codec_args.clone());
+            if has_audio {
+                args.push(Source::Ladder, &["-c:a", audio_codec, "-b:a", &audio_bitrate]);
+            }
+            args.append(plan::output_tail_args(settings));
+            args.push(Source::Output, &[&out]);
+            job.outputs.push(out);
+        }
+        job.runs.push(args);
+    } else {
+        for r in rungs {
+            let out = rendition_path(output, r.height);
+            let mut args = plan::input_args(settings, input);
+            let mut filters = pre.clone();
+            filters.push(format!("scale=-2:{}", r.height));
+            args.push(Source::Filters, &["-vf", &filters.join(",")]);
+            args.push(Source::Codec, &["-c:v", codec]);
+            args.push(Source::Ladder, &["-b:v", &format!("{}k", r.bitrate_k)]);
+            args.append(codec_args.clone());
+            if has_audio {
+                args.push(Source::Ladder, &["-c:a", audio_codec, "-b:a", &audio_bitrate]);
+            }
+            args.append(plan::output_tail_args(settings));
+            args.push(Source::Output, &[&out]);
+            job.runs.push(args);
+            job.outputs.push(out);
+        }
+    }
+    job
+}
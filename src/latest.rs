@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 工作线程和界面之间共享的一份快照。原来每个字段一把锁，ffmpeg 每输出一块进度
+// 工作线程就要连锁好几次，界面每帧也要把这些锁再各拿一遍。
+// 现在整份换成新的 Arc，界面先比较版本号（原子变量，不加锁），只有变了才去拿一次锁
+pub struct Latest<T> {
+    version: AtomicU64,
+    slot: Mutex<Arc<T>>,
+}
+
+impl<T> Latest<T> {
+    pub fn new(value: T) -> Self {
+        Latest { version: AtomicU64::new(0), slot: Mutex::new(Arc::new(value)) }
+    }
+
+    // 先换内容再加版本号，读的一方看到新版本时一定能拿到新内容
+    pub fn store(&self, value: T) {
+        *self.slot.lock().unwrap() = Arc::new(value);
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        self.slot.lock().unwrap().clone()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+}
+
+// 界面持有的一端：缓存上次读到的值，版本号没变时直接返回缓存
+pub struct Reader<T: Copy> {
+    shared: Arc<Latest<T>>,
+    seen: u64,
+    value: T,
+}
+
+impl<T: Copy> Reader<T> {
+    pub fn new(value: T) -> Self {
+        Reader { shared: Arc::new(Latest::new(value)), seen: 0, value }
+    }
+
+    pub fn get(&mut self) -> T {
+        let version = self.shared.version();
+        if version != self.seen {
+            self.value = *self.shared.load();
+            self.seen = version;
+        }
+        self.value
+    }
+
+    // 给工作线程、旁观发布等其他线程用
+    pub fn shared(&self) -> Arc<Latest<T>> {
+        self.shared.clone()
+    }
+
+    // 界面线程自己改（开始新任务时清零），下一次 get 会读回这个值
+    pub fn store(&self, value: T) {
+        self.shared.store(value);
+    }
+}
+
+// 大约 30 次每秒，比界面刷新慢一点，进度条看起来仍然是连续的
+pub const PUBLISH_EVERY: Duration = Duration::from_millis(33);
+
+// 工作线程持有的一端：在本地改，限频发布。阶段切换（开始、结束、出错）用 set 立即发布
+pub struct Publisher<T: Copy> {
+    shared: Arc<Latest<T>>,
+    value: T,
+    last: Option<Instant>,
+}
+
+impl<T: Copy> Publisher<T> {
+    // 从当前发布的值接着改
+    pub fn new(shared: Arc<Latest<T>>) -> Self {
+        let value = *shared.load();
+        Publisher { shared, value, last: None }
+    }
+
+    // 距上次发布不到 PUBLISH_EVERY 时只改本地，留给下一次 update 或 flush 带出去
+    pub fn update(&mut self, change: impl FnOnce(&mut T)) {
+        change(&mut self.value);
+        let now = Instant::now();
+        if self.last.is_none_or(|last| now.duration_since(last) >= PUBLISH_EVERY) {
+            self.shared.store(self.value);
+            self.last = Some(now);
+        }
+    }
+
+    pub fn set(&mut self, change: impl FnOnce(&mut T)) {
+        change(&mut self.value);
+        self.flush();
+    }
+
+    pub fn flush(&mut self) {
+        self.shared.store(self.value);
+        self.last = Some(Instant::now());
+    }
+}
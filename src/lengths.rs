@@ -0,0 +1,199 @@
+use crate::probe::{MediaInfo, StreamInfo};
+use crate::timestamp;
+
+// 音频和视频时长不一致（演唱会录像音频比画面多录了几秒等）。不处理时输出的长度
+// 取决于封装格式的默认行为，播放到结尾可能黑屏或突然静音
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum LengthPolicy {
+    #[default]
+    Keep,
+    // 按较短的流截断（-shortest）
+    Shortest,
+    // 视频较短时重复最后一帧补齐（tpad）
+    PadVideo,
+    // 音频较短时补静音（apad）
+    PadAudio,
+}
+
+impl LengthPolicy {
+    pub const ALL: [LengthPolicy; 4] = [LengthPolicy::Keep, LengthPolicy::Shortest, LengthPolicy::PadVideo, LengthPolicy::PadAudio];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LengthPolicy::Keep => "保持原样",
+            LengthPolicy::Shortest => "按较短的截断",
+            LengthPolicy::PadVideo => "视频重复最后一帧补齐",
+            LengthPolicy::PadAudio => "音频补静音",
+        }
+    }
+
+    // 任务列表里保存的名字
+    pub fn tag(self) -> &'static str {
+        match self {
+            LengthPolicy::Keep => "keep",
+            LengthPolicy::Shortest => "shortest",
+            LengthPolicy::PadVideo => "pad_video",
+            LengthPolicy::PadAudio => "pad_audio",
+        }
+    }
+}
+
+// 相差不到这么多秒当作一样长，各流的结尾本来就差几帧
+pub const THRESHOLD_SECS: f64 = 0.5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub video: f64,
+    pub audio: f64,
+}
+
+impl Mismatch {
+    pub fn label(&self) -> String {
+        if self.audio > self.video {
+            format!("音频比视频长 {:.1} 秒", self.audio - self.video)
+        } else {
+            format!("视频比音频长 {:.1} 秒", self.video - self.audio)
+        }
+    }
+}
+
+// 流的时长：mp4 等写在 duration 里，mkv 只有 DURATION 标签
+fn stream_secs(stream: &StreamInfo) -> Option<f64> {
+    let secs = stream.props.get("duration").and_then(|d| d.parse::<f64>().ok()).or_else(|| {
+        let tag = stream.props.get("tags.DURATION").or_else(|| stream.props.get("tags.duration"))?;
+        timestamp::parse(tag).ok().map(|d| d.as_secs_f64())
+    })?;
+    (secs > 0.0).then_some(secs)
+}
+
+// 比较第一条视频流和第一条音频流
+pub fn detect(info: &MediaInfo) -> Option<Mismatch> {
+    let video = info.streams.iter().find(|s| s.codec_type == "video").and_then(stream_secs)?;
+    let audio = info.streams.iter().find(|s| s.codec_type == "audio").and_then(stream_secs)?;
+    ((audio - video).abs() >= THRESHOLD_SECS).then_some(Mismatch { video, audio })
+}
+
+// 按策略生成的参数；补齐只在对应的流较短时生效
+#[derive(Default, PartialEq)]
+pub struct LengthArgs {
+    pub video_filter: Option<String>,
+    pub audio_filter: Option<String>,
+    pub shortest: bool,
+}
+
+pub fn args(policy: LengthPolicy, info: &MediaInfo) -> LengthArgs {
+    let Some(m) = detect(info) else { return LengthArgs::default() };
+    match policy {
+        LengthPolicy::Keep => LengthArgs::default(),
+        LengthPolicy::Shortest => LengthArgs { shortest: true, ..Default::default() },
+        LengthPolicy::PadVideo if m.audio > m.video => LengthArgs {
+            video_filter: Some(format!("tpad=stop_mode=clone:stop_duration={:.3}", m.audio - m.video)),
+            ..Default::default()
+        },
+        LengthPolicy::PadAudio if m.video > m.audio => LengthArgs {
+            audio_filter: Some(format!("apad=whole_dur={:.3}", m.video)),
+            ..Default::default()
+        },
+        LengthPolicy::PadVideo | LengthPolicy::PadAudio => LengthArgs::default(),
+    }
+}
+
+// 由 plan::resolve 调用，说明检测结果和会怎样处理
+pub fn note(policy: LengthPolicy, info: &MediaInfo) -> Option<String> {
+    let m = detect(info)?;
+    let action = match (policy, args(policy, info) == LengthArgs::default()) {
+        (LengthPolicy::Keep, _) => "保持原样，结尾的表现取决于输出格式".to_string(),
+        (LengthPolicy::PadVideo | LengthPolicy::PadAudio, true) => format!("{}不适用，保持原样", policy.label()),
+        (_, _) => policy.label().to_string(),
+    };
+    Some(format!("时长: {}，{}", m.label(), action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(kind: &str, props: &[(&str, &str)]) -> StreamInfo {
+        StreamInfo {
+            codec_type: kind.to_string(),
+            props: props.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    // 视频和音频各一条，时长写在 duration 里
+    fn media(video: f64, audio: f64) -> MediaInfo {
+        MediaInfo {
+            streams: vec![
+                stream("video", &[("duration", &video.to_string())]),
+                stream("audio", &[("duration", &audio.to_string())]),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn small_differences_are_ignored() {
+        assert!(detect(&media(60.0, 60.4)).is_none());
+        assert!(detect(&media(60.0, 60.5)) == Some(Mismatch { video: 60.0, audio: 60.5 }));
+        assert_eq!(detect(&media(60.0, 63.3)).unwrap().label(), "音频比视频长 3.3 秒");
+        assert_eq!(detect(&media(90.0, 60.0)).unwrap().label(), "视频比音频长 30.0 秒");
+    }
+
+    #[test]
+    fn durations_from_tags() {
+        // mkv 只有 DURATION 标签，第一条视频和第一条音频才算
+        let info = MediaInfo {
+            streams: vec![
+                stream("video", &[("tags.DURATION", "00:01:00.000000000")]),
+                stream("audio", &[("tags.duration", "00:01:05.500000000")]),
+                stream("audio", &[("duration", "60.0")]),
+            ],
+            ..Default::default()
+        };
+        assert!(detect(&info) == Some(Mismatch { video: 60.0, audio: 65.5 }));
+        // 没有时长、时长为 0 或缺少一种流时不判断
+        let info = MediaInfo { streams: vec![stream("video", &[("duration", "0")]), stream("audio", &[("duration", "5")])], ..Default::default() };
+        assert!(detect(&info).is_none());
+        let info = MediaInfo { streams: vec![stream("video", &[]), stream("audio", &[("duration", "5")])], ..Default::default() };
+        assert!(detect(&info).is_none());
+        let info = MediaInfo { streams: vec![stream("audio", &[("duration", "5")])], ..Default::default() };
+        assert!(detect(&info).is_none());
+    }
+
+    #[test]
+    fn policies() {
+        let longer_audio = media(60.0, 63.5);
+        let longer_video = media(63.5, 60.0);
+        for info in [&longer_audio, &longer_video] {
+            assert!(args(LengthPolicy::Keep, info) == LengthArgs::default());
+            assert!(args(LengthPolicy::Shortest, info) == LengthArgs { shortest: true, ..Default::default() });
+        }
+        assert_eq!(args(LengthPolicy::PadVideo, &longer_audio).video_filter.as_deref(), Some("tpad=stop_mode=clone:stop_duration=3.500"));
+        assert_eq!(args(LengthPolicy::PadAudio, &longer_video).audio_filter.as_deref(), Some("apad=whole_dur=63.500"));
+        // 要补的流本来就更长时不补
+        assert!(args(LengthPolicy::PadVideo, &longer_video) == LengthArgs::default());
+        assert!(args(LengthPolicy::PadAudio, &longer_audio) == LengthArgs::default());
+        // 没有差异时任何策略都不加参数
+        for policy in LengthPolicy::ALL {
+            assert!(args(policy, &media(60.0, 60.1)) == LengthArgs::default());
+        }
+    }
+
+    #[test]
+    fn notes() {
+        let info = media(60.0, 63.5);
+        assert_eq!(note(LengthPolicy::Keep, &info).unwrap(), "时长: 音频比视频长 3.5 秒，保持原样，结尾的表现取决于输出格式");
+        assert_eq!(note(LengthPolicy::Shortest, &info).unwrap(), "时长: 音频比视频长 3.5 秒，按较短的截断");
+        assert_eq!(note(LengthPolicy::PadVideo, &info).unwrap(), "时长: 音频比视频长 3.5 秒，视频重复最后一帧补齐");
+        assert_eq!(note(LengthPolicy::PadAudio, &info).unwrap(), "时长: 音频比视频长 3.5 秒，音频补静音不适用，保持原样");
+        assert!(note(LengthPolicy::PadAudio, &media(60.0, 60.0)).is_none());
+    }
+
+    #[test]
+    fn tags_are_unique() {
+        for (i, p) in LengthPolicy::ALL.iter().enumerate() {
+            assert!(LengthPolicy::ALL[..i].iter().all(|q| q.tag() != p.tag()));
+        }
+    }
+}
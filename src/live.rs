@@ -0,0 +1,79 @@
+use crate::plan::{JobPlan, JobSettings};
+
+// 实时输入：从标准输入或命名管道读取另一个采集程序的输出。
+// 数据读过就没有了，不能先用 ffprobe 探测、也不能跑两遍；没有总时长，
+// 进度改为显示已编码的时长，输入格式必须由用户指定（-f mpegts 等）
+
+// ffui --stdin-input 或 ffui - ：把 ffui 自己的标准输入转给 ffmpeg
+pub fn is_stdin(input: &str) -> bool {
+    matches!(input, "-" | "pipe:" | "pipe:0")
+}
+
+// Windows 的 \\.\pipe\名字，其他系统的 FIFO（mkfifo）
+pub fn is_named_pipe(input: &str) -> bool {
+    let lower = input.to_ascii_lowercase();
+    if lower.starts_with(r"\\.\pipe\") || lower.starts_with("//./pipe/") {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        std::fs::metadata(input).is_ok_and(|m| m.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    { false }
+}
+
+pub fn is_live(input: &str) -> bool {
+    is_stdin(input) || is_named_pipe(input)
+}
+
+// 界面和命令行里显示的输入名
+pub fn display_name(input: &str) -> String {
+    if is_stdin(input) { "标准输入".to_string() } else { input.to_string() }
+}
+
+// 输出名：标准输入用 stdin-时间，命名管道用管道名-时间，避免写进 \\.\pipe\ 下面
+pub fn output_base(input: &str, now: u64) -> String {
+    let name = if is_stdin(input) {
+        "stdin"
+    } else {
+        input.rsplit(['\\', '/']).next().filter(|n| !n.is_empty()).unwrap_or("pipe")
+    };
+    format!("{}-{}", name, now)
+}
+
+// 开始前检查设置是否能用于实时输入
+pub fn check(settings: &JobSettings, input: &str) -> Result<(), String> {
+    if !is_live(input) {
+        return Ok(());
+    }
+    if settings.input_format.trim().is_empty() {
+        return Err("从标准输入或命名管道读取时需要指定输入格式（如 mpegts）".to_string());
+    }
+    Ok(())
+}
+
+// 两遍编码、分档依次编码、取样预览都要多次运行 ffmpeg，实时输入只能读一次
+pub fn check_plan(input: &str, job: &JobPlan) -> Result<(), String> {
+    if is_live(input) && job.runs.len() > 1 {
+        return Err("这些设置需要多次读取输入，实时输入只能读取一次".to_string());
+    }
+    Ok(())
+}
+
+// 由 plan::resolve 调用：关掉需要先读一遍源文件的选项
+pub fn resolve(settings: &mut JobSettings) -> Vec<String> {
+    let mut notes = vec![format!("实时输入: 格式 {}，不预先探测，进度显示已编码的时长", settings.input_format.trim())];
+    if settings.hash_source || settings.keep_dates || settings.incremental {
+        settings.hash_source = false;
+        settings.hash_embed = false;
+        settings.keep_dates = false;
+        settings.incremental = false;
+        notes.push("实时输入: 不计算源文件校验值、不保留日期、不做增量判断".to_string());
+    }
+    if let Some(speech) = &mut settings.speech {
+        speech.trim_silence = false;
+    }
+    notes
+}
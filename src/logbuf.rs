@@ -0,0 +1,182 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+// 只保留最后 max_lines 行的文本缓冲，超出时从头整行丢弃。
+// 界面日志和每个任务的 stderr 尾部都用它，长任务不会无限占内存
+pub struct LogBuffer {
+    text: String,
+    lines: usize,
+    max_lines: usize,
+    // 界面日志同时完整地追加到这个文件，裁掉的部分还能在磁盘上搜索
+    disk: Option<PathBuf>,
+    // ffmpeg 的输出行先放进通道，界面每帧（或下一次写入时）一次性并进来，
+    // 读 stderr 的线程不用每行都抢这把锁，磁盘上也是一段一次追加
+    pending: Option<(Sender<String>, Receiver<String>)>,
+}
+
+// 复制出来的是当时的快照，不再接收通道里的行
+impl Clone for LogBuffer {
+    fn clone(&self) -> Self {
+        LogBuffer { text: self.text.clone(), lines: self.lines, max_lines: self.max_lines, disk: self.disk.clone(), pending: None }
+    }
+}
+
+// 磁盘日志超过这个大小时，启动时改名为 .old 另起一个，只留一份旧的
+const DISK_LIMIT: u64 = 32 * 1024 * 1024;
+
+impl LogBuffer {
+    pub fn new(max_lines: usize) -> Self {
+        LogBuffer { text: String::new(), lines: 0, max_lines: max_lines.max(1), disk: None, pending: None }
+    }
+
+    pub fn on_disk(max_lines: usize, path: PathBuf) -> Self {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if fs::metadata(&path).is_ok_and(|m| m.len() > DISK_LIMIT) {
+            let _ = fs::rename(&path, path.with_extension("old.txt"));
+        }
+        LogBuffer { disk: Some(path), ..LogBuffer::new(max_lines) }
+    }
+
+    pub fn disk_path(&self) -> Option<&Path> {
+        self.disk.as_deref()
+    }
+
+    // 写不进去（目录只读、磁盘满）时只丢磁盘上的这一段，界面日志照常
+    fn append_disk(&self, s: &str) {
+        if let Some(path) = &self.disk
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+        {
+            let _ = file.write_all(s.as_bytes());
+        }
+    }
+
+    // 发送端，每行一条（不带换行）
+    pub fn feeder(&mut self) -> Sender<String> {
+        self.pending.get_or_insert_with(mpsc::channel).0.clone()
+    }
+
+    // 把通道里攒下的行按顺序并进来
+    pub fn merge(&mut self) {
+        let Some((_, receiver)) = &self.pending else { return };
+        let mut batch = String::new();
+        for line in receiver.try_iter() {
+            batch.push_str(&line);
+            batch.push('\n');
+        }
+        if !batch.is_empty() {
+            self.append(&batch);
+        }
+    }
+
+    // 直接写入前先并入通道里更早的行，日志顺序不会乱
+    pub fn push_str(&mut self, s: &str) {
+        self.merge();
+        self.append(s);
+    }
+
+    fn append(&mut self, s: &str) {
+        self.append_disk(s);
+        self.text.push_str(s);
+        self.lines += s.matches('\n').count();
+        // 多攒四分之一再裁，避免每行都挪动整段文本
+        if self.lines > self.max_lines + self.max_lines / 4 {
+            let drop = self.lines - self.max_lines;
+            if let Some((end, _)) = self.text.match_indices('\n').nth(drop - 1) {
+                self.text.drain(..=end);
+                self.lines -= drop;
+            }
+        }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        self.push_str(line);
+        self.push_str("\n");
+    }
+
+    // 界面上换成新内容，磁盘上接着写，中间空一行分开
+    pub fn set(&mut self, text: &str) {
+        self.merge();
+        self.append_disk("\n");
+        self.text.clear();
+        self.lines = 0;
+        self.append(text);
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn lines(&self) -> std::str::Lines<'_> {
+        self.text.lines()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(from: usize, to: usize) -> String {
+        (from..to).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn keeps_only_the_tail() {
+        let mut log = LogBuffer::new(200);
+        for i in 0..1000 {
+            log.push_line(&format!("line {}", i));
+        }
+        // 最多多留四分之一
+        let kept: Vec<&str> = log.lines().collect();
+        assert!(kept.len() >= 200 && kept.len() <= 250, "{}", kept.len());
+        assert_eq!(kept.last(), Some(&"line 999"));
+        assert!(log.as_str().ends_with(&numbered(800, 1000)));
+    }
+
+    #[test]
+    fn one_big_push_is_trimmed_too() {
+        let mut log = LogBuffer::new(10);
+        log.push_str(&numbered(0, 100));
+        assert_eq!(log.as_str(), numbered(90, 100));
+        // 没有换行结尾的半行留着，等下一次写入接上
+        log.push_str("partial");
+        log.push_str(" end\n");
+        assert_eq!(log.lines().last(), Some("partial end"));
+    }
+
+    #[test]
+    fn fed_lines_keep_their_order() {
+        let mut log = LogBuffer::new(100);
+        let feeder = log.feeder();
+        feeder.send("a".to_string()).unwrap();
+        feeder.send("b".to_string()).unwrap();
+        // 直接写入先并入通道里更早的行
+        log.push_line("c");
+        feeder.send("d".to_string()).unwrap();
+        assert_eq!(log.as_str(), "a\nb\nc\n");
+        log.merge();
+        assert_eq!(log.as_str(), "a\nb\nc\nd\n");
+        // 复制出的快照不再接收
+        let snapshot = log.clone();
+        feeder.send("e".to_string()).unwrap();
+        log.merge();
+        assert_eq!(snapshot.as_str(), "a\nb\nc\nd\n");
+        assert!(log.as_str().ends_with("e\n"));
+    }
+
+    #[test]
+    fn set_replaces_screen_but_disk_keeps_everything() {
+        let dir = std::env::temp_dir().join(format!("ffui_logbuf_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("log.txt");
+        let mut log = LogBuffer::on_disk(5, path.clone());
+        log.push_str(&numbered(0, 20));
+        log.set("fresh\n");
+        assert_eq!(log.as_str(), "fresh\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\nfresh\n", numbered(0, 20)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::egui::{self, Align, Key, ScrollArea, Ui};
+use egui::text::{CCursor, LayoutJob, TextFormat};
+
+use crate::a11y;
+use crate::cancel::CancelToken;
+use crate::logbuf::LogBuffer;
+
+// 日志搜索：界面日志里逐字高亮、上一个/下一个跳转并滚动到匹配处；
+// 界面只保留最后几千行，更早的内容到磁盘日志里逐行流式搜索，不整个读进内存
+
+// 磁盘搜索每处匹配前后各带几行，最多列出多少处
+const CONTEXT: usize = 2;
+const MAX_HITS: usize = 500;
+
+// 匹配位置（字节范围）。不区分大小写时只忽略英文字母的大小写：
+// 日志里的中文没有大小写，这样转换前后字节位置不变，可以直接用来高亮原文
+pub fn find(text: &str, query: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let found = |hay: &str, needle: &str| hay.match_indices(needle).map(|(i, _)| i..i + needle.len()).collect();
+    if case_sensitive {
+        found(text, query)
+    } else {
+        found(&text.to_ascii_lowercase(), &query.to_ascii_lowercase())
+    }
+}
+
+// 等宽字体的文本，匹配处加底色，当前匹配用选中色
+fn highlight(ui: &Ui, text: &str, matches: &[Range<usize>], current: Option<usize>) -> LayoutJob {
+    let plain = TextFormat {
+        font_id: egui::TextStyle::Monospace.resolve(ui.style()),
+        color: ui.visuals().text_color(),
+        ..Default::default()
+    };
+    let hit = TextFormat { background: ui.visuals().warn_fg_color.gamma_multiply(0.35), ..plain.clone() };
+    let selected = TextFormat { background: ui.visuals().selection.bg_fill, ..plain.clone() };
+    let mut job = LayoutJob::default();
+    let mut at = 0;
+    for (i, range) in matches.iter().enumerate() {
+        job.append(&text[at..range.start], 0.0, plain.clone());
+        let format = if current == Some(i) { selected.clone() } else { hit.clone() };
+        job.append(&text[range.clone()], 0.0, format);
+        at = range.end;
+    }
+    job.append(&text[at..], 0.0, plain);
+    job
+}
+
+pub struct Hit {
+    // 从 1 开始的行号
+    pub line_no: usize,
+    pub before: Vec<String>,
+    pub line: String,
+    pub after: Vec<String>,
+}
+
+pub struct DiskResult {
+    pub query: String,
+    pub case_sensitive: bool,
+    pub hits: Vec<Hit>,
+    // 超过 MAX_HITS 处后不再记录
+    pub truncated: bool,
+}
+
+// 逐行读磁盘日志，内存里只留前几行上下文和结果。不是 UTF-8 的行按有损转换处理
+pub fn search_file(path: &Path, query: &str, case_sensitive: bool, cancel: &CancelToken) -> io::Result<DiskResult> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut result = DiskResult { query: query.to_string(), case_sensitive, hits: Vec::new(), truncated: false };
+    let mut before: VecDeque<String> = VecDeque::with_capacity(CONTEXT + 1);
+    // 还在等后文的匹配（在 hits 里的序号）
+    let mut open: Vec<usize> = Vec::new();
+    let mut buf = Vec::new();
+    let mut line_no = 0;
+    loop {
+        if cancel.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "已取消"));
+        }
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_no += 1;
+        let line = String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_string();
+        open.retain(|&i| {
+            let hit = &mut result.hits[i];
+            hit.after.push(line.clone());
+            hit.after.len() < CONTEXT
+        });
+        if !find(&line, query, case_sensitive).is_empty() {
+            if result.hits.len() == MAX_HITS {
+                result.truncated = true;
+                if open.is_empty() {
+                    break;
+                }
+            } else {
+                open.push(result.hits.len());
+                result.hits.push(Hit { line_no, before: before.iter().cloned().collect(), line: line.clone(), after: Vec::new() });
+            }
+        }
+        before.push_back(line);
+        if before.len() > CONTEXT {
+            before.pop_front();
+        }
+    }
+    Ok(result)
+}
+
+enum DiskState {
+    Idle,
+    Running(CancelToken),
+    Done(Result<DiskResult, String>),
+}
+
+// 日志区域上方的搜索栏和日志本身
+pub struct LogSearch {
+    query: String,
+    case_sensitive: bool,
+    // 当前定位到第几处匹配
+    current: usize,
+    // 换了匹配后滚动一次，之后不再拉住滚动条
+    scroll: bool,
+    disk: Arc<Mutex<DiskState>>,
+}
+
+impl Default for LogSearch {
+    fn default() -> Self {
+        LogSearch { query: String::new(), case_sensitive: false, current: 0, scroll: false, disk: Arc::new(Mutex::new(DiskState::Idle)) }
+    }
+}
+
+impl LogSearch {
+    pub fn show(&mut self, ui: &mut Ui, log: &LogBuffer, name: &str) {
+        let text = log.as_str();
+        let matches = find(text, &self.query, self.case_sensitive);
+        self.current = self.current.min(matches.len().saturating_sub(1));
+        ui.horizontal(|ui| {
+            let label = ui.label("搜索日志:");
+            let edit = ui.text_edit_singleline(&mut self.query).labelled_by(label.id);
+            if edit.changed() {
+                self.current = 0;
+                self.scroll = true;
+            }
+            // 回车跳到下一处，Shift+回车上一处，焦点留在搜索框里
+            if edit.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                if ui.input(|i| i.modifiers.shift) {
+                    self.step(matches.len(), false);
+                } else {
+                    self.step(matches.len(), true);
+                }
+                edit.request_focus();
+            }
+            if ui.checkbox(&mut self.case_sensitive, "区分大小写").changed() {
+                self.current = 0;
+                self.scroll = true;
+            }
+            ui.add_enabled_ui(!matches.is_empty(), |ui| {
+                if a11y::named(ui.button("⏶"), "上一处匹配").clicked() {
+                    self.step(matches.len(), false);
+                }
+                if a11y::named(ui.button("⏷"), "下一处匹配").clicked() {
+                    self.step(matches.len(), true);
+                }
+            });
+            if !self.query.is_empty() {
+                if matches.is_empty() {
+                    ui.label("没有匹配");
+                } else {
+                    ui.label(format!("第 {} / {} 处", self.current + 1, matches.len()));
+                }
+            }
+        });
+        if let Some(path) = log.disk_path() {
+            self.disk_search(ui, path);
+        }
+
+        let current = (!matches.is_empty()).then_some(self.current);
+        ScrollArea::vertical().id_source("log_search").show(ui, |ui| {
+            let mut layouter = |ui: &Ui, text: &str, wrap_width: f32| {
+                let mut job = highlight(ui, text, &matches, current);
+                job.wrap.max_width = wrap_width;
+                ui.fonts(|f| f.layout_job(job))
+            };
+            let mut shown = text;
+            let output = egui::TextEdit::multiline(&mut shown)
+                .font(egui::TextStyle::Monospace)
+                .desired_width(f32::INFINITY)
+                .layouter(&mut layouter)
+                .show(ui);
+            a11y::named(output.response, name);
+            if self.scroll
+                && let Some(range) = current.map(|i| &matches[i])
+            {
+                let cursor = output.galley.from_ccursor(CCursor::new(text[..range.start].chars().count()));
+                let rect = output.galley.pos_from_cursor(&cursor).translate(output.text_draw_pos.to_vec2());
+                ui.scroll_to_rect(rect, Some(Align::Center));
+            }
+            self.scroll = false;
+        });
+    }
+
+    fn step(&mut self, count: usize, forward: bool) {
+        if count == 0 {
+            return;
+        }
+        self.current = if forward { (self.current + 1) % count } else { (self.current + count - 1) % count };
+        self.scroll = true;
+    }
+
+    // 界面日志裁掉的部分在磁盘日志里，后台逐行搜索，结果单独列出
+    fn disk_search(&mut self, ui: &mut Ui, path: &Path) {
+        let mut disk = self.disk.lock().unwrap();
+        ui.horizontal(|ui| match &*disk {
+            DiskState::Running(cancel) => {
+                ui.spinner();
+                ui.label("正在搜索磁盘日志…");
+                if ui.button("取消").clicked() {
+                    cancel.cancel();
+                }
+            }
+            _ => {
+                let button = ui.add_enabled(!self.query.is_empty(), egui::Button::new("搜索完整磁盘日志"));
+                if button.on_hover_text(path.display().to_string()).clicked() {
+                    let cancel = CancelToken::new();
+                    *disk = DiskState::Running(cancel.clone());
+                    let (state, path, query, case) = (self.disk.clone(), PathBuf::from(path), self.query.clone(), self.case_sensitive);
+                    let ctx = ui.ctx().clone();
+                    thread::spawn(move || {
+                        let result = search_file(&path, &query, case, &cancel).map_err(|e| e.to_string());
+                        *state.lock().unwrap() = DiskState::Done(result);
+                        ctx.request_repaint();
+                    });
+                }
+                if matches!(&*disk, DiskState::Done(_)) && ui.button("关闭结果").clicked() {
+                    *disk = DiskState::Idle;
+                }
+            }
+        });
+        match &*disk {
+            DiskState::Done(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, format!("无法搜索磁盘日志: {}", e));
+            }
+            DiskState::Done(Ok(result)) => {
+                let more = if result.truncated { format!("，只列出前 {} 处", MAX_HITS) } else { String::new() };
+                ui.label(format!("磁盘日志中“{}”共 {} 处{}", result.query, result.hits.len(), more));
+                ScrollArea::vertical().id_source("disk_hits").max_height(250.0).show(ui, |ui| {
+                    for hit in &result.hits {
+                        ui.label(format!("第 {} 行", hit.line_no));
+                        for line in &hit.before {
+                            ui.weak(egui::RichText::new(line).monospace());
+                        }
+                        let matches = find(&hit.line, &result.query, result.case_sensitive);
+                        ui.label(highlight(ui, &hit.line, &matches, None));
+                        for line in &hit.after {
+                            ui.weak(egui::RichText::new(line).monospace());
+                        }
+                        ui.separator();
+                    }
+                });
+            }
+            _ => {}
+        }
+    }
+}
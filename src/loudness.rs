@@ -0,0 +1,151 @@
+use std::io::Read;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+use crate::cancel::CancelToken;
+use crate::process;
+
+// 用 ebur128 滤镜测响度：解码第一条音轨跑一遍，读 stderr 最后的汇总。
+// 只测不转换，输出丢给 null
+
+// 取消时 measure_until 返回的错误
+pub const CANCELLED: &str = "已取消";
+
+// 响度标准化的默认目标（播客、流媒体常用的 -16 LUFS），以及 loudnorm 接受的范围
+pub const DEFAULT_TARGET: f64 = -16.0;
+pub const TARGET_MIN: f64 = -70.0;
+pub const TARGET_MAX: f64 = -5.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Loudness {
+    // 综合响度 LUFS
+    pub integrated: f64,
+    // 响度范围 LU
+    pub range: f64,
+    // 真峰值 dBFS
+    pub true_peak: f64,
+}
+
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    line.trim().strip_prefix(key)?.split_whitespace().next()?.parse().ok()
+}
+
+// 汇总部分形如：
+//   Integrated loudness:
+//     I:         -16.1 LUFS
+//   Loudness range:
+//     LRA:         5.0 LU
+//   True peak:
+//     Peak:       -0.5 dBFS
+// 前面每 100ms 一行的实时数值里也有 I: 和 LRA:，只看 Summary 之后的
+pub fn parse_summary(stderr: &str) -> Option<Loudness> {
+    let summary = &stderr[stderr.rfind("Summary:")?..];
+    let mut integrated = None;
+    let mut range = None;
+    let mut true_peak = None;
+    for line in summary.lines() {
+        integrated = integrated.or_else(|| value_after(line, "I:"));
+        range = range.or_else(|| value_after(line, "LRA:"));
+        true_peak = true_peak.or_else(|| value_after(line, "Peak:"));
+    }
+    // 全程静音时 ffmpeg 写 -inf，解析成负无穷
+    Some(Loudness { integrated: integrated?, range: range.unwrap_or(0.0), true_peak: true_peak.unwrap_or(f64::NEG_INFINITY) })
+}
+
+// 单遍 loudnorm：按 EBU R128 把综合响度调到 target LUFS，真峰值不超过 -1.5 dBTP。
+// 它内部按 192 kHz 处理，输出也是 192 kHz，后面再换回源文件的采样率
+pub fn normalize_filter(target: f64, sample_rate: u64) -> String {
+    format!("loudnorm=I={:.1}:TP=-1.5:LRA=11,aresample={}", target, sample_rate)
+}
+
+pub fn measure(input: &str) -> Result<Loudness, String> {
+    measure_until(input, &CancelToken::new())
+}
+
+// 批量测量时可以中途取消：结束正在跑的 ffmpeg，返回 Err
+pub fn measure_until(input: &str, cancel: &CancelToken) -> Result<Loudness, String> {
+    let mut child = process::command("ffmpeg")
+        .args([
+            "-nostdin", "-hide_banner", "-nostats",
+            "-i", input,
+            "-map", "0:a:0",
+            "-af", "ebur128=peak=true",
+            "-f", "null", "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("无法启动 ffmpeg: {}", e))?;
+    let mut pipe = child.stderr.take();
+    let reader = thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(pipe) = &mut pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    });
+    let status = loop {
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = reader.join();
+            return Err(CANCELLED.to_string());
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => thread::sleep(Duration::from_millis(100)),
+            Err(e) => return Err(format!("ffmpeg 异常: {}", e)),
+        }
+    };
+    let stderr = reader.join().unwrap_or_default();
+    let stderr = String::from_utf8_lossy(&stderr);
+    if !status.success() {
+        return Err(stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("ffmpeg 无法分析音频").trim().to_string());
+    }
+    parse_summary(&stderr).ok_or("没有读到响度汇总（文件里可能没有音轨）".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ffmpeg 在 C locale 下的输出，前面的实时行也有 I: 和 LRA:
+    const C_LOCALE: &str = "\
+[Parsed_ebur128_0 @ 0x55d] t: 9.9     TARGET:-23 LUFS    M: -18.2 S: -17.9     I: -20.4 LUFS       LRA:   3.1 LU
+[Parsed_ebur128_0 @ 0x55d] Summary:
+
+  Integrated loudness:
+    I:         -16.1 LUFS
+    Threshold: -26.3 LUFS
+
+  Loudness range:
+    LRA:         5.0 LU
+    Threshold: -36.2 LUFS
+    LRA low:   -19.5 LUFS
+    LRA high:  -14.5 LUFS
+
+  True peak:
+    Peak:       -0.5 dBFS
+";
+
+    #[test]
+    fn summary_ignores_running_values() {
+        let l = parse_summary(C_LOCALE).unwrap();
+        assert_eq!((l.integrated, l.range, l.true_peak), (-16.1, 5.0, -0.5));
+    }
+
+    #[test]
+    fn silent_input_reads_negative_infinity() {
+        let l = parse_summary(&C_LOCALE.replace("-0.5 dBFS", "-inf dBFS")).unwrap();
+        assert_eq!(l.true_peak, f64::NEG_INFINITY);
+    }
+
+    // 没有强制 C locale 时某些打包版本输出逗号小数，宁可解析失败也不能读成别的数
+    #[test]
+    fn comma_decimals_are_rejected() {
+        assert!(parse_summary(&C_LOCALE.replace("-16.1", "-16,1")).is_none());
+        assert!(parse_summary("Zusammenfassung:\n    I: -16.1 LUFS\n").is_none());
+    }
+}
@@ -0,0 +1,465 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::a11y;
+use crate::cancel::CancelToken;
+use crate::config;
+use crate::joblist::{self, Job};
+use crate::loudness::{self, Loudness};
+use crate::output;
+use crate::pipeline;
+use crate::plan::{self, JobSettings};
+
+// 文件夹响度普查：对每个音视频文件跑一遍 ebur128，只测不转换。
+// 几个文件同时测，结果列成可排序的表格，可以导出 CSV；
+// 超出目标范围的文件一次加入任务列表，转换时做响度标准化
+
+// 按扩展名挑出要测的文件
+const MEDIA_EXTS: [&str; 22] = [
+    "mp4", "m4v", "mkv", "mov", "avi", "flv", "wmv", "webm", "ts", "mts", "m2ts", "mpg", "mpeg",
+    "mp3", "aac", "m4a", "wav", "flac", "ogg", "opus", "wma", "aiff",
+];
+
+// 连同子文件夹一起找，按路径排序；子文件夹读不了时跳过
+pub fn collect(dir: &Path) -> io::Result<Vec<String>> {
+    fs::read_dir(dir)?;
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().and_then(|e| e.to_str()).is_some_and(|e| MEDIA_EXTS.contains(&e.to_ascii_lowercase().as_str())) {
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// 判断超标的范围
+#[derive(Clone, Copy, PartialEq)]
+pub struct Target {
+    // 目标综合响度 LUFS，也是加入队列后标准化的目标
+    pub integrated: f64,
+    // 允许偏离目标多少 LU
+    pub tolerance: f64,
+    // 真峰值上限 dBTP
+    pub max_peak: f64,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target { integrated: loudness::DEFAULT_TARGET, tolerance: 2.0, max_peak: -1.0 }
+    }
+}
+
+impl Target {
+    // 超标的原因，在范围内时返回 None
+    pub fn check(&self, l: &Loudness) -> Option<String> {
+        let mut reasons = Vec::new();
+        let off = l.integrated - self.integrated;
+        if off > self.tolerance {
+            reasons.push(format!("偏响 {:.1} LU", off));
+        } else if off < -self.tolerance {
+            reasons.push(format!("偏轻 {:.1} LU", -off));
+        }
+        if l.true_peak > self.max_peak {
+            reasons.push(format!("峰值 {:.1} dBTP", l.true_peak));
+        }
+        (!reasons.is_empty()).then(|| reasons.join("，"))
+    }
+}
+
+#[derive(Clone)]
+pub enum State {
+    Waiting,
+    Measuring,
+    Done(Loudness),
+    Failed(String),
+}
+
+// 同时测几个文件：解码音频基本只占一个核，取核数的一半，最多 8 个
+pub fn workers(files: usize) -> usize {
+    let cores = thread::available_parallelism().map_or(2, |n| n.get());
+    (cores / 2).clamp(1, 8).min(files.max(1))
+}
+
+// 一次普查。每个工作线程测完一个再取下一个，长文件不会拖住后面整排
+pub struct Survey {
+    files: Vec<String>,
+    states: Arc<Mutex<Vec<State>>>,
+    cancel: CancelToken,
+}
+
+impl Survey {
+    pub fn start(files: Vec<String>) -> Survey {
+        let states = Arc::new(Mutex::new(vec![State::Waiting; files.len()]));
+        let cancel = CancelToken::new();
+        let next = Arc::new(AtomicUsize::new(0));
+        let shared = Arc::new(files.clone());
+        for _ in 0..workers(files.len()) {
+            let (files, states, cancel, next) = (shared.clone(), states.clone(), cancel.clone(), next.clone());
+            thread::spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= files.len() || cancel.is_cancelled() {
+                    break;
+                }
+                states.lock().unwrap()[i] = State::Measuring;
+                let state = match loudness::measure_until(&files[i], &cancel) {
+                    Ok(l) => State::Done(l),
+                    Err(e) => State::Failed(e),
+                };
+                states.lock().unwrap()[i] = state;
+            });
+        }
+        Survey { files, states, cancel }
+    }
+
+    // 正在测的结束 ffmpeg，还没轮到的不再测
+    pub fn stop(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub fn states(&self) -> Vec<State> {
+        self.states.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Survey {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) { format!("\"{}\"", text.replace('"', "\"\"")) } else { text.to_string() }
+}
+
+// 带 BOM，Excel 直接打开不会把中文显示成乱码
+pub fn to_csv(files: &[String], states: &[State], target: &Target) -> String {
+    let mut csv = "\u{feff}文件,综合响度 (LUFS),真峰值 (dBTP),响度范围 (LU),超标,错误\n".to_string();
+    for (file, state) in files.iter().zip(states) {
+        let fields = match state {
+            State::Done(l) => [
+                format!("{:.1}", l.integrated),
+                format!("{:.1}", l.true_peak),
+                format!("{:.1}", l.range),
+                target.check(l).unwrap_or_default(),
+                String::new(),
+            ],
+            State::Failed(e) => [String::new(), String::new(), String::new(), String::new(), e.clone()],
+            State::Waiting | State::Measuring => Default::default(),
+        };
+        let row: Vec<String> = std::iter::once(file.as_str()).chain(fields.iter().map(|f| f.as_str())).map(csv_field).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// 超标文件的转换任务：目标格式能直接用原来的扩展名时保持不变，输出加 _loudnorm 后缀，
+// 否则用默认格式、默认输出名；音频按目标做响度标准化
+pub fn normalize_jobs(files: &[String], states: &[State], target: &Target) -> Vec<Job> {
+    let config = config::current();
+    files.iter().zip(states)
+        .filter(|(_, state)| matches!(state, State::Done(l) if target.check(l).is_some()))
+        .map(|(file, _)| {
+            let ext = Path::new(file).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            let keep = plan::FORMATS.contains(&ext.as_str());
+            let settings = JobSettings {
+                format: if keep { ext } else { config.format.clone() },
+                gpu: config.gpu.clone(),
+                loudnorm: Some(target.integrated),
+                ..Default::default()
+            };
+            let output = if keep { output::tagged_output(file, "loudnorm") } else { String::new() };
+            Job { input: file.clone(), output, settings, stages: pipeline::default_stages(), problem: None }
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Column {
+    Name,
+    Integrated,
+    Peak,
+    Range,
+    Flag,
+}
+
+impl Column {
+    const ALL: [Column; 5] = [Column::Name, Column::Integrated, Column::Peak, Column::Range, Column::Flag];
+
+    fn label(self) -> &'static str {
+        match self {
+            Column::Name => "文件",
+            Column::Integrated => "综合响度",
+            Column::Peak => "真峰值",
+            Column::Range => "响度范围",
+            Column::Flag => "超标",
+        }
+    }
+
+    fn width(self) -> f32 {
+        match self {
+            Column::Name => 280.0,
+            Column::Flag => 200.0,
+            _ => 90.0,
+        }
+    }
+
+    fn value(self, l: &Loudness) -> f64 {
+        match self {
+            Column::Integrated => l.integrated,
+            Column::Peak => l.true_peak,
+            Column::Range => l.range,
+            Column::Name | Column::Flag => 0.0,
+        }
+    }
+}
+
+// 文件夹响度报告窗口，命令行 --loudness 和设置窗口都用它
+pub struct SurveyApp {
+    folder: String,
+    target: Target,
+    survey: Option<Survey>,
+    // (列, 升序)，None 表示按路径
+    sort: Option<(Column, bool)>,
+    only_flagged: bool,
+    csv_path: String,
+    joblist_path: String,
+    message: String,
+}
+
+impl SurveyApp {
+    pub fn new(folder: String) -> Self {
+        let mut app = SurveyApp {
+            folder,
+            target: Target::default(),
+            survey: None,
+            sort: None,
+            only_flagged: false,
+            csv_path: String::new(),
+            joblist_path: String::new(),
+            message: String::new(),
+        };
+        if !app.folder.is_empty() {
+            app.scan();
+        }
+        app
+    }
+
+    fn scan(&mut self) {
+        let dir = Path::new(self.folder.trim()).to_path_buf();
+        match collect(&dir) {
+            Ok(files) if files.is_empty() => self.message = "这个文件夹里没有音视频文件".to_string(),
+            Ok(files) => {
+                self.message = format!("共 {} 个文件，同时测 {} 个", files.len(), workers(files.len()));
+                self.csv_path = dir.join("loudness.csv").to_string_lossy().into_owned();
+                if self.joblist_path.is_empty() {
+                    self.joblist_path = dir.join("jobs.json").to_string_lossy().into_owned();
+                }
+                self.survey = Some(Survey::start(files));
+            }
+            Err(e) => self.message = format!("无法读取 {}: {}", dir.display(), e),
+        }
+    }
+
+    fn state_label(state: &State, stopped: bool) -> String {
+        match state {
+            State::Waiting if stopped => "已停止".to_string(),
+            State::Waiting => "等待中".to_string(),
+            State::Measuring => "测量中…".to_string(),
+            State::Done(_) => String::new(),
+            State::Failed(e) => format!("失败：{}", e),
+        }
+    }
+
+    // 显示顺序：没有结果的排在最后，不论升降序
+    fn view_order(&self, files: &[String], states: &[State]) -> Vec<usize> {
+        let flagged = |i: usize| matches!(&states[i], State::Done(l) if self.target.check(l).is_some());
+        let mut order: Vec<usize> = (0..files.len()).filter(|&i| !self.only_flagged || flagged(i)).collect();
+        let Some((column, ascending)) = self.sort else {
+            return order;
+        };
+        order.sort_by(|&a, &b| {
+            let ordering = match (&states[a], &states[b]) {
+                _ if column == Column::Name => files[a].cmp(&files[b]),
+                _ if column == Column::Flag => flagged(a).cmp(&flagged(b)),
+                (State::Done(x), State::Done(y)) => column.value(x).total_cmp(&column.value(y)),
+                (State::Done(_), _) => return CmpOrdering::Less,
+                (_, State::Done(_)) => return CmpOrdering::Greater,
+                _ => CmpOrdering::Equal,
+            };
+            if ascending { ordering } else { ordering.reverse() }
+        });
+        order
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.heading("文件夹响度报告");
+        let running = self.survey.as_ref().is_some_and(|s| {
+            !s.stopped() && s.states().iter().any(|st| matches!(st, State::Waiting | State::Measuring))
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label("文件夹");
+            ui.add(egui::TextEdit::singleline(&mut self.folder).desired_width(360.0)).labelled_by(label.id);
+            if running {
+                if ui.button("停止").clicked()
+                    && let Some(survey) = &self.survey
+                {
+                    survey.stop();
+                }
+            } else if ui.add_enabled(!self.folder.trim().is_empty(), egui::Button::new("开始测量")).clicked() {
+                self.scan();
+            }
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label("目标");
+            ui.add(egui::DragValue::new(&mut self.target.integrated).clamp_range(loudness::TARGET_MIN..=loudness::TARGET_MAX).speed(0.5).suffix(" LUFS"))
+                .labelled_by(label.id);
+            let label = ui.label("±");
+            ui.add(egui::DragValue::new(&mut self.target.tolerance).clamp_range(0.5..=10.0).speed(0.1).suffix(" LU")).labelled_by(label.id);
+            let label = ui.label("真峰值上限");
+            ui.add(egui::DragValue::new(&mut self.target.max_peak).clamp_range(-9.0..=0.0).speed(0.1).suffix(" dBTP")).labelled_by(label.id);
+        });
+        if !self.message.is_empty() {
+            ui.label(&self.message);
+        }
+        let Some(survey) = &self.survey else {
+            return;
+        };
+        let files = survey.files().to_vec();
+        let states = survey.states();
+        let stopped = survey.stopped();
+        let finished = states.iter().filter(|s| matches!(s, State::Done(_) | State::Failed(_))).count();
+        let flagged = states.iter().filter(|s| matches!(s, State::Done(l) if self.target.check(l).is_some())).count();
+        let fraction = finished as f32 / files.len() as f32;
+        a11y::progress(ui.add(egui::ProgressBar::new(fraction).text(format!("已测 {} / {}，超标 {} 个", finished, files.len(), flagged))), fraction, "响度测量");
+        ui.checkbox(&mut self.only_flagged, "只显示超标的文件");
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+        // 表头：点列名排序，再点一次反向
+        ui.horizontal(|ui| {
+            for column in Column::ALL {
+                let arrow = match self.sort {
+                    Some((c, true)) if c == column => " ▲",
+                    Some((c, false)) if c == column => " ▼",
+                    _ => "",
+                };
+                let text = egui::RichText::new(format!("{}{}", column.label(), arrow)).strong();
+                if a11y::named(ui.add_sized([column.width(), row_height], egui::Button::new(text).frame(false)), &format!("按{}排序", column.label())).clicked() {
+                    self.sort = match self.sort {
+                        Some((c, true)) if c == column => Some((c, false)),
+                        _ => Some((column, true)),
+                    };
+                }
+            }
+        });
+        let order = self.view_order(&files, &states);
+        egui::ScrollArea::vertical().max_height(400.0).auto_shrink([false, true]).show_rows(ui, row_height, order.len(), |ui, range| {
+            for &i in &order[range] {
+                let name = Path::new(&files[i]).file_name().map_or(files[i].clone(), |n| n.to_string_lossy().into_owned());
+                let (cells, color) = match &states[i] {
+                    State::Done(l) => {
+                        let flag = self.target.check(l);
+                        let color = flag.is_some().then_some(egui::Color32::YELLOW);
+                        ([
+                            name,
+                            format!("{:.1} LUFS", l.integrated),
+                            format!("{:.1} dBTP", l.true_peak),
+                            format!("{:.1} LU", l.range),
+                            flag.unwrap_or_default(),
+                        ], color)
+                    }
+                    state => {
+                        let color = matches!(state, State::Failed(_)).then_some(egui::Color32::RED);
+                        ([name, String::new(), String::new(), String::new(), Self::state_label(state, stopped)], color)
+                    }
+                };
+                ui.horizontal(|ui| {
+                    for (column, text) in Column::ALL.into_iter().zip(cells) {
+                        ui.allocate_ui_with_layout(
+                            egui::vec2(column.width(), row_height),
+                            egui::Layout::left_to_right(egui::Align::Center),
+                            |ui| {
+                                ui.set_clip_rect(ui.max_rect().intersect(ui.clip_rect()));
+                                ui.set_min_width(column.width());
+                                let mut rich = egui::RichText::new(&text);
+                                if let Some(color) = color.filter(|_| column == Column::Flag) {
+                                    rich = rich.color(color);
+                                }
+                                let hover = if column == Column::Name { &files[i] } else { &text };
+                                ui.add(egui::Label::new(rich).wrap(false).sense(egui::Sense::hover())).on_hover_text(hover);
+                            },
+                        );
+                    }
+                });
+            }
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let label = ui.label("CSV");
+            ui.add(egui::TextEdit::singleline(&mut self.csv_path).desired_width(360.0)).labelled_by(label.id);
+            if cfg!(target_os = "windows") && ui.button("浏览…").clicked()
+                && let Some(path) = output::save_dialog(&self.csv_path, "csv")
+            {
+                self.csv_path = path;
+            }
+            if ui.add_enabled(finished > 0 && !self.csv_path.trim().is_empty(), egui::Button::new("导出 CSV")).clicked() {
+                let path = self.csv_path.trim();
+                self.message = match fs::write(path, to_csv(&files, &states, &self.target)) {
+                    Ok(()) => format!("已导出 {}", path),
+                    Err(e) => format!("无法写入 {}: {}", path, e),
+                };
+            }
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label("任务列表");
+            ui.add(egui::TextEdit::singleline(&mut self.joblist_path).hint_text("jobs.json").desired_width(360.0)).labelled_by(label.id);
+            let enabled = flagged > 0 && !self.joblist_path.trim().is_empty();
+            if ui.add_enabled(enabled, egui::Button::new("将超标文件加入队列并应用响度标准化"))
+                .on_hover_text("按当前目标响度加入任务列表，用 ffui --queue 运行")
+                .clicked()
+            {
+                let path = Path::new(self.joblist_path.trim());
+                let jobs = normalize_jobs(&files, &states, &self.target);
+                let added = jobs.len();
+                self.message = match joblist::extend(path, jobs, true) {
+                    Ok(n) => format!("已加入 {} 个任务，列表里共 {} 个", added, n),
+                    Err(e) => e,
+                };
+            }
+        });
+    }
+}
+
+impl eframe::App for SurveyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| self.show(ui));
+        // 测量结果在后台线程里更新
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+}
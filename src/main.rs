@@ -1,46 +1,107 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use eframe::{egui, App};
-use std::process::{Command, Stdio, Child};
-use std::io::{BufRead, BufReader};
+use std::process::Child;
 use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use std::thread;
+use std::time::{Duration, Instant};
 use std::path::Path;
 use std::env;
-use egui::{FontDefinitions, FontFamily};
+use cancel::CancelToken;
+use plan::{AudioCodec, JobSettings, TrackChoice};
+use quick::QuickOp;
+use resolution::Resolution;
+use process::FfError;
+use runner::StopMode;
+use egui::FontDefinitions;
 
-#[cfg(target_os="windows")]
-use std::os::windows::process::CommandExt;
+mod a11y;
+mod album;
+mod args;
+mod aspect;
+mod audiocompare;
+mod autopreset;
+mod av1;
+mod batch;
+mod bottleneck;
+mod budget;
+mod burnin;
+mod cancel;
+mod cli;
+mod compare;
+mod config;
+mod confirm;
+mod crash;
+mod coverart;
+mod dataloss;
+mod diagnose;
+mod encoders;
+mod errors;
+mod filedate;
+mod filterchain;
+mod framerate;
+mod filelock;
+mod gop;
+mod gputest;
+mod hash;
+mod history;
+mod hook;
+mod hwlimit;
+mod inspect;
+mod joblist;
+mod json;
+mod interlace;
+mod ladder;
+mod lengths;
+mod latest;
+mod live;
+mod logbuf;
+mod loudness;
+mod loudscan;
+mod logsearch;
+mod monitor;
+mod mp4meta;
+mod nvsession;
+mod onboarding;
+mod output;
+mod paths;
+mod pipeline;
+mod plan;
+mod preset;
+mod presets;
+mod probe;
+mod quality;
+mod quick;
+mod resolution;
+mod process;
+mod publish;
+mod queueview;
+mod retime;
+mod runner;
+mod sample;
+mod selftest;
+mod session;
+mod snapshot;
+mod speech;
+mod stats;
+mod subtitle;
+mod tempfiles;
+mod thermal;
+mod throttle;
+mod timecode;
+mod timestamp;
+mod undo;
+mod verify;
+mod warnings;
+mod watchdog;
+mod web;
 
 #[cfg(target_os = "windows")]
-mod winctx {
-    use std::io;
-    use std::path::PathBuf;
-    use winreg::enums::*;
-    use winreg::RegKey;
-
-    pub fn add_context_menu(app_path: &str) -> io::Result<()> {
-        let hkcr = RegKey::predef(HKEY_CLASSES_ROOT);
-        let (shell, _) = hkcr.create_subkey(r"*\\shell\\FFmpeg_Transcoder")?;
-        shell.set_value("", &"使用 FFmpeg 转换")?;
-        let (cmd, _) = shell.create_subkey("command")?;
-        let command = format!("\"{}\" \"%1\"", app_path);
-        cmd.set_value("", &command)?;
-        Ok(())
-    }
-
-    pub fn remove_context_menu() -> io::Result<()> {
-        let hkcr = RegKey::predef(HKEY_CLASSES_ROOT);
-        hkcr.delete_subkey_all(r"*\\shell\\FFmpeg_Transcoder")?;
-        Ok(())
-    }
-
-    pub fn get_app_path() -> PathBuf {
-        std::env::current_exe().unwrap_or_else(|_| PathBuf::from("ffmpeg_gui.exe"))
-    }
-}
+mod winctx;
+mod zipfile;
 
 fn setup_fonts(ctx: &egui::Context) {
+    #[allow(unused_mut)]
     let mut fonts = FontDefinitions::default();
 
     // 尝试加载系统常见中文字体
@@ -48,7 +109,7 @@ fn setup_fonts(ctx: &egui::Context) {
     {
         let yahei = r"C:\Windows\Fonts\msyh.ttc"; // Microsoft YaHei
         if Path::new(yahei).exists() {
-            use egui::FontData;
+            use egui::{FontData, FontFamily};
 
             fonts.font_data.insert(
                 "yahei".to_owned(),
@@ -63,255 +124,3660 @@ fn setup_fonts(ctx: &egui::Context) {
     ctx.set_fonts(fonts);
 }
 
+// 设置文件读不出来时在顶部提示，不挡住其余界面；处理失败时返回说明
+fn config_banner(ui: &mut egui::Ui) -> Option<String> {
+    let problem = config::problem()?;
+    let mut result = None;
+    ui.horizontal_wrapped(|ui| {
+        ui.colored_label(egui::Color32::YELLOW, format!("设置文件有问题（{}），暂时使用能读出的部分", problem));
+        if config::has_backup() && ui.button("恢复备份").clicked() {
+            result = Some(config::restore_backup());
+        }
+        if ui.button("重置为默认").on_hover_text("原文件会另存为 config.txt.broken").clicked() {
+            result = Some(config::reset());
+        }
+        if ui.button("查看文件").clicked() {
+            let _ = process::open_file(&config::config_path().to_string_lossy());
+        }
+    });
+    match result? {
+        Ok(()) => {
+            config::current().theme.apply(ui.ctx());
+            None
+        }
+        Err(e) => Some(e),
+    }
+}
+
+// 命令预览里鼠标停在某个来源的参数或图例上时，给加这些参数的设置控件画框
+fn mark_source(response: &egui::Response, hovered: Option<args::Source>, sources: &[args::Source]) {
+    if hovered.is_some_and(|s| sources.contains(&s)) {
+        let stroke = egui::Stroke::new(2.0, response.ctx.style().visuals.selection.stroke.color);
+        response.ctx.layer_painter(response.layer_id).rect_stroke(response.rect.expand(2.0), 2.0, stroke);
+    }
+}
+
+const PREVIEW_SECS: u32 = 30;
+const PAUSED_BY_BUDGET: &str = "节能界面：转换期间暂停刷新";
+// 界面日志最多保留的行数
+const LOG_LINES: usize = 5000;
+
 struct FFUIApp {
     file: String,
-    format: String,
-    gpu: String,
-    progress: Arc<Mutex<f32>>,
+    settings: JobSettings,
+    info: Option<probe::MediaInfo>,
+    // 进度、剩余时间、编码速度和用时，工作线程限频整份发布
+    progress: latest::Reader<runner::Progress>,
     running: Arc<Mutex<bool>>,
-    log_text: Arc<Mutex<String>>,
+    log_text: Arc<Mutex<logbuf::LogBuffer>>,
+    log_search: logsearch::LogSearch,
     completed: Arc<Mutex<bool>>,
+    failure: Arc<Mutex<Option<errors::ErrorHint>>>,
+    // 失败时 ffmpeg stderr 的最后几百行，和界面日志分开保存
+    failure_detail: Arc<Mutex<String>>,
+    // ffmpeg 崩溃（而不是报错退出）时的原因、报告文件和可以换的重试方式
+    crash: Arc<Mutex<Option<crash::Report>>>,
+    // 成功但 stderr 里有值得注意的警告（“完成但有警告”）
+    job_warnings: Arc<Mutex<warnings::Tally>>,
+    output: String,
     child_process: Arc<Mutex<Option<Child>>>,
-    stop_flag: Arc<AtomicBool>,
+    // 当前任务的取消标记，每次开始换一个新的；编码、校验等阶段各用它的子标记
+    job_cancel: CancelToken,
+    // 已请求停止但任务还没结束时为 Some，界面显示“正在停止…”
+    stop_mode: Arc<Mutex<Option<StopMode>>>,
+    thermal: thermal::ThermalConfig,
+    paused: Arc<Mutex<Option<f32>>>,
+    // (字幕路径, 检测结果)，路径变化时重新检测
+    sub_detected: Option<(String, String)>,
+    // 超过这么多分钟没有输出就认为 ffmpeg 可能挂起
+    hang_minutes: u64,
+    stalled: Arc<Mutex<bool>>,
+    // 最近一次任务是预览时为 Some(预览文件)
+    preview: Option<String>,
+    // 取样预览的段数和每段秒数；最近一次预览用的 (秒数, 段数)
+    sample_count: u32,
+    sample_secs: u32,
+    preview_shape: (u32, u32),
+    // 取样预览推算的完整文件大小说明
+    sample_estimate: Arc<Mutex<Option<String>>>,
+    // 转换前后的音频对比窗口
+    audio_compare: Option<audiocompare::AudioCompare>,
+    stats: stats::StatsCache,
+    // 节能界面的开关，以及转换期间界面进程自己的 CPU 占用
+    budget: budget::UiBudget,
+    // 正在编辑的 ffmpeg 目录；启动和保存时检查，找不到时的说明
+    ffmpeg_dir: String,
+    ffmpeg_missing: Option<String>,
+    // 生成前预览的诊断包，确认后才写出
+    diagnose: Option<diagnose::Bundle>,
+    // 正在添加的命名 ffmpeg 版本
+    build_name: String,
+    build_dir: String,
+    monitor: monitor::MonitorView,
+    // 硬件编码器的试编码结果
+    gpu_tests: gputest::GpuTests,
+    web_platform: web::Platform,
+    // 从“转成可发送的视频”右键菜单打开时只显示一键方案
+    share_mode: bool,
+    // 输入文件被占用时暂存的任务，以及“等待并自动开始”的（取消, 就绪）标志
+    blocked: Option<(JobSettings, String)>,
+    snapshot: snapshot::Snapshot,
+    speech: speech::Speech,
+    album: album::Album,
+    snap_interval: timestamp::TimeField,
+    fps_field: framerate::RateField,
+    trim_fields: [timestamp::TimeField; 2],
+    waiting: Option<(CancelToken, Arc<AtomicBool>)>,
+    // 源文件校验进度，计算中为 Some
+    hash_progress: Arc<Mutex<Option<f32>>>,
+    hash_cancel: CancelToken,
+    // 最近一次普通转换的 (设置, 输出)，“基于此任务新建”用
+    last_job: Option<(JobSettings, String)>,
+    // 基于上次任务新建时记下上次的输出，开始时避开它
+    derived_from: Option<String>,
+    // 比较面板里勾选要合并的设置项
+    diff_pick: Vec<&'static str>,
+    // 比较两个预设时选的预设名称和勾选的设置项
+    preset_pair: (String, String),
+    preset_pick: Vec<&'static str>,
+    // 任务列表文件路径、是否存相对路径、导入的任务和提示
+    joblist_path: String,
+    joblist_relative: bool,
+    // 导出到队列时在转换之后加上的检查
+    joblist_verify: bool,
+    joblist_vmaf: bool,
+    joblist: queueview::QueueView,
+    joblist_message: String,
+    // 命令预览里鼠标停在哪个来源的参数上，同一来源的参数一起高亮
+    hovered_source: Option<args::Source>,
+    // 命令行传入多个文件时的转换队列、正在转换的序号；点“全部开始”后依次转换
+    batch: Vec<batch::BatchItem>,
+    batch_current: Option<usize>,
+    batch_active: bool,
+    // 中断当前文件时一并取消队列里剩下的
+    batch_cancel_rest: bool,
+    // 用户选的输出文件，空表示按输入文件名自动生成；整个窗口共用的输出目录，空表示默认位置
+    output_choice: String,
+    output_dir: String,
+    // 保存的命名预设、名称输入框，以及保存或应用失败时的提示
+    presets: Vec<presets::Preset>,
+    preset_name: String,
+    preset_message: String,
+    // 载入文件时自动套用的预设名称和套用前的设置；编辑匹配规则的预设和测试结果
+    auto_preset: Option<(String, JobSettings)>,
+    rule_preset: String,
+    rule_test: Vec<String>,
+    // 等用户确认的开始清单，以及确认后要做的事；勾了“不再提示”的类型
+    confirm: Option<(confirm::Summary, Confirmed)>,
+    confirm_mute: Vec<confirm::Trigger>,
+    // 误关窗口前没转换的编辑：自动保存，以及启动时等用户决定是否恢复的那一份
+    autosave: session::Autosave,
+    session_offer: Option<session::Saved>,
+    // 设置的撤销/重做
+    history: undo::History,
+}
+
+// 开始前确认通过后要做的事
+enum Confirmed {
+    Start(String),
+    Batch,
 }
 
 impl FFUIApp {
-    fn get_duration(input: &str) -> f64 {
-        let output = Command::new("ffprobe")
-            .args(&[
-                "-v", "error",
-                "-show_entries", "format=duration",
-                "-of", "default=noprint_wrappers=1:nokey=1",
-                input
-            ])
-            .output()
-            .expect("无法执行 ffprobe");
-        String::from_utf8_lossy(&output.stdout).trim().parse::<f64>().unwrap_or(0.0)
+    fn new(file: String) -> Self {
+        let config = config::current();
+        let mut settings = JobSettings { format: config.format, gpu: config.gpu, ..Default::default() };
+        // 上次用过的格式、设备、编码等优先；没有或读不出时用向导里选的默认值
+        if let Some(last) = presets::load_last_used() {
+            let _ = last.apply(&mut settings);
+        }
+        FFUIApp {
+            info: probe::probe(&file, Default::default()).ok(),
+            file,
+            // 启动时的设置是撤销的起点
+            history: undo::History::new(&settings),
+            settings,
+            progress: latest::Reader::new(runner::Progress::default()),
+            running: Arc::new(Mutex::new(false)),
+            log_text: Arc::new(Mutex::new(logbuf::LogBuffer::on_disk(LOG_LINES, paths::store_dir().join("log.txt")))),
+            log_search: logsearch::LogSearch::default(),
+            completed: Arc::new(Mutex::new(false)),
+            failure: Arc::new(Mutex::new(None)),
+            failure_detail: Arc::new(Mutex::new(String::new())),
+            crash: Arc::new(Mutex::new(None)),
+            job_warnings: Arc::new(Mutex::new(warnings::Tally::default())),
+            output: String::new(),
+            child_process: Arc::new(Mutex::new(None)),
+            job_cancel: CancelToken::new(),
+            stop_mode: Arc::new(Mutex::new(None)),
+            thermal: thermal::ThermalConfig::default(),
+            paused: Arc::new(Mutex::new(None)),
+            sub_detected: None,
+            hang_minutes: 5,
+            stalled: Arc::new(Mutex::new(false)),
+            preview: None,
+            sample_count: 3,
+            sample_secs: 60,
+            preview_shape: (PREVIEW_SECS, 1),
+            sample_estimate: Arc::new(Mutex::new(None)),
+            audio_compare: None,
+            stats: stats::StatsCache::default(),
+            budget: budget::UiBudget::default(),
+            ffmpeg_dir: config::current().ffmpeg_dir,
+            ffmpeg_missing: config::missing_tools(),
+            diagnose: None,
+            build_name: String::new(),
+            build_dir: String::new(),
+            monitor: monitor::MonitorView::default(),
+            gpu_tests: gputest::GpuTests::default(),
+            web_platform: web::Platform::WeChat,
+            share_mode: false,
+            blocked: None,
+            snapshot: snapshot::Snapshot::default(),
+            speech: speech::Speech::default(),
+            album: album::Album::default(),
+            snap_interval: timestamp::TimeField::new(snapshot::Snapshot::default().interval),
+            fps_field: framerate::RateField::default(),
+            trim_fields: [timestamp::TimeField::new(Duration::ZERO), timestamp::TimeField::new(Duration::ZERO)],
+            waiting: None,
+            hash_progress: Arc::new(Mutex::new(None)),
+            hash_cancel: CancelToken::new(),
+            last_job: None,
+            derived_from: None,
+            diff_pick: Vec::new(),
+            preset_pair: (String::new(), String::new()),
+            preset_pick: Vec::new(),
+            joblist_path: String::new(),
+            joblist_relative: true,
+            joblist_verify: false,
+            joblist_vmaf: false,
+            joblist: queueview::QueueView::new(),
+            joblist_message: String::new(),
+            hovered_source: None,
+            batch: Vec::new(),
+            batch_current: None,
+            batch_active: false,
+            batch_cancel_rest: false,
+            output_choice: String::new(),
+            output_dir: String::new(),
+            presets: presets::load(),
+            preset_name: String::new(),
+            preset_message: String::new(),
+            auto_preset: None,
+            rule_preset: String::new(),
+            rule_test: Vec::new(),
+            confirm: None,
+            confirm_mute: Vec::new(),
+            autosave: session::Autosave::new("", "", &JobSettings::default()),
+            session_offer: None,
+        }
+    }
+
+    // 同时用于 ffprobe 和转换，改变后重新探测
+    fn set_probe_depth(&mut self, depth: probe::ProbeDepth) {
+        self.settings.probe_depth = depth;
+        self.info = probe::probe(&self.file, depth).ok();
     }
 
-    fn get_media_info(input: &str) -> String {
-        let output = Command::new("ffprobe")
-            .args(&["-i", input, "-hide_banner"])
+    // 读不了输入文件时 ffprobe 也会在 stderr 说明原因，照样显示；只有 ffprobe 本身启动不了时出错
+    fn get_media_info(input: &str, depth: probe::ProbeDepth) -> Result<String, FfError> {
+        if live::is_live(input) {
+            return Ok(format!("{}: 实时输入，不预先读取媒体信息\n", live::display_name(input)));
+        }
+        let output = process::command("ffprobe")
+            .args(depth.args())
+            .args(["-i", input, "-hide_banner"])
             .output()
-            .unwrap_or_else(|_| panic!("无法执行 ffprobe"));
-        String::from_utf8_lossy(&output.stderr).to_string()
+            .map_err(|e| FfError::launch("ffprobe", e))?;
+        Ok(String::from_utf8_lossy(&output.stderr).to_string())
     }
-}
 
-impl App for FFUIApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        use egui::{ComboBox, ScrollArea, ProgressBar};
+    fn request_stop(&mut self, mode: StopMode) {
+        *self.stop_mode.lock().unwrap() = Some(mode);
+        if let Some(child) = self.child_process.lock().unwrap().as_mut() {
+            // 暂停中的进程不会响应，先恢复
+            if self.paused.lock().unwrap().is_some() {
+                let _ = process::resume(child.id());
+            }
+            // 从标准输入读取时 stdin 不归 ffui，发不了 q；由 run_ffmpeg 关闭输入让 ffmpeg 收尾
+            if mode == StopMode::Keep && runner::send_quit(child) {
+                return;
+            }
+        }
+        self.job_cancel.cancel();
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.label(format!("输入文件: {}", self.file));
+    fn start(&mut self, mut output: String) {
+        if output::same_file(&self.file, &output) {
+            self.log_text.lock().unwrap().set(&format!("=== 不能开始：输出文件 {} 就是输入文件，ffmpeg 会在读取的同时覆盖它 ===\n", output));
+            return;
+        }
+        self.remove_preview();
+        if self.derived_from.take().as_deref() == Some(output.as_str()) {
+            output = output::unique_path(Path::new(&output)).to_string_lossy().into_owned();
+        }
+        if self.settings.incremental && output::is_up_to_date(Path::new(&self.file), Path::new(&output)) {
+            *self.completed.lock().unwrap() = false;
+            *self.failure.lock().unwrap() = None;
+            *self.crash.lock().unwrap() = None;
+            self.log_text.lock().unwrap().set(&format!("=== 已跳过：{} 比源文件新，无需重新转换 ===\n", output));
+            return;
+        }
+        self.run_job(self.settings.clone(), output);
+    }
 
-            ComboBox::from_label("目标格式")
-                .selected_text(&self.format)
-                .show_ui(ui, |ui| {
-                    for fmt in &["mp4","avi","mkv","mov","flv","wmv","mp3","aac","wav","ogg"] {
-                        ui.selectable_value(&mut self.format, fmt.to_string(), *fmt);
-                    }
-                });
+    // 载入上次任务的完整设置，改完再点开始转换，输出自动加后缀不覆盖上次的结果
+    fn derive_from_last(&mut self) {
+        let Some((settings, output)) = self.last_job.clone() else { return };
+        self.settings = settings;
+        self.history.named(&self.settings, "载入上次任务的设置".to_string());
+        self.derived_from = Some(output.clone());
+        *self.completed.lock().unwrap() = false;
+        self.log_text.lock().unwrap().set(&format!("=== 已载入上次任务的设置，修改后点击开始转换，不会覆盖 {} ===\n", output));
+    }
 
-            ComboBox::from_label("处理设备")
-                .selected_text(&self.gpu)
-                .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.gpu, "CPU".to_string(), "CPU");
-                    ui.selectable_value(&mut self.gpu, "NVIDIA".to_string(), "NVIDIA GPU");
-                    ui.selectable_value(&mut self.gpu, "Intel".to_string(), "Intel GPU");
-                    ui.selectable_value(&mut self.gpu, "AMD".to_string(), "AMD GPU");
-                });
+    // 启动时接上误关窗口前的编辑：同一个文件直接恢复，打开的是别的文件时先问一下
+    fn resume_session(&mut self) {
+        self.autosave = session::Autosave::new(&self.file, &self.output_choice, &self.settings);
+        match session::load() {
+            Some(saved) if output::same_file(&saved.input, &self.file) => self.restore_session(saved),
+            Some(saved) => self.session_offer = Some(saved),
+            None => {}
+        }
+    }
 
-            ui.horizontal(|ui| {
-                if ui.button("开始转换").clicked() && !*self.running.lock().unwrap() {
-                    let input = self.file.clone();
-                    let output = format!("{}.{}", input, self.format);
-                    let progress = self.progress.clone();
-                    let running = self.running.clone();
-                    let log_text = self.log_text.clone();
-                    let completed = self.completed.clone();
-                    let child_arc = self.child_process.clone();
-                    let stop_flag = self.stop_flag.clone();
-                    let gpu_option = self.gpu.clone();
-                    let _format = self.format.clone();
-
-                    *running.lock().unwrap() = true;
-                    *completed.lock().unwrap() = false;
-                    *log_text.lock().unwrap() = FFUIApp::get_media_info(&input);
-                    *progress.lock().unwrap() = 0.0;
-                    stop_flag.store(false, Ordering::SeqCst);
-
-                    thread::spawn(move || {
-                        let duration = FFUIApp::get_duration(&input);
-
-                        let codec = match gpu_option.as_str() {
-                            "NVIDIA" => "h264_nvenc",
-                            "Intel" => "h264_qsv",
-                            "AMD" => "h264_amf",
-                            _ => "libx264",
-                        };
+    // 恢复的设置整个替换掉按默认格式、设备生成的设置
+    fn restore_session(&mut self, saved: session::Saved) {
+        if !output::same_file(&saved.input, &self.file) {
+            self.set_input(saved.input);
+        }
+        self.settings = saved.settings;
+        self.auto_preset = None;
+        self.history.named(&self.settings, "恢复上次没有转换的编辑".to_string());
+        self.output_choice = saved.output;
+        self.set_probe_depth(self.settings.probe_depth);
+        self.autosave = session::Autosave::new(&self.file, &self.output_choice, &self.settings);
+        self.log_text.lock().unwrap().push_str("\n=== 已恢复上次没有转换的编辑 ===\n");
+    }
 
-                        let mut cmd = Command::new("ffmpeg");
-                        if gpu_option != "CPU" {
-                            match gpu_option.as_str() {
-                                "NVIDIA" => { cmd.args(&["-hwaccel","cuda"]); },
-                                "Intel" => { cmd.args(&["-hwaccel","qsv"]); },
-                                "AMD" => { cmd.args(&["-hwaccel","dxva2"]); },
-                                _ => {},
-                            }
-                        }
-
-                        cmd.args(&[
-                            "-y",
-                            "-i", &input,
-                            "-c:v", codec,
-                            &output,
-                            "-progress", "pipe:1",
-                            "-nostats"
-                        ])
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::null());
-
-                        #[cfg(target_os="windows")]
-                        { cmd.creation_flags(0x08000000); }
-
-                        let child = cmd.spawn().expect("无法启动 ffmpeg");
-                        *child_arc.lock().unwrap() = Some(child);
-
-                        if let Some(stdout) = child_arc.lock().unwrap().as_mut().unwrap().stdout.take() {
-                            let reader = BufReader::new(stdout);
-                            for line in reader.lines().flatten() {
-                                if stop_flag.load(Ordering::SeqCst) { break; }
-                                if line.starts_with("out_time_ms=") && duration > 0.0 {
-                                    if let Ok(ms) = line["out_time_ms=".len()..].parse::<f64>() {
-                                        *progress.lock().unwrap() = ((ms / (duration*1_000_000.0)) * 100.0) as f32;
-                                    }
-                                }
-                            }
-                        }
+    // 当前设置和上次任务逐项对比，只列出不同的项，勾选后可以合并到当前设置
+    fn compare_panel(&mut self, ui: &mut egui::Ui) {
+        let Some((last, output)) = &self.last_job else {
+            ui.label("还没有完成过转换");
+            return;
+        };
+        let rows = compare::diff(&self.settings, last);
+        self.diff_pick.retain(|l| rows.iter().any(|(label, _, _)| label == l));
+        ui.label(format!("上次任务: {}", output));
+        if rows.is_empty() {
+            ui.label("当前设置与上次任务相同");
+            return;
+        }
+        egui::Grid::new("compare").striped(true).show(ui, |ui| {
+            ui.label("");
+            ui.strong("设置项");
+            ui.strong("当前");
+            ui.strong("上次任务");
+            ui.end_row();
+            for (label, current, previous) in &rows {
+                let mut picked = self.diff_pick.contains(label);
+                if ui.checkbox(&mut picked, "").changed() {
+                    if picked {
+                        self.diff_pick.push(label);
+                    } else {
+                        self.diff_pick.retain(|l| l != label);
+                    }
+                }
+                ui.label(*label);
+                ui.label(current);
+                ui.label(previous);
+                ui.end_row();
+            }
+        });
+        if ui.add_enabled(!self.diff_pick.is_empty(), egui::Button::new("合并选中的差异到当前设置")).clicked() {
+            for label in self.diff_pick.drain(..) {
+                compare::copy(&mut self.settings, last, label);
+            }
+            self.history.named(&self.settings, "合并上次任务的差异".to_string());
+        }
+    }
 
-                        if stop_flag.load(Ordering::SeqCst) {
-                            if let Some(mut c) = child_arc.lock().unwrap().take() {
-                                let _ = c.kill();
-                            }
-                            let mut log = log_text.lock().unwrap();
-                            log.push_str("\n=== 已中断 ===\n");
-                            *progress.lock().unwrap() = 0.0;
-                        } else {
-                            let _ = child_arc.lock().unwrap().take().unwrap().wait();
-                            let path = Path::new(&output);
-                            if !path.exists() || path.metadata().map(|m| m.len()).unwrap_or(0) == 0 {
-                                let mut log = log_text.lock().unwrap();
-                                log.push_str("\n=== 转换失败：输出文件为空 ===\n");
-                                *completed.lock().unwrap() = false;
-                                *progress.lock().unwrap() = 0.0;
-                            } else {
-                                *completed.lock().unwrap() = true;
-                                *progress.lock().unwrap() = 100.0;
-                                let mut log = log_text.lock().unwrap();
-                                log.push_str("\n=== 转换完成 ===\n");
-                            }
+    // 选两个预设逐项对比，只列出不同的项，勾选后可以合并到其中一个保存的预设
+    fn preset_compare_panel(&mut self, ui: &mut egui::Ui) {
+        let all = presets::all(&self.presets);
+        if all.len() < 2 {
+            ui.label("至少要有两个预设");
+            return;
+        }
+        ui.horizontal(|ui| {
+            for (side, name) in [("预设 A", &mut self.preset_pair.0), ("预设 B", &mut self.preset_pair.1)] {
+                let combo = egui::ComboBox::from_label(side)
+                    .selected_text(if name.is_empty() { "选择预设…" } else { name.as_str() })
+                    .show_ui(ui, |ui| {
+                        for preset in &all {
+                            ui.selectable_value(name, preset.name.clone(), &preset.name);
                         }
-                        *running.lock().unwrap() = false;
                     });
+                a11y::selected(combo.response, name);
+            }
+        });
+        let find = |name: &str| all.iter().find(|p| p.name == name);
+        let (Some(a), Some(b)) = (find(&self.preset_pair.0), find(&self.preset_pair.1)) else {
+            return;
+        };
+        let (sa, sb) = match (a.settings(), b.settings()) {
+            (Ok(sa), Ok(sb)) => (sa, sb),
+            (Err(e), _) | (_, Err(e)) => {
+                ui.label(e);
+                return;
+            }
+        };
+        let rows = compare::diff(&sa, &sb);
+        self.preset_pick.retain(|l| rows.iter().any(|(label, _, _)| label == l));
+        if rows.is_empty() {
+            ui.label("两个预设的设置相同");
+            return;
+        }
+        egui::Grid::new("preset_compare").striped(true).show(ui, |ui| {
+            ui.label("");
+            ui.strong("设置项");
+            ui.strong(&a.name);
+            ui.strong(&b.name);
+            ui.end_row();
+            for (label, va, vb) in &rows {
+                let mut picked = self.preset_pick.contains(label);
+                if ui.checkbox(&mut picked, "").changed() {
+                    if picked {
+                        self.preset_pick.push(label);
+                    } else {
+                        self.preset_pick.retain(|l| l != label);
+                    }
                 }
-
-                if ui.button("中断").clicked() {
-                    self.stop_flag.store(true, Ordering::SeqCst);
+                ui.label(*label);
+                ui.label(va);
+                ui.label(vb);
+                ui.end_row();
+            }
+        });
+        // 选中的项从一个预设复制到另一个，内置预设不能改
+        let mut merge = None;
+        ui.add_enabled_ui(!self.preset_pick.is_empty(), |ui| {
+            ui.menu_button("合并差异到…", |ui| {
+                for (from, from_settings, to, to_settings) in [(a, &sa, b, &sb), (b, &sb, a, &sa)] {
+                    let text = format!("“{}”（取“{}”的值）", to.name, from.name);
+                    if ui.add_enabled(!presets::is_builtin(&to.name), egui::Button::new(text)).clicked() {
+                        merge = Some((to.name.clone(), from_settings.clone(), to_settings.clone()));
+                        ui.close_menu();
+                    }
                 }
             });
+        });
+        if let Some((name, from, mut to)) = merge {
+            for label in self.preset_pick.drain(..) {
+                compare::copy(&mut to, &from, label);
+            }
+            presets::put(&mut self.presets, presets::Preset::capture(&name, &to));
+            self.preset_message = match presets::save(&self.presets) {
+                Ok(()) => format!("已把选中的差异合并到预设“{}”", name),
+                Err(e) => format!("无法保存预设: {}", e),
+            };
+        }
+        if !self.preset_message.is_empty() {
+            ui.label(&self.preset_message);
+        }
+    }
+
+    // 视频滤镜的先后。只能做合乎规则的调换，不合规则的按钮变灰并说明原因
+    fn filter_chain_panel(&mut self, ui: &mut egui::Ui) {
+        ui.label("按从上到下的顺序处理画面；没有启用的步骤不会出现在命令里。烧录时间、变速和硬件缩放固定在最后");
+        let order = filterchain::normalized(&self.settings.filter_order);
+        let mut moved = None;
+        egui::Grid::new("filter_chain").striped(true).show(ui, |ui| {
+            for (i, step) in order.iter().enumerate() {
+                let state = match step {
+                    filterchain::FilterStep::PixelAspect => "按源文件自动",
+                    filterchain::FilterStep::Deinterlace if self.settings.deinterlace == interlace::Deinterlace::Auto => "自动检测",
+                    _ if plan::step_filter(&self.settings, *step).is_some() => "已启用",
+                    _ => "未启用",
+                };
+                ui.label((i + 1).to_string());
+                let name = egui::RichText::new(step.label());
+                ui.label(if state == "未启用" { name.weak() } else { name });
+                ui.label(state);
+                for (arrow, direction, to) in [("⬆", "上移", i.checked_sub(1)), ("⬇", "下移", Some(i + 1).filter(|j| *j < order.len()))] {
+                    let check = to.map(|j| filterchain::swapped(&order, i, j).map(|_| j));
+                    let button = ui.add_enabled(matches!(check, Some(Ok(_))), egui::Button::new(arrow).small());
+                    let button = match check {
+                        Some(Err(reason)) => button.on_disabled_hover_text(reason),
+                        _ => button,
+                    };
+                    if a11y::named(button, &format!("{}{}", step.label(), direction)).clicked() {
+                        moved = to.map(|j| (i, j));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        if let Some((i, j)) = moved
+            && let Ok(order) = filterchain::swapped(&order, i, j)
+        {
+            self.settings.filter_order = order;
+        }
+        ui.horizontal_wrapped(|ui| {
+            let chain = plan::video_filters(&self.settings);
+            ui.label("-vf");
+            ui.monospace(if chain.is_empty() { "（无）".to_string() } else { chain.join(",") });
+        });
+        if ui.add_enabled(self.settings.filter_order != filterchain::FilterStep::ALL, egui::Button::new("恢复默认顺序")).clicked() {
+            self.settings.filter_order = filterchain::FilterStep::ALL.to_vec();
+        }
+    }
 
-            let p = *self.progress.lock().unwrap();
-            ui.add(ProgressBar::new(p / 100.0).show_percentage());
+    // 开始按钮上面：按当前的目标格式会丢掉的字幕、附件、章节、HDR 和封面，每类选一种处理方式
+    fn data_loss_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(info) = &self.info else { return };
+        let losses = dataloss::report(&self.settings, info);
+        if losses.is_empty() {
+            return;
+        }
+        ui.group(|ui| {
+            ui.colored_label(egui::Color32::YELLOW, format!("数据丢失预检：转成 {} 会丢失以下内容", self.settings.format));
+            for loss in &losses {
+                ui.horizontal_wrapped(|ui| {
+                    let label = ui.label(format!("{}: {}", loss.kind.label(), loss.items.join("，")));
+                    let mut choice = loss.choice;
+                    let combo = egui::ComboBox::from_id_source(("data_loss", loss.kind.tag()))
+                        .selected_text(loss.kind.choice_label(choice))
+                        .show_ui(ui, |ui| {
+                            for c in &loss.choices {
+                                ui.selectable_value(&mut choice, *c, loss.kind.choice_label(*c));
+                            }
+                        });
+                    a11y::selected(combo.response, loss.kind.choice_label(choice)).labelled_by(label.id);
+                    if choice != loss.choice {
+                        dataloss::set_choice(&mut self.settings, loss.kind, choice);
+                    }
+                });
+            }
+        });
+    }
 
-            ScrollArea::vertical().show(ui, |ui| {
-                let log = self.log_text.lock().unwrap();
-                ui.monospace(log.as_str());
+    // 按当前设置生成的 ffmpeg 参数，颜色区分来源，鼠标停在参数或图例上高亮同一来源的参数和对应的设置控件
+    fn command_panel(&mut self, ui: &mut egui::Ui) {
+        let info = self.info.clone().unwrap_or_default();
+        let output = self.planned_output();
+        let job = plan::plan_job(&self.settings, &info, &self.file, &output, &std::env::temp_dir());
+        ui.label("未经开始前的自动调整（探测静音、检查编码器等），实际运行的命令可能略有不同");
+        let color = |source: args::Source| {
+            const PALETTE: [egui::Color32; 7] = [
+                egui::Color32::GRAY,
+                egui::Color32::from_rgb(90, 160, 230),
+                egui::Color32::from_rgb(230, 150, 60),
+                egui::Color32::from_rgb(110, 190, 90),
+                egui::Color32::from_rgb(200, 100, 200),
+                egui::Color32::from_rgb(220, 90, 90),
+                egui::Color32::from_rgb(80, 190, 180),
+            ];
+            let index = args::Source::ALL.iter().position(|s| *s == source).unwrap_or(0);
+            PALETTE[index % PALETTE.len()]
+        };
+        let mut hovered = None;
+        let mut present = Vec::new();
+        for (i, args) in job.runs.iter().enumerate() {
+            if job.runs.len() > 1 {
+                ui.label(format!("第 {} 次调用", i + 1));
+            }
+            ui.horizontal_wrapped(|ui| {
+                ui.monospace("ffmpeg");
+                for (arg, source) in args.iter() {
+                    if !present.contains(&source) {
+                        present.push(source);
+                    }
+                    let mut text = egui::RichText::new(arg).monospace().color(color(source));
+                    if self.hovered_source == Some(source) {
+                        text = text.background_color(ui.visuals().selection.bg_fill);
+                    }
+                    if ui.add(egui::Label::new(text).sense(egui::Sense::hover())).on_hover_text(source.label()).hovered() {
+                        hovered = Some(source);
+                    }
+                }
             });
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label("来源：");
+            for source in present {
+                let mut text = egui::RichText::new(source.label()).color(color(source));
+                if self.hovered_source == Some(source) {
+                    text = text.background_color(ui.visuals().selection.bg_fill);
+                }
+                if ui.add(egui::Label::new(text).sense(egui::Sense::hover())).hovered() {
+                    hovered = Some(source);
+                }
+            }
+        });
+        self.hovered_source = hovered;
+    }
 
-            if *self.completed.lock().unwrap() {
-                ui.label("✅ 转换完成！");
+    // 把当前文件和设置加入任务列表文件，或导入列表后逐个载入到窗口
+    fn joblist_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let label = ui.label("任务列表");
+            ui.add(egui::TextEdit::singleline(&mut self.joblist_path).hint_text("jobs.json")).labelled_by(label.id);
+            ui.checkbox(&mut self.joblist_relative, "相对于列表文件保存路径");
+        });
+        let path = Path::new(self.joblist_path.trim()).to_path_buf();
+        ui.horizontal(|ui| {
+            ui.label("转换之后");
+            ui.checkbox(&mut self.joblist_verify, "校验输出");
+            ui.checkbox(&mut self.joblist_vmaf, "计算 VMAF（失败只记警告）");
+        });
+        ui.horizontal(|ui| {
+            let has_path = !self.joblist_path.trim().is_empty();
+            if ui.add_enabled(has_path, egui::Button::new("导出到队列")).clicked() {
+                let stages = [
+                    (true, pipeline::StageKind::Encode),
+                    (self.joblist_verify, pipeline::StageKind::Verify),
+                    (self.joblist_vmaf, pipeline::StageKind::Vmaf),
+                    (true, pipeline::StageKind::Hook),
+                ]
+                .into_iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, kind)| pipeline::Stage::new(kind))
+                .collect();
+                let job = joblist::Job { input: self.file.clone(), output: String::new(), settings: self.settings.clone(), stages, problem: None };
+                self.joblist_message = match joblist::append(&path, job, self.joblist_relative) {
+                    Ok(n) => {
+                        self.autosave.enqueued(&self.file, &self.output_choice, &self.settings);
+                        format!("已加入，列表里共 {} 个任务", n)
+                    }
+                    Err(e) => e,
+                };
+            }
+            if ui.add_enabled(has_path, egui::Button::new("导入队列")).clicked() {
+                match joblist::load(&path) {
+                    Ok(jobs) => {
+                        let bad = jobs.iter().filter(|j| j.problem.is_some()).count();
+                        self.joblist_message = format!("共 {} 个任务，{} 个有问题", jobs.len(), bad);
+                        self.joblist.set_jobs(jobs);
+                    }
+                    Err(e) => self.joblist_message = e,
+                }
             }
         });
+        if !self.joblist_message.is_empty() {
+            ui.label(&self.joblist_message);
+        }
+        if self.joblist.is_empty() {
+            ui.label("在编码机上运行: ffui --queue 任务列表.json --no-gui");
+            return;
+        }
+        // 删除、重排、改设置后要保存回列表文件才会生效
+        if self.joblist.dirty
+            && ui.add_enabled(!self.joblist_path.trim().is_empty(), egui::Button::new("保存队列修改")).clicked()
+        {
+            self.joblist_message = match joblist::save(&path, &self.joblist.jobs(), self.joblist_relative) {
+                Ok(()) => {
+                    self.joblist.dirty = false;
+                    "已保存".to_string()
+                }
+                Err(e) => format!("无法写入 {}: {}", path.display(), e),
+            };
+        }
+        let speeds = self.stats.get().map(|s| s.speed).unwrap_or_default();
+        if let Some(job) = self.joblist.show(ui, &self.settings, &speeds) {
+            self.file = job.input;
+            self.settings = job.settings;
+            self.history.named(&self.settings, "载入队列里的任务".to_string());
+            self.info = probe::probe(&self.file, self.settings.probe_depth).ok();
+            self.sub_detected = None;
+        }
+        ui.label("在编码机上运行: ffui --queue 任务列表.json --no-gui");
+    }
 
-        ctx.request_repaint();
+    fn start_web(&mut self) {
+        self.remove_preview();
+        let mut settings = self.settings.clone();
+        settings.web = Some(self.web_platform);
+        let output = output::web_output(&self.file, self.web_platform);
+        self.run_job(settings, output);
     }
-}
 
-struct ContextMenuApp {
-    log: String,
-}
+    fn web_button(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let platform = egui::ComboBox::from_id_source("web_platform")
+                .selected_text(self.web_platform.label())
+                .show_ui(ui, |ui| {
+                    for platform in web::Platform::ALL {
+                        ui.selectable_value(&mut self.web_platform, platform, platform.label());
+                    }
+                });
+            a11y::named(a11y::selected(platform.response, self.web_platform.label()), "发送到的平台");
+            let big = egui::Button::new(egui::RichText::new("📤 一键转成可发送的视频").size(18.0));
+            if ui.add(big).clicked() && !*self.running.lock().unwrap() {
+                self.start_web();
+            }
+            ui.label(format!("不超过 {} MB", self.web_platform.limits().max_mb));
+        });
+    }
 
-impl App for ContextMenuApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    // 精简界面：选平台、一键转换、看进度
+    fn share_panel(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("右键菜单");
+            ui.label(format!("输入文件: {}", self.file));
+            self.web_button(ui);
+            self.blocked_panel(ui);
+            let p = self.progress.get().progress;
+            a11y::progress(ui.add(egui::ProgressBar::new(p / 100.0).show_percentage()), p / 100.0, "转换进度");
+            if *self.completed.lock().unwrap() {
+                ui.label(format!("✅ 已保存为 {}", self.output));
+            }
+            if ui.button("更多选项…").clicked() {
+                self.share_mode = false;
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let log = self.log_text.lock().unwrap();
+                a11y::log_view(ui, log.as_str(), "日志");
+            });
+        });
+    }
 
-            #[cfg(target_os = "windows")]
-            {
-                if ui.button("添加到右键菜单").clicked() {
-                    let path = winctx::get_app_path();
-                    match winctx::add_context_menu(path.to_str().unwrap()) {
-                        Ok(_) => self.log = "✅ 完成".to_string(),
-                        Err(e) => self.log = format!("❌ 失败: {}", e),
+    fn start_snapshot(&mut self, snap: snapshot::Snapshot) {
+        self.remove_preview();
+        let output = snap.output_dir(&self.file);
+        let mut settings = self.settings.clone();
+        settings.snapshot = Some(snap);
+        self.run_job(settings, output);
+    }
+
+    fn snapshot_panel(&mut self, ui: &mut egui::Ui) {
+        let has_duration = self.info.as_ref().is_some_and(|i| i.duration > 0.0);
+        let snap = &mut self.snapshot;
+        if !has_duration {
+            snap.mode = snapshot::SnapMode::Interval;
+        }
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut snap.mode, snapshot::SnapMode::Interval, "每隔");
+            a11y::named(self.snap_interval.show(ui, &mut snap.interval, snapshot::MIN_INTERVAL), "截图间隔");
+            ui.add_enabled_ui(has_duration, |ui| {
+                ui.radio_value(&mut snap.mode, snapshot::SnapMode::Count, "平均取");
+                a11y::named(ui.add(egui::DragValue::new(&mut snap.count).clamp_range(1..=10_000).suffix(" 张")), "截图张数");
+            });
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label("质量");
+            ui.add(egui::DragValue::new(&mut snap.quality).clamp_range(1..=100)).labelled_by(label.id);
+            let label = ui.label("最大宽度");
+            ui.add(egui::DragValue::new(&mut snap.max_width).clamp_range(0..=7680).suffix(" px")).labelled_by(label.id);
+            ui.label("(0 = 原始大小)");
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label("保存到");
+            ui.add(egui::TextEdit::singleline(&mut snap.dir).hint_text(format!("{}_frames", self.file))).labelled_by(label.id);
+        });
+        let valid = snap.mode == snapshot::SnapMode::Count || self.snap_interval.is_valid();
+        if ui.add_enabled(valid, egui::Button::new("导出截图")).clicked() && !*self.running.lock().unwrap() {
+            let snap = self.snapshot.clone();
+            self.start_snapshot(snap);
+        }
+    }
+
+    fn start_speech(&mut self) {
+        self.remove_preview();
+        let mut settings = self.settings.clone();
+        settings.speech = Some(self.speech.clone());
+        let output = output::avoid_existing(output::default_output(&self.file, self.speech.codec.ext()), &self.settings);
+        self.run_job(settings, output);
+    }
+
+    fn speech_panel(&mut self, ui: &mut egui::Ui) {
+        let sp = &mut self.speech;
+        ui.label("只保留第一条音轨，转成单声道低码率音频，适合讲座录音、播客和有声书");
+        ui.horizontal(|ui| {
+            let codec = egui::ComboBox::from_id_source("speech_codec")
+                .selected_text(sp.codec.label())
+                .show_ui(ui, |ui| {
+                    for codec in speech::SpeechCodec::ALL {
+                        ui.selectable_value(&mut sp.codec, codec, codec.label());
                     }
+                });
+            a11y::named(a11y::selected(codec.response, sp.codec.label()), "语音编码");
+            a11y::named(ui.add(egui::DragValue::new(&mut sp.bitrate_k).clamp_range(6..=256).suffix(" kbps")), "语音码率");
+        });
+        let normalize = egui::ComboBox::from_label("响度均衡")
+            .selected_text(sp.normalize.label())
+            .show_ui(ui, |ui| {
+                for n in speech::Normalize::ALL {
+                    ui.selectable_value(&mut sp.normalize, n, n.label());
                 }
-                if ui.button("从右键菜单移除").clicked() {
-                    match winctx::remove_context_menu() {
-                        Ok(_) => self.log = "✅ 完成".to_string(),
-                        Err(e) => self.log = format!("❌ 失败: {}", e),
+            });
+        a11y::selected(normalize.response, sp.normalize.label());
+        ui.checkbox(&mut sp.trim_silence, "去掉开头和结尾的静音");
+        ui.horizontal(|ui| {
+            let label = ui.label("源文件没有章节时每隔");
+            ui.add(egui::DragValue::new(&mut sp.chapter_minutes).clamp_range(0..=600).suffix(" 分钟")).labelled_by(label.id);
+            ui.label("生成一章 (0 = 不生成)");
+        });
+        let has_audio = self.info.as_ref().is_some_and(|i| i.streams.iter().any(|s| s.codec_type == "audio"));
+        if ui.add_enabled(has_audio, egui::Button::new("转成语音文件")).clicked() && !*self.running.lock().unwrap() {
+            self.start_speech();
+        }
+    }
+
+    // 当前文件所在的专辑按音轨排进转换队列，确认后依次转换
+    fn queue_album(&mut self) {
+        let (tracks, note) = match album::scan(&self.file, &self.album) {
+            Ok(found) => found,
+            Err(e) => {
+                self.log_text.lock().unwrap().push_str(&format!("\n=== 无法按专辑排队：{} ===\n", e));
+                return;
+            }
+        };
+        self.batch.extend(tracks.into_iter().map(batch::BatchItem::track));
+        self.log_text.lock().unwrap().push_str(&format!("\n专辑: {}，已加入转换队列\n", note));
+        self.request_batch();
+    }
+
+    fn album_panel(&mut self, ui: &mut egui::Ui) {
+        let al = &mut self.album;
+        ui.label("把当前文件所在的专辑按音轨顺序排进队列：整轨文件有 cue 时按 cue 切开，否则取文件名以音轨号开头的文件");
+        ui.horizontal(|ui| {
+            let codec = egui::ComboBox::from_id_source("album_codec")
+                .selected_text(al.codec.label())
+                .show_ui(ui, |ui| {
+                    for codec in album::AlbumCodec::ALL {
+                        ui.selectable_value(&mut al.codec, codec, codec.label());
                     }
+                });
+            a11y::named(a11y::selected(codec.response, al.codec.label()), "专辑编码");
+            a11y::named(ui.add(egui::DragValue::new(&mut al.bitrate_k).clamp_range(32..=512).suffix(" kbps")), "专辑码率");
+        });
+        ui.checkbox(&mut al.split_cue, "整轨文件有 cue 时切成一轨一个文件");
+        ui.add_enabled(al.codec == album::AlbumCodec::Aac, egui::Checkbox::new(&mut al.gapless, "写入无缝播放信息 (iTunSMPB)"))
+            .on_hover_text("AAC 编码会在开头多出一段延迟、末尾补齐整帧，播放器按这条信息去掉后连播没有间隙。Opus 文件本身就带着这些信息");
+        let idle = !*self.running.lock().unwrap() && !self.batch_active && self.blocked.is_none();
+        if ui.add_enabled(idle && !self.file.is_empty(), egui::Button::new("按专辑排队")).clicked() {
+            self.queue_album();
+        }
+    }
+
+    fn start_quick(&mut self, op: QuickOp) {
+        self.remove_preview();
+        let mut settings = self.settings.clone();
+        settings.quick = Some(op);
+        let output = output::avoid_existing(output::tagged_output(&self.file, op.suffix()), &settings);
+        self.run_job(settings, output);
+    }
+
+    fn quick_panel(&mut self, ui: &mut egui::Ui) {
+        ui.label("不重新编码，几秒钟就能完成，输出和源文件是同一种格式");
+        let has = |kind: &str| self.info.as_ref().is_some_and(|i| i.streams.iter().any(|s| s.codec_type == kind));
+        let (has_video, has_audio) = (has("video"), has("audio"));
+        let ready = !live::is_live(&self.file) && !*self.running.lock().unwrap();
+        let container = quick::container(&self.file);
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            for rotation in quick::Rotation::ALL {
+                if ui.add_enabled(ready && has_video, egui::Button::new(rotation.label())).clicked() {
+                    clicked = Some(QuickOp::Rotate(rotation));
                 }
             }
-
-            ui.separator();
-            ui.label(&self.log);
+            if ui.add_enabled(ready && has_audio, egui::Button::new(QuickOp::StripAudio.label())).clicked() {
+                clicked = Some(QuickOp::StripAudio);
+            }
         });
+        if has_video && !quick::carries_rotation(&container) {
+            ui.label(format!("{} 没有通用的旋转标记，旋转时要重新编码视频", if container.is_empty() { "这个文件" } else { &container }));
+        }
+        if let Some(op) = clicked {
+            self.start_quick(op);
+        }
+    }
 
-        ctx.request_repaint();
+    // samples 为 1 时只编码开头 secs 秒
+    fn start_preview(&mut self, secs: u32, samples: u32) {
+        self.remove_preview();
+        self.preview_shape = (secs, samples);
+        let mut settings = self.settings.clone();
+        settings.preview_secs = Some(secs);
+        settings.preview_samples = samples;
+        let output = output::preview_path(&settings.format);
+        self.preview = Some(output.clone());
+        self.run_job(settings, output);
     }
-}
 
-fn main() -> eframe::Result<()> {
-    let args: Vec<String> = env::args().collect();
+    fn remove_preview(&mut self) {
+        if let Some(path) = self.preview.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
 
-    let native_options = eframe::NativeOptions::default();
+    fn stats_panel(&mut self, ui: &mut egui::Ui) {
+        use egui::plot::{Bar, BarChart, Plot};
 
-    if args.len() > 1 {
-        // 正常进入转码器
-        let file = args[1].clone();
-        let app = FFUIApp {
-            file,
-            format: "mp4".to_string(), // 默认输出mp4
-            gpu: "CPU".to_string(), // 默认用CPU处理
-            progress: Arc::new(Mutex::new(0.0)),
-            running: Arc::new(Mutex::new(false)),
-            log_text: Arc::new(Mutex::new(String::new())),
-            completed: Arc::new(Mutex::new(false)),
-            child_process: Arc::new(Mutex::new(None)),
-            stop_flag: Arc::new(AtomicBool::new(false)),
+        let Some(stats) = self.stats.get() else {
+            ui.spinner();
+            return;
         };
+        let total = stats.succeeded + stats.failed;
+        egui::Grid::new("stats").num_columns(2).striped(true).show(ui, |ui| {
+            ui.label("已转换文件");
+            ui.horizontal(|ui| {
+                ui.label(format!("{} 成功 / {} 失败", stats.succeeded, stats.failed));
+                if stats.warned > 0 {
+                    ui.colored_label(egui::Color32::YELLOW, format!("（{} 个完成但有警告）", stats.warned));
+                }
+            });
+            ui.end_row();
+            ui.label("成功率");
+            ui.label(if total > 0 { format!("{:.0}%", stats.succeeded as f64 * 100.0 / total as f64) } else { "-".to_string() });
+            ui.end_row();
+            ui.label("输入 / 输出");
+            ui.label(format!(
+                "{} / {}",
+                inspect::format_bytes(stats.input_bytes as f64),
+                inspect::format_bytes(stats.output_bytes as f64),
+            ));
+            ui.end_row();
+            ui.label("节省空间");
+            let saved = stats.input_bytes as f64 - stats.output_bytes as f64;
+            ui.label(if saved >= 0.0 { inspect::format_bytes(saved) } else { format!("-{}", inspect::format_bytes(-saved)) });
+            ui.end_row();
+            ui.label("累计编码时间");
+            ui.label(format!("{:.1} 小时", stats.encode_secs / 3600.0));
+            ui.end_row();
+            for (encoder, (media, spent)) in &stats.speed {
+                ui.label(encoder);
+                ui.label(if *spent > 0.0 { format!("平均 {:.2}x", media / spent) } else { "-".to_string() });
+                ui.end_row();
+            }
+            if let Some((input, output)) = &stats.last {
+                ui.label("最近一次");
+                ui.label(format!("{} → {}", input, output));
+                ui.end_row();
+            }
+        });
 
-        eframe::run_native(
-            "FFUI",
-            native_options,
-            Box::new(|cc| {
-                setup_fonts(&cc.egui_ctx);
-                Box::new(app)
-            }),
-        )
-    } else {
-        // 无参数时打开右键菜单管理界面
-        let app = ContextMenuApp {
-            log: "将本程序添加到Windows右键菜单".to_string(),
-        };
-        eframe::run_native(
-            "FFUI 右键菜单设置",
-            native_options,
-            Box::new(|cc| {
-                setup_fonts(&cc.egui_ctx);
-                Box::new(app)
-            }),
-        )
+        // 最近 30 天每天的转换数，0 为今天
+        let today = history::now() / 86400;
+        let bars: Vec<Bar> = stats.per_day.range(today.saturating_sub(29)..=today)
+            .map(|(day, n)| Bar::new(*day as f64 - today as f64, *n as f64).width(0.8))
+            .collect();
+        Plot::new("stats_per_day")
+            .height(120.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .include_x(-29.5)
+            .include_x(0.5)
+            .include_y(0.0)
+            .show(ui, |plot| plot.bar_chart(BarChart::new(bars).name("每日转换数")));
+
+        if ui.button("重置统计").clicked()
+            && let Err(e) = stats::reset()
+        {
+            self.log_text.lock().unwrap().push_str(&format!("\n无法重置统计: {}\n", e));
+        }
+    }
+
+    // 完成但有警告：列出说明，提供加上建议参数后重新转换
+    fn warnings_panel(&mut self, ui: &mut egui::Ui) {
+        let tally = self.job_warnings.lock().unwrap().clone();
+        if tally.is_empty() {
+            return;
+        }
+        ui.colored_label(egui::Color32::YELLOW, "⚠ 完成但有警告，输出可能有卡顿、音画不同步或瑕疵");
+        for found in &tally.found {
+            ui.label(format!("• {}（{} 次）", found.pattern.explanation, found.count))
+                .on_hover_text(&found.first_line);
+        }
+        let fixes = tally.suggested(&self.settings.fixes);
+        if fixes.is_empty() || *self.running.lock().unwrap() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label(format!("建议: {}", fixes.iter().map(|f| f.label).collect::<Vec<_>>().join("，")));
+            if ui.button("加上建议的参数重新转换").clicked() {
+                self.settings.fixes.extend(fixes.iter().map(|f| f.id.to_string()));
+                if self.preview.is_some() {
+                    let (secs, samples) = self.preview_shape;
+                    self.start_preview(secs, samples);
+                } else {
+                    // 覆盖刚才有问题的输出
+                    let mut settings = self.settings.clone();
+                    settings.overwrite = true;
+                    self.run_job(settings, self.output.clone());
+                }
+            }
+        });
+    }
+
+    // 上一个文件结束后记下结果，队列在运行时接着开始下一个等待中的文件
+    fn drive_batch(&mut self) {
+        if *self.running.lock().unwrap() || self.blocked.is_some() {
+            return;
+        }
+        if let Some(i) = self.batch_current.take() {
+            let stopped = self.stop_mode.lock().unwrap().is_some();
+            self.batch[i].status = if *self.completed.lock().unwrap() {
+                batch::ItemStatus::Done
+            } else if stopped {
+                batch::ItemStatus::Cancelled
+            } else {
+                let reason = match self.crash.lock().unwrap().as_ref() {
+                    Some(report) => report.label.clone(),
+                    None => self.failure.lock().unwrap().map(|h| h.message).unwrap_or("转换失败").to_string(),
+                };
+                batch::ItemStatus::Failed(reason)
+            };
+            if stopped && self.batch_cancel_rest {
+                batch::cancel_waiting(&mut self.batch);
+            }
+        }
+        if !self.batch_active {
+            return;
+        }
+        match batch::next_waiting(&self.batch) {
+            Some(i) => self.start_batch_item(i),
+            None => self.batch_active = false,
+        }
+    }
+
+    // 用窗口里的设置和这个文件自己的目标格式开始转换
+    fn start_batch_item(&mut self, i: usize) {
+        self.remove_preview();
+        self.batch[i].status = batch::ItemStatus::Running;
+        self.batch_current = Some(i);
+        self.file = self.batch[i].path.clone();
+        self.info = probe::probe(&self.file, self.settings.probe_depth).ok();
+        self.sub_detected = None;
+        // 被占用而没有开始时不能沿用上一个文件的结果
+        *self.completed.lock().unwrap() = false;
+        *self.failure.lock().unwrap() = None;
+        *self.crash.lock().unwrap() = None;
+        *self.stop_mode.lock().unwrap() = None;
+
+        let settings = self.batch_settings(&self.batch[i]);
+        let output = self.batch[i].output(&settings, &self.output_dir);
+        if settings.incremental && output::is_up_to_date(Path::new(&self.file), Path::new(&output)) {
+            self.log_text.lock().unwrap().set(&format!("=== 已跳过：{} 比源文件新，无需重新转换 ===\n", output));
+            *self.completed.lock().unwrap() = true;
+            return;
+        }
+        self.run_job(settings, output);
+    }
+
+    // 开始转换时用的输出路径
+    fn planned_output(&self) -> String {
+        match self.output_choice.trim() {
+            "" => output::avoid_existing(output::suggested_output(&self.file, &self.settings.format, &self.output_dir), &self.settings),
+            path => path.to_string(),
+        }
+    }
+
+    // 开始前按需要列出清单请用户确认，没有需要确认的情况时直接开始
+    fn request_start(&mut self, output: String) {
+        let speeds = self.stats.get().map(|s| s.speed).unwrap_or_default();
+        let duration = self.info.as_ref().map(|info| plan::trimmed_secs(&self.settings, &self.file, info.duration));
+        let mut item = confirm::Item::new(&self.settings, &self.file, &output, duration, &speeds);
+        if let Some(info) = &self.info {
+            item.losses = dataloss::report(&self.settings, info);
+        }
+        let mut summary = confirm::Summary::new(vec![item], &[&self.settings]);
+        summary.size_note = self.sample_estimate.lock().unwrap().clone();
+        self.ask(summary, Confirmed::Start(output));
+    }
+
+    // 队列里等待中的文件，各用自己的目标格式
+    fn request_batch(&mut self) {
+        let speeds = self.stats.get().map(|s| s.speed).unwrap_or_default();
+        let mut items = Vec::new();
+        for item in self.batch.iter().filter(|item| item.status == batch::ItemStatus::Waiting) {
+            let settings = self.batch_settings(item);
+            let output = item.output(&settings, &self.output_dir);
+            let info = probe::probe(&item.path, settings.probe_depth).ok();
+            let duration = info.as_ref().map(|info| plan::trimmed_secs(&settings, &item.path, info.duration));
+            let mut confirm_item = confirm::Item::new(&settings, &item.path, &output, duration, &speeds);
+            if let Some(info) = &info {
+                confirm_item.losses = dataloss::report(&settings, info);
+            }
+            items.push(confirm_item);
+        }
+        let summary = confirm::Summary::new(items, &[&self.settings]);
+        self.ask(summary, Confirmed::Batch);
+    }
+
+    fn ask(&mut self, summary: confirm::Summary, action: Confirmed) {
+        let skip = confirm::parse_skip(&config::current().confirm_skip);
+        if summary.triggers(&skip).is_empty() {
+            self.confirmed(action);
+        } else {
+            self.confirm = Some((summary, action));
+            self.confirm_mute.clear();
+        }
+    }
+
+    fn confirmed(&mut self, action: Confirmed) {
+        match action {
+            Confirmed::Start(output) => self.start(output),
+            Confirmed::Batch => self.batch_active = true,
+        }
+    }
+
+    fn audio_compare_window(&mut self, ctx: &egui::Context) {
+        let Some(view) = &mut self.audio_compare else { return };
+        let mut open = true;
+        egui::Window::new("音频对比").open(&mut open).show(ctx, |ui| view.show(ui));
+        if !open {
+            self.audio_compare = None;
+        }
+    }
+
+    fn confirm_window(&mut self, ctx: &egui::Context) {
+        let Some((summary, _)) = &self.confirm else { return };
+        let skip = confirm::parse_skip(&config::current().confirm_skip);
+        let fired = summary.triggers(&skip);
+        let lines = summary.lines();
+        let mut decision = None;
+        egui::Window::new("开始前确认")
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let reasons: Vec<&str> = fired.iter().map(|t| t.label()).collect();
+                ui.colored_label(egui::Color32::YELLOW, format!("请确认：{}", reasons.join("、")));
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for line in &lines {
+                        ui.label(line);
+                    }
+                });
+                ui.separator();
+                for trigger in &fired {
+                    let mut muted = self.confirm_mute.contains(trigger);
+                    if ui.checkbox(&mut muted, format!("不再提示此类操作（{}）", trigger.label())).changed() {
+                        self.confirm_mute.retain(|t| t != trigger);
+                        if muted {
+                            self.confirm_mute.push(*trigger);
+                        }
+                    }
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("开始").clicked() {
+                        decision = Some(true);
+                    }
+                    if ui.button("取消").clicked() {
+                        decision = Some(false);
+                    }
+                });
+            });
+        match decision {
+            Some(true) => {
+                if !self.confirm_mute.is_empty() {
+                    let mut skip = skip;
+                    skip.append(&mut self.confirm_mute);
+                    let _ = config::save(&config::Config { confirm_skip: confirm::format_skip(&skip), ..config::current() });
+                }
+                if let Some((_, action)) = self.confirm.take() {
+                    self.confirmed(action);
+                }
+            }
+            Some(false) => self.confirm = None,
+            None => {}
+        }
+    }
+
+    // 换一个输入文件：重新探测，日志换成它的媒体信息，清掉上一个文件的进度和结果
+    fn set_input(&mut self, file: String) {
+        self.remove_preview();
+        self.cancel_wait();
+        self.blocked = None;
+        self.derived_from = None;
+        self.output_choice.clear();
+        self.file = file;
+        self.info = probe::probe(&self.file, self.settings.probe_depth).ok();
+        self.sub_detected = None;
+        self.auto_apply_preset();
+        self.progress.store(runner::Progress::default());
+        *self.completed.lock().unwrap() = false;
+        *self.failure.lock().unwrap() = None;
+        self.failure_detail.lock().unwrap().clear();
+        *self.crash.lock().unwrap() = None;
+        *self.job_warnings.lock().unwrap() = warnings::Tally::default();
+        *self.sample_estimate.lock().unwrap() = None;
+        let text = FFUIApp::get_media_info(&self.file, self.settings.probe_depth).unwrap_or_else(|e| format!("=== {} ===\n", e));
+        self.log_text.lock().unwrap().set(&text);
+    }
+
+    // 拖进窗口的文件：空闲时第一个成为输入文件，多个时和命令行一样排进队列；
+    // 正在转换（或队列在跑）时不打断，全部加到队列末尾，当前文件结束后依次转换
+    fn open_files(&mut self, files: Vec<String>) {
+        let Some(first) = files.first().cloned() else { return };
+        let busy = *self.running.lock().unwrap() || self.batch_active || self.blocked.is_some();
+        if busy || !self.batch.is_empty() {
+            let items: Vec<batch::BatchItem> = files.iter().map(|f| self.batch_item(f.clone())).collect();
+            self.batch.extend(items);
+            // 当前文件结束后接着转换拖进来的文件
+            if busy {
+                self.batch_active = true;
+            }
+            let note = if busy { "正在转换，" } else { "" };
+            self.log_text.lock().unwrap().push_str(&format!("\n{}已把 {} 个文件加入转换队列\n", note, files.len()));
+            return;
+        }
+        self.set_input(first);
+        if files.len() > 1 {
+            self.batch = files.iter().map(|f| self.batch_item(f.clone())).collect();
+        }
+    }
+
+    fn dropped_files(ctx: &egui::Context) -> Vec<String> {
+        ctx.input(|i| i.raw.dropped_files.clone())
+            .into_iter()
+            .filter_map(|f| f.path)
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    // ffmpeg 所在目录：Windows 上可以直接选 ffmpeg.exe。保存后重新检查，测试把 ffmpeg -version 写进日志
+    fn ffmpeg_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let label = ui.label("ffmpeg 目录");
+            ui.add(egui::TextEdit::singleline(&mut self.ffmpeg_dir).hint_text("自动查找（PATH、ffui 旁边）").desired_width(280.0))
+                .labelled_by(label.id);
+            let mut save = false;
+            if cfg!(target_os = "windows")
+                && ui.button("选择 ffmpeg 路径").clicked()
+                && let Some(path) = output::open_dialog("ffmpeg.exe", "ffmpeg.exe")
+            {
+                self.ffmpeg_dir = Path::new(&path).parent().map(|d| d.to_string_lossy().into_owned()).unwrap_or_default();
+                save = true;
+            }
+            if ui.button("保存").clicked() || save {
+                let dir = self.ffmpeg_dir.trim().to_string();
+                match config::save(&config::Config { ffmpeg_dir: dir.clone(), ..config::current() }) {
+                    Ok(()) => {
+                        self.ffmpeg_dir = dir;
+                        self.ffmpeg_missing = config::missing_tools();
+                        if self.ffmpeg_missing.is_none() && !self.file.is_empty() {
+                            self.info = probe::probe(&self.file, self.settings.probe_depth).ok();
+                        }
+                    }
+                    Err(e) => self.log_text.lock().unwrap().push_str(&format!("\n无法保存设置: {}\n", e)),
+                }
+            }
+            if ui.button("测试").on_hover_text("运行 ffmpeg -version，结果写进日志").clicked() {
+                self.test_ffmpeg("");
+            }
+        });
+    }
+
+    // 运行某个 ffmpeg 版本（空名字是默认的）的 ffmpeg -version，连同编码器、滤镜数量写进日志
+    fn test_ffmpeg(&self, build: &str) {
+        process::use_build(build);
+        let program = process::tool("ffmpeg");
+        let text = match process::output_with_timeout(process::command("ffmpeg").arg("-version"), Duration::from_secs(10)) {
+            Ok((Some(status), stdout, _)) if status.success() => {
+                format!("{}: {}，{}", program.display(), stdout.lines().next().unwrap_or("").trim(), encoders::summary())
+            }
+            Ok((_, _, stderr)) => format!("{} 无法正常运行: {}", program.display(), stderr.trim()),
+            Err(e) => format!("找不到 {}（{}）", program.display(), e),
+        };
+        process::use_build(&self.settings.ffmpeg);
+        self.log_text.lock().unwrap().push_str(&format!("\n{}\n", text));
+    }
+
+    // 另外装的 ffmpeg 版本，任务和预设按名字选用；同名的覆盖
+    fn builds_row(&mut self, ui: &mut egui::Ui) {
+        let mut builds = config::current().ffmpeg_builds;
+        let mut changed = false;
+        let mut test = None;
+        ui.label("其他 ffmpeg 版本");
+        builds.retain(|build| {
+            let mut keep = true;
+            ui.horizontal(|ui| {
+                ui.label(format!("{}: {}", build.name, build.dir));
+                if ui.button("测试").on_hover_text("运行这个版本的 ffmpeg -version，结果写进日志").clicked() {
+                    test = Some(build.name.clone());
+                }
+                if ui.button("删除").clicked() {
+                    keep = false;
+                    changed = true;
+                }
+            });
+            keep
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label("名称");
+            ui.add(egui::TextEdit::singleline(&mut self.build_name).hint_text("如 6.1").desired_width(80.0)).labelled_by(label.id);
+            let label = ui.label("目录");
+            ui.add(egui::TextEdit::singleline(&mut self.build_dir).hint_text("ffmpeg 和 ffprobe 所在的文件夹").desired_width(240.0))
+                .labelled_by(label.id);
+            let name = config::build_name(&self.build_name);
+            let dir = self.build_dir.trim().to_string();
+            if ui.add_enabled(!name.is_empty() && !dir.is_empty(), egui::Button::new("添加")).clicked() {
+                builds.retain(|b| b.name != name);
+                builds.push(config::Build { name, dir });
+                self.build_name.clear();
+                self.build_dir.clear();
+                changed = true;
+            }
+        });
+        if changed {
+            if let Err(e) = config::save(&config::Config { ffmpeg_builds: builds, ..config::current() }) {
+                self.log_text.lock().unwrap().push_str(&format!("\n无法保存设置: {}\n", e));
+            }
+            process::use_build(&self.settings.ffmpeg);
+        }
+        if let Some(name) = test {
+            self.test_ffmpeg(&name);
+        }
+    }
+
+    // 这个任务用哪个 ffmpeg 版本；设置里没有其他版本时不显示
+    fn build_combo(&mut self, ui: &mut egui::Ui) {
+        let builds = config::current().ffmpeg_builds;
+        if builds.is_empty() && self.settings.ffmpeg.is_empty() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            let selected = if self.settings.ffmpeg.is_empty() { "默认".to_string() } else { self.settings.ffmpeg.clone() };
+            let combo = egui::ComboBox::from_label("ffmpeg 版本").selected_text(&selected).show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.settings.ffmpeg, String::new(), "默认");
+                for build in &builds {
+                    ui.selectable_value(&mut self.settings.ffmpeg, build.name.clone(), &build.name);
+                }
+            });
+            a11y::selected(combo.response, &selected);
+            if !self.settings.ffmpeg.is_empty() && !builds.iter().any(|b| b.name == self.settings.ffmpeg) {
+                ui.colored_label(egui::Color32::RED, "设置里已经没有这个版本");
+            }
+        });
+        // 编码器、滤镜是否可用按选中的版本判断
+        process::use_build(&self.settings.ffmpeg);
+    }
+
+    // 找到这个文件最近一次失败的转换记录，先生成内容给用户看
+    fn prepare_diagnose(&mut self) {
+        match history::load().into_iter().rev().find(|r| r.input == self.file && !r.ok) {
+            Some(record) => {
+                let log = self.log_text.lock().unwrap().as_str().to_string();
+                self.diagnose = Some(diagnose::Bundle::from_record(&record, Some(&log)));
+            }
+            None => self.log_text.lock().unwrap().push_str("\n没有找到这个文件失败的转换记录，无法生成诊断包\n"),
+        }
+    }
+
+    // 诊断包的内容预览和隐私说明，确认后才写出
+    fn diagnose_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(bundle) = &self.diagnose else { return };
+        let mut close = false;
+        ui.group(|ui| {
+            ui.label(format!("诊断包内容（共 {}）", inspect::format_bytes(bundle.size() as f64)));
+            ui.colored_label(egui::Color32::YELLOW, diagnose::PRIVACY_NOTE);
+            for (name, text) in &bundle.files {
+                egui::CollapsingHeader::new(format!("{}（{}）", name, inspect::format_bytes(text.len() as f64)))
+                    .id_source(("diagnose", name))
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical().id_source(("diagnose_text", name)).max_height(160.0).show(ui, |ui| {
+                            a11y::log_view(ui, text, name);
+                        });
+                    });
+            }
+            ui.horizontal(|ui| {
+                if ui.button("保存诊断包").clicked() {
+                    let text = match bundle.write() {
+                        Ok(path) => format!("诊断包已保存到 {}", path.display()),
+                        Err(e) => format!("无法写入诊断包: {}", e),
+                    };
+                    self.log_text.lock().unwrap().push_str(&format!("\n{}\n", text));
+                    close = true;
+                }
+                if ui.button("取消").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if close {
+            self.diagnose = None;
+        }
+    }
+
+    // 撤销、重做 n 步。格式被改回去时手选输出的扩展名跟着改
+    fn step_history(&mut self, undo: bool, n: usize) {
+        let before = self.settings.format.clone();
+        if undo {
+            self.history.undo(&mut self.settings, n);
+        } else {
+            self.history.redo(&mut self.settings, n);
+        }
+        if self.settings.format != before && !self.output_choice.trim().is_empty() {
+            self.output_choice = Path::new(self.output_choice.trim()).with_extension(&self.settings.format).to_string_lossy().into_owned();
+        }
+    }
+
+    // 撤销、重做按钮和修改记录。点记录里的一步，撤销或重做到那一步为止
+    fn history_row(&mut self, ui: &mut egui::Ui) {
+        let mut step = None;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.history.can_undo(), egui::Button::new("↶ 撤销")).on_hover_text("Ctrl+Z").clicked() {
+                step = Some((true, 1));
+            }
+            if ui.add_enabled(self.history.can_redo(), egui::Button::new("↷ 重做")).on_hover_text("Ctrl+Y").clicked() {
+                step = Some((false, 1));
+            }
+            let undo = self.history.undo_labels();
+            let redo = self.history.redo_labels();
+            let combo = egui::ComboBox::from_id_source("undo_history")
+                .selected_text(format!("修改记录（{}）", undo.len()))
+                .show_ui(ui, |ui| {
+                    // 重做的在上面，颜色淡一些；当前的状态在两者之间
+                    for (i, label) in redo.iter().enumerate().rev() {
+                        if ui.selectable_label(false, egui::RichText::new(label).weak()).clicked() {
+                            step = Some((false, i + 1));
+                        }
+                    }
+                    ui.label(egui::RichText::new("● 当前").strong());
+                    for (i, label) in undo.iter().enumerate() {
+                        if ui.selectable_label(false, label).clicked() {
+                            step = Some((true, i + 1));
+                        }
+                    }
+                });
+            a11y::selected(combo.response, &format!("修改记录，可撤销 {} 步，可重做 {} 步", undo.len(), redo.len()));
+        });
+        if let Some((undo, n)) = step {
+            self.step_history(undo, n);
+        }
+    }
+
+    // 套用预设并记成一步修改，action 是撤销历史里的说法。套用失败时返回 false
+    fn apply_preset(&mut self, preset: &presets::Preset, action: &str) -> bool {
+        self.preset_name = preset.name.clone();
+        let applied = preset.apply(&mut self.settings);
+        self.preset_message = match &applied {
+            Ok(()) => String::new(),
+            Err(e) => e.clone(),
+        };
+        // 发到平台的方案由“一键转成可发送的视频”启动，不留在设置里，
+        // 否则截图、预览等其他操作也会按这个方案转换
+        if let Some(platform) = self.settings.web.take() {
+            self.web_platform = platform;
+            self.preset_message = format!("已选择发送到{}，点“一键转成可发送的视频”开始", platform.label());
+        }
+        self.history.named(&self.settings, format!("{}“{}”", action, self.preset_name));
+        applied.is_ok()
+    }
+
+    // 载入文件时按预设的匹配规则自动套用，记下套用前的设置，点提示可以换一个或撤回
+    fn auto_apply_preset(&mut self) {
+        self.auto_preset = None;
+        let Some(preset) = autopreset::pick(&self.presets, &self.file, self.info.as_ref()).cloned() else { return };
+        let before = self.settings.clone();
+        if self.apply_preset(&preset, "自动应用预设") {
+            self.auto_preset = Some((preset.name, before));
+        }
+    }
+
+    // 排进队列的文件：有匹配的预设时记在这一项上，目标格式也按预设
+    fn batch_item(&self, path: String) -> batch::BatchItem {
+        let mut item = batch::BatchItem::new(path, &self.settings.format);
+        if !autopreset::any_rules(&self.presets) {
+            return item;
+        }
+        let info = probe::probe(&item.path, self.settings.probe_depth).ok();
+        if let Some(preset) = autopreset::pick(&self.presets, &item.path, info.as_ref())
+            && let Ok(settings) = preset.settings()
+        {
+            item.format = settings.format;
+            item.preset = Some(preset.clone());
+        }
+        item
+    }
+
+    // 队列里一项开始时的设置：窗口的设置叠加这一项的预设、目标格式和专辑
+    fn batch_settings(&self, item: &batch::BatchItem) -> JobSettings {
+        let mut settings = self.settings.clone();
+        if let Some(preset) = &item.preset {
+            let _ = preset.apply(&mut settings);
+            settings.web = None;
+        }
+        settings.format = item.format.clone();
+        settings.album = item.album.clone();
+        settings
+    }
+
+    // 选一个预设直接套用到下面的设置；保存时同名的覆盖
+    fn presets_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut chosen = None;
+            let all = presets::all(&self.presets);
+            let combo = egui::ComboBox::from_label("预设")
+                .selected_text(if self.preset_name.is_empty() { "选择预设…" } else { self.preset_name.as_str() })
+                .show_ui(ui, |ui| {
+                    for (i, preset) in all.iter().enumerate() {
+                        let text = if presets::is_builtin(&preset.name) { format!("{}（内置）", preset.name) } else { preset.name.clone() };
+                        if ui.selectable_label(preset.name == self.preset_name, text).clicked() {
+                            chosen = Some(i);
+                        }
+                    }
+                });
+            a11y::selected(combo.response, &self.preset_name);
+            if let Some(i) = chosen {
+                self.auto_preset = None;
+                self.apply_preset(&all[i], "套用预设");
+            }
+            let label = ui.label("名称");
+            ui.add(egui::TextEdit::singleline(&mut self.preset_name).hint_text("如 phone-1080p-h265").desired_width(160.0))
+                .labelled_by(label.id);
+            let name = self.preset_name.trim().to_string();
+            let builtin = presets::is_builtin(&name);
+            let save = ui.add_enabled(!name.is_empty() && !builtin, egui::Button::new("保存预设"));
+            let save = if builtin { save.on_disabled_hover_text("内置预设不能覆盖，请换个名称") } else { save };
+            if save.clicked() {
+                presets::put(&mut self.presets, presets::Preset::capture(&name, &self.settings));
+                self.preset_message = match presets::save(&self.presets) {
+                    Ok(()) => format!("已保存预设“{}”", name),
+                    Err(e) => format!("无法保存预设: {}", e),
+                };
+            }
+            let exists = self.presets.iter().any(|p| p.name == name);
+            if ui.add_enabled(exists, egui::Button::new("删除预设")).clicked() {
+                self.presets.retain(|p| p.name != name);
+                self.preset_message = match presets::save(&self.presets) {
+                    Ok(()) => format!("已删除预设“{}”", name),
+                    Err(e) => format!("无法保存预设: {}", e),
+                };
+                self.preset_name.clear();
+            }
+        });
+        if let Some((name, before)) = self.auto_preset.clone() {
+            let mut chosen = None;
+            let mut revert = false;
+            ui.menu_button(format!("已自动应用预设: {} — 点击更改", name), |ui| {
+                for preset in presets::all(&self.presets) {
+                    if ui.add_enabled(preset.name != name, egui::Button::new(&preset.name)).clicked() {
+                        chosen = Some(preset);
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                if ui.button("不使用预设（恢复之前的设置）").clicked() {
+                    revert = true;
+                    ui.close_menu();
+                }
+            });
+            if let Some(preset) = chosen {
+                self.auto_preset = None;
+                self.settings = before;
+                self.apply_preset(&preset, "改用预设");
+            } else if revert {
+                self.auto_preset = None;
+                self.settings = before;
+                self.preset_name.clear();
+                self.history.named(&self.settings, format!("撤回自动应用的预设“{}”", name));
+            }
+        }
+        if !self.preset_message.is_empty() {
+            ui.label(&self.preset_message);
+        }
+    }
+
+    // 给保存的预设设置匹配规则：载入或排队的文件符合规则时自动套用这个预设
+    fn preset_rules_panel(&mut self, ui: &mut egui::Ui) {
+        if self.presets.is_empty() {
+            ui.label("先保存一个预设，再给它设置匹配规则（内置预设不能设置）");
+            return;
+        }
+        let combo = egui::ComboBox::from_label("预设")
+            .selected_text(if self.rule_preset.is_empty() { "选择预设…" } else { self.rule_preset.as_str() })
+            .show_ui(ui, |ui| {
+                for preset in &self.presets {
+                    ui.selectable_value(&mut self.rule_preset, preset.name.clone(), &preset.name);
+                }
+            });
+        a11y::selected(combo.response, &self.rule_preset);
+        let mut save = false;
+        if let Some(preset) = self.presets.iter_mut().find(|p| p.name == self.rule_preset) {
+            let rule = &mut preset.rule;
+            egui::Grid::new("preset_rule").num_columns(2).show(ui, |ui| {
+                let label = ui.label("文件名");
+                ui.add(egui::TextEdit::singleline(&mut rule.names).hint_text("*.mkv, wav")).labelled_by(label.id)
+                    .on_hover_text("通配符 * 和 ?，只写扩展名也行；逗号分隔，符合一个即可");
+                ui.end_row();
+                let label = ui.label("编码");
+                ui.add(egui::TextEdit::singleline(&mut rule.codecs).hint_text("hevc, pcm_s16le")).labelled_by(label.id)
+                    .on_hover_text("任一路视频或音频是这些编码之一");
+                ui.end_row();
+                let label = ui.label("视频高度");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut rule.min_height).clamp_range(0..=8640).prefix("至少 ")).labelled_by(label.id);
+                    ui.add(egui::DragValue::new(&mut rule.max_height).clamp_range(0..=8640).prefix("至多 "));
+                    ui.label("（0 为不限）");
+                });
+                ui.end_row();
+                let label = ui.label("路径包含");
+                ui.add(egui::TextEdit::singleline(&mut rule.paths).hint_text("播客, Podcasts")).labelled_by(label.id);
+                ui.end_row();
+                let label = ui.label("优先级");
+                ui.add(egui::DragValue::new(&mut rule.priority).clamp_range(-100..=100)).labelled_by(label.id)
+                    .on_hover_text("几个预设同时匹配时大的优先，相同时按保存的先后");
+                ui.end_row();
+            });
+            save = ui.button("保存规则").clicked();
+        }
+        if save {
+            self.preset_message = match presets::save(&self.presets) {
+                Ok(()) => format!("已保存预设“{}”的匹配规则", self.rule_preset),
+                Err(e) => format!("无法保存预设: {}", e),
+            };
+        }
+        if ui.add_enabled(!self.file.is_empty(), egui::Button::new("用此文件测试规则")).clicked() {
+            self.rule_test = autopreset::explain(&self.presets, &self.file, self.info.as_ref());
+        }
+        for line in &self.rule_test {
+            ui.label(line);
+        }
+        if !self.preset_message.is_empty() {
+            ui.label(&self.preset_message);
+        }
+    }
+
+    fn batch_panel(&mut self, ui: &mut egui::Ui) {
+        let running = *self.running.lock().unwrap();
+        let finished = self.batch.iter().filter(|item| item.status.finished()).count();
+        let overall = batch::overall(&self.batch, self.progress.get().progress);
+        let bar = ui.add(egui::ProgressBar::new(overall).text(format!("总进度 {}/{}", finished, self.batch.len())));
+        a11y::progress(bar, overall, &format!("队列总进度，已完成 {}/{}", finished, self.batch.len()));
+        ui.horizontal(|ui| {
+            let waiting = batch::next_waiting(&self.batch).is_some();
+            if ui.add_enabled(!self.batch_active && !running && waiting, egui::Button::new("全部开始")).clicked() {
+                self.request_batch();
+            }
+            if self.batch_active && ui.button("当前文件结束后暂停队列").clicked() {
+                self.batch_active = false;
+            }
+            let retry = self.batch.iter().any(|item| matches!(item.status, batch::ItemStatus::Failed(_) | batch::ItemStatus::Cancelled));
+            if ui.add_enabled(retry, egui::Button::new("重新排队失败和取消的")).clicked() {
+                for item in self.batch.iter_mut().filter(|item| matches!(item.status, batch::ItemStatus::Failed(_) | batch::ItemStatus::Cancelled)) {
+                    item.status = batch::ItemStatus::Waiting;
+                }
+            }
+        });
+        let progress = self.progress.get().progress;
+        egui::Grid::new("batch").striped(true).show(ui, |ui| {
+            for (i, item) in self.batch.iter_mut().enumerate() {
+                ui.label((i + 1).to_string());
+                let name = item.name();
+                let shown = match &item.preset {
+                    Some(preset) => format!("{}（已自动应用预设: {}）", name, preset.name),
+                    None => name.clone(),
+                };
+                ui.label(shown).on_hover_text(&item.path);
+                // 专辑里的音轨按专辑的编码输出
+                let fixed = item.album.is_some();
+                ui.add_enabled_ui(item.status == batch::ItemStatus::Waiting && !fixed, |ui| {
+                    let format = egui::ComboBox::from_id_source(("batch_format", i))
+                        .selected_text(&item.format)
+                        .show_ui(ui, |ui| {
+                            for fmt in plan::FORMATS {
+                                ui.selectable_value(&mut item.format, fmt.to_string(), *fmt);
+                            }
+                        });
+                    a11y::named(a11y::selected(format.response, &item.format), &format!("第 {} 个文件 {} 的目标格式", i + 1, name));
+                });
+                match &item.status {
+                    batch::ItemStatus::Running => ui.label(format!("转换中 {:.0}%", progress)),
+                    batch::ItemStatus::Failed(_) => ui.colored_label(egui::Color32::RED, item.status.label()),
+                    status => ui.label(status.label()),
+                };
+                ui.end_row();
+            }
+        });
+    }
+
+    // 输入还在被别的程序写入时先不开始，让用户选择等待或强行开始
+    fn run_job(&mut self, settings: JobSettings, output: String) {
+        self.cancel_wait();
+        // 在 Windows 上检查占用要打开文件，会连上命名管道
+        if !live::is_live(&self.file) && filelock::has_writer(Path::new(&self.file)) {
+            self.log_text.lock().unwrap().push_str("\n=== 文件似乎仍在写入/被占用 ===\n");
+            self.blocked = Some((settings, output));
+            return;
+        }
+        self.launch(settings, output);
+    }
+
+    fn wait_and_start(&mut self) {
+        let (cancel, ready) = (CancelToken::new(), Arc::new(AtomicBool::new(false)));
+        self.waiting = Some((cancel.clone(), ready.clone()));
+        let file = self.file.clone();
+        thread::spawn(move || {
+            if filelock::wait_until_stable(Path::new(&file), &cancel) {
+                ready.store(true, Ordering::SeqCst);
+            }
+        });
+    }
+
+    fn cancel_wait(&mut self) {
+        if let Some((cancel, _)) = self.waiting.take() {
+            cancel.cancel();
+        }
+        self.blocked = None;
+    }
+
+    fn blocked_panel(&mut self, ui: &mut egui::Ui) {
+        if self.blocked.is_none() {
+            return;
+        }
+        if self.waiting.as_ref().is_some_and(|(_, ready)| ready.load(Ordering::SeqCst)) {
+            self.waiting = None;
+            if let Some((settings, output)) = self.blocked.take() {
+                self.launch(settings, output);
+            }
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.colored_label(egui::Color32::YELLOW, "文件似乎仍在写入/被占用");
+            if self.waiting.is_some() {
+                ui.spinner();
+                ui.label("等待写入结束…");
+                if ui.button("取消等待").clicked() {
+                    self.cancel_wait();
+                }
+            } else {
+                if ui.button("等待并自动开始").clicked() {
+                    self.wait_and_start();
+                }
+                if ui.button("仍然开始").clicked()
+                    && let Some((settings, output)) = self.blocked.take()
+                {
+                    self.launch(settings, output);
+                }
+            }
+        });
+    }
+
+    fn launch(&mut self, settings: JobSettings, output: String) {
+        let input = self.file.clone();
+        let progress = self.progress.shared();
+        let running = self.running.clone();
+        let log_text = self.log_text.clone();
+        let completed = self.completed.clone();
+        let failure = self.failure.clone();
+        let failure_detail = self.failure_detail.clone();
+        let crash = self.crash.clone();
+        let job_warnings = self.job_warnings.clone();
+        let estimate = self.sample_estimate.clone();
+        let child_arc = self.child_process.clone();
+        let job_cancel = CancelToken::new();
+        self.job_cancel = job_cancel.clone();
+        let stop_mode = self.stop_mode.clone();
+        let thermal = self.thermal.clone();
+        let paused = self.paused.clone();
+        let stalled = self.stalled.clone();
+        let hash_progress = self.hash_progress.clone();
+        // 只取消校验时不影响编码
+        let hash_cancel = job_cancel.child();
+        self.hash_cancel = hash_cancel.clone();
+        let hang_limit = Duration::from_secs(self.hang_minutes * 60);
+
+        self.output = output.clone();
+        if settings.preview_secs.is_none() && settings.snapshot.is_none() && settings.web.is_none() && settings.speech.is_none() {
+            self.last_job = Some((settings.clone(), output.clone()));
+        }
+        // 记下实际开始的设置：队列里的文件有自己的格式和自动套用的预设，和窗口里的不一定相同
+        if presets::remembered(&settings) {
+            let _ = presets::save_last_used(&settings);
+        }
+        *completed.lock().unwrap() = false;
+        *failure.lock().unwrap() = None;
+        failure_detail.lock().unwrap().clear();
+        *crash.lock().unwrap() = None;
+        *job_warnings.lock().unwrap() = warnings::Tally::default();
+        *estimate.lock().unwrap() = None;
+        match FFUIApp::get_media_info(&input, settings.probe_depth) {
+            Ok(text) => log_text.lock().unwrap().set(&text),
+            // 没有 ffprobe 时 ffmpeg 多半也不在，不开始转换
+            Err(e) => {
+                log_text.lock().unwrap().set(&format!("=== {} ===\n", e));
+                *failure.lock().unwrap() = Some(errors::NO_FFMPEG);
+                return;
+            }
+        }
+        progress.store(runner::Progress { clock: runner::Clock::start(Instant::now()), ..Default::default() });
+
+        if let Err(e) = live::check(&settings, &input) {
+            log_text.lock().unwrap().push_str(&format!("\n=== {} ===\n", e));
+            return;
+        }
+        if !settings.ffmpeg.is_empty() && config::build_dir(&settings.ffmpeg).is_none() {
+            log_text.lock().unwrap().push_str(&format!("\n=== 设置里没有名为 {} 的 ffmpeg 版本，请在“高级”里添加或改回默认 ===\n", settings.ffmpeg));
+            return;
+        }
+        if let Err(e) = output::check_writable(Path::new(&output)) {
+            let hint = errors::explain_io_error(&e);
+            log_text.lock().unwrap().push_str(&format!("\n=== 无法写入 {}: {} ({}) ===\n", output, hint.message, e));
+            *failure.lock().unwrap() = Some(hint);
+            return;
+        }
+
+        *running.lock().unwrap() = true;
+        *stop_mode.lock().unwrap() = None;
+        monitor::publish(input.clone(), progress.clone(), running.clone(), log_text.clone(), job_cancel.clone());
+
+        thread::spawn(move || {
+            let mut settings = settings;
+            let mut publisher = latest::Publisher::new(progress);
+            // 任务指定的 ffmpeg 版本只对这个线程生效
+            process::use_build(&settings.ffmpeg);
+            // 这个线程结束（完成、失败或停止）时连同中间文件一起删除
+            let temp = match tempfiles::TempFiles::new() {
+                Ok(temp) => temp,
+                Err(e) => {
+                    log_text.lock().unwrap().push_str(&format!(
+                        "\n=== 无法创建临时目录 {}: {} ===\n", tempfiles::root().display(), e
+                    ));
+                    *running.lock().unwrap() = false;
+                    return;
+                }
+            };
+            if !settings.subtitle_file.is_empty() {
+                match subtitle::prepare(&mut settings, temp.dir()) {
+                    Ok(note) => {
+                        log_text.lock().unwrap().push_str(&format!("\n{}\n", note));
+                    }
+                    Err(e) => {
+                        log_text.lock().unwrap().push_str(&format!("\n=== 无法读取字幕文件: {} ===\n", e));
+                        *running.lock().unwrap() = false;
+                        return;
+                    }
+                }
+            }
+
+            let info = probe::probe(&input, settings.probe_depth).unwrap_or_default();
+            // 裁剪后实际转换的时长，进度、速度统计和样本推算都按它算
+            let duration = plan::trimmed_secs(&settings, &input, info.duration);
+            let mut notes = plan::resolve(&mut settings, &input, &info);
+            let job = plan::plan_job(&settings, &info, &input, &output, temp.dir());
+            notes.extend(job.notes.iter().cloned());
+            for note in &notes {
+                log_text.lock().unwrap().push_str(&format!("\n{}\n", note));
+            }
+            if let Err(e) = settings.resolution.check()
+                .and_then(|_| plan::check_trim(&settings, &info))
+                .and_then(|_| plan::check_extra_args(&settings))
+                .and_then(|_| plan::check_audio(&settings, &info))
+                .and_then(|_| live::check_plan(&input, &job))
+            {
+                log_text.lock().unwrap().push_str(&format!("\n=== {} ===\n", e));
+                *running.lock().unwrap() = false;
+                return;
+            }
+            // 检查都通过后才开始校验，和编码同时进行，结束时再等它
+            let hasher = (settings.hash_source && settings.preview_secs.is_none() && settings.snapshot.is_none() && !live::is_live(&input)).then(|| {
+                *hash_progress.lock().unwrap() = Some(0.0);
+                let (path, cancel, progress) = (input.clone(), hash_cancel.clone(), hash_progress.clone());
+                thread::spawn(move || hash::hash_file(Path::new(&path), &cancel, |p| *progress.lock().unwrap() = Some(p)))
+            });
+
+            let created_dirs: Vec<&String> = job.dirs.iter().filter(|d| !Path::new(d).exists()).collect();
+            for dir in &job.dirs {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            for (path, content) in &job.write_before {
+                if let Err(e) = std::fs::write(path, content) {
+                    log_text.lock().unwrap().push_str(&format!("\n无法写入 {}: {}\n", path, e));
+                }
+            }
+
+            let last_activity = Arc::new(Mutex::new(Instant::now()));
+            watchdog::watch(hang_limit, last_activity.clone(), running.clone(), paused.clone(), stalled, log_text.clone());
+            thermal::guard(thermal, child_arc.clone(), running.clone(), paused, log_text.clone());
+
+            // 多次调用时进度按调用次数平分
+            let runs = job.runs.len() as f32;
+            let run_secs = job.run_secs.unwrap_or(duration);
+            let started = Instant::now();
+            let mut result = Ok(None);
+            // 失败的那次调用，崩溃报告里要写出完整命令
+            let mut failed_argv = Vec::new();
+            let mut tally = warnings::Tally::default();
+            let encode_cancel = job_cancel.child();
+            // 硬件编码时 CPU 闲着不代表在等磁盘，不判断
+            let judge_io = settings.gpu == "CPU" || settings.remux;
+            // 只调用一次、从头读到尾时输入文件的大小，受限于读取时按它估计剩余时间
+            let whole_input = (job.runs.len() == 1 && settings.trim_start.is_none() && settings.trim_end.is_none() && settings.preview_secs.is_none())
+                .then(|| std::fs::metadata(&input).ok().filter(|m| m.is_file()).map(|m| m.len()))
+                .flatten();
+            for (i, args) in job.runs.iter().enumerate() {
+                if stop_mode.lock().unwrap().is_some() {
+                    result = Ok(Some(runner::RunOutcome {
+                        exited_ok: false,
+                        exit_code: None,
+                        stopped: true,
+                        tail: logbuf::LogBuffer::new(runner::TAIL_LINES),
+                        warnings: warnings::Tally::default(),
+                        crash: None,
+                    }));
+                    break;
+                }
+                // 转封装按已写入的字节算进度；没有时长（实时输入、探测失败）时改为显示已编码的时长。
+                // 剩余时间只估算当前这次调用，多次调用时不显示
+                let argv = args.argv();
+                log_text.lock().unwrap().push_str(&format!("\n命令: {}\n", plan::quote_command("ffmpeg", &argv)));
+                if args.iter().any(|(_, s)| s == args::Source::Extra) {
+                    log_text.lock().unwrap().push_str("附加参数放在输出路径前面，和界面生成的参数重复时以后出现的为准\n");
+                }
+                let mut tracker = runner::Tracker::new(runner::strategy(&argv, &info, &input), run_secs);
+                let mut io = bottleneck::Monitor::default();
+                let on_progress = |block: runner::ProgressBlock| {
+                    let now = Instant::now();
+                    let frac = tracker.update(&block, now);
+                    let pid = child_arc.lock().unwrap().as_ref().map(|c| c.id());
+                    let verdict = match pid.and_then(bottleneck::sample) {
+                        Some(counters) if judge_io => {
+                            io.push(now, counters);
+                            io.verdict()
+                        }
+                        _ => bottleneck::Verdict::Unknown,
+                    };
+                    let eta = whole_input
+                        .and_then(|size| io.read_eta(verdict, size))
+                        .or(tracker.eta_secs())
+                        .filter(|_| job.runs.len() == 1);
+                    publisher.update(|p| {
+                        p.stats.update(&block);
+                        p.bottleneck = verdict;
+                        match frac {
+                            Some(frac) => {
+                                p.progress = (i as f32 + frac as f32) / runs * 100.0;
+                                p.eta_secs = eta;
+                            }
+                            None => {
+                                if let Some(secs) = block.out_time {
+                                    p.live_secs = Some(secs);
+                                }
+                            }
+                        }
+                    });
+                };
+                match runner::run_ffmpeg(&argv, &child_arc, &encode_cancel, &last_activity, Some(&log_text), on_progress) {
+                    Ok(outcome) if outcome.exited_ok && stop_mode.lock().unwrap().is_none() => tally.merge(outcome.warnings),
+                    Ok(outcome) => {
+                        // ffmpeg 拒绝某个参数时指出是哪个设置加的
+                        if !outcome.stopped && let Some(message) = args::explain_rejection(outcome.tail.lines(), args) {
+                            log_text.lock().unwrap().push_str(&format!("\n{}\n", message));
+                        }
+                        failed_argv = argv.clone();
+                        result = Ok(Some(outcome));
+                        break;
+                    }
+                    Err(e) => { result = Err(FfError::launch("ffmpeg", e)); break; }
+                }
+            }
+
+            publisher.set(|p| {
+                p.eta_secs = None;
+                p.bottleneck = bottleneck::Verdict::Unknown;
+                p.clock.stop(Instant::now());
+            });
+            let success = matches!(result, Ok(None));
+            let source_hash = hasher.and_then(|handle| {
+                if !success {
+                    hash_cancel.cancel();
+                } else if !handle.is_finished() {
+                    log_text.lock().unwrap().push_str("\n等待源文件校验完成…\n");
+                }
+                let hash = match handle.join() {
+                    Ok(Ok(Some(hash))) => {
+                        log_text.lock().unwrap().push_str(&format!("\n源文件 SHA-256: {}\n", hash));
+                        Some(hash)
+                    }
+                    Ok(Ok(None)) => {
+                        if success {
+                            log_text.lock().unwrap().push_str("\n已取消源文件校验\n");
+                        }
+                        None
+                    }
+                    Ok(Err(e)) => {
+                        log_text.lock().unwrap().push_str(&format!("\n无法计算源文件校验值: {}\n", e));
+                        None
+                    }
+                    Err(_) => None,
+                };
+                *hash_progress.lock().unwrap() = None;
+                hash
+            });
+
+            let empty = |path: &String| {
+                let path = Path::new(path);
+                !path.exists() || path.metadata().map(|m| m.len()).unwrap_or(0) == 0
+            };
+            match result {
+                Err(e) => {
+                    log_text.lock().unwrap().push_str(&format!("\n=== {} ===\n", e));
+                    *failure.lock().unwrap() = Some(errors::NO_FFMPEG);
+                    publisher.set(|p| p.progress = 0.0);
+                }
+                Ok(Some(outcome)) if outcome.stopped || stop_mode.lock().unwrap().is_some() => {
+                    match *stop_mode.lock().unwrap() {
+                        Some(StopMode::Keep) => {
+                            let kept: Vec<&str> = job.outputs.iter().filter(|p| !empty(p)).map(|p| p.as_str()).collect();
+                            let mut log = log_text.lock().unwrap();
+                            if kept.is_empty() {
+                                log.push_str("\n=== 已停止，没有可保留的输出 ===\n");
+                            } else {
+                                log.push_str(&format!("\n=== 已停止，已保留部分输出: {} ===\n", kept.join(", ")));
+                            }
+                        }
+                        Some(StopMode::Delete) => {
+                            for path in &job.outputs {
+                                let _ = std::fs::remove_file(path);
+                            }
+                            for dir in &created_dirs {
+                                let _ = std::fs::remove_dir_all(dir);
+                            }
+                            log_text.lock().unwrap().push_str("\n=== 已中断，已删除未完成的输出 ===\n");
+                            publisher.set(|p| p.progress = 0.0);
+                        }
+                        None => {
+                            log_text.lock().unwrap().push_str("\n=== 已中断 ===\n");
+                            publisher.set(|p| p.progress = 0.0);
+                        }
+                    }
+                }
+                Ok(outcome) => {
+                    let no_images = job.expect_images.as_ref().is_some_and(|(dir, _)| snapshot::count_images(dir) == 0);
+                    let any_empty = job.outputs.iter().any(empty) || no_images;
+                    let tail = outcome.as_ref().map(|o| o.tail.as_str().to_string()).unwrap_or_default();
+                    let exit_code = outcome.as_ref().map_or(Some(0), |o| o.exit_code);
+                    let ok = outcome.is_none() && !any_empty;
+                    if settings.preview_secs.is_none() && settings.snapshot.is_none() {
+                        let size = |p: &String| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+                        let encoder = if plan::is_video_container(&settings.format) {
+                            plan::video_codec(&settings)
+                        } else {
+                            plan::default_audio_codec(&settings.format)
+                        };
+                        let record = history::Record {
+                            time: history::now(),
+                            input: input.clone(),
+                            output: output.clone(),
+                            encoder: encoder.to_string(),
+                            input_bytes: size(&input),
+                            output_bytes: job.outputs.iter().map(size).sum(),
+                            media_secs: duration,
+                            encode_secs: started.elapsed().as_secs_f64(),
+                            ok,
+                            stderr_tail: if ok { String::new() } else { tail.clone() },
+                            source_sha256: source_hash.clone().unwrap_or_default(),
+                            warnings: if ok { tally.ids() } else { String::new() },
+                            settings: joblist::settings_to_value(&settings).to_pretty(),
+                            command: job.runs.iter().map(|a| plan::quote_command("ffmpeg", &a.argv())).collect::<Vec<_>>().join("\n"),
+                        };
+                        if let Err(e) = history::append(&record) {
+                            log_text.lock().unwrap().push_str(&format!("\n无法写入转换记录: {}\n", e));
+                        }
+                    }
+                    if !ok {
+                        let mut log = log_text.lock().unwrap();
+                        *failure_detail.lock().unwrap() = tail.clone();
+                        if let Some(kind) = outcome.as_ref().and_then(|o| o.crash) {
+                            let label = kind.label();
+                            log.push_str(&format!("\n=== 转换失败：{} ===\n", label));
+                            let path = match crash::write_report(kind, &input, &failed_argv, &tail) {
+                                Ok(path) => {
+                                    log.push_str(&format!("崩溃报告已保存到 {}\n", path.display()));
+                                    Some(path)
+                                }
+                                Err(e) => {
+                                    log.push_str(&format!("无法写入崩溃报告: {}\n", e));
+                                    None
+                                }
+                            };
+                            let retries = crash::suggestions(&settings, &failed_argv);
+                            for retry in &retries {
+                                log.push_str(&format!("{}，可以{}\n", retry.cause(), retry.label()));
+                            }
+                            *crash.lock().unwrap() = Some(crash::Report { label, path, retries });
+                        } else {
+                            match errors::match_stderr(tail.lines()) {
+                                Some(hint) => {
+                                    log.push_str(&format!("\n=== 转换失败：{} ===\n", hint.message));
+                                    *failure.lock().unwrap() = Some(hint);
+                                }
+                                None if any_empty && exit_code == Some(0) => log.push_str("\n=== 转换失败：输出文件为空 ===\n"),
+                                None => log.push_str("\n=== 转换失败：ffmpeg 异常退出 ===\n"),
+                            }
+                        }
+                        // 非零退出时输出可能已经写了一部分，同样按失败处理，不能当成完整的结果
+                        if let Some(code) = exit_code.filter(|c| *c != 0) {
+                            let partial = job.outputs.iter().any(|p| !empty(p));
+                            log.push_str(&format!("ffmpeg 退出码: {}{}\n", code, if partial { "（输出文件不完整）" } else { "" }));
+                        }
+                        let quoted = runner::last_lines(&tail, runner::QUOTE_LINES);
+                        if !quoted.is_empty() {
+                            log.push_str("ffmpeg 最后的输出:\n");
+                            for line in quoted {
+                                log.push_str(&format!("  {}\n", line));
+                            }
+                        }
+                        *completed.lock().unwrap() = false;
+                        publisher.set(|p| p.progress = 0.0);
+                    } else {
+                        for (path, content) in &job.write_after {
+                            if let Err(e) = std::fs::write(path, content) {
+                                log_text.lock().unwrap().push_str(&format!("\n无法写入 {}: {}\n", path, e));
+                            }
+                        }
+                        if settings.hash_embed
+                            && let Some(hash) = &source_hash
+                            && let [out] = job.outputs.as_slice()
+                        {
+                            let (mut args, tmp) = hash::embed_args(out, hash);
+                            // 又把整个输出写一遍，同样要限速；流复制只能按输出自己的码率放慢读取
+                            let size = std::fs::metadata(out).map(|m| m.len() as f64).unwrap_or(0.0);
+                            if settings.write_limit_mb > 0.0
+                                && let Some(mechanism) = throttle::Mechanism::copy(settings.write_limit_mb, throttle::bits_from_size(size, run_secs))
+                                && let Some(at) = args.iter().position(|a| a == "-i")
+                            {
+                                args.splice(at..at, ["-readrate".to_string(), mechanism.readrate_arg()]);
+                                log_text.lock().unwrap().push_str(&format!("\n写入校验值时{}\n", mechanism.describe(settings.write_limit_mb)));
+                            }
+                            match runner::run_ffmpeg(&args, &child_arc, &job_cancel.child(), &last_activity, Some(&log_text), |_| {}) {
+                                Ok(o) if o.exited_ok && std::fs::rename(&tmp, out).is_ok() => {
+                                    log_text.lock().unwrap().push_str("\n已把源文件校验值写入输出的注释\n");
+                                }
+                                _ => {
+                                    let _ = std::fs::remove_file(&tmp);
+                                    log_text.lock().unwrap().push_str("\n无法把校验值写入输出，输出文件未改动\n");
+                                }
+                            }
+                        }
+                        // 同样是改写整个文件，也要在复制日期之前
+                        if let Some(album) = &settings.album
+                            && album.writes_gapless()
+                            && let [out] = job.outputs.as_slice()
+                        {
+                            let note = album::write_gapless(out, run_secs).unwrap_or_else(|e| format!("无法写入无缝播放信息: {}", e));
+                            log_text.lock().unwrap().push_str(&format!("\n{}\n", note));
+                        }
+                        // 放在写校验值的改名之后，否则时间戳会被改名后的新文件覆盖
+                        if settings.keep_dates && settings.preview_secs.is_none() && settings.snapshot.is_none() {
+                            let note = filedate::copy_to_outputs(&input, &job.outputs);
+                            log_text.lock().unwrap().push_str(&format!("\n{}\n", note));
+                        }
+                        if let Some((dir, expected)) = &job.expect_images {
+                            let got = snapshot::count_images(dir);
+                            let mut log = log_text.lock().unwrap();
+                            if *expected > 0 && got * 10 < expected * 9 {
+                                log.push_str(&format!("\n=== 只导出了 {} 张，预计 {} 张 ===\n", got, expected));
+                            } else {
+                                log.push_str(&format!("\n=== 已导出 {} 张图片 ===\n", got));
+                            }
+                            let _ = process::open_file(dir);
+                        }
+                        *completed.lock().unwrap() = true;
+                        publisher.set(|p| p.progress = 100.0);
+                        let mut log = log_text.lock().unwrap();
+                        if let Some(secs) = settings.preview_secs
+                            && settings.preview_samples > 1
+                            && let Some(bytes) = sample::estimate_bytes(
+                                std::fs::metadata(&output).map(|m| m.len()).unwrap_or(0), duration, settings.preview_samples, secs,
+                            )
+                        {
+                            let text = format!(
+                                "按 {} 段样本的平均码率推算，完整文件约 {}（推算值，实际大小会有出入）",
+                                settings.preview_samples, inspect::format_bytes(bytes)
+                            );
+                            log.push_str(&format!("\n{}\n", text));
+                            *estimate.lock().unwrap() = Some(text);
+                        }
+                        let done = if settings.preview_secs.is_some() { "预览完成" } else { "转换完成" };
+                        if tally.is_empty() {
+                            log.push_str(&format!("\n=== {} ===\n", done));
+                        } else {
+                            log.push_str(&format!("\n=== {}但有警告 ===\n{}", done, tally.summary()));
+                            *job_warnings.lock().unwrap() = tally;
+                        }
+                    }
+                }
+            }
+            // 停止的任务和预览不运行钩子
+            if settings.preview_secs.is_none() && stop_mode.lock().unwrap().is_none() {
+                let text = hook::after_job(&settings, &input, &output, *completed.lock().unwrap());
+                if !text.is_empty() {
+                    log_text.lock().unwrap().push_str(&format!("\n{}", text));
+                }
+            }
+            drop(temp);
+            *running.lock().unwrap() = false;
+        });
+    }
+}
+
+impl App for FFUIApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        use egui::{ComboBox, ScrollArea, ProgressBar};
+
+        // ffmpeg 的输出行在这里每帧并一次
+        self.log_text.lock().unwrap().merge();
+        if self.share_mode {
+            self.share_panel(ctx);
+            ctx.request_repaint();
+            return;
+        }
+
+        if self.session_offer.is_none()
+            && self.autosave.tick(&self.file, &self.output_choice, &self.settings, *self.completed.lock().unwrap())
+        {
+            ctx.request_repaint_after(Duration::from_millis(300));
+        }
+        self.history.tick(&self.settings, ctx.input(|i| i.pointer.any_down()));
+        // 输入框有焦点时 Ctrl+Z 留给输入框撤销文字
+        if ctx.memory(|m| m.focus().is_none()) {
+            let (undo, redo) = ctx.input_mut(|i| {
+                let undo = i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z);
+                let redo = i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y)
+                    || i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z);
+                (undo, redo)
+            });
+            if undo {
+                self.step_history(true, 1);
+            } else if redo {
+                self.step_history(false, 1);
+            }
+        }
+        // 界面上按正在编辑的任务选的 ffmpeg 版本判断编码器、滤镜是否可用
+        process::use_build(&self.settings.ffmpeg);
+        self.drive_batch();
+        if let Some(line) = self.budget.tick(*self.running.lock().unwrap()) {
+            self.log_text.lock().unwrap().push_str(&format!("\n{}\n", line));
+        }
+        let dropped = FFUIApp::dropped_files(ctx);
+        if !dropped.is_empty() {
+            self.open_files(dropped);
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                ui.colored_label(egui::Color32::LIGHT_BLUE, "松开鼠标添加文件");
+            }
+            if let Some(warning) = paths::warning() {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+            if let Some(missing) = self.ffmpeg_missing.clone() {
+                ui.colored_label(egui::Color32::RED, missing);
+                self.ffmpeg_row(ui);
+            }
+            if let Some(e) = config_banner(ui) {
+                self.log_text.lock().unwrap().push_str(&format!("\n{}\n", e));
+            }
+            if let Some(saved) = &self.session_offer {
+                let mut restore = None;
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, format!("上次编辑 {} 的设置还没有转换", live::display_name(&saved.input)));
+                    if ui.button("恢复上次编辑").clicked() {
+                        restore = Some(true);
+                    }
+                    if ui.button("丢弃").clicked() {
+                        restore = Some(false);
+                    }
+                });
+                match restore {
+                    Some(true) => {
+                        let saved = self.session_offer.take().unwrap();
+                        self.restore_session(saved);
+                    }
+                    Some(false) => {
+                        self.session_offer = None;
+                        session::clear();
+                    }
+                    None => {}
+                }
+            }
+            if !self.batch.is_empty() {
+                egui::CollapsingHeader::new(format!("转换队列（{} 个文件）", self.batch.len()))
+                    .default_open(true)
+                    .show(ui, |ui| self.batch_panel(ui));
+            }
+            ui.label(format!("输入文件: {}", live::display_name(&self.file)));
+            // 命令预览里停在哪个来源上（上一帧的），对应的控件画框
+            let hovered = self.hovered_source;
+            let row = ui.horizontal(|ui| {
+                let label = ui.label("输入格式");
+                ui.add(egui::TextEdit::singleline(&mut self.settings.input_format)
+                    .hint_text("自动；实时输入必填，如 mpegts")
+                    .desired_width(180.0))
+                    .labelled_by(label.id);
+                if ui.button("从标准输入读取")
+                    .on_hover_text("转换另一个程序通过管道送来的数据，例如: 采集程序 | ffui --stdin-input --input-format mpegts")
+                    .clicked()
+                {
+                    self.file = "-".to_string();
+                    self.info = None;
+                }
+            });
+            mark_source(&row.response, hovered, &[args::Source::InputFormat]);
+            if let Err(e) = live::check(&self.settings, &self.file) {
+                ui.colored_label(egui::Color32::YELLOW, e);
+            }
+            let row = ui.horizontal(|ui| {
+                let [start, end] = &mut self.trim_fields;
+                let label = ui.label("开始时间");
+                start.show_optional(ui, &mut self.settings.trim_start).labelled_by(label.id);
+                let label = ui.label("结束时间");
+                end.show_optional(ui, &mut self.settings.trim_end).labelled_by(label.id);
+                let unknown = probe::MediaInfo::default();
+                let info = self.info.as_ref().unwrap_or(&unknown);
+                if let Err(e) = plan::check_trim(&self.settings, info) {
+                    ui.colored_label(egui::Color32::RED, e);
+                } else if plan::trims(&self.settings, &self.file) && info.duration > 0.0 {
+                    let secs = plan::trimmed_secs(&self.settings, &self.file, info.duration);
+                    ui.label(format!("共 {}", timestamp::format(Duration::from_secs_f64(secs))));
+                }
+            });
+            mark_source(&row.response, hovered, &[args::Source::Trim]);
+
+            let before = self.settings.format.clone();
+            self.history_row(ui);
+            self.presets_row(ui);
+            self.build_combo(ui);
+            let settings = &mut self.settings;
+            let format = ComboBox::from_label("目标格式")
+                .selected_text(&settings.format)
+                .show_ui(ui, |ui| {
+                    for fmt in plan::FORMATS {
+                        ui.selectable_value(&mut settings.format, fmt.to_string(), *fmt);
+                    }
+                });
+            a11y::selected(format.response, &settings.format);
+            let check = ui.checkbox(&mut settings.remux, "仅转换封装")
+                .on_hover_text("所有流直接复制到目标格式（-c copy -map 0），几秒钟就能完成；不使用下面的设备、编码、画质和滤镜设置");
+            mark_source(&check, hovered, &[args::Source::Remux]);
+            // 换了格式时跟着换掉手选输出的扩展名
+            if settings.format != before && !self.output_choice.trim().is_empty() {
+                self.output_choice = Path::new(self.output_choice.trim()).with_extension(&settings.format).to_string_lossy().into_owned();
+            }
+            let row = ui.horizontal(|ui| {
+                let label = ui.label("输出文件");
+                let suggested = output::avoid_existing(output::suggested_output(&self.file, &settings.format, &self.output_dir), settings);
+                ui.add(egui::TextEdit::singleline(&mut self.output_choice).hint_text(&suggested).desired_width(360.0)).labelled_by(label.id);
+                if cfg!(target_os = "windows") && ui.button("浏览…").clicked() {
+                    let current = if self.output_choice.trim().is_empty() { suggested } else { self.output_choice.clone() };
+                    if let Some(path) = output::save_dialog(&current, &settings.format) {
+                        self.output_choice = path;
+                    }
+                }
+                if !self.output_choice.is_empty() && ui.button("恢复默认").clicked() {
+                    self.output_choice.clear();
+                }
+            });
+            mark_source(&row.response, hovered, &[args::Source::Output]);
+            ui.horizontal(|ui| {
+                let label = ui.label("输出目录");
+                let default_dir = config::current().output_dir;
+                let hint = if default_dir.is_empty() { "和源文件相同".to_string() } else { default_dir };
+                ui.add(egui::TextEdit::singleline(&mut self.output_dir).hint_text(hint).desired_width(360.0))
+                    .labelled_by(label.id)
+                    .on_hover_text("只影响这个窗口里的转换（包括队列），没有手选输出文件时生效");
+            });
+            // 源文件的封面能嵌入 mp3 等格式时自动保留，放不下时可以另存
+            if !plan::is_video_container(&settings.format) {
+                let art = self.info.as_ref().is_some_and(|info| coverart::find(info).is_some());
+                let check = ui.add_enabled(art, egui::Checkbox::new(&mut settings.cover_file, format!("不能嵌入封面时另存为 {}", coverart::FALLBACK_NAME)))
+                    .on_hover_text("mp3 能保留封面；aac、wav、ogg 不能")
+                    .on_disabled_hover_text("源文件没有封面");
+                mark_source(&check, hovered, &[args::Source::CoverArt]);
+            }
+
+            let current = settings.clone();
+            let encoder_for = |gpu: &str| plan::video_codec(&JobSettings { gpu: gpu.to_string(), ..current.clone() });
+            let gpu_tests = &self.gpu_tests;
+            // 音频格式不编码视频，处理设备用不上；仅转换封装什么都不编码
+            let video = plan::is_video_container(&settings.format) && !settings.remux;
+            ui.add_enabled_ui(video, |ui| {
+                let device = ComboBox::from_label("处理设备")
+                    .selected_text(&settings.gpu)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut settings.gpu, "CPU".to_string(), "CPU");
+                        for (gpu, label) in [("NVIDIA", "NVIDIA GPU"), ("Intel", "Intel GPU"), ("AMD", "AMD GPU")] {
+                            let (mark, hover) = match gpu_tests.status(encoder_for(gpu)) {
+                                Some(gputest::Status::Passed) => (" ✓", "测试编码通过".to_string()),
+                                Some(gputest::Status::Failed(reason)) => (" ✗", reason),
+                                Some(gputest::Status::Testing) => (" …", "正在测试".to_string()),
+                                None => ("", "选中后测试".to_string()),
+                            };
+                            ui.selectable_value(&mut settings.gpu, gpu.to_string(), format!("{}{}", label, mark))
+                                .on_hover_text(hover);
+                        }
+                    });
+                mark_source(&a11y::selected(device.response, &settings.gpu), hovered, &[args::Source::Device]);
+            });
+            if !video && settings.gpu != "CPU" {
+                ui.label(if settings.remux { "仅转换封装不编码，不使用所选的处理设备" } else { "音频格式只编码音频，不使用所选的处理设备" });
+            }
+            if settings.gpu != "CPU" && video {
+                let encoder = encoder_for(&settings.gpu);
+                gpu_tests.ensure(encoder);
+                match gpu_tests.status(encoder) {
+                    Some(gputest::Status::Testing) => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("正在测试 {}…", encoder));
+                        });
+                    }
+                    Some(gputest::Status::Failed(reason)) => {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(egui::Color32::RED, format!("✗ {} 测试失败", encoder)).on_hover_text(&reason);
+                            let mut forced = gpu_tests.forced(encoder);
+                            if ui.checkbox(&mut forced, "强制使用（我知道风险）").changed() {
+                                gpu_tests.set_forced(encoder, forced);
+                            }
+                        });
+                    }
+                    Some(gputest::Status::Passed) if settings.gpu == "NVIDIA" => {
+                        // 实测会占用编码会话，转换进行中不开始
+                        if !*self.running.lock().unwrap() {
+                            nvsession::ensure();
+                        }
+                        match nvsession::current() {
+                            Some(limit) => {
+                                ui.label(format!("NVENC 同时编码上限: {}", limit.label()))
+                                    .on_hover_text("多分辨率输出超过这个路数时改为依次编码");
+                            }
+                            None => {
+                                ui.horizontal(|ui| {
+                                    ui.spinner();
+                                    ui.label("正在检测 NVENC 同时编码上限…");
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(info) = &self.info
+                && video
+                && let Some(over) = hwlimit::check(settings, info)
+            {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, format!(
+                        "{}x{} 超过 {} 的上限 {}x{}，将缩小到 {}x{}",
+                        over.size.0, over.size.1, over.encoder, over.limit.0, over.limit.1, over.scaled.0, over.scaled.1
+                    ));
+                    if ui.button("改用 CPU 编码").clicked() {
+                        settings.gpu = "CPU".to_string();
+                    }
+                });
+            }
+
+            // 仅转换封装时编码相关的设置都不生效，灰掉但保留原值
+            let remux = settings.remux;
+            ui.add_enabled_ui(!remux, |ui| {
+                let codec = ComboBox::from_label("视频编码")
+                    .selected_text(settings.codec.label())
+                    .show_ui(ui, |ui| {
+                        for codec in av1::VideoCodec::ALL {
+                            ui.selectable_value(&mut settings.codec, codec, codec.label());
+                        }
+                    });
+                mark_source(&a11y::selected(codec.response, settings.codec.label()), hovered, &[args::Source::Codec]);
+                if plan::is_video_container(&settings.format) {
+                    if !settings.codec.fits(&settings.format) {
+                        ui.colored_label(egui::Color32::YELLOW, format!("{} 不支持 {}，将使用 H.264", settings.format, settings.codec.label()));
+                    } else if settings.gpu != "CPU" && settings.codec.encoder(&settings.gpu, settings.av1.encoder).is_none() {
+                        ui.colored_label(egui::Color32::YELLOW, format!("{} 没有硬件编码器，将用 CPU 编码（{}）", settings.codec.label(), plan::video_codec(settings)));
+                    }
+                }
+                if settings.codec == av1::VideoCodec::Av1 && settings.gpu == "CPU" {
+                    // 按 ffmpeg 实际带的编码器显示对应参数
+                    match av1::SoftEncoder::detect() {
+                        Some(encoder) => {
+                            // 选了按质量或按码率时 CRF 由画质设置决定
+                            let own_crf = settings.quality.mode == quality::RateMode::Auto;
+                            let av1 = &mut settings.av1;
+                            ui.horizontal(|ui| {
+                                ui.label(encoder.name());
+                                let label = ui.label("速度");
+                                ui.add(egui::DragValue::new(&mut av1.preset).clamp_range(0..=13)).labelled_by(label.id);
+                                if encoder == av1::SoftEncoder::Aom {
+                                    ui.label(format!("(cpu-used {})", av1.cpu_used()));
+                                }
+                                let label = ui.label("CRF");
+                                ui.add_enabled(own_crf, egui::DragValue::new(&mut av1.crf).clamp_range(0..=63)).labelled_by(label.id);
+                                let label = ui.label("胶片颗粒");
+                                ui.add(egui::DragValue::new(&mut av1.film_grain).clamp_range(0..=50)).labelled_by(label.id);
+                                let tune = ComboBox::from_id_source("av1_tune")
+                                    .selected_text(av1.tune.label())
+                                    .show_ui(ui, |ui| {
+                                        for tune in [av1::Tune::Visual, av1::Tune::Psnr] {
+                                            ui.selectable_value(&mut av1.tune, tune, tune.label());
+                                        }
+                                    });
+                                a11y::named(a11y::selected(tune.response, av1.tune.label()), "AV1 调优目标");
+                            });
+                        }
+                        None => { ui.colored_label(egui::Color32::YELLOW, "ffmpeg 没有 AV1 软件编码器，将使用 H.264"); }
+                    }
+                }
+
+                // 画质：质量值按当前编码器换算成它自己的参数，旁边直接显示出来
+                ui.add_enabled_ui(video, |ui| {
+                    let encoder = plan::video_codec(settings);
+                    let q = &mut settings.quality;
+                    let row = ui.horizontal(|ui| {
+                        ui.label("画质");
+                        for mode in quality::RateMode::ALL {
+                            ui.radio_value(&mut q.mode, mode, mode.label());
+                        }
+                    });
+                    mark_source(&row.response, hovered, &[args::Source::Quality]);
+                    match q.mode {
+                        quality::RateMode::Auto => {}
+                        quality::RateMode::Quality => {
+                            ui.horizontal(|ui| {
+                                let slider = egui::Slider::new(&mut q.level, 0..=quality::MAX_LEVEL).text("越小画质越好、文件越大");
+                                a11y::named(ui.add(slider), "质量值");
+                                let args = quality::level_args(encoder, q.level);
+                                if !args.is_empty() {
+                                    ui.monospace(args.join(" "));
+                                }
+                            });
+                        }
+                        quality::RateMode::Bitrate => {
+                            ui.horizontal(|ui| {
+                                let label = ui.label("目标码率");
+                                ui.add(egui::DragValue::new(&mut q.bitrate_k).clamp_range(100..=100_000).suffix(" kbps")).labelled_by(label.id);
+                                ui.label("（峰值不超过目标，缓冲 2 倍）");
+                            });
+                            // 硬件编码器和 x265 没有 -pass 这种两遍方式
+                            let supported = quality::supports_two_pass(encoder);
+                            let check = ui.add_enabled(supported, egui::Checkbox::new(&mut settings.two_pass, "两遍编码"));
+                            mark_source(&check, hovered, &[args::Source::TwoPass]);
+                            if supported {
+                                check.on_hover_text("先分析一遍再编码，同样的码率画质更稳定，耗时约为两倍");
+                            } else {
+                                check.on_disabled_hover_text(format!("{} 不支持两遍编码", encoder));
+                            }
+                        }
+                    }
+                    // 只列出当前编码器支持的档位和调优，换了编码器后不适用的选择回到默认
+                    let (presets, tunes) = (preset::presets(encoder), preset::tunes(encoder));
+                    if settings.preset.as_deref().is_some_and(|p| !presets.contains(&p)) {
+                        settings.preset = None;
+                    }
+                    if settings.tune.as_deref().is_some_and(|t| !tunes.contains(&t)) {
+                        settings.tune = None;
+                    }
+                    if !presets.is_empty() {
+                        let row = ui.horizontal(|ui| {
+                            for (name, choice, options) in [("速度档位", &mut settings.preset, presets), ("调优", &mut settings.tune, tunes)] {
+                                if options.is_empty() {
+                                    continue;
+                                }
+                                let label = ui.label(name);
+                                let shown = choice.clone().unwrap_or("默认".to_string());
+                                let combo = ComboBox::from_id_source(name)
+                                    .selected_text(&shown)
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(choice, None, "默认");
+                                        for option in options {
+                                            ui.selectable_value(choice, Some(option.to_string()), *option);
+                                        }
+                                    });
+                                a11y::selected(combo.response, &shown).labelled_by(label.id);
+                            }
+                            ui.label(format!("({})", encoder));
+                        });
+                        mark_source(&row.response, hovered, &[args::Source::Preset]);
+                    }
+                });
+
+                let row = ui.horizontal(|ui| {
+                    let label = ui.label("音频编码:");
+                    let codec = ComboBox::from_id_source("audio_codec")
+                        .selected_text(settings.audio_codec.label())
+                        .show_ui(ui, |ui| {
+                            for c in AudioCodec::ALL {
+                                ui.selectable_value(&mut settings.audio_codec, c, c.label());
+                            }
+                        });
+                    a11y::selected(codec.response, settings.audio_codec.label()).labelled_by(label.id);
+                    ui.add_enabled_ui(settings.audio_codec.uses_bitrate(), |ui| {
+                        let bitrate = ComboBox::from_id_source("audio_bitrate")
+                            .selected_text(format!("{}k", settings.audio_bitrate_k))
+                            .show_ui(ui, |ui| {
+                                for k in plan::AUDIO_BITRATES {
+                                    ui.selectable_value(&mut settings.audio_bitrate_k, k, format!("{}k", k));
+                                }
+                            });
+                        a11y::named(a11y::selected(bitrate.response, &format!("{}k", settings.audio_bitrate_k)), "音频码率");
+                    });
+                });
+                mark_source(&row.response, hovered, &[args::Source::AudioCodec]);
+                let row = ui.horizontal(|ui| {
+                    let mut on = settings.loudnorm.is_some();
+                    if ui.checkbox(&mut on, "响度标准化").on_hover_text("用 loudnorm 把整体响度调到目标值，音频需要重新编码").changed() {
+                        settings.loudnorm = on.then_some(loudness::DEFAULT_TARGET);
+                    }
+                    if let Some(target) = &mut settings.loudnorm {
+                        let label = ui.label("目标");
+                        ui.add(egui::DragValue::new(target).clamp_range(loudness::TARGET_MIN..=loudness::TARGET_MAX).speed(0.5).suffix(" LUFS"))
+                            .labelled_by(label.id);
+                    }
+                });
+                mark_source(&row.response, hovered, &[args::Source::Loudnorm]);
+                if let Some(info) = &self.info
+                    && let Err(e) = plan::check_audio(settings, info)
+                {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+                mark_source(&ui.checkbox(&mut settings.keep_all_audio, "保留所有音轨"), hovered, &[args::Source::Tracks]);
+                if settings.keep_all_audio && let Some(info) = &self.info {
+                    let decisions = plan::plan_audio(settings, info);
+                    for (n, stream) in info.streams.iter().filter(|s| s.codec_type == "audio").enumerate() {
+                        if settings.audio_tracks.len() <= n {
+                            settings.audio_tracks.resize(n + 1, TrackChoice::Auto);
+                        }
+                        ui.horizontal(|ui| {
+                            let lang = stream.props.get("tags.language").map(|l| l.as_str()).unwrap_or("und");
+                            let label = ui.label(format!("音轨 {}: {} ({})", n, stream.codec_name, lang));
+                            let choice = &mut settings.audio_tracks[n];
+                            let track = ComboBox::from_id_source(("audio_track", n))
+                                .selected_text(choice.label())
+                                .show_ui(ui, |ui| {
+                                    for c in TrackChoice::ALL {
+                                        ui.selectable_value(choice, c, c.label());
+                                    }
+                                });
+                            a11y::selected(track.response, choice.label()).labelled_by(label.id);
+                            match decisions.iter().find(|d| d.input_index == n) {
+                                Some(d) if d.forced => { ui.label(format!("目标容器不支持，将转为 {}", d.codec.unwrap_or_default())); }
+                                Some(d) => { ui.label(d.codec.map(|c| format!("→ {}", c)).unwrap_or("→ 复制".to_string())); }
+                                None => {}
+                            }
+                        });
+                    }
+                }
+            });
+
+            self.data_loss_panel(ui);
+
+            // 硬件编码器测试失败又没有强制使用时不能开始
+            let gpu_ok = self.settings.gpu == "CPU"
+                || self.settings.remux
+                || !plan::is_video_container(&self.settings.format)
+                || !self.gpu_tests.blocked(plan::video_codec(&self.settings));
+            // 自定义分辨率、帧率、裁剪时间或附加参数不合法时不能开始，错误显示在设置旁边
+            let gpu_ok = gpu_ok && self.settings.resolution.check().is_ok() && self.fps_field.is_valid()
+                && self.trim_fields.iter().all(|f| f.is_valid())
+                && plan::check_trim(&self.settings, self.info.as_ref().unwrap_or(&probe::MediaInfo::default())).is_ok()
+                && plan::check_extra_args(&self.settings).is_ok();
+            // 实时输入只能读一次，不能先预览
+            let live = live::is_live(&self.file);
+            let live_ok = live::check(&self.settings, &self.file).is_ok();
+            // 输出就是输入时 ffmpeg 会边读边覆盖源文件
+            let same_file = output::same_file(&self.file, &self.planned_output());
+            if same_file {
+                ui.colored_label(egui::Color32::RED, "输出文件和输入文件相同，请换一个输出文件或输出目录");
+            }
+            ui.horizontal(|ui| {
+                if ui.add_enabled(gpu_ok && live_ok && !same_file, egui::Button::new("开始转换")).clicked() && !*self.running.lock().unwrap() {
+                    let output = self.planned_output();
+                    self.request_start(output);
+                }
+                if ui.add_enabled(gpu_ok && !live, egui::Button::new(format!("预览前 {} 秒", PREVIEW_SECS))).clicked() && !*self.running.lock().unwrap() {
+                    self.start_preview(PREVIEW_SECS, 1);
+                }
+                let has_duration = self.info.as_ref().is_some_and(|i| i.duration > 0.0);
+                if ui.add_enabled(has_duration && gpu_ok, egui::Button::new("取样预览"))
+                    .on_hover_text("在全片均匀取几段编码后拼成一个短文件，并按样本码率推算完整文件大小")
+                    .clicked()
+                    && !*self.running.lock().unwrap()
+                {
+                    self.start_preview(self.sample_secs, self.sample_count);
+                }
+                a11y::named(ui.add(egui::DragValue::new(&mut self.sample_count).clamp_range(2..=20).suffix(" 段")), "取样段数");
+                a11y::named(ui.add(egui::DragValue::new(&mut self.sample_secs).clamp_range(5..=600).suffix(" 秒/段")), "每段秒数");
+
+                let running = *self.running.lock().unwrap();
+                if running && self.stop_mode.lock().unwrap().is_some() {
+                    ui.add_enabled(false, egui::Button::new("正在停止…"));
+                } else {
+                    ui.add_enabled_ui(running, |ui| {
+                        ui.menu_button("中断 ⏷", |ui| {
+                            if ui.button("停止并保留").clicked() {
+                                self.request_stop(StopMode::Keep);
+                                ui.close_menu();
+                            }
+                            if ui.button("停止并删除").clicked() {
+                                self.request_stop(StopMode::Delete);
+                                ui.close_menu();
+                            }
+                            if self.batch_active {
+                                ui.checkbox(&mut self.batch_cancel_rest, "同时取消队列里剩下的文件");
+                            }
+                        });
+                    });
+                }
+            });
+
+            self.web_button(ui);
+
+            let row = ui.horizontal(|ui| {
+                let label = ui.label("烧录字幕");
+                ui.text_edit_singleline(&mut self.settings.subtitle_file).labelled_by(label.id);
+            });
+            mark_source(&row.response, hovered, &[args::Source::Filters]);
+            if !self.settings.subtitle_file.is_empty() {
+                let path = self.settings.subtitle_file.clone();
+                if self.sub_detected.as_ref().map(|(p, _)| p) != Some(&path) {
+                    let desc = match subtitle::detect(Path::new(&path)) {
+                        Ok(d) => format!(
+                            "{}{}{}",
+                            d.encoding.name(),
+                            if d.bom { " (BOM)" } else { "" },
+                            if d.malformed { "，部分字符无法识别，可能混用了多种编码" } else { "" },
+                        ),
+                        Err(e) => format!("无法读取: {}", e),
+                    };
+                    self.sub_detected = Some((path, desc));
+                }
+                ui.horizontal(|ui| {
+                    ui.label(format!("检测到的编码: {}", self.sub_detected.as_ref().unwrap().1));
+                    let enc = &mut self.settings.subtitle_encoding;
+                    let encoding = ComboBox::from_label("字幕编码")
+                        .selected_text(enc.as_deref().unwrap_or("自动"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(enc, None, "自动");
+                            for name in subtitle::ENCODINGS {
+                                ui.selectable_value(enc, Some(name.to_string()), *name);
+                            }
+                        });
+                    a11y::selected(encoding.response, enc.as_deref().unwrap_or("自动"));
+                });
+            }
+
+            ui.horizontal(|ui| {
+                let check = ui.checkbox(&mut self.settings.overwrite, "覆盖已存在的输出文件")
+                    .on_hover_text("只对手选的输出文件生效；自动生成的文件名遇到同名文件时会加上 (1)、(2)");
+                mark_source(&check, hovered, &[args::Source::Overwrite]);
+                ui.checkbox(&mut self.settings.incremental, "只转换比输出新的文件");
+                let check = ui.checkbox(&mut self.settings.keep_dates, "保留拍摄日期")
+                    .on_hover_text("录制时间写入输出的 creation_time，并把源文件的修改/创建时间复制到输出");
+                mark_source(&check, hovered, &[args::Source::Dates]);
+            });
+            if !self.settings.fixes.is_empty() {
+                let row = ui.horizontal(|ui| {
+                    let labels: Vec<&str> = warnings::FIXES.iter()
+                        .filter(|f| self.settings.fixes.iter().any(|id| id == f.id))
+                        .map(|f| f.label)
+                        .collect();
+                    ui.label(format!("修正参数: {}", labels.join("，")));
+                    if ui.button("清除").clicked() {
+                        self.settings.fixes.clear();
+                    }
+                });
+                mark_source(&row.response, hovered, &[args::Source::Fixes]);
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.settings.hash_source, "记录源文件 SHA-256")
+                    .on_hover_text("和转换同时进行，结果写入日志和转换记录");
+                ui.add_enabled_ui(self.settings.hash_source, |ui| {
+                    ui.checkbox(&mut self.settings.hash_embed, "写入输出文件的注释");
+                });
+                let hashing = *self.hash_progress.lock().unwrap();
+                if let Some(p) = hashing {
+                    let bar = ui.add(ProgressBar::new(p / 100.0).text(format!("校验 {:.0}%", p)).desired_width(160.0));
+                    a11y::progress(bar, p / 100.0, "源文件校验进度");
+                    if ui.button("取消校验").clicked() {
+                        self.hash_cancel.cancel();
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                let label = ui.label("无输出超过");
+                ui.add(egui::DragValue::new(&mut self.hang_minutes).clamp_range(1..=120).suffix(" 分钟")).labelled_by(label.id);
+                ui.label("视为挂起");
+            });
+
+            let deint = &mut self.settings.deinterlace;
+            let mode = ComboBox::from_label("反交错")
+                .selected_text(deint.label())
+                .show_ui(ui, |ui| {
+                    for mode in interlace::Deinterlace::ALL {
+                        ui.selectable_value(deint, mode, mode.label());
+                    }
+                });
+            mark_source(&a11y::selected(mode.response, deint.label()), hovered, &[args::Source::Filters]);
+
+            let fit = &mut self.settings.fit;
+            let row = ui.horizontal(|ui| {
+                let target = ComboBox::from_label("目标宽高比")
+                    .selected_text(fit.target.label())
+                    .show_ui(ui, |ui| {
+                        for target in aspect::AspectTarget::ALL {
+                            ui.selectable_value(&mut fit.target, target, target.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("等比缩放并补边，不拉伸画面。先反交错和烧录字幕，再补边；多分辨率按补边后的画面缩放");
+                a11y::selected(target, fit.target.label());
+                if fit.target == aspect::AspectTarget::Custom {
+                    a11y::named(ui.add(egui::DragValue::new(&mut fit.custom.0).clamp_range(1..=100)), "自定义宽高比的宽");
+                    ui.label(":");
+                    a11y::named(ui.add(egui::DragValue::new(&mut fit.custom.1).clamp_range(1..=100)), "自定义宽高比的高");
+                }
+                if fit.target != aspect::AspectTarget::Off {
+                    let fill = ComboBox::from_id_source("fit_fill")
+                        .selected_text(fit.fill.label())
+                        .show_ui(ui, |ui| {
+                            for fill in [aspect::Fill::Color, aspect::Fill::Blur] {
+                                ui.selectable_value(&mut fit.fill, fill, fill.label());
+                            }
+                        });
+                    a11y::named(a11y::selected(fill.response, fit.fill.label()), "补边方式");
+                    if fit.fill == aspect::Fill::Color {
+                        a11y::named(ui.color_edit_button_srgb(&mut fit.color), "补边颜色");
+                    }
+                }
+            });
+            mark_source(&row.response, hovered, &[args::Source::Filters]);
+
+            let video = self.info.as_ref().and_then(|i| i.streams.iter().find(|s| s.codec_type == "video"));
+            let source_size = video.and_then(aspect::display_size).map(|(w, h, _)| (w, h));
+            let res = &mut self.settings.resolution;
+            let row = ui.horizontal(|ui| {
+                let picked = ComboBox::from_label("分辨率")
+                    .selected_text(if matches!(res, Resolution::Custom(..)) { "自定义".to_string() } else { res.label() })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(res, Resolution::Original, Resolution::Original.label());
+                        for h in resolution::HEIGHTS {
+                            ui.selectable_value(res, Resolution::Height(h), Resolution::Height(h).label());
+                        }
+                        // 从源文件的大小开始改
+                        if ui.selectable_label(matches!(res, Resolution::Custom(..)), "自定义").clicked() && !matches!(res, Resolution::Custom(..)) {
+                            let (w, h) = source_size.unwrap_or((1920, 1080));
+                            *res = Resolution::Custom(w / 2 * 2, h / 2 * 2);
+                        }
+                    })
+                    .response
+                    .on_hover_text("按短边缩放，竖拍的视频同样适用；在补边之后缩放");
+                a11y::selected(picked, &res.label());
+                if let Resolution::Custom(w, h) = res {
+                    a11y::named(ui.add(egui::DragValue::new(w).clamp_range(0..=resolution::MAX_SIDE)), "自定义宽度");
+                    ui.label("×");
+                    a11y::named(ui.add(egui::DragValue::new(h).clamp_range(0..=resolution::MAX_SIDE)), "自定义高度");
+                }
+                if let Err(e) = res.check() {
+                    ui.colored_label(egui::Color32::RED, e);
+                } else if let Some(size) = source_size
+                    && *res != Resolution::Original
+                {
+                    let (w, h) = res.apply(size);
+                    ui.label(format!("{}x{} → {}x{}", size.0, size.1, w, h));
+                }
+            });
+            mark_source(&row.response, hovered, &[args::Source::Filters]);
+            let source_rate = self.info.as_ref().filter(|_| video.is_some()).and_then(retime::source_rate);
+            let row = ui.add_enabled_ui(self.settings.retime.is_none(), |ui| {
+                self.fps_field.show(ui, &mut self.settings.fps, source_rate);
+            });
+            mark_source(&row.response, hovered, &[args::Source::Filters]);
+
+            if video.and_then(aspect::display_size).is_some_and(|(_, _, anamorphic)| anamorphic) {
+                let mode = ComboBox::from_label("非方形像素")
+                    .selected_text(self.settings.sar_mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in [aspect::SarMode::Square, aspect::SarMode::Keep] {
+                            ui.selectable_value(&mut self.settings.sar_mode, mode, mode.label());
+                        }
+                    });
+                mark_source(&a11y::selected(mode.response, self.settings.sar_mode.label()), hovered, &[args::Source::Aspect]);
+            }
+
+            mark_source(&ui.checkbox(&mut self.settings.ladder_enabled, "多分辨率"), hovered, &[args::Source::Ladder]);
+            if self.settings.ladder_enabled {
+                let ladder = &mut self.settings.ladder;
+                let mut remove = None;
+                for (i, rung) in ladder.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        a11y::named(ui.add(egui::DragValue::new(&mut rung.height).clamp_range(144..=4320).suffix("p")), &format!("第 {} 档高度", i + 1));
+                        a11y::named(ui.add(egui::DragValue::new(&mut rung.bitrate_k).clamp_range(100..=100_000).suffix(" kbps")), &format!("第 {} 档码率", i + 1));
+                        if a11y::named(ui.button("删除"), &format!("删除第 {} 档", i + 1)).clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    ladder.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("添加档位").clicked() {
+                        let next = ladder.last().map(|r| ladder::Rung { height: r.height / 2 / 2 * 2, bitrate_k: r.bitrate_k / 2 });
+                        ladder.push(next.unwrap_or(ladder::Rung { height: 720, bitrate_k: 2800 }));
+                    }
+                    ui.checkbox(&mut self.settings.ladder_hls, "生成 HLS 主播放列表");
+                });
+                if ladder.len() > ladder::max_sessions(&self.settings.gpu) {
+                    ui.label(format!("{} 最多同时进行 {} 路编码，将依次编码", self.settings.gpu, ladder::max_sessions(&self.settings.gpu)));
+                }
+            }
+
+            let row = ui.horizontal(|ui| {
+                let gop = &mut self.settings.gop;
+                let label = ui.label("关键帧间隔");
+                ui.add(egui::DragValue::new(&mut gop.frames).clamp_range(0..=1000).suffix(" 帧"))
+                    .labelled_by(label.id)
+                    .on_hover_text("0 表示用编码器默认值");
+                ui.add_enabled(gop.frames > 0, egui::Checkbox::new(&mut gop.fixed, "固定间隔"))
+                    .on_hover_text("不在场景切换处插入额外的关键帧");
+            });
+            mark_source(&row.response, hovered, &[args::Source::Gop]);
+            // HLS 分片要从关键帧开始，间隔和分片时长对不上时提示并一键修正
+            let segment = ladder::HLS_SEGMENT_SECS as f64;
+            if self.settings.ladder_enabled
+                && self.settings.ladder_hls
+                && let Some(fps) = self.info.as_ref().and_then(|info| gop::output_fps(&self.settings, info))
+                && let Some(problem) = gop::check(self.settings.gop, fps, segment)
+            {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, problem);
+                    let fixed = gop::fix(fps, segment);
+                    if ui.button("自动修正")
+                        .on_hover_text(format!("设为固定 {} 帧（{:.2} 秒），每个 {} 秒分片 {} 个关键帧",
+                            fixed.frames, fixed.frames as f64 / fps, segment, (fps * segment).round() as u32 / fixed.frames))
+                        .clicked()
+                    {
+                        self.settings.gop = fixed;
+                    }
+                });
+            }
+
+            // 音频和视频时长相差较多时提示，并选择处理方式
+            if let Some(mismatch) = self.info.as_ref().and_then(lengths::detect) {
+                let row = ui.horizontal(|ui| {
+                    let label = ui.colored_label(egui::Color32::YELLOW, mismatch.label());
+                    let policy = egui::ComboBox::from_id_source("length_policy")
+                        .selected_text(self.settings.length_policy.label())
+                        .show_ui(ui, |ui| {
+                            for policy in lengths::LengthPolicy::ALL {
+                                ui.selectable_value(&mut self.settings.length_policy, policy, policy.label());
+                            }
+                        });
+                    a11y::selected(policy.response, self.settings.length_policy.label()).labelled_by(label.id);
+                });
+                mark_source(&row.response, hovered, &[args::Source::Lengths]);
+            }
+
+            // 帧率重映射：改帧率但不丢帧、不补帧，音频同比例变速
+            let row = ui.horizontal(|ui| {
+                let mut enabled = self.settings.retime.is_some();
+                if ui.checkbox(&mut enabled, "帧率重映射")
+                    .on_hover_text("保留每一帧，只改播放速度，音频按同样的比例伸缩。常用于还原 PAL 加速（25 → 23.976）")
+                    .changed()
+                {
+                    self.settings.retime = enabled.then(|| {
+                        let mut retime = retime::Retime::pal_slowdown();
+                        if let Some(rate) = self.info.as_ref().and_then(retime::source_rate) {
+                            retime.from = rate;
+                        }
+                        retime
+                    });
+                }
+                if ui.button("PAL 加速还原 (25 → 23.976)").clicked() {
+                    self.settings.retime = Some(retime::Retime::pal_slowdown());
+                }
+            });
+            mark_source(&row.response, hovered, &[args::Source::Retime]);
+            if let Some(retime) = &mut self.settings.retime {
+                ui.horizontal(|ui| {
+                    for (id, rate) in [("retime_from", &mut retime.from), ("retime_to", &mut retime.to)] {
+                        let label = ui.label(if id == "retime_from" { "源帧率" } else { "→ 目标帧率" });
+                        let pick = egui::ComboBox::from_id_source(id)
+                            .selected_text(rate.label())
+                            .show_ui(ui, |ui| {
+                                for r in retime::common_rates() {
+                                    ui.selectable_value(rate, r, r.label());
+                                }
+                            });
+                        a11y::selected(pick.response, &rate.label()).labelled_by(label.id);
+                    }
+                    ui.checkbox(&mut retime.keep_pitch, "保持音调");
+                });
+                let mut text = retime.label();
+                if let Some(info) = self.info.as_ref().filter(|info| info.duration > 0.0) {
+                    text.push_str(&format!("，时长 {} → {}",
+                        timestamp::format(Duration::from_secs_f64(info.duration)),
+                        timestamp::format(Duration::from_secs_f64(retime.scale_secs(info.duration)))));
+                }
+                ui.label(text);
+            }
+
+            // 烧录时间码：给审片副本画上时间或帧号
+            let mut burn = self.settings.burn_in.is_some();
+            let check = ui.checkbox(&mut burn, "烧录时间码").on_hover_text("用 drawtext 把时间或帧号画进画面，给审片用的副本");
+            mark_source(&check, hovered, &[args::Source::Filters]);
+            if check.changed() {
+                self.settings.burn_in = burn.then(burnin::BurnIn::default);
+            }
+            if let Some(b) = &mut self.settings.burn_in {
+                ui.horizontal(|ui| {
+                    for content in burnin::Content::ALL {
+                        ui.radio_value(&mut b.content, content, content.label());
+                    }
+                    ui.separator();
+                    for clock in burnin::Clock::ALL {
+                        let hint = match clock {
+                            burnin::Clock::Source => "显示这一帧在源文件里的位置，取样预览和变速后仍能对照原片",
+                            burnin::Clock::Output => "显示在输出文件里的位置，从 0 开始；变速后按新的时长计算",
+                        };
+                        ui.radio_value(&mut b.clock, clock, clock.label()).on_hover_text(hint);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let pick = ComboBox::from_label("位置")
+                        .selected_text(b.corner.label())
+                        .show_ui(ui, |ui| {
+                            for corner in burnin::Corner::ALL {
+                                ui.selectable_value(&mut b.corner, corner, corner.label());
+                            }
+                        });
+                    a11y::selected(pick.response, b.corner.label());
+                    let label = ui.label("字号");
+                    ui.add(egui::DragValue::new(&mut b.font_size).clamp_range(8..=400)).labelled_by(label.id);
+                    ui.checkbox(&mut b.boxed, "黑底");
+                });
+                if b.content == burnin::Content::Time && b.clock == burnin::Clock::Source {
+                    let tc = self.info.as_ref().and_then(timecode::from_info);
+                    let text = match tc {
+                        Some(tc) => format!("从源时间码 {} 开始，显示为 时:分:秒:帧", tc),
+                        None => "从源时间码开始（源文件没有时间码）".to_string(),
+                    };
+                    ui.add_enabled(tc.is_some(), egui::Checkbox::new(&mut b.source_timecode, text));
+                }
+                ui.horizontal(|ui| {
+                    let label = ui.label("字体文件");
+                    let hint = if cfg!(target_os = "windows") { "默认 Consolas" } else { "默认字体" };
+                    ui.add(egui::TextEdit::singleline(&mut b.font_file).hint_text(hint).desired_width(260.0)).labelled_by(label.id);
+                });
+            }
+
+            let suspicious = match &self.info {
+                Some(info) => info.suspicious(),
+                None => Some("ffprobe 无法读取"),
+            };
+            if let Some(reason) = suspicious
+                && self.settings.probe_depth != probe::ProbeDepth::DEEP
+            {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, format!("探测结果可能不准确：{}", reason));
+                    if ui.button("深度重新分析").clicked() {
+                        self.set_probe_depth(probe::ProbeDepth::DEEP);
+                    }
+                });
+            }
+
+            ui.collapsing("高级", |ui| {
+                let mut depth = self.settings.probe_depth;
+                let row = ui.horizontal(|ui| {
+                    let label = ui.label("分析时长");
+                    ui.add(egui::DragValue::new(&mut depth.analyze_secs).clamp_range(0..=3600).suffix(" 秒")).labelled_by(label.id);
+                    let label = ui.label("探测大小");
+                    ui.add(egui::DragValue::new(&mut depth.probesize_mb).clamp_range(0..=4096).suffix(" MB")).labelled_by(label.id);
+                    ui.label("(0 = 默认)");
+                });
+                mark_source(&row.response, hovered, &[args::Source::ProbeDepth]);
+                if depth != self.settings.probe_depth {
+                    self.set_probe_depth(depth);
+                }
+                let row = ui.horizontal(|ui| {
+                    let label = ui.label("限制写入速度 (MB/s)");
+                    ui.add(egui::DragValue::new(&mut self.settings.write_limit_mb).clamp_range(0.0..=1000.0).speed(0.5)).labelled_by(label.id);
+                    ui.label("(0 = 不限)");
+                }).response.on_hover_text("输出在网络共享或 SMR 硬盘上时避免占满带宽。重新编码时限制码率和处理速度，流复制时放慢读取");
+                mark_source(&row, hovered, &[args::Source::Throttle]);
+                let row = ui.horizontal(|ui| {
+                    let label = ui.label("附加参数");
+                    ui.add(egui::TextEdit::singleline(&mut self.settings.extra_args).hint_text("-metadata title=\"My Video\"").desired_width(360.0))
+                        .labelled_by(label.id)
+                        .on_hover_text("放在输出路径前面；和界面生成的参数重复时以这里的为准");
+                });
+                mark_source(&row.response, hovered, &[args::Source::Extra]);
+                if let Err(e) = plan::check_extra_args(&self.settings) {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+                self.ffmpeg_row(ui);
+                self.builds_row(ui);
+                let mut low_power = self.budget.enabled();
+                if ui.checkbox(&mut low_power, "节能界面")
+                    .on_hover_text("转换期间界面每秒只刷新 4 次，不显示缩略图，暂停统计和其他实例的刷新，日志展开才显示。适合配置较低的电脑")
+                    .changed()
+                    && let Err(e) = self.budget.set_enabled(low_power)
+                {
+                    self.log_text.lock().unwrap().push_str(&format!("\n无法保存设置: {}\n", e));
+                }
+                ui.horizontal(|ui| {
+                    let mut parallel = config::clamp_parallel(config::current().parallel_jobs);
+                    let label = ui.label("同时转换的任务数");
+                    let changed = ui.add(egui::DragValue::new(&mut parallel).clamp_range(1..=config::max_parallel()))
+                        .labelled_by(label.id)
+                        .on_hover_text("ffui --queue 无界面转换任务列表时同时跑几个任务，最多为 CPU 核心数。用 NVIDIA 编码的任务还受 NVENC 同时编码的路数限制")
+                        .changed();
+                    if changed
+                        && let Err(e) = config::save(&config::Config { parallel_jobs: config::clamp_parallel(parallel), ..config::current() })
+                    {
+                        self.log_text.lock().unwrap().push_str(&format!("\n无法保存设置: {}\n", e));
+                    }
+                });
+            });
+
+            ui.collapsing("温度保护", |ui| {
+                let t = &mut self.thermal;
+                ui.checkbox(&mut t.enabled, "CPU 温度过高时暂停转换");
+                ui.horizontal(|ui| {
+                    let label = ui.label("暂停温度");
+                    ui.add(egui::DragValue::new(&mut t.high).clamp_range(50.0..=110.0).suffix("°C")).labelled_by(label.id);
+                    let label = ui.label("持续");
+                    ui.add(egui::DragValue::new(&mut t.hold_secs).clamp_range(0..=600).suffix(" 秒")).labelled_by(label.id);
+                });
+                ui.horizontal(|ui| {
+                    let label = ui.label("恢复温度");
+                    ui.add(egui::DragValue::new(&mut t.low).clamp_range(30.0..=t.high).suffix("°C")).labelled_by(label.id);
+                });
+            });
+
+            ui.collapsing("完成后运行命令", |ui| {
+                let s = &mut self.settings;
+                ui.horizontal(|ui| {
+                    let label = ui.label("成功后");
+                    ui.add(egui::TextEdit::singleline(&mut s.hook_success).hint_text("例如 upload.exe \"{output}\"")).labelled_by(label.id);
+                });
+                ui.horizontal(|ui| {
+                    let label = ui.label("失败后");
+                    ui.text_edit_singleline(&mut s.hook_failure).labelled_by(label.id);
+                });
+                ui.horizontal(|ui| {
+                    let label = ui.label("超时");
+                    ui.add(egui::DragValue::new(&mut s.hook_timeout_secs).clamp_range(1..=86_400).suffix(" 秒")).labelled_by(label.id);
+                });
+                ui.label(format!("可用占位符: {}。命令不经过 shell，含空格的参数用引号括起来；输出写入日志，失败只提示不影响转换结果", hook::PLACEHOLDERS));
+            });
+            ui.collapsing("导出截图", |ui| self.snapshot_panel(ui));
+            ui.collapsing("语音优化（讲座/播客/有声书）", |ui| self.speech_panel(ui));
+            ui.collapsing("专辑（按音轨顺序整张转换）", |ui| self.album_panel(ui));
+            ui.collapsing("快速操作（转正画面/去掉音轨）", |ui| self.quick_panel(ui));
+            let polling = self.budget.live_polling(*self.running.lock().unwrap());
+            ui.collapsing("统计", |ui| if polling { self.stats_panel(ui) } else { ui.label(PAUSED_BY_BUDGET); });
+            ui.collapsing("滤镜顺序", |ui| self.filter_chain_panel(ui));
+            ui.collapsing("命令预览", |ui| self.command_panel(ui));
+            ui.collapsing("与上次任务比较", |ui| self.compare_panel(ui));
+            ui.collapsing("比较两个预设", |ui| self.preset_compare_panel(ui));
+            ui.collapsing("自动应用预设的规则", |ui| self.preset_rules_panel(ui));
+            ui.collapsing("任务列表", |ui| self.joblist_panel(ui));
+            ui.collapsing("其他 ffui 实例", |ui| if polling { self.monitor.show(ui) } else { ui.label(PAUSED_BY_BUDGET); });
+
+            self.blocked_panel(ui);
+            let snapshot = self.progress.get();
+            let p = snapshot.progress;
+            // 没开始过或开始前就出错的任务不显示用时；时长未知时只显示已用时间
+            let clock = snapshot.clock;
+            let running = *self.running.lock().unwrap();
+            let elapsed = clock.elapsed(Instant::now()).filter(|_| running || clock.stopped());
+            let elapsed_label = elapsed.map(|e| format!("已用时间 {}", inspect::format_duration(e.as_secs_f64())));
+            match snapshot.live_secs {
+                Some(secs) => {
+                    let encoded = format!("已编码 {}", timestamp::format(Duration::from_secs_f64(secs)));
+                    ui.label(elapsed_label.map_or(encoded.clone(), |e| format!("{} · {}", encoded, e)));
+                }
+                None => {
+                    ui.horizontal(|ui| {
+                        a11y::progress(ui.add(ProgressBar::new(p / 100.0).show_percentage().desired_width(300.0)), p / 100.0, "转换进度");
+                        if let Some(label) = &elapsed_label {
+                            ui.label(label);
+                        }
+                        let eta = match elapsed.filter(|_| running && !clock.stopped()) {
+                            Some(elapsed) => runner::eta(p as f64 / 100.0, elapsed, snapshot.eta_secs),
+                            None => runner::Eta::Hidden,
+                        };
+                        match eta {
+                            runner::Eta::Secs(secs) => { ui.label(format!("预计剩余 {}", inspect::format_duration(secs))); }
+                            runner::Eta::AlmostDone => { ui.label("即将完成"); }
+                            runner::Eta::Hidden => {}
+                        }
+                    });
+                }
+            }
+            if let Some(stats) = snapshot.stats.label() {
+                ui.label(stats);
+            }
+            if let Some(bottleneck) = snapshot.bottleneck.label() {
+                ui.label(bottleneck).on_hover_text("按 ffmpeg 的 CPU 占用和读写字节数估计：CPU 闲下来而读写还在继续时是在等磁盘或网络");
+            }
+            if *self.stalled.lock().unwrap() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, "ffmpeg 长时间没有输出，可能已挂起");
+                    if ui.button("结束 ffmpeg").clicked() {
+                        self.job_cancel.cancel();
+                        if let Some(child) = self.child_process.lock().unwrap().as_mut() {
+                            let _ = child.kill();
+                        }
+                    }
+                });
+            }
+            if let Some(temp) = *self.paused.lock().unwrap() {
+                ui.colored_label(egui::Color32::YELLOW, format!("已暂停：温度过高 {:.0}°C", temp));
+            }
+            if let Some(percent) = self.budget.suggestion() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::YELLOW, format!("界面本身占用 CPU {:.0}%，会拖慢转换", percent));
+                    if ui.button("开启节能界面").clicked()
+                        && let Err(e) = self.budget.set_enabled(true)
+                    {
+                        self.log_text.lock().unwrap().push_str(&format!("\n无法保存设置: {}\n", e));
+                    }
+                    if ui.button("不用了").clicked() {
+                        self.budget.dismiss();
+                    }
+                });
+            }
+
+            let failure = *self.failure.lock().unwrap();
+            if let Some(hint) = failure {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::RED, format!("❌ {}", hint.message));
+                    if hint.retry_rename
+                        && !*self.running.lock().unwrap()
+                        && ui.button("换个文件名重试").clicked()
+                    {
+                        let output = output::unique_path(Path::new(&self.output));
+                        self.start(output.to_string_lossy().into_owned());
+                    }
+                });
+            }
+
+            // 崩溃时按可能的原因给出重试方式，点了直接改窗口里的设置再转换
+            let mut retry = None;
+            if let Some(report) = self.crash.lock().unwrap().as_ref() {
+                ui.colored_label(egui::Color32::RED, format!("❌ {}", report.label));
+                if let Some(path) = &report.path {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("崩溃报告: {}", path.display()));
+                        if ui.button("打开报告").clicked() {
+                            let _ = process::open_file(&path.to_string_lossy());
+                        }
+                    });
+                }
+                if !*self.running.lock().unwrap() {
+                    for &option in &report.retries {
+                        ui.horizontal(|ui| {
+                            ui.label(option.cause());
+                            if ui.button(option.label()).clicked() {
+                                retry = Some(option);
+                            }
+                        });
+                    }
+                }
+            }
+            if let Some(option) = retry {
+                option.apply(&mut self.settings);
+                let output = output::unique_path(Path::new(&self.output));
+                self.start(output.to_string_lossy().into_owned());
+            }
+
+            let detail = self.failure_detail.lock().unwrap().clone();
+            if !detail.is_empty() && !*self.running.lock().unwrap() {
+                ui.collapsing("ffmpeg 错误输出", |ui| {
+                    ScrollArea::vertical().id_source("failure_detail").max_height(200.0).stick_to_bottom(true).show(ui, |ui| {
+                        a11y::log_view(ui, &detail, "ffmpeg 错误输出");
+                    });
+                });
+                if self.diagnose.is_none()
+                    && ui.button("生成诊断包").on_hover_text("把这次转换的设置、命令、日志和环境信息打包，方便发给开发者").clicked()
+                {
+                    self.prepare_diagnose();
+                }
+            }
+            self.diagnose_panel(ui);
+
+            // 节能界面下转换期间日志收起，展开才排版
+            if self.budget.log_open(*self.running.lock().unwrap()) {
+                let log = self.log_text.lock().unwrap();
+                self.log_search.show(ui, &log, "日志");
+            } else {
+                egui::CollapsingHeader::new("日志（节能界面）").default_open(false).show(ui, |ui| {
+                    let log = self.log_text.lock().unwrap();
+                    self.log_search.show(ui, &log, "日志");
+                });
+            }
+
+            if *self.completed.lock().unwrap() {
+                self.warnings_panel(ui);
+                match self.preview.clone() {
+                    Some(path) => {
+                        ui.horizontal(|ui| {
+                            ui.label("✅ 预览完成");
+                            if let Some(text) = self.sample_estimate.lock().unwrap().as_ref() {
+                                ui.label(text);
+                            }
+                            if ui.button("播放").clicked()
+                                && let Err(e) = process::open_file(&path)
+                            {
+                                self.log_text.lock().unwrap().push_str(&format!("\n无法打开预览: {}\n", e));
+                            }
+                            if ui.button("确认并开始完整转换").clicked() {
+                                let output = self.planned_output();
+                                self.request_start(output);
+                            }
+                        });
+                    }
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.label("✅ 转换完成！");
+                            if self.last_job.as_ref().is_some_and(|(_, out)| *out == self.output)
+                                && ui.button("基于此任务新建").clicked()
+                            {
+                                self.derive_from_last();
+                            }
+                            let has_audio = self.info.as_ref().is_some_and(|i| i.streams.iter().any(|s| s.codec_type == "audio"));
+                            if has_audio
+                                && Path::new(&self.output).is_file()
+                                && ui.button("对比音频").on_hover_text("比较转换前后的响度和波形，并轮流试听同一段").clicked()
+                            {
+                                self.audio_compare = Some(audiocompare::AudioCompare::new(&self.file, &self.output));
+                            }
+                        });
+                    }
+                }
+            }
+        });
+        self.confirm_window(ctx);
+        self.audio_compare_window(ctx);
+
+        match self.budget.repaint_after(*self.running.lock().unwrap()) {
+            Some(interval) => ctx.request_repaint_after(interval),
+            None => ctx.request_repaint(),
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.remove_preview();
+        // 退出时窗口里的选择就是下次打开时的默认值，没开始转换也记下
+        if presets::remembered(&self.settings) {
+            let _ = presets::save_last_used(&self.settings);
+        }
+    }
+}
+
+struct ContextMenuApp {
+    log: String,
+    monitor: monitor::MonitorView,
+    // 切换便携/普通模式后另一处还有数据时，提示复制一次
+    migrate_from: Option<std::path::PathBuf>,
+    storage_message: String,
+    // 正在编辑的临时文件目录
+    scratch_dir: String,
+    // 首次运行或从设置里重新打开时显示向导
+    wizard: Option<onboarding::Wizard>,
+    // 把文件拖进窗口后换成转换界面，没有右键菜单的系统也能用
+    converter: Option<FFUIApp>,
+    // 文件夹响度报告，打开后代替设置界面，点返回关闭
+    survey: Option<loudscan::SurveyApp>,
+    // 右键菜单在各个位置的注册状态，添加、移除、修复后重新读
+    #[cfg(target_os = "windows")]
+    menu_status: Vec<(winctx::Scope, Result<winctx::Status, String>)>,
+}
+
+impl ContextMenuApp {
+    #[cfg(target_os = "windows")]
+    fn refresh_menu_status(&mut self) {
+        let path = winctx::get_app_path();
+        self.menu_status = winctx::query_status(&winctx::WinRegistry, &path.to_string_lossy())
+            .into_iter()
+            .map(|(scope, status)| (scope, status.map_err(|e| e.to_string())))
+            .collect();
+    }
+
+    fn storage_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("数据目录");
+        let mode = if paths::is_portable() { "便携模式" } else { "用户目录" };
+        ui.label(format!("{}: {}", mode, paths::store_dir().display()));
+        if let Some(warning) = paths::warning() {
+            ui.colored_label(egui::Color32::YELLOW, warning);
+        }
+        if let Some(from) = self.migrate_from.clone() {
+            ui.label(format!("{} 里有以前的转换记录和设置，是否复制到当前数据目录？", from.display()));
+            ui.horizontal(|ui| {
+                let choice = if ui.button("复制").clicked() {
+                    Some(true)
+                } else if ui.button("不用了").clicked() {
+                    Some(false)
+                } else {
+                    None
+                };
+                if let Some(copy) = choice {
+                    self.storage_message = match paths::finish_migration(&from, copy) {
+                        Ok(n) if copy => format!("已复制 {} 个文件", n),
+                        Ok(_) => String::new(),
+                        Err(e) => format!("复制失败: {}", e),
+                    };
+                    self.migrate_from = None;
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            let label = ui.label("临时文件目录");
+            ui.add(egui::TextEdit::singleline(&mut self.scratch_dir).hint_text(std::env::temp_dir().to_string_lossy())).labelled_by(label.id);
+            if ui.button("保存").clicked() {
+                let dir = self.scratch_dir.trim().to_string();
+                let mut config = config::current();
+                config.scratch_dir = dir.clone();
+                self.storage_message = match std::fs::create_dir_all(if dir.is_empty() { std::env::temp_dir() } else { dir.into() })
+                    .and_then(|_| config::save(&config))
+                {
+                    Ok(()) => format!("中间文件将放在 {}", tempfiles::root().display()),
+                    Err(e) => format!("无法使用这个目录: {}", e),
+                };
+            }
+        });
+        ui.label("两遍编码的统计、取样片段等中间文件放在这里，换到更快的硬盘能加快转换");
+        if !self.storage_message.is_empty() {
+            ui.label(&self.storage_message);
+        }
+    }
+}
+
+impl App for ContextMenuApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if self.converter.is_none() {
+            let dropped = FFUIApp::dropped_files(ctx);
+            if !dropped.is_empty() {
+                let mut app = FFUIApp::new(String::new());
+                app.open_files(dropped);
+                self.converter = Some(app);
+                frame.set_window_title("FFUI");
+            }
+        }
+        if let Some(app) = &mut self.converter {
+            app.update(ctx, frame);
+            return;
+        }
+        if let Some(survey) = &mut self.survey {
+            let mut close = false;
+            egui::CentralPanel::default().show(ctx, |ui| {
+                close = ui.button("← 返回").clicked();
+                survey.show(ui);
+            });
+            if close {
+                self.survey = None;
+            }
+            ctx.request_repaint();
+            return;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(e) = config_banner(ui) {
+                self.log = format!("❌ {}", e);
+            }
+            if let Some(wizard) = &mut self.wizard {
+                if wizard.show(ui) {
+                    self.wizard = None;
+                    self.log = "✅ 首次设置完成".to_string();
+                    #[cfg(target_os = "windows")]
+                    self.refresh_menu_status();
+                }
+                return;
+            }
+            if ui.button("重新运行首次设置向导").clicked() {
+                self.wizard = Some(onboarding::Wizard::new());
+            }
+            ui.label("把视频文件拖到这个窗口里即可开始转换");
+            if ui.button("文件夹响度报告").on_hover_text("测量整个文件夹的响度，不转换").clicked() {
+                self.survey = Some(loudscan::SurveyApp::new(String::new()));
+            }
+            ui.separator();
+            ui.heading("右键菜单");
+
+            #[cfg(target_os = "windows")]
+            {
+                let path = winctx::get_app_path();
+                let mut repair = None;
+                for (scope, status) in &self.menu_status {
+                    ui.horizontal(|ui| {
+                        match status {
+                            Ok(status) => ui.label(format!("{}: {}", scope.label(), status.label())),
+                            Err(e) => ui.colored_label(egui::Color32::YELLOW, format!("{}: 无法读取（{}）", scope.label(), e)),
+                        };
+                        if matches!(status, Ok(winctx::Status::Stale(_))) && ui.button("修复（更新为当前路径）").clicked() {
+                            repair = Some(*scope);
+                        }
+                    });
+                }
+                if let Some(scope) = repair {
+                    self.log = match winctx::repair(&winctx::WinRegistry, scope, &path.to_string_lossy()) {
+                        Ok(()) => format!("✅ 已把{}的右键菜单更新为 {}", scope.label(), path.display()),
+                        Err(e) => format!("❌ 修复失败: {}", e),
+                    };
+                    self.refresh_menu_status();
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("添加到右键菜单").clicked() {
+                        match winctx::add_context_menu(&path.to_string_lossy()) {
+                            Ok(_) => self.log = "✅ 完成".to_string(),
+                            Err(e) => self.log = format!("❌ 失败: {}", e),
+                        }
+                        self.refresh_menu_status();
+                    }
+                    if ui.button("从右键菜单移除").clicked() {
+                        match winctx::remove(&winctx::WinRegistry) {
+                            Ok(removed) if removed.is_empty() => self.log = "没有找到已注册的右键菜单".to_string(),
+                            Ok(removed) => self.log = format!("✅ 已删除:\n{}", removed.join("\n")),
+                            Err(e) => self.log = format!("❌ 失败: {}", e),
+                        }
+                        self.refresh_menu_status();
+                    }
+                    if ui.button("重新检查").clicked() {
+                        self.refresh_menu_status();
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.label(&self.log);
+
+            ui.separator();
+            self.storage_panel(ui);
+
+            ui.separator();
+            ui.heading("正在运行的转换");
+            self.monitor.show(ui);
+        });
+
+        ctx.request_repaint();
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let native_options = eframe::NativeOptions::default();
+
+    // 清理异常退出留下的临时目录，不耽误启动
+    thread::spawn(|| tempfiles::sweep(&tempfiles::root(), tempfiles::ORPHAN_AGE));
+
+    let mode = cli::parse(&args[1..]);
+    let share = matches!(mode, cli::Mode::Share(_));
+    let input_format = match &mode {
+        cli::Mode::Convert(_, format) => format.clone(),
+        _ => String::new(),
+    };
+    match mode {
+        cli::Mode::Exit(code) => std::process::exit(code),
+        cli::Mode::PrintCmd => {
+            cli::attach_console();
+            std::process::exit(cli::print_cmd(&args[1..]));
+        }
+        cli::Mode::Queue(list) => {
+            cli::attach_console();
+            std::process::exit(joblist::run_headless(&list));
+        }
+        cli::Mode::SelfTest => {
+            cli::attach_console();
+            std::process::exit(selftest::run());
+        }
+        cli::Mode::Diagnose(id) => {
+            cli::attach_console();
+            std::process::exit(diagnose::run_headless(&id));
+        }
+        cli::Mode::Inspect(file) => {
+            // 右键“查看媒体信息”
+            let app = inspect::InspectApp::new(file);
+            eframe::run_native(
+                "FFUI 媒体信息",
+                native_options,
+                Box::new(|cc| {
+                    setup_fonts(&cc.egui_ctx);
+                    config::current().theme.apply(&cc.egui_ctx);
+                    Box::new(app)
+                }),
+            )
+        }
+        cli::Mode::Loudness(folder) => {
+            let app = loudscan::SurveyApp::new(folder);
+            eframe::run_native(
+                "FFUI 响度报告",
+                native_options,
+                Box::new(|cc| {
+                    setup_fonts(&cc.egui_ctx);
+                    config::current().theme.apply(&cc.egui_ctx);
+                    Box::new(app)
+                }),
+            )
+        }
+        cli::Mode::Convert(files, _) => {
+            // 正常进入转码器；传入多个文件时先显示第一个，队列里依次转换
+            let mut app = FFUIApp::new(files[0].clone());
+            app.auto_apply_preset();
+            if files.len() > 1 {
+                app.batch = files.iter().map(|f| app.batch_item(f.clone())).collect();
+            }
+            app.settings.input_format = input_format;
+            if files.len() == 1 {
+                app.resume_session();
+            }
+
+            eframe::run_native(
+                "FFUI",
+                native_options,
+                Box::new(|cc| {
+                    setup_fonts(&cc.egui_ctx);
+                    config::current().theme.apply(&cc.egui_ctx);
+                    Box::new(app)
+                }),
+            )
+        }
+        cli::Mode::Share(file) => {
+            // “转成可发送的视频”只显示一键方案
+            let mut app = FFUIApp::new(file);
+            app.share_mode = share;
+
+            eframe::run_native(
+                "FFUI",
+                native_options,
+                Box::new(|cc| {
+                    setup_fonts(&cc.egui_ctx);
+                    config::current().theme.apply(&cc.egui_ctx);
+                    Box::new(app)
+                }),
+            )
+        }
+        cli::Mode::Setup => {
+            // 无参数时打开右键菜单管理界面
+            #[allow(unused_mut)]
+            let mut app = ContextMenuApp {
+                log: "将本程序添加到Windows右键菜单".to_string(),
+                monitor: monitor::MonitorView::default(),
+                migrate_from: paths::pending_migration(),
+                storage_message: String::new(),
+                scratch_dir: config::current().scratch_dir,
+                wizard: (!config::current().onboarded).then(onboarding::Wizard::new),
+                converter: None,
+                survey: None,
+                #[cfg(target_os = "windows")]
+                menu_status: Vec::new(),
+            };
+            #[cfg(target_os = "windows")]
+            app.refresh_menu_status();
+            eframe::run_native(
+                "FFUI 右键菜单设置",
+                native_options,
+                Box::new(|cc| {
+                    setup_fonts(&cc.egui_ctx);
+                    config::current().theme.apply(&cc.egui_ctx);
+                    Box::new(app)
+                }),
+            )
+        }
     }
 }
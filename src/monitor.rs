@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use eframe::egui;
+
+use crate::a11y;
+use crate::cancel::CancelToken;
+use crate::paths;
+use crate::latest::Latest;
+use crate::logbuf::LogBuffer;
+use crate::runner;
+
+// 每个正在转换的 ffui 把状态写到共享目录，其他实例读取来旁观，
+// 放一个 .cancel 文件请求取消。首行带协议版本，版本不同的实例只显示提示
+const PROTOCOL: u32 = 1;
+const PUBLISH_EVERY: Duration = Duration::from_secs(1);
+// 超过这么久没更新的状态文件视为进程已经退出
+const STALE_AFTER: Duration = Duration::from_secs(15);
+const LOG_LINES: usize = 200;
+
+fn status_dir() -> PathBuf {
+    paths::store_dir().join("running")
+}
+
+fn status_path(pid: u32) -> PathBuf {
+    status_dir().join(format!("{}.status", pid))
+}
+
+fn cancel_path(pid: u32) -> PathBuf {
+    status_dir().join(format!("{}.cancel", pid))
+}
+
+// 任务运行期间定期发布进度和日志尾部，收到取消请求时取消整个任务
+pub fn publish(
+    input: String,
+    progress: Arc<Latest<runner::Progress>>,
+    running: Arc<Mutex<bool>>,
+    log_text: Arc<Mutex<LogBuffer>>,
+    cancel: CancelToken,
+) {
+    let pid = std::process::id();
+    let _ = fs::create_dir_all(status_dir());
+    let _ = fs::remove_file(cancel_path(pid));
+    thread::spawn(move || {
+        while *running.lock().unwrap() {
+            let tail = {
+                let mut log = log_text.lock().unwrap();
+                log.merge();
+                let lines: Vec<&str> = log.lines().collect();
+                lines[lines.len().saturating_sub(LOG_LINES)..].join("\n")
+            };
+            let status = format!(
+                "ffui-status {}\ninput={}\nprogress={:.1}\n\n{}",
+                PROTOCOL, input.replace('\n', " "), progress.load().progress, tail
+            );
+            // 先写临时文件再改名，读的一方不会看到写了一半的内容
+            let tmp = status_dir().join(format!("{}.tmp", pid));
+            if fs::write(&tmp, status).is_ok() {
+                let _ = fs::rename(&tmp, status_path(pid));
+            }
+            if cancel_path(pid).exists() {
+                let _ = fs::remove_file(cancel_path(pid));
+                cancel.cancel();
+                log_text.lock().unwrap().push_str("\n=== 收到其他 ffui 窗口的取消请求 ===\n");
+            }
+            thread::sleep(PUBLISH_EVERY);
+        }
+        let _ = fs::remove_file(status_path(pid));
+    });
+}
+
+pub struct Remote {
+    pub pid: u32,
+    pub input: String,
+    pub progress: f32,
+    pub log: String,
+    // 协议版本不兼容时为 Some(对方版本)
+    pub incompatible: Option<String>,
+}
+
+fn parse_status(pid: u32, text: &str) -> Remote {
+    let (head, log) = text.split_once("\n\n").unwrap_or((text, ""));
+    let mut lines = head.lines();
+    let mut remote = Remote { pid, input: String::new(), progress: 0.0, log: log.to_string(), incompatible: None };
+    match lines.next().and_then(|l| l.strip_prefix("ffui-status ")) {
+        Some(v) if v.trim() == PROTOCOL.to_string() => {}
+        other => {
+            remote.incompatible = Some(other.unwrap_or("?").trim().to_string());
+            return remote;
+        }
+    }
+    for line in lines {
+        if let Some(v) = line.strip_prefix("input=") {
+            remote.input = v.to_string();
+        } else if let Some(v) = line.strip_prefix("progress=") {
+            remote.progress = v.parse().unwrap_or(0.0);
+        }
+    }
+    remote
+}
+
+// 除自己以外仍在更新的实例
+pub fn list() -> Vec<Remote> {
+    let own = std::process::id();
+    let Ok(entries) = fs::read_dir(status_dir()) else { return Vec::new() };
+    let mut remotes = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("status") {
+            continue;
+        }
+        let Some(pid) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<u32>().ok()) else { continue };
+        if pid == own {
+            continue;
+        }
+        let age = entry.metadata().and_then(|m| m.modified()).ok().and_then(|t| SystemTime::now().duration_since(t).ok());
+        if age.is_none_or(|a| a > STALE_AFTER) {
+            // 异常退出的实例留下的文件
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+        if let Ok(text) = fs::read_to_string(&path) {
+            remotes.push(parse_status(pid, &text));
+        }
+    }
+    remotes.sort_by_key(|r| r.pid);
+    remotes
+}
+
+pub fn request_cancel(pid: u32) -> std::io::Result<()> {
+    fs::write(cancel_path(pid), "")
+}
+
+// 只读的旁观面板，每秒刷新一次列表
+#[derive(Default)]
+pub struct MonitorView {
+    remotes: Vec<Remote>,
+    refreshed: Option<Instant>,
+    cancelled: Vec<u32>,
+}
+
+impl MonitorView {
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if self.refreshed.is_none_or(|t| t.elapsed() >= PUBLISH_EVERY) {
+            self.remotes = list();
+            self.refreshed = Some(Instant::now());
+            let alive: Vec<u32> = self.remotes.iter().map(|r| r.pid).collect();
+            self.cancelled.retain(|pid| alive.contains(pid));
+        }
+        if self.remotes.is_empty() {
+            ui.label("没有其他正在转换的 ffui");
+            return;
+        }
+        for remote in &self.remotes {
+            ui.push_id(remote.pid, |ui| {
+                if let Some(version) = &remote.incompatible {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!("进程 {} 使用不同版本的 ffui（协议 {}），无法查看", remote.pid, version),
+                    );
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.label(format!("进程 {}: {}", remote.pid, remote.input));
+                    if self.cancelled.contains(&remote.pid) {
+                        ui.label("已请求取消…");
+                    } else if a11y::named(ui.button("取消"), &format!("取消进程 {} 的转换", remote.pid)).clicked()
+                        && request_cancel(remote.pid).is_ok()
+                    {
+                        self.cancelled.push(remote.pid);
+                    }
+                });
+                let bar = ui.add(egui::ProgressBar::new(remote.progress / 100.0).show_percentage());
+                a11y::progress(bar, remote.progress / 100.0, &format!("进程 {} 的转换进度", remote.pid));
+                egui::CollapsingHeader::new("日志").show(ui, |ui| {
+                    egui::ScrollArea::vertical().max_height(200.0).stick_to_bottom(true).show(ui, |ui| {
+                        a11y::log_view(ui, &remote.log, &format!("进程 {} 的日志", remote.pid));
+                    });
+                });
+            });
+            ui.separator();
+        }
+    }
+}
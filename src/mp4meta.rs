@@ -0,0 +1,142 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// 给 m4a 加 iTunes 的自定义标签（"----" 原子：mean + name + data），如无缝播放用的 iTunSMPB。
+// ffmpeg 的 mp4 封装只写它认识的标签；自定义标签要加 -movflags +use_metadata_tags，
+// 那样所有标签都改成 QuickTime 的 mdta 格式，iTunes 和多数播放器就读不到标题、音轨号了，
+// 所以转换完直接改文件：在 moov/udta/meta/ilst 末尾插入，并把沿途各层原子的大小改对
+
+struct Atom {
+    start: usize,
+    // 子原子（或内容）开始的位置
+    body: usize,
+    end: usize,
+    // 大小用的是 64 位的扩展字段
+    large: bool,
+    kind: [u8; 4],
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn u64_at(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+// from..to 之间依次排列的原子
+fn atoms(data: &[u8], from: usize, to: usize) -> io::Result<Vec<Atom>> {
+    let mut list = Vec::new();
+    let mut at = from;
+    while at + 8 <= to {
+        let size = u32_at(data, at) as usize;
+        let kind = [data[at + 4], data[at + 5], data[at + 6], data[at + 7]];
+        let (header, size, large) = match size {
+            // 大小为 0 表示一直到文件（或上一层）结尾
+            0 => (8, to - at, false),
+            1 if at + 16 <= to => (16, u64_at(data, at + 8) as usize, true),
+            _ => (8, size, false),
+        };
+        if size < header || at + size > to {
+            return Err(invalid("mp4 原子的大小不对"));
+        }
+        list.push(Atom { start: at, body: at + header, end: at + size, large, kind });
+        at += size;
+    }
+    Ok(list)
+}
+
+fn child(data: &[u8], parent: &Atom, kind: &[u8; 4]) -> io::Result<Option<Atom>> {
+    // meta 是 full box，子原子前面还有 4 字节的版本和标志
+    let from = if &parent.kind == b"meta" { parent.body + 4 } else { parent.body };
+    Ok(atoms(data, from, parent.end)?.into_iter().find(|a| &a.kind == kind))
+}
+
+fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    out.extend(kind);
+    out.extend(body);
+    out
+}
+
+// "----" 原子：mean 是命名空间，name 是标签名，data 的类型 1 表示 UTF-8 文本
+fn freeform(name: &str, value: &str) -> Vec<u8> {
+    let full = |text: &str| [&[0u8; 4][..], text.as_bytes()].concat();
+    let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+    data.extend(value.as_bytes());
+    let body = [atom(b"mean", &full("com.apple.iTunes")), atom(b"name", &full(name)), atom(b"data", &data)].concat();
+    atom(b"----", &body)
+}
+
+// moov 在 mdat 前面（faststart）时，moov 变长会把音频数据往后推，
+// 每条轨道 stco/co64 里记的数据块位置都要加上 delta
+fn shift_chunk_offsets(data: &mut [u8], moov: &Atom, delta: u64) -> io::Result<()> {
+    let mut tables = Vec::new();
+    for trak in atoms(data, moov.body, moov.end)?.iter().filter(|a| &a.kind == b"trak") {
+        let Some(mdia) = child(data, trak, b"mdia")? else { continue };
+        let Some(minf) = child(data, &mdia, b"minf")? else { continue };
+        let Some(stbl) = child(data, &minf, b"stbl")? else { continue };
+        tables.extend(atoms(data, stbl.body, stbl.end)?.into_iter().filter(|a| &a.kind == b"stco" || &a.kind == b"co64"));
+    }
+    for table in tables {
+        let count = u32_at(data, table.body + 4) as usize;
+        let width = if &table.kind == b"co64" { 8 } else { 4 };
+        let first = table.body + 8;
+        if first + count * width > table.end {
+            return Err(invalid("mp4 的数据块位置表不完整"));
+        }
+        for i in 0..count {
+            let at = first + i * width;
+            if width == 8 {
+                let value = u64_at(data, at) + delta;
+                data[at..at + 8].copy_from_slice(&value.to_be_bytes());
+            } else {
+                let value = u32::try_from(u32_at(data, at) as u64 + delta).map_err(|_| invalid("文件太大，数据块位置超出 32 位"))?;
+                data[at..at + 4].copy_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+// 把一个自定义文本标签加到 m4a 里。文件先写到旁边的临时文件，成功后再替换
+pub fn add_freeform(path: &Path, name: &str, value: &str) -> io::Result<()> {
+    let mut data = fs::read(path)?;
+    let top = atoms(&data, 0, data.len())?;
+    let moov = top.iter().find(|a| &a.kind == b"moov").ok_or_else(|| invalid("不是 mp4 文件（没有 moov）"))?;
+    // ffmpeg 总会写编码器名 ©too，所以正常的输出都有 ilst
+    let udta = child(&data, moov, b"udta")?.ok_or_else(|| invalid("mp4 里没有标签区（udta）"))?;
+    let meta = child(&data, &udta, b"meta")?.ok_or_else(|| invalid("mp4 里没有标签区（meta）"))?;
+    let ilst = child(&data, &meta, b"ilst")?.ok_or_else(|| invalid("mp4 里没有标签区（ilst）"))?;
+
+    let added = freeform(name, value);
+    let delta = added.len() as u64;
+    if top.iter().any(|a| &a.kind == b"mdat" && a.start > moov.start) {
+        shift_chunk_offsets(&mut data, moov, delta)?;
+    }
+    for parent in [moov, &udta, &meta, &ilst] {
+        if parent.large {
+            let size = u64_at(&data, parent.start + 8) + delta;
+            data[parent.start + 8..parent.start + 16].copy_from_slice(&size.to_be_bytes());
+        } else {
+            let size = u32::try_from(u32_at(&data, parent.start) as u64 + delta).map_err(|_| invalid("mp4 原子超出 32 位大小"))?;
+            data[parent.start..parent.start + 4].copy_from_slice(&size.to_be_bytes());
+        }
+    }
+    data.splice(ilst.end..ilst.end, added);
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".ffui_tag_{}", file_name));
+    if let Err(e) = fs::write(&tmp, &data).and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::process::{Child, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::paths;
+use crate::process;
+
+// NVENC 能同时打开的编码会话数。消费级 N 卡的驱动有限制（早期 3 路，后来放宽到 5 路、8 路），
+// 超出的那一路会直接初始化失败；专业卡不限。
+// 先按显卡名称和驱动版本查表给个估计，再在后台实测：一路一路地加开测试编码，
+// 新开的打不开就说明到了上限。实测结果按 显卡 + 驱动版本 缓存，驱动更新后重测
+
+// 还没有结果时按最保守的 3 路算
+const DEFAULT: usize = 3;
+// 最多试到这么多路，都打得开就当作不限
+const PROBE_MAX: usize = 8;
+// 每开一路后等待的时间：打不开会话的编码一两秒内就会退出
+const SETTLE: Duration = Duration::from_secs(2);
+// 测试编码按实时速度跑，-t 只是万一没被结束时的保险
+const PROBE_ARGS: [&str; 14] = [
+    "-hide_banner", "-re", "-f", "lavfi", "-i", "testsrc2=size=640x360:rate=30", "-t", "60",
+    "-c:v", "h264_nvenc", "-an", "-f", "null", "-",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Limit {
+    // 按显卡名称和驱动版本估计，还在实测或实测不成
+    Table(usize),
+    Measured(usize),
+}
+
+impl Limit {
+    pub fn sessions(self) -> usize {
+        match self {
+            Limit::Table(n) | Limit::Measured(n) => n,
+        }
+    }
+
+    pub fn label(self) -> String {
+        let count = match self.sessions() {
+            usize::MAX => "不限".to_string(),
+            n => format!("{} 路", n),
+        };
+        match self {
+            Limit::Table(_) => format!("{}（按驱动版本估计）", count),
+            Limit::Measured(_) => format!("{}（实测）", count),
+        }
+    }
+}
+
+fn state() -> &'static Mutex<Option<Limit>> {
+    static STATE: Mutex<Option<Limit>> = Mutex::new(None);
+    &STATE
+}
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// 界面和计划每帧都会读，只看内存里的结果
+pub fn current() -> Option<Limit> {
+    *state().lock().unwrap()
+}
+
+pub fn sessions() -> usize {
+    current().map_or(DEFAULT, Limit::sessions)
+}
+
+// 显卡名称和驱动版本，第一块 N 卡
+fn gpu_identity() -> Option<(String, String)> {
+    let output = process::command("nvidia-smi").args(["--query-gpu=name,driver_version", "--format=csv,noheader"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (name, driver) = text.lines().next()?.split_once(',')?;
+    Some((name.trim().to_string(), driver.trim().to_string()))
+}
+
+// 专业卡不限；GeForce 按驱动主版本：530 起 5 路，551 起 8 路
+pub fn from_table(name: &str, driver: &str) -> usize {
+    if ["Quadro", "Tesla", "RTX A", "RTX PRO"].iter().any(|p| name.contains(p)) {
+        return usize::MAX;
+    }
+    match driver.split('.').next().and_then(|m| m.parse::<u32>().ok()) {
+        Some(major) if major >= 551 => 8,
+        Some(major) if major >= 530 => 5,
+        _ => DEFAULT,
+    }
+}
+
+fn stop(children: Vec<Child>) {
+    for mut child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+// 逐路加开测试编码，直到有一路提前退出。第一路就失败说明 NVENC 本身不能用，返回 None
+pub fn probe() -> Option<usize> {
+    let mut running: Vec<Child> = Vec::new();
+    for opened in 0..PROBE_MAX {
+        let child = process::command("ffmpeg").args(PROBE_ARGS).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+        match child {
+            Ok(child) => running.push(child),
+            Err(_) => {
+                stop(running);
+                return None;
+            }
+        }
+        thread::sleep(SETTLE);
+        if running.iter_mut().any(|c| !matches!(c.try_wait(), Ok(None))) {
+            stop(running);
+            return (opened > 0).then_some(opened);
+        }
+    }
+    stop(running);
+    Some(usize::MAX)
+}
+
+fn cache_path() -> std::path::PathBuf {
+    paths::store_dir().join("nvenc_sessions.tsv")
+}
+
+// 显卡 \t 驱动版本 \t 路数（max 表示不限）
+fn load_cached(name: &str, driver: &str) -> Option<usize> {
+    let text = fs::read_to_string(cache_path()).ok()?;
+    let f: Vec<&str> = text.lines().next()?.split('\t').collect();
+    if f.len() != 3 || f[0] != name || f[1] != driver {
+        return None;
+    }
+    match f[2] {
+        "max" => Some(usize::MAX),
+        n => n.parse().ok(),
+    }
+}
+
+fn save_cached(name: &str, driver: &str, limit: usize) {
+    let count = if limit == usize::MAX { "max".to_string() } else { limit.to_string() };
+    if fs::create_dir_all(paths::store_dir()).is_ok() {
+        let _ = fs::write(cache_path(), format!("{}\t{}\t{}\n", name, driver, count));
+    }
+}
+
+// 查缓存，没有就先填表里的估计再实测。会阻塞十几秒，界面里用 ensure
+pub fn detect() {
+    let Some((name, driver)) = gpu_identity() else {
+        return;
+    };
+    if let Some(limit) = load_cached(&name, &driver) {
+        *state().lock().unwrap() = Some(Limit::Measured(limit));
+        return;
+    }
+    *state().lock().unwrap() = Some(Limit::Table(from_table(&name, &driver)));
+    if let Some(limit) = probe() {
+        save_cached(&name, &driver, limit);
+        *state().lock().unwrap() = Some(Limit::Measured(limit));
+    }
+}
+
+// 每次运行只在后台检测一次。实测要占用 NVENC，不能在转换进行中调用
+pub fn ensure() {
+    if !STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(detect);
+    }
+}
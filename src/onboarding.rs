@@ -0,0 +1,251 @@
+use std::fs;
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::a11y;
+use crate::config::{self, Config, Theme};
+use crate::plan;
+use crate::process;
+
+// 首次运行向导：ffmpeg 位置 → 外观 → 默认输出 → 右键菜单。
+// 每一步检查通过才能进入下一步，走完才写入设置；之后可以从设置界面重新运行
+const DOWNLOAD_PAGE: &str = "https://ffmpeg.org/download.html";
+const STEPS: [&str; 4] = ["找到 ffmpeg", "外观", "默认输出", "右键菜单"];
+
+pub struct Wizard {
+    step: usize,
+    draft: Config,
+    // ffmpeg 检测结果：Ok(版本行) 或 Err(原因)
+    ffmpeg: Option<Result<String, String>>,
+    fixed_dir: bool,
+    register_menu: bool,
+    message: String,
+}
+
+impl Wizard {
+    pub fn new() -> Self {
+        let draft = config::current();
+        Wizard {
+            step: 0,
+            fixed_dir: !draft.output_dir.is_empty(),
+            ffmpeg: Some(check_ffmpeg(&draft.ffmpeg_dir)),
+            draft,
+            register_menu: cfg!(target_os = "windows"),
+            message: String::new(),
+        }
+    }
+
+    // 走完返回 true
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.heading(format!("首次设置 {}/{}：{}", self.step + 1, STEPS.len(), STEPS[self.step]));
+        ui.separator();
+        let ready = match self.step {
+            0 => self.ffmpeg_step(ui),
+            1 => self.theme_step(ui),
+            2 => self.output_step(ui),
+            _ => self.menu_step(ui),
+        };
+        if !self.message.is_empty() {
+            ui.colored_label(egui::Color32::YELLOW, &self.message);
+        }
+        ui.separator();
+        let mut finished = false;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.step > 0, egui::Button::new("上一步")).clicked() {
+                self.step -= 1;
+                self.message.clear();
+            }
+            let last = self.step + 1 == STEPS.len();
+            if ui.add_enabled(ready, egui::Button::new(if last { "完成" } else { "下一步" })).clicked() {
+                match self.leave_step() {
+                    Ok(()) if last => finished = self.finish(),
+                    Ok(()) => {
+                        self.step += 1;
+                        self.message.clear();
+                    }
+                    Err(e) => self.message = e,
+                }
+            }
+        });
+        finished
+    }
+
+    fn ffmpeg_step(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.label("ffui 调用 ffmpeg 和 ffprobe 完成转换。指定它们所在的文件夹，或留空使用 PATH 里或 ffui 旁边的版本。");
+        ui.horizontal(|ui| {
+            let label = ui.label("ffmpeg 目录");
+            if ui.add(egui::TextEdit::singleline(&mut self.draft.ffmpeg_dir).hint_text("留空自动查找")).labelled_by(label.id).changed() {
+                self.ffmpeg = None;
+            }
+        });
+        if self.ffmpeg.is_none() && ui.button("检测").clicked() {
+            self.ffmpeg = Some(check_ffmpeg(&self.draft.ffmpeg_dir));
+        }
+        match &self.ffmpeg {
+            Some(Ok(version)) => { ui.colored_label(egui::Color32::GREEN, format!("✓ {}", version)); }
+            Some(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, format!("✗ {}", e));
+                ui.horizontal(|ui| {
+                    ui.label("还没有 ffmpeg？");
+                    if ui.button("打开下载页面").clicked()
+                        && let Err(e) = process::open_file(DOWNLOAD_PAGE)
+                    {
+                        self.message = format!("无法打开浏览器: {}，请手动访问 {}", e, DOWNLOAD_PAGE);
+                    }
+                    if ui.button("重新检测").clicked() {
+                        self.ffmpeg = Some(check_ffmpeg(&self.draft.ffmpeg_dir));
+                    }
+                });
+            }
+            None => {}
+        }
+        matches!(self.ffmpeg, Some(Ok(_)))
+    }
+
+    fn theme_step(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.label("界面语言: 简体中文（目前只有这一种）");
+        ui.horizontal(|ui| {
+            ui.label("主题");
+            for theme in Theme::ALL {
+                if ui.radio_value(&mut self.draft.theme, theme, theme.label()).changed() {
+                    theme.apply(ui.ctx());
+                }
+            }
+        });
+        true
+    }
+
+    fn output_step(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.radio_value(&mut self.fixed_dir, false, "输出和源文件放在同一文件夹");
+        ui.horizontal(|ui| {
+            let label = ui.radio_value(&mut self.fixed_dir, true, "统一输出到");
+            ui.add_enabled(self.fixed_dir, egui::TextEdit::singleline(&mut self.draft.output_dir)).labelled_by(label.id);
+        });
+        // 新任务默认的格式和设备；上次用过的设置（last-used.toml）和套用的预设会覆盖它们
+        let format = egui::ComboBox::from_label("默认格式")
+            .selected_text(&self.draft.format)
+            .show_ui(ui, |ui| {
+                for fmt in plan::FORMATS {
+                    ui.selectable_value(&mut self.draft.format, fmt.to_string(), *fmt);
+                }
+            });
+        a11y::selected(format.response, &self.draft.format);
+        let gpu = egui::ComboBox::from_label("默认处理设备")
+            .selected_text(&self.draft.gpu)
+            .show_ui(ui, |ui| {
+                for gpu in ["CPU", "NVIDIA", "Intel", "AMD"] {
+                    ui.selectable_value(&mut self.draft.gpu, gpu.to_string(), gpu);
+                }
+            });
+        a11y::selected(gpu.response, &self.draft.gpu);
+        !self.fixed_dir || !self.draft.output_dir.trim().is_empty()
+    }
+
+    fn menu_step(&mut self, ui: &mut egui::Ui) -> bool {
+        if cfg!(target_os = "windows") {
+            ui.checkbox(&mut self.register_menu, "添加到右键菜单（使用 FFmpeg 转换 / 查看媒体信息 / 转成可发送的视频）");
+        } else {
+            ui.label("右键菜单只支持 Windows，可以直接用命令行 ffui <文件> 打开");
+        }
+        true
+    }
+
+    // 离开当前步骤前的检查
+    fn leave_step(&mut self) -> Result<(), String> {
+        if self.step == 2 {
+            if self.fixed_dir {
+                let dir = self.draft.output_dir.trim().to_string();
+                fs::create_dir_all(&dir).map_err(|e| format!("无法创建 {}: {}", dir, e))?;
+                self.draft.output_dir = dir;
+            } else {
+                self.draft.output_dir.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> bool {
+        self.draft.onboarded = true;
+        if let Err(e) = config::save(&self.draft) {
+            self.message = format!("无法保存设置: {}", e);
+            return false;
+        }
+        if self.register_menu
+            && let Err(e) = register_menu()
+        {
+            self.message = format!("设置已保存，但无法添加右键菜单: {}", e);
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn register_menu() -> std::io::Result<()> {
+    let path = crate::winctx::get_app_path();
+    crate::winctx::add_context_menu(&path.to_string_lossy())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn register_menu() -> std::io::Result<()> {
+    Ok(())
+}
+
+// 两个程序都要能运行，返回 ffmpeg -version 的第一行
+fn check_ffmpeg(dir: &str) -> Result<String, String> {
+    let mut version = String::new();
+    for name in ["ffmpeg", "ffprobe"] {
+        let program = config::tool_in(dir, name);
+        // 还没保存的目录，不能经过 process::command 的路径替换
+        match process::output_with_timeout(process::user_command(&program).arg("-version"), Duration::from_secs(10)) {
+            Ok((Some(status), stdout, _)) if status.success() => {
+                if name == "ffmpeg" {
+                    version = stdout.lines().next().unwrap_or("").trim().to_string();
+                }
+            }
+            Ok(_) => return Err(format!("{} 无法正常运行", program.display())),
+            Err(e) => return Err(format!("找不到 {}（{}）", program.display(), e)),
+        }
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wizard(step: usize, fixed_dir: bool, output_dir: &str) -> Wizard {
+        let draft = Config { output_dir: output_dir.to_string(), ..Config::default() };
+        Wizard { step, draft, ffmpeg: None, fixed_dir, register_menu: false, message: String::new() }
+    }
+
+    #[test]
+    fn output_step_creates_the_fixed_dir() {
+        let dir = std::env::temp_dir().join(format!("ffui_onboarding_test_{}", std::process::id())).join("输出");
+        let _ = fs::remove_dir_all(&dir);
+        let mut w = wizard(2, true, &format!("  {}  ", dir.display()));
+        w.leave_step().unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(w.draft.output_dir, dir.to_string_lossy());
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn output_next_to_the_source_clears_the_dir() {
+        let mut w = wizard(2, false, "/somewhere");
+        w.leave_step().unwrap();
+        assert!(w.draft.output_dir.is_empty());
+        // 其他步骤离开时不检查输出目录
+        let mut w = wizard(1, true, "");
+        w.leave_step().unwrap();
+        assert!(w.draft.output_dir.is_empty());
+    }
+
+    #[test]
+    fn missing_ffmpeg_is_reported() {
+        let dir = std::env::temp_dir().join(format!("ffui_onboarding_test_missing_{}", std::process::id()));
+        let err = check_ffmpeg(&dir.to_string_lossy()).unwrap_err();
+        assert!(err.starts_with("找不到") && err.contains("ffmpeg"), "{}", err);
+    }
+}
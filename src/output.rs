@@ -0,0 +1,309 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::history;
+use crate::live;
+use crate::plan::JobSettings;
+use crate::tempfiles;
+use crate::web::Platform;
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
+
+// 设置了固定输出目录时放到那里，否则和源文件放在一起
+fn place(path: String) -> String {
+    let dir = config::current().output_dir;
+    match Path::new(&path).file_name() {
+        Some(name) if !dir.is_empty() => Path::new(&dir).join(name).to_string_lossy().into_owned(),
+        _ => path,
+    }
+}
+
+// 实时输入没有文件名，输出放在当前目录（或固定输出目录），按开始时间命名
+fn base(input: &str) -> String {
+    if live::is_live(input) { live::output_base(input, history::now()) } else { input.to_string() }
+}
+
+// clip.mov -> clip.mp4。实时输入的名字不是文件名，整个保留
+fn renamed(input: &str, format: &str) -> String {
+    if live::is_live(input) {
+        return format!("{}.{}", base(input), format);
+    }
+    let path = Path::new(input);
+    match path.file_stem() {
+        Some(stem) => path.with_file_name(format!("{}.{}", stem.to_string_lossy(), format)).to_string_lossy().into_owned(),
+        None => format!("{}.{}", input, format),
+    }
+}
+
+// 同格式转换时 clip.mp4 -> clip (1).mp4，不能和源文件同名
+fn not_input(input: &str, output: String) -> String {
+    if same_file(input, &output) { unique_path(Path::new(&output)).to_string_lossy().into_owned() } else { output }
+}
+
+// 源文件所在目录（或固定输出目录）下换掉扩展名
+pub fn default_output(input: &str, format: &str) -> String {
+    not_input(input, place(renamed(input, format)))
+}
+
+// 界面里输出文件框的默认值：dir 不为空时放到 dir 下
+pub fn suggested_output(input: &str, format: &str, dir: &str) -> String {
+    if dir.trim().is_empty() {
+        return default_output(input, format);
+    }
+    let renamed = renamed(input, format);
+    let name = Path::new(&renamed).file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    not_input(input, Path::new(dir.trim()).join(name).to_string_lossy().into_owned())
+}
+
+// 给定文件名（不含扩展名）的输出，放在源文件旁边（或固定输出目录），dir 不为空时放到 dir 下
+pub fn named_output(input: &str, name: &str, format: &str, dir: &str) -> String {
+    let file = format!("{}.{}", name, format);
+    let path = if dir.trim().is_empty() {
+        place(parent_dir(Path::new(input)).join(file).to_string_lossy().into_owned())
+    } else {
+        Path::new(dir.trim()).join(file).to_string_lossy().into_owned()
+    };
+    not_input(input, path)
+}
+
+// clip.mp4 -> clip_rotated.mp4，格式不变
+pub fn tagged_output(input: &str, tag: &str) -> String {
+    let path = Path::new(input);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    place(path.with_file_name(format!("{}_{}{}", stem, tag, ext)).to_string_lossy().into_owned())
+}
+
+// 自动生成的文件名遇到同名文件时另起一个名字，不靠 -y 悄悄覆盖上一次的结果。
+// 增量转换要按原名比较新旧，保留原名
+pub fn avoid_existing(output: String, settings: &JobSettings) -> String {
+    if settings.incremental { output } else { unique_path(Path::new(&output)).to_string_lossy().into_owned() }
+}
+
+// 输出和输入是同一个文件时 ffmpeg 会一边读一边把源文件截断
+pub fn same_file(input: &str, output: &str) -> bool {
+    if live::is_live(input) {
+        return false;
+    }
+    match (fs::canonicalize(input), fs::canonicalize(output)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(input) == Path::new(output),
+    }
+}
+
+// clip.mp4 -> clip.mp4.wechat.mp4
+pub fn web_output(input: &str, platform: Platform) -> String {
+    place(format!("{}.{}.mp4", base(input), platform.tag()))
+}
+
+// 预览文件放在整个进程共用的临时目录，完整转换开始或程序退出时删除
+pub fn preview_path(format: &str) -> String {
+    let name = format!("preview.{}", format);
+    match tempfiles::session() {
+        Some(session) => session.path(&name),
+        None => tempfiles::join(&std::env::temp_dir(), &format!("ffui_{}_{}", std::process::id(), name)),
+    }
+}
+
+// 开始前检查输出位置是否可写：在目录里建一个临时文件再删掉，
+// 已存在的输出文件再尝试以写方式打开（被播放器独占时会失败）
+pub fn check_writable(output: &Path) -> io::Result<()> {
+    // 截图任务的输出是目录，直接在里面试写
+    let dir = if output.is_dir() { output } else { parent_dir(output) };
+    let probe = dir.join(format!(".ffui_probe_{}.tmp", std::process::id()));
+    OpenOptions::new().write(true).create(true).truncate(true).open(&probe)?;
+    let _ = fs::remove_file(&probe);
+
+    if output.is_file() {
+        OpenOptions::new().write(true).open(output)?;
+    }
+    Ok(())
+}
+
+// clip.mp4 -> clip (1).mp4, clip (2).mp4 ...
+pub fn unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+// FAT/exFAT 的修改时间只精确到 2 秒
+const MTIME_SLACK: u64 = 2;
+
+// 类似 make：输出存在且不早于源文件就算最新。
+// 网络共享按时区错开整小时的时间戳也视为同一次转换
+pub fn is_up_to_date(input: &Path, output: &Path) -> bool {
+    let mtime = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let (Some(src), Some(out)) = (mtime(input), mtime(output)) else {
+        return false;
+    };
+    let Ok(newer_by) = src.duration_since(out) else {
+        return true;
+    };
+    let secs = newer_by.as_secs();
+    if secs <= MTIME_SLACK {
+        return true;
+    }
+    let off_hour = (secs % 3600).min(3600 - secs % 3600);
+    secs <= 14 * 3600 && off_hour <= MTIME_SLACK
+}
+
+// 系统的“另存为”对话框，取消时返回 None；只有 Windows 有
+#[cfg(target_os = "windows")]
+pub fn save_dialog(default: &str, format: &str) -> Option<String> {
+    use winapi::um::commdlg::{GetSaveFileNameW, OFN_NOCHANGEDIR, OFN_OVERWRITEPROMPT, OFN_PATHMUSTEXIST, OPENFILENAMEW};
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let mut buf = vec![0u16; 32768];
+    for (i, c) in default.encode_utf16().take(buf.len() - 1).enumerate() {
+        buf[i] = c;
+    }
+    // 过滤器是“说明\0模式\0……\0\0”
+    let filter = wide(&format!("{} 文件\0*.{}\0所有文件\0*.*\0", format, format));
+    let ext = wide(format);
+    let mut ofn: OPENFILENAMEW = unsafe { std::mem::zeroed() };
+    ofn.lStructSize = std::mem::size_of::<OPENFILENAMEW>() as u32;
+    ofn.lpstrFilter = filter.as_ptr();
+    ofn.lpstrFile = buf.as_mut_ptr();
+    ofn.nMaxFile = buf.len() as u32;
+    ofn.lpstrDefExt = ext.as_ptr();
+    ofn.Flags = OFN_OVERWRITEPROMPT | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR;
+    if unsafe { GetSaveFileNameW(&mut ofn) } == 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    Some(String::from_utf16_lossy(&buf[..len]))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn save_dialog(_default: &str, _format: &str) -> Option<String> {
+    None
+}
+
+// 系统的“打开”对话框，选一个已有的文件，如 ffmpeg.exe；只有 Windows 有
+#[cfg(target_os = "windows")]
+pub fn open_dialog(label: &str, pattern: &str) -> Option<String> {
+    use winapi::um::commdlg::{GetOpenFileNameW, OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR, OFN_PATHMUSTEXIST, OPENFILENAMEW};
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let mut buf = vec![0u16; 32768];
+    let filter = wide(&format!("{}\0{}\0所有文件\0*.*\0", label, pattern));
+    let mut ofn: OPENFILENAMEW = unsafe { std::mem::zeroed() };
+    ofn.lStructSize = std::mem::size_of::<OPENFILENAMEW>() as u32;
+    ofn.lpstrFilter = filter.as_ptr();
+    ofn.lpstrFile = buf.as_mut_ptr();
+    ofn.nMaxFile = buf.len() as u32;
+    ofn.Flags = OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR;
+    if unsafe { GetOpenFileNameW(&mut ofn) } == 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    Some(String::from_utf16_lossy(&buf[..len]))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn open_dialog(_label: &str, _pattern: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ffui_output_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn writable_directory_passes_and_leaves_no_probe() {
+        let dir = scratch_dir("writable");
+        let out = dir.join("clip.mp4");
+        check_writable(&out).unwrap();
+        fs::write(&out, b"old").unwrap();
+        check_writable(&out).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_directory_fails() {
+        let dir = scratch_dir("missing");
+        let err = check_writable(&dir.join("gone").join("clip.mp4")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn renamed_replaces_only_the_last_extension() {
+        assert_eq!(renamed("clip.mov", "mp4"), "clip.mp4");
+        assert_eq!(renamed("show.s01e02.final.mkv", "mp4"), "show.s01e02.final.mp4");
+        assert_eq!(renamed("clip.tar.gz", "mp4"), "clip.tar.mp4");
+        assert_eq!(renamed("旅行 2023.夏天.mov", "mp4"), "旅行 2023.夏天.mp4");
+        assert_eq!(renamed("Café.Ünïcode.webm", "mkv"), "Café.Ünïcode.mkv");
+        // 没有扩展名、点开头的隐藏文件整个当作文件名
+        assert_eq!(renamed("clip", "mp4"), "clip.mp4");
+        assert_eq!(renamed(".hidden", "mp4"), ".hidden.mp4");
+        let nested = Path::new("素材").join("第一集.mov").to_string_lossy().into_owned();
+        assert_eq!(renamed(&nested, "mp4"), Path::new("素材").join("第一集.mp4").to_string_lossy());
+    }
+
+    #[test]
+    fn unique_path_keeps_dots_and_unicode() {
+        let dir = scratch_dir("unique");
+        let free = dir.join("片段.v2.mp4");
+        assert_eq!(unique_path(&free), free);
+        fs::write(&free, b"").unwrap();
+        assert_eq!(unique_path(&free), dir.join("片段.v2 (1).mp4"));
+        fs::write(dir.join("片段.v2 (1).mp4"), b"").unwrap();
+        assert_eq!(unique_path(&free), dir.join("片段.v2 (2).mp4"));
+        // 没有扩展名时编号加在最后
+        let bare = dir.join("Ünïcode");
+        fs::write(&bare, b"").unwrap();
+        assert_eq!(unique_path(&bare), dir.join("Ünïcode (1)"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn same_format_output_never_overwrites_the_input() {
+        let dir = scratch_dir("same");
+        let input = dir.join("会议.2024.03.mp4");
+        fs::write(&input, b"").unwrap();
+        let input = input.to_string_lossy().into_owned();
+        assert_eq!(not_input(&input, renamed(&input, "mp4")), dir.join("会议.2024.03 (1).mp4").to_string_lossy());
+        assert_eq!(not_input(&input, renamed(&input, "mkv")), dir.join("会议.2024.03.mkv").to_string_lossy());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 像播放器一样以不共享的方式打开输出文件，检查应当报告“正被其他程序占用”
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn held_file_reports_sharing_violation() {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        let dir = scratch_dir("held");
+        let out = dir.join("clip.mp4");
+        fs::write(&out, b"old").unwrap();
+        let held = OpenOptions::new().read(true).share_mode(0).open(&out).unwrap();
+        let err = check_writable(&out).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(32));
+        assert!(crate::errors::explain_io_error(&err).retry_rename);
+        assert_eq!(crate::errors::explain_io_error(&err).message, "输出文件正被其他程序占用");
+        drop(held);
+        check_writable(&out).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// ffui 自己保存的文件（转换记录、统计、运行状态）都放在 store_dir() 下。
+// 程序旁边有 ffui-portable.toml 或 portable 文件时是便携模式，数据放在程序旁的 data 目录，
+// 从 U 盘运行时不在每台电脑的用户目录里留东西
+const MARKERS: &[&str] = &["ffui-portable.toml", "portable"];
+const PORTABLE_DIR: &str = "data";
+// 已经问过是否从另一种模式复制数据，不再提示
+const MIGRATED: &str = "migrated";
+
+struct Store {
+    dir: PathBuf,
+    portable: bool,
+    warning: Option<String>,
+}
+
+pub fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe().ok()?.parent().map(Path::to_path_buf)
+}
+
+// %APPDATA%\ffui，其他系统用 ~/.config/ffui
+fn user_dir() -> PathBuf {
+    let base = std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("ffui")
+}
+
+fn portable_dir() -> Option<PathBuf> {
+    let exe = exe_dir()?;
+    MARKERS.iter().any(|m| exe.join(m).exists()).then(|| exe.join(PORTABLE_DIR))
+}
+
+// U 盘写保护、程序装在 Program Files 时写不进去，先试写一个文件
+fn writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".ffui_write_test_{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+fn store() -> &'static Store {
+    static STORE: OnceLock<Store> = OnceLock::new();
+    STORE.get_or_init(|| match portable_dir() {
+        None => Store { dir: user_dir(), portable: false, warning: None },
+        Some(dir) => match writable(&dir) {
+            Ok(()) => Store { dir, portable: true, warning: None },
+            Err(e) => Store {
+                warning: Some(format!("便携模式的 {} 无法写入（{}），本次改用 {}", dir.display(), e, user_dir().display())),
+                dir: user_dir(),
+                portable: false,
+            },
+        },
+    })
+}
+
+pub fn store_dir() -> PathBuf {
+    store().dir.clone()
+}
+
+pub fn is_portable() -> bool {
+    store().portable
+}
+
+// 便携目录不可写、退回用户目录时的提示
+pub fn warning() -> Option<&'static str> {
+    store().warning.as_deref()
+}
+
+// 另一种模式的目录里有数据、当前目录还没有问过时返回它，界面据此提示复制一次
+pub fn pending_migration() -> Option<PathBuf> {
+    let current = store_dir();
+    if current.join(MIGRATED).exists() || warning().is_some() {
+        return None;
+    }
+    let other = if is_portable() { user_dir() } else { portable_dir_on_disk()? };
+    let has_data = fs::read_dir(&other).map(|mut d| d.next().is_some()).unwrap_or(false);
+    (has_data && other != current).then_some(other)
+}
+
+// 非便携模式下只在 data 目录确实存在时才算有旧数据（去掉了标记文件的情况）
+fn portable_dir_on_disk() -> Option<PathBuf> {
+    let dir = exe_dir()?.join(PORTABLE_DIR);
+    dir.is_dir().then_some(dir)
+}
+
+fn copy_missing(from: &Path, to: &Path, copied: &mut usize) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        // running 里是正在运行的实例的状态，不属于设置
+        if name == MIGRATED || name == "running" {
+            continue;
+        }
+        let target = to.join(&name);
+        if entry.file_type()?.is_dir() {
+            copy_missing(&entry.path(), &target, copied)?;
+        } else if !target.exists() {
+            fs::copy(entry.path(), &target)?;
+            *copied += 1;
+        }
+    }
+    Ok(())
+}
+
+// 把 from 里当前目录还没有的文件复制过来，已有的不覆盖；原目录保持不动。
+// copy 为 false 表示用户不需要，只记下已经问过
+pub fn finish_migration(from: &Path, copy: bool) -> io::Result<usize> {
+    let to = store_dir();
+    let mut copied = 0;
+    if copy {
+        copy_missing(from, &to, &mut copied)?;
+    }
+    fs::create_dir_all(&to)?;
+    fs::write(to.join(MIGRATED), from.to_string_lossy().as_bytes())?;
+    Ok(copied)
+}
@@ -0,0 +1,198 @@
+// 队列里的一个任务按顺序执行的阶段，例如 转换 → 校验输出 → VMAF → 完成后命令。
+// 每个阶段声明依赖的阶段和失败时的处理：依赖没有成功的阶段跳过；
+// 策略为“中止”的阶段失败后，后面的阶段除了完成后命令都跳过。
+// 输出先写在暂存目录，运行时在最后一个检查阶段后面自动加上“发布输出”，把输出一起移到位
+#[derive(Clone, Copy, PartialEq)]
+pub enum StageKind {
+    Encode,
+    Verify,
+    Vmaf,
+    Hook,
+    // 不能在任务列表里写，由 run 自动加上
+    Publish,
+}
+
+impl StageKind {
+    pub const ALL: [StageKind; 4] = [StageKind::Encode, StageKind::Verify, StageKind::Vmaf, StageKind::Hook];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StageKind::Encode => "转换",
+            StageKind::Verify => "校验输出",
+            StageKind::Vmaf => "VMAF 画质",
+            StageKind::Hook => "完成后命令",
+            StageKind::Publish => "发布输出",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            StageKind::Encode => "encode",
+            StageKind::Verify => "verify",
+            StageKind::Vmaf => "vmaf",
+            StageKind::Hook => "hook",
+            StageKind::Publish => "publish",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<StageKind> {
+        StageKind::ALL.into_iter().find(|k| k.tag() == tag)
+    }
+
+    pub fn needs(self) -> &'static [StageKind] {
+        match self {
+            StageKind::Verify | StageKind::Vmaf | StageKind::Publish => &[StageKind::Encode],
+            StageKind::Encode | StageKind::Hook => &[],
+        }
+    }
+
+    // 完成后命令分成功/失败两种，中止后也要运行
+    fn runs_after_abort(self) -> bool {
+        self == StageKind::Hook
+    }
+
+    pub fn default_policy(self) -> OnFailure {
+        match self {
+            StageKind::Encode | StageKind::Verify | StageKind::Publish => OnFailure::Abort,
+            StageKind::Vmaf | StageKind::Hook => OnFailure::Continue,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OnFailure {
+    Abort,
+    // 记为警告，后面的阶段照常执行
+    Continue,
+}
+
+impl OnFailure {
+    pub fn tag(self) -> &'static str {
+        match self {
+            OnFailure::Abort => "abort",
+            OnFailure::Continue => "continue",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<OnFailure> {
+        [OnFailure::Abort, OnFailure::Continue].into_iter().find(|p| p.tag() == tag)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Stage {
+    pub kind: StageKind,
+    pub on_failure: OnFailure,
+}
+
+impl Stage {
+    pub fn new(kind: StageKind) -> Stage {
+        Stage { kind, on_failure: kind.default_policy() }
+    }
+}
+
+// 没有写阶段的任务：转换后运行完成后命令，和以前一样
+pub fn default_stages() -> Vec<Stage> {
+    vec![Stage::new(StageKind::Encode), Stage::new(StageKind::Hook)]
+}
+
+// 第一个阶段必须是转换，每个阶段只出现一次，依赖的阶段排在前面
+pub fn validate(stages: &[Stage]) -> Result<(), String> {
+    if stages.first().map(|s| s.kind) != Some(StageKind::Encode) {
+        return Err("第一个阶段必须是 encode".to_string());
+    }
+    for (i, stage) in stages.iter().enumerate() {
+        let before = &stages[..i];
+        if before.iter().any(|s| s.kind == stage.kind) {
+            return Err(format!("阶段 {} 重复", stage.kind.tag()));
+        }
+        if let Some(dep) = stage.kind.needs().iter().find(|d| !before.iter().any(|s| s.kind == **d)) {
+            return Err(format!("阶段 {} 要排在 {} 后面", stage.kind.tag(), dep.tag()));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Status {
+    Passed(String),
+    Warned(String),
+    Failed(String),
+    Skipped(String),
+}
+
+impl Status {
+    pub fn badge(&self) -> &'static str {
+        match self {
+            Status::Passed(_) => "✓",
+            Status::Warned(_) => "⚠",
+            Status::Failed(_) => "✗",
+            Status::Skipped(_) => "—",
+        }
+    }
+
+    pub fn detail(&self) -> &str {
+        match self {
+            Status::Passed(s) | Status::Warned(s) | Status::Failed(s) | Status::Skipped(s) => s,
+        }
+    }
+
+    fn ok(&self) -> bool {
+        matches!(self, Status::Passed(_) | Status::Warned(_))
+    }
+}
+
+pub struct Outcome {
+    pub stages: Vec<(Stage, Status)>,
+}
+
+impl Outcome {
+    // 有策略为“中止”的阶段失败
+    pub fn failed(&self) -> Option<&(Stage, Status)> {
+        self.stages.iter().find(|(stage, status)| matches!(status, Status::Failed(_)) && stage.on_failure == OnFailure::Abort)
+    }
+
+    // 没有中止，但有阶段报了警告或按“继续”策略失败
+    pub fn warned(&self) -> bool {
+        self.failed().is_none() && self.stages.iter().any(|(_, status)| matches!(status, Status::Warned(_) | Status::Failed(_)))
+    }
+
+    // 转换 ✓ → 校验输出 ✓ → VMAF 画质 ⚠
+    pub fn badges(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(stage, status)| format!("{} {}", stage.kind.label(), status.badge()))
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+}
+
+// 依次执行各阶段。exec 收到阶段和流水线到目前为止是否没有中止，返回该阶段的状态。
+// 每个任务的流水线只在自己的线程里顺序执行，不同任务之间互不影响
+pub fn run(stages: &[Stage], mut exec: impl FnMut(StageKind, bool) -> Status) -> Outcome {
+    let mut done: Vec<(Stage, Status)> = Vec::new();
+    let mut aborted = false;
+    for stage in &with_publish(stages) {
+        let missing = stage.kind.needs().iter().find(|d| !done.iter().any(|(s, st)| s.kind == **d && st.ok()));
+        let status = if aborted && !stage.kind.runs_after_abort() {
+            Status::Skipped("前面的阶段失败，已中止".to_string())
+        } else if let Some(dep) = missing {
+            Status::Skipped(format!("{}没有成功", dep.label()))
+        } else {
+            exec(stage.kind, !aborted)
+        };
+        if matches!(status, Status::Failed(_)) && stage.on_failure == OnFailure::Abort {
+            aborted = true;
+        }
+        done.push((*stage, status));
+    }
+    Outcome { stages: done }
+}
+
+// 发布放在最后一个不是完成后命令的阶段后面：完成后命令拿到的是已经就位的输出
+fn with_publish(stages: &[Stage]) -> Vec<Stage> {
+    let at = stages.iter().rposition(|s| s.kind != StageKind::Hook).map_or(0, |i| i + 1);
+    let mut all = stages.to_vec();
+    all.insert(at, Stage::new(StageKind::Publish));
+    all
+}
@@ -0,0 +1,1384 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::album::{self, Album};
+use crate::args::{self, Args, Source};
+use crate::aspect::{self, Fit, SarMode};
+use crate::av1::{Av1Settings, SoftEncoder, VideoCodec};
+use crate::burnin::{self, BurnIn, Clock};
+use crate::coverart;
+use crate::dataloss::{self, Choice, Kind};
+use crate::encoders;
+use crate::filedate;
+use crate::filterchain::{self, FilterStep};
+use crate::framerate;
+use crate::gop::{self, Gop};
+use crate::hwlimit;
+use crate::inspect;
+use crate::interlace::{self, Deinterlace};
+use crate::ladder::{self, Rung};
+use crate::lengths::{self, LengthPolicy};
+use crate::live;
+use crate::loudness;
+use crate::preset;
+use crate::probe::{self, MediaInfo, ProbeDepth};
+use crate::quality::{self, Quality, RateMode};
+use crate::quick::{self, QuickOp};
+use crate::resolution::Resolution;
+use crate::retime::{self, Rate, Retime};
+use crate::sample;
+use crate::snapshot::{self, Snapshot};
+use crate::speech::{self, Speech};
+use crate::subtitle;
+use crate::tempfiles;
+use crate::throttle::{self, Mechanism};
+use crate::timecode::{self, Timecode};
+use crate::timestamp;
+use crate::warnings;
+use crate::web::{self, Platform};
+
+// 一次转换需要的全部设置，界面和命令行共用
+#[derive(Clone)]
+pub struct JobSettings {
+    pub format: String,
+    pub gpu: String,
+    pub codec: VideoCodec,
+    pub av1: Av1Settings,
+    pub quality: Quality,
+    // 按目标码率时先分析一遍再编码，只有支持的软件编码器生效
+    pub two_pass: bool,
+    // 编码器的 -preset / -tune，None 表示用编码器默认值
+    pub preset: Option<String>,
+    pub tune: Option<String>,
+    // 仅转换封装：所有流原样复制到目标格式，忽略设备、编码、画质和滤镜
+    pub remux: bool,
+    // 保留所有音轨时按输入音轨顺序逐条决定
+    pub keep_all_audio: bool,
+    pub audio_tracks: Vec<TrackChoice>,
+    // 音频编码和码率；自动时由 ffmpeg 按容器选，码率只用于有损编码
+    pub audio_codec: AudioCodec,
+    pub audio_bitrate_k: u32,
+    // 用 loudnorm 把综合响度调到这么多 LUFS，None 表示不调整
+    pub loudnorm: Option<f64>,
+    // 烧录进画面的外挂字幕，编码为 None 时自动检测
+    pub subtitle_file: String,
+    pub subtitle_encoding: Option<String>,
+    // 输出已存在时覆盖（-y）还是放弃（-n）
+    pub overwrite: bool,
+    // 增量：输出比源文件新时跳过
+    pub incremental: bool,
+    // 多分辨率：一次输入输出多个清晰度
+    pub ladder_enabled: bool,
+    pub ladder: Vec<Rung>,
+    pub ladder_hls: bool,
+    pub deinterlace: Deinterlace,
+    // 输出分辨率，一键方案和多分辨率有自己的尺寸，不用它
+    pub resolution: Resolution,
+    // 输出帧率，None 保持原始；帧率重映射、一键方案、多分辨率和截图时不用它
+    pub fps: Option<Rate>,
+    // 审片副本上烧录的时间或帧号
+    pub burn_in: Option<BurnIn>,
+    pub fit: Fit,
+    pub sar_mode: SarMode,
+    // 反交错、改帧率、像素比修正、字幕、补边、缩放的先后，违反规则时按默认顺序
+    pub filter_order: Vec<FilterStep>,
+    // 奇数宽高、非方形像素的修正滤镜和 -aspect，由 resolve 填写
+    pub pixel_filter: Option<String>,
+    pub display_aspect: Option<String>,
+    // 画面超过硬件编码器上限时缩小到的尺寸，由 resolve 填写
+    pub hw_scale: Option<(u32, u32)>,
+    pub probe_depth: ProbeDepth,
+    // 音频输出不能嵌入封面时，把封面另存为输出旁边的 cover.jpg
+    pub cover_file: bool,
+    // 数据丢失预检里用户改过的处理方式，没有记的按默认（能转换就转换，否则接受丢失）
+    pub data_loss: Vec<(Kind, Choice)>,
+    // 关键帧间隔，HLS 输出时要和分片时长对齐
+    pub gop: Gop,
+    // 音频和视频时长相差较多时的处理方式
+    pub length_policy: LengthPolicy,
+    // 帧率重映射（如 PAL 25 -> 23.976）：保留每一帧，视频和音频按同样的比例变速
+    pub retime: Option<Retime>,
+    // 写入速度上限（MB/s），0 表示不限，输出在网络共享或 SMR 硬盘上时用
+    pub write_limit_mb: f64,
+    // 附加参数，按 args::split_extra 拆开后放在输出路径前面
+    pub extra_args: String,
+    // 用设置里哪个命名的 ffmpeg 版本，空表示默认的
+    pub ffmpeg: String,
+    // 强制指定的输入格式（-f），空表示由 ffmpeg 自动识别；标准输入和命名管道必须填
+    pub input_format: String,
+    // 只转换源文件的一段，None 表示从开头/到结尾
+    pub trim_start: Option<Duration>,
+    pub trim_end: Option<Duration>,
+    // 发到聊天/网页的一键方案，设置后忽略格式、编码器和多分辨率
+    pub web: Option<Platform>,
+    // 按间隔导出 JPEG 截图，设置后不输出视频
+    pub snapshot: Option<Snapshot>,
+    // 语音优化（讲座、播客、有声书），设置后只输出单声道音频
+    pub speech: Option<Speech>,
+    // 专辑里的一轨（音轨号、cue 切分、无缝播放信息），设置后只输出 Opus/AAC 音频
+    pub album: Option<Album>,
+    // 只转正画面或去掉音轨，流复制，设置后忽略其他设置
+    pub quick: Option<QuickOp>,
+    // 开始转换的同时计算源文件 SHA-256，可选写进输出的注释
+    pub hash_source: bool,
+    pub hash_embed: bool,
+    // 转换成功/失败后运行的命令模板，空表示不运行
+    pub hook_success: String,
+    pub hook_failure: String,
+    pub hook_timeout_secs: u64,
+    // 把录制时间写进 creation_time，完成后复制源文件的时间戳
+    pub keep_dates: bool,
+    // 写进输出的录制时间，由 resolve 填写
+    pub creation_time: Option<String>,
+    // 上次转换出现警告后加上的修正（warnings::FIXES 里的 id）
+    pub fixes: Vec<String>,
+    // 预览：只编码开头若干秒，完整转换时为 None
+    pub preview_secs: Option<u32>,
+    // 大于 1 时预览改为在全片均匀取这么多段、每段 preview_secs 秒
+    pub preview_samples: u32,
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        JobSettings {
+            format: "mp4".to_string(), // 默认输出mp4
+            gpu: "CPU".to_string(), // 默认用CPU处理
+            codec: VideoCodec::H264,
+            av1: Av1Settings::default(),
+            quality: Quality::default(),
+            two_pass: false,
+            preset: None,
+            tune: None,
+            remux: false,
+            keep_all_audio: false,
+            audio_tracks: Vec::new(),
+            audio_codec: AudioCodec::Auto,
+            audio_bitrate_k: 192,
+            loudnorm: None,
+            subtitle_file: String::new(),
+            subtitle_encoding: None,
+            overwrite: true,
+            incremental: false,
+            ladder_enabled: false,
+            ladder: ladder::default_rungs(),
+            ladder_hls: false,
+            deinterlace: Deinterlace::Off,
+            resolution: Resolution::Original,
+            fps: None,
+            burn_in: None,
+            fit: Fit { custom: (21, 9), ..Default::default() },
+            sar_mode: SarMode::Square,
+            filter_order: FilterStep::ALL.to_vec(),
+            pixel_filter: None,
+            display_aspect: None,
+            hw_scale: None,
+            probe_depth: ProbeDepth::default(),
+            cover_file: false,
+            data_loss: Vec::new(),
+            gop: Gop::default(),
+            length_policy: LengthPolicy::Keep,
+            retime: None,
+            write_limit_mb: 0.0,
+            extra_args: String::new(),
+            ffmpeg: String::new(),
+            input_format: String::new(),
+            trim_start: None,
+            trim_end: None,
+            web: None,
+            snapshot: None,
+            speech: None,
+            album: None,
+            quick: None,
+            hash_source: false,
+            hash_embed: false,
+            hook_success: String::new(),
+            hook_failure: String::new(),
+            hook_timeout_secs: 300,
+            keep_dates: false,
+            creation_time: None,
+            fixes: Vec::new(),
+            preview_secs: None,
+            preview_samples: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum TrackChoice {
+    #[default]
+    Auto,
+    Copy,
+    Transcode,
+    Drop,
+}
+
+impl TrackChoice {
+    pub const ALL: [TrackChoice; 4] = [TrackChoice::Auto, TrackChoice::Copy, TrackChoice::Transcode, TrackChoice::Drop];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TrackChoice::Auto => "自动",
+            TrackChoice::Copy => "直接复制",
+            TrackChoice::Transcode => "重新编码",
+            TrackChoice::Drop => "丢弃",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum AudioCodec {
+    #[default]
+    Auto,
+    Copy,
+    Aac,
+    Mp3,
+    Opus,
+    Flac,
+}
+
+impl AudioCodec {
+    pub const ALL: [AudioCodec; 6] = [AudioCodec::Auto, AudioCodec::Copy, AudioCodec::Aac, AudioCodec::Mp3, AudioCodec::Opus, AudioCodec::Flac];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AudioCodec::Auto => "自动",
+            AudioCodec::Copy => "直接复制",
+            AudioCodec::Aac => "AAC",
+            AudioCodec::Mp3 => "MP3",
+            AudioCodec::Opus => "Opus",
+            AudioCodec::Flac => "FLAC",
+        }
+    }
+
+    // 任务列表里的写法
+    pub fn tag(self) -> &'static str {
+        match self {
+            AudioCodec::Auto => "auto",
+            AudioCodec::Copy => "copy",
+            AudioCodec::Aac => "aac",
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::Opus => "opus",
+            AudioCodec::Flac => "flac",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<AudioCodec> {
+        AudioCodec::ALL.into_iter().find(|c| c.tag() == tag)
+    }
+
+    // (ffmpeg 编码器, 编码后的格式名)，自动和复制没有
+    fn encoder(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            AudioCodec::Auto | AudioCodec::Copy => None,
+            AudioCodec::Aac => Some(("aac", "aac")),
+            AudioCodec::Mp3 => Some(("libmp3lame", "mp3")),
+            AudioCodec::Opus => Some(("libopus", "opus")),
+            AudioCodec::Flac => Some(("flac", "flac")),
+        }
+    }
+
+    // 码率只对有损编码有意义
+    pub fn uses_bitrate(self) -> bool {
+        !matches!(self, AudioCodec::Copy | AudioCodec::Flac)
+    }
+}
+
+// 界面上可选的音频码率（kbps）
+pub const AUDIO_BITRATES: [u32; 5] = [96, 128, 192, 256, 320];
+
+// 界面上可选的目标格式
+pub const FORMATS: &[&str] = &["mp4", "avi", "mkv", "mov", "flv", "wmv", "mp3", "aac", "wav", "ogg"];
+
+// 各容器能直接装下的音频编码
+fn audio_copy_ok(container: &str, codec: &str) -> bool {
+    match container {
+        "mkv" => true,
+        "mp4" => matches!(codec, "aac" | "mp3" | "ac3" | "eac3" | "alac" | "opus" | "flac"),
+        "mov" => matches!(codec, "aac" | "mp3" | "ac3" | "alac") || codec.starts_with("pcm_"),
+        "avi" => matches!(codec, "mp3" | "ac3") || codec.starts_with("pcm_"),
+        "flv" => matches!(codec, "aac" | "mp3"),
+        "wmv" => matches!(codec, "wmav2" | "wmapro"),
+        "mp3" => codec == "mp3",
+        "aac" => codec == "aac",
+        "wav" => codec.starts_with("pcm_"),
+        "ogg" => matches!(codec, "vorbis" | "opus" | "flac"),
+        _ => false,
+    }
+}
+
+fn audio_bitrate(settings: &JobSettings) -> String {
+    format!("{}k", settings.audio_bitrate_k)
+}
+
+// wav 的 PCM 和 FLAC 是无损的，不设码率
+fn lossless_audio(encoder: &str) -> bool {
+    encoder.starts_with("pcm_") || encoder == "flac"
+}
+
+fn audio_codec_args(args: &mut Args, settings: &JobSettings, source: Source) {
+    let encoder = audio_encoder(settings);
+    args.push(source, &["-c:a", encoder]);
+    if !lossless_audio(encoder) {
+        args.push(source, &["-b:a", &audio_bitrate(settings)]);
+    }
+}
+
+// 重新编码音频时用的编码器：选了具体编码就用它，否则按容器默认
+fn audio_encoder(settings: &JobSettings) -> &'static str {
+    settings.audio_codec.encoder().map_or(default_audio_codec(&settings.format), |(encoder, _)| encoder)
+}
+
+// 音频滤镜不能和直接复制一起用；补静音只在视频容器里做
+fn audio_filtered(settings: &JobSettings, info: &MediaInfo) -> bool {
+    settings.fixes.iter().any(|f| f == "async")
+        || (is_video_container(&settings.format) && lengths::args(settings.length_policy, info).audio_filter.is_some())
+        || settings.retime.is_some()
+        || settings.loudnorm.is_some()
+}
+
+// 开始前检查选的音频编码能不能放进目标容器，不行时不启动 ffmpeg。
+// 一键方案、截图、语音优化、专辑、多分辨率和快速操作自己决定音频，不检查
+pub fn check_audio(settings: &JobSettings, info: &MediaInfo) -> Result<(), String> {
+    let special = settings.web.is_some() || settings.snapshot.is_some() || settings.speech.is_some() || settings.album.is_some() || settings.quick.is_some()
+        || settings.remux
+        || (settings.ladder_enabled && is_video_container(&settings.format));
+    if special {
+        return Ok(());
+    }
+    let container = settings.format.as_str();
+    if let Some((encoder, name)) = settings.audio_codec.encoder() {
+        if !audio_copy_ok(container, name) {
+            return Err(format!("{} 不能装 {} 音频（{}），请换一种音频编码或目标格式", container, settings.audio_codec.label(), encoder));
+        }
+        return Ok(());
+    }
+    if settings.audio_codec != AudioCodec::Copy {
+        return Ok(());
+    }
+    if audio_filtered(settings, info) {
+        return Err("音频需要经过滤镜处理（修正参数 async、时长补齐、帧率重映射或响度标准化），不能直接复制".to_string());
+    }
+    // 实时输入不探测，只能交给 ffmpeg
+    let tracks: Vec<&str> = info.streams.iter().filter(|s| s.codec_type == "audio").map(|s| s.codec_name.as_str()).collect();
+    let checked = if settings.keep_all_audio && is_video_container(container) { tracks.as_slice() } else { &tracks[..tracks.len().min(1)] };
+    match checked.iter().find(|codec| !audio_copy_ok(container, codec)) {
+        Some(codec) => Err(format!("源文件的 {} 音频不能直接复制进 {}，请选择一种音频编码或换个目标格式", codec, container)),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn default_audio_codec(container: &str) -> &'static str {
+    match container {
+        "avi" | "mp3" => "libmp3lame",
+        "wmv" => "wmav2",
+        "wav" => "pcm_s16le",
+        "ogg" => "libvorbis",
+        _ => "aac",
+    }
+}
+
+pub(crate) fn is_video_container(container: &str) -> bool {
+    matches!(container, "mp4" | "avi" | "mkv" | "mov" | "flv" | "wmv")
+}
+
+// 单条音轨的处理方式；output_index 是丢弃音轨后在输出里的序号
+pub struct AudioDecision {
+    pub input_index: usize,
+    pub output_index: usize,
+    pub codec: Option<&'static str>, // None 表示直接复制
+    pub forced: bool, // 用户选了复制但容器不支持
+}
+
+pub fn plan_audio(settings: &JobSettings, info: &MediaInfo) -> Vec<AudioDecision> {
+    let container = settings.format.as_str();
+    let mut decisions = Vec::new();
+    for (input_index, stream) in info.streams.iter().filter(|s| s.codec_type == "audio").enumerate() {
+        let choice = settings.audio_tracks.get(input_index).copied().unwrap_or_default();
+        let compatible = audio_copy_ok(container, &stream.codec_name) && !audio_filtered(settings, info);
+        // 选了具体的音频编码时，自动的音轨也按它重新编码
+        let encode = settings.audio_codec.encoder().is_some();
+        let (codec, forced) = match choice {
+            TrackChoice::Drop => continue,
+            TrackChoice::Transcode => (Some(audio_encoder(settings)), false),
+            TrackChoice::Auto if encode => (Some(audio_encoder(settings)), false),
+            TrackChoice::Auto | TrackChoice::Copy if compatible => (None, false),
+            TrackChoice::Auto => (Some(audio_encoder(settings)), false),
+            TrackChoice::Copy => (Some(audio_encoder(settings)), true),
+        };
+        decisions.push(AudioDecision { input_index, output_index: decisions.len(), codec, forced });
+    }
+    decisions
+}
+
+// 一个任务要依次执行的 ffmpeg 调用，以及成功后应当存在的文件
+#[derive(Default)]
+pub struct JobPlan {
+    pub runs: Vec<Args>,
+    pub outputs: Vec<String>,
+    // 开始前需要创建的目录
+    pub dirs: Vec<String>,
+    // 全部调用成功后由 ffui 自己写出的文件（路径, 内容）
+    pub write_after: Vec<(String, String)>,
+    pub notes: Vec<String>,
+    // 截图任务：(目录, 预计张数)，完成后核对数量
+    pub expect_images: Option<(String, usize)>,
+    // 开始前由 ffui 写出的文件（路径, 内容），如 concat 列表
+    pub write_before: Vec<(String, String)>,
+    // 每次调用输出的媒体时长，比输入短时（预览、取样）用来算进度
+    pub run_secs: Option<f64>,
+    // 源文件有封面，但输出格式放不下、也没有另存
+    pub art_lost: bool,
+    // 只复制、不编码的调用（取样拼接、另存封面）在 runs 里的序号，附加参数不加在这些调用上
+    pub copy_runs: Vec<usize>,
+}
+
+// 需要先分析素材才能决定的设置，界面和 --print-cmd 都在生成命令前调用
+pub fn resolve(settings: &mut JobSettings, input: &str, info: &MediaInfo) -> Vec<String> {
+    let mut notes = Vec::new();
+    if live::is_live(input) {
+        notes.extend(live::resolve(settings));
+    }
+    // 快速操作沿用源文件的容器，后面的分析都用不上
+    if settings.quick.is_some() {
+        settings.format = quick::container(input);
+        return notes;
+    }
+    // 仅转换封装不解码也不编码，编码器、滤镜相关的分析都用不上
+    if settings.remux && settings.web.is_none() && settings.snapshot.is_none() && settings.speech.is_none() && settings.album.is_none() {
+        notes.push(format!("仅转换封装: 所有流直接复制到 {}，不重新编码；目标格式装不下的编码会让 ffmpeg 报错", settings.format));
+        if settings.write_limit_mb > 0.0 {
+            notes.push(match remux_throttle(settings, info, input) {
+                Some(mechanism) => throttle_note(&mechanism, settings.write_limit_mb, info.duration),
+                None if live::is_live(input) => "限速: 实时输入本来就按实时速度写入，不另外限速".to_string(),
+                None => "限速: 不知道源文件的码率，没法换算复制时的读取速度，已忽略".to_string(),
+            });
+        }
+        return notes;
+    }
+    // 一键方案固定用 CPU 的 libx264 输出 mp4，两遍编码不支持硬件编码器
+    if settings.web.is_some() {
+        settings.format = "mp4".to_string();
+        settings.gpu = "CPU".to_string();
+        settings.codec = VideoCodec::H264;
+        settings.ladder_enabled = false;
+    }
+    if let Some(speech) = &mut settings.speech {
+        settings.format = speech.codec.ext().to_string();
+        settings.ladder_enabled = false;
+        notes.extend(speech::resolve(speech, input, info));
+    }
+    if let Some(album) = &settings.album {
+        settings.format = album.codec.ext().to_string();
+        settings.ladder_enabled = false;
+        notes.extend(album::resolve(album));
+    }
+    let has_video = info.streams.iter().any(|s| s.codec_type == "video");
+    if settings.deinterlace == Deinterlace::Auto {
+        if !has_video || !is_video_container(&settings.format) {
+            settings.deinterlace = Deinterlace::Off;
+        } else {
+            match interlace::detect(input, info.duration, settings.probe_depth) {
+                Ok((mode, stats)) => {
+                    settings.deinterlace = mode;
+                    let repeated = stats.repeated_top + stats.repeated_bottom;
+                    notes.push(match mode {
+                        Deinterlace::Ivtc => format!(
+                            "隔行检测: 3:2 下拉的电视电影素材（{} 个重复场），使用 fieldmatch+decimate 还原，输出帧率约为原来的 4/5",
+                            repeated
+                        ),
+                        Deinterlace::Yadif => format!(
+                            "隔行检测: 隔行扫描（{} 帧 TFF / {} 帧 BFF），使用 yadif 反交错",
+                            stats.tff, stats.bff
+                        ),
+                        _ => "隔行检测: 逐行扫描，无需处理".to_string(),
+                    });
+                }
+                Err(e) => {
+                    settings.deinterlace = Deinterlace::Off;
+                    notes.push(format!("隔行检测失败（{}），按逐行处理", e));
+                }
+            }
+        }
+    } else if settings.deinterlace != Deinterlace::Off {
+        notes.push(format!("反交错: {}（手动指定）", settings.deinterlace.label()));
+    }
+
+    if is_video_container(&settings.format) {
+        let codec = settings.codec;
+        if !codec.fits(&settings.format) {
+            settings.codec = VideoCodec::H264;
+            notes.push(format!("{} 不支持 {}，改用 H.264", settings.format, codec.label()));
+        } else if settings.gpu != "CPU" && codec.encoder(&settings.gpu, settings.av1.encoder).is_none() {
+            notes.push(format!("{} 没有 {} 硬件编码器，改用 CPU 编码（{}）", settings.gpu, codec.label(), video_codec(settings)));
+            settings.gpu = "CPU".to_string();
+        }
+        let codec = settings.codec;
+        if codec == VideoCodec::Av1 && settings.gpu == "CPU" {
+            match SoftEncoder::detect() {
+                Some(encoder) => settings.av1.encoder = encoder,
+                None => {
+                    settings.codec = VideoCodec::H264;
+                    notes.push("ffmpeg 没有 libsvtav1 或 libaom-av1，改用 H.264".to_string());
+                }
+            }
+        } else if codec != VideoCodec::H264 && !encoders::available(video_codec(settings)) {
+            if settings.gpu == "CPU" {
+                notes.push(format!("ffmpeg 没有 {}，改用 H.264", video_codec(settings)));
+                settings.codec = VideoCodec::H264;
+            } else {
+                notes.push(format!("ffmpeg 没有列出 {}，显卡或驱动可能不支持 {} 编码", video_codec(settings), codec.label()));
+            }
+        }
+    }
+
+    // 精简编译的 ffmpeg 可能没有这些滤镜，换了 ffmpeg 版本时尤其要看
+    if !settings.subtitle_file.is_empty() && !encoders::filter_available("subtitles") {
+        notes.push("ffmpeg 没有 subtitles 滤镜（编译时没有 libass），烧录字幕会失败".to_string());
+    }
+    if settings.burn_in.is_some() && !encoders::filter_available("drawtext") {
+        notes.push("ffmpeg 没有 drawtext 滤镜（编译时没有 freetype），烧录时间码会失败".to_string());
+    }
+
+    if let Some(tc) = timecode::from_info(info) {
+        notes.push(match output_timecode(settings, info) {
+            Some(_) => format!("时间码: {}，写入输出", tc),
+            None if !matches!(settings.format.as_str(), "mov" | "mp4" | "mkv") => format!("时间码: {}，{} 不支持，不保留", tc, settings.format),
+            None if settings.deinterlace == Deinterlace::Ivtc => format!("时间码: {}，IVTC 改变了帧率，不保留", tc),
+            None if settings.retime.is_some() => format!("时间码: {}，帧率重映射改变了时长，不保留", tc),
+            None if output_fps_override(settings).is_some() => format!("时间码: {}，改了输出帧率，不保留", tc),
+            None => format!("时间码: {}，与视频帧率不符，不保留", tc),
+        });
+    }
+
+    // 只提示，不自动改动；界面里可以一键修正
+    if settings.ladder_enabled
+        && settings.ladder_hls
+        && let Some(fps) = gop::output_fps(settings, info)
+        && let Some(problem) = gop::check(settings.gop, fps, ladder::HLS_SEGMENT_SECS as f64)
+    {
+        let fixed = gop::fix(fps, ladder::HLS_SEGMENT_SECS as f64);
+        notes.push(format!("关键帧: {}，建议设为固定 {} 帧", problem, fixed.frames));
+    }
+
+    if has_video && is_video_container(&settings.format) && settings.snapshot.is_none() && settings.speech.is_none() {
+        if let Some(note) = lengths::note(settings.length_policy, info) {
+            notes.push(note);
+        }
+        if settings.length_policy != LengthPolicy::Keep && (settings.web.is_some() || settings.ladder_enabled) && lengths::detect(info).is_some() {
+            notes.push("时长: 一键方案和多分辨率不处理时长差异".to_string());
+        }
+    }
+
+    if let Some(retime) = settings.retime {
+        let special = settings.web.is_some() || settings.ladder_enabled || settings.snapshot.is_some() || settings.speech.is_some() || settings.album.is_some();
+        notes.push(if special {
+            "帧率重映射: 一键方案、多分辨率、截图、语音优化和专辑不支持，已忽略".to_string()
+        } else if info.duration > 0.0 {
+            format!(
+                "帧率重映射: {}，时长 {} → {}",
+                retime.label(),
+                timestamp::format(Duration::from_secs_f64(info.duration)),
+                timestamp::format(Duration::from_secs_f64(retime.scale_secs(info.duration)))
+            )
+        } else {
+            format!("帧率重映射: {}", retime.label())
+        });
+    }
+
+    if settings.write_limit_mb > 0.0 {
+        let special = settings.web.is_some() || settings.ladder_enabled || settings.snapshot.is_some() || settings.speech.is_some() || settings.album.is_some();
+        notes.push(match throttle(settings, info, input) {
+            _ if special => "限速: 一键方案、多分辨率、截图、语音优化和专辑不支持，已忽略".to_string(),
+            Some(mechanism) => throttle_note(&mechanism, settings.write_limit_mb, info.duration),
+            None if live::is_live(input) => "限速: 实时输入本来就按实时速度写入，不另外限速".to_string(),
+            None => "限速: 音频输出码率很低，不限速".to_string(),
+        });
+    }
+
+    settings.fit.canvas = None;
+    if let Some(ratio) = settings.fit.ratio()
+        && is_video_container(&settings.format)
+    {
+        match info.streams.iter().find(|s| s.codec_type == "video").and_then(aspect::display_size) {
+            Some((w, h, anamorphic)) => {
+                let canvas = aspect::canvas(w, h, ratio);
+                if canvas == (w, h) {
+                    notes.push(format!("宽高比: 已是 {}:{}，无需补边", ratio.0, ratio.1));
+                } else {
+                    settings.fit.canvas = Some(canvas);
+                    settings.fit.anamorphic = anamorphic;
+                    notes.push(format!(
+                        "宽高比: {}x{} 补边到 {}x{}（{}）",
+                        w, h, canvas.0, canvas.1, settings.fit.fill.label()
+                    ));
+                }
+            }
+            None => notes.push("宽高比: 无法读取视频分辨率，跳过补边".to_string()),
+        }
+    }
+
+    if settings.resolution != Resolution::Original && is_video_container(&settings.format) {
+        let source = info.streams.iter().find(|s| s.codec_type == "video").and_then(aspect::display_size);
+        if !own_size(settings) {
+            notes.push(format!("分辨率: 一键方案和多分辨率有自己的尺寸，已忽略 {}", settings.resolution.label()));
+        } else if let Some((w, h, _)) = source {
+            let (tw, th) = settings.resolution.apply((w, h));
+            let enlarged = tw as u64 * th as u64 > w as u64 * h as u64;
+            notes.push(format!("分辨率: {}x{} → {}x{}{}", w, h, tw, th, if enlarged { "（放大，不会增加细节）" } else { "" }));
+        }
+    }
+
+    if let Some(rate) = settings.fps
+        && is_video_container(&settings.format)
+    {
+        let source = info.streams.iter().any(|s| s.codec_type == "video").then(|| retime::source_rate(info)).flatten();
+        notes.push(match source {
+            _ if settings.retime.is_some() => format!("帧率: 已设置帧率重映射，忽略 {} fps", rate.label()),
+            _ if output_fps_override(settings).is_none() => format!("帧率: 一键方案、多分辨率和截图有自己的帧率，已忽略 {} fps", rate.label()),
+            Some(from) if from == rate => format!("帧率: 已是 {} fps", rate.label()),
+            Some(from) => format!(
+                "帧率: {} → {} fps{}",
+                from.label(),
+                rate.label(),
+                if rate.as_f64() > from.as_f64() { "（高于源帧率，会重复帧）" } else { "" }
+            ),
+            None => format!("帧率: {} fps", rate.label()),
+        });
+    }
+
+    if settings.trim_start.is_some() || settings.trim_end.is_some() {
+        notes.push(if !trims(settings, input) {
+            "裁剪: 实时输入和语音优化不支持，已忽略".to_string()
+        } else {
+            let start = timestamp::format(settings.trim_start.unwrap_or_default());
+            let end = settings.trim_end.map_or("结尾".to_string(), timestamp::format);
+            match info.duration > 0.0 {
+                true => format!("裁剪: {} – {}，共 {}", start, end, timestamp::format(Duration::from_secs_f64(trimmed_secs(settings, input, info.duration)))),
+                false => format!("裁剪: {} – {}", start, end),
+            }
+        });
+    }
+
+    if let Some(burn) = &settings.burn_in
+        && is_video_container(&settings.format)
+    {
+        notes.push(burnin::note(burn, settings, info));
+    }
+
+    settings.hw_scale = None;
+    if let Some(over) = hwlimit::check(settings, info) {
+        settings.hw_scale = Some(over.scaled);
+        notes.push(format!(
+            "{}x{} 超过 {} 的上限 {}x{}，缩小到 {}x{}",
+            over.size.0, over.size.1, over.encoder, over.limit.0, over.limit.1, over.scaled.0, over.scaled.1
+        ));
+        // 多分辨率的档位在缩小后的画面上再缩放，超过上限的档位也要降下来
+        if settings.ladder_enabled {
+            let (w, h) = over.scaled;
+            for rung in &mut settings.ladder {
+                let width = (w as u64 * rung.height as u64 / h as u64) as u32;
+                if let Some((_, height)) = hwlimit::fit_within((width, rung.height), over.limit) {
+                    notes.push(format!("档位 {}p 超过编码器上限，改为 {}p", rung.height, height));
+                    rung.height = height;
+                }
+            }
+        }
+    }
+
+    settings.creation_time = None;
+    if settings.keep_dates && settings.snapshot.is_none() && settings.preview_secs.is_none() {
+        match filedate::recording_date(info, input) {
+            Some((date, source)) => {
+                notes.push(format!("录制时间: {}（{}），写入输出并复制文件时间戳", date, source));
+                settings.creation_time = Some(date);
+            }
+            None => notes.push("录制时间: 无法读取，只复制文件时间戳".to_string()),
+        }
+    }
+
+    // 补边和硬件缩放已经输出偶数宽高的方形像素，其余情况单独修正
+    settings.pixel_filter = None;
+    settings.display_aspect = None;
+    if settings.fit.canvas.is_none()
+        && settings.hw_scale.is_none()
+        && is_video_container(&settings.format)
+        && let Some(stream) = info.streams.iter().find(|s| s.codec_type == "video")
+    {
+        // 发到聊天软件的视频不能指望播放器认 -aspect
+        let mode = if settings.web.is_some() { SarMode::Square } else { settings.sar_mode };
+        if let Some((filter, aspect, note)) = aspect::pixel_fix(stream, mode) {
+            settings.pixel_filter = filter;
+            settings.display_aspect = aspect;
+            notes.push(note);
+        }
+    }
+    notes
+}
+
+// 中间文件都放在 temp 目录里，由调用方在任务结束后整个删除
+pub fn plan_job(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str, temp: &Path) -> JobPlan {
+    let mut job = plan_runs(settings, info, input, output, temp);
+    let extra = extra_args(settings);
+    let encodes = job.runs.iter_mut().enumerate().filter(|(i, a)| !job.copy_runs.contains(i) && a.iter().last().is_some_and(|(_, s)| s == Source::Output));
+    for (_, args) in encodes {
+        for arg in &extra {
+            args.insert(args.len() - 1, Source::Extra, arg.clone());
+        }
+    }
+    // 裁剪后进度按裁出的一段算；预览、变速这些已经算好的不动
+    if job.run_secs.is_none() && trims(settings, input) && info.duration > 0.0 {
+        job.run_secs = Some(trimmed_secs(settings, input, info.duration));
+    }
+    job
+}
+
+fn plan_runs(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str, temp: &Path) -> JobPlan {
+    if let Some(secs) = settings.preview_secs {
+        if settings.preview_samples > 1 && info.duration > 0.0 {
+            return sample::plan(settings, info, input, output, temp, settings.preview_samples, secs);
+        }
+        return plan_preview(settings, info, input, output, temp, secs);
+    }
+    if let Some(op) = settings.quick {
+        return quick::plan(settings, op, info, input, output);
+    }
+    if let Some(snap) = &settings.snapshot {
+        return snapshot::plan(settings, snap, info, input, output);
+    }
+    if let Some(platform) = settings.web {
+        return web::plan(settings, platform, info, input, output, temp);
+    }
+    if let Some(speech) = &settings.speech {
+        let mut job = speech::plan(settings, speech, info, input, output, temp);
+        coverart::plan(&mut job, settings, speech.codec.ext(), info, input, output);
+        return job;
+    }
+    if let Some(album) = &settings.album {
+        let mut job = album::plan(settings, album, info, input, output);
+        coverart::plan(&mut job, settings, album.codec.ext(), info, input, output);
+        return job;
+    }
+    if settings.ladder_enabled && !settings.remux && is_video_container(&settings.format) && !settings.ladder.is_empty() {
+        return ladder::plan(settings, info, input, output);
+    }
+    let args = build_args(settings, info, input, output);
+    let mut job = JobPlan {
+        runs: if two_pass(settings, input) { two_pass_runs(settings, args, temp) } else { vec![args] },
+        outputs: vec![output.to_string()],
+        ..Default::default()
+    };
+    if settings.two_pass && !two_pass(settings, input) {
+        job.notes.push(two_pass_ignored(settings, input).to_string());
+    }
+    // 进度和剩余时间按变速后的时长算
+    if let Some(retime) = settings.retime
+        && info.duration > 0.0
+    {
+        job.run_secs = Some(retime.scale_secs(trimmed_secs(settings, input, info.duration)));
+    }
+    if !is_video_container(&settings.format) && !settings.remux {
+        coverart::plan(&mut job, settings, &settings.format, info, input, output);
+    }
+    dataloss::plan(&mut job, settings, info, input, output);
+    job
+}
+
+// 附加参数：界面上没有的选项，原样放在每次调用的输出路径前面。
+// 和界面生成的参数重复时按 ffmpeg 的规则以后出现的为准
+pub(crate) fn extra_args(settings: &JobSettings) -> Vec<String> {
+    args::split_extra(&settings.extra_args).unwrap_or_default()
+}
+
+// 开始前检查：引号没有配对时不启动
+pub fn check_extra_args(settings: &JobSettings) -> Result<(), String> {
+    args::split_extra(&settings.extra_args).map(|_| ())
+}
+
+// 两遍编码是否生效：按目标码率、编码器支持，输入还要能从头再读一遍
+pub(crate) fn two_pass(settings: &JobSettings, input: &str) -> bool {
+    settings.two_pass && settings.quality.mode == RateMode::Bitrate && !settings.remux && is_video_container(&settings.format)
+        && quality::supports_two_pass(video_codec(settings)) && !live::is_live(input)
+}
+
+fn two_pass_ignored(settings: &JobSettings, input: &str) -> &'static str {
+    if settings.remux || !is_video_container(&settings.format) {
+        "两遍编码: 输出不重新编码视频，已忽略"
+    } else if settings.quality.mode != RateMode::Bitrate {
+        "两遍编码: 只在按目标码率时使用，已忽略"
+    } else if live::is_live(input) {
+        "两遍编码: 实时输入读不了两遍，已忽略"
+    } else {
+        "两遍编码: 当前编码器不支持，已忽略"
+    }
+}
+
+// 第一遍只编码视频、统计写到临时目录，输出丢弃；第二遍按统计分配码率，写出真正的文件。
+// 进度按调用次数平分，正好各占一半。附加参数也要影响第一遍的码率分配，放在 -f null 前面
+fn two_pass_runs(settings: &JobSettings, mut args: Args, temp: &Path) -> Vec<Args> {
+    let passlog = tempfiles::join(temp, "pass");
+    let output = args.pop().unwrap_or_default();
+    let mut first = args.clone();
+    first.push_all(Source::Extra, extra_args(settings));
+    first.push(Source::TwoPass, &["-pass", "1", "-passlogfile", &passlog, "-an", "-sn", "-dn", "-f", "null", web::null_output()]);
+    let mut second = args;
+    second.push(Source::TwoPass, &["-pass", "2", "-passlogfile", &passlog]);
+    second.push(Source::Output, &[&output]);
+    vec![first, second]
+}
+
+// 用同样的参数只编码前 secs 秒，输出到单个临时文件
+fn plan_preview(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str, temp: &Path, secs: u32) -> JobPlan {
+    let mut settings = settings.clone();
+    settings.overwrite = true;
+    let mut notes = Vec::new();
+    if settings.web.is_none() && settings.ladder_enabled && is_video_container(&settings.format) && !settings.ladder.is_empty() {
+        notes.push("预览只生成单个输出，已忽略多分辨率设置".to_string());
+    }
+    let mut job = match settings.web {
+        Some(platform) => web::plan(&settings, platform, info, input, output, temp),
+        None => JobPlan {
+            runs: vec![build_args(&settings, info, input, output)],
+            outputs: vec![output.to_string()],
+            ..Default::default()
+        },
+    };
+    // -t 是输出选项，放在输出路径前面
+    for args in &mut job.runs {
+        args.insert(args.len() - 1, Source::Preview, secs.to_string());
+        args.insert(args.len() - 2, Source::Preview, "-t".to_string());
+    }
+    job.notes.extend(notes);
+    if info.duration > 0.0 {
+        let duration = trimmed_secs(&settings, input, info.duration);
+        let duration = if settings.web.is_none() && let Some(retime) = settings.retime { retime.scale_secs(duration) } else { duration };
+        job.run_secs = Some(duration.min(secs as f64));
+    }
+    job
+}
+
+// 全局参数、硬件解码和输入部分
+pub(crate) fn input_args(settings: &JobSettings, input: &str) -> Args {
+    let mut args = Args::new();
+    // 用 -y/-n 明确覆盖策略，避免 ffmpeg 在无窗口时等待 y/N 回答而卡住；
+    // stdin 留给“停止并保留”发送 q，其他分析用的 ffmpeg 一律 -nostdin
+    args.push(Source::Base, &["-progress", "pipe:1", "-nostats"]);
+    args.push(Source::Overwrite, &[if settings.overwrite { "-y" } else { "-n" }]);
+
+    // 音频输出不解码视频，硬件解码用不上
+    match settings.gpu.as_str() {
+        _ if !is_video_container(&settings.format) => {}
+        "NVIDIA" => args.push(Source::Device, &["-hwaccel", "cuda"]),
+        "Intel" => args.push(Source::Device, &["-hwaccel", "qsv"]),
+        "AMD" => args.push(Source::Device, &["-hwaccel", "dxva2"]),
+        _ => {}
+    }
+
+    args.push_all(Source::ProbeDepth, settings.probe_depth.args());
+    args.push_all(Source::Fixes, warnings::input_args(&settings.fixes));
+    if !settings.input_format.trim().is_empty() {
+        args.push(Source::InputFormat, &["-f", settings.input_format.trim()]);
+    }
+    // 都作为输入选项放在 -i 前面，按源文件的时间算；-ss 之后的输出选项 -to 会从切点重新计时
+    if trims(settings, input) {
+        if let Some(start) = settings.trim_start {
+            args.push(Source::Trim, &["-ss", &format!("{:.3}", start.as_secs_f64())]);
+        }
+        if let Some(end) = settings.trim_end {
+            args.push(Source::Trim, &["-to", &format!("{:.3}", end.as_secs_f64())]);
+        }
+    }
+    args.push(Source::Input, &["-i", input]);
+    args
+}
+
+// 实时输入没法定位；语音优化按整个文件检测首尾静音，专辑按 cue 切分，自己决定范围
+pub(crate) fn trims(settings: &JobSettings, input: &str) -> bool {
+    (settings.trim_start.is_some() || settings.trim_end.is_some()) && !live::is_live(input) && settings.speech.is_none() && settings.album.is_none()
+}
+
+// 裁剪后要转换的时长，进度和剩余时间按它算；不裁剪时就是 duration
+pub(crate) fn trimmed_secs(settings: &JobSettings, input: &str, duration: f64) -> f64 {
+    if !trims(settings, input) {
+        return duration;
+    }
+    let start = settings.trim_start.map_or(0.0, |d| d.as_secs_f64());
+    let end = settings.trim_end.map_or(duration, |d| d.as_secs_f64().min(duration));
+    (end - start).max(0.0)
+}
+
+// 开始前检查：结束时间要晚于开始时间，开始时间不能超过片长
+pub fn check_trim(settings: &JobSettings, info: &MediaInfo) -> Result<(), String> {
+    if let (Some(start), Some(end)) = (settings.trim_start, settings.trim_end)
+        && end <= start
+    {
+        return Err(format!("结束时间 {} 不晚于开始时间 {}", timestamp::format(end), timestamp::format(start)));
+    }
+    if let Some(start) = settings.trim_start
+        && info.duration > 0.0
+        && start.as_secs_f64() >= info.duration
+    {
+        return Err(format!("开始时间 {} 超过片长 {}", timestamp::format(start), timestamp::format(Duration::from_secs_f64(info.duration))));
+    }
+    Ok(())
+}
+
+// 设备上没有这种编码时按 CPU 算，和 resolve 的回退一致
+pub(crate) fn video_codec(settings: &JobSettings) -> &'static str {
+    let soft = settings.av1.encoder;
+    settings.codec.encoder(&settings.gpu, soft).or_else(|| settings.codec.encoder("CPU", soft)).unwrap_or("libx264")
+}
+
+// 源文件的时间码能原样写进输出时返回
+pub(crate) fn output_timecode(settings: &JobSettings, info: &MediaInfo) -> Option<Timecode> {
+    if !matches!(settings.format.as_str(), "mov" | "mp4" | "mkv") || settings.deinterlace == Deinterlace::Ivtc || settings.retime.is_some()
+        || output_fps_override(settings).is_some()
+    {
+        return None;
+    }
+    let tc = timecode::from_info(info)?;
+    let video = info.streams.iter().find(|s| s.codec_type == "video")?;
+    let fps = video.props.get("avg_frame_rate").and_then(|r| probe::parse_rate(r))
+        .or_else(|| video.props.get("r_frame_rate").and_then(|r| probe::parse_rate(r)));
+    if !fps.is_none_or(|fps| tc.fits_rate(fps)) {
+        return None;
+    }
+    // 裁掉开头时时间码跟着往后推，输出的第一帧仍对应源文件同一帧
+    match (settings.trim_start, fps) {
+        (None, _) => Some(tc),
+        (Some(start), Some(fps)) => Some(tc.add_frames((start.as_secs_f64() * fps).round() as u64, fps)),
+        (Some(_), None) => None,
+    }
+}
+
+// 放在输出路径前面：针对警告的修正参数和录制时间
+pub(crate) fn output_tail_args(settings: &JobSettings) -> Args {
+    let mut args = Args::new();
+    args.push_all(Source::Fixes, warnings::output_args(&settings.fixes));
+    if let Some(date) = &settings.creation_time {
+        args.push(Source::Dates, &["-metadata", &format!("creation_time={}", date)]);
+    }
+    args
+}
+
+// 紧跟在 -c:v 后面的编码参数。with_crf 为 false 时码率由调用方决定（多分辨率），不加质量参数；
+// 画质选了按质量或按码率时，AV1 和 VP9 不再用各自的默认 CRF
+pub(crate) fn video_codec_args(settings: &JobSettings, with_crf: bool) -> Args {
+    let mut args = Args::new();
+    let codec = video_codec(settings);
+    let own_crf = with_crf && settings.quality.mode == RateMode::Auto;
+    match codec {
+        _ if settings.codec == VideoCodec::Av1 && settings.gpu == "CPU" => args.push_all(Source::Quality, settings.av1.args(own_crf)),
+        // libvpx 默认按很低的目标码率编码，要用 -b:v 0 打开恒定质量；默认速度又极慢
+        "libvpx-vp9" => {
+            if own_crf {
+                args.push(Source::Quality, &["-crf", "31", "-b:v", "0"]);
+            }
+            args.push(Source::Quality, &["-row-mt", "1", "-cpu-used", "4"]);
+        }
+        _ => {}
+    }
+    if with_crf {
+        args.push_all(Source::Quality, settings.quality.args(codec));
+    }
+    args.push_all(Source::Preset, preset::args(codec, settings.preset.as_deref(), settings.tune.as_deref()));
+    // mp4/mov 里的 HEVC 标成 hvc1，苹果的播放器才认
+    if settings.codec == VideoCodec::Hevc && matches!(settings.format.as_str(), "mp4" | "mov") {
+        args.push(Source::Codec, &["-tag:v", "hvc1"]);
+    }
+    args.push_all(Source::Gop, gop::args(video_codec(settings), settings.gop));
+    args
+}
+
+// 一键方案和多分辨率自己决定输出尺寸
+pub(crate) fn own_size(settings: &JobSettings) -> bool {
+    settings.web.is_none() && !settings.ladder_enabled
+}
+
+// 生效的输出帧率：帧率重映射自己用 -r 改帧率，截图按间隔取帧
+pub(crate) fn output_fps_override(settings: &JobSettings) -> Option<Rate> {
+    settings.fps.filter(|_| own_size(settings) && settings.retime.is_none() && settings.snapshot.is_none())
+}
+
+// 滤镜链里的一步，这一步没有启用时返回 None
+pub(crate) fn step_filter(settings: &JobSettings, step: FilterStep) -> Option<String> {
+    match step {
+        FilterStep::Deinterlace => settings.deinterlace.filter().map(|f| f.to_string()),
+        FilterStep::FrameRate => output_fps_override(settings).map(framerate::filter),
+        FilterStep::PixelAspect => settings.pixel_filter.clone(),
+        FilterStep::Subtitles if !settings.subtitle_file.is_empty() => {
+            let mut filter = format!("subtitles={}", subtitle::escape_filter_path(&settings.subtitle_file));
+            // 没能转成 UTF-8 副本时让 ffmpeg 自己按指定编码读取
+            if let Some(enc) = settings.subtitle_encoding.as_deref().filter(|e| !e.eq_ignore_ascii_case("UTF-8")) {
+                filter.push_str(&format!(":charenc={}", enc));
+            }
+            Some(filter)
+        }
+        FilterStep::Subtitles => None,
+        FilterStep::Fit => settings.fit.filter(),
+        // 一键方案和多分辨率自己缩放
+        FilterStep::Scale => settings.resolution.filter().filter(|_| own_size(settings)),
+    }
+}
+
+// 按顺序应用的视频滤镜。默认先丢帧再做后面的滤镜，降帧率时少处理一些帧；
+// 先修正像素比，字幕按显示比例渲染；补边在字幕之后，字幕落在原画面上；补边后再缩放，比例不变
+pub(crate) fn video_filters(settings: &JobSettings) -> Vec<String> {
+    let mut filters: Vec<String> = filterchain::effective(&settings.filter_order)
+        .into_iter()
+        .filter_map(|step| step_filter(settings, step))
+        .collect();
+    // 硬件编码器的上限按缩放后的大小算，所以硬件缩放总在最后；多分辨率的缩放在这之后
+    if let Some((w, h)) = settings.hw_scale {
+        filters.push(format!("scale={}:{},setsar=1", w, h));
+    }
+    filters
+}
+
+// 限制写入速度的方式：目前单个输出的任务都会重新编码视频；音频输出码率很低，实时输入本身就是实时速度
+pub(crate) fn throttle(settings: &JobSettings, info: &MediaInfo, input: &str) -> Option<Mechanism> {
+    if settings.write_limit_mb <= 0.0 || live::is_live(input) || !is_video_container(&settings.format) {
+        return None;
+    }
+    Some(Mechanism::encode(settings.write_limit_mb, throttle::source_bits(info, input)))
+}
+
+// 仅转换封装时输出码率就是源码率，只能放慢读取；不知道源码率时不限速
+pub(crate) fn remux_throttle(settings: &JobSettings, info: &MediaInfo, input: &str) -> Option<Mechanism> {
+    if settings.write_limit_mb <= 0.0 || live::is_live(input) {
+        return None;
+    }
+    Mechanism::copy(settings.write_limit_mb, throttle::source_bits(info, input))
+}
+
+// 限速的日志说明，知道时长时带上限速下的最短耗时
+fn throttle_note(mechanism: &Mechanism, limit_mb: f64, duration: f64) -> String {
+    if duration > 0.0 {
+        format!("{}，至少需要 {}", mechanism.describe(limit_mb), inspect::format_duration(mechanism.min_secs(duration)))
+    } else {
+        mechanism.describe(limit_mb)
+    }
+}
+
+// -readrate 是输入选项，放在 -i 前面
+fn insert_readrate(args: &mut Args, mechanism: &Mechanism) {
+    if let Some(at) = args.position("-i") {
+        args.insert(at, Source::Throttle, mechanism.readrate_arg());
+        args.insert(at, Source::Throttle, "-readrate".to_string());
+    }
+}
+
+// 仅转换封装：-map 0 带上所有流（包括字幕、章节和附件），-c copy 不重新编码。
+// 流复制不解码，不加硬件解码参数；进度照样来自 -progress，runner 按写入的字节算
+fn remux_args(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str) -> Args {
+    let mut args = input_args(&JobSettings { gpu: "CPU".to_string(), ..settings.clone() }, input);
+    if let Some(mechanism) = remux_throttle(settings, info, input) {
+        insert_readrate(&mut args, &mechanism);
+    }
+    args.push(Source::Remux, &["-map", "0", "-c", "copy"]);
+    args.append(dataloss::remux_args(settings, info));
+    args.append(output_tail_args(settings));
+    args.push(Source::Output, &[output]);
+    args
+}
+
+// 根据设置和探测结果生成单个输出的 ffmpeg 参数（不含程序名），不依赖界面状态
+pub fn build_args(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str) -> Args {
+    if settings.remux {
+        return remux_args(settings, info, input, output);
+    }
+    let lengths = if is_video_container(&settings.format) { lengths::args(settings.length_policy, info) } else { Default::default() };
+    // 音频对齐的修正、补静音和变速并进同一条 -af，不然后面的 -af 会覆盖前面的。
+    // 补静音按源文件的时长算，放在变速前面
+    let mut settings = settings.clone();
+    let mut audio_filters = Vec::new();
+    if (lengths.audio_filter.is_some() || settings.retime.is_some())
+        && let Some(at) = settings.fixes.iter().position(|f| f == "async")
+    {
+        settings.fixes.remove(at);
+        audio_filters.push("aresample=async=1".to_string());
+    }
+    audio_filters.extend(lengths.audio_filter);
+    if let Some(retime) = settings.retime {
+        audio_filters.push(retime.audio_filter(retime::sample_rate(info)));
+    }
+    if let Some(target) = settings.loudnorm {
+        audio_filters.push(loudness::normalize_filter(target, retime::sample_rate(info)));
+    }
+    let settings = &settings;
+    let mut args = input_args(settings, input);
+    let throttle = throttle(settings, info, input);
+    if let Some(mechanism) = &throttle {
+        insert_readrate(&mut args, mechanism);
+    }
+    // 音频输出没有内嵌封面时用源文件旁边的图片，多一个输入
+    let art = if is_video_container(&settings.format) { None } else { coverart::pick(info, input) };
+    args.append(coverart::input_args(art.as_ref(), &settings.format));
+
+    let per_stream_audio = settings.keep_all_audio && is_video_container(&settings.format);
+    let audio = if per_stream_audio { plan_audio(settings, info) } else { Vec::new() };
+    if per_stream_audio {
+        args.push(Source::Tracks, &["-map", "0:V?"]);
+        for a in &audio {
+            args.push(Source::Tracks, &["-map", &format!("0:a:{}", a.input_index)]);
+        }
+    }
+    if is_video_container(&settings.format) {
+        args.append(dataloss::stream_args(settings, info, per_stream_audio));
+    }
+
+    // 补最后一帧放在最后，补的是处理过的画面；变速在补帧之后，补帧时长按源文件算
+    let mut filters = video_filters(settings);
+    // HDR 转 SDR 要在其他滤镜之前，后面的滤镜按 SDR 画面处理
+    if let Some(tonemap) = dataloss::tonemap(settings, info) {
+        filters.insert(0, tonemap.to_string());
+    }
+    // 按源文件时间烧录放在变速前，按输出时间放在最后
+    let burn = burnin::active(settings).filter(|_| is_video_container(&settings.format));
+    if let Some(burn) = burn.filter(|b| b.clock == Clock::Source) {
+        filters.push(burnin::filter(burn, settings, info));
+    }
+    filters.extend(lengths.video_filter);
+    let retime = settings.retime.filter(|_| is_video_container(&settings.format));
+    if let Some(retime) = retime {
+        filters.push(retime.video_filter());
+    }
+    if let Some(burn) = burn.filter(|b| b.clock == Clock::Output) {
+        filters.push(burnin::filter(burn, settings, info));
+    }
+    if !filters.is_empty() && is_video_container(&settings.format) {
+        args.push(Source::Filters, &["-vf", &filters.join(",")]);
+    }
+    // 不指定输出帧率时 ffmpeg 会按源帧率复制帧来填满放慢后的时间
+    if let Some(retime) = retime {
+        args.push(Source::Retime, &["-r", &retime.to.tag()]);
+    }
+    if !audio_filters.is_empty() {
+        let source = match settings.retime {
+            Some(_) => Source::Retime,
+            None if settings.loudnorm.is_some() => Source::Loudnorm,
+            None => Source::Lengths,
+        };
+        args.push(source, &["-af", &audio_filters.join(",")]);
+    }
+
+    // 音频输出不编码视频，只在能嵌入时带上封面
+    if is_video_container(&settings.format) {
+        args.push(Source::Codec, &["-c:v", video_codec(settings)]);
+        args.append(video_codec_args(settings, true));
+        if let Some(mechanism) = &throttle {
+            args.append(mechanism.output_args());
+        }
+        // 保留所有音轨时按轨道分别指定；自动时交给 ffmpeg 按容器选
+        if !per_stream_audio {
+            match settings.audio_codec {
+                AudioCodec::Auto => {}
+                AudioCodec::Copy => args.push(Source::AudioCodec, &["-c:a", "copy"]),
+                _ => audio_codec_args(&mut args, settings, Source::AudioCodec),
+            }
+        }
+    } else {
+        args.append(coverart::embed_args(art.as_ref(), &settings.format, 1));
+        // 明确指定音频编码器，不依赖 ffmpeg 按扩展名猜
+        match settings.audio_codec {
+            AudioCodec::Auto => audio_codec_args(&mut args, settings, Source::Tracks),
+            AudioCodec::Copy => args.push(Source::AudioCodec, &["-c:a", "copy"]),
+            _ => audio_codec_args(&mut args, settings, Source::AudioCodec),
+        }
+    }
+    for a in &audio {
+        let index = a.output_index;
+        match a.codec {
+            None => args.push(Source::Tracks, &[&format!("-c:a:{}", index), "copy"]),
+            Some(c) if lossless_audio(c) => args.push(Source::Tracks, &[&format!("-c:a:{}", index), c]),
+            Some(c) => args.push(Source::Tracks, &[
+                &format!("-c:a:{}", index), c,
+                &format!("-b:a:{}", index), &audio_bitrate(settings),
+            ]),
+        }
+    }
+
+    if let Some(aspect) = &settings.display_aspect {
+        args.push(Source::Aspect, &["-aspect", aspect]);
+    }
+
+    // mov/mp4 写 tmcd 轨，mkv 没有时间码轨，只能写成标签
+    if let Some(tc) = output_timecode(settings, info) {
+        if settings.format == "mkv" {
+            args.push(Source::Timecode, &["-metadata", &format!("timecode={}", tc)]);
+        } else {
+            args.push(Source::Timecode, &["-timecode", &tc.to_string()]);
+        }
+    }
+
+    if lengths.shortest {
+        args.push(Source::Lengths, &["-shortest"]);
+    }
+
+    args.append(output_tail_args(settings));
+    args.push(Source::Output, &[output]);
+    args
+}
+
+fn quote(arg: &str) -> String {
+    // ; 会被 shell 当作命令分隔符（丢帧时间码、滤镜图里都有）
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ';')) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
+
+// 拼成一行便于复制到终端
+pub fn quote_command(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(|a| a.as_str()))
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+
+    fn media(codecs: &[(&str, &str)]) -> MediaInfo {
+        let streams = codecs
+            .iter()
+            .map(|(kind, codec)| StreamInfo { codec_type: kind.to_string(), codec_name: codec.to_string(), ..Default::default() })
+            .collect();
+        MediaInfo { streams, ..Default::default() }
+    }
+
+    #[test]
+    fn dropped_tracks_renumber_outputs() {
+        // 视频夹在音轨中间，音轨序号只按音轨数
+        let info = media(&[("video", "h264"), ("audio", "truehd"), ("audio", "aac"), ("video", "mjpeg"), ("audio", "ac3")]);
+        let settings = JobSettings {
+            keep_all_audio: true,
+            audio_tracks: vec![TrackChoice::Drop, TrackChoice::Auto, TrackChoice::Transcode],
+            ..Default::default()
+        };
+        let plan = plan_audio(&settings, &info);
+        let indices: Vec<(usize, usize)> = plan.iter().map(|a| (a.input_index, a.output_index)).collect();
+        assert_eq!(indices, [(1, 0), (2, 1)]);
+        assert_eq!(plan[0].codec, None);
+        assert_eq!(plan[1].codec, Some("aac"));
+        assert!(plan.iter().all(|a| !a.forced));
+
+        let argv = build_args(&settings, &info, "in.mkv", "out.mp4").argv().join(" ");
+        assert!(argv.contains("-map 0:a:1 -map 0:a:2"), "{}", argv);
+        assert!(argv.contains("-c:a:0 copy") && argv.contains("-c:a:1 aac -b:a:1"), "{}", argv);
+        assert!(!argv.contains("0:a:0") && !argv.contains("-c:a:2"), "{}", argv);
+    }
+
+    #[test]
+    fn copy_into_incompatible_container_is_forced() {
+        let info = media(&[("audio", "truehd"), ("audio", "aac")]);
+        let settings = JobSettings { keep_all_audio: true, audio_tracks: vec![TrackChoice::Copy, TrackChoice::Copy], ..Default::default() };
+        let plan = plan_audio(&settings, &info);
+        assert_eq!((plan[0].codec, plan[0].forced), (Some("aac"), true));
+        assert_eq!((plan[1].codec, plan[1].forced), (None, false));
+        // mkv 什么都能放
+        let mkv = JobSettings { format: "mkv".to_string(), ..settings };
+        assert!(plan_audio(&mkv, &info).iter().all(|a| a.codec.is_none() && !a.forced));
+    }
+
+    #[test]
+    fn missing_choices_default_to_auto_and_filters_force_encoding() {
+        let info = media(&[("audio", "aac"), ("audio", "opus")]);
+        let settings = JobSettings { keep_all_audio: true, ..Default::default() };
+        assert!(plan_audio(&settings, &info).iter().all(|a| a.codec.is_none()));
+        // 加了音频滤镜就不能直接复制
+        let filtered = JobSettings { fixes: vec!["async".to_string()], ..settings };
+        assert!(plan_audio(&filtered, &info).iter().all(|a| a.codec == Some("aac") && !a.forced));
+    }
+
+    #[test]
+    fn all_dropped_leaves_no_audio() {
+        let info = media(&[("audio", "aac"), ("audio", "ac3")]);
+        let settings = JobSettings { keep_all_audio: true, audio_tracks: vec![TrackChoice::Drop; 2], ..Default::default() };
+        assert!(plan_audio(&settings, &info).is_empty());
+    }
+
+    // 附加参数只加在编码的调用上，拼接取样的 -c copy 调用不加
+    #[test]
+    fn extra_args_skip_the_sample_concat() {
+        let info = MediaInfo { duration: 100.0, ..media(&[("video", "h264"), ("audio", "aac")]) };
+        let settings = JobSettings {
+            preview_secs: Some(4),
+            preview_samples: 3,
+            extra_args: "-metadata title=x".to_string(),
+            ..Default::default()
+        };
+        let job = plan_job(&settings, &info, "in.mkv", "out.mp4", Path::new("/tmp"));
+        assert_eq!(job.runs.len(), 4);
+        assert_eq!(job.copy_runs, [3]);
+        for run in &job.runs[..3] {
+            let argv = run.argv();
+            assert_eq!(argv[argv.len() - 3..argv.len() - 1], ["-metadata", "title=x"]);
+        }
+        let concat = job.runs[3].argv().join(" ");
+        assert!(concat.ends_with("-c copy out.mp4") && !concat.contains("-metadata"), "{}", concat);
+    }
+
+    #[test]
+    fn extra_args_skip_the_cover_file() {
+        let mut info = MediaInfo { duration: 100.0, ..media(&[("audio", "flac"), ("video", "mjpeg")]) };
+        info.streams[1].props.insert("disposition.attached_pic".to_string(), "1".to_string());
+        let settings = JobSettings { format: "wav".to_string(), cover_file: true, extra_args: "-ac 2".to_string(), ..Default::default() };
+        let dir = std::env::temp_dir().join(format!("ffui_plan_test_cover_{}", std::process::id()));
+        let output = dir.join("track.wav").to_string_lossy().into_owned();
+        let job = plan_job(&settings, &info, "in.flac", &output, &dir);
+        assert_eq!((job.runs.len(), job.copy_runs.as_slice()), (2, [1].as_slice()));
+        assert!(job.runs[0].argv().join(" ").ends_with(&format!("-ac 2 {}", output)));
+        assert!(!job.runs[1].argv().contains(&"-ac".to_string()));
+    }
+
+    // 总码率 20 Mbps、时长 100 秒的源文件
+    fn twenty_megabits() -> MediaInfo {
+        let mut info = media(&[("video", "h264"), ("audio", "aac")]);
+        info.duration = 100.0;
+        info.format.insert("bit_rate".to_string(), "20000000".to_string());
+        info
+    }
+
+    #[test]
+    fn remux_with_a_write_limit_slows_the_read() {
+        let info = twenty_megabits();
+        let mut settings = JobSettings { remux: true, format: "mkv".to_string(), write_limit_mb: 4.0, ..Default::default() };
+        // 4 MB/s = 32 Mbps，源码率 20 Mbps，读取不超过 1.6 倍实时
+        let argv = build_args(&settings, &info, "in.mp4", "out.mkv").argv().join(" ");
+        assert!(argv.contains("-readrate 1.600 -i in.mp4 -map 0 -c copy"), "{}", argv);
+        assert!(!argv.contains("-maxrate"), "{}", argv);
+        let notes = resolve(&mut settings, "in.mp4", &info);
+        assert!(notes.iter().any(|n| n.contains("流复制") && n.contains("1.60 倍") && n.contains("至少需要")), "{:?}", notes);
+    }
+
+    #[test]
+    fn remux_without_a_bitrate_ignores_the_limit() {
+        let mut info = media(&[("video", "h264")]);
+        info.duration = 100.0;
+        let mut settings = JobSettings { remux: true, format: "mkv".to_string(), write_limit_mb: 4.0, ..Default::default() };
+        let argv = build_args(&settings, &info, "missing.mp4", "out.mkv").argv().join(" ");
+        assert!(!argv.contains("-readrate"), "{}", argv);
+        let notes = resolve(&mut settings, "missing.mp4", &info);
+        assert!(notes.iter().any(|n| n.contains("不知道源文件的码率") && n.contains("已忽略")), "{:?}", notes);
+    }
+
+    #[test]
+    fn write_limit_places_readrate_and_maxrate() {
+        let info = twenty_megabits();
+        let settings = JobSettings { write_limit_mb: 10.0, ..Default::default() };
+        let argv = build_args(&settings, &info, "in.mkv", "out.mp4").argv();
+        let at = |flag: &str| argv.iter().position(|a| a == flag).unwrap_or_else(|| panic!("{} {:?}", flag, argv));
+        // 80 Mbps 的上限：视频码率不超过源的 20 Mbps，处理速度 80 / 20.32 倍实时
+        assert_eq!(argv[at("-readrate") + 1], "3.937");
+        assert_eq!(at("-readrate") + 2, at("-i"));
+        assert_eq!(argv[at("-maxrate") + 1], "20000000");
+        assert_eq!(argv[at("-bufsize") + 1], "40000000");
+        assert!(at("-maxrate") > at("-c:v") && at("-bufsize") > at("-maxrate"));
+        // 音频输出不限速
+        let audio = JobSettings { format: "mp3".to_string(), ..settings };
+        assert!(!build_args(&audio, &info, "in.mkv", "out.mp3").argv().iter().any(|a| a == "-readrate"));
+    }
+}
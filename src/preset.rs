@@ -0,0 +1,40 @@
+// 编码器的速度档位（-preset）和调优（-tune）。各编码器的取值不同，界面按当前编码器列出；
+// 编码器没有的选项不提供，没选时不加参数，用编码器的默认值。
+// CPU 编码 AV1 的速度在 AV1 参数里单独设置，VP9 固定用 -cpu-used，这里都不提供
+
+// 档位从快到慢排列
+pub fn presets(encoder: &str) -> &'static [&'static str] {
+    match encoder {
+        "libx264" | "libx265" => &["ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow"],
+        e if e.ends_with("_nvenc") => &["p1", "p2", "p3", "p4", "p5", "p6", "p7"],
+        e if e.ends_with("_qsv") => &["veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow"],
+        e if e.ends_with("_amf") => &["speed", "balanced", "quality"],
+        _ => &[],
+    }
+}
+
+pub fn tunes(encoder: &str) -> &'static [&'static str] {
+    match encoder {
+        "libx264" => &["film", "animation", "grain", "stillimage", "fastdecode", "zerolatency"],
+        "libx265" => &["animation", "grain", "fastdecode", "zerolatency"],
+        e if e.ends_with("_nvenc") => &["hq", "ll", "ull", "lossless"],
+        _ => &[],
+    }
+}
+
+// AMF 的档位参数叫 -quality
+fn preset_flag(encoder: &str) -> &'static str {
+    if encoder.ends_with("_amf") { "-quality" } else { "-preset" }
+}
+
+// 换了设备或编码后不再适用的值直接忽略
+pub fn args(encoder: &str, preset: Option<&str>, tune: Option<&str>) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(p) = preset.filter(|p| presets(encoder).contains(p)) {
+        args.extend([preset_flag(encoder).to_string(), p.to_string()]);
+    }
+    if let Some(t) = tune.filter(|t| tunes(encoder).contains(t)) {
+        args.extend(["-tune".to_string(), t.to_string()]);
+    }
+    args
+}
@@ -0,0 +1,415 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::autopreset::Rule;
+use crate::config;
+use crate::joblist;
+use crate::json::Value;
+use crate::paths;
+use crate::plan::JobSettings;
+use crate::web::Platform;
+
+// 用户保存的命名预设（和 preset.rs 里编码器的速度档位不是一回事），存成 presets.toml：
+//   [presets."phone-1080p-h265"]
+//   format = "mp4"
+//   codec = "h265"
+// 键名和任务列表里的设置相同，只有常用的这些；其他设置（输出、裁剪、钩子等）和具体文件有关，不放进预设。
+// 自动套用的匹配规则（autopreset.rs）以 match_ 开头，写在同一个表里
+const KEYS: &[&str] = &[
+    "format",
+    "gpu",
+    "codec",
+    "av1_preset",
+    "av1_crf",
+    "av1_film_grain",
+    "av1_tune",
+    "quality_mode",
+    "quality_level",
+    "quality_bitrate_k",
+    "two_pass",
+    "preset",
+    "tune",
+    "remux",
+    "resolution",
+    "fps",
+    "deinterlace",
+    "filter_order",
+    "audio_codec",
+    "audio_bitrate_k",
+    "loudnorm",
+    "keep_all_audio",
+    "data_loss",
+    "extra_args",
+    "ffmpeg",
+    "web",
+];
+
+const TABLE: &str = "presets";
+// 上次使用的设置单独存一个文件，格式相同，只有这一个预设
+const LAST_USED: &str = "last-used";
+
+#[derive(Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    // 只有 KEYS 里的项，值为空（null）的不写
+    values: Vec<(String, Value)>,
+    // 载入文件时按这个规则自动套用，空规则不自动套用
+    pub rule: Rule,
+}
+
+impl Preset {
+    pub fn capture(name: &str, settings: &JobSettings) -> Preset {
+        let all = joblist::settings_to_value(settings);
+        let values = KEYS
+            .iter()
+            .filter_map(|key| all.get(key).filter(|v| !matches!(v, Value::Null)).map(|v| (key.to_string(), v.clone())))
+            .collect();
+        Preset { name: name.to_string(), values, rule: Rule::default() }
+    }
+
+    // 只改预设里有的那几项，其余设置保持不变；文件里缺少的项用默认值
+    pub fn apply(&self, settings: &mut JobSettings) -> Result<(), String> {
+        let from = joblist::settings_from_value(&Value::Obj(self.values.clone()))
+            .map_err(|e| format!("预设“{}”无法使用: {}", self.name, e))?;
+        settings.format = from.format;
+        settings.gpu = from.gpu;
+        settings.codec = from.codec;
+        settings.av1 = from.av1;
+        settings.quality = from.quality;
+        settings.two_pass = from.two_pass;
+        settings.preset = from.preset;
+        settings.tune = from.tune;
+        settings.remux = from.remux;
+        settings.resolution = from.resolution;
+        settings.fps = from.fps;
+        settings.deinterlace = from.deinterlace;
+        settings.filter_order = from.filter_order;
+        settings.audio_codec = from.audio_codec;
+        settings.audio_bitrate_k = from.audio_bitrate_k;
+        settings.loudnorm = from.loudnorm;
+        settings.keep_all_audio = from.keep_all_audio;
+        settings.data_loss = from.data_loss;
+        settings.extra_args = from.extra_args;
+        settings.ffmpeg = from.ffmpeg;
+        settings.web = from.web;
+        Ok(())
+    }
+
+    // 预设单独展开成完整的设置，其他项是默认值。两个预设之间比较用
+    pub fn settings(&self) -> Result<JobSettings, String> {
+        let mut settings = JobSettings::default();
+        self.apply(&mut settings)?;
+        Ok(settings)
+    }
+}
+
+// 内置预设：发到各个平台的一键方案，名称和 --web 的参数对应。
+// 不写进 presets.toml，不能删除，也不能用同名的预设覆盖
+pub fn builtin() -> Vec<Preset> {
+    Platform::ALL
+        .into_iter()
+        .map(|platform| {
+            let settings = JobSettings { format: "mp4".to_string(), web: Some(platform), ..Default::default() };
+            Preset::capture(&format!("web-{}", platform.tag()), &settings)
+        })
+        .collect()
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    name.strip_prefix("web-").and_then(Platform::from_tag).is_some()
+}
+
+// 内置的在前，然后是保存的
+pub fn all(saved: &[Preset]) -> Vec<Preset> {
+    builtin().into_iter().chain(saved.iter().filter(|p| !is_builtin(&p.name)).cloned()).collect()
+}
+
+pub fn presets_path() -> PathBuf {
+    paths::store_dir().join("presets.toml")
+}
+
+// 没有文件时是空列表；读不出的行跳过
+pub fn load() -> Vec<Preset> {
+    fs::read_to_string(presets_path()).map(|text| parse(&text)).unwrap_or_default()
+}
+
+pub fn save(presets: &[Preset]) -> io::Result<()> {
+    fs::create_dir_all(paths::store_dir())?;
+    store(&presets_path(), presets)
+}
+
+// 和设置文件一样先写临时文件再改名，旧文件读得出预设时留一份 .bak
+fn store(path: &Path, presets: &[Preset]) -> io::Result<()> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    config::store_with(path, Path::new(&backup), None, &format(presets), |old| !parse(old).is_empty())
+}
+
+fn last_used_path() -> PathBuf {
+    paths::store_dir().join("last-used.toml")
+}
+
+// 只记普通转换的设置：预览、截图、发到平台、语音优化、专辑音轨和快速操作是一次性的，
+// 格式也跟着各自的方案走，记下来会让下次打开时的默认格式莫名其妙
+pub fn remembered(settings: &JobSettings) -> bool {
+    settings.preview_secs.is_none()
+        && settings.snapshot.is_none()
+        && settings.web.is_none()
+        && settings.speech.is_none()
+        && settings.album.is_none()
+        && settings.quick.is_none()
+}
+
+// 没有或读不出时返回 None，调用方用默认设置
+pub fn load_last_used() -> Option<Preset> {
+    let text = fs::read_to_string(last_used_path()).ok()?;
+    parse(&text).into_iter().find(|p| p.name == LAST_USED)
+}
+
+pub fn save_last_used(settings: &JobSettings) -> io::Result<()> {
+    fs::create_dir_all(paths::store_dir())?;
+    store(&last_used_path(), &[Preset::capture(LAST_USED, settings)])
+}
+
+// 同名的覆盖，新的放在最后。重新保存设置时新预设没有规则，保留原来的匹配规则
+pub fn put(presets: &mut Vec<Preset>, mut preset: Preset) {
+    match presets.iter_mut().find(|p| p.name == preset.name) {
+        Some(old) => {
+            if preset.rule.is_empty() {
+                preset.rule = old.rule.clone();
+            }
+            *old = preset;
+        }
+        None => presets.push(preset),
+    }
+}
+
+fn format(presets: &[Preset]) -> String {
+    let mut out = String::from("# ffui 预设，一个表一个预设；认不出的项会被忽略\n");
+    for preset in presets {
+        out.push_str(&format!("\n[{}.{}]\n", TABLE, quote(&preset.name)));
+        for (key, value) in preset.values.iter().chain(&preset.rule.values()) {
+            let text = match value {
+                Value::Str(s) => quote(s),
+                Value::Bool(b) => b.to_string(),
+                Value::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+                Value::Num(n) => format!("{:?}", n),
+                _ => continue,
+            };
+            out.push_str(&format!("{} = {}\n", key, text));
+        }
+    }
+    out
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// 只认这里写出的那部分 TOML：表头、字符串、数字和布尔值。
+// 更新版本写的数组、日期等认不出的值，以及别的表，整行忽略，不影响其他预设
+fn parse(text: &str) -> Vec<Preset> {
+    let mut presets: Vec<Preset> = Vec::new();
+    let mut current: Option<usize> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            current = parse_header(header).map(|name| match presets.iter().position(|p| p.name == name) {
+                Some(i) => i,
+                None => {
+                    presets.push(Preset { name, values: Vec::new(), rule: Rule::default() });
+                    presets.len() - 1
+                }
+            });
+            continue;
+        }
+        let Some(i) = current else { continue };
+        let Some((key, rest)) = parse_key(line) else { continue };
+        let Some(value) = rest.trim_start().strip_prefix('=').and_then(|v| parse_value(v.trim())) else { continue };
+        if presets[i].rule.set(&key, &value) {
+            continue;
+        }
+        let values = &mut presets[i].values;
+        values.retain(|(k, _)| *k != key);
+        values.push((key, value));
+    }
+    presets
+}
+
+// [presets."名称"] 或 [presets.名称]，其他表返回 None
+fn parse_header(header: &str) -> Option<String> {
+    let rest = header.trim_start().strip_prefix(TABLE)?.trim_start().strip_prefix('.')?.trim_start();
+    let (name, tail) = parse_key(rest)?;
+    trailing_ok(tail.trim_start().strip_prefix(']')?).then_some(name)
+}
+
+// 开头的键（裸键或带引号的键）和剩下的部分
+fn parse_key(text: &str) -> Option<(String, &str)> {
+    if text.starts_with('"') || text.starts_with('\'') {
+        let (key, rest) = parse_string(text)?;
+        return Some((key, rest));
+    }
+    let end = text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(text.len());
+    (end > 0).then(|| (text[..end].to_string(), &text[end..]))
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if text.starts_with('"') || text.starts_with('\'') {
+        let (s, rest) = parse_string(text)?;
+        return trailing_ok(rest).then_some(Value::Str(s));
+    }
+    let end = text.find('#').unwrap_or(text.len());
+    let word = text[..end].trim();
+    match word {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => word.replace('_', "").parse::<f64>().ok().filter(|n| n.is_finite()).map(Value::Num),
+    }
+}
+
+// 值后面只能是空白或注释
+fn trailing_ok(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+// 单行的基本字符串（"…"，支持转义）或字面字符串（'…'），返回内容和后面剩下的部分
+fn parse_string(text: &str) -> Option<(String, &str)> {
+    if let Some(body) = text.strip_prefix('\'') {
+        let end = body.find('\'')?;
+        return Some((body[..end].to_string(), &body[end + 1..]));
+    }
+    let body = text.strip_prefix('"')?;
+    let mut out = String::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &body[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                '"' => out.push('"'),
+                '\\' => out.push('\\'),
+                u @ ('u' | 'U') => {
+                    let len = if u == 'u' { 4 } else { 8 };
+                    let hex: String = (0..len).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                    out.push(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)?);
+                }
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tv() -> Preset {
+        let settings = JobSettings { format: "mkv".to_string(), audio_bitrate_k: 256, ..Default::default() };
+        let mut preset = Preset::capture("电视兼容", &settings);
+        preset.rule = Rule { names: "*.mkv, *.ts".to_string(), min_height: 720, priority: 2, ..Default::default() };
+        preset
+    }
+
+    #[test]
+    fn rules_round_trip_through_the_file() {
+        let presets = vec![tv(), Preset::capture("plain", &JobSettings::default())];
+        let text = format(&presets);
+        assert!(text.contains("match_names = \"*.mkv, *.ts\"\nmatch_min_height = 720\nmatch_priority = 2\n"));
+        let back = parse(&text);
+        assert!(back == presets);
+        // 规则的键不算设置，不会混进预设的值里
+        assert!(back[0].values.iter().all(|(k, _)| !k.starts_with("match_")));
+        assert!(back[1].rule.is_empty());
+    }
+
+    #[test]
+    fn rules_do_not_change_settings() {
+        let settings = tv().settings().unwrap();
+        assert_eq!((settings.format.as_str(), settings.audio_bitrate_k), ("mkv", 256));
+    }
+
+    #[test]
+    fn resaving_keeps_the_rule() {
+        let mut presets = vec![tv()];
+        let changed = JobSettings { format: "mp4".to_string(), ..Default::default() };
+        put(&mut presets, Preset::capture("电视兼容", &changed));
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets[0].settings().unwrap().format, "mp4");
+        assert!(presets[0].rule == tv().rule);
+        // 新预设自己带了规则时用新的
+        let mut replaced = Preset::capture("电视兼容", &changed);
+        replaced.rule.paths = "电视".to_string();
+        put(&mut presets, replaced.clone());
+        assert!(presets[0] == replaced);
+    }
+
+    #[test]
+    fn saving_keeps_a_backup_of_the_old_file() {
+        let dir = std::env::temp_dir().join(format!("ffui_presets_test_store_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("presets.toml");
+        let backup = dir.join("presets.toml.bak");
+        let first = vec![tv()];
+        store(&path, &first).unwrap();
+        assert!(!backup.exists());
+        let second = vec![tv(), Preset::capture("plain", &JobSettings::default())];
+        store(&path, &second).unwrap();
+        assert!(parse(&fs::read_to_string(&path).unwrap()) == second);
+        assert!(parse(&fs::read_to_string(&backup).unwrap()) == first);
+        // 读不出预设的旧文件不覆盖已有的备份
+        fs::write(&path, "garbage").unwrap();
+        store(&path, &second).unwrap();
+        assert!(parse(&fs::read_to_string(&backup).unwrap()) == first);
+        // 临时文件改名后不留下
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_plain_conversions_are_remembered() {
+        let plain = JobSettings { format: "webm".to_string(), ..Default::default() };
+        assert!(remembered(&plain));
+        assert!(!remembered(&JobSettings { preview_secs: Some(10), ..plain.clone() }));
+        assert!(!remembered(&JobSettings { quick: Some(crate::quick::QuickOp::StripAudio), ..plain.clone() }));
+    }
+
+    #[test]
+    fn last_used_keeps_the_launched_settings() {
+        // 队列里的文件套了预设时，记下的是它实际用的设置，不是窗口里的
+        let dir = std::env::temp_dir().join(format!("ffui_presets_test_last_used_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("last-used.toml");
+        let launched = tv().settings().unwrap();
+        store(&path, &[Preset::capture(LAST_USED, &launched)]).unwrap();
+        let back = parse(&fs::read_to_string(&path).unwrap()).into_iter().find(|p| p.name == LAST_USED).unwrap();
+        let settings = back.settings().unwrap();
+        assert_eq!((settings.format.as_str(), settings.audio_bitrate_k), ("mkv", 256));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
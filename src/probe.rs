@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use crate::live;
+use crate::process::{self, FfError};
+
+// ffprobe -of flat 的解析结果
+#[derive(Clone, Default)]
+pub struct MediaInfo {
+    pub duration: f64,
+    pub format: BTreeMap<String, String>,
+    pub streams: Vec<StreamInfo>,
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Clone, Default)]
+pub struct StreamInfo {
+    pub codec_type: String,
+    pub codec_name: String,
+    pub props: BTreeMap<String, String>,
+}
+
+// 源文件里的章节，起止时间单位为秒
+#[derive(Clone, Default)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+// -analyzeduration / -probesize，0 表示用 ffmpeg 默认值
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct ProbeDepth {
+    pub analyze_secs: u64,
+    pub probesize_mb: u64,
+}
+
+impl ProbeDepth {
+    // 快速探测结果可疑时用来重新分析
+    pub const DEEP: ProbeDepth = ProbeDepth { analyze_secs: 100, probesize_mb: 100 };
+
+    // 放在 -i 之前，ffprobe 和 ffmpeg 通用
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.analyze_secs > 0 {
+            args.push("-analyzeduration".to_string());
+            args.push((self.analyze_secs * 1_000_000).to_string());
+        }
+        if self.probesize_mb > 0 {
+            args.push("-probesize".to_string());
+            args.push((self.probesize_mb * 1024 * 1024).to_string());
+        }
+        args
+    }
+}
+
+impl MediaInfo {
+    // 默认探测深度下常见的误判迹象
+    pub fn suspicious(&self) -> Option<&'static str> {
+        if self.streams.is_empty() {
+            return Some("没有检测到任何流");
+        }
+        if self.duration <= 0.0 {
+            return Some("没有检测到时长");
+        }
+        let zero = |s: &StreamInfo, k: &str| s.props.get(k).is_none_or(|v| v == "0");
+        for s in &self.streams {
+            if s.codec_name.is_empty() || s.codec_name == "unknown" {
+                return Some("有无法识别编码的流");
+            }
+            if s.codec_type == "video" && (zero(s, "width") || zero(s, "height")) {
+                return Some("视频流缺少分辨率");
+            }
+            if s.codec_type == "audio" && (zero(s, "sample_rate") || zero(s, "channels")) {
+                return Some("音频流缺少采样率或声道信息");
+            }
+        }
+        None
+    }
+}
+
+// 30000/1001 -> 29.97
+pub fn parse_rate(rate: &str) -> Option<f64> {
+    match rate.split_once('/') {
+        Some((n, d)) => {
+            let (n, d) = (n.parse::<f64>().ok()?, d.parse::<f64>().ok()?);
+            (d > 0.0 && n > 0.0).then_some(n / d)
+        }
+        None => rate.parse().ok().filter(|r: &f64| *r > 0.0),
+    }
+}
+
+pub fn probe(input: &str, depth: ProbeDepth) -> Result<MediaInfo, FfError> {
+    // 探测会读走实时输入的数据
+    if live::is_live(input) {
+        return Err(FfError::Exit(format!("{} 是实时输入，不能预先探测", live::display_name(input))));
+    }
+    let output = process::command("ffprobe")
+        .args(["-v", "error"])
+        .args(depth.args())
+        .args([
+            "-show_format", "-show_streams", "-show_chapters",
+            "-of", "flat",
+            input,
+        ])
+        .output()
+        .map_err(|e| FfError::launch("ffprobe", e))?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(FfError::Exit(format!("ffprobe 无法读取 {}: {}", input, err.trim())));
+    }
+    let info = parse_flat(&String::from_utf8_lossy(&output.stdout));
+    // -show_format 总会输出 format.*，一项都没有说明输出不是这个格式（不是 ffprobe、版本太旧）
+    if info.format.is_empty() && info.streams.is_empty() {
+        return Err(FfError::Unparsable(format!("读不懂 ffprobe 对 {} 的输出", input)));
+    }
+    Ok(info)
+}
+
+// 诊断包里附带的完整探测结果，ffprobe 自己的 JSON 格式
+pub fn json(input: &str) -> Result<String, FfError> {
+    if live::is_live(input) {
+        return Err(FfError::Exit(format!("{} 是实时输入，不能预先探测", live::display_name(input))));
+    }
+    let output = process::command("ffprobe")
+        .args(["-v", "error", "-show_format", "-show_streams", "-show_chapters", "-of", "json", input])
+        .output()
+        .map_err(|e| FfError::launch("ffprobe", e))?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(FfError::Exit(format!("ffprobe 无法读取 {}: {}", input, err.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(v) => v.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_string(),
+    }
+}
+
+// streams.stream.0.codec_name="h264" / format.duration="12.3"
+pub fn parse_flat(text: &str) -> MediaInfo {
+    let mut info = MediaInfo::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = unquote(value.trim());
+        if let Some(rest) = key.strip_prefix("streams.stream.") {
+            let Some((idx, field)) = rest.split_once('.') else { continue };
+            let Ok(idx) = idx.parse::<usize>() else { continue };
+            if info.streams.len() <= idx {
+                info.streams.resize_with(idx + 1, StreamInfo::default);
+            }
+            let stream = &mut info.streams[idx];
+            match field {
+                "codec_type" => stream.codec_type = value.clone(),
+                "codec_name" => stream.codec_name = value.clone(),
+                _ => {}
+            }
+            stream.props.insert(field.to_string(), value);
+        } else if let Some(rest) = key.strip_prefix("chapters.chapter.") {
+            // chapters.chapter.0.start_time="12.000000" / chapters.chapter.0.tags.title="..."
+            let Some((idx, field)) = rest.split_once('.') else { continue };
+            let Ok(idx) = idx.parse::<usize>() else { continue };
+            if info.chapters.len() <= idx {
+                info.chapters.resize_with(idx + 1, Chapter::default);
+            }
+            let chapter = &mut info.chapters[idx];
+            match field {
+                "start_time" => chapter.start = value.parse().unwrap_or(0.0),
+                "end_time" => chapter.end = value.parse().unwrap_or(0.0),
+                "tags.title" => chapter.title = value,
+                _ => {}
+            }
+        } else if let Some(field) = key.strip_prefix("format.") {
+            info.format.insert(field.to_string(), value);
+        }
+    }
+    info.duration = info.format.get("duration").and_then(|d| d.parse().ok()).unwrap_or(0.0);
+    info
+}
@@ -0,0 +1,256 @@
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use crate::config;
+
+// 子进程只继承这些环境变量：找得到程序、临时目录、字体和硬件驱动即可
+const PASSTHROUGH: &[&str] = &[
+    "PATH", "PATHEXT", "SYSTEMROOT", "SYSTEMDRIVE", "WINDIR", "COMSPEC", "PSMODULEPATH",
+    "TEMP", "TMP", "TMPDIR", "HOME", "USERPROFILE", "APPDATA", "LOCALAPPDATA", "PROGRAMDATA",
+    "PROGRAMFILES", "NUMBER_OF_PROCESSORS", "PROCESSOR_ARCHITECTURE",
+    "LD_LIBRARY_PATH", "DYLD_LIBRARY_PATH", "FONTCONFIG_FILE", "FONTCONFIG_PATH",
+    "DISPLAY", "WAYLAND_DISPLAY", "DBUS_SESSION_BUS_ADDRESS",
+    "CUDA_PATH", "CUDA_VISIBLE_DEVICES", "LIBVA_DRIVER_NAME", "LIBVA_DRIVERS_PATH", "VDPAU_DRIVER",
+];
+
+const PASSTHROUGH_PREFIXES: &[&str] = &["NVIDIA_", "INTEL_", "AMD_", "ONEVPL_", "MFX_", "XDG_"];
+
+// 运行 ffmpeg/ffprobe 出错的几种情况，调用方据此决定提示用户检查 ffmpeg 目录还是输入文件
+#[derive(Debug)]
+pub enum FfError {
+    // 程序不存在或启动不了（没有执行权限、缺少依赖的库）
+    NotFound { program: PathBuf, error: io::Error },
+    // 运行了，但以非 0 退出码结束；内容是给用户看的说明
+    Exit(String),
+    // 正常结束，输出里却找不到要的内容
+    Unparsable(String),
+}
+
+impl FfError {
+    // 当前线程用的 ffmpeg/ffprobe 启动失败
+    pub fn launch(name: &str, error: io::Error) -> FfError {
+        FfError::NotFound { program: tool(name), error }
+    }
+}
+
+impl fmt::Display for FfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FfError::NotFound { program, error } => write!(f, "无法执行 {}: {}", program.display(), error),
+            FfError::Exit(message) | FfError::Unparsable(message) => f.write_str(message),
+        }
+    }
+}
+
+// 大部分调用方的错误是给用户看的文字，可以直接用 ?
+impl From<FfError> for String {
+    fn from(e: FfError) -> String {
+        e.to_string()
+    }
+}
+
+thread_local! {
+    // 这个线程用的 ffmpeg 版本：(名字, ffmpeg/ffprobe/ffplay 所在目录)，None 表示设置里的默认目录
+    static BUILD: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+// 之后这个线程里的 ffmpeg 调用都用任务选的版本（设置里命名的 ffmpeg），空名字是默认的。
+// 任务线程开始时调用；界面线程编辑任务时也切过去，编码器、滤镜是否可用按那个版本判断。
+// 列表里已经没有这个名字时用默认的，返回 false
+pub fn use_build(name: &str) -> bool {
+    let build = if name.is_empty() { None } else { config::build_dir(name).map(|dir| (name.to_string(), dir)) };
+    let found = name.is_empty() || build.is_some();
+    BUILD.with(|b| *b.borrow_mut() = build);
+    found
+}
+
+// 当前线程用的 ffmpeg 版本名，默认的是空字符串。后台线程要沿用时传过去再 use_build
+pub fn build() -> String {
+    BUILD.with(|b| b.borrow().as_ref().map(|(name, _)| name.clone()).unwrap_or_default())
+}
+
+// 当前线程用的 ffmpeg/ffprobe/ffplay 的完整路径
+pub fn tool(name: &str) -> PathBuf {
+    BUILD.with(|b| match &*b.borrow() {
+        Some((_, dir)) => config::tool_in(dir, name),
+        None => config::tool_path(name),
+    })
+}
+
+// 所有子进程都从这里创建：干净的环境 + C locale，
+// 让 ffmpeg/ffprobe 的输出（小数点、报错文字、颜色码）不受用户系统设置影响
+pub fn command(program: impl AsRef<OsStr>) -> Command {
+    // 首次运行向导里指定了 ffmpeg 目录时用那里的 ffmpeg/ffprobe（以及同一套里的 ffplay），
+    // 任务指定了 ffmpeg 版本时用那个版本的
+    let mut cmd = match program.as_ref().to_str() {
+        Some(name @ ("ffmpeg" | "ffprobe" | "ffplay")) => Command::new(tool(name)),
+        _ => Command::new(program),
+    };
+    cmd.env_clear();
+    for (key, value) in std::env::vars_os() {
+        // Windows 的变量名不区分大小写
+        let upper = key.to_string_lossy().to_uppercase();
+        if PASSTHROUGH.contains(&upper.as_str()) || PASSTHROUGH_PREFIXES.iter().any(|p| upper.starts_with(p)) {
+            cmd.env(key, value);
+        }
+    }
+    cmd.env("LC_ALL", "C")
+        .env("LANG", "C")
+        .env("AV_LOG_FORCE_NOCOLOR", "1");
+    // ffui 的标准输入可能是别的程序送来的媒体数据（实时输入），不让子进程顺手读走；
+    // 需要 stdin 的调用自己改成 piped
+    cmd.stdin(Stdio::null());
+
+    #[cfg(target_os="windows")]
+    { cmd.creation_flags(0x08000000); }
+
+    cmd
+}
+
+// 用户自己配置的命令（完成后钩子）：保留完整环境变量，脚本可能依赖代理、凭据等设置
+pub fn user_command(program: impl AsRef<OsStr>) -> Command {
+    #[allow(unused_mut)]
+    let mut cmd = Command::new(program);
+    #[cfg(target_os="windows")]
+    { cmd.creation_flags(0x08000000); }
+    cmd
+}
+
+// 运行到结束或超时（超时则结束进程，状态为 None），收集 stdout 和 stderr
+pub fn output_with_timeout(cmd: &mut Command, limit: Duration) -> io::Result<(Option<ExitStatus>, String, String)> {
+    let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            String::from_utf8_lossy(&bytes).into_owned()
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() > limit {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    Ok((status, stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default()))
+}
+
+// 暂停/恢复整个 ffmpeg 进程
+pub fn suspend(pid: u32) -> io::Result<()> {
+    #[cfg(target_os = "windows")]
+    { nt_call(pid, c"NtSuspendProcess") }
+    #[cfg(not(target_os = "windows"))]
+    { signal(pid, "-STOP") }
+}
+
+pub fn resume(pid: u32) -> io::Result<()> {
+    #[cfg(target_os = "windows")]
+    { nt_call(pid, c"NtResumeProcess") }
+    #[cfg(not(target_os = "windows"))]
+    { signal(pid, "-CONT") }
+}
+
+// 用系统默认程序打开文件（播放预览）
+pub fn open_file(path: &str) -> io::Result<()> {
+    #[cfg(target_os = "windows")]
+    let program = "explorer";
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let program = "xdg-open";
+    command(program).arg(path).spawn().map(|_| ())
+}
+
+#[cfg(target_os = "windows")]
+fn nt_call(pid: u32, name: &std::ffi::CStr) -> io::Result<()> {
+    use winapi::shared::minwindef::FARPROC;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::libloaderapi::{GetModuleHandleA, GetProcAddress};
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winnt::{HANDLE, PROCESS_SUSPEND_RESUME};
+
+    unsafe {
+        let ntdll = GetModuleHandleA(c"ntdll.dll".as_ptr());
+        if ntdll.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let proc_addr = GetProcAddress(ntdll, name.as_ptr());
+        if proc_addr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let func = std::mem::transmute::<FARPROC, extern "system" fn(HANDLE) -> i32>(proc_addr);
+        let handle = OpenProcess(PROCESS_SUSPEND_RESUME, 0, pid);
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let status = func(handle);
+        CloseHandle(handle);
+        if status < 0 {
+            return Err(io::Error::other(format!("NTSTATUS {:#010x}", status)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn signal(pid: u32, sig: &str) -> io::Result<()> {
+    let status = command("kill").args([sig, &pid.to_string()]).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("kill {} 失败", sig)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_get_c_locale_and_no_color() {
+        let cmd = command("ffprobe");
+        let envs: Vec<(String, Option<String>)> = cmd
+            .get_envs()
+            .map(|(k, v)| (k.to_string_lossy().into_owned(), v.map(|v| v.to_string_lossy().into_owned())))
+            .collect();
+        for key in ["LC_ALL", "LANG"] {
+            assert!(envs.contains(&(key.to_string(), Some("C".to_string()))), "{}", key);
+        }
+        assert!(envs.contains(&("AV_LOG_FORCE_NOCOLOR".to_string(), Some("1".to_string()))));
+    }
+
+    // 用户的 locale 变量不会漏给子进程，PATH 保留
+    #[cfg(unix)]
+    #[test]
+    fn environment_is_cleared_except_passthrough() {
+        let (status, stdout, _) =
+            output_with_timeout(command("env").env("LC_NUMERIC", "de_DE.UTF-8"), Duration::from_secs(10)).unwrap();
+        assert!(status.unwrap().success());
+        // 上面显式设置的会保留，用来确认确实是这条命令的输出
+        assert!(stdout.lines().any(|l| l == "LC_NUMERIC=de_DE.UTF-8"));
+        assert!(stdout.lines().any(|l| l == "LC_ALL=C"));
+        assert!(stdout.lines().all(|l| !l.starts_with("LANGUAGE=") && !l.starts_with("CARGO_PKG_NAME=")));
+        if std::env::var_os("PATH").is_some() {
+            assert!(stdout.lines().any(|l| l.starts_with("PATH=")));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// 任务的全部输出（多分辨率、HLS 目录、另存的封面……）先写在暂存目录里，
+// 转换和校验都成功后一起移到输出位置，不会出现只有视频、缺了另一部分的半成品。
+// 移动到一半失败时，已经移过去的删掉，被覆盖的旧文件放回原处
+
+// 被覆盖的旧文件先改成这个后缀，全部移动成功后再删
+const BACKUP_SUFFIX: &str = ".ffui-old";
+
+struct Moved {
+    target: PathBuf,
+    // 覆盖前改名保存的旧文件
+    backup: Option<PathBuf>,
+}
+
+// 暂存目录里放一个输出时的路径：文件名不变，目录换成暂存目录
+pub fn staged_path(stage: &Path, output: &str) -> String {
+    let name = Path::new(output).file_name().map(|n| n.to_os_string()).unwrap_or_else(|| "output".into());
+    stage.join(name).to_string_lossy().into_owned()
+}
+
+// 暂存路径发布后的位置
+pub fn published_path(stage: &Path, dest: &Path, staged: &str) -> String {
+    match Path::new(staged).strip_prefix(stage) {
+        Ok(rest) => dest.join(rest).to_string_lossy().into_owned(),
+        Err(_) => staged.to_string(),
+    }
+}
+
+// 输出所在目录，相对路径没有上级时是当前目录
+pub fn dest_dir(output: &str) -> PathBuf {
+    match Path::new(output).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// 把 stage 下的每一项移到 dest 下同名的位置。outputs 是任务声明的输出和目录（完整的暂存路径），
+// 已存在时按 overwrite 决定覆盖还是报错；其他附带文件（另存的封面）已存在时保留原来的，和直接输出时一样。
+// 返回实际移过去的路径
+pub fn publish(stage: &Path, dest: &Path, outputs: &[String], overwrite: bool) -> Result<Vec<PathBuf>, String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(stage)
+        .map_err(|e| format!("无法读取暂存目录 {}: {}", stage.display(), e))?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+    let declared = |path: &Path| outputs.iter().any(|o| Path::new(o).starts_with(path));
+
+    // 先检查全部冲突，一个都不动
+    let mut plan = Vec::new();
+    for path in entries {
+        let Some(name) = path.file_name() else { continue };
+        let target = dest.join(name);
+        if fs::symlink_metadata(&target).is_ok() {
+            if !declared(&path) {
+                continue;
+            }
+            if !overwrite {
+                return Err(format!("{} 已存在（没有允许覆盖），输出没有移过去", target.display()));
+            }
+        }
+        plan.push((path, target));
+    }
+
+    fs::create_dir_all(dest).map_err(|e| format!("无法创建 {}: {}", dest.display(), e))?;
+    let mut moved: Vec<Moved> = Vec::new();
+    for (from, target) in plan {
+        match place(&from, &target) {
+            Ok(m) => moved.push(m),
+            Err(e) => {
+                let message = format!("无法把 {} 移到 {}: {}", from.display(), target.display(), e);
+                return Err(match rollback(moved) {
+                    Ok(()) => format!("{}，已撤回移过去的输出", message),
+                    Err(e) => format!("{}；撤回时也出错: {}", message, e),
+                });
+            }
+        }
+    }
+    for m in &moved {
+        if let Some(backup) = &m.backup {
+            let _ = remove(backup);
+        }
+    }
+    Ok(moved.into_iter().map(|m| m.target).collect())
+}
+
+// 移一项：目标已存在时先改名备份，移动失败就把备份放回
+fn place(from: &Path, target: &Path) -> io::Result<Moved> {
+    let backup = if fs::symlink_metadata(target).is_ok() {
+        let mut name = target.as_os_str().to_os_string();
+        name.push(BACKUP_SUFFIX);
+        let backup = PathBuf::from(name);
+        let _ = remove(&backup);
+        fs::rename(target, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+    match move_path(from, target) {
+        Ok(()) => Ok(Moved { target: target.to_path_buf(), backup }),
+        Err(e) => {
+            if let Some(backup) = &backup {
+                let _ = fs::rename(backup, target);
+            }
+            Err(e)
+        }
+    }
+}
+
+// 倒序撤回：删掉移过去的，放回备份。返回第一个撤不回的错误
+fn rollback(moved: Vec<Moved>) -> io::Result<()> {
+    let mut first = Ok(());
+    for m in moved.into_iter().rev() {
+        let mut result = remove(&m.target);
+        if let Some(backup) = &m.backup
+            && result.is_ok()
+        {
+            result = fs::rename(backup, &m.target);
+        }
+        if first.is_ok() {
+            first = result;
+        }
+    }
+    first
+}
+
+// 同一分区直接改名；跨分区（暂存目录在另一块盘上）改名会失败，改成复制后删除，复制到一半的删掉
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    match copy_all(from, to) {
+        // 暂存目录随后整个删除，源删不掉不影响结果
+        Ok(()) => {
+            let _ = remove(from);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = remove(to);
+            Err(e)
+        }
+    }
+}
+
+// 复制文件或整个目录
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::metadata(from)?.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+    fs::copy(from, to).map(|_| ())
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,173 @@
+// 画质控制：按质量或按目标码率，默认交给编码器。
+// 界面上的质量值统一用 0～51（x264 的 CRF 范围，越小画质越好、文件越大），
+// 各编码器的参数名和范围不同，在 level_args 里换算
+
+// 质量值的上限
+pub const MAX_LEVEL: u8 = 51;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum RateMode {
+    // 编码器默认值（AV1、VP9 用它们各自的默认质量）
+    #[default]
+    Auto,
+    Quality,
+    Bitrate,
+}
+
+impl RateMode {
+    pub const ALL: [RateMode; 3] = [RateMode::Auto, RateMode::Quality, RateMode::Bitrate];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RateMode::Auto => "编码器默认",
+            RateMode::Quality => "按质量",
+            RateMode::Bitrate => "按目标码率",
+        }
+    }
+
+    // 任务列表里的写法
+    pub fn tag(self) -> &'static str {
+        match self {
+            RateMode::Auto => "auto",
+            RateMode::Quality => "quality",
+            RateMode::Bitrate => "bitrate",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<RateMode> {
+        RateMode::ALL.into_iter().find(|m| m.tag() == tag)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Quality {
+    pub mode: RateMode,
+    // 0～51，按质量时用
+    pub level: u8,
+    // kbps，按目标码率时用
+    pub bitrate_k: u32,
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        // 23 是 x264 的默认 CRF
+        Quality { mode: RateMode::Auto, level: 23, bitrate_k: 5000 }
+    }
+}
+
+impl Quality {
+    // -c:v 之后的码率控制参数；默认模式不加参数
+    pub fn args(&self, encoder: &str) -> Vec<String> {
+        match self.mode {
+            RateMode::Auto => Vec::new(),
+            RateMode::Quality => level_args(encoder, self.level),
+            RateMode::Bitrate => bitrate_args(self.bitrate_k),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self.mode {
+            RateMode::Auto => self.mode.label().to_string(),
+            RateMode::Quality => format!("{} {}", self.mode.label(), self.level),
+            RateMode::Bitrate => format!("{} {} kbps", self.mode.label(), self.bitrate_k),
+        }
+    }
+}
+
+// 0～51 的质量值换算成各编码器的参数，不认识的编码器返回空：
+// x264/x265 的 -crf 就是 0～51；NVENC 的 -cq 和 QSV 的 -global_quality 是 1～51（NVENC 的 0 表示自动），
+// 要同时去掉默认的目标码率；AMF 用固定 QP；AV1 和 VP9 的 CRF 是 0～63，按比例放大
+pub fn level_args(encoder: &str, level: u8) -> Vec<String> {
+    let level = level.min(MAX_LEVEL);
+    let at_least_one = level.max(1).to_string();
+    let wide = (level as u32 * 63).div_ceil(MAX_LEVEL as u32).to_string();
+    let level = level.to_string();
+    let args: Vec<&str> = match encoder {
+        "libx264" | "libx265" => vec!["-crf", &level],
+        e if e.ends_with("_nvenc") => vec!["-cq", &at_least_one, "-b:v", "0"],
+        e if e.ends_with("_qsv") => vec!["-global_quality", &at_least_one],
+        e if e.ends_with("_amf") => vec!["-rc", "cqp", "-qp_i", &level, "-qp_p", &level],
+        "libsvtav1" => vec!["-crf", &wide],
+        "libaom-av1" | "libvpx-vp9" => vec!["-crf", &wide, "-b:v", "0"],
+        _ => Vec::new(),
+    };
+    args.into_iter().map(String::from).collect()
+}
+
+// 目标码率：峰值不超过目标，缓冲两秒
+pub fn bitrate_args(bitrate_k: u32) -> Vec<String> {
+    vec![
+        "-b:v".to_string(), format!("{}k", bitrate_k),
+        "-maxrate".to_string(), format!("{}k", bitrate_k),
+        "-bufsize".to_string(), format!("{}k", bitrate_k * 2),
+    ]
+}
+
+// 认 -pass/-passlogfile 的软件编码器。x265 的两遍统计要写进 -x265-params，
+// 路径里的冒号会被当成分隔符；硬件编码器没有这种两遍方式
+pub fn supports_two_pass(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libvpx-vp9" | "libaom-av1" | "mpeg4")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_tags_round_trip() {
+        for mode in RateMode::ALL {
+            assert!(RateMode::from_tag(mode.tag()) == Some(mode));
+        }
+        assert!(RateMode::from_tag("crf").is_none());
+    }
+
+    #[test]
+    fn level_per_encoder() {
+        assert_eq!(level_args("libx264", 23), ["-crf", "23"]);
+        assert_eq!(level_args("libx265", 28), ["-crf", "28"]);
+        assert_eq!(level_args("h264_nvenc", 23), ["-cq", "23", "-b:v", "0"]);
+        assert_eq!(level_args("hevc_qsv", 30), ["-global_quality", "30"]);
+        assert_eq!(level_args("h264_amf", 20), ["-rc", "cqp", "-qp_i", "20", "-qp_p", "20"]);
+        assert!(level_args("mpeg4", 23).is_empty());
+    }
+
+    // 0～51 换算到 0～63 向上取整，两端对齐
+    #[test]
+    fn level_scales_for_av1_and_vp9() {
+        assert_eq!(level_args("libsvtav1", 0), ["-crf", "0"]);
+        assert_eq!(level_args("libsvtav1", 23), ["-crf", "29"]);
+        assert_eq!(level_args("libsvtav1", 51), ["-crf", "63"]);
+        assert_eq!(level_args("libvpx-vp9", 1), ["-crf", "2", "-b:v", "0"]);
+        assert_eq!(level_args("libaom-av1", 51), ["-crf", "63", "-b:v", "0"]);
+    }
+
+    #[test]
+    fn level_edges() {
+        // NVENC 的 0 是自动，QSV 的下限是 1
+        assert_eq!(level_args("h264_nvenc", 0), ["-cq", "1", "-b:v", "0"]);
+        assert_eq!(level_args("h264_qsv", 0), ["-global_quality", "1"]);
+        // 超出范围的值按 51 算
+        assert_eq!(level_args("libx264", 80), ["-crf", "51"]);
+        assert_eq!(level_args("libsvtav1", 255), ["-crf", "63"]);
+    }
+
+    #[test]
+    fn args_and_labels_per_mode() {
+        let mut q = Quality::default();
+        assert!(q.args("libx264").is_empty());
+        assert_eq!(q.label(), "编码器默认");
+        q.mode = RateMode::Quality;
+        assert_eq!(q.args("libx264"), ["-crf", "23"]);
+        assert_eq!(q.label(), "按质量 23");
+        q.mode = RateMode::Bitrate;
+        q.bitrate_k = 2500;
+        assert_eq!(q.args("h264_nvenc"), ["-b:v", "2500k", "-maxrate", "2500k", "-bufsize", "5000k"]);
+        assert_eq!(q.label(), "按目标码率 2500 kbps");
+    }
+
+    #[test]
+    fn two_pass_encoders() {
+        assert!(supports_two_pass("libx264") && supports_two_pass("libvpx-vp9"));
+        assert!(!supports_two_pass("libx265") && !supports_two_pass("h264_nvenc"));
+    }
+}
@@ -0,0 +1,569 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::egui;
+use egui_extras::{Column as TableColumn, TableBuilder};
+
+use crate::a11y;
+use crate::cancel::CancelToken;
+use crate::config;
+use crate::confirm;
+use crate::inspect;
+use crate::joblist::Job;
+use crate::live;
+use crate::pipeline::OnFailure;
+use crate::plan::JobSettings;
+use crate::presets::{self, Preset};
+use crate::probe;
+
+// 任务列表的表格。排序和筛选只影响显示，执行顺序始终是 rows 的顺序；
+// 点“按当前排序重新排列执行顺序”才把显示顺序写回去
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Name,
+    Size,
+    Duration,
+    Status,
+    Eta,
+    Output,
+    Ffmpeg,
+}
+
+impl Column {
+    pub const ALL: [Column; 7] =
+        [Column::Name, Column::Size, Column::Duration, Column::Status, Column::Eta, Column::Output, Column::Ffmpeg];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Name => "文件名",
+            Column::Size => "大小",
+            Column::Duration => "时长",
+            Column::Status => "状态",
+            Column::Eta => "预计耗时",
+            Column::Output => "输出",
+            Column::Ffmpeg => "ffmpeg",
+        }
+    }
+
+    // 设置文件里保存的名字
+    pub fn tag(self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Size => "size",
+            Column::Duration => "duration",
+            Column::Status => "status",
+            Column::Eta => "eta",
+            Column::Output => "output",
+            Column::Ffmpeg => "ffmpeg",
+        }
+    }
+
+    fn width(self) -> f32 {
+        match self {
+            Column::Name | Column::Output => 220.0,
+            Column::Status => 160.0,
+            Column::Size | Column::Duration | Column::Eta | Column::Ffmpeg => 80.0,
+        }
+    }
+}
+
+// 设置里的 queue_columns：逗号分隔的列名，空表示全部显示
+pub fn parse_columns(text: &str) -> Vec<Column> {
+    let columns: Vec<Column> = Column::ALL.into_iter().filter(|c| text.split(',').any(|t| t.trim() == c.tag())).collect();
+    if columns.is_empty() { Column::ALL.to_vec() } else { columns }
+}
+
+struct Row {
+    // 删除、重排后不变，用来记选中状态和探测结果
+    id: u64,
+    size: Option<u64>,
+    job: Job,
+}
+
+// 编码器 -> (媒体时长, 耗时)，来自统计
+pub type Speeds = BTreeMap<String, (f64, f64)>;
+
+pub struct QueueView {
+    rows: Vec<Row>,
+    next_id: u64,
+    // 后台逐个探测的时长，id -> 秒
+    durations: Arc<Mutex<HashMap<u64, f64>>>,
+    probe_cancel: CancelToken,
+    // (列, 升序)，None 表示按执行顺序显示
+    sort: Option<(Column, bool)>,
+    filter: String,
+    selected: HashSet<u64>,
+    columns: Vec<Column>,
+    // 改动过还没保存到列表文件
+    pub dirty: bool,
+    // 批量操作失败的说明
+    notice: Option<String>,
+}
+
+impl QueueView {
+    pub fn new() -> Self {
+        QueueView {
+            rows: Vec::new(),
+            next_id: 0,
+            durations: Arc::new(Mutex::new(HashMap::new())),
+            probe_cancel: CancelToken::new(),
+            sort: None,
+            filter: String::new(),
+            selected: HashSet::new(),
+            columns: parse_columns(&config::current().queue_columns),
+            dirty: false,
+            notice: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    // 按执行顺序
+    pub fn jobs(&self) -> Vec<Job> {
+        self.rows.iter().map(|r| r.job.clone()).collect()
+    }
+
+    // 换成新导入的列表，大小当场读，时长在后台探测
+    pub fn set_jobs(&mut self, jobs: Vec<Job>) {
+        self.probe_cancel.cancel();
+        self.probe_cancel = CancelToken::new();
+        self.durations = Arc::new(Mutex::new(HashMap::new()));
+        self.selected.clear();
+        self.dirty = false;
+        self.rows = jobs
+            .into_iter()
+            .map(|job| {
+                self.next_id += 1;
+                let size = if live::is_live(&job.input) { None } else { std::fs::metadata(&job.input).ok().map(|m| m.len()) };
+                Row { id: self.next_id, size, job }
+            })
+            .collect();
+        let pending: Vec<(u64, String, probe::ProbeDepth)> = self
+            .rows
+            .iter()
+            .filter(|r| r.job.problem.is_none() && r.size.is_some())
+            .map(|r| (r.id, r.job.input.clone(), r.job.settings.probe_depth))
+            .collect();
+        let (durations, cancel) = (self.durations.clone(), self.probe_cancel.clone());
+        thread::spawn(move || {
+            for (id, input, depth) in pending {
+                if cancel.is_cancelled() {
+                    return;
+                }
+                if let Ok(info) = probe::probe(&input, depth)
+                    && info.duration > 0.0
+                {
+                    durations.lock().unwrap().insert(id, info.duration);
+                }
+            }
+        });
+    }
+
+    fn duration(&self, row: &Row) -> Option<f64> {
+        self.durations.lock().unwrap().get(&row.id).copied()
+    }
+
+    fn eta(&self, row: &Row, speeds: &Speeds) -> Option<f64> {
+        confirm::estimate_secs(&row.job.settings, self.duration(row)?, row.size, speeds)
+    }
+
+    fn name(row: &Row) -> String {
+        if live::is_live(&row.job.input) {
+            return live::display_name(&row.job.input);
+        }
+        Path::new(&row.job.input).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(row.job.input.clone())
+    }
+
+    fn status(row: &Row) -> String {
+        match &row.job.problem {
+            Some(problem) => problem.clone(),
+            None => row
+                .job
+                .stages
+                .iter()
+                .map(|s| match s.on_failure {
+                    OnFailure::Continue => format!("{}（失败继续）", s.kind.label()),
+                    OnFailure::Abort => s.kind.label().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" → "),
+        }
+    }
+
+    // 这个任务会用哪个 ffmpeg 版本，设置里已经删掉的标出来
+    fn build(row: &Row) -> String {
+        match row.job.settings.ffmpeg.as_str() {
+            "" => "默认".to_string(),
+            name if config::build_dir(name).is_none() => format!("{}（已删除）", name),
+            name => name.to_string(),
+        }
+    }
+
+    fn cell(&self, row: &Row, column: Column, speeds: &Speeds) -> String {
+        let dash = || "—".to_string();
+        match column {
+            Column::Name => Self::name(row),
+            Column::Size => row.size.map(|s| inspect::format_bytes(s as f64)).unwrap_or_else(dash),
+            Column::Duration => self.duration(row).map(inspect::format_duration).unwrap_or_else(dash),
+            Column::Status => Self::status(row),
+            Column::Eta => self.eta(row, speeds).map(inspect::format_duration).unwrap_or_else(dash),
+            Column::Output => row.job.output_path(),
+            Column::Ffmpeg => Self::build(row),
+        }
+    }
+
+    // 大小、时长、预计耗时可能还没有值
+    fn missing(&self, row: &Row, column: Column, speeds: &Speeds) -> bool {
+        match column {
+            Column::Size => row.size.is_none(),
+            Column::Duration => self.duration(row).is_none(),
+            Column::Eta => self.eta(row, speeds).is_none(),
+            _ => false,
+        }
+    }
+
+    fn compare(&self, a: &Row, b: &Row, column: Column, speeds: &Speeds) -> CmpOrdering {
+        match column {
+            Column::Name => Self::name(a).to_lowercase().cmp(&Self::name(b).to_lowercase()),
+            Column::Size => a.size.cmp(&b.size),
+            Column::Duration => self.duration(a).partial_cmp(&self.duration(b)).unwrap_or(CmpOrdering::Equal),
+            Column::Status => (a.job.problem.is_some(), Self::status(a)).cmp(&(b.job.problem.is_some(), Self::status(b))),
+            Column::Eta => self.eta(a, speeds).partial_cmp(&self.eta(b, speeds)).unwrap_or(CmpOrdering::Equal),
+            Column::Output => a.job.output_path().to_lowercase().cmp(&b.job.output_path().to_lowercase()),
+            Column::Ffmpeg => Self::build(a).cmp(&Self::build(b)),
+        }
+    }
+
+    fn matches(&self, row: &Row) -> bool {
+        let filter = self.filter.trim().to_lowercase();
+        filter.is_empty()
+            || row.job.input.to_lowercase().contains(&filter)
+            || row.job.output_path().to_lowercase().contains(&filter)
+            || Self::status(row).to_lowercase().contains(&filter)
+    }
+
+    // 显示顺序：筛选后按当前排序，行在 rows 里的下标
+    fn view_order(&self, speeds: &Speeds, filtered: bool) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.rows.len()).filter(|i| !filtered || self.matches(&self.rows[*i])).collect();
+        if let Some((column, ascending)) = self.sort {
+            // 没有值的排在最后，不论升降序
+            order.sort_by(|a, b| {
+                let (a, b) = (&self.rows[*a], &self.rows[*b]);
+                match (self.missing(a, column, speeds), self.missing(b, column, speeds)) {
+                    (false, true) => CmpOrdering::Less,
+                    (true, false) => CmpOrdering::Greater,
+                    _ if ascending => self.compare(a, b, column, speeds),
+                    _ => self.compare(a, b, column, speeds).reverse(),
+                }
+            });
+        }
+        order
+    }
+
+    fn save_columns(&self) {
+        let columns = self.columns.iter().map(|c| c.tag()).collect::<Vec<_>>().join(",");
+        let _ = config::save(&config::Config { queue_columns: columns, ..config::current() });
+    }
+
+    // 选中的任务移到执行顺序的最前或最后，相对顺序不变
+    fn move_selected(&mut self, to_front: bool) {
+        let (picked, rest): (Vec<Row>, Vec<Row>) = self.rows.drain(..).partition(|r| self.selected.contains(&r.id));
+        self.rows = if to_front { picked.into_iter().chain(rest).collect() } else { rest.into_iter().chain(picked).collect() };
+        self.dirty = true;
+    }
+
+    // 只改预设里有的项；预设读不出时一个任务都不改
+    fn apply_preset(&mut self, preset: &Preset) -> Result<(), String> {
+        preset.settings()?;
+        for row in self.rows.iter_mut().filter(|r| self.selected.contains(&r.id)) {
+            preset.apply(&mut row.job.settings)?;
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // 返回要载入到窗口的任务
+    pub fn show(&mut self, ui: &mut egui::Ui, current: &JobSettings, speeds: &Speeds) -> Option<Job> {
+        ui.horizontal(|ui| {
+            let label = ui.label("筛选");
+            ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("文件名、输出或状态").desired_width(200.0)).labelled_by(label.id);
+            ui.menu_button("显示的列", |ui| {
+                for column in Column::ALL {
+                    let mut shown = self.columns.contains(&column);
+                    if ui.checkbox(&mut shown, column.label()).changed() {
+                        self.columns = Column::ALL.into_iter().filter(|c| if *c == column { shown } else { self.columns.contains(c) }).collect();
+                        self.save_columns();
+                    }
+                }
+            });
+            if self.sort.is_some() && ui.button("恢复执行顺序显示").clicked() {
+                self.sort = None;
+            }
+        });
+
+        let order = self.view_order(speeds, true);
+        let selected = self.selected.len();
+        ui.horizontal(|ui| {
+            ui.label(format!("共 {} 个任务，显示 {} 个，选中 {} 个", self.rows.len(), order.len(), selected));
+            if ui.add_enabled(selected > 0, egui::Button::new("删除")).clicked() {
+                self.rows.retain(|r| !self.selected.contains(&r.id));
+                self.selected.clear();
+                self.dirty = true;
+            }
+            if ui.add_enabled(selected > 0, egui::Button::new("移到最前")).clicked() {
+                self.move_selected(true);
+            }
+            if ui.add_enabled(selected > 0, egui::Button::new("移到最后")).clicked() {
+                self.move_selected(false);
+            }
+            if ui.add_enabled(selected > 0, egui::Button::new("应用当前设置"))
+                .on_hover_text("把窗口里的转换设置应用到选中的任务")
+                .clicked()
+            {
+                for row in self.rows.iter_mut().filter(|r| self.selected.contains(&r.id)) {
+                    row.job.settings = current.clone();
+                }
+                self.dirty = true;
+            }
+            ui.add_enabled_ui(selected > 0, |ui| {
+                ui.menu_button("应用预设", |ui| {
+                    for preset in presets::all(&presets::load()) {
+                        let name = if presets::is_builtin(&preset.name) { format!("{}（内置）", preset.name) } else { preset.name.clone() };
+                        if ui.button(name).clicked() {
+                            self.notice = self.apply_preset(&preset).err();
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+            if ui.add_enabled(self.sort.is_some(), egui::Button::new("按当前排序重新排列执行顺序")).clicked() {
+                let all = self.view_order(speeds, false);
+                let mut old: Vec<Option<Row>> = self.rows.drain(..).map(Some).collect();
+                self.rows = all.into_iter().filter_map(|i| old[i].take()).collect();
+                self.sort = None;
+                self.dirty = true;
+            }
+        });
+
+        if let Some(notice) = &self.notice {
+            ui.colored_label(egui::Color32::RED, notice);
+        }
+
+        // 上面删除、移动过任务时下标已经变了
+        let order = self.view_order(speeds, true);
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+        let columns = self.columns.clone();
+        let mut load = None;
+        let mut toggled = Vec::new();
+        // 换了显示的列时各列宽度重新开始
+        let key = columns.iter().map(|c| c.tag()).collect::<Vec<_>>().join(",");
+        ui.push_id(key, |ui| {
+            let mut table = TableBuilder::new(ui)
+                .striped(true)
+                .max_scroll_height(400.0)
+                .auto_shrink([false, true])
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(TableColumn::exact(24.0))
+                .column(TableColumn::exact(36.0));
+            for column in &columns {
+                table = table.column(TableColumn::initial(column.width()).at_least(40.0).resizable(true).clip(true));
+            }
+            table
+                .column(TableColumn::remainder())
+                .header(row_height, |mut header| {
+                    // 表头：点列名排序，再点一次反向
+                    header.col(|ui| {
+                        let mut all = !order.is_empty() && order.iter().all(|i| self.selected.contains(&self.rows[*i].id));
+                        if a11y::named(ui.checkbox(&mut all, ""), "全选显示的任务").on_hover_text("全选显示的任务").changed() {
+                            for i in &order {
+                                if all {
+                                    self.selected.insert(self.rows[*i].id);
+                                } else {
+                                    self.selected.remove(&self.rows[*i].id);
+                                }
+                            }
+                        }
+                    });
+                    header.col(|ui| {
+                        ui.strong("顺序");
+                    });
+                    for column in &columns {
+                        header.col(|ui| {
+                            let arrow = match self.sort {
+                                Some((c, true)) if c == *column => " ▲",
+                                Some((c, false)) if c == *column => " ▼",
+                                _ => "",
+                            };
+                            let text = egui::RichText::new(format!("{}{}", column.label(), arrow)).strong();
+                            let name = match self.sort {
+                                Some((c, true)) if c == *column => format!("按{}排序，当前升序", column.label()),
+                                Some((c, false)) if c == *column => format!("按{}排序，当前降序", column.label()),
+                                _ => format!("按{}排序", column.label()),
+                            };
+                            if a11y::named(ui.add(egui::Button::new(text).frame(false)), &name).clicked() {
+                                self.sort = match self.sort {
+                                    Some((c, true)) if c == *column => Some((c, false)),
+                                    _ => Some((*column, true)),
+                                };
+                            }
+                        });
+                    }
+                    header.col(|_| {});
+                })
+                .body(|body| {
+                    // 只绘制看得见的行，上千个任务也不卡
+                    body.rows(row_height, order.len(), |n, mut cells| {
+                        let i = order[n];
+                        let row = &self.rows[i];
+                        // 每行的复选框和载入按钮没有可见文字，读屏时带上序号和文件名
+                        let file = Path::new(&row.job.input).file_name().map_or(row.job.input.clone(), |n| n.to_string_lossy().into_owned());
+                        cells.col(|ui| {
+                            let mut picked = self.selected.contains(&row.id);
+                            if a11y::named(ui.checkbox(&mut picked, ""), &format!("选择第 {} 个任务 {}", i + 1, file)).changed() {
+                                toggled.push(row.id);
+                            }
+                        });
+                        cells.col(|ui| {
+                            ui.label((i + 1).to_string());
+                        });
+                        for column in &columns {
+                            let text = self.cell(row, *column, speeds);
+                            // 超出列宽的部分裁掉，完整内容放在悬停提示里
+                            cells.col(|ui| {
+                                let mut rich = egui::RichText::new(&text);
+                                if *column == Column::Status && row.job.problem.is_some() {
+                                    rich = rich.color(egui::Color32::RED);
+                                }
+                                ui.add(egui::Label::new(rich).wrap(false).sense(egui::Sense::hover())).on_hover_text(&text);
+                            });
+                        }
+                        cells.col(|ui| {
+                            if row.job.problem.is_none() && a11y::named(ui.small_button("载入"), &format!("载入第 {} 个任务 {}", i + 1, file)).clicked() {
+                                load = Some(row.job.clone());
+                            }
+                        });
+                    });
+                });
+        });
+        for id in toggled {
+            if !self.selected.remove(&id) {
+                self.selected.insert(id);
+            }
+        }
+        load
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 不经过 set_jobs，不读文件也不在后台探测
+    fn view(rows: &[(&str, Option<u64>, Option<&str>)]) -> QueueView {
+        let mut view = QueueView {
+            rows: Vec::new(),
+            next_id: 0,
+            durations: Arc::new(Mutex::new(HashMap::new())),
+            probe_cancel: CancelToken::new(),
+            sort: None,
+            filter: String::new(),
+            selected: HashSet::new(),
+            columns: Column::ALL.to_vec(),
+            dirty: false,
+            notice: None,
+        };
+        for (input, size, problem) in rows {
+            view.next_id += 1;
+            let job = Job {
+                input: input.to_string(),
+                output: String::new(),
+                settings: JobSettings::default(),
+                stages: Vec::new(),
+                problem: problem.map(str::to_string),
+            };
+            view.rows.push(Row { id: view.next_id, size: *size, job });
+        }
+        view
+    }
+
+    fn names(view: &QueueView, order: &[usize]) -> Vec<String> {
+        order.iter().map(|i| QueueView::name(&view.rows[*i])).collect()
+    }
+
+    #[test]
+    fn columns_from_config() {
+        assert!(parse_columns("") == Column::ALL.to_vec());
+        assert!(parse_columns("bogus") == Column::ALL.to_vec());
+        // 按固定的列顺序，不按文本里的顺序
+        assert!(parse_columns(" eta,name ,bogus") == vec![Column::Name, Column::Eta]);
+        let tags: Vec<&str> = Column::ALL.iter().map(|c| c.tag()).collect();
+        assert!(parse_columns(&tags.join(",")) == Column::ALL.to_vec());
+    }
+
+    #[test]
+    fn sorting_is_only_a_view() {
+        let mut view = view(&[("b.mkv", Some(300), None), ("a.mkv", None, None), ("C.mkv", Some(100), None)]);
+        let speeds = Speeds::new();
+        assert_eq!(view.view_order(&speeds, true), [0, 1, 2]);
+        view.sort = Some((Column::Name, true));
+        assert_eq!(names(&view, &view.view_order(&speeds, true)), ["a.mkv", "b.mkv", "C.mkv"]);
+        // 没有大小的不论升降序都在最后
+        view.sort = Some((Column::Size, true));
+        assert_eq!(view.view_order(&speeds, true), [2, 0, 1]);
+        view.sort = Some((Column::Size, false));
+        assert_eq!(view.view_order(&speeds, true), [0, 2, 1]);
+        assert_eq!(names(&view, &(0..3).collect::<Vec<_>>()), ["b.mkv", "a.mkv", "C.mkv"]);
+    }
+
+    #[test]
+    fn durations_sort_once_probed() {
+        let mut view = view(&[("a.mkv", Some(1), None), ("b.mkv", Some(1), None), ("c.mkv", Some(1), None)]);
+        view.durations.lock().unwrap().extend([(1, 90.0), (3, 30.0)]);
+        view.sort = Some((Column::Duration, true));
+        assert_eq!(view.view_order(&Speeds::new(), true), [2, 0, 1]);
+    }
+
+    #[test]
+    fn filter_matches_input_output_and_status() {
+        let mut view = view(&[("/clips/Holiday.mov", None, None), ("/clips/talk.mkv", None, Some("找不到输入文件")), ("/music/song.flac", None, None)]);
+        let speeds = Speeds::new();
+        view.filter = " CLIPS ".to_string();
+        assert_eq!(view.view_order(&speeds, true), [0, 1]);
+        view.filter = "找不到".to_string();
+        assert_eq!(view.view_order(&speeds, true), [1]);
+        view.filter = "holiday.mp4".to_string();
+        assert_eq!(view.view_order(&speeds, true), [0]);
+        // 重新排列执行顺序时不看筛选
+        assert_eq!(view.view_order(&speeds, false), [0, 1, 2]);
+    }
+
+    #[test]
+    fn move_keeps_relative_order() {
+        let mut view = view(&[("1", None, None), ("2", None, None), ("3", None, None), ("4", None, None)]);
+        view.selected.extend([2, 4]);
+        view.move_selected(true);
+        assert_eq!(names(&view, &[0, 1, 2, 3]), ["2", "4", "1", "3"]);
+        view.move_selected(false);
+        assert_eq!(names(&view, &[0, 1, 2, 3]), ["1", "3", "2", "4"]);
+        assert!(view.dirty);
+    }
+
+    #[test]
+    fn preset_goes_to_selected_jobs() {
+        let mut view = view(&[("a.mov", None, None), ("b.mov", None, None)]);
+        view.rows[1].job.settings.overwrite = true;
+        let preset = Preset::capture("mkv", &JobSettings { format: "mkv".to_string(), ..Default::default() });
+        view.selected.insert(2);
+        assert!(view.apply_preset(&preset).is_ok());
+        assert_eq!(view.rows[0].job.settings.format, "mp4");
+        assert_eq!(view.rows[1].job.settings.format, "mkv");
+        // 预设里没有的项不动
+        assert!(view.rows[1].job.settings.overwrite);
+        assert!(view.dirty);
+    }
+}
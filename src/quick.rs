@@ -0,0 +1,238 @@
+use std::path::Path;
+
+use crate::args::Source;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::MediaInfo;
+
+// 两个不重新编码的小操作：把手机拍歪的视频转正、去掉音轨。都是流复制，几秒钟就能完成，
+// 输出和源文件同一种容器。只有容器存不下旋转标记时才退回重新编码视频，并在说明里写明原因
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Right,
+    Left,
+    Half,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 3] = [Rotation::Right, Rotation::Left, Rotation::Half];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Rotation::Right => "向右转 90°",
+            Rotation::Left => "向左转 90°",
+            Rotation::Half => "旋转 180°",
+        }
+    }
+
+    // 显示矩阵里的角度按逆时针算
+    fn ccw_degrees(self) -> i32 {
+        match self {
+            Rotation::Right => -90,
+            Rotation::Left => 90,
+            Rotation::Half => 180,
+        }
+    }
+
+    // 重新编码时用的滤镜
+    fn filter(self) -> &'static str {
+        match self {
+            Rotation::Right => "transpose=clock",
+            Rotation::Left => "transpose=cclock",
+            Rotation::Half => "hflip,vflip",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum QuickOp {
+    Rotate(Rotation),
+    StripAudio,
+}
+
+impl QuickOp {
+    pub fn label(self) -> &'static str {
+        match self {
+            QuickOp::Rotate(r) => r.label(),
+            QuickOp::StripAudio => "去掉音轨",
+        }
+    }
+
+    // 输出文件名里加的后缀：clip.mp4 -> clip_rotated.mp4
+    pub fn suffix(self) -> &'static str {
+        match self {
+            QuickOp::Rotate(_) => "rotated",
+            QuickOp::StripAudio => "noaudio",
+        }
+    }
+}
+
+// 输出沿用源文件的容器
+pub fn container(input: &str) -> String {
+    Path::new(input).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+// mov 一族把旋转写在轨道头的显示矩阵里，播放器都认；
+// mkv、avi、flv、wmv 等没有通用的旋转标记，只能把画面真的转过来
+pub fn carries_rotation(container: &str) -> bool {
+    matches!(container, "mp4" | "m4v" | "mov" | "3gp")
+}
+
+// 源文件已有的旋转（逆时针度数）：新版 ffprobe 报显示矩阵，老文件只有顺时针的 rotate 标签
+pub fn source_rotation(info: &MediaInfo) -> i32 {
+    let Some(video) = info.streams.iter().find(|s| s.codec_type == "video") else {
+        return 0;
+    };
+    let matrix = video.props.iter()
+        .find(|(k, _)| k.starts_with("side_data_list.") && k.ends_with(".rotation"))
+        .and_then(|(_, v)| v.parse::<f64>().ok());
+    match matrix {
+        Some(degrees) => degrees.round() as i32,
+        None => video.props.get("tags.rotate").and_then(|v| v.parse::<i32>().ok()).map_or(0, |cw| -cw),
+    }
+}
+
+// 转到 -180～180 之间，ffmpeg 和播放器对 0/90/180/-90 的写法最稳妥
+fn normalize(degrees: i32) -> i32 {
+    match degrees.rem_euclid(360) {
+        d if d > 180 => d - 360,
+        d => d,
+    }
+}
+
+pub fn plan(settings: &JobSettings, op: QuickOp, info: &MediaInfo, input: &str, output: &str) -> JobPlan {
+    let mut job = JobPlan { outputs: vec![output.to_string()], ..Default::default() };
+    let container = container(input);
+    let copy = match op {
+        QuickOp::Rotate(_) => carries_rotation(&container),
+        QuickOp::StripAudio => true,
+    };
+    // 流复制不解码，硬件解码用不上
+    let input_settings = if copy { JobSettings { gpu: "CPU".to_string(), ..settings.clone() } } else { settings.clone() };
+    let mut args = plan::input_args(&input_settings, input);
+
+    match op {
+        QuickOp::Rotate(rotation) if copy => {
+            let degrees = normalize(source_rotation(info) + rotation.ccw_degrees());
+            if let Some(at) = args.position("-i") {
+                args.insert(at, Source::Quick, degrees.to_string());
+                args.insert(at, Source::Quick, "-display_rotation:v:0".to_string());
+            }
+            args.push(Source::Quick, &["-map", "0:v", "-map", "0:a?", "-map", "0:s?", "-c", "copy"]);
+            job.notes.push(format!("{}: {} 可以只改显示方向标记，不重新编码", rotation.label(), container));
+        }
+        QuickOp::Rotate(rotation) => {
+            let codec = plan::video_codec(settings);
+            args.push(Source::Quick, &["-map", "0:v:0", "-map", "0:a?", "-vf", rotation.filter()]);
+            args.push(Source::Codec, &["-c:v", codec]);
+            args.append(plan::video_codec_args(settings, true));
+            args.push(Source::Quick, &["-c:a", "copy"]);
+            job.notes.push(format!(
+                "{}: {} 容器没有通用的旋转标记，改为重新编码视频（{}），耗时和普通转换相同；音频直接复制",
+                rotation.label(), if container.is_empty() { "源文件的" } else { &container }, codec
+            ));
+        }
+        QuickOp::StripAudio => {
+            args.push(Source::Quick, &["-map", "0:v", "-map", "0:s?", "-c", "copy", "-an"]);
+            if info.streams.iter().any(|s| s.codec_type == "audio") {
+                job.notes.push("去掉音轨: 视频和字幕直接复制，不重新编码".to_string());
+            } else {
+                job.notes.push("去掉音轨: 源文件本来就没有音轨，输出只是一份复制".to_string());
+            }
+        }
+    }
+    args.append(plan::output_tail_args(settings));
+    args.push(Source::Output, &[output]);
+    job.runs.push(args);
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+
+    // 一条视频，props 里是旋转信息；audio 为 true 时再加一条音频
+    fn media(props: &[(&str, &str)], audio: bool) -> MediaInfo {
+        let mut video = StreamInfo { codec_type: "video".to_string(), codec_name: "h264".to_string(), ..Default::default() };
+        for (k, v) in props {
+            video.props.insert(k.to_string(), v.to_string());
+        }
+        let mut streams = vec![video];
+        if audio {
+            streams.push(StreamInfo { codec_type: "audio".to_string(), codec_name: "aac".to_string(), ..Default::default() });
+        }
+        MediaInfo { duration: 10.0, streams, ..Default::default() }
+    }
+
+    fn argv(job: &JobPlan) -> String {
+        job.runs[0].argv().join(" ")
+    }
+
+    #[test]
+    fn container_from_the_input() {
+        assert_eq!(container("clip.MOV"), "mov");
+        assert_eq!(container("旅行.2023.mkv"), "mkv");
+        assert_eq!(container("clip"), "");
+        assert!(carries_rotation("mp4") && carries_rotation("3gp"));
+        assert!(!carries_rotation("mkv") && !carries_rotation(""));
+    }
+
+    #[test]
+    fn source_rotation_prefers_the_display_matrix() {
+        assert_eq!(source_rotation(&media(&[], false)), 0);
+        assert_eq!(source_rotation(&media(&[("side_data_list.0.rotation", "-90")], false)), -90);
+        // 老文件的 rotate 标签是顺时针
+        assert_eq!(source_rotation(&media(&[("tags.rotate", "90")], false)), -90);
+        assert_eq!(source_rotation(&media(&[("side_data_list.0.rotation", "180"), ("tags.rotate", "90")], false)), 180);
+        assert_eq!(source_rotation(&MediaInfo::default()), 0);
+    }
+
+    #[test]
+    fn normalize_wraps_into_half_turns() {
+        assert_eq!(normalize(0), 0);
+        assert_eq!(normalize(270), -90);
+        assert_eq!(normalize(-270), 90);
+        assert_eq!(normalize(180), 180);
+        assert_eq!(normalize(-180), 180);
+        assert_eq!(normalize(450), 90);
+    }
+
+    #[test]
+    fn rotate_copies_when_the_container_has_a_flag() {
+        let settings = JobSettings { gpu: "NVIDIA".to_string(), ..JobSettings::default() };
+        // 已经向右转过的视频再向右转一次成了 180°
+        let info = media(&[("side_data_list.0.rotation", "-90")], true);
+        let job = plan(&settings, QuickOp::Rotate(Rotation::Right), &info, "phone.mp4", "phone_rotated.mp4");
+        let cmd = argv(&job);
+        assert!(cmd.contains("-display_rotation:v:0 180 -i phone.mp4"), "{}", cmd);
+        assert!(cmd.contains("-c copy"));
+        assert!(!cmd.contains("-hwaccel"));
+        assert!(cmd.ends_with("phone_rotated.mp4"));
+        assert_eq!(job.outputs, ["phone_rotated.mp4"]);
+    }
+
+    #[test]
+    fn rotate_reencodes_without_a_flag() {
+        let job = plan(&JobSettings::default(), QuickOp::Rotate(Rotation::Left), &media(&[], true), "clip.mkv", "clip_rotated.mkv");
+        let cmd = argv(&job);
+        assert!(cmd.contains("-vf transpose=cclock"), "{}", cmd);
+        assert!(cmd.contains("-c:v libx264"));
+        assert!(cmd.contains("-c:a copy"));
+        assert!(!cmd.contains("-display_rotation"));
+        assert!(job.notes[0].contains("mkv 容器没有通用的旋转标记"));
+        let job = plan(&JobSettings::default(), QuickOp::Rotate(Rotation::Half), &media(&[], false), "clip", "out.mp4");
+        assert!(argv(&job).contains("-vf hflip,vflip"));
+        assert!(job.notes[0].contains("源文件的 容器"));
+    }
+
+    #[test]
+    fn strip_audio_copies_the_rest() {
+        let job = plan(&JobSettings::default(), QuickOp::StripAudio, &media(&[], true), "clip.mp4", "clip_noaudio.mp4");
+        assert!(argv(&job).contains("-map 0:v -map 0:s? -c copy -an"));
+        assert!(job.notes[0].contains("不重新编码"));
+        let job = plan(&JobSettings::default(), QuickOp::StripAudio, &media(&[], false), "clip.mp4", "clip_noaudio.mp4");
+        assert!(job.notes[0].contains("本来就没有音轨"));
+        assert_eq!((QuickOp::StripAudio.suffix(), QuickOp::Rotate(Rotation::Half).suffix()), ("noaudio", "rotated"));
+    }
+}
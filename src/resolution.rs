@@ -0,0 +1,84 @@
+// 输出分辨率：保持原样、按常见档位缩放，或指定宽×高。
+// 档位按短边算，横拍的 4K 缩到 1080p 是 1920x1080，竖拍的是 1080x1920，宽度跟着比例取偶数
+
+// 界面上可选的档位（短边像素）
+pub const HEIGHTS: [u32; 5] = [2160, 1440, 1080, 720, 480];
+// 自定义宽高的上限，超过这个编码器基本都不接受
+pub const MAX_SIDE: u32 = 16384;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Resolution {
+    #[default]
+    Original,
+    Height(u32),
+    Custom(u32, u32),
+}
+
+impl Resolution {
+    pub fn label(self) -> String {
+        match self {
+            Resolution::Original => "原始".to_string(),
+            Resolution::Height(h) => format!("{}p", h),
+            Resolution::Custom(w, h) => format!("自定义 {}x{}", w, h),
+        }
+    }
+
+    // 任务列表里的写法：original、1080p、1280x720
+    pub fn tag(self) -> String {
+        match self {
+            Resolution::Original => "original".to_string(),
+            Resolution::Height(h) => format!("{}p", h),
+            Resolution::Custom(w, h) => format!("{}x{}", w, h),
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Resolution> {
+        if tag == "original" {
+            return Some(Resolution::Original);
+        }
+        if let Some(h) = tag.strip_suffix('p') {
+            return h.parse().ok().map(Resolution::Height);
+        }
+        let (w, h) = tag.split_once('x')?;
+        Some(Resolution::Custom(w.parse().ok()?, h.parse().ok()?))
+    }
+
+    // 自定义宽高必须是不为零的偶数（4:2:0 的色度按 2x2 采样）
+    pub fn check(self) -> Result<(), String> {
+        let Resolution::Custom(w, h) = self else {
+            return Ok(());
+        };
+        for (name, v) in [("宽度", w), ("高度", h)] {
+            if v == 0 {
+                return Err(format!("{}不能为 0", name));
+            }
+            if v % 2 != 0 {
+                return Err(format!("{} {} 不是偶数", name, v));
+            }
+            if v > MAX_SIDE {
+                return Err(format!("{} {} 超过 {}", name, v, MAX_SIDE));
+            }
+        }
+        Ok(())
+    }
+
+    // scale 滤镜。档位的表达式里有逗号，用单引号括起来，放进 -vf 的滤镜链也不会被拆开
+    pub fn filter(self) -> Option<String> {
+        match self {
+            Resolution::Original => None,
+            Resolution::Height(h) => Some(format!("scale='if(gte(iw,ih),-2,{h})':'if(gte(iw,ih),{h},-2)'")),
+            Resolution::Custom(w, h) => Some(format!("scale={}:{},setsar=1", w, h)),
+        }
+    }
+
+    // 缩放后的画面大小，用来检查硬件编码器的上限
+    pub fn apply(self, (w, h): (u32, u32)) -> (u32, u32) {
+        let even = |v: u64| ((v / 2 * 2) as u32).max(2);
+        match self {
+            Resolution::Original => (w, h),
+            Resolution::Height(t) if w >= h => (even(w as u64 * t as u64 / h.max(1) as u64), t),
+            Resolution::Height(t) => (t, even(h as u64 * t as u64 / w.max(1) as u64)),
+            Resolution::Custom(cw, ch) => (cw, ch),
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::probe::MediaInfo;
+
+// 帧率重映射：把 25 fps 的 PAL 片源放慢成 23.976（还原 PAL 加速），或反过来。
+// 每一帧都保留，只改时间戳；音频按同样的比例伸缩才能保持同步。
+// 比例全程用整数分数计算，1000/1001 的帧率不会因为浮点误差越走越偏
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// 约分后的帧率 num/den
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rate {
+    pub num: u64,
+    pub den: u64,
+}
+
+impl Rate {
+    pub fn new(num: u64, den: u64) -> Option<Rate> {
+        if num == 0 || den == 0 {
+            return None;
+        }
+        let g = gcd(num, den);
+        Some(Rate { num: num / g, den: den / g })
+    }
+
+    // 25、24000/1001；23.976、29.97 这类写法按 NTSC 的 1000/1001 理解，其他小数按字面
+    pub fn parse(text: &str) -> Option<Rate> {
+        let text = text.trim();
+        if let Some((n, d)) = text.split_once('/') {
+            return Rate::new(n.trim().parse().ok()?, d.trim().parse().ok()?);
+        }
+        if let Some(ntsc) = NTSC.iter().find(|(label, _)| *label == text) {
+            return Some(ntsc.1);
+        }
+        let (whole, frac) = text.split_once('.').unwrap_or((text, ""));
+        if frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let scale = 10u64.pow(frac.len() as u32);
+        let frac: u64 = if frac.is_empty() { 0 } else { frac.parse().ok()? };
+        Rate::new(whole.parse::<u64>().ok()?.checked_mul(scale)?.checked_add(frac)?, scale)
+    }
+
+    // 任务列表里保存的写法
+    pub fn tag(self) -> String {
+        format!("{}/{}", self.num, self.den)
+    }
+
+    pub fn label(self) -> String {
+        if let Some((label, _)) = NTSC.iter().find(|(_, r)| *r == self) {
+            return label.to_string();
+        }
+        if self.den == 1 { self.num.to_string() } else { format!("{:.3}", self.as_f64()) }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+const NTSC: [(&str, Rate); 5] = [
+    ("23.976", Rate { num: 24000, den: 1001 }),
+    ("29.97", Rate { num: 30000, den: 1001 }),
+    ("47.952", Rate { num: 48000, den: 1001 }),
+    ("59.94", Rate { num: 60000, den: 1001 }),
+    ("119.88", Rate { num: 120000, den: 1001 }),
+];
+
+// 界面上可选的帧率
+pub fn common_rates() -> Vec<Rate> {
+    [(24000, 1001), (24, 1), (25, 1), (30000, 1001), (30, 1), (50, 1), (60000, 1001), (60, 1)]
+        .into_iter()
+        .filter_map(|(n, d)| Rate::new(n, d))
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Retime {
+    pub from: Rate,
+    pub to: Rate,
+    // 音频伸缩时保持音调（atempo）；关掉时像磁带变速一样音调跟着变
+    pub keep_pitch: bool,
+}
+
+impl Retime {
+    // PAL 加速还原：25 -> 23.976
+    pub fn pal_slowdown() -> Retime {
+        Retime { from: Rate { num: 25, den: 1 }, to: Rate { num: 24000, den: 1001 }, keep_pitch: true }
+    }
+
+    // 播放速度 = 目标帧率 / 源帧率，约分后的 (分子, 分母)
+    pub fn speed(&self) -> (u64, u64) {
+        let num = self.to.num as u128 * self.from.den as u128;
+        let den = self.to.den as u128 * self.from.num as u128;
+        let g = gcd_u128(num, den);
+        ((num / g) as u64, (den / g) as u64)
+    }
+
+    // 输出时长
+    pub fn scale_secs(&self, secs: f64) -> f64 {
+        let (n, d) = self.speed();
+        secs * d as f64 / n as f64
+    }
+
+    pub fn label(&self) -> String {
+        let (n, d) = self.speed();
+        format!(
+            "{} → {} fps，速度 ×{}，{}",
+            self.from.label(),
+            self.to.label(),
+            decimal(n as f64 / d as f64),
+            if self.keep_pitch { "保持音调" } else { "音调随速度变化" }
+        )
+    }
+
+    // 时间戳乘以 源/目标，再按目标帧率输出，不丢帧也不补帧
+    pub fn video_filter(&self) -> String {
+        let (n, d) = self.speed();
+        format!("setpts={}/{}*PTS", d, n)
+    }
+
+    // 音频滤镜。不保持音调时先按整数采样率 asetrate 变速，再用 atempo 补上取整的差，
+    // 总的速度仍然正好是 n/d
+    pub fn audio_filter(&self, sample_rate: u64) -> String {
+        let (n, d) = self.speed();
+        if self.keep_pitch {
+            return atempo(n as u128, d as u128);
+        }
+        let scaled = sample_rate as u128 * n as u128;
+        let rate = (scaled + d as u128 / 2) / d as u128;
+        let mut filter = format!("asetrate={},aresample={}", rate, sample_rate);
+        // 剩下的比例 = (sr*n/d) / rate = sr*n / (d*rate)
+        let (rn, rd) = (scaled, d as u128 * rate);
+        if rn != rd {
+            filter.push(',');
+            filter.push_str(&atempo(rn, rd));
+        }
+        filter
+    }
+}
+
+fn gcd_u128(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd_u128(b, a % b) }
+}
+
+// 十二位小数足够：两小时的片子误差不到一微秒
+fn decimal(ratio: f64) -> String {
+    let text = format!("{:.12}", ratio);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// atempo 单级只接受 0.5~100，更慢时串联
+fn atempo(n: u128, d: u128) -> String {
+    let mut stages = Vec::new();
+    let mut ratio = n as f64 / d as f64;
+    while ratio < 0.5 {
+        stages.push("atempo=0.5".to_string());
+        ratio *= 2.0;
+    }
+    stages.push(format!("atempo={}", decimal(ratio)));
+    stages.join(",")
+}
+
+// 源文件第一条音轨的采样率，读不到时按 48 kHz
+pub fn sample_rate(info: &MediaInfo) -> u64 {
+    info.streams
+        .iter()
+        .find(|s| s.codec_type == "audio")
+        .and_then(|s| s.props.get("sample_rate"))
+        .and_then(|r| r.parse().ok())
+        .filter(|r| *r > 0)
+        .unwrap_or(48000)
+}
+
+// 源文件的帧率，界面上作为默认的源帧率
+pub fn source_rate(info: &MediaInfo) -> Option<Rate> {
+    let video = info.streams.iter().find(|s| s.codec_type == "video")?;
+    let text = video.props.get("avg_frame_rate").filter(|r| r.as_str() != "0/0").or_else(|| video.props.get("r_frame_rate"))?;
+    Rate::parse(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+
+    fn rate(num: u64, den: u64) -> Rate {
+        Rate::new(num, den).unwrap()
+    }
+
+    #[test]
+    fn parse_rates() {
+        assert_eq!(Rate::parse("25"), Some(rate(25, 1)));
+        assert_eq!(Rate::parse(" 24000/1001 "), Some(rate(24000, 1001)));
+        assert_eq!(Rate::parse("48/2"), Some(rate(24, 1)));
+        // NTSC 的写法按 1000/1001 理解，其他小数按字面
+        assert_eq!(Rate::parse("23.976"), Some(rate(24000, 1001)));
+        assert_eq!(Rate::parse("59.94"), Some(rate(60000, 1001)));
+        assert_eq!(Rate::parse("23.98"), Some(rate(1199, 50)));
+        assert_eq!(Rate::parse("29.970"), Some(rate(2997, 100)));
+        assert_eq!(Rate::parse("12.5"), Some(rate(25, 2)));
+        for bad in ["", "0", "0/0", "25/0", "abc", "1.2.3", "-25", "2.5e1", "1.1234567891", "99999999999999999999"] {
+            assert_eq!(Rate::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn labels_and_tags() {
+        assert_eq!(rate(24000, 1001).label(), "23.976");
+        assert_eq!(rate(50, 2).label(), "25");
+        assert_eq!(rate(1199, 50).label(), "23.980");
+        for r in common_rates() {
+            assert_eq!(Rate::parse(&r.tag()), Some(r));
+            assert_eq!(Rate::parse(&r.label()), Some(r));
+        }
+    }
+
+    #[test]
+    fn pal_slowdown_is_exact() {
+        let retime = Retime::pal_slowdown();
+        // 24000/1001 ÷ 25 = 960/1001，约分后没有浮点
+        assert_eq!(retime.speed(), (960, 1001));
+        assert_eq!(retime.video_filter(), "setpts=1001/960*PTS");
+        // 两小时 180000 帧按 24000/1001 播放正好 7507.5 秒
+        assert_eq!(retime.scale_secs(7200.0), 7507.5);
+        assert_eq!(retime.label(), "25 → 23.976 fps，速度 ×0.959040959041，保持音调");
+        assert_eq!(retime.audio_filter(48000), "atempo=0.959040959041");
+        // 反过来是加速
+        let speedup = Retime { from: rate(24000, 1001), to: rate(25, 1), keep_pitch: true };
+        assert_eq!(speedup.speed(), (1001, 960));
+        assert_eq!(speedup.video_filter(), "setpts=960/1001*PTS");
+        assert_eq!(speedup.audio_filter(48000), "atempo=1.042708333333");
+    }
+
+    #[test]
+    fn pitch_shift_keeps_the_exact_ratio() {
+        let retime = Retime { keep_pitch: false, ..Retime::pal_slowdown() };
+        for (sample_rate, filter) in [
+            (48000, "asetrate=46034,aresample=48000,atempo=0.999999262153"),
+            (44100, "asetrate=42294,aresample=44100,atempo=0.999993055604"),
+        ] {
+            assert_eq!(retime.audio_filter(sample_rate), filter);
+            // asetrate 的取整由 atempo 补上：rate/sr × (sr·n)/(d·rate) = n/d
+            let rate: u128 = filter.split(['=', ',']).nth(1).unwrap().parse().unwrap();
+            let (n, d) = retime.speed();
+            let (rn, rd) = (sample_rate as u128 * n as u128, d as u128 * rate);
+            assert_eq!(rate * rn * d as u128, sample_rate as u128 * rd * n as u128);
+        }
+        // 整除时不需要 atempo
+        let double = Retime { from: rate(25, 1), to: rate(50, 1), keep_pitch: false };
+        assert_eq!(double.audio_filter(48000), "asetrate=96000,aresample=48000");
+    }
+
+    #[test]
+    fn slow_atempo_is_chained() {
+        assert_eq!(atempo(1, 5), "atempo=0.5,atempo=0.5,atempo=0.8");
+        assert_eq!(atempo(1, 2), "atempo=0.5");
+        assert_eq!(atempo(3, 1), "atempo=3");
+    }
+
+    #[test]
+    fn rates_from_probe() {
+        let stream = |kind: &str, props: &[(&str, &str)]| StreamInfo {
+            codec_type: kind.to_string(),
+            props: props.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        };
+        let info = MediaInfo {
+            streams: vec![
+                stream("audio", &[("sample_rate", "44100")]),
+                stream("video", &[("avg_frame_rate", "0/0"), ("r_frame_rate", "25/1")]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(sample_rate(&info), 44100);
+        assert_eq!(source_rate(&info), Some(rate(25, 1)));
+        assert_eq!(sample_rate(&MediaInfo::default()), 48000);
+        assert_eq!(source_rate(&MediaInfo::default()), None);
+    }
+}
@@ -0,0 +1,782 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bottleneck;
+use crate::cancel::CancelToken;
+use crate::crash;
+use crate::inspect;
+use crate::live;
+use crate::logbuf::LogBuffer;
+use crate::probe::MediaInfo;
+use crate::process;
+use crate::timestamp;
+use crate::warnings::Tally;
+
+// 停止并保留：让 ffmpeg 自己收尾写完文件；停止并删除：直接结束并清理输出
+#[derive(Clone, Copy, PartialEq)]
+pub enum StopMode {
+    Keep,
+    Delete,
+}
+
+// 向 ffmpeg 的 stdin 发送 q，和在终端里按 q 一样正常结束
+pub fn send_quit(child: &mut Child) -> bool {
+    child.stdin.as_mut().is_some_and(|stdin| stdin.write_all(b"q").and_then(|_| stdin.flush()).is_ok())
+}
+
+pub const TAIL_LINES: usize = 200;
+// 转换失败时日志里引用 stderr 最后几行
+pub const QUOTE_LINES: usize = 5;
+
+// 实时写进界面日志的 stderr 行：版本和编译配置那一段、逐帧的统计（附加参数里又打开了 -stats 时）不写，
+// 编码器警告、流的映射和报错原样保留
+fn worth_logging(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !(line.trim().is_empty()
+        || line.starts_with("ffmpeg version")
+        || trimmed.starts_with("built with")
+        || trimmed.starts_with("configuration:")
+        || (line.starts_with("  lib") && line.contains('/'))
+        || trimmed.starts_with("frame=")
+        || trimmed.starts_with("size="))
+}
+
+// stderr 尾部的最后几行（跳过空行），失败时引用
+pub fn last_lines(tail: &str, n: usize) -> Vec<&str> {
+    let mut lines: Vec<&str> = tail.lines().rev().filter(|l| !l.trim().is_empty()).take(n).collect();
+    lines.reverse();
+    lines
+}
+
+// 输入是标准输入时 ffmpeg 的 stdin 用来送媒体数据，不能再发 q。
+// 这个线程把 ffui 自己的标准输入转过去，来源结束或写入端被拿走（停止）时 ffmpeg 读到结尾正常收尾。
+// 停止后线程可能还卡在读 ffui 的标准输入上，下一块读到后发现写入端已关闭就退出
+fn pump_stdin(sink: Arc<Mutex<Option<ChildStdin>>>) {
+    thread::spawn(move || {
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut source = io::stdin();
+        loop {
+            let n = match source.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            let mut sink = sink.lock().unwrap();
+            let Some(pipe) = sink.as_mut() else { return };
+            if pipe.write_all(&buf[..n]).is_err() {
+                break;
+            }
+        }
+        sink.lock().unwrap().take();
+    });
+}
+
+// -i 后面是标准输入
+fn reads_stdin(args: &[String]) -> bool {
+    args.windows(2).any(|w| w[0] == "-i" && live::is_stdin(&w[1]))
+}
+
+pub struct RunOutcome {
+    pub exited_ok: bool,
+    // ffmpeg 的退出码，被信号结束或被停止时为 None
+    pub exit_code: Option<i32>,
+    pub stopped: bool,
+    // stderr 最后几行，用于判断失败原因，也随失败记录保存
+    pub tail: LogBuffer,
+    // 整个 stderr 里匹配到的警告
+    pub warnings: Tally,
+    // 被信号或异常结束（崩溃）时的原因
+    pub crash: Option<crash::Crash>,
+}
+
+// 检查停止请求的间隔
+const POLL: Duration = Duration::from_millis(100);
+// 读 stderr 时最多这么久更新一次最后活动时间
+const ACTIVITY_EVERY: Duration = Duration::from_millis(100);
+
+// -progress 输出的一块：若干 key=value，以 progress=continue 或 progress=end 结束
+#[derive(Clone, Copy, Default)]
+pub struct ProgressBlock {
+    // 已输出的时长（秒）
+    pub out_time: Option<f64>,
+    // 已写入输出的字节数
+    pub total_size: Option<u64>,
+    // 编码速度：每秒处理的帧数、相对实时的倍数（speed=2.3x）、输出码率（kbit/s）。开头几块是 N/A
+    pub fps: Option<f64>,
+    pub speed: Option<f64>,
+    pub bitrate_k: Option<f64>,
+    // 以 progress=end 结束：ffmpeg 已经写完，进度直接到头
+    pub ended: bool,
+}
+
+// 超过这个长度的时间当作无效值（如 ffmpeg 没有时间戳时打印的 INT64_MAX）
+const MAX_OUT_TIME: Duration = Duration::from_secs(366 * 24 * 3600);
+
+// -progress 里已输出时长的一行：out_time_us=1234567（out_time_ms 的单位其实也是微秒）
+// 或 out_time=00:00:01.234567。开头几块和某些输入上是 N/A，有的版本只给 out_time；
+// 刚开始时可能是负数，这些都返回 None，不覆盖已有的值
+pub fn parse_progress_line(line: &str) -> Option<Duration> {
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+    let time = match key.trim() {
+        "out_time_us" | "out_time_ms" => Duration::from_micros(u64::try_from(value.parse::<i64>().ok()?).ok()?),
+        "out_time" => timestamp::parse(value).ok()?,
+        _ => return None,
+    };
+    (time <= MAX_OUT_TIME).then_some(time)
+}
+
+// N/A、0 和解析不了的都当作没有
+fn positive(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite() && *v > 0.0)
+}
+
+// 界面上进度条下面显示的速度、帧率、码率和已写入大小。每个值保留最近一次有效的，
+// 新任务开始时清空，结束后停在最后的值
+#[derive(Clone, Copy, Default)]
+pub struct JobStats {
+    pub speed: Option<f64>,
+    pub fps: Option<f64>,
+    pub bitrate_k: Option<f64>,
+    pub written: Option<u64>,
+}
+
+impl JobStats {
+    pub fn update(&mut self, block: &ProgressBlock) {
+        self.speed = block.speed.or(self.speed);
+        self.fps = block.fps.or(self.fps);
+        self.bitrate_k = block.bitrate_k.or(self.bitrate_k);
+        self.written = block.total_size.or(self.written);
+    }
+
+    // "2.3x · 68 fps · 4.2 Mbit/s · 已写入 213 MB"，一项都没有时为 None
+    pub fn label(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(speed) = self.speed {
+            parts.push(format!("{:.1}x", speed));
+        }
+        if let Some(fps) = self.fps {
+            parts.push(format!("{:.0} fps", fps));
+        }
+        match self.bitrate_k {
+            Some(k) if k >= 1000.0 => parts.push(format!("{:.1} Mbit/s", k / 1000.0)),
+            Some(k) => parts.push(format!("{:.0} kbit/s", k)),
+            None => {}
+        }
+        if let Some(bytes) = self.written.filter(|b| *b > 0) {
+            parts.push(format!("已写入 {}", inspect::format_bytes(bytes as f64)));
+        }
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+}
+
+// 任务开始的时刻；结束后停在总用时，界面上继续显示
+#[derive(Clone, Copy, Default)]
+pub struct Clock {
+    started: Option<Instant>,
+    total: Option<Duration>,
+}
+
+impl Clock {
+    pub fn start(now: Instant) -> Clock {
+        Clock { started: Some(now), total: None }
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if self.total.is_none() {
+            self.total = self.started.map(|t| now.duration_since(t));
+        }
+    }
+
+    pub fn elapsed(&self, now: Instant) -> Option<Duration> {
+        self.total.or_else(|| self.started.map(|t| now.duration_since(t)))
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.total.is_some()
+    }
+}
+
+// 工作线程发布给界面的整份状态，见 latest.rs
+#[derive(Clone, Copy, Default)]
+pub struct Progress {
+    // 0–100
+    pub progress: f32,
+    // 没有时长时改为显示已编码的时长
+    pub live_secs: Option<f64>,
+    pub eta_secs: Option<f64>,
+    pub stats: JobStats,
+    pub clock: Clock,
+    // 慢在编码器还是磁盘
+    pub bottleneck: bottleneck::Verdict,
+}
+
+// 进度不到 3% 时估计的剩余时间忽大忽小，不显示；超过 99% 时只说即将完成
+pub const ETA_MIN_FRACTION: f64 = 0.03;
+pub const ETA_DONE_FRACTION: f64 = 0.99;
+
+pub enum Eta {
+    Hidden,
+    Secs(f64),
+    AlmostDone,
+}
+
+// fraction 是整个任务完成的比例。有最近一段时间的速度（recent）时按它估计，
+// 否则按已用时间和完成比例平均算
+pub fn eta(fraction: f64, elapsed: Duration, recent: Option<f64>) -> Eta {
+    if fraction > ETA_DONE_FRACTION {
+        Eta::AlmostDone
+    } else if fraction < ETA_MIN_FRACTION {
+        Eta::Hidden
+    } else {
+        Eta::Secs(recent.unwrap_or(elapsed.as_secs_f64() * (1.0 - fraction) / fraction))
+    }
+}
+
+#[derive(Default)]
+struct BlockParser {
+    current: ProgressBlock,
+}
+
+impl BlockParser {
+    // 一块读完时返回它
+    fn feed(&mut self, line: &str) -> Option<ProgressBlock> {
+        if let Some(time) = parse_progress_line(line) {
+            self.current.out_time = Some(time.as_secs_f64());
+            return None;
+        }
+        let (key, value) = line.split_once('=')?;
+        match key.trim() {
+            "total_size" => {
+                if let Ok(bytes) = value.trim().parse::<u64>() {
+                    self.current.total_size = Some(bytes);
+                }
+            }
+            "fps" => self.current.fps = positive(value),
+            "speed" => self.current.speed = positive(value.trim().trim_end_matches('x')),
+            "bitrate" => self.current.bitrate_k = positive(value.trim().trim_end_matches("kbits/s")),
+            "progress" => {
+                self.current.ended = value.trim() == "end";
+                return Some(std::mem::take(&mut self.current));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+// 读 -progress 输出的线程：每读完一块放进 latest，覆盖还没被取走的旧块，再叫醒等待的一方。
+// 处理跟不上时中间的块会被跳过，但最后一块一定留在 latest 里
+fn spawn_block_reader(
+    stdout: impl Read + Send + 'static,
+    latest: Arc<Mutex<Option<ProgressBlock>>>,
+    activity: Arc<Mutex<Instant>>,
+    notify: mpsc::SyncSender<()>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut parser = BlockParser::default();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(block) = parser.feed(&line) {
+                *activity.lock().unwrap() = Instant::now();
+                *latest.lock().unwrap() = Some(block);
+                let _ = notify.try_send(());
+            }
+        }
+    })
+}
+
+// 进度的算法。流复制的转封装几秒钟就能处理完几十分钟的内容，out_time 一下子就跳到头，
+// 进度条没有意义，剩余时间也忽长忽短；这时已写入的字节数更能反映进度
+#[derive(Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // 已输出时长 / 总时长
+    Time,
+    // 已写入字节 / 预计输出大小，再和时长比例混合
+    Bytes(f64),
+}
+
+// 字节比例在混合进度里的权重。预计大小按源文件减去丢弃的流估算，容器开销会有出入，
+// 留一部分时长比例，临近结束时两者一起收拢到 100%
+const BYTE_WEIGHT: f64 = 0.75;
+// 剩余时间按最近这段时间的平均速度算
+const ETA_WINDOW: Duration = Duration::from_secs(10);
+
+// 命令里所有流都是直接复制（或被去掉）时算转封装
+fn is_remux(args: &[String]) -> bool {
+    let value = |flags: &[&str]| args.windows(2).rev().find(|w| flags.contains(&w[0].as_str())).map(|w| w[1].as_str());
+    if value(&["-c", "-codec"]) == Some("copy") {
+        return true;
+    }
+    let video = args.iter().any(|a| a == "-vn") || value(&["-c:v", "-vcodec"]) == Some("copy");
+    let audio = args.iter().any(|a| a == "-an") || value(&["-c:a", "-acodec"]) == Some("copy");
+    video && audio
+}
+
+// 输出里保留了源文件的哪些流；-map 写法认不出时返回 None。
+// 没有 -map 时 ffmpeg 默认各取一路视频、音频和字幕，这里按各类的第一路算
+fn kept_streams(args: &[String], info: &MediaInfo) -> Option<Vec<bool>> {
+    let maps: Vec<&str> = args.windows(2).filter(|w| w[0] == "-map").map(|w| w[1].as_str()).collect();
+    let mut kept = vec![false; info.streams.len()];
+    let kind = |t: &str| match t {
+        "v" | "V" => Some("video"),
+        "a" => Some("audio"),
+        "s" => Some("subtitle"),
+        _ => None,
+    };
+    if maps.is_empty() {
+        for t in ["video", "audio", "subtitle"] {
+            if let Some(i) = info.streams.iter().position(|s| s.codec_type == t) {
+                kept[i] = true;
+            }
+        }
+    }
+    for map in maps {
+        // -map 0 是全部的流
+        if map.trim_end_matches('?') == "0" {
+            kept.fill(true);
+            continue;
+        }
+        let spec = map.trim_end_matches('?').strip_prefix("0:")?;
+        let (t, nth) = match spec.split_once(':') {
+            Some((t, n)) => (t, Some(n.parse::<usize>().ok()?)),
+            None => (spec, None),
+        };
+        let t = kind(t)?;
+        for (n, (i, _)) in info.streams.iter().enumerate().filter(|(_, s)| s.codec_type == t).enumerate() {
+            if nth.is_none_or(|nth| nth == n) {
+                kept[i] = true;
+            }
+        }
+    }
+    for (flag, t) in [("-vn", "video"), ("-an", "audio"), ("-sn", "subtitle")] {
+        if args.iter().any(|a| a == flag) {
+            for (i, s) in info.streams.iter().enumerate() {
+                if s.codec_type == t {
+                    kept[i] = false;
+                }
+            }
+        }
+    }
+    Some(kept)
+}
+
+// 转封装且知道源文件大小时按字节算。去掉了流就从源文件大小里减去它们的估计大小，
+// 有一路去掉的流估不出大小时退回按时长算
+pub fn strategy(args: &[String], info: &MediaInfo, input: &str) -> Strategy {
+    if !is_remux(args) || live::is_live(input) {
+        return Strategy::Time;
+    }
+    let Ok(size) = fs::metadata(input).map(|m| m.len() as f64) else {
+        return Strategy::Time;
+    };
+    let Some(kept) = kept_streams(args, info) else {
+        return Strategy::Time;
+    };
+    let mut dropped = 0.0;
+    for (stream, _) in info.streams.iter().zip(kept).filter(|(_, k)| !k) {
+        match inspect::stream_size(stream, info.duration) {
+            Some(bytes) => dropped += bytes,
+            None => return Strategy::Time,
+        }
+    }
+    let expected = size - dropped;
+    if expected > 0.0 { Strategy::Bytes(expected) } else { Strategy::Time }
+}
+
+// 把一次运行的进度块换算成 0～1 的进度和剩余时间
+pub struct Tracker {
+    strategy: Strategy,
+    duration: f64,
+    // 最近一段时间的 (时刻, 进度)
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl Tracker {
+    // duration 为 0 表示时长未知
+    pub fn new(strategy: Strategy, duration: f64) -> Tracker {
+        Tracker { strategy, duration, samples: VecDeque::new() }
+    }
+
+    // 两种比例都没有时返回 None，由调用方改为显示已编码的时长
+    pub fn update(&mut self, block: &ProgressBlock, now: Instant) -> Option<f64> {
+        if block.ended {
+            self.samples.push_back((now, 1.0));
+            return Some(1.0);
+        }
+        let time = block.out_time.filter(|_| self.duration > 0.0).map(|t| (t / self.duration).clamp(0.0, 1.0));
+        let bytes = match self.strategy {
+            Strategy::Bytes(expected) => block.total_size.map(|b| (b as f64 / expected).clamp(0.0, 1.0)),
+            Strategy::Time => None,
+        };
+        let frac = match (time, bytes) {
+            (Some(t), Some(b)) => BYTE_WEIGHT * b + (1.0 - BYTE_WEIGHT) * t,
+            (t, b) => t.or(b)?,
+        };
+        // 进度条不倒退
+        let frac = self.samples.back().map_or(frac, |(_, last)| frac.max(*last));
+        self.samples.push_back((now, frac));
+        while self.samples.len() > 2 && self.samples.front().is_some_and(|(t, _)| now.duration_since(*t) > ETA_WINDOW) {
+            self.samples.pop_front();
+        }
+        Some(frac)
+    }
+
+    // 窗口不到一秒或这段时间没有进展时不给估计
+    pub fn eta_secs(&self) -> Option<f64> {
+        let (&(t0, f0), &(t1, f1)) = (self.samples.front()?, self.samples.back()?);
+        let span = t1.duration_since(t0).as_secs_f64();
+        let gained = f1 - f0;
+        (span >= 1.0 && gained > 0.0).then(|| (1.0 - f1) * span / gained)
+    }
+}
+
+// 运行一次 ffmpeg，把每块进度回调给 on_progress，直到结束或 cancel 被取消。
+// 读取线程只按块解析、覆盖最新的一块，再用容量为 1 的通道通知；通知已满时丢弃，
+// 快速转封装时每秒几百块也不会因为等锁或等界面而堵住 ffmpeg 的管道。
+// 从标准输入读取时取消不结束进程，而是关闭 ffmpeg 的输入，等它写完文件。
+// 给了 log 时 stderr 边读边写进去（去掉版本信息和统计行），命令行模式下为 None
+pub fn run_ffmpeg(
+    args: &[String],
+    child_arc: &Arc<Mutex<Option<Child>>>,
+    cancel: &CancelToken,
+    last_activity: &Arc<Mutex<Instant>>,
+    log: Option<&Arc<Mutex<LogBuffer>>>,
+    mut on_progress: impl FnMut(ProgressBlock),
+) -> io::Result<RunOutcome> {
+    let mut cmd = process::command("ffmpeg");
+    // stdin 由 ffui 持有，只用来发送 q，不会回答任何提示
+    cmd.args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn()?;
+    let input = reads_stdin(args).then(|| Arc::new(Mutex::new(child.stdin.take())));
+    if let Some(sink) = &input {
+        pump_stdin(sink.clone());
+    }
+    *last_activity.lock().unwrap() = Instant::now();
+    let stderr = child.stderr.take();
+    let stderr_activity = last_activity.clone();
+    let feed = log.map(|log| log.lock().unwrap().feeder());
+    let stderr_reader = thread::spawn(move || {
+        let mut tail = LogBuffer::new(TAIL_LINES);
+        let mut warnings = Tally::default();
+        let mut noted: Option<Instant> = None;
+        if let Some(stderr) = stderr {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                // 挂起检测只看秒级，输出很多时不必每行都更新
+                let now = Instant::now();
+                if noted.is_none_or(|t| now.duration_since(t) >= ACTIVITY_EVERY) {
+                    *stderr_activity.lock().unwrap() = now;
+                    noted = Some(now);
+                }
+                warnings.feed(&line);
+                tail.push_line(&line);
+                if let Some(feed) = &feed
+                    && worth_logging(&line)
+                {
+                    let _ = feed.send(line);
+                }
+            }
+        }
+        (tail, warnings)
+    });
+    let stdout = child.stdout.take();
+    *child_arc.lock().unwrap() = Some(child);
+
+    let latest: Arc<Mutex<Option<ProgressBlock>>> = Arc::default();
+    let (notify, wake) = mpsc::sync_channel::<()>(1);
+    let stdout_reader = stdout.map(|stdout| spawn_block_reader(stdout, latest.clone(), last_activity.clone(), notify));
+
+    // 读取线程结束（通道断开）时再取一次，保证最后一块不会丢
+    let mut stopped = false;
+    loop {
+        let finished = matches!(wake.recv_timeout(POLL), Err(RecvTimeoutError::Disconnected));
+        if let Some(block) = latest.lock().unwrap().take() {
+            on_progress(block);
+        }
+        if finished {
+            break;
+        }
+        if cancel.is_cancelled() {
+            stopped = true;
+            match &input {
+                Some(sink) => drop(sink.lock().unwrap().take()),
+                None => break,
+            }
+        }
+    }
+
+    if input.is_none() && (stopped || cancel.is_cancelled()) {
+        if let Some(mut c) = child_arc.lock().unwrap().take() {
+            let _ = c.kill();
+            let _ = c.wait();
+        }
+        if let Some(reader) = stdout_reader {
+            let _ = reader.join();
+        }
+        return Ok(RunOutcome { exited_ok: false, exit_code: None, stopped: true, tail: LogBuffer::new(TAIL_LINES), warnings: Tally::default(), crash: None });
+    }
+
+    if let Some(reader) = stdout_reader {
+        let _ = reader.join();
+    }
+    let child = child_arc.lock().unwrap().take();
+    let status = child.and_then(|mut c| c.wait().ok());
+    let exited_ok = status.is_some_and(|s| s.success());
+    let exit_code = status.and_then(|s| s.code());
+    let crash = status.as_ref().and_then(crash::from_status);
+    let (tail, warnings) = stderr_reader.join().unwrap_or_else(|_| (LogBuffer::new(TAIL_LINES), Tally::default()));
+    // 调用方接着写的结果要排在 ffmpeg 的输出后面
+    if let Some(log) = log {
+        log.lock().unwrap().merge();
+    }
+    Ok(RunOutcome { exited_ok, exit_code, stopped, tail, warnings, crash })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(out_time_us: u64, end: bool) -> String {
+        format!(
+            "frame=10\nfps=25.0\nbitrate=1000.0kbits/s\ntotal_size={}\nout_time_us={}\nout_time=N/A\nspeed=1.5x\nprogress={}\n",
+            out_time_us / 10, out_time_us, if end { "end" } else { "continue" }
+        )
+    }
+
+    // 每次最多读出 chunk 个字节，像管道一样把行从中间切开
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn feed_all(parser: &mut BlockParser, text: &str) -> Vec<ProgressBlock> {
+        text.lines().filter_map(|l| parser.feed(l)).collect()
+    }
+
+    #[test]
+    fn progress_lines() {
+        assert_eq!(parse_progress_line("out_time_us=1500000"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_progress_line("out_time_ms=1500000"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_progress_line("out_time=00:01:02.500000"), Some(Duration::from_millis(62_500)));
+        for line in ["out_time_us=N/A", "out_time_us=-23220", "out_time=-00:00:00.023220", "out_time_us=9223372036854775807", "fps=25", "garbage"] {
+            assert_eq!(parse_progress_line(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn a_block_ends_at_progress() {
+        let mut parser = BlockParser::default();
+        let blocks = feed_all(&mut parser, &block(2_000_000, false));
+        assert_eq!(blocks.len(), 1);
+        let b = blocks[0];
+        assert_eq!(b.out_time, Some(2.0));
+        assert_eq!((b.total_size, b.fps, b.speed, b.bitrate_k, b.ended), (Some(200_000), Some(25.0), Some(1.5), Some(1000.0), false));
+        assert!(feed_all(&mut parser, &block(3_000_000, true))[0].ended);
+    }
+
+    // 读到一半的块不返回，N/A 不覆盖同一块里已有的值，也不带进下一块
+    #[test]
+    fn partial_and_missing_values() {
+        let mut parser = BlockParser::default();
+        assert!(feed_all(&mut parser, "out_time_us=1000000\nspeed=N/A\nfps=0.00\ntotal_size=N/A").is_empty());
+        let b = parser.feed("progress=continue").unwrap();
+        assert_eq!((b.out_time, b.speed, b.fps, b.total_size), (Some(1.0), None, None, None));
+        let b = feed_all(&mut parser, "out_time_us=N/A\nprogress=continue")[0];
+        assert_eq!(b.out_time, None);
+        // 没有 = 的行、未知的键、空行都跳过
+        assert!(feed_all(&mut parser, "\nhello\ndup_frames=3\n=\n").is_empty());
+    }
+
+    // 一行被拆成两次读入时（管道里常见）按行拼好再解析，结果和整块读入相同
+    #[test]
+    fn split_reads_give_the_same_blocks() {
+        let text: String = (1..=50).map(|i| block(i * 100_000, i == 50)).collect();
+        let whole = feed_all(&mut BlockParser::default(), &text);
+        for chunk in [1, 3, 7, 64] {
+            let reader = BufReader::new(Trickle { data: text.as_bytes(), chunk });
+            let mut parser = BlockParser::default();
+            let blocks: Vec<ProgressBlock> = reader.lines().map_while(Result::ok).filter_map(|l| parser.feed(&l)).collect();
+            assert_eq!(blocks.len(), whole.len());
+            assert!(blocks.iter().zip(&whole).all(|(a, b)| a.out_time == b.out_time && a.ended == b.ended));
+        }
+    }
+
+    // 两次 ffmpeg 调用的输出混在一起时（不该发生，但不能崩）块边界以 progress= 为准
+    #[test]
+    fn interleaved_keys_stay_within_their_block() {
+        let mut parser = BlockParser::default();
+        let blocks = feed_all(&mut parser, "out_time_us=1000000\nspeed=2.0x\nout_time_us=5000000\nprogress=continue\nspeed=3.0x\nprogress=end\n");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[0].out_time, blocks[0].speed), (Some(5.0), Some(2.0)));
+        assert_eq!((blocks[1].out_time, blocks[1].speed, blocks[1].ended), (None, Some(3.0), true));
+    }
+
+    // 读取线程一直在写，处理的一方很慢：中间的块可以丢，最后一块一定能拿到
+    #[test]
+    fn last_block_always_wins() {
+        const BLOCKS: u64 = 20_000;
+        for _ in 0..5 {
+            let text: String = (1..=BLOCKS).map(|i| block(i, i == BLOCKS)).collect();
+            let latest: Arc<Mutex<Option<ProgressBlock>>> = Arc::default();
+            let (notify, wake) = mpsc::sync_channel::<()>(1);
+            let activity = Arc::new(Mutex::new(Instant::now()));
+            let reader = spawn_block_reader(io::Cursor::new(text.into_bytes()), latest.clone(), activity, notify);
+            let mut seen = Vec::new();
+            loop {
+                let finished = matches!(wake.recv_timeout(POLL), Err(RecvTimeoutError::Disconnected));
+                if let Some(block) = latest.lock().unwrap().take() {
+                    seen.push(block);
+                    thread::sleep(Duration::from_micros(50));
+                }
+                if finished {
+                    break;
+                }
+            }
+            reader.join().unwrap();
+            let last = seen.last().unwrap();
+            assert!(last.ended);
+            assert_eq!(last.out_time, Some(BLOCKS as f64 / 1e6));
+            // 取到的块按顺序，没有旧块盖住新块
+            assert!(seen.windows(2).all(|w| w[0].out_time < w[1].out_time));
+        }
+    }
+
+    fn at(out_time: Option<f64>, total_size: Option<u64>) -> ProgressBlock {
+        ProgressBlock { out_time, total_size, ..Default::default() }
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn tracker_by_time() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(Strategy::Time, 100.0);
+        assert_eq!(tracker.update(&at(Some(25.0), Some(1000)), start), Some(0.25));
+        // 超过总时长也只到 100%
+        assert_eq!(tracker.update(&at(Some(150.0), None), start), Some(1.0));
+        // 时长未知、这一块没有时间时交给调用方
+        assert_eq!(Tracker::new(Strategy::Time, 0.0).update(&at(Some(5.0), None), start), None);
+        assert_eq!(Tracker::new(Strategy::Time, 100.0).update(&at(None, Some(1000)), start), None);
+    }
+
+    #[test]
+    fn tracker_blends_bytes_and_time() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(Strategy::Bytes(1000.0), 100.0);
+        let frac = tracker.update(&at(Some(20.0), Some(400)), start).unwrap();
+        assert!((frac - (BYTE_WEIGHT * 0.4 + (1.0 - BYTE_WEIGHT) * 0.2)).abs() < 1e-9);
+        // 只有一种比例时就用那一种
+        let mut tracker = Tracker::new(Strategy::Bytes(1000.0), 0.0);
+        assert_eq!(tracker.update(&at(Some(20.0), Some(500)), start), Some(0.5));
+        // 写入的比预计还多时按 100% 算
+        assert_eq!(tracker.update(&at(None, Some(5000)), start), Some(1.0));
+    }
+
+    #[test]
+    fn tracker_never_goes_back_and_ends_at_full() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(Strategy::Time, 100.0);
+        assert_eq!(tracker.update(&at(Some(50.0), None), start), Some(0.5));
+        assert_eq!(tracker.update(&at(Some(40.0), None), start + Duration::from_secs(1)), Some(0.5));
+        let end = ProgressBlock { ended: true, ..Default::default() };
+        assert_eq!(tracker.update(&end, start + Duration::from_secs(2)), Some(1.0));
+    }
+
+    #[test]
+    fn tracker_eta_from_recent_speed() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(Strategy::Time, 100.0);
+        tracker.update(&at(Some(10.0), None), start);
+        // 不到一秒不估计
+        tracker.update(&at(Some(11.0), None), start + Duration::from_millis(500));
+        assert_eq!(tracker.eta_secs(), None);
+        // 两秒走了 10%，剩下 80% 要 16 秒
+        tracker.update(&at(Some(20.0), None), start + Duration::from_secs(2));
+        assert!((tracker.eta_secs().unwrap() - 16.0).abs() < 1e-9);
+        // 一直没有进展时不估计
+        let mut stalled = Tracker::new(Strategy::Time, 100.0);
+        stalled.update(&at(Some(10.0), None), start);
+        stalled.update(&at(Some(10.0), None), start + Duration::from_secs(5));
+        assert_eq!(stalled.eta_secs(), None);
+    }
+
+    // 只按最近 ETA_WINDOW 里的速度算，开头慢的一段不拖累后面的估计
+    #[test]
+    fn tracker_eta_forgets_old_samples() {
+        let start = Instant::now();
+        let mut tracker = Tracker::new(Strategy::Time, 100.0);
+        tracker.update(&at(Some(0.0), None), start);
+        tracker.update(&at(Some(1.0), None), start + Duration::from_secs(20));
+        tracker.update(&at(Some(11.0), None), start + Duration::from_secs(25));
+        tracker.update(&at(Some(21.0), None), start + Duration::from_secs(30));
+        // 窗口里是 20 秒到 30 秒：10 秒走了 20%
+        assert!((tracker.eta_secs().unwrap() - 39.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn remux_detection() {
+        assert!(is_remux(&strings(&["-i", "a.mkv", "-c", "copy", "a.mp4"])));
+        assert!(is_remux(&strings(&["-i", "a.mkv", "-c:v", "copy", "-an", "a.mp4"])));
+        assert!(!is_remux(&strings(&["-i", "a.mkv", "-c:v", "copy", "-c:a", "aac", "a.mp4"])));
+        // 后面的设置覆盖前面的
+        assert!(!is_remux(&strings(&["-c", "copy", "-c", "libx264", "-c:v", "libx264", "-c:a", "copy"])));
+    }
+
+    #[test]
+    fn kept_streams_follow_maps() {
+        let stream = |t: &str| crate::probe::StreamInfo { codec_type: t.to_string(), ..Default::default() };
+        let info = MediaInfo { streams: vec![stream("video"), stream("audio"), stream("audio"), stream("subtitle")], ..Default::default() };
+        let kept = |args: &[&str]| kept_streams(&strings(args), &info);
+        assert_eq!(kept(&["-map", "0"]), Some(vec![true; 4]));
+        assert_eq!(kept(&["-map", "0:v", "-map", "0:a:1"]), Some(vec![true, false, true, false]));
+        assert_eq!(kept(&["-map", "0", "-an"]), Some(vec![true, false, false, true]));
+        assert_eq!(kept(&["-map", "0:s?"]), Some(vec![false, false, false, true]));
+        assert_eq!(kept(&["-map", "1:v"]), None);
+        assert_eq!(kept(&["-map", "0:a:x"]), None);
+    }
+
+    #[test]
+    fn strategy_subtracts_dropped_streams() {
+        let path = std::env::temp_dir().join(format!("ffui_runner_test_strategy_{}.mkv", std::process::id()));
+        fs::write(&path, vec![0u8; 10_000]).unwrap();
+        let input = path.to_string_lossy().into_owned();
+        let mut audio = crate::probe::StreamInfo { codec_type: "audio".to_string(), ..Default::default() };
+        audio.props.insert("bit_rate".to_string(), "8000".to_string());
+        let video = crate::probe::StreamInfo { codec_type: "video".to_string(), ..Default::default() };
+        let info = MediaInfo { duration: 2.0, streams: vec![video, audio.clone()], ..Default::default() };
+        let copy = strings(&["-i", &input, "-map", "0", "-c", "copy", "out.mp4"]);
+        assert!(strategy(&copy, &info, &input) == Strategy::Bytes(10_000.0));
+        // 去掉的音轨 8000 bit/s × 2 秒 = 2000 字节
+        let drop_audio = strings(&["-i", &input, "-map", "0", "-c", "copy", "-an", "out.mp4"]);
+        assert!(strategy(&drop_audio, &info, &input) == Strategy::Bytes(8_000.0));
+        // 估不出去掉的流有多大、重新编码时按时长
+        audio.props.clear();
+        let unknown = MediaInfo { duration: 2.0, streams: vec![info.streams[0].clone(), audio], ..Default::default() };
+        assert!(strategy(&drop_audio, &unknown, &input) == Strategy::Time);
+        assert!(strategy(&strings(&["-i", &input, "-c:v", "libx264", "out.mp4"]), &info, &input) == Strategy::Time);
+        let _ = fs::remove_file(&path);
+        assert!(strategy(&copy, &info, &input) == Strategy::Time);
+    }
+
+}
@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use crate::args::{Args, Source};
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::MediaInfo;
+use crate::tempfiles;
+
+// 取样预览：在整部片子里均匀取几段分别编码，再无损拼成一个短文件，
+// 比只看开头更能代表暗场、动作戏的画质和码率
+
+// 把时长平均分成 count 份，每份中间取 secs 秒。
+// 总长不够放下所有窗口（会重叠）时退化为整个文件一段
+pub fn windows(duration: f64, count: u32, secs: u32) -> Vec<(f64, f64)> {
+    let (count, secs) = (count.max(1), secs.max(1) as f64);
+    if duration <= 0.0 || count as f64 * secs >= duration {
+        return vec![(0.0, duration.max(0.0))];
+    }
+    let slot = duration / count as f64;
+    (0..count).map(|i| (i as f64 * slot + (slot - secs) / 2.0, secs)).collect()
+}
+
+// 每段单独编码到临时文件，最后用 concat 分离器直接复制拼接
+pub fn plan(settings: &JobSettings, info: &MediaInfo, input: &str, output: &str, temp: &Path, count: u32, secs: u32) -> JobPlan {
+    let mut settings = settings.clone();
+    settings.overwrite = true;
+    settings.ladder_enabled = false;
+    // 裁剪后只在裁出的一段里取样，每段自己定位，不再加裁剪的 -ss/-to
+    let duration = plan::trimmed_secs(&settings, input, info.duration);
+    let offset = if plan::trims(&settings, input) { settings.trim_start.map_or(0.0, |d| d.as_secs_f64()) } else { 0.0 };
+    (settings.trim_start, settings.trim_end) = (None, None);
+    let windows: Vec<(f64, f64)> = windows(duration, count, secs).into_iter().map(|(start, len)| (offset + start, len)).collect();
+    let mut job = JobPlan::default();
+    if windows.len() < count as usize {
+        job.notes.push(format!("片长 {:.0} 秒放不下 {} 段 {} 秒的样本，改为编码整个文件", duration, count, secs));
+    }
+
+    let mut list = String::new();
+    for (i, (start, len)) in windows.iter().enumerate() {
+        let part = tempfiles::join(temp, &format!("sample_{}.{}", i, settings.format));
+        // 按源文件时间烧录时每段从自己的位置开始数
+        if let Some(burn) = &mut settings.burn_in {
+            burn.start_secs = *start;
+        }
+        let mut args = plan::build_args(&settings, info, input, &part);
+        // -ss 放在 -i 前面快速定位，-t 是输出选项
+        let at = args.position("-i").unwrap_or(0);
+        args.insert(at, Source::Preview, format!("{:.3}", start));
+        args.insert(at, Source::Preview, "-ss".to_string());
+        args.insert(args.len() - 1, Source::Preview, "-t".to_string());
+        args.insert(args.len() - 1, Source::Preview, format!("{:.3}", len));
+        job.runs.push(args);
+        // concat 列表里的单引号写成 '\''
+        list.push_str(&format!("file '{}'\n", part.replace('\'', "'\\''")));
+    }
+
+    let list_path = tempfiles::join(temp, "samples.txt");
+    let mut concat = Args::new();
+    concat.push(Source::Base, &["-progress", "pipe:1", "-nostats", "-y"]);
+    concat.push(Source::Preview, &["-f", "concat", "-safe", "0", "-i", &list_path, "-c", "copy"]);
+    concat.push(Source::Output, &[output]);
+    job.copy_runs.push(job.runs.len());
+    job.runs.push(concat);
+    job.write_before.push((list_path.clone(), list));
+    job.outputs.push(output.to_string());
+    job.run_secs = windows.first().map(|(_, len)| *len);
+    job
+}
+
+// 按样本的平均码率推算完整文件大小
+pub fn estimate_bytes(sample_bytes: u64, info_duration: f64, count: u32, secs: u32) -> Option<f64> {
+    let sampled: f64 = windows(info_duration, count, secs).iter().map(|(_, len)| len).sum();
+    (sampled > 0.0 && info_duration > 0.0).then(|| sample_bytes as f64 / sampled * info_duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn windows_sit_in_the_middle_of_each_slot() {
+        assert_eq!(windows(100.0, 4, 5), [(10.0, 5.0), (35.0, 5.0), (60.0, 5.0), (85.0, 5.0)]);
+        assert_eq!(windows(60.0, 1, 10), [(25.0, 10.0)]);
+        // 0 段、0 秒按 1 算
+        assert_eq!(windows(10.0, 0, 0), [(4.5, 1.0)]);
+    }
+
+    #[test]
+    fn short_clip_falls_back_to_whole_file() {
+        assert_eq!(windows(12.0, 3, 5), [(0.0, 12.0)]);
+        // 正好放满也会首尾相接，同样整段编码
+        assert_eq!(windows(15.0, 3, 5), [(0.0, 15.0)]);
+        assert_eq!(windows(0.0, 3, 5), [(0.0, 0.0)]);
+        assert_eq!(windows(-1.0, 3, 5), [(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn estimate_scales_sample_bitrate() {
+        assert_eq!(estimate_bytes(1_000_000, 100.0, 4, 5), Some(5_000_000.0));
+        assert_eq!(estimate_bytes(1_000_000, 12.0, 3, 5), Some(1_000_000.0));
+        assert_eq!(estimate_bytes(1_000_000, 0.0, 3, 5), None);
+    }
+
+    #[test]
+    fn plan_encodes_each_window_then_concats() {
+        let info = MediaInfo { duration: 100.0, ..Default::default() };
+        let settings = JobSettings { trim_start: Some(Duration::from_secs(20)), trim_end: Some(Duration::from_secs(60)), ..Default::default() };
+        let temp = Path::new("/tmp/it's");
+        let job = plan(&settings, &info, "in.mkv", "out.mp4", temp, 2, 4);
+        assert_eq!(job.runs.len(), 3);
+        // 裁剪后的 40 秒里取两段，各自定位
+        let first = job.runs[0].argv();
+        let i = first.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(first[i - 2..i], ["-ss", "28.000"]);
+        assert_eq!(first[first.len() - 3..], ["-t", "4.000", &tempfiles::join(temp, "sample_0.mp4")]);
+        assert!(job.runs[1].argv().contains(&"48.000".to_string()));
+        let concat = job.runs[2].argv().join(" ");
+        assert!(concat.contains("-f concat -safe 0 -i") && concat.ends_with("-c copy out.mp4"), "{}", concat);
+        assert_eq!(job.copy_runs, [2]);
+        let (_, list) = &job.write_before[0];
+        assert_eq!(list.lines().count(), 2);
+        assert!(list.starts_with("file '/tmp/it'\\''s/sample_0.mp4'"), "{}", list);
+        assert_eq!(job.run_secs, Some(4.0));
+        assert!(job.notes.is_empty());
+    }
+
+    #[test]
+    fn plan_notes_when_clip_is_too_short() {
+        let info = MediaInfo { duration: 8.0, ..Default::default() };
+        let job = plan(&JobSettings::default(), &info, "in.mkv", "out.mp4", Path::new("/tmp"), 3, 5);
+        assert_eq!(job.runs.len(), 2);
+        assert_eq!(job.run_secs, Some(8.0));
+        assert!(job.notes[0].contains("改为编码整个文件"), "{}", job.notes[0]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::av1::{SoftEncoder, VideoCodec};
+use crate::encoders;
+use crate::plan::{self, JobSettings};
+use crate::probe;
+use crate::process;
+
+// ffui --selftest：不需要输入文件，给打包者检查 ffui 和随附的 ffmpeg 能否配合。
+// 先用 lavfi 生成两秒的测试片源，再按界面里的每种编码器各编码一次并用 ffprobe 核对
+const SOURCE: &str = "testsrc2=size=640x360:rate=30:duration=2";
+const TONE: &str = "sine=frequency=440:sample_rate=48000:duration=2";
+// 单次 ffmpeg 的上限，硬件编码器初始化卡住时不拖住整个自检
+const RUN_LIMIT: Duration = Duration::from_secs(20);
+// 整个自检的时间预算，超出后剩余项目记为跳过
+const BUDGET: Duration = Duration::from_secs(50);
+
+struct Case {
+    name: String,
+    // CPU 路径必须通过，硬件编码器没有对应显卡时失败是正常的
+    mandatory: bool,
+    settings: JobSettings,
+}
+
+struct Outcome {
+    name: String,
+    mandatory: bool,
+    passed: Option<bool>, // None 表示跳过
+    secs: f64,
+    detail: String,
+}
+
+// 运行 ffmpeg 直到结束或超时，失败时返回 stderr 最后一行
+fn run_ffmpeg(args: &[String]) -> Result<(), String> {
+    let (status, _, stderr) = process::output_with_timeout(process::command("ffmpeg").args(args), RUN_LIMIT)
+        .map_err(|e| format!("无法启动 ffmpeg: {}", e))?;
+    let last = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string();
+    match status {
+        Some(s) if s.success() => Ok(()),
+        Some(_) => Err(if last.is_empty() { "ffmpeg 异常退出".to_string() } else { last }),
+        None => Err(format!("超过 {} 秒没有完成", RUN_LIMIT.as_secs())),
+    }
+}
+
+fn cases() -> Vec<Case> {
+    let make = |name: &str, mandatory: bool, gpu: &str, codec: VideoCodec| {
+        let mut settings = JobSettings { gpu: gpu.to_string(), codec, overwrite: true, ..Default::default() };
+        if let Some(encoder) = SoftEncoder::detect() {
+            settings.av1.encoder = encoder;
+        }
+        Case { name: name.to_string(), mandatory, settings }
+    };
+    let mut cases = vec![make("CPU H.264", true, "CPU", VideoCodec::H264)];
+    for codec in [VideoCodec::Hevc, VideoCodec::Av1, VideoCodec::Vp9] {
+        let case = make(&format!("CPU {}", codec.label()), false, "CPU", codec);
+        if encoders::available(plan::video_codec(&case.settings)) {
+            cases.push(case);
+        }
+    }
+    for gpu in ["NVIDIA", "Intel", "AMD"] {
+        // VP9 没有硬件编码
+        for codec in [VideoCodec::H264, VideoCodec::Hevc, VideoCodec::Av1] {
+            let case = make(&format!("{} {}", gpu, codec.label()), false, gpu, codec);
+            // 只测 ffmpeg 列出的编码器
+            if encoders::available(plan::video_codec(&case.settings)) {
+                cases.push(case);
+            }
+        }
+    }
+    cases
+}
+
+// 输出里要有视频和音频，时长接近片源
+fn verify(path: &Path) -> Result<(), String> {
+    let info = probe::probe(&path.to_string_lossy(), Default::default())?;
+    let has = |kind: &str| info.streams.iter().any(|s| s.codec_type == kind);
+    if !has("video") {
+        return Err("输出没有视频流".to_string());
+    }
+    if !has("audio") {
+        return Err("输出没有音频流".to_string());
+    }
+    if info.duration < 1.5 {
+        return Err(format!("输出时长只有 {:.2} 秒", info.duration));
+    }
+    Ok(())
+}
+
+fn run_case(case: &Case, source: &str, dir: &Path, index: usize) -> Result<(), String> {
+    let info = probe::probe(source, Default::default())?;
+    let output = dir.join(format!("out{}.mp4", index)).to_string_lossy().into_owned();
+    let args = plan::build_args(&case.settings, &info, source, &output);
+    run_ffmpeg(&args.argv())?;
+    verify(Path::new(&output))
+}
+
+fn run_in(dir: &Path) -> Vec<Outcome> {
+    let mut outcomes = Vec::new();
+    let started = Instant::now();
+
+    // 片源用 ffmpeg 内置、所有版本都有的 ffv1 + pcm 保存
+    let source = dir.join("source.mkv").to_string_lossy().into_owned();
+    let args: Vec<String> = [
+        "-hide_banner", "-y",
+        "-f", "lavfi", "-i", SOURCE,
+        "-f", "lavfi", "-i", TONE,
+        "-c:v", "ffv1", "-c:a", "pcm_s16le", &source,
+    ].iter().map(|a| a.to_string()).collect();
+    let generated = run_ffmpeg(&args).and_then(|_| verify(Path::new(&source)));
+    outcomes.push(Outcome {
+        name: "生成测试片源 (lavfi)".to_string(),
+        mandatory: true,
+        passed: Some(generated.is_ok()),
+        secs: started.elapsed().as_secs_f64(),
+        detail: generated.clone().err().unwrap_or_default(),
+    });
+    if generated.is_err() {
+        return outcomes;
+    }
+
+    for (i, case) in cases().iter().enumerate() {
+        if started.elapsed() > BUDGET {
+            outcomes.push(Outcome {
+                name: case.name.clone(),
+                mandatory: case.mandatory,
+                passed: None,
+                secs: 0.0,
+                detail: "超出时间预算".to_string(),
+            });
+            continue;
+        }
+        let t = Instant::now();
+        let result = run_case(case, &source, dir, i);
+        outcomes.push(Outcome {
+            name: format!("{} ({})", case.name, plan::video_codec(&case.settings)),
+            mandatory: case.mandatory,
+            passed: Some(result.is_ok()),
+            secs: t.elapsed().as_secs_f64(),
+            detail: result.err().unwrap_or_default(),
+        });
+    }
+    outcomes
+}
+
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("ffui_selftest_{}", std::process::id()))
+}
+
+// 打印结果表，必测项目有失败或被跳过时返回 1
+pub fn run() -> i32 {
+    let dir = temp_dir();
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("无法创建临时目录 {}: {}", dir.display(), e);
+        return 1;
+    }
+    let outcomes = run_in(&dir);
+    let _ = fs::remove_dir_all(&dir);
+
+    println!("{:<36} {:<6} {:>7}  说明", "项目", "结果", "耗时");
+    let mut failed = false;
+    for o in &outcomes {
+        let result = match o.passed {
+            Some(true) => "通过",
+            Some(false) if o.mandatory => "失败",
+            Some(false) => "不可用",
+            None => "跳过",
+        };
+        failed |= o.mandatory && o.passed != Some(true);
+        println!("{:<36} {:<6} {:>6.1}s  {}", o.name, result, o.secs, o.detail);
+    }
+    println!();
+    println!("{}", if failed { "自检失败" } else { "自检通过" });
+    if failed { 1 } else { 0 }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::joblist::{self, Job};
+use crate::live;
+use crate::paths;
+use crate::pipeline;
+use crate::plan::JobSettings;
+
+// 还没加入队列、也没转换完的编辑状态（输入文件、输出文件和全部设置），误关窗口后下次还能接着改。
+// 用任务列表的格式保存，版本号和设置项跟着任务列表一起升级
+
+// 停止修改这么久之后才写盘，拖动滑块时不会每帧都写
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+pub fn session_path() -> PathBuf {
+    paths::store_dir().join("session.json")
+}
+
+pub struct Saved {
+    pub input: String,
+    pub output: String,
+    pub settings: JobSettings,
+}
+
+// 没有保存的编辑，或者读不出、输入文件已经不在了时返回 None
+pub fn load() -> Option<Saved> {
+    let path = session_path();
+    if !path.exists() {
+        return None;
+    }
+    let job = joblist::load(&path).ok()?.into_iter().next()?;
+    if job.problem.is_some() {
+        return None;
+    }
+    Some(Saved { input: job.input, output: job.output, settings: job.settings })
+}
+
+pub fn clear() {
+    let _ = fs::remove_file(session_path());
+}
+
+fn key(input: &str, output: &str, settings: &JobSettings) -> String {
+    format!("{}\n{}\n{}", input, output, joblist::settings_to_value(settings).to_pretty())
+}
+
+// 命令行传进来的可能是相对路径，下次启动的工作目录不一定相同
+fn absolute(path: &str) -> String {
+    if path.is_empty() || live::is_live(path) {
+        return path.to_string();
+    }
+    std::path::absolute(path).map_or(path.to_string(), |p| p.to_string_lossy().into_owned())
+}
+
+// 每帧对比当前状态和上次写盘的状态，有变化且停了一会儿后写盘
+pub struct Autosave {
+    saved: String,
+    changed: Option<(String, Instant)>,
+    completed: bool,
+}
+
+impl Autosave {
+    // 启动时的状态算作已保存，只打开文件不改设置不会覆盖上次的编辑
+    pub fn new(input: &str, output: &str, settings: &JobSettings) -> Self {
+        Autosave { saved: key(input, output, settings), changed: None, completed: false }
+    }
+
+    // 返回 true 时还有没写盘的修改，调用方过一会儿再刷新界面
+    pub fn tick(&mut self, input: &str, output: &str, settings: &JobSettings, completed: bool) -> bool {
+        let current = key(input, output, settings);
+        // 刚转换完，这份设置已经用掉了
+        if completed && !self.completed {
+            self.completed = true;
+            self.forget(current);
+            return false;
+        }
+        self.completed = completed;
+        if current == self.saved {
+            self.changed = None;
+            return false;
+        }
+        // 标准输入的数据只能读一次，下次打开也恢复不了
+        if live::is_stdin(input) {
+            return false;
+        }
+        match &self.changed {
+            Some((pending, since)) if *pending == current && since.elapsed() >= DEBOUNCE => {
+                let mut settings = settings.clone();
+                settings.subtitle_file = absolute(&settings.subtitle_file);
+                let job = Job { input: absolute(input), output: absolute(output), settings, stages: pipeline::default_stages(), problem: None };
+                if fs::create_dir_all(paths::store_dir()).is_ok() && joblist::save(&session_path(), &[job], false).is_ok() {
+                    self.saved = current;
+                    self.changed = None;
+                }
+                false
+            }
+            Some((pending, _)) if *pending == current => true,
+            _ => {
+                self.changed = Some((current, Instant::now()));
+                true
+            }
+        }
+    }
+
+    // 已经加入任务列表：删掉保存的编辑，当前状态不再写盘，直到再次修改
+    pub fn enqueued(&mut self, input: &str, output: &str, settings: &JobSettings) {
+        self.forget(key(input, output, settings));
+    }
+
+    fn forget(&mut self, current: String) {
+        clear();
+        self.saved = current;
+        self.changed = None;
+    }
+}
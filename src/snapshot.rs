@@ -0,0 +1,89 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::args::Source;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::MediaInfo;
+
+pub const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum SnapMode {
+    #[default]
+    Interval,
+    // 在整个时长里平均取 count 张
+    Count,
+}
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub mode: SnapMode,
+    pub interval: Duration,
+    pub count: u32,
+    pub quality: u8,   // 1～100，越大越清晰
+    pub max_width: u32, // 0 表示不缩小
+    pub dir: String,    // 空时用“输入文件名_frames”
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Snapshot { mode: SnapMode::Interval, interval: Duration::from_secs(10), count: 20, quality: 90, max_width: 0, dir: String::new() }
+    }
+}
+
+impl Snapshot {
+    pub fn output_dir(&self, input: &str) -> String {
+        if self.dir.is_empty() { format!("{}_frames", input) } else { self.dir.clone() }
+    }
+
+    // mjpeg 的 -q:v 是 2（最好）～31
+    fn qscale(&self) -> u32 {
+        2 + (100 - self.quality.clamp(1, 100) as u32) * 29 / 99
+    }
+}
+
+// 导出的图片数量，完成后用来核对
+pub fn count_images(dir: &str) -> usize {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.flatten().filter(|e| e.path().extension().is_some_and(|x| x == "jpg")).count())
+        .unwrap_or(0)
+}
+
+pub fn plan(settings: &JobSettings, snap: &Snapshot, info: &MediaInfo, input: &str, dir: &str) -> JobPlan {
+    let mut job = JobPlan::default();
+    let duration = info.duration;
+    let mut mode = snap.mode;
+    if mode == SnapMode::Count && (duration <= 0.0 || snap.count == 0) {
+        mode = SnapMode::Interval;
+        job.notes.push("无法读取时长，按固定间隔截图".to_string());
+    }
+
+    let (fps, expected) = match mode {
+        SnapMode::Count => (format!("{}/{:.3}", snap.count, duration), Some(snap.count as usize)),
+        SnapMode::Interval => {
+            let interval = snap.interval.as_secs_f64().max(MIN_INTERVAL.as_secs_f64());
+            (format!("1/{}", interval), (duration > 0.0).then(|| (duration / interval).ceil() as usize))
+        }
+    };
+    let mut filters = plan::video_filters(settings);
+    filters.push(format!("fps={}", fps));
+    if snap.max_width > 0 {
+        filters.push(format!("scale='min({},iw)':-2", snap.max_width));
+    }
+
+    let mut args = plan::input_args(settings, input);
+    args.push(Source::Snapshot, &["-map", "0:v:0", "-vf", &filters.join(","), "-q:v", &snap.qscale().to_string(), "-an", "-sn"]);
+    if mode == SnapMode::Count {
+        args.push(Source::Snapshot, &["-frames:v", &snap.count.to_string()]);
+    }
+    let pattern = Path::new(dir).join("%05d.jpg").to_string_lossy().into_owned();
+    args.push(Source::Output, &[&pattern]);
+
+    if let Some(n) = expected {
+        job.notes.push(format!("截图: 预计 {} 张，保存到 {}", n, dir));
+    }
+    job.runs.push(args);
+    job.dirs.push(dir.to_string());
+    job.expect_images = Some((dir.to_string(), expected.unwrap_or(0)));
+    job
+}
@@ -0,0 +1,375 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::args::Source;
+use crate::coverart;
+use crate::encoders;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::{Chapter, MediaInfo};
+use crate::process;
+use crate::tempfiles;
+
+// 语音优化：讲座录音、播客、有声书。只保留第一条音轨，单声道、低采样率、低码率，
+// 可选语音响度均衡和去掉首尾静音；输出带章节，播放器里能按章跳转
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpeechCodec {
+    Opus,
+    // 输出 m4b 有声书
+    HeAac,
+}
+
+impl SpeechCodec {
+    pub const ALL: [SpeechCodec; 2] = [SpeechCodec::Opus, SpeechCodec::HeAac];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SpeechCodec::Opus => "Opus (.opus)",
+            SpeechCodec::HeAac => "HE-AAC 有声书 (.m4b)",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            SpeechCodec::Opus => "opus",
+            SpeechCodec::HeAac => "he-aac",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<SpeechCodec> {
+        SpeechCodec::ALL.into_iter().find(|c| c.tag().eq_ignore_ascii_case(tag))
+    }
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            SpeechCodec::Opus => "opus",
+            SpeechCodec::HeAac => "m4b",
+        }
+    }
+
+    // Opus 只支持 48/24/16/12/8 kHz
+    fn sample_rate(self) -> u32 {
+        match self {
+            SpeechCodec::Opus => 24000,
+            SpeechCodec::HeAac => 22050,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Normalize {
+    Off,
+    // 按语音的周期调整增益，适合音量忽大忽小的讲座
+    Speechnorm,
+    Dynaudnorm,
+}
+
+impl Normalize {
+    pub const ALL: [Normalize; 3] = [Normalize::Off, Normalize::Speechnorm, Normalize::Dynaudnorm];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Normalize::Off => "不处理",
+            Normalize::Speechnorm => "语音均衡 (speechnorm)",
+            Normalize::Dynaudnorm => "动态均衡 (dynaudnorm)",
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Normalize::Off => "off",
+            Normalize::Speechnorm => "speechnorm",
+            Normalize::Dynaudnorm => "dynaudnorm",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Normalize> {
+        Normalize::ALL.into_iter().find(|n| n.tag() == tag)
+    }
+
+    fn filter(self) -> Option<&'static str> {
+        match self {
+            Normalize::Off => None,
+            Normalize::Speechnorm => Some("speechnorm=e=12.5:r=0.0001:l=1"),
+            Normalize::Dynaudnorm => Some("dynaudnorm=f=250:g=15"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Speech {
+    pub codec: SpeechCodec,
+    pub bitrate_k: u32,
+    pub normalize: Normalize,
+    pub trim_silence: bool,
+    // 源文件没有章节时每隔这么多分钟生成一章，0 表示不生成
+    pub chapter_minutes: u32,
+    // 去掉首尾静音后保留的 (开始, 结束) 秒，由 resolve 填写
+    pub keep: Option<(f64, f64)>,
+}
+
+impl Default for Speech {
+    fn default() -> Self {
+        Speech {
+            codec: SpeechCodec::Opus,
+            bitrate_k: 32,
+            normalize: Normalize::Speechnorm,
+            trim_silence: true,
+            chapter_minutes: 10,
+            keep: None,
+        }
+    }
+}
+
+// 低于这个音量、持续这么久才算静音；讲座里的停顿一般更短
+const NOISE: &str = "-45dB";
+const MIN_SILENCE: f64 = 1.0;
+// 首尾静音的判断允许的误差
+const EDGE: f64 = 0.05;
+
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    line.split(key).nth(1)?.split_whitespace().next()?.parse().ok()
+}
+
+// silencedetect 打印的静音段 (开始, 结束)，文件以静音结束时最后一段没有结束时间
+pub fn parse_silence(stderr: &str) -> Vec<(f64, Option<f64>)> {
+    let mut spans: Vec<(f64, Option<f64>)> = Vec::new();
+    for line in stderr.lines() {
+        if let Some(start) = value_after(line, "silence_start:") {
+            spans.push((start, None));
+        } else if let Some(end) = value_after(line, "silence_end:")
+            && let Some(last) = spans.last_mut()
+        {
+            last.1 = Some(end);
+        }
+    }
+    spans
+}
+
+// 去掉从 0 开始的静音和一直持续到结尾的静音后剩下的 (开始, 结束)
+pub fn audible_span(spans: &[(f64, Option<f64>)], duration: f64) -> (f64, f64) {
+    let mut start = 0.0;
+    let mut end = duration;
+    if let Some((s, Some(e))) = spans.first()
+        && *s <= EDGE
+    {
+        start = *e;
+    }
+    if let Some((s, e)) = spans.last()
+        && e.is_none_or(|e| e >= duration - EDGE)
+        && *s > start
+    {
+        end = *s;
+    }
+    (start, end.max(start))
+}
+
+// 只解码音频跑一遍 silencedetect
+fn detect(input: &str, duration: f64) -> Result<(f64, f64), String> {
+    let output = process::command("ffmpeg")
+        .args([
+            "-nostdin", "-hide_banner",
+            "-i", input,
+            "-map", "0:a:0",
+            "-af", &format!("silencedetect=noise={}:d={}", NOISE, MIN_SILENCE),
+            "-f", "null", "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("无法启动 ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err("ffmpeg 无法分析音频".to_string());
+    }
+    Ok(audible_span(&parse_silence(&String::from_utf8_lossy(&output.stderr)), duration))
+}
+
+// 由 plan::resolve 调用：检测首尾静音，检查编码器
+pub fn resolve(speech: &mut Speech, input: &str, info: &MediaInfo) -> Vec<String> {
+    let mut notes = Vec::new();
+    if speech.codec == SpeechCodec::Opus && !encoders::available("libopus") {
+        notes.push("ffmpeg 没有列出 libopus，Opus 输出可能失败，可以改用 HE-AAC".to_string());
+    }
+    speech.keep = None;
+    if speech.trim_silence && info.duration > 0.0 {
+        match detect(input, info.duration) {
+            Ok((start, end)) if start > EDGE || end < info.duration - EDGE => {
+                notes.push(format!("静音: 去掉开头 {:.1} 秒、结尾 {:.1} 秒", start, info.duration - end));
+                speech.keep = Some((start, end));
+            }
+            Ok(_) => notes.push("静音: 首尾没有需要去掉的静音".to_string()),
+            Err(e) => notes.push(format!("静音检测失败（{}），不裁剪", e)),
+        }
+    }
+    notes
+}
+
+// 源文件没有章节时，从保留部分的开头起每隔 every_minutes 分钟一章。
+// 时间仍按源文件计，和源文件自带的章节一样由 ffmpeg 按输出的 -ss 平移、截掉
+pub fn generate_chapters(keep: (f64, f64), every_minutes: u32) -> Vec<Chapter> {
+    if every_minutes == 0 {
+        return Vec::new();
+    }
+    let ((start, end), step) = (keep, every_minutes as f64 * 60.0);
+    (0..)
+        .map(|i| start + i as f64 * step)
+        .take_while(|s| *s < end)
+        .enumerate()
+        .map(|(i, s)| Chapter { start: s, end: (s + step).min(end), title: format!("第 {} 章", i + 1) })
+        .collect()
+}
+
+// ffmetadata 里 = ; # \ 和换行前面要加反斜杠
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// 章节写成 ffmetadata 文件，时间以毫秒为单位
+pub fn ffmetadata(chapters: &[Chapter]) -> String {
+    let ms = |secs: f64| (secs * 1000.0).round() as u64;
+    let mut text = ";FFMETADATA1\n".to_string();
+    for c in chapters {
+        text.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            ms(c.start), ms(c.end), escape(&c.title)
+        ));
+    }
+    text
+}
+
+pub fn plan(settings: &JobSettings, speech: &Speech, info: &MediaInfo, input: &str, output: &str, temp: &Path) -> JobPlan {
+    let mut job = JobPlan::default();
+    let keep = speech.keep.unwrap_or((0.0, info.duration.max(0.0)));
+    let from_source = !info.chapters.is_empty();
+    let generated = if from_source { Vec::new() } else { generate_chapters(keep, speech.chapter_minutes) };
+
+    // 音频对齐的修正并进同一条 -af，不然后面的 -af 会覆盖前面的
+    let mut settings = settings.clone();
+    let mut filters = Vec::new();
+    if let Some(at) = settings.fixes.iter().position(|f| f == "async") {
+        settings.fixes.remove(at);
+        filters.push("aresample=async=1".to_string());
+    }
+    filters.extend(speech.normalize.filter().map(|f| f.to_string()));
+
+    let mut args = plan::input_args(&settings, input);
+    let meta = tempfiles::join(temp, "chapters.txt");
+    if !generated.is_empty() {
+        args.push(Source::Speech, &["-i", &meta]);
+    }
+    // 标签沿用源文件，章节来自源文件或生成的元数据文件
+    let chapter_source = match (from_source, generated.is_empty()) {
+        (true, _) => "0",
+        (false, false) => "1",
+        (false, true) => "-1",
+    };
+    // m4b 能带封面，opus 不能；旁边的图片作为最后一个输入
+    let art = coverart::pick(info, input);
+    args.append(coverart::input_args(art.as_ref(), speech.codec.ext()));
+    let art = coverart::embed_args(art.as_ref(), speech.codec.ext(), if generated.is_empty() { 1 } else { 2 });
+    if art.position("-vn").is_none() {
+        args.append(art);
+    } else {
+        args.push(Source::Speech, &["-map", "0:a:0", "-vn"]);
+    }
+    args.push(Source::Speech, &["-sn", "-dn", "-map_metadata", "0", "-map_chapters", chapter_source]);
+    if let Some((start, end)) = speech.keep {
+        args.push(Source::Speech, &["-ss", &format!("{:.3}", start), "-t", &format!("{:.3}", end - start)]);
+        job.run_secs = Some(end - start);
+    }
+    if !filters.is_empty() {
+        args.push(Source::Speech, &["-af", &filters.join(",")]);
+    }
+    args.push(Source::Speech, &["-ac", "1", "-ar", &speech.codec.sample_rate().to_string()]);
+
+    let bitrate = format!("{}k", speech.bitrate_k);
+    let codec_note = match speech.codec {
+        SpeechCodec::Opus => {
+            args.push(Source::Speech, &["-c:a", "libopus", "-b:a", &bitrate, "-application", "voip"]);
+            "Opus"
+        }
+        // ffmpeg 自带的 aac 编码器不支持 HE-AAC，需要 libfdk_aac
+        SpeechCodec::HeAac if encoders::available("libfdk_aac") => {
+            args.push(Source::Speech, &["-c:a", "libfdk_aac", "-profile:a", "aac_he", "-b:a", &bitrate]);
+            "HE-AAC"
+        }
+        SpeechCodec::HeAac => {
+            args.push(Source::Speech, &["-c:a", "aac", "-b:a", &bitrate]);
+            job.notes.push("ffmpeg 没有 libfdk_aac，改用 AAC-LC，同样码率下音质稍差".to_string());
+            "AAC-LC"
+        }
+    };
+    args.append(plan::output_tail_args(&settings));
+    if speech.codec == SpeechCodec::HeAac {
+        args.push(Source::Speech, &["-f", "ipod", "-movflags", "+faststart"]);
+    }
+    args.push(Source::Output, &[output]);
+
+    job.notes.push(format!(
+        "语音优化: 单声道 {} Hz {} {} kbps，{}",
+        speech.codec.sample_rate(),
+        codec_note,
+        speech.bitrate_k,
+        if from_source {
+            format!("保留源文件的 {} 个章节", info.chapters.len())
+        } else if generated.is_empty() {
+            "不写章节".to_string()
+        } else {
+            format!("每 {} 分钟一章，共 {} 章", speech.chapter_minutes, generated.len())
+        }
+    ));
+    if !generated.is_empty() {
+        job.write_before.push((meta, ffmetadata(&generated)));
+    }
+    job.runs.push(args);
+    job.outputs.push(output.to_string());
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_spans_pair_start_and_end() {
+        let stderr = "\
+[silencedetect @ 0x5566] silence_start: 0
+[silencedetect @ 0x5566] silence_end: 1.52 | silence_duration: 1.52
+size=N/A time=00:00:10.00 bitrate=N/A speed= 512x
+[silencedetect @ 0x5566] silence_start: 8.25
+";
+        assert_eq!(parse_silence(stderr), [(0.0, Some(1.52)), (8.25, None)]);
+        assert_eq!(audible_span(&parse_silence(stderr), 10.0), (1.52, 8.25));
+    }
+
+    #[test]
+    fn comma_decimals_are_skipped() {
+        let stderr = "[silencedetect @ 0x5566] silence_start: 3,5\n[silencedetect @ 0x5566] silence_end: 4,5 | silence_duration: 1\n";
+        assert!(parse_silence(stderr).is_empty());
+        assert_eq!(audible_span(&[], 10.0), (0.0, 10.0));
+    }
+
+    #[test]
+    fn cover_beside_follows_the_chapter_input() {
+        let dir = std::env::temp_dir().join(format!("ffui_speech_test_cover_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("lecture.wav").to_string_lossy().into_owned();
+        let cover = dir.join("cover.jpg").to_string_lossy().into_owned();
+        std::fs::write(&cover, b"jpg").unwrap();
+        let info = MediaInfo { duration: 1800.0, ..Default::default() };
+        let speech = Speech { codec: SpeechCodec::HeAac, ..Default::default() };
+        let settings = JobSettings { speech: Some(speech.clone()), ..Default::default() };
+        let args = plan(&settings, &speech, &info, &input, "lecture.m4b", &dir).runs[0].argv().join(" ");
+        // 生成的章节是 1 号输入，封面排在它后面
+        assert!(args.contains(&format!("-i {} -map 0:a:0 -map 2:0", cover)), "{}", args);
+        let speech = Speech { codec: SpeechCodec::HeAac, chapter_minutes: 0, ..Default::default() };
+        let args = plan(&settings, &speech, &info, &input, "lecture.m4b", &dir).runs[0].argv().join(" ");
+        assert!(args.contains(&format!("-i {} -i {} -map 0:a:0 -map 1:0", input, cover)), "{}", args);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::history::{self, Record};
+use crate::paths;
+
+#[derive(Clone, Default)]
+pub struct Stats {
+    pub succeeded: u64,
+    pub failed: u64,
+    // 成功里完成但有警告的
+    pub warned: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub encode_secs: f64,
+    // 编码器 -> (媒体时长, 耗时)，相除得到平均速度
+    pub speed: BTreeMap<String, (f64, f64)>,
+    // 天（unix 秒 / 86400）-> 转换数
+    pub per_day: BTreeMap<u64, u32>,
+    pub last: Option<(String, String)>,
+}
+
+impl Stats {
+    fn add(&mut self, r: &Record) {
+        if r.ok {
+            self.succeeded += 1;
+            if !r.warnings.is_empty() {
+                self.warned += 1;
+            }
+            self.input_bytes += r.input_bytes;
+            self.output_bytes += r.output_bytes;
+            let entry = self.speed.entry(r.encoder.clone()).or_default();
+            entry.0 += r.media_secs;
+            entry.1 += r.encode_secs;
+            self.last = Some((r.input.clone(), r.output.clone()));
+        } else {
+            self.failed += 1;
+        }
+        self.encode_secs += r.encode_secs;
+        *self.per_day.entry(r.time / 86400).or_default() += 1;
+    }
+}
+
+// “重置统计”只记一个时间点，历史记录本身不动
+fn reset_path() -> PathBuf {
+    paths::store_dir().join("stats_reset")
+}
+
+fn reset_at() -> u64 {
+    fs::read_to_string(reset_path()).ok().and_then(|s| s.trim().parse().ok()).unwrap_or(0)
+}
+
+pub fn reset() -> std::io::Result<()> {
+    fs::create_dir_all(paths::store_dir())?;
+    fs::write(reset_path(), history::now().to_string())
+}
+
+pub fn compute() -> Stats {
+    let since = reset_at();
+    let mut stats = Stats::default();
+    for r in history::load().iter().filter(|r| r.time >= since) {
+        stats.add(r);
+    }
+    stats
+}
+
+type CacheKey = (Option<SystemTime>, u64, u64);
+
+// 历史文件或重置时间变了才在后台线程重新统计，界面只读缓存
+#[derive(Default)]
+pub struct StatsCache {
+    key: Option<CacheKey>,
+    stats: Arc<Mutex<Option<Stats>>>,
+    computing: Arc<AtomicBool>,
+}
+
+impl StatsCache {
+    pub fn get(&mut self) -> Option<Stats> {
+        let meta = fs::metadata(history::history_path()).ok();
+        let key = (meta.as_ref().and_then(|m| m.modified().ok()), meta.map(|m| m.len()).unwrap_or(0), reset_at());
+        if self.key != Some(key) && !self.computing.swap(true, Ordering::SeqCst) {
+            self.key = Some(key);
+            let (stats, computing) = (self.stats.clone(), self.computing.clone());
+            thread::spawn(move || {
+                *stats.lock().unwrap() = Some(compute());
+                computing.store(false, Ordering::SeqCst);
+            });
+        }
+        self.stats.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::plan::JobSettings;
+
+// 下拉框里可手动指定的编码
+pub const ENCODINGS: &[&str] = &["UTF-8", "GBK", "Big5", "Shift_JIS", "windows-1252", "UTF-16LE", "UTF-16BE"];
+
+pub struct Detected {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    // 用检测出的编码解码时出现了无法识别的字节（可能是混合编码）
+    pub malformed: bool,
+}
+
+pub fn detect(path: &Path) -> io::Result<Detected> {
+    let bytes = fs::read(path)?;
+    if let Some((encoding, _)) = Encoding::for_bom(&bytes) {
+        let (_, malformed) = encoding.decode_with_bom_removal(&bytes);
+        return Ok(Detected { encoding, bom: true, malformed });
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(&bytes, true);
+    let encoding = detector.guess(None, true);
+    let (_, _, malformed) = encoding.decode(&bytes);
+    Ok(Detected { encoding, bom: false, malformed })
+}
+
+pub fn lookup(name: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(name.as_bytes())
+}
+
+static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+// 在任务的临时目录里写一份不带 BOM 的 UTF-8 副本，返回副本路径
+pub fn to_utf8_copy(path: &Path, encoding: &'static Encoding, temp: &Path) -> io::Result<PathBuf> {
+    let bytes = fs::read(path)?;
+    let (text, _, _) = encoding.decode(&bytes);
+    let ext = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or("srt".to_string());
+    let n = COPIES.fetch_add(1, Ordering::Relaxed);
+    let copy = temp.join(format!("sub_{}.{}", n, ext));
+    fs::write(&copy, text.trim_start_matches('\u{feff}').as_bytes())?;
+    Ok(copy)
+}
+
+// 转换前把非 UTF-8 字幕转成临时副本并改写设置，返回一行日志。副本随临时目录一起删除
+pub fn prepare(settings: &mut JobSettings, temp: &Path) -> io::Result<String> {
+    let path = PathBuf::from(&settings.subtitle_file);
+    let encoding = match settings.subtitle_encoding.as_deref().and_then(lookup) {
+        Some(enc) => enc,
+        None => detect(&path)?.encoding,
+    };
+    if encoding == UTF_8 {
+        settings.subtitle_encoding = None;
+        return Ok("字幕编码: UTF-8".to_string());
+    }
+    match to_utf8_copy(&path, encoding, temp) {
+        Ok(copy) => {
+            settings.subtitle_file = copy.to_string_lossy().into_owned();
+            settings.subtitle_encoding = None;
+            Ok(format!("字幕编码: {}，已转换为 UTF-8 临时副本", encoding.name()))
+        }
+        Err(e) => {
+            settings.subtitle_encoding = Some(encoding.name().to_string());
+            Ok(format!("字幕编码: {}，无法写入临时副本 ({})，改由 ffmpeg 按该编码读取", encoding.name(), e))
+        }
+    }
+}
+
+// 滤镜参数里的路径要经过两层转义：先是选项值（: \ '），再是滤镜图（\ ' , ; [ ]）
+pub fn escape_filter_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let escape = |s: &str, special: &[char]| {
+        let mut out = String::new();
+        for c in s.chars() {
+            if special.contains(&c) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    };
+    let value = escape(&path, &[':', '\\', '\'']);
+    escape(&value, &['\\', '\'', ',', ';', '[', ']'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, UTF_16BE, UTF_16LE};
+
+    const LINES: &str = "1\r\n00:00:01,000 --> 00:00:02,500\r\n你好，世界\r\n";
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ffui_subtitle_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        let mut bytes = if big_endian { vec![0xFE, 0xFF] } else { vec![0xFF, 0xFE] };
+        for unit in text.encode_utf16() {
+            bytes.extend(if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() });
+        }
+        bytes
+    }
+
+    #[test]
+    fn bom_decides_encoding() {
+        let dir = scratch_dir("bom");
+        let mut utf8 = vec![0xEF, 0xBB, 0xBF];
+        utf8.extend(LINES.as_bytes());
+        let cases = [("utf8.srt", utf8, UTF_8), ("le.srt", utf16(LINES, false), UTF_16LE), ("be.srt", utf16(LINES, true), UTF_16BE)];
+        for (name, bytes, encoding) in cases {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            let found = detect(&path).unwrap();
+            assert_eq!(found.encoding, encoding, "{}", name);
+            assert!(found.bom && !found.malformed, "{}", name);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn without_bom_falls_back_to_detector() {
+        let dir = scratch_dir("nobom");
+        let plain = dir.join("plain.srt");
+        fs::write(&plain, LINES).unwrap();
+        let found = detect(&plain).unwrap();
+        assert_eq!(found.encoding, UTF_8);
+        assert!(!found.bom && !found.malformed);
+
+        let gbk = dir.join("gbk.srt");
+        let text = LINES.repeat(8);
+        fs::write(&gbk, GBK.encode(&text).0).unwrap();
+        let found = detect(&gbk).unwrap();
+        assert_eq!(found.encoding, GBK);
+        assert!(!found.bom && !found.malformed);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn utf8_copy_drops_bom() {
+        let dir = scratch_dir("copy");
+        let source = dir.join("le.ass");
+        fs::write(&source, utf16(LINES, false)).unwrap();
+        let copy = to_utf8_copy(&source, UTF_16LE, &dir).unwrap();
+        assert_eq!(copy.extension().unwrap(), "ass");
+        assert_eq!(fs::read(&copy).unwrap(), LINES.as_bytes());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prepare_rewrites_only_non_utf8() {
+        let dir = scratch_dir("prepare");
+        let source = dir.join("be.srt");
+        fs::write(&source, utf16(LINES, true)).unwrap();
+        let mut settings = JobSettings { subtitle_file: source.to_string_lossy().into_owned(), ..Default::default() };
+        prepare(&mut settings, &dir).unwrap();
+        assert_ne!(settings.subtitle_file, source.to_string_lossy());
+        assert_eq!(fs::read_to_string(&settings.subtitle_file).unwrap(), LINES);
+        assert_eq!(settings.subtitle_encoding, None);
+
+        let plain = dir.join("plain.srt");
+        fs::write(&plain, LINES).unwrap();
+        let mut settings = JobSettings { subtitle_file: plain.to_string_lossy().into_owned(), ..Default::default() };
+        prepare(&mut settings, &dir).unwrap();
+        assert_eq!(settings.subtitle_file, plain.to_string_lossy());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn filter_path_is_escaped_twice() {
+        assert_eq!(escape_filter_path(r"C:\subs\a.srt"), r"C\\:/subs/a.srt");
+        assert_eq!(escape_filter_path("it's[1].srt"), r"it\\\'s\[1\].srt");
+    }
+}
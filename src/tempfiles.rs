@@ -0,0 +1,147 @@
+use std::fs::{self, File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::config;
+
+// 每个任务的中间文件（两遍编码的统计、取样片段、章节元数据、字幕副本）放在
+// <临时目录>/ffui/<进程号>-<序号>/ 下，任务结束或停止时整个目录删除。
+// 目录里的 .lock 在使用期间一直持有独占锁，启动时清理靠它跳过别的实例正在用的目录
+const LOCK: &str = ".lock";
+// 超过这么久没有改动、也没有被锁住的目录是异常退出留下的
+pub const ORPHAN_AGE: Duration = Duration::from_secs(24 * 3600);
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+// 设置了临时文件目录（比如更快的硬盘）时用它，否则用系统临时目录
+pub fn root() -> PathBuf {
+    let scratch = config::current().scratch_dir;
+    let base = if scratch.trim().is_empty() { std::env::temp_dir() } else { PathBuf::from(scratch.trim()) };
+    base.join("ffui")
+}
+
+pub fn join(dir: &Path, name: &str) -> String {
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+pub struct TempFiles {
+    dir: PathBuf,
+    lock: Option<File>,
+}
+
+impl TempFiles {
+    pub fn new() -> io::Result<TempFiles> {
+        TempFiles::create_in(&root())
+    }
+
+    pub fn create_in(root: &Path) -> io::Result<TempFiles> {
+        let dir = root.join(format!("{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&dir)?;
+        let lock = File::create(dir.join(LOCK))?;
+        lock.lock()?;
+        Ok(TempFiles { dir, lock: Some(lock) })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, name: &str) -> String {
+        join(&self.dir, name)
+    }
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        // Windows 上打开着的文件删不掉，先放开锁
+        self.lock.take();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// 整个进程共用的目录，放预览这类任务结束后还要留着播放的文件。
+// 程序退出时不会删除，锁随进程释放，之后由启动时的清理收走
+pub fn session() -> Option<&'static TempFiles> {
+    static SESSION: OnceLock<Option<TempFiles>> = OnceLock::new();
+    SESSION.get_or_init(|| TempFiles::new().ok()).as_ref()
+}
+
+// 锁被别的进程持有说明还在用；没有锁文件（创建到一半就退出）的按没在用处理
+fn in_use(dir: &Path) -> bool {
+    match File::open(dir.join(LOCK)) {
+        Ok(lock) => matches!(lock.try_lock(), Err(TryLockError::WouldBlock)),
+        Err(_) => false,
+    }
+}
+
+// 启动时调用：删除 root 下超过 older_than 没有改动、锁也没有被持有的目录，返回删除的个数
+pub fn sweep(root: &Path, older_than: Duration) -> usize {
+    let Ok(entries) = fs::read_dir(root) else { return 0 };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let age = entry.metadata().and_then(|m| m.modified()).ok().and_then(|t| SystemTime::now().duration_since(t).ok());
+        if age.is_none_or(|a| a < older_than) || in_use(&path) {
+            continue;
+        }
+        if fs::remove_dir_all(&path).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ffui_tempfiles_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn each_job_gets_its_own_dir_removed_on_drop() {
+        let root = scratch_root("drop");
+        let a = TempFiles::create_in(&root).unwrap();
+        let b = TempFiles::create_in(&root).unwrap();
+        assert_ne!(a.dir(), b.dir());
+        fs::write(a.path("pass-0.log"), b"x").unwrap();
+        assert_eq!(a.path("pass-0.log"), join(a.dir(), "pass-0.log"));
+        let dir = a.dir().to_path_buf();
+        drop(a);
+        assert!(!dir.exists());
+        assert!(b.dir().exists());
+        drop(b);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn sweep_keeps_locked_and_recent_dirs() {
+        let root = scratch_root("sweep");
+        let held = TempFiles::create_in(&root).unwrap();
+        // 异常退出留下的：锁文件还在但没人持有；创建到一半的：没有锁文件
+        let crashed = root.join("123-0");
+        fs::create_dir_all(&crashed).unwrap();
+        File::create(crashed.join(LOCK)).unwrap();
+        let half = root.join("123-1");
+        fs::create_dir_all(&half).unwrap();
+        fs::write(root.join("stray.txt"), b"x").unwrap();
+
+        assert_eq!(sweep(&root, ORPHAN_AGE), 0);
+        assert_eq!(sweep(&root, Duration::ZERO), 2);
+        assert!(held.dir().exists() && !crashed.exists() && !half.exists());
+        assert!(root.join("stray.txt").exists());
+        drop(held);
+        assert_eq!(sweep(&root.join("missing"), Duration::ZERO), 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,230 @@
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logbuf::LogBuffer;
+use crate::process;
+
+#[derive(Clone)]
+pub struct ThermalConfig {
+    pub enabled: bool,
+    pub high: f32, // 超过此温度持续 hold_secs 秒则暂停
+    pub low: f32,  // 降到此温度以下恢复
+    pub hold_secs: u64,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        ThermalConfig { enabled: false, high: 90.0, low: 75.0, hold_secs: 10 }
+    }
+}
+
+const POLL: Duration = Duration::from_secs(3);
+
+// 读取 CPU 温度（摄氏度），拿不到传感器时返回 None
+pub fn read_cpu_temp() -> Option<f32> {
+    #[cfg(target_os = "windows")]
+    { read_wmi() }
+    #[cfg(not(target_os = "windows"))]
+    { read_hwmon() }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn read_hwmon() -> Option<f32> {
+    use std::fs;
+    let mut hottest: Option<f32> = None;
+    for dir in fs::read_dir("/sys/class/hwmon").ok()?.flatten() {
+        let name = fs::read_to_string(dir.path().join("name")).unwrap_or_default();
+        if !matches!(name.trim(), "coretemp" | "k10temp" | "zenpower" | "cpu_thermal" | "acpitz") {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(dir.path()) else { continue };
+        for entry in entries.flatten() {
+            let file = entry.file_name().to_string_lossy().into_owned();
+            if !(file.starts_with("temp") && file.ends_with("_input")) {
+                continue;
+            }
+            let millis = fs::read_to_string(entry.path()).ok().and_then(|v| v.trim().parse::<f32>().ok());
+            if let Some(c) = millis.map(|m| m / 1000.0) {
+                hottest = Some(hottest.map_or(c, |h: f32| h.max(c)));
+            }
+        }
+    }
+    hottest
+}
+
+#[cfg(target_os = "windows")]
+fn read_wmi() -> Option<f32> {
+    // MSAcpi_ThermalZoneTemperature 单位是 0.1 开尔文
+    let output = process::command("powershell")
+        .args([
+            "-NoProfile", "-Command",
+            "(Get-CimInstance -Namespace root/wmi -ClassName MSAcpi_ThermalZoneTemperature).CurrentTemperature",
+        ])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.trim().parse::<f32>().ok())
+        .map(|dk| dk / 10.0 - 273.15)
+        .reduce(f32::max)
+}
+
+// 暂停和恢复的判断，不碰传感器和进程：超过 high 持续 hold 才暂停，中间降下来就重新计时；
+// 暂停后降到 low 以下才恢复，两个阈值之间不来回切换
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Action {
+    Keep,
+    Pause,
+    Resume,
+}
+
+struct Hysteresis {
+    high: f32,
+    low: f32,
+    hold: Duration,
+    hot_since: Option<Instant>,
+    paused: bool,
+}
+
+impl Hysteresis {
+    fn new(cfg: &ThermalConfig) -> Self {
+        Hysteresis { high: cfg.high, low: cfg.low, hold: Duration::from_secs(cfg.hold_secs), hot_since: None, paused: false }
+    }
+
+    fn step(&mut self, temp: f32, now: Instant) -> Action {
+        if self.paused {
+            return if temp < self.low { Action::Resume } else { Action::Keep };
+        }
+        if temp < self.high {
+            self.hot_since = None;
+            return Action::Keep;
+        }
+        let since = *self.hot_since.get_or_insert(now);
+        if now.duration_since(since) >= self.hold { Action::Pause } else { Action::Keep }
+    }
+
+    // 暂停或恢复成功后调用；失败时状态不变，下次轮询再判断
+    fn done(&mut self, action: Action) {
+        match action {
+            Action::Pause => {
+                self.paused = true;
+                self.hot_since = None;
+            }
+            Action::Resume => self.paused = false,
+            Action::Keep => {}
+        }
+    }
+}
+
+// 任务运行期间轮询温度，过热时暂停 ffmpeg，降温后恢复
+pub fn guard(
+    cfg: ThermalConfig,
+    child: Arc<Mutex<Option<Child>>>,
+    running: Arc<Mutex<bool>>,
+    paused: Arc<Mutex<Option<f32>>>,
+    log_text: Arc<Mutex<LogBuffer>>,
+) {
+    if !cfg.enabled {
+        return;
+    }
+    thread::spawn(move || {
+        if read_cpu_temp().is_none() {
+            log_text.lock().unwrap().push_str("\n=== 未找到 CPU 温度传感器，温度保护已停用 ===\n");
+            return;
+        }
+        let mut state = Hysteresis::new(&cfg);
+        while *running.lock().unwrap() {
+            thread::sleep(POLL);
+            let Some(temp) = read_cpu_temp() else { continue };
+            // 一个任务可能依次运行多个 ffmpeg，每次取当前进程
+            let Some(pid) = child.lock().unwrap().as_ref().map(|c| c.id()) else { continue };
+            if state.paused {
+                *paused.lock().unwrap() = Some(temp);
+            }
+            match state.step(temp, Instant::now()) {
+                Action::Keep => {}
+                Action::Resume => {
+                    if process::resume(pid).is_ok() {
+                        state.done(Action::Resume);
+                        *paused.lock().unwrap() = None;
+                        log_text.lock().unwrap().push_str(&format!("\n=== 温度已降至 {:.0}°C，继续转换 ===\n", temp));
+                    }
+                }
+                Action::Pause => match process::suspend(pid) {
+                    Ok(()) => {
+                        state.done(Action::Pause);
+                        *paused.lock().unwrap() = Some(temp);
+                        log_text.lock().unwrap().push_str(&format!("\n=== 已暂停：温度过高 {:.0}°C ===\n", temp));
+                    }
+                    Err(e) => {
+                        log_text.lock().unwrap().push_str(&format!("\n=== 无法暂停 ffmpeg ({})，温度保护已停用 ===\n", e));
+                        return;
+                    }
+                },
+            }
+        }
+        *paused.lock().unwrap() = None;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> Hysteresis {
+        // 默认 90°C 持续 10 秒暂停，75°C 以下恢复
+        Hysteresis::new(&ThermalConfig { enabled: true, ..Default::default() })
+    }
+
+    fn secs(start: Instant, n: u64) -> Instant {
+        start + Duration::from_secs(n)
+    }
+
+    #[test]
+    fn pauses_after_the_hold_time() {
+        let (mut s, t) = (state(), Instant::now());
+        assert_eq!(s.step(92.0, secs(t, 0)), Action::Keep);
+        assert_eq!(s.step(95.0, secs(t, 9)), Action::Keep);
+        assert_eq!(s.step(90.0, secs(t, 10)), Action::Pause);
+        s.done(Action::Pause);
+        assert!(s.paused && s.hot_since.is_none());
+    }
+
+    #[test]
+    fn a_dip_restarts_the_hold_time() {
+        let (mut s, t) = (state(), Instant::now());
+        assert_eq!(s.step(92.0, secs(t, 0)), Action::Keep);
+        assert_eq!(s.step(89.9, secs(t, 6)), Action::Keep);
+        assert!(s.hot_since.is_none());
+        assert_eq!(s.step(92.0, secs(t, 9)), Action::Keep);
+        assert_eq!(s.step(92.0, secs(t, 18)), Action::Keep);
+        assert_eq!(s.step(92.0, secs(t, 19)), Action::Pause);
+    }
+
+    #[test]
+    fn resumes_only_below_low() {
+        let (mut s, t) = (state(), Instant::now());
+        s.step(95.0, secs(t, 0));
+        assert_eq!(s.step(95.0, secs(t, 10)), Action::Pause);
+        s.done(Action::Pause);
+        // 降到 high 以下但还没到 low：继续暂停
+        assert_eq!(s.step(85.0, secs(t, 13)), Action::Keep);
+        assert_eq!(s.step(75.0, secs(t, 16)), Action::Keep);
+        assert_eq!(s.step(74.9, secs(t, 19)), Action::Resume);
+        // 恢复失败时还是暂停状态，下次接着尝试
+        assert_eq!(s.step(70.0, secs(t, 22)), Action::Resume);
+        s.done(Action::Resume);
+        assert!(!s.paused);
+        // 恢复后重新计时，不会马上又暂停
+        assert_eq!(s.step(91.0, secs(t, 25)), Action::Keep);
+        assert_eq!(s.step(91.0, secs(t, 35)), Action::Pause);
+    }
+
+    #[test]
+    fn zero_hold_pauses_at_once() {
+        let mut s = Hysteresis::new(&ThermalConfig { hold_secs: 0, ..Default::default() });
+        assert_eq!(s.step(90.0, Instant::now()), Action::Pause);
+    }
+}
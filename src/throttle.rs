@@ -0,0 +1,166 @@
+use crate::args::{Args, Source};
+use crate::probe::MediaInfo;
+
+// 限制写入速度：输出在 NAS、SMR 硬盘上时，全速写入会占满网络或拖垮磁盘。
+// ffmpeg 没有直接限制写入速度的选项，按任务类型换算：
+// 编码：-maxrate/-bufsize 把码率压在上限以内，再用 -readrate 把处理速度限制在若干倍实时，
+//   写入速度 ≈ 码率 × 倍速，两者的乘积不超过上限；
+// 流复制：输出码率就是源码率，只能用 -readrate 按 上限/源码率 放慢读取
+
+// 1 MB/s 按 10^6 字节算，和 NAS、网络的标称一致
+const BITS_PER_MB: f64 = 8_000_000.0;
+// 给音频和封装开销留的余量，视频码率上限要减掉这部分
+const HEADROOM_BITS: f64 = 320_000.0;
+// 视频码率上限不低于这个值，上限设得太小时宁可放慢也不把画面压坏
+const MIN_VIDEO_BITS: f64 = 500_000.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mechanism {
+    // 重新编码：限制码率和处理速度
+    Encode { maxrate: u64, readrate: f64 },
+    // 流复制：只限制读取速度
+    Copy { readrate: f64 },
+}
+
+impl Mechanism {
+    // 编码时码率上限不超过源码率：重新编码很少需要比源文件更高的码率，
+    // 源码率较低时就能允许多倍实时的速度
+    pub fn encode(limit_mb: f64, source_bits: Option<f64>) -> Mechanism {
+        let limit = limit_mb * BITS_PER_MB;
+        let mut video = (limit - HEADROOM_BITS).max(MIN_VIDEO_BITS);
+        if let Some(source) = source_bits.filter(|b| *b > 0.0) {
+            video = video.min(source.max(MIN_VIDEO_BITS));
+        }
+        Mechanism::Encode { maxrate: video as u64, readrate: limit / (video + HEADROOM_BITS) }
+    }
+
+    // 不知道源码率时没法换算，返回 None
+    pub fn copy(limit_mb: f64, source_bits: Option<f64>) -> Option<Mechanism> {
+        let source = source_bits.filter(|b| *b > 0.0)?;
+        Some(Mechanism::Copy { readrate: limit_mb * BITS_PER_MB / source })
+    }
+
+    fn readrate(&self) -> f64 {
+        match *self {
+            Mechanism::Encode { readrate, .. } | Mechanism::Copy { readrate } => readrate,
+        }
+    }
+
+    // 写进日志的说明
+    pub fn describe(&self, limit_mb: f64) -> String {
+        match *self {
+            Mechanism::Encode { maxrate, readrate } => format!(
+                "限速 {} MB/s: 重新编码，码率上限 {} kbps（-maxrate/-bufsize），处理速度不超过 {:.2} 倍实时（-readrate）",
+                limit_mb, maxrate / 1000, readrate
+            ),
+            Mechanism::Copy { readrate } => format!(
+                "限速 {} MB/s: 流复制，读取速度不超过 {:.2} 倍实时（-readrate）",
+                limit_mb, readrate
+            ),
+        }
+    }
+
+    // 输入选项 -readrate 的值，放在 -i 前面
+    pub fn readrate_arg(&self) -> String {
+        format!("{:.3}", self.readrate())
+    }
+
+    // 视频编码选项，放在 -c:v 后面；缓冲取两秒，短时间的码率峰值不至于被削得太狠
+    pub fn output_args(&self) -> Args {
+        let mut args = Args::new();
+        if let Mechanism::Encode { maxrate, .. } = *self {
+            args.push(Source::Throttle, &["-maxrate", &maxrate.to_string(), "-bufsize", &(maxrate * 2).to_string()]);
+        }
+        args
+    }
+
+    // 受限速影响的最短耗时，剩余时间的估算不能比这个短
+    pub fn min_secs(&self, duration: f64) -> f64 {
+        duration / self.readrate()
+    }
+}
+
+// 源文件的总码率：优先用 ffprobe 报的，没有时按文件大小和时长算
+pub fn source_bits(info: &MediaInfo, input: &str) -> Option<f64> {
+    if let Some(bits) = info.format.get("bit_rate").and_then(|b| b.parse::<f64>().ok()).filter(|b| *b > 0.0) {
+        return Some(bits);
+    }
+    let size = std::fs::metadata(input).ok()?.len() as f64;
+    bits_from_size(size, info.duration)
+}
+
+pub fn bits_from_size(size: f64, duration: f64) -> Option<f64> {
+    (size > 0.0 && duration > 0.0).then(|| size * 8.0 / duration)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn encode_splits_the_limit_into_bitrate_and_speed() {
+        // 10 MB/s = 80 Mbps，不知道源码率：视频码率用满上限，处理速度 1 倍实时
+        let Mechanism::Encode { maxrate, readrate } = Mechanism::encode(10.0, None) else { panic!() };
+        assert_eq!(maxrate, 80_000_000 - 320_000);
+        assert!(close(readrate, 1.0));
+    }
+
+    #[test]
+    fn encode_caps_the_bitrate_at_the_source() {
+        // 源码率 8 Mbps 时码率上限就是 8 Mbps，多出来的余量换成倍速
+        let Mechanism::Encode { maxrate, readrate } = Mechanism::encode(10.0, Some(8_000_000.0)) else { panic!() };
+        assert_eq!(maxrate, 8_000_000);
+        assert!(close(readrate, 80_000_000.0 / 8_320_000.0));
+        // 源码率为 0 当作不知道
+        assert!(Mechanism::encode(10.0, Some(0.0)) == Mechanism::encode(10.0, None));
+    }
+
+    #[test]
+    fn encode_keeps_a_minimum_bitrate() {
+        // 0.05 MB/s 扣掉余量后不到 0.5 Mbps，码率按下限算，只能放慢
+        let Mechanism::Encode { maxrate, readrate } = Mechanism::encode(0.05, None) else { panic!() };
+        assert_eq!(maxrate, 500_000);
+        assert!(close(readrate, 400_000.0 / 820_000.0));
+        // 源码率比下限还低时也不低于下限
+        let Mechanism::Encode { maxrate, .. } = Mechanism::encode(10.0, Some(100_000.0)) else { panic!() };
+        assert_eq!(maxrate, 500_000);
+    }
+
+    #[test]
+    fn copy_needs_the_source_bitrate() {
+        assert!(Mechanism::copy(4.0, None).is_none());
+        assert!(Mechanism::copy(4.0, Some(0.0)).is_none());
+        let copy = Mechanism::copy(4.0, Some(20_000_000.0)).unwrap();
+        assert!(copy == Mechanism::Copy { readrate: 1.6 });
+        assert_eq!(copy.readrate_arg(), "1.600");
+        // 流复制不加码率选项
+        assert!(copy.output_args().argv().is_empty());
+    }
+
+    #[test]
+    fn min_secs_follows_the_readrate() {
+        // 剩余时间的估算不能比限速允许的更短
+        let copy = Mechanism::copy(4.0, Some(20_000_000.0)).unwrap();
+        assert!(close(copy.min_secs(100.0), 62.5));
+        let slow = Mechanism::copy(1.0, Some(16_000_000.0)).unwrap();
+        assert!(close(slow.min_secs(60.0), 120.0));
+    }
+
+    #[test]
+    fn bitrate_from_the_file_size() {
+        assert_eq!(bits_from_size(250_000_000.0, 100.0), Some(20_000_000.0));
+        assert_eq!(bits_from_size(0.0, 100.0), None);
+        assert_eq!(bits_from_size(1000.0, 0.0), None);
+        // ffprobe 报了码率时用它，不看文件
+        let mut info = MediaInfo { duration: 100.0, ..Default::default() };
+        info.format.insert("bit_rate".to_string(), "5000000".to_string());
+        assert_eq!(source_bits(&info, "missing.mp4"), Some(5_000_000.0));
+        info.format.insert("bit_rate".to_string(), "N/A".to_string());
+        assert_eq!(source_bits(&info, "missing.mp4"), None);
+    }
+}
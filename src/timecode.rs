@@ -0,0 +1,181 @@
+use std::fmt;
+
+use crate::probe::MediaInfo;
+
+// SMPTE 时间码 HH:MM:SS:FF，丢帧格式最后一个分隔符写成 ;
+#[derive(Clone, Copy, PartialEq)]
+pub struct Timecode {
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub frames: u32,
+    pub drop_frame: bool,
+}
+
+impl Timecode {
+    // 接受 01:00:00:00、01:00:00;00，以及部分设备写的 01:00:00.00 / 01:00:00,00
+    pub fn parse(text: &str) -> Option<Timecode> {
+        let text = text.trim();
+        let split = text.rfind([':', ';', '.', ','])?;
+        let drop_frame = text[split..].starts_with([';', '.', ',']);
+        let mut hms = text[..split].split(':').map(|p| p.parse::<u32>().ok());
+        let (hours, minutes, seconds) = (hms.next()??, hms.next()??, hms.next()??);
+        if hms.next().is_some() {
+            return None;
+        }
+        let frames = text[split + 1..].parse().ok()?;
+        if minutes >= 60 || seconds >= 60 || hours >= 24 {
+            return None;
+        }
+        // 丢帧格式每分钟开头跳过 0、1 帧（整十分钟除外），这样的时间码不存在
+        if drop_frame && seconds == 0 && frames < 2 && minutes % 10 != 0 {
+            return None;
+        }
+        Some(Timecode { hours, minutes, seconds, frames, drop_frame })
+    }
+
+    // 帧号必须小于取整后的帧率；丢帧只适用于 29.97/59.94
+    pub fn fits_rate(&self, fps: f64) -> bool {
+        let nominal = fps.round() as u32;
+        let ntsc = (fps * 1001.0 / 1000.0 - nominal as f64).abs() < 0.01 && nominal.is_multiple_of(30);
+        self.frames < nominal && (!self.drop_frame || ntsc)
+    }
+
+    // 往后数 frames 帧，超过 24 小时回到 0。
+    // 丢帧格式每分钟开头跳过 fps/15 个帧号（整十分钟除外），先换成连续帧数再换回来
+    pub fn add_frames(self, frames: u64, fps: f64) -> Timecode {
+        let nominal = (fps.round() as u64).max(1);
+        let skip = if self.drop_frame { nominal / 15 } else { 0 };
+        let per_minute = nominal * 60 - skip;
+        let per_ten = per_minute * 10 + skip;
+        let day = per_ten * 6 * 24;
+        let minutes = self.hours as u64 * 60 + self.minutes as u64;
+        let start = (minutes * 60 + self.seconds as u64) * nominal + self.frames as u64 - skip * (minutes - minutes / 10);
+        let total = (start + frames) % day;
+        let (tens, rest) = (total / per_ten, total % per_ten);
+        let numbered = total + skip * 9 * tens + if rest > skip { skip * ((rest - skip) / per_minute) } else { 0 };
+        Timecode {
+            hours: (numbered / (nominal * 3600)) as u32,
+            minutes: (numbered / (nominal * 60) % 60) as u32,
+            seconds: (numbered / nominal % 60) as u32,
+            frames: (numbered % nominal) as u32,
+            drop_frame: self.drop_frame,
+        }
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds,
+            if self.drop_frame { ';' } else { ':' },
+            self.frames
+        )
+    }
+}
+
+// 先看容器标签，再看各条流（视频流或 tmcd 数据流）的标签；没有时间码的普通文件返回 None
+pub fn from_info(info: &MediaInfo) -> Option<Timecode> {
+    info.format.get("tags.timecode")
+        .into_iter()
+        .chain(info.streams.iter().filter_map(|s| s.props.get("tags.timecode")))
+        .find_map(|t| Timecode::parse(t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NTSC: f64 = 30000.0 / 1001.0;
+
+    fn tc(text: &str) -> Timecode {
+        Timecode::parse(text).unwrap()
+    }
+
+    fn add(text: &str, frames: u64, fps: f64) -> String {
+        tc(text).add_frames(frames, fps).to_string()
+    }
+
+    #[test]
+    fn parse_accepts_drop_frame_separators() {
+        assert!(!tc("01:00:00:00").drop_frame);
+        for text in ["01:00:00;00", "01:00:00.00", "01:00:00,00"] {
+            let t = tc(text);
+            assert!(t.drop_frame, "{}", text);
+            assert_eq!(t.to_string(), "01:00:00;00");
+        }
+        assert_eq!(tc(" 10:20:30:15 ").to_string(), "10:20:30:15");
+    }
+
+    #[test]
+    fn parse_rejects_impossible_timecodes() {
+        for text in ["", "01:00:00", "1:2", "01:00:00:00:00", "24:00:00:00", "00:60:00:00", "00:00:60:00", "00:00:00:xx"] {
+            assert!(Timecode::parse(text).is_none(), "{}", text);
+        }
+        // 丢帧格式里不存在的帧号，整十分钟除外
+        assert!(Timecode::parse("00:01:00;00").is_none());
+        assert!(Timecode::parse("00:01:00;01").is_none());
+        assert!(Timecode::parse("00:01:00;02").is_some());
+        assert!(Timecode::parse("00:10:00;00").is_some());
+    }
+
+    #[test]
+    fn drop_frame_skips_at_minute_boundaries() {
+        assert_eq!(add("00:00:59;29", 1, NTSC), "00:01:00;02");
+        assert_eq!(add("00:01:59;29", 1, NTSC), "00:02:00;02");
+        assert_eq!(add("00:01:00;02", 1, NTSC), "00:01:00;03");
+        // 整十分钟不跳
+        assert_eq!(add("00:09:59;29", 1, NTSC), "00:10:00;00");
+        assert_eq!(add("00:19:59;29", 1, NTSC), "00:20:00;00");
+        // 59.94 每分钟跳 4 个帧号
+        assert_eq!(add("00:00:59;59", 1, 60000.0 / 1001.0), "00:01:00;04");
+    }
+
+    #[test]
+    fn drop_frame_counts_match_real_time() {
+        // 丢帧时间码十分钟正好 17982 帧，一小时 107892 帧
+        assert_eq!(add("00:00:00;00", 17982, NTSC), "00:10:00;00");
+        assert_eq!(add("00:00:00;00", 107892, NTSC), "01:00:00;00");
+        assert_eq!(add("00:00:00;00", 1800, NTSC), "00:01:00;02");
+        assert_eq!(add("00:59:59;29", 1, NTSC), "01:00:00;00");
+        // 逐帧往后数和一次加完结果相同
+        let mut step = tc("00:08:59;28");
+        for _ in 0..4000 {
+            step = step.add_frames(1, NTSC);
+        }
+        assert_eq!(step.to_string(), add("00:08:59;28", 4000, NTSC));
+    }
+
+    #[test]
+    fn non_drop_and_wraparound() {
+        assert_eq!(add("00:00:59:24", 1, 25.0), "00:01:00:00");
+        assert_eq!(add("00:00:00:00", 25 * 3600, 25.0), "01:00:00:00");
+        assert_eq!(add("23:59:59:24", 1, 25.0), "00:00:00:00");
+        assert_eq!(add("23:59:59;29", 1, NTSC), "00:00:00;00");
+    }
+
+    #[test]
+    fn frame_numbers_must_fit_the_rate() {
+        assert!(tc("00:00:00;29").fits_rate(NTSC));
+        assert!(!tc("00:00:00:25").fits_rate(25.0));
+        assert!(tc("00:00:00:24").fits_rate(25.0));
+        // 丢帧只用于 29.97/59.94
+        assert!(!tc("00:00:00;10").fits_rate(25.0));
+        assert!(!tc("00:00:00;10").fits_rate(30.0));
+        assert!(tc("00:00:00;10").fits_rate(60000.0 / 1001.0));
+    }
+
+    #[test]
+    fn container_tag_wins_over_streams() {
+        let mut info = MediaInfo::default();
+        assert!(from_info(&info).is_none());
+        let mut stream = crate::probe::StreamInfo::default();
+        stream.props.insert("tags.timecode".to_string(), "00:00:10;00".to_string());
+        info.streams.push(stream);
+        assert_eq!(from_info(&info).unwrap().to_string(), "00:00:10;00");
+        info.format.insert("tags.timecode".to_string(), "01:00:00:00".to_string());
+        assert_eq!(from_info(&info).unwrap().to_string(), "01:00:00:00");
+    }
+}
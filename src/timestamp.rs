@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use eframe::egui;
+
+// 界面上输入的时间：SS、SS.mmm、MM:SS、HH:MM:SS.mmm，小数点也可以写成逗号。
+// 只有第一段可以超过 59（90:00 即 90 分钟）
+pub fn parse(text: &str) -> Result<Duration, String> {
+    let text = text.trim().replace(',', ".");
+    if text.is_empty() {
+        return Err("请输入时间".to_string());
+    }
+    if text.starts_with('-') {
+        return Err("不能为负数".to_string());
+    }
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() > 3 {
+        return Err("格式应为 时:分:秒".to_string());
+    }
+
+    let (whole, frac) = parts[parts.len() - 1].split_once('.').unwrap_or((parts[parts.len() - 1], ""));
+    let number = |p: &str| -> Result<u64, String> {
+        if p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("“{}” 不是有效的数字", p));
+        }
+        p.parse().map_err(|_| format!("“{}” 太大", p))
+    };
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("“{}” 不是有效的小数", frac));
+    }
+
+    let mut secs = 0u64;
+    for (i, part) in parts[..parts.len() - 1].iter().enumerate() {
+        let n = number(part)?;
+        if i > 0 && n >= 60 {
+            return Err(format!("分钟 {} 超过 59", n));
+        }
+        secs = secs.checked_mul(60).and_then(|s| s.checked_add(n)).ok_or("时间太长")?;
+    }
+    // .5 当作 0.5
+    let s = if whole.is_empty() && !frac.is_empty() { 0 } else { number(whole)? };
+    if parts.len() > 1 && s >= 60 {
+        return Err(format!("秒数 {} 超过 59", s));
+    }
+    secs = secs.checked_mul(60).and_then(|t| t.checked_add(s)).ok_or("时间太长")?;
+
+    // 小数部分精确到纳秒，多余的位数舍去
+    let digits: String = frac.chars().chain(std::iter::repeat('0')).take(9).collect();
+    Ok(Duration::new(secs, digits.parse().unwrap_or(0)))
+}
+
+// 显示用：1:02:03.5、2:03、7.25，毫秒以下不显示
+pub fn format(d: Duration) -> String {
+    let total = d.as_secs();
+    let millis = d.subsec_millis();
+    let frac = if millis == 0 { String::new() } else { format!(".{:03}", millis).trim_end_matches('0').to_string() };
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}{}", h, m, s, frac)
+    } else if m > 0 {
+        format!("{}:{:02}{}", m, s, frac)
+    } else {
+        format!("{}{}", s, frac)
+    }
+}
+
+// 时间输入框：保留用户输入的原文，能解析时才写回 value，否则在旁边显示错误
+pub struct TimeField {
+    text: String,
+    error: Option<String>,
+}
+
+impl TimeField {
+    pub fn new(value: Duration) -> Self {
+        TimeField { text: format(value), error: None }
+    }
+
+    // 返回输入框本身，调用方可以把它和说明文字关联起来
+    pub fn show(&mut self, ui: &mut egui::Ui, value: &mut Duration, min: Duration) -> egui::Response {
+        let edit = ui.add(egui::TextEdit::singleline(&mut self.text).desired_width(80.0))
+            .on_hover_text("秒、分:秒 或 时:分:秒，例如 90、1:30、0:01:30.5");
+        if edit.changed() {
+            self.error = match parse(&self.text) {
+                Ok(d) if d < min => Some(format!("不能小于 {} 秒", format(min))),
+                Ok(d) => {
+                    *value = d;
+                    None
+                }
+                Err(e) => Some(e),
+            };
+        }
+        if edit.lost_focus() && self.error.is_none() {
+            self.text = format(*value);
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+        edit
+    }
+
+    // 可以留空的输入框，空表示 None。设置从别处载入（任务列表、恢复编辑）时跟着更新显示
+    pub fn show_optional(&mut self, ui: &mut egui::Ui, value: &mut Option<Duration>) -> egui::Response {
+        let shown = if self.text.trim().is_empty() { None } else { parse(&self.text).ok() };
+        if self.error.is_none() && shown != *value {
+            self.text = value.map(format).unwrap_or_default();
+        }
+        let edit = ui.add(egui::TextEdit::singleline(&mut self.text).desired_width(80.0).hint_text("不限"))
+            .on_hover_text("秒、分:秒 或 时:分:秒，例如 90、1:30、0:01:30.5；留空表示不限");
+        if edit.changed() {
+            self.error = if self.text.trim().is_empty() {
+                *value = None;
+                None
+            } else {
+                match parse(&self.text) {
+                    Ok(d) => {
+                        *value = Some(d);
+                        None
+                    }
+                    Err(e) => Some(e),
+                }
+            };
+        }
+        if edit.lost_focus() && self.error.is_none() {
+            self.text = value.map(format).unwrap_or_default();
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+        edit
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(text: &str) -> u128 {
+        parse(text).unwrap().as_millis()
+    }
+
+    #[test]
+    fn accepts_all_shapes() {
+        assert_eq!(ms("01:02:03.456"), 3_723_456);
+        assert_eq!(ms("1:02:03"), 3_723_000);
+        assert_eq!(ms("02:03"), 123_000);
+        assert_eq!(ms("90:00"), 5_400_000);
+        assert_eq!(ms("90"), 90_000);
+        assert_eq!(ms("7.25"), 7_250);
+        assert_eq!(ms(".5"), 500);
+        assert_eq!(ms("  0:00:00  "), 0);
+        // 多余的位数舍去，不四舍五入
+        assert_eq!(parse("1.1234567899").unwrap(), Duration::new(1, 123_456_789));
+    }
+
+    #[test]
+    fn comma_is_a_decimal_point() {
+        assert_eq!(ms("1:30,5"), 90_500);
+        assert_eq!(ms("00:00:01,250"), 1_250);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        for text in ["", "   ", "-1", "-0:30", "1:2:3:4", "a", "1:b", "1:", ":30", "1.2.3", "1.x", "1:30.-5", "+5", "1 30"] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn only_the_first_field_may_exceed_59() {
+        assert!(parse("1:60").is_err());
+        assert!(parse("1:60:00").is_err());
+        assert!(parse("1:00:60").is_err());
+        assert!(parse("0:59:59.999").is_ok());
+        assert_eq!(ms("100:00:00"), 360_000_000);
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert!(parse("99999999999999999999").is_err());
+        assert!(parse(&format!("{}:00", u64::MAX / 30)).is_err());
+        assert!(parse(&format!("{}:00:00", u64::MAX / 60)).is_err());
+        assert_eq!(parse(&u64::MAX.to_string()).unwrap().as_secs(), u64::MAX);
+    }
+
+    #[test]
+    fn format_round_trips() {
+        assert_eq!(format(Duration::from_millis(3_723_500)), "1:02:03.5");
+        assert_eq!(format(Duration::from_secs(123)), "2:03");
+        assert_eq!(format(Duration::from_millis(7_250)), "7.25");
+        assert_eq!(format(Duration::ZERO), "0");
+        for text in ["1:02:03.5", "2:03", "7.25", "10:00:00.001"] {
+            assert_eq!(format(parse(text).unwrap()), text);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::compare;
+use crate::plan::JobSettings;
+
+// 编辑设置时的撤销/重做。和自动保存一样每帧对比一次，只看 compare.rs 里列出的设置项，
+// 每次任务临时决定的那些不算。撤销只把这一步改过的项改回去，其他项保持现在的值
+
+// 最多记这么多步，更早的丢掉
+pub const DEPTH: usize = 50;
+// 同一组设置项停下不到这么久又接着改（在输入框里打字、连续点数值框）合成一步
+const COALESCE: Duration = Duration::from_millis(800);
+// 历史下拉框里每步最多列出几项
+const SHOWN_LABELS: usize = 3;
+
+struct Step {
+    // 这一步改了哪些项（compare 里的名称）
+    labels: Vec<&'static str>,
+    // 套用预设、恢复编辑这类整体操作的名称，不和前后的修改合并
+    name: Option<String>,
+    before: JobSettings,
+    after: JobSettings,
+    at: Instant,
+}
+
+impl Step {
+    fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        let shown = self.labels.iter().take(SHOWN_LABELS).copied().collect::<Vec<_>>().join("、");
+        if self.labels.len() > SHOWN_LABELS {
+            format!("{} 等 {} 项", shown, self.labels.len())
+        } else {
+            shown
+        }
+    }
+}
+
+pub struct History {
+    undo: VecDeque<Step>,
+    redo: Vec<Step>,
+    // 上次记录时的设置和各项显示的值
+    last: JobSettings,
+    values: Vec<(&'static str, String)>,
+    // 最近一步还能接着合并
+    open: bool,
+}
+
+impl History {
+    pub fn new(settings: &JobSettings) -> Self {
+        History { undo: VecDeque::new(), redo: Vec::new(), last: settings.clone(), values: compare::values(settings), open: false }
+    }
+
+    // 每帧调用一次。held 为 true（鼠标还按着，正在拖滑块）时同一组项的修改一直合并
+    pub fn tick(&mut self, settings: &JobSettings, held: bool) {
+        self.record(settings, None, held);
+    }
+
+    // 整体替换了设置（套用预设、载入上次任务等），记成单独的一步
+    pub fn named(&mut self, settings: &JobSettings, name: String) {
+        self.record(settings, Some(name), false);
+        self.open = false;
+    }
+
+    fn record(&mut self, settings: &JobSettings, name: Option<String>, held: bool) {
+        let values = compare::values(settings);
+        let labels: Vec<&'static str> =
+            values.iter().zip(&self.values).filter(|(now, was)| now.1 != was.1).map(|(now, _)| now.0).collect();
+        if labels.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let merge = name.is_none()
+            && self.open
+            && self.undo.back().is_some_and(|step| step.labels == labels && (held || now.duration_since(step.at) < COALESCE));
+        if merge {
+            let step = self.undo.back_mut().unwrap();
+            step.after = settings.clone();
+            step.at = now;
+            // 改了又改回原样（同一个勾选框连点两下）时这一步就没有了
+            if compare::diff(&step.before, &step.after).is_empty() {
+                self.undo.pop_back();
+                self.open = false;
+            }
+        } else {
+            self.redo.clear();
+            self.undo.push_back(Step { labels, name, before: self.last.clone(), after: settings.clone(), at: now });
+            if self.undo.len() > DEPTH {
+                self.undo.pop_front();
+            }
+            self.open = true;
+        }
+        self.last = settings.clone();
+        self.values = values;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // 撤销最近的 n 步。还没记录的修改先记成一步，不会被一起丢掉
+    pub fn undo(&mut self, settings: &mut JobSettings, n: usize) {
+        self.record(settings, None, false);
+        for _ in 0..n {
+            let Some(step) = self.undo.pop_back() else { break };
+            for label in &step.labels {
+                compare::copy(settings, &step.before, label);
+            }
+            self.redo.push(step);
+        }
+        self.sync(settings);
+    }
+
+    pub fn redo(&mut self, settings: &mut JobSettings, n: usize) {
+        self.record(settings, None, false);
+        for _ in 0..n {
+            let Some(step) = self.redo.pop() else { break };
+            for label in &step.labels {
+                compare::copy(settings, &step.after, label);
+            }
+            self.undo.push_back(step);
+        }
+        self.sync(settings);
+    }
+
+    // 撤销、重做之后的设置作为新的起点，下一次修改另起一步
+    fn sync(&mut self, settings: &JobSettings) {
+        self.last = settings.clone();
+        self.values = compare::values(settings);
+        self.open = false;
+    }
+
+    // 能撤销的步骤，最近的在前
+    pub fn undo_labels(&self) -> Vec<String> {
+        self.undo.iter().rev().map(Step::label).collect()
+    }
+
+    // 能重做的步骤，下一个要重做的在前
+    pub fn redo_labels(&self) -> Vec<String> {
+        self.redo.iter().rev().map(Step::label).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 把最近一步的时间往前挪，当作停下来过一会儿
+    fn pause(h: &mut History) {
+        h.undo.back_mut().unwrap().at -= COALESCE;
+    }
+
+    #[test]
+    fn quick_edits_merge_into_one_step() {
+        let mut s = JobSettings::default();
+        let base = s.audio_bitrate_k;
+        let mut h = History::new(&s);
+        for k in [base + 1, base + 2, base + 3] {
+            s.audio_bitrate_k = k;
+            h.tick(&s, false);
+        }
+        assert_eq!(h.undo_labels(), ["音频码率"]);
+        // 改的是别的项时另起一步
+        s.format = "mkv".to_string();
+        h.tick(&s, false);
+        assert_eq!(h.undo_labels(), ["目标格式", "音频码率"]);
+        h.undo(&mut s, 2);
+        assert_eq!((s.audio_bitrate_k, s.format.as_str()), (base, JobSettings::default().format.as_str()));
+    }
+
+    #[test]
+    fn a_pause_starts_a_new_step_unless_held() {
+        let mut s = JobSettings::default();
+        let base = s.audio_bitrate_k;
+        let mut h = History::new(&s);
+        s.audio_bitrate_k = base + 1;
+        h.tick(&s, false);
+        pause(&mut h);
+        // 还按着鼠标拖滑块时停多久都算同一步
+        s.audio_bitrate_k = base + 2;
+        h.tick(&s, true);
+        assert_eq!(h.undo_labels().len(), 1);
+        pause(&mut h);
+        s.audio_bitrate_k = base + 3;
+        h.tick(&s, false);
+        assert_eq!(h.undo_labels().len(), 2);
+        h.undo(&mut s, 1);
+        assert_eq!(s.audio_bitrate_k, base + 2);
+    }
+
+    #[test]
+    fn toggling_back_removes_the_step() {
+        let mut s = JobSettings::default();
+        let mut h = History::new(&s);
+        s.two_pass = !s.two_pass;
+        h.tick(&s, false);
+        assert!(h.can_undo());
+        s.two_pass = !s.two_pass;
+        h.tick(&s, false);
+        assert!(!h.can_undo());
+        // 改回原样后再改，另起一步，不接到已经没了的那步上
+        s.two_pass = !s.two_pass;
+        h.tick(&s, false);
+        assert_eq!(h.undo_labels(), ["两遍编码"]);
+    }
+
+    #[test]
+    fn named_steps_never_merge() {
+        let mut s = JobSettings::default();
+        let mut h = History::new(&s);
+        s.format = "mkv".to_string();
+        h.named(&s, "套用预设 A".to_string());
+        s.format = "webm".to_string();
+        h.tick(&s, false);
+        assert_eq!(h.undo_labels(), ["目标格式", "套用预设 A"]);
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped_past_the_depth() {
+        let mut s = JobSettings::default();
+        let mut h = History::new(&s);
+        for i in 0..DEPTH + 10 {
+            s.audio_bitrate_k = 1000 + i as u32;
+            h.named(&s, format!("第 {} 步", i));
+        }
+        let labels = h.undo_labels();
+        assert_eq!(labels.len(), DEPTH);
+        assert_eq!(labels[0], format!("第 {} 步", DEPTH + 9));
+        assert_eq!(labels[DEPTH - 1], "第 10 步");
+        // 全部撤销后停在最早还留着的那步之前
+        h.undo(&mut s, usize::MAX);
+        assert_eq!(s.audio_bitrate_k, 1009);
+        assert!(!h.can_undo());
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut s = JobSettings::default();
+        let base = s.audio_bitrate_k;
+        let mut h = History::new(&s);
+        s.audio_bitrate_k = base + 1;
+        h.tick(&s, false);
+        h.undo(&mut s, 1);
+        assert!(h.can_redo());
+        assert_eq!(h.redo_labels(), ["音频码率"]);
+        h.redo(&mut s, 1);
+        assert_eq!(s.audio_bitrate_k, base + 1);
+        h.undo(&mut s, 1);
+        s.format = "mkv".to_string();
+        h.tick(&s, false);
+        assert!(!h.can_redo());
+        assert_eq!(h.undo_labels(), ["目标格式"]);
+    }
+
+    #[test]
+    fn undo_records_pending_edits_first() {
+        let mut s = JobSettings::default();
+        let base = s.audio_bitrate_k;
+        let mut h = History::new(&s);
+        s.audio_bitrate_k = base + 1;
+        h.tick(&s, false);
+        pause(&mut h);
+        // 这一帧还没来得及记录就点了撤销：撤掉的是这次修改，前面那步还在
+        s.format = "mkv".to_string();
+        h.undo(&mut s, 1);
+        assert_eq!((s.audio_bitrate_k, s.format.as_str()), (base + 1, JobSettings::default().format.as_str()));
+        assert_eq!(h.undo_labels(), ["音频码率"]);
+        assert_eq!(h.redo_labels(), ["目标格式"]);
+    }
+
+    #[test]
+    fn undo_only_restores_its_own_items() {
+        let mut s = JobSettings::default();
+        let base = s.audio_bitrate_k;
+        let mut h = History::new(&s);
+        s.audio_bitrate_k = base + 1;
+        h.tick(&s, false);
+        s.format = "mkv".to_string();
+        h.tick(&s, false);
+        // 撤销格式那一步时，前一步改的码率保持现在的值
+        h.undo(&mut s, 1);
+        assert_eq!((s.audio_bitrate_k, s.format.as_str()), (base + 1, JobSettings::default().format.as_str()));
+    }
+}
@@ -0,0 +1,54 @@
+use std::process::Stdio;
+
+use crate::probe::{self, MediaInfo, ProbeDepth};
+use crate::process;
+
+// 转换后的检查：输出能被 ffprobe 正常读出，时长和预期相符；可选用 VMAF 对比源文件评估画质
+
+// 时长允许相差 1 秒或 1%，取较大者
+const TOLERANCE_SECS: f64 = 1.0;
+const TOLERANCE_RATIO: f64 = 0.01;
+// VMAF 低于这个分数算失败
+pub const VMAF_MIN: f64 = 80.0;
+
+// expected 为 0 时不检查时长
+pub fn check_outputs(outputs: &[String], expected: f64) -> Result<String, String> {
+    for output in outputs {
+        let info = probe::probe(output, ProbeDepth::default())?;
+        if info.streams.is_empty() {
+            return Err(format!("{} 里没有可读的流", output));
+        }
+        if expected > 0.0 && (info.duration - expected).abs() > TOLERANCE_SECS.max(expected * TOLERANCE_RATIO) {
+            return Err(format!("{} 时长 {:.1} 秒，预期 {:.1} 秒", output, info.duration, expected));
+        }
+    }
+    Ok(format!("{} 个输出可以正常读取", outputs.len()))
+}
+
+// "VMAF score: 93.412345"
+pub fn parse_vmaf(stderr: &str) -> Option<f64> {
+    stderr.lines().rev().find_map(|l| l.split("VMAF score:").nth(1)?.trim().parse().ok())
+}
+
+// 输出先缩放回源文件的分辨率再和源文件逐帧比较，需要 ffmpeg 编译了 libvmaf
+pub fn vmaf(output: &str, input: &str, source: &MediaInfo) -> Result<f64, String> {
+    let video = source.streams.iter().find(|s| s.codec_type == "video").ok_or("源文件没有视频")?;
+    let size = |k: &str| video.props.get(k).and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+    let graph = format!(
+        "[0:v]scale={}:{}:flags=bicubic,setsar=1[dist];[1:v]setsar=1[ref];[dist][ref]libvmaf",
+        size("width"), size("height")
+    );
+    let result = process::command("ffmpeg")
+        .args(["-nostdin", "-hide_banner", "-i", output, "-i", input, "-lavfi", &graph, "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("无法启动 ffmpeg: {}", e))?;
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if stderr.contains("No such filter: 'libvmaf'") {
+        return Err("ffmpeg 没有编译 libvmaf".to_string());
+    }
+    match parse_vmaf(&stderr) {
+        Some(score) if result.status.success() => Ok(score),
+        _ => Err(stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("ffmpeg 异常退出").trim().to_string()),
+    }
+}
@@ -0,0 +1,245 @@
+// ffmpeg 正常退出但输出可能有问题（卡顿、音画不同步、花屏）时打印的警告。
+// 表里每一项：要同时出现的片段（不区分大小写）、给用户看的说明、重新转换时建议加的参数
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Fix {
+    pub id: &'static str,
+    pub label: &'static str,
+    // 放在 -i 前面
+    pub input_args: &'static [&'static str],
+    // 放在输出路径前面
+    pub output_args: &'static [&'static str],
+}
+
+pub struct Pattern {
+    pub id: &'static str,
+    pub needles: &'static [&'static str],
+    pub explanation: &'static str,
+    pub fix: Option<Fix>,
+}
+
+const GENPTS: Fix = Fix {
+    id: "genpts",
+    label: "重新生成时间戳 (-fflags +genpts)",
+    input_args: &["-fflags", "+genpts"],
+    output_args: &[],
+};
+const CFR: Fix = Fix {
+    id: "cfr",
+    label: "固定帧率输出 (-vsync cfr)",
+    input_args: &[],
+    output_args: &["-vsync", "cfr"],
+};
+const ASYNC: Fix = Fix {
+    id: "async",
+    label: "音频按时间戳对齐 (aresample=async=1)",
+    input_args: &[],
+    output_args: &["-af", "aresample=async=1"],
+};
+
+pub const FIXES: &[Fix] = &[GENPTS, CFR, ASYNC];
+
+pub const PATTERNS: &[Pattern] = &[
+    Pattern {
+        id: "past_duration",
+        needles: &["past duration", "too large"],
+        explanation: "帧的时间间隔不规则，输出播放时可能卡顿",
+        fix: Some(CFR),
+    },
+    Pattern {
+        id: "non_monotonic_dts",
+        needles: &["non monotonically increasing dts"],
+        explanation: "时间戳倒退，输出可能卡顿或音画不同步",
+        fix: Some(GENPTS),
+    },
+    Pattern {
+        id: "non_monotonous_dts",
+        needles: &["non-monotonous dts"],
+        explanation: "时间戳倒退，输出可能卡顿或音画不同步",
+        fix: Some(GENPTS),
+    },
+    Pattern {
+        id: "invalid_dts",
+        needles: &["invalid dts"],
+        explanation: "源文件的时间戳有误，输出可能跳帧",
+        fix: Some(GENPTS),
+    },
+    Pattern {
+        id: "unset_timestamps",
+        needles: &["timestamps are unset in a packet"],
+        explanation: "源文件部分数据没有时间戳，输出可能卡顿或时长不对",
+        fix: Some(GENPTS),
+    },
+    Pattern {
+        id: "audio_backward",
+        needles: &["queue input is backward in time"],
+        explanation: "音频时间戳倒退，输出可能音画不同步",
+        fix: Some(ASYNC),
+    },
+    Pattern {
+        id: "frames_duplicated",
+        needles: &["more than 1000 frames duplicated"],
+        explanation: "源文件帧率不稳定，输出里有大量重复帧，画面可能卡顿",
+        fix: None,
+    },
+    Pattern {
+        id: "frame_rate_high",
+        needles: &["frame rate very high for a muxer not efficiently supporting it"],
+        explanation: "帧率过高，输出文件会变大且部分播放器卡顿",
+        fix: Some(CFR),
+    },
+    Pattern {
+        id: "decode_error",
+        needles: &["error while decoding"],
+        explanation: "源文件部分数据损坏，输出画面或声音可能有瑕疵",
+        fix: None,
+    },
+    Pattern {
+        id: "concealing",
+        needles: &["concealing", "errors in"],
+        explanation: "源文件部分数据损坏，输出画面可能有马赛克",
+        fix: None,
+    },
+];
+
+// 匹配到的警告：第一次出现的原文和出现次数
+#[derive(Clone)]
+pub struct Found {
+    pub pattern: &'static Pattern,
+    pub first_line: String,
+    pub count: usize,
+}
+
+// 逐行统计 stderr 里的警告，不受日志尾部行数限制
+#[derive(Clone, Default)]
+pub struct Tally {
+    pub found: Vec<Found>,
+}
+
+impl Tally {
+    pub fn feed(&mut self, line: &str) {
+        let lower = line.to_lowercase();
+        let Some(pattern) = PATTERNS.iter().find(|p| p.needles.iter().all(|n| lower.contains(n))) else {
+            return;
+        };
+        match self.found.iter_mut().find(|f| f.pattern.id == pattern.id) {
+            Some(found) => found.count += 1,
+            None => self.found.push(Found { pattern, first_line: line.trim().to_string(), count: 1 }),
+        }
+    }
+
+    pub fn merge(&mut self, other: Tally) {
+        for f in other.found {
+            match self.found.iter_mut().find(|x| x.pattern.id == f.pattern.id) {
+                Some(found) => found.count += f.count,
+                None => self.found.push(f),
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.found.is_empty()
+    }
+
+    // 写进日志和报告的说明，每种警告一行
+    pub fn summary(&self) -> String {
+        self.found.iter()
+            .map(|f| format!("⚠ {}（{} 次）: {}\n", f.pattern.explanation, f.count, f.first_line))
+            .collect()
+    }
+
+    // 存进转换记录的警告名，逗号分隔
+    pub fn ids(&self) -> String {
+        self.found.iter().map(|f| f.pattern.id).collect::<Vec<_>>().join(",")
+    }
+
+    // 建议的修正，去掉重复和已经用上的
+    pub fn suggested(&self, applied: &[String]) -> Vec<Fix> {
+        let mut fixes: Vec<Fix> = Vec::new();
+        for fix in self.found.iter().filter_map(|f| f.pattern.fix) {
+            if !fixes.contains(&fix) && !applied.iter().any(|a| a == fix.id) {
+                fixes.push(fix);
+            }
+        }
+        fixes
+    }
+}
+
+fn lookup(id: &str) -> Option<&'static Fix> {
+    FIXES.iter().find(|f| f.id == id)
+}
+
+pub fn input_args(applied: &[String]) -> Vec<String> {
+    applied.iter().filter_map(|id| lookup(id)).flat_map(|f| f.input_args.iter().map(|a| a.to_string())).collect()
+}
+
+pub fn output_args(applied: &[String]) -> Vec<String> {
+    applied.iter().filter_map(|id| lookup(id)).flat_map(|f| f.output_args.iter().map(|a| a.to_string())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &[&str] = &[
+        "[mp4 @ 0x5581] Non-monotonous DTS in output stream 0:1; previous: 1024, current: 512; changing to 1025.",
+        "frame=  100 fps= 50 q=28.0 size=    1024kB time=00:00:04.00 bitrate=2097.2kbits/s speed=2.0x",
+        "[h264 @ 0x5582] concealing 120 DC, 120 AC, 120 MV errors in P frame",
+        "[mp4 @ 0x5581] Non-monotonous DTS in output stream 0:1; previous: 2048, current: 1024; changing to 2049.",
+        "Past duration 0.999 too large",
+        "[aac @ 0x5583] Queue input is backward in time",
+    ];
+
+    fn tally(lines: &[&str]) -> Tally {
+        let mut tally = Tally::default();
+        for line in lines {
+            tally.feed(line);
+        }
+        tally
+    }
+
+    #[test]
+    fn lines_are_counted_per_pattern() {
+        let t = tally(STDERR);
+        assert_eq!(t.ids(), "non_monotonous_dts,concealing,past_duration,audio_backward");
+        assert_eq!(t.found[0].count, 2);
+        assert_eq!(t.found[0].first_line, STDERR[0]);
+        assert!(t.summary().lines().next().unwrap().contains("（2 次）"));
+        // 所有片段都要出现
+        assert!(tally(&["concealing nothing", "past duration"]).is_empty());
+    }
+
+    #[test]
+    fn merge_adds_counts() {
+        let mut a = tally(&STDERR[..2]);
+        a.merge(tally(STDERR));
+        assert_eq!(a.found[0].count, 3);
+        assert_eq!(a.ids(), "non_monotonous_dts,concealing,past_duration,audio_backward");
+    }
+
+    #[test]
+    fn suggestions_skip_duplicates_and_applied() {
+        let t = tally(STDERR);
+        let ids = |fixes: Vec<Fix>| fixes.iter().map(|f| f.id).collect::<Vec<_>>();
+        assert_eq!(ids(t.suggested(&[])), ["genpts", "cfr", "async"]);
+        assert_eq!(ids(t.suggested(&["cfr".to_string()])), ["genpts", "async"]);
+        assert!(tally(&[STDERR[2]]).suggested(&[]).is_empty());
+    }
+
+    #[test]
+    fn applied_fixes_become_arguments() {
+        let applied = ["async".to_string(), "genpts".to_string(), "unknown".to_string(), "cfr".to_string()];
+        assert_eq!(input_args(&applied), ["-fflags", "+genpts"]);
+        assert_eq!(output_args(&applied), ["-af", "aresample=async=1", "-vsync", "cfr"]);
+    }
+
+    #[test]
+    fn table_is_consistent() {
+        for (i, p) in PATTERNS.iter().enumerate() {
+            assert!(PATTERNS[..i].iter().all(|q| q.id != p.id), "{}", p.id);
+            // 按小写比较，片段本身必须是小写
+            assert!(p.needles.iter().all(|n| *n == n.to_lowercase()), "{}", p.id);
+            assert!(p.fix.is_none_or(|f| lookup(f.id).is_some()), "{}", p.id);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logbuf::LogBuffer;
+
+// ffmpeg 超过 limit 没有任何输出时标记为可能已挂起
+pub fn watch(
+    limit: Duration,
+    last_activity: Arc<Mutex<Instant>>,
+    running: Arc<Mutex<bool>>,
+    paused: Arc<Mutex<Option<f32>>>,
+    stalled: Arc<Mutex<bool>>,
+    log_text: Arc<Mutex<LogBuffer>>,
+) {
+    thread::spawn(move || {
+        while *running.lock().unwrap() {
+            thread::sleep(Duration::from_secs(1));
+            // 温度保护暂停期间没有输出是正常的
+            if paused.lock().unwrap().is_some() {
+                *last_activity.lock().unwrap() = Instant::now();
+                continue;
+            }
+            let idle = last_activity.lock().unwrap().elapsed();
+            let mut stalled = stalled.lock().unwrap();
+            if idle >= limit && !*stalled {
+                *stalled = true;
+                log_text.lock().unwrap().push_str(&format!(
+                    "\n=== ffmpeg 已 {} 分钟没有任何输出，可能已挂起 ===\n",
+                    idle.as_secs() / 60
+                ));
+            } else if idle < limit {
+                *stalled = false;
+            }
+        }
+        *stalled.lock().unwrap() = false;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Watched {
+        running: Arc<Mutex<bool>>,
+        paused: Arc<Mutex<Option<f32>>>,
+        stalled: Arc<Mutex<bool>>,
+        log: Arc<Mutex<LogBuffer>>,
+        last_activity: Arc<Mutex<Instant>>,
+    }
+
+    fn start(limit: Duration, paused: Option<f32>) -> Watched {
+        let w = Watched {
+            running: Arc::new(Mutex::new(true)),
+            paused: Arc::new(Mutex::new(paused)),
+            stalled: Arc::new(Mutex::new(false)),
+            log: Arc::new(Mutex::new(LogBuffer::new(100))),
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+        };
+        watch(limit, w.last_activity.clone(), w.running.clone(), w.paused.clone(), w.stalled.clone(), w.log.clone());
+        w
+    }
+
+    #[test]
+    fn silence_marks_stalled_once_and_activity_clears_it() {
+        let w = start(Duration::ZERO, None);
+        thread::sleep(Duration::from_millis(2500));
+        assert!(*w.stalled.lock().unwrap());
+        assert_eq!(w.log.lock().unwrap().as_str().matches("可能已挂起").count(), 1);
+        *w.running.lock().unwrap() = false;
+        thread::sleep(Duration::from_millis(1200));
+        assert!(!*w.stalled.lock().unwrap());
+    }
+
+    #[test]
+    fn paused_job_is_never_stalled() {
+        let w = start(Duration::ZERO, Some(80.0));
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!*w.stalled.lock().unwrap());
+        assert!(w.log.lock().unwrap().as_str().is_empty());
+        assert!(w.last_activity.lock().unwrap().elapsed() < Duration::from_secs(1));
+        *w.running.lock().unwrap() = false;
+    }
+}
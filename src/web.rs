@@ -0,0 +1,236 @@
+use std::path::Path;
+
+use crate::args::Source;
+use crate::plan::{self, JobPlan, JobSettings};
+use crate::probe::{self, MediaInfo};
+use crate::tempfiles;
+
+// “发到聊天/网页”一键方案：H.264 main + AAC 立体声 + faststart，
+// 长边不超过 1280、帧率不超过 30，按平台的大小上限两遍编码
+#[derive(Clone, Copy, PartialEq)]
+pub enum Platform {
+    WeChat,
+    WhatsApp,
+    Discord,
+    Email,
+}
+
+pub struct Limits {
+    pub max_mb: u32,
+    pub long_side: u32,
+    pub fps: f64,
+}
+
+const AUDIO_K: u32 = 128;
+// 大小上限很宽松时也没必要给更高码率
+const MAX_VIDEO_K: u32 = 4000;
+const MIN_VIDEO_K: u32 = 64;
+
+impl Platform {
+    pub const ALL: [Platform; 4] = [Platform::WeChat, Platform::WhatsApp, Platform::Discord, Platform::Email];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Platform::WeChat => "微信",
+            Platform::WhatsApp => "WhatsApp",
+            Platform::Discord => "Discord",
+            Platform::Email => "邮件附件",
+        }
+    }
+
+    // 输出文件名里的标记，也是 --web 的参数
+    pub fn tag(self) -> &'static str {
+        match self {
+            Platform::WeChat => "wechat",
+            Platform::WhatsApp => "whatsapp",
+            Platform::Discord => "discord",
+            Platform::Email => "email",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Platform> {
+        Platform::ALL.into_iter().find(|p| p.tag().eq_ignore_ascii_case(tag))
+    }
+
+    pub fn limits(self) -> Limits {
+        let max_mb = match self {
+            Platform::WeChat => 25,
+            Platform::WhatsApp => 16,
+            Platform::Discord => 10,
+            Platform::Email => 20, // 常见邮箱 25 MB，base64 编码后会变大
+        };
+        Limits { max_mb, long_side: 1280, fps: 30.0 }
+    }
+}
+
+// 留 5% 给容器开销
+pub fn video_bitrate_k(max_mb: u32, duration: f64, audio_k: u32) -> u32 {
+    if duration <= 0.0 {
+        return MAX_VIDEO_K;
+    }
+    let total_k = max_mb as f64 * 1024.0 * 1024.0 * 8.0 * 0.95 / duration / 1000.0;
+    ((total_k - audio_k as f64).max(0.0) as u32).clamp(MIN_VIDEO_K, MAX_VIDEO_K)
+}
+
+pub(crate) fn null_output() -> &'static str {
+    if cfg!(target_os = "windows") { "NUL" } else { "/dev/null" }
+}
+
+pub fn plan(settings: &JobSettings, platform: Platform, info: &MediaInfo, input: &str, output: &str, temp: &Path) -> JobPlan {
+    let limits = platform.limits();
+    let mut job = JobPlan::default();
+    let video = info.streams.iter().find(|s| s.codec_type == "video");
+    let has_audio = info.streams.iter().any(|s| s.codec_type == "audio");
+
+    let mut filters = plan::video_filters(settings);
+    let size = |k: &str| video.and_then(|v| v.props.get(k)).and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+    let (w, h) = settings.fit.canvas.unwrap_or((size("width"), size("height")));
+    if w.max(h) > limits.long_side {
+        filters.push(if w >= h { format!("scale={}:-2", limits.long_side) } else { format!("scale=-2:{}", limits.long_side) });
+    }
+    let rate = |k: &str| video.and_then(|v| v.props.get(k)).and_then(|r| probe::parse_rate(r));
+    let fps = rate("avg_frame_rate").or_else(|| rate("r_frame_rate"));
+    if fps.is_none_or(|fps| fps > limits.fps + 0.01) {
+        filters.push(format!("fps={}", limits.fps));
+    }
+    filters.push("format=yuv420p".to_string());
+
+    let bitrate = video_bitrate_k(limits.max_mb, info.duration, if has_audio { AUDIO_K } else { 0 });
+    job.notes.push(format!(
+        "{}: 上限 {} MB，视频码率 {} kbps，两遍编码",
+        platform.label(), limits.max_mb, bitrate
+    ));
+    if bitrate == MIN_VIDEO_K {
+        job.notes.push("视频太长，压到这个大小画质会很差，建议先剪短".to_string());
+    }
+
+    let passlog = tempfiles::join(temp, "pass");
+    let video_args = |pass: &str| {
+        let mut args = plan::input_args(settings, input);
+        args.push(Source::Web, &[
+            "-map", "0:v:0",
+            "-vf", &filters.join(","),
+            "-c:v", "libx264",
+            "-profile:v", "main",
+            "-level", "3.1",
+            "-preset", "medium",
+            "-b:v", &format!("{}k", bitrate),
+            "-maxrate", &format!("{}k", bitrate * 3 / 2),
+            "-bufsize", &format!("{}k", bitrate * 2),
+            "-pass", pass,
+            "-passlogfile", &passlog,
+        ]);
+        args
+    };
+
+    let mut first = video_args("1");
+    first.push(Source::Web, &["-an", "-f", "null", null_output()]);
+    job.runs.push(first);
+
+    let mut second = video_args("2");
+    if has_audio {
+        second.push(Source::Web, &["-map", "0:a:0", "-c:a", "aac", "-b:a", &format!("{}k", AUDIO_K), "-ac", "2"]);
+    }
+    if let Some(tc) = plan::output_timecode(settings, info) {
+        second.push(Source::Timecode, &["-timecode", &tc.to_string()]);
+    }
+    second.append(plan::output_tail_args(settings));
+    second.push(Source::Web, &["-movflags", "+faststart"]);
+    second.push(Source::Output, &[output]);
+    job.runs.push(second);
+
+    job.outputs.push(output.to_string());
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::probe::StreamInfo;
+
+    fn stream(kind: &str, props: &[(&str, &str)]) -> StreamInfo {
+        StreamInfo {
+            codec_type: kind.to_string(),
+            props: props.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn platform_limits() {
+        let table: Vec<(&str, u32)> = Platform::ALL.iter().map(|p| (p.tag(), p.limits().max_mb)).collect();
+        assert_eq!(table, [("wechat", 25), ("whatsapp", 16), ("discord", 10), ("email", 20)]);
+        for p in Platform::ALL {
+            assert_eq!((p.limits().long_side, p.limits().fps), (1280, 30.0));
+            assert!(Platform::from_tag(&p.tag().to_uppercase()) == Some(p));
+        }
+        assert!(Platform::from_tag("slack").is_none());
+    }
+
+    #[test]
+    fn bitrate_fits_the_size_limit() {
+        assert_eq!(video_bitrate_k(16, 60.0, 128), 1997);
+        // 太短时封顶，太长时保底，时长未知时按封顶算
+        assert_eq!(video_bitrate_k(25, 10.0, 128), MAX_VIDEO_K);
+        assert_eq!(video_bitrate_k(10, 3600.0, 128), MIN_VIDEO_K);
+        assert_eq!(video_bitrate_k(10, 0.0, 128), MAX_VIDEO_K);
+        // 16 MB 的一分钟视频，加上音频和容器开销不超过上限
+        let bytes = (1997.0 + 128.0) * 1000.0 / 8.0 * 60.0;
+        assert!(bytes < 16.0 * 1024.0 * 1024.0);
+    }
+
+    #[test]
+    fn plan_caps_size_and_rate_in_two_passes() {
+        let info = MediaInfo {
+            duration: 60.0,
+            streams: vec![
+                stream("video", &[("width", "1080"), ("height", "1920"), ("avg_frame_rate", "60/1")]),
+                stream("audio", &[]),
+            ],
+            ..Default::default()
+        };
+        let temp = std::env::temp_dir();
+        let job = plan(&JobSettings::default(), Platform::WhatsApp, &info, "in.mov", "out.mp4", &temp);
+        assert_eq!(job.runs.len(), 2);
+        let first = job.runs[0].argv().join(" ");
+        let second = job.runs[1].argv().join(" ");
+        assert!(first.contains("-vf scale=-2:1280,fps=30,format=yuv420p"), "{}", first);
+        assert!(first.contains("-b:v 1997k -maxrate 2995k -bufsize 3994k -pass 1"), "{}", first);
+        assert!(first.contains("-an -f null"), "{}", first);
+        assert!(second.contains("-pass 2") && second.contains("-c:a aac -b:a 128k -ac 2"), "{}", second);
+        assert!(second.ends_with("-movflags +faststart out.mp4"), "{}", second);
+        assert_eq!(job.outputs, ["out.mp4"]);
+    }
+
+    #[test]
+    fn small_silent_video_is_left_alone() {
+        let info = MediaInfo {
+            duration: 10.0,
+            streams: vec![stream("video", &[("width", "640"), ("height", "360"), ("avg_frame_rate", "30000/1001")])],
+            ..Default::default()
+        };
+        let job = plan(&JobSettings::default(), Platform::Discord, &info, "in.mp4", "out.mp4", &std::env::temp_dir());
+        let second = job.runs[1].argv().join(" ");
+        assert!(second.contains("-vf format=yuv420p "), "{}", second);
+        assert!(!second.contains("0:a:0"), "{}", second);
+    }
+
+    #[test]
+    fn recipes_are_builtin_presets() {
+        for p in Platform::ALL {
+            let name = format!("web-{}", p.tag());
+            assert!(presets::is_builtin(&name));
+            let preset = presets::builtin().into_iter().find(|b| b.name == name).unwrap();
+            let mut settings = JobSettings { format: "mkv".to_string(), ..Default::default() };
+            preset.apply(&mut settings).unwrap();
+            assert!(settings.web == Some(p));
+            assert_eq!(settings.format, "mp4");
+        }
+        assert!(!presets::is_builtin("web-slack") && !presets::is_builtin("wechat"));
+        // 保存的同名预设不会遮住内置的
+        let saved = presets::Preset::capture("web-wechat", &JobSettings::default());
+        let all = presets::all(&[saved]);
+        assert_eq!(all.len(), Platform::ALL.len());
+    }
+}
@@ -0,0 +1,358 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use winreg::enums::*;
+use winreg::RegKey;
+
+// 资源管理器右键菜单：注册、移除，以及读回注册表判断当前的状态。
+// 读写都经过 Registry，状态判断不直接碰注册表
+
+// 菜单项的键名、标题和命令行参数
+pub struct Verb {
+    pub key: &'static str,
+    pub title: &'static str,
+    pub flag: &'static str,
+}
+
+impl Verb {
+    pub fn command(&self, app_path: &str) -> String {
+        format!("\"{}\" {}\"%1\"", app_path, self.flag)
+    }
+}
+
+pub const VERBS: [Verb; 3] = [
+    Verb { key: r"*\\shell\\FFmpeg_Transcoder", title: "使用 FFmpeg 转换", flag: "" },
+    Verb { key: r"*\\shell\\FFmpeg_Inspect", title: "查看媒体信息", flag: "--inspect " },
+    Verb { key: r"*\\shell\\FFmpeg_Share", title: "转成可发送的视频", flag: "--share " },
+];
+
+// 注册的位置。写 HKCR 时没有管理员权限的普通用户会落到 HKCU\Software\Classes，
+// 管理员写到 HKLM\Software\Classes，对所有用户生效；读的时候分开看
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scope {
+    User,
+    Machine,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::User, Scope::Machine];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scope::User => "当前用户（HKCU）",
+            Scope::Machine => "所有用户（HKCR）",
+        }
+    }
+
+    fn root(self) -> &'static str {
+        match self {
+            Scope::User => r"HKCU\Software\Classes",
+            Scope::Machine => r"HKLM\Software\Classes",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Status {
+    Installed,
+    // 注册了，但指向的程序不是现在这个（移动、改名过），或者少了菜单项
+    Stale(String),
+    NotInstalled,
+}
+
+impl Status {
+    pub fn label(&self) -> String {
+        match self {
+            Status::Installed => "已添加".to_string(),
+            Status::Stale(reason) => format!("已添加但需要修复：{}", reason),
+            Status::NotInstalled => "未添加".to_string(),
+        }
+    }
+}
+
+pub trait Registry {
+    // 菜单项 command 子键的默认值，键不存在时 Ok(None)
+    fn command(&self, scope: Scope, key: &str) -> io::Result<Option<String>>;
+    fn set_verb(&self, scope: Scope, key: &str, title: &str, command: &str) -> io::Result<()>;
+    // 删除整个菜单项，键不存在时 Ok(false)
+    fn delete(&self, scope: Scope, key: &str) -> io::Result<bool>;
+}
+
+// 命令开头引号里的程序路径
+pub fn registered_exe(command: &str) -> Option<&str> {
+    let rest = command.trim_start().strip_prefix('"')?;
+    rest.split_once('"').map(|(path, _)| path)
+}
+
+// Windows 路径不区分大小写，斜杠方向也不要紧
+fn same_path(a: &str, b: &str) -> bool {
+    a.replace('/', "\\").eq_ignore_ascii_case(&b.replace('/', "\\"))
+}
+
+// commands 和 VERBS 一一对应
+pub fn classify(commands: &[Option<String>], app_path: &str) -> Status {
+    let present: Vec<(&Verb, &String)> = VERBS.iter().zip(commands).filter_map(|(v, c)| Some((v, c.as_ref()?))).collect();
+    if present.is_empty() {
+        return Status::NotInstalled;
+    }
+    for (verb, command) in &present {
+        match registered_exe(command) {
+            Some(exe) if same_path(exe, app_path) => {}
+            Some(exe) if !Path::new(exe).exists() => return Status::Stale(format!("{} 已不存在", exe)),
+            Some(exe) => return Status::Stale(format!("指向另一个程序 {}", exe)),
+            None => return Status::Stale(format!("“{}”的命令无法识别", verb.title)),
+        }
+    }
+    // 旧版本只注册了转换菜单
+    if let Some(missing) = VERBS.iter().zip(commands).find(|(_, c)| c.is_none()) {
+        return Status::Stale(format!("缺少“{}”", missing.0.title));
+    }
+    Status::Installed
+}
+
+pub fn query_status(registry: &impl Registry, app_path: &str) -> Vec<(Scope, io::Result<Status>)> {
+    Scope::ALL
+        .into_iter()
+        .map(|scope| {
+            let commands: io::Result<Vec<Option<String>>> = VERBS.iter().map(|v| registry.command(scope, v.key)).collect();
+            (scope, commands.map(|c| classify(&c, app_path)))
+        })
+        .collect()
+}
+
+// 向导、设置界面可能同时点添加和移除，注册表操作一个一个来
+static LOCK: Mutex<()> = Mutex::new(());
+
+// 把 scope 下的菜单项全部改成指向 app_path，修复时用
+pub fn repair(registry: &impl Registry, scope: Scope, app_path: &str) -> io::Result<()> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for verb in &VERBS {
+        registry.set_verb(scope, verb.key, verb.title, &verb.command(app_path))?;
+    }
+    Ok(())
+}
+
+// 两个位置都删，返回实际删掉的键。别处已经删掉的键不算错误
+pub fn remove(registry: &impl Registry) -> io::Result<Vec<String>> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut removed = Vec::new();
+    for scope in Scope::ALL {
+        for verb in &VERBS {
+            if registry.delete(scope, verb.key)? {
+                removed.push(format!(r"{}\{}", scope.root(), verb.key));
+            }
+        }
+    }
+    Ok(removed)
+}
+
+// 真正的注册表
+pub struct WinRegistry;
+
+impl WinRegistry {
+    fn classes(scope: Scope) -> io::Result<RegKey> {
+        match scope {
+            Scope::User => RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(r"Software\Classes", KEY_ALL_ACCESS),
+            Scope::Machine => RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(r"Software\Classes", KEY_READ),
+        }
+    }
+}
+
+fn not_found<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+    match result {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Registry for WinRegistry {
+    fn command(&self, scope: Scope, key: &str) -> io::Result<Option<String>> {
+        let Some(cmd) = not_found(WinRegistry::classes(scope).and_then(|c| c.open_subkey(format!(r"{}\command", key))))? else {
+            return Ok(None);
+        };
+        not_found(cmd.get_value(""))
+    }
+
+    fn set_verb(&self, scope: Scope, key: &str, title: &str, command: &str) -> io::Result<()> {
+        let classes = match scope {
+            Scope::User => WinRegistry::classes(scope)?,
+            // 写所有用户的位置要管理员权限
+            Scope::Machine => RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(r"Software\Classes", KEY_ALL_ACCESS)?,
+        };
+        add_verb(&classes, key, title, command)
+    }
+
+    fn delete(&self, scope: Scope, key: &str) -> io::Result<bool> {
+        let classes = match scope {
+            Scope::User => WinRegistry::classes(scope)?,
+            Scope::Machine => {
+                // 没装在所有用户的位置时不需要写权限
+                if not_found(WinRegistry::classes(scope)?.open_subkey(key))?.is_none() {
+                    return Ok(false);
+                }
+                RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(r"Software\Classes", KEY_ALL_ACCESS)?
+            }
+        };
+        Ok(not_found(classes.delete_subkey_all(key))?.is_some())
+    }
+}
+
+fn add_verb(hkcr: &RegKey, key: &str, title: &str, command: &str) -> io::Result<()> {
+    let (shell, _) = hkcr.create_subkey(key)?;
+    shell.set_value("", &title)?;
+    let (cmd, _) = shell.create_subkey("command")?;
+    cmd.set_value("", &command)?;
+    Ok(())
+}
+
+pub fn add_context_menu(app_path: &str) -> io::Result<()> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let hkcr = RegKey::predef(HKEY_CLASSES_ROOT);
+    for verb in &VERBS {
+        add_verb(&hkcr, verb.key, verb.title, &verb.command(app_path))?;
+    }
+    Ok(())
+}
+
+pub fn get_app_path() -> PathBuf {
+    std::env::current_exe().unwrap_or_else(|_| PathBuf::from("ffmpeg_gui.exe"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    const APP: &str = r"C:\Program Files\ffui\ffui.exe";
+
+    // 内存里的注册表：(位置, 键) -> command 的值。fail 为 true 时读写都报没有权限
+    #[derive(Default)]
+    struct FakeRegistry {
+        keys: RefCell<BTreeMap<(bool, String), String>>,
+        fail: bool,
+    }
+
+    fn user(scope: Scope) -> bool {
+        scope == Scope::User
+    }
+
+    impl FakeRegistry {
+        fn with(scope: Scope, verbs: &[&Verb], app_path: &str) -> FakeRegistry {
+            let registry = FakeRegistry::default();
+            for verb in verbs {
+                registry.keys.borrow_mut().insert((user(scope), verb.key.to_string()), verb.command(app_path));
+            }
+            registry
+        }
+
+        fn denied() -> io::Error {
+            io::Error::new(io::ErrorKind::PermissionDenied, "拒绝访问")
+        }
+    }
+
+    impl Registry for FakeRegistry {
+        fn command(&self, scope: Scope, key: &str) -> io::Result<Option<String>> {
+            if self.fail {
+                return Err(FakeRegistry::denied());
+            }
+            Ok(self.keys.borrow().get(&(user(scope), key.to_string())).cloned())
+        }
+
+        fn set_verb(&self, scope: Scope, key: &str, _title: &str, command: &str) -> io::Result<()> {
+            if self.fail {
+                return Err(FakeRegistry::denied());
+            }
+            self.keys.borrow_mut().insert((user(scope), key.to_string()), command.to_string());
+            Ok(())
+        }
+
+        fn delete(&self, scope: Scope, key: &str) -> io::Result<bool> {
+            if self.fail {
+                return Err(FakeRegistry::denied());
+            }
+            Ok(self.keys.borrow_mut().remove(&(user(scope), key.to_string())).is_some())
+        }
+    }
+
+    fn statuses(registry: &FakeRegistry, app_path: &str) -> Vec<Status> {
+        query_status(registry, app_path).into_iter().map(|(_, s)| s.unwrap()).collect()
+    }
+
+    #[test]
+    fn installed_for_the_current_user() {
+        let registry = FakeRegistry::with(Scope::User, &VERBS.iter().collect::<Vec<_>>(), APP);
+        assert!(statuses(&registry, APP) == [Status::Installed, Status::NotInstalled]);
+        // 大小写和斜杠方向不同也是同一个程序
+        assert!(statuses(&registry, "c:/program files/FFUI/ffui.exe")[0] == Status::Installed);
+    }
+
+    #[test]
+    fn nothing_registered() {
+        assert!(statuses(&FakeRegistry::default(), APP) == [Status::NotInstalled, Status::NotInstalled]);
+    }
+
+    #[test]
+    fn stale_path_after_moving_the_exe() {
+        let old = r"C:\Users\me\Downloads\ffui-old\ffui.exe";
+        let registry = FakeRegistry::with(Scope::Machine, &VERBS.iter().collect::<Vec<_>>(), old);
+        let status = statuses(&registry, APP);
+        assert!(status[0] == Status::NotInstalled);
+        assert!(status[1] == Status::Stale(format!("{} 已不存在", old)));
+    }
+
+    // 菜单指向另一个还存在的程序（别的转换工具用了同样的键名）
+    #[test]
+    fn foreign_handler() {
+        let other = std::env::current_exe().unwrap().to_string_lossy().into_owned();
+        let registry = FakeRegistry::with(Scope::User, &VERBS.iter().collect::<Vec<_>>(), &other);
+        assert!(statuses(&registry, APP)[0] == Status::Stale(format!("指向另一个程序 {}", other)));
+        registry.keys.borrow_mut().insert((true, VERBS[0].key.to_string()), "notepad.exe %1".to_string());
+        assert!(statuses(&registry, APP)[0] == Status::Stale(format!("“{}”的命令无法识别", VERBS[0].title)));
+    }
+
+    // 旧版本只注册了转换菜单
+    #[test]
+    fn missing_key() {
+        let registry = FakeRegistry::with(Scope::User, &[&VERBS[0]], APP);
+        assert!(statuses(&registry, APP)[0] == Status::Stale(format!("缺少“{}”", VERBS[1].title)));
+    }
+
+    #[test]
+    fn read_errors_are_reported_per_scope() {
+        let registry = FakeRegistry { fail: true, ..Default::default() };
+        let status = query_status(&registry, APP);
+        assert!(status.iter().all(|(_, s)| s.as_ref().err().map(|e| e.kind()) == Some(io::ErrorKind::PermissionDenied)));
+    }
+
+    #[test]
+    fn repair_points_every_verb_at_the_app() {
+        let registry = FakeRegistry::with(Scope::User, &[&VERBS[0]], r"D:\old\ffui.exe");
+        repair(&registry, Scope::User, APP).unwrap();
+        assert!(statuses(&registry, APP)[0] == Status::Installed);
+        assert_eq!(registry.command(Scope::User, VERBS[2].key).unwrap().unwrap(), format!("\"{}\" --share \"%1\"", APP));
+    }
+
+    #[test]
+    fn remove_lists_the_deleted_keys() {
+        let registry = FakeRegistry::with(Scope::User, &VERBS.iter().collect::<Vec<_>>(), APP);
+        registry.set_verb(Scope::Machine, VERBS[1].key, VERBS[1].title, &VERBS[1].command(APP)).unwrap();
+        let removed = remove(&registry).unwrap();
+        assert_eq!(removed.len(), 4);
+        assert_eq!(removed[0], format!(r"HKCU\Software\Classes\{}", VERBS[0].key));
+        assert_eq!(removed[3], format!(r"HKLM\Software\Classes\{}", VERBS[1].key));
+        // 已经删掉的不再列出
+        assert!(remove(&registry).unwrap().is_empty());
+        assert!(remove(&FakeRegistry { fail: true, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn registered_exe_from_the_command() {
+        assert_eq!(registered_exe(&VERBS[1].command(APP)), Some(APP));
+        assert_eq!(registered_exe("  \"C:\\a b\\c.exe\" \"%1\""), Some(r"C:\a b\c.exe"));
+        assert_eq!(registered_exe("c.exe %1"), None);
+        assert_eq!(registered_exe("\"unterminated"), None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{self, Write};
+
+// 最简单的 zip：只存储不压缩，文件名用 UTF-8。诊断包里都是几十 KB 的文本，
+// 为这一处不值得引入压缩库；任何解压工具都能打开
+
+const LOCAL: u32 = 0x0403_4b50;
+const CENTRAL: u32 = 0x0201_4b50;
+const END: u32 = 0x0605_4b50;
+// 解压需要的版本 2.0；通用标志第 11 位表示文件名是 UTF-8
+const VERSION: u16 = 20;
+const UTF8: u16 = 0x0800;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// unix 秒 -> DOS 的 (时间, 日期)，按 UTC；早于 1980 年的记成 1980-01-01
+fn dos_time(unix: u64) -> (u16, u16) {
+    let days = (unix / 86400) as i64;
+    let secs = unix % 86400;
+    // 公历日期，算法见 Howard Hinnant 的 civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    if year < 1980 {
+        return (0, 0x21);
+    }
+    let time = ((secs / 3600) << 11) | (((secs % 3600) / 60) << 5) | ((secs % 60) / 2);
+    let date = (((year - 1980) as u16) << 9) | ((month as u16) << 5) | day as u16;
+    (time as u16, date)
+}
+
+// 按顺序写出 (文件名, 内容)，modified 是所有文件的修改时间（unix 秒）
+pub fn write(out: &mut impl Write, files: &[(String, Vec<u8>)], modified: u64) -> io::Result<()> {
+    let (time, date) = dos_time(modified);
+    let mut central = Vec::new();
+    let mut offset = 0u32;
+    for (name, data) in files {
+        let name = name.as_bytes();
+        let (crc, size) = (crc32(data), data.len() as u32);
+        // 本地文件头和中央目录里相同的那一段：标志、方法、时间、日期、CRC、两个大小、文件名长度
+        let mut common = Vec::new();
+        common.extend(UTF8.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(time.to_le_bytes());
+        common.extend(date.to_le_bytes());
+        common.extend(crc.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+
+        let mut local = Vec::new();
+        local.extend(LOCAL.to_le_bytes());
+        local.extend(VERSION.to_le_bytes());
+        local.extend(&common);
+        local.extend(0u16.to_le_bytes());
+        local.extend(name);
+        out.write_all(&local)?;
+        out.write_all(data)?;
+
+        central.extend(CENTRAL.to_le_bytes());
+        central.extend(VERSION.to_le_bytes());
+        central.extend(VERSION.to_le_bytes());
+        central.extend(&common);
+        // 附加字段、注释长度，磁盘号，内部、外部属性
+        central.extend([0u8; 12]);
+        central.extend(offset.to_le_bytes());
+        central.extend(name);
+        offset += (local.len() + data.len()) as u32;
+    }
+    out.write_all(&central)?;
+    let count = (files.len() as u16).to_le_bytes();
+    let mut end = Vec::new();
+    end.extend(END.to_le_bytes());
+    end.extend([0u8; 4]);
+    end.extend(count);
+    end.extend(count);
+    end.extend((central.len() as u32).to_le_bytes());
+    end.extend(offset.to_le_bytes());
+    end.extend(0u16.to_le_bytes());
+    out.write_all(&end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        // CRC-32/ISO-HDLC 的标准校验值
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"a"), 0xE8B7_BE43);
+    }
+
+    #[test]
+    fn dos_time_of_known_dates() {
+        // 2024-02-29 13:45:58 UTC
+        let (time, date) = dos_time(1_709_214_358);
+        assert_eq!(time, (13 << 11) | (45 << 5) | 29);
+        assert_eq!(date, (44 << 9) | (2 << 5) | 29);
+        assert_eq!(dos_time(0), (0, 0x21));
+    }
+
+    #[test]
+    fn headers_carry_the_crc_and_offsets() {
+        let files = vec![("日志.txt".to_string(), b"123456789".to_vec()), ("b".to_string(), Vec::new())];
+        let mut out = Vec::new();
+        write(&mut out, &files, 1_709_214_358).unwrap();
+        let u16_at = |i: usize| u16::from_le_bytes([out[i], out[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(out[i..i + 4].try_into().unwrap());
+        assert_eq!(u32_at(0), LOCAL);
+        assert_eq!(u16_at(6), UTF8);
+        assert_eq!(u32_at(14), 0xCBF4_3926);
+        assert_eq!((u32_at(18), u32_at(22)), (9, 9));
+        let name_len = "日志.txt".len();
+        assert_eq!(u16_at(26) as usize, name_len);
+        assert_eq!(&out[30..30 + name_len], "日志.txt".as_bytes());
+        // 第二个文件紧跟在第一个的内容后面
+        let second = 30 + name_len + 9;
+        assert_eq!(u32_at(second), LOCAL);
+        assert_eq!(u32_at(second + 14), 0);
+        // 结尾记录：两个条目，中央目录的大小和位置
+        let end = out.len() - 22;
+        assert_eq!(u32_at(end), END);
+        assert_eq!((u16_at(end + 8), u16_at(end + 10)), (2, 2));
+        let (size, start) = (u32_at(end + 12) as usize, u32_at(end + 16) as usize);
+        assert_eq!(start, second + 30 + 1);
+        assert_eq!(start + size, end);
+        assert_eq!(u32_at(start), CENTRAL);
+        assert_eq!(u32_at(start + 16), 0xCBF4_3926);
+        // 中央目录里第二项指回第二个本地文件头
+        let next = start + 46 + name_len;
+        assert_eq!(u32_at(next), CENTRAL);
+        assert_eq!(u32_at(next + 42) as usize, second);
+    }
+}